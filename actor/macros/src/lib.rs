@@ -0,0 +1,271 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The procedural macros of the builtin actors, which are re-exported by `plum_actor`.
+
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Error, Expr, FnArg, GenericArgument, Generics, Ident, ImplItem,
+    ImplItemMethod, ItemImpl, PathArguments, ReturnType, Type,
+};
+
+/// Generate the method dispatch of an actor from the handlers of an impl block.
+///
+/// A handler is an associated function marked with `#[method(number)]`, where `number` is
+/// any expression convertible into `MethodNum`, e.g. `2` or `Method::Exec`. The first argument
+/// of a handler is the context of the call, e.g. the runtime, which must be the same for all
+/// the handlers, and the optional second argument is the params decoded from CBOR. A handler
+/// returns `Result<T, E>`, or an alias of it, where `T` is encoded into CBOR unless it's `()`,
+/// and `E` converts into `ActorError`.
+///
+/// The generated `invoke_method` decodes the params, calls the handler of the method number
+/// and encodes its return, `ActorError::UnhandledMethod` is returned for the other numbers.
+///
+/// ```ignore
+/// #[actor_methods]
+/// impl Actor {
+///     #[method(Method::Constructor)]
+///     fn constructor<RT: Runtime>(
+///         rt: &mut RT,
+///         params: ConstructorParams,
+///     ) -> Result<(), ActorError> {
+///         ...
+///     }
+///
+///     #[method(Method::Exec)]
+///     fn exec<RT: Runtime>(rt: &mut RT, params: ExecParams) -> Result<ExecReturn, ActorError> {
+///         ...
+///     }
+/// }
+///
+/// let ret = Actor::invoke_method(&mut rt, method, &params)?;
+/// ```
+#[proc_macro_attribute]
+pub fn actor_methods(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let err = Error::new(
+            TokenStream2::from(attr).span(),
+            "`actor_methods` takes no argument",
+        );
+        return err.to_compile_error().into();
+    }
+    let mut item = parse_macro_input!(item as ItemImpl);
+    match expand(&mut item) {
+        Ok(dispatch) => quote!(#item #dispatch).into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+// A method handler of the impl block.
+struct Handler {
+    number: Expr,
+    ident: Ident,
+    has_params: bool,
+    returns_unit: bool,
+}
+
+// The context argument shared by the handlers, along with the generics of the first handler.
+struct Context {
+    ty: Type,
+    generics: Generics,
+}
+
+fn expand(item: &mut ItemImpl) -> syn::Result<TokenStream2> {
+    let mut handlers = Vec::<Handler>::new();
+    let mut context = None;
+    for impl_item in &mut item.items {
+        let method = match impl_item {
+            ImplItem::Method(method) => method,
+            _ => continue,
+        };
+        let number = match take_method_attr(&mut method.attrs)? {
+            Some(number) => number,
+            None => continue,
+        };
+        let handler = parse_handler(method, number, &mut context)?;
+        let number = handler.number.to_token_stream().to_string();
+        if handlers
+            .iter()
+            .any(|other| other.number.to_token_stream().to_string() == number)
+        {
+            return Err(Error::new(
+                handler.number.span(),
+                format!("duplicate method {}", number),
+            ));
+        }
+        handlers.push(handler);
+    }
+    let context = match context {
+        Some(context) => context,
+        None => {
+            return Err(Error::new(
+                item.self_ty.span(),
+                "no handler is marked with `#[method(..)]`",
+            ))
+        }
+    };
+
+    let arms = handlers.iter().map(|handler| {
+        let Handler {
+            number,
+            ident,
+            has_params,
+            returns_unit,
+        } = handler;
+        let params = if *has_params {
+            quote!(, ::plum_actor::dispatch::decode_method_params(method, params)?)
+        } else {
+            quote!()
+        };
+        let call = quote!(Self::#ident(ctx #params)?);
+        let body = if *returns_unit {
+            quote! {
+                #call;
+                return Ok(Vec::new());
+            }
+        } else {
+            quote! {
+                let ret = #call;
+                return Ok(::plum_actor::dispatch::encode_return(&ret));
+            }
+        };
+        // The integer literals are compared directly, as `From` can't infer their type.
+        let number = match number {
+            Expr::Lit(lit) => quote!(#lit),
+            number => quote!(::plum_actor::dispatch::MethodNum::from(#number)),
+        };
+        quote! {
+            if method == #number {
+                #body
+            }
+        }
+    });
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, ty_generics, where_clause) = item.generics.split_for_impl();
+    let ctx_ty = &context.ty;
+    let (method_generics, _, method_where_clause) = context.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #self_ty #ty_generics #where_clause {
+            /// Call the handler of the `method` with the CBOR encoded `params`, return the CBOR
+            /// encoded return value of the handler.
+            #[allow(unused_variables)]
+            pub fn invoke_method #method_generics (
+                ctx: #ctx_ty,
+                method: ::plum_actor::dispatch::MethodNum,
+                params: &[u8],
+            ) -> ::std::result::Result<Vec<u8>, ::plum_actor::dispatch::ActorError>
+            #method_where_clause
+            {
+                #(#arms)*
+                Err(::plum_actor::dispatch::ActorError::UnhandledMethod(method))
+            }
+        }
+    })
+}
+
+// Remove the `#[method(number)]` attribute of the handler and return the number.
+fn take_method_attr(attrs: &mut Vec<Attribute>) -> syn::Result<Option<Expr>> {
+    let pos = match attrs.iter().position(|attr| attr.path.is_ident("method")) {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let attr = attrs.remove(pos);
+    if attrs.iter().any(|attr| attr.path.is_ident("method")) {
+        return Err(Error::new(attr.span(), "duplicate `#[method(..)]`"));
+    }
+    attr.parse_args().map(Some)
+}
+
+fn parse_handler(
+    method: &ImplItemMethod,
+    number: Expr,
+    context: &mut Option<Context>,
+) -> syn::Result<Handler> {
+    let sig = &method.sig;
+    let mut inputs = sig.inputs.iter();
+    let ctx_ty = match inputs.next() {
+        Some(FnArg::Typed(arg)) => (*arg.ty).clone(),
+        Some(FnArg::Receiver(receiver)) => {
+            return Err(Error::new(
+                receiver.span(),
+                "the handler must be an associated function without `self`",
+            ))
+        }
+        None => {
+            return Err(Error::new(
+                sig.span(),
+                "the handler must take the context as the first argument",
+            ))
+        }
+    };
+    match context {
+        Some(context) => {
+            if context.ty.to_token_stream().to_string() != ctx_ty.to_token_stream().to_string()
+                || context.generics.to_token_stream().to_string()
+                    != sig.generics.to_token_stream().to_string()
+            {
+                return Err(Error::new(
+                    ctx_ty.span(),
+                    "the context and the generics of all the handlers must be the same",
+                ));
+            }
+        }
+        None => {
+            *context = Some(Context {
+                ty: ctx_ty,
+                generics: sig.generics.clone(),
+            })
+        }
+    }
+    let has_params = inputs.next().is_some();
+    if let Some(arg) = inputs.next() {
+        return Err(Error::new(
+            arg.span(),
+            "the handler takes at most one params argument after the context",
+        ));
+    }
+
+    Ok(Handler {
+        number,
+        ident: sig.ident.clone(),
+        has_params,
+        returns_unit: returns_unit(&sig.output)?,
+    })
+}
+
+// Return true if the `Ok` type of the `Result` returned by the handler is `()`.
+fn returns_unit(output: &ReturnType) -> syn::Result<bool> {
+    let ty = match output {
+        ReturnType::Type(_, ty) => ty,
+        ReturnType::Default => {
+            return Err(Error::new(
+                output.span(),
+                "the handler must return a `Result`",
+            ))
+        }
+    };
+    let ok = match &**ty {
+        Type::Path(path) => {
+            path.path
+                .segments
+                .last()
+                .and_then(|segment| match &segment.arguments {
+                    PathArguments::AngleBracketed(args) => args.args.first(),
+                    _ => None,
+                })
+        }
+        _ => None,
+    };
+    match ok {
+        Some(GenericArgument::Type(Type::Tuple(tuple))) => Ok(tuple.elems.is_empty()),
+        Some(GenericArgument::Type(_)) => Ok(false),
+        _ => Err(Error::new(ty.span(), "the handler must return a `Result`")),
+    }
+}
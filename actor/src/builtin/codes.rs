@@ -0,0 +1,74 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use cid::{Cid, Codec};
+use lazy_static::lazy_static;
+
+fn make_builtin(name: &str) -> Cid {
+    Cid::new_v1(Codec::Raw, multihash::Identity::digest(name.as_bytes()))
+}
+
+lazy_static! {
+    /// The code CID of the system actor.
+    pub static ref SYSTEM_ACTOR_CODE_ID: Cid = make_builtin("fil/1/system");
+    /// The code CID of the init actor.
+    pub static ref INIT_ACTOR_CODE_ID: Cid = make_builtin("fil/1/init");
+    /// The code CID of the cron actor.
+    pub static ref CRON_ACTOR_CODE_ID: Cid = make_builtin("fil/1/cron");
+    /// The code CID of the storage power actor.
+    pub static ref STORAGE_POWER_ACTOR_CODE_ID: Cid = make_builtin("fil/1/storagepower");
+    /// The code CID of the storage miner actor.
+    pub static ref STORAGE_MINER_ACTOR_CODE_ID: Cid = make_builtin("fil/1/storageminer");
+    /// The code CID of the storage market actor.
+    pub static ref STORAGE_MARKET_ACTOR_CODE_ID: Cid = make_builtin("fil/1/storagemarket");
+    /// The code CID of the payment channel actor.
+    pub static ref PAYMENT_CHANNEL_ACTOR_CODE_ID: Cid = make_builtin("fil/1/paymentchannel");
+    /// The code CID of the multisig actor.
+    pub static ref MULTISIG_ACTOR_CODE_ID: Cid = make_builtin("fil/1/multisig");
+    /// The code CID of the reward actor.
+    pub static ref REWARD_ACTOR_CODE_ID: Cid = make_builtin("fil/1/reward");
+    /// The code CID of the verified registry actor.
+    pub static ref VERIFIED_REGISTRY_ACTOR_CODE_ID: Cid = make_builtin("fil/1/verifiedregistry");
+    /// The code CID of the account actor.
+    pub static ref ACCOUNT_ACTOR_CODE_ID: Cid = make_builtin("fil/1/account");
+
+    static ref BUILTIN_ACTORS: [(&'static str, &'static Cid); 11] = [
+        ("system", &SYSTEM_ACTOR_CODE_ID),
+        ("init", &INIT_ACTOR_CODE_ID),
+        ("cron", &CRON_ACTOR_CODE_ID),
+        ("storagepower", &STORAGE_POWER_ACTOR_CODE_ID),
+        ("storageminer", &STORAGE_MINER_ACTOR_CODE_ID),
+        ("storagemarket", &STORAGE_MARKET_ACTOR_CODE_ID),
+        ("paymentchannel", &PAYMENT_CHANNEL_ACTOR_CODE_ID),
+        ("multisig", &MULTISIG_ACTOR_CODE_ID),
+        ("reward", &REWARD_ACTOR_CODE_ID),
+        ("verifiedregistry", &VERIFIED_REGISTRY_ACTOR_CODE_ID),
+        ("account", &ACCOUNT_ACTOR_CODE_ID),
+    ];
+}
+
+/// Return the name of the builtin actor with the code CID, e.g. `storageminer`.
+pub fn actor_name(code: &Cid) -> Option<&'static str> {
+    BUILTIN_ACTORS
+        .iter()
+        .find(|(_, id)| *id == code)
+        .map(|(name, _)| *name)
+}
+
+/// Return the code CID of the builtin actor with the name, e.g. `storageminer`.
+pub fn actor_code(name: &str) -> Option<&'static Cid> {
+    BUILTIN_ACTORS
+        .iter()
+        .find(|(actor, _)| *actor == name)
+        .map(|(_, code)| *code)
+}
+
+/// Return true if the code CID is of a builtin actor.
+pub fn is_builtin_actor(code: &Cid) -> bool {
+    actor_name(code).is_some()
+}
+
+/// Return true if the code CID is of an account actor, or a multisig actor,
+/// which can be the sender of a message.
+pub fn is_signable_actor(code: &Cid) -> bool {
+    code == &*ACCOUNT_ACTOR_CODE_ID || code == &*MULTISIG_ACTOR_CODE_ID
+}
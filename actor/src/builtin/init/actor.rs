@@ -1 +1,96 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use cid::Cid;
+use plum_address::Address;
+use plum_types::MethodNum;
+
+/// The ID of the singleton init actor.
+pub const INIT_ACTOR_ID: u64 = 1;
+
+/// The methods of the init actor.
+#[allow(missing_docs)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    Constructor = 1,
+    Exec = 2,
+}
+
+impl From<Method> for MethodNum {
+    fn from(method: Method) -> Self {
+        method as MethodNum
+    }
+}
+
+/// The params of the `Exec` method, which creates a new actor of `code_cid` and calls its
+/// constructor with `constructor_params`.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExecParams {
+    #[serde(rename = "CodeCID")]
+    pub code_cid: Cid,
+    #[serde(with = "plum_bytes::base64")]
+    pub constructor_params: Vec<u8>,
+}
+
+// Implement CBOR serialization for ExecParams.
+impl encode::Encode for ExecParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(&self.code_cid)?
+            .bytes(&self.constructor_params)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ExecParams.
+impl<'b> decode::Decode<'b> for ExecParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of ExecParams"));
+        }
+        Ok(ExecParams {
+            code_cid: d.decode::<Cid>()?,
+            constructor_params: d.bytes()?.to_vec(),
+        })
+    }
+}
+
+/// The return of the `Exec` method.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExecReturn {
+    /// The canonical ID-based address of the new actor.
+    pub id_address: Address,
+    /// The re-org safe address of the new actor.
+    pub robust_address: Address,
+}
+
+// Implement CBOR serialization for ExecReturn.
+impl encode::Encode for ExecReturn {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(&self.id_address)?
+            .encode(&self.robust_address)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ExecReturn.
+impl<'b> decode::Decode<'b> for ExecReturn {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        // The return is read from the receipts on chain, don't panic on the malformed one.
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of ExecReturn"));
+        }
+        Ok(ExecReturn {
+            id_address: d.decode::<Address>()?,
+            robust_address: d.decode::<Address>()?,
+        })
+    }
+}
@@ -1,12 +1,36 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
 use cid::Cid;
 use plum_address::Address;
-use plum_bigint::bigint_json;
+use plum_bigint::{bigint_json, BigIntRefWrapper, BigIntWrapper};
+use plum_crypto::Signature;
 use plum_piece::PaddedPieceSize;
-use plum_types::{ChainEpoch, TokenAmount};
+use plum_types::{ChainEpoch, DealId, MethodNum, TokenAmount};
+
+/// The methods of the storage market actor.
+#[allow(missing_docs)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    Constructor = 1,
+    AddBalance = 2,
+    WithdrawBalance = 3,
+    PublishStorageDeals = 4,
+    VerifyDealsForActivation = 5,
+    ActivateDeals = 6,
+    OnMinerSectorsTerminate = 7,
+    ComputeDataCommitment = 8,
+    CronTick = 9,
+}
+
+impl From<Method> for MethodNum {
+    fn from(method: Method) -> Self {
+        method as MethodNum
+    }
+}
 
 // Note: Deal Collateral is only released and returned to clients and miners
 // when the storage deal stops counting towards power. In the current iteration,
@@ -43,6 +67,158 @@ pub struct DealProposal {
     pub client_collateral: TokenAmount,
 }
 
+impl DealProposal {
+    /// The duration of the deal in epochs, `None` is returned if it overflows.
+    pub fn duration(&self) -> Option<ChainEpoch> {
+        self.end_epoch.checked_sub(self.start_epoch)
+    }
+
+    /// The total storage fee paid by the client over the duration of the deal,
+    /// `None` is returned if the duration overflows.
+    pub fn total_storage_fee(&self) -> Option<TokenAmount> {
+        self.duration()
+            .map(|duration| &self.storage_price_per_epoch * duration)
+    }
+
+    /// The total funds that the client should lock for the deal,
+    /// `None` is returned if the duration overflows.
+    pub fn client_balance_requirement(&self) -> Option<TokenAmount> {
+        self.total_storage_fee()
+            .map(|fee| &self.client_collateral + fee)
+    }
+
+    /// The bytes to be signed by the client, which is the CBOR of the proposal.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        minicbor::to_vec(self).expect("CBOR serialization of DealProposal shouldn't fail; qed")
+    }
+}
+
+impl minicbor::Encode for DealProposal {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(10)?
+            .encode(&self.piece_cid)?
+            .u64(self.piece_size.0)?
+            .bool(self.verified_deal)?
+            .encode(&self.client)?
+            .encode(&self.provider)?
+            .i64(self.start_epoch)?
+            .i64(self.end_epoch)?
+            .encode(BigIntRefWrapper::from(&self.storage_price_per_epoch))?
+            .encode(BigIntRefWrapper::from(&self.provider_collateral))?
+            .encode(BigIntRefWrapper::from(&self.client_collateral))?
+            .ok()
+    }
+}
+
+impl<'b> minicbor::Decode<'b> for DealProposal {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(10) {
+            return Err(decode::Error::Message("expected 10 fields of DealProposal"));
+        }
+        Ok(DealProposal {
+            piece_cid: d.decode::<Cid>()?,
+            piece_size: PaddedPieceSize(d.u64()?),
+            verified_deal: d.bool()?,
+            client: d.decode::<Address>()?,
+            provider: d.decode::<Address>()?,
+            start_epoch: d.i64()?,
+            end_epoch: d.i64()?,
+            storage_price_per_epoch: d.decode::<BigIntWrapper>()?.into_inner(),
+            provider_collateral: d.decode::<BigIntWrapper>()?.into_inner(),
+            client_collateral: d.decode::<BigIntWrapper>()?.into_inner(),
+        })
+    }
+}
+
+/// The deal proposal signed by the client.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ClientDealProposal {
+    pub proposal: DealProposal,
+    pub client_signature: Signature,
+}
+
+impl minicbor::Encode for ClientDealProposal {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(&self.proposal)?
+            .encode(&self.client_signature)?
+            .ok()
+    }
+}
+
+impl<'b> minicbor::Decode<'b> for ClientDealProposal {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of ClientDealProposal",
+            ));
+        }
+        Ok(ClientDealProposal {
+            proposal: d.decode::<DealProposal>()?,
+            client_signature: d.decode::<Signature>()?,
+        })
+    }
+}
+
+/// The params of the `PublishStorageDeals` method.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublishStorageDealsParams {
+    pub deals: Vec<ClientDealProposal>,
+}
+
+impl minicbor::Encode for PublishStorageDealsParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.encode(&self.deals)?.ok()
+    }
+}
+
+impl<'b> minicbor::Decode<'b> for PublishStorageDealsParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message(
+                "expected 1 field of PublishStorageDealsParams",
+            ));
+        }
+        Ok(PublishStorageDealsParams {
+            deals: d.decode::<Vec<ClientDealProposal>>()?,
+        })
+    }
+}
+
+/// The return of the `PublishStorageDeals` method.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PublishStorageDealsReturn {
+    /// The IDs of the published deals, in the order of the proposals.
+    #[serde(rename = "IDs")]
+    pub ids: Vec<DealId>,
+}
+
+impl minicbor::Encode for PublishStorageDealsReturn {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.encode(&self.ids)?.ok()
+    }
+}
+
+impl<'b> minicbor::Decode<'b> for PublishStorageDealsReturn {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        // The return is read from the receipts on chain, don't panic on the malformed one.
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message(
+                "expected 1 field of PublishStorageDealsReturn",
+            ));
+        }
+        Ok(PublishStorageDealsReturn {
+            ids: d.decode::<Vec<DealId>>()?,
+        })
+    }
+}
+
 ///
 #[doc(hidden)]
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -1,13 +1,53 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
 
 use plum_sector::SectorNumber;
+use plum_types::MethodNum;
+
+/// The methods of the storage miner actor.
+#[allow(missing_docs)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    Constructor = 1,
+    ControlAddresses = 2,
+    ChangeWorkerAddress = 3,
+    ChangePeerID = 4,
+    SubmitWindowedPoSt = 5,
+    PreCommitSector = 6,
+    ProveCommitSector = 7,
+    ExtendSectorExpiration = 8,
+    TerminateSectors = 9,
+    DeclareFaults = 10,
+    DeclareFaultsRecovered = 11,
+    OnDeferredCronEvent = 12,
+    CheckSectorProven = 13,
+    AddLockedFund = 14,
+    ReportConsensusFault = 15,
+    WithdrawBalance = 16,
+    ConfirmSectorProofsValid = 17,
+    ChangeMultiaddrs = 18,
+    CompactPartitions = 19,
+    CompactSectorNumbers = 20,
+    ConfirmUpdateWorkerKey = 21,
+    RepayDebt = 22,
+    ChangeOwnerAddress = 23,
+}
+
+impl From<Method> for MethodNum {
+    fn from(method: Method) -> Self {
+        method as MethodNum
+    }
+}
 
 #[doc(hidden)]
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct ProveCommitSectorParams {
     pub sector_number: SectorNumber,
+    #[serde(with = "plum_bytes::base64")]
     pub proof: Vec<u8>,
 }
 
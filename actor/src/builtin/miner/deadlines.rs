@@ -4,6 +4,11 @@ use serde::{Deserialize, Serialize};
 
 use plum_types::ChainEpoch;
 
+use super::policy::{
+    FAULT_DECLARATION_CUTOFF, W_POST_CHALLENGE_LOOKBACK, W_POST_CHALLENGE_WINDOW,
+    W_POST_PERIOD_DEADLINES, W_POST_PROVING_PERIOD,
+};
+
 /// Deadline calculations with respect to a current epoch.
 /// "Deadline" refers to the window during which proofs may be submitted.
 /// Windows are non-overlapping ranges [open, close), but the challenge epoch for a window occurs
@@ -21,3 +26,108 @@ pub struct DeadlineInfo {
     pub challenge: ChainEpoch, // Epoch at which to sample the chain for challenge (< open).
     pub fault_cutoff: ChainEpoch, // First epoch at which a fault declaration is rejected (< open).
 }
+
+impl DeadlineInfo {
+    /// Return the deadline info of the deadline `index` in the proving period starting at `period_start`.
+    ///
+    /// If `index` is not less than `W_POST_PERIOD_DEADLINES`, the info of a no-duty deadline
+    /// immediately after the proving period is returned.
+    pub fn new(period_start: ChainEpoch, index: u64, current_epoch: ChainEpoch) -> Self {
+        if index < W_POST_PERIOD_DEADLINES {
+            let open = period_start + index as ChainEpoch * W_POST_CHALLENGE_WINDOW as ChainEpoch;
+            Self {
+                current_epoch,
+                period_start,
+                index,
+                open,
+                close: open + W_POST_CHALLENGE_WINDOW as ChainEpoch,
+                challenge: open - W_POST_CHALLENGE_LOOKBACK,
+                fault_cutoff: open - FAULT_DECLARATION_CUTOFF,
+            }
+        } else {
+            let after_last_deadline = period_start + W_POST_PROVING_PERIOD as ChainEpoch;
+            Self {
+                current_epoch,
+                period_start,
+                index,
+                open: after_last_deadline,
+                close: after_last_deadline,
+                challenge: after_last_deadline,
+                fault_cutoff: 0,
+            }
+        }
+    }
+
+    /// Whether the proving period has begun.
+    pub fn period_started(&self) -> bool {
+        self.current_epoch >= self.period_start
+    }
+
+    /// Whether the proving period has elapsed.
+    pub fn period_elapsed(&self) -> bool {
+        self.current_epoch >= self.next_period_start()
+    }
+
+    /// The last epoch in the proving period.
+    pub fn period_end(&self) -> ChainEpoch {
+        self.period_start + W_POST_PROVING_PERIOD as ChainEpoch - 1
+    }
+
+    /// The first epoch in the next proving period.
+    pub fn next_period_start(&self) -> ChainEpoch {
+        self.period_start + W_POST_PROVING_PERIOD as ChainEpoch
+    }
+
+    /// Whether the deadline is currently open.
+    pub fn is_open(&self) -> bool {
+        self.current_epoch >= self.open && self.current_epoch < self.close
+    }
+
+    /// Whether the deadline has already closed.
+    pub fn has_elapsed(&self) -> bool {
+        self.current_epoch >= self.close
+    }
+
+    /// Whether the fault cutoff of the deadline has passed.
+    pub fn fault_cutoff_passed(&self) -> bool {
+        self.current_epoch >= self.fault_cutoff
+    }
+
+    /// Return the same deadline in the first proving period whose deadline has not elapsed yet,
+    /// which is `self` if the deadline hasn't elapsed.
+    pub fn next_not_elapsed(self) -> Self {
+        if !self.has_elapsed() {
+            return self;
+        }
+        let proving_period = W_POST_PROVING_PERIOD as ChainEpoch;
+        let periods = (self.current_epoch - self.close) / proving_period + 1;
+        Self::new(
+            self.period_start + periods * proving_period,
+            self.index,
+            self.current_epoch,
+        )
+    }
+}
+
+/// Return the deadline info of the deadline containing `current_epoch`
+/// in the proving period starting at `period_start`.
+///
+/// If the proving period hasn't started, the first deadline is returned;
+/// if the proving period has elapsed, the no-duty deadline after the period is returned.
+pub fn compute_proving_period_deadline(
+    period_start: ChainEpoch,
+    current_epoch: ChainEpoch,
+) -> DeadlineInfo {
+    let period_progress = current_epoch - period_start;
+    if period_progress >= W_POST_PROVING_PERIOD as ChainEpoch {
+        // Proving period has completely elapsed.
+        return DeadlineInfo::new(period_start, W_POST_PERIOD_DEADLINES, current_epoch);
+    }
+    let index = if period_progress < 0 {
+        // Period not yet started.
+        0
+    } else {
+        (period_progress / W_POST_CHALLENGE_WINDOW as ChainEpoch) as u64
+    };
+    DeadlineInfo::new(period_start, index, current_epoch)
+}
@@ -3,11 +3,15 @@
 mod actor;
 mod deadlines;
 mod policy;
+mod post;
 mod state;
 #[cfg(test)]
 mod test;
+mod worker;
 
 pub use self::actor::*;
 pub use self::deadlines::*;
 pub use self::policy::*;
+pub use self::post::*;
 pub use self::state::*;
+pub use self::worker::*;
@@ -9,9 +9,19 @@ pub const W_POST_PROVING_PERIOD: u64 = EPOCH_IN_DAY; // 24 hours
 pub const W_POST_CHALLENGE_WINDOW: u64 = 3600 / EPOCH_DURATION_SECONDS; // An hour (=24 per day)
 /// The number of non-overlapping PoSt deadlines in each proving period.
 pub const W_POST_PERIOD_DEADLINES: u64 = W_POST_PROVING_PERIOD / W_POST_CHALLENGE_WINDOW;
+/// The lookback from the deadline's challenge window opening from which to sample chain randomness for the challenge seed.
+pub const W_POST_CHALLENGE_LOOKBACK: ChainEpoch = 20;
+/// Minimum period before a deadline's challenge window opens that a fault must be declared for that deadline.
+pub const FAULT_DECLARATION_CUTOFF: ChainEpoch = W_POST_CHALLENGE_LOOKBACK + 50;
 
 /// The maximum number of new sectors that may be staged by a miner during a single proving period.
 pub const NEW_SECTORS_PER_PERIOD_MAX: u64 = 128 << 10;
 
 /// An approximation to chain state finality (should include message propagation time as well).
 pub const CHAIN_FINALITYISH: ChainEpoch = 500; // PARAM_FINISH
+
+/// The maximum number of partitions that may be required to be loaded in a single invocation.
+/// This limits the number of simultaneous fault, recovery, or sector-extension declarations.
+pub const ADDRESSED_PARTITIONS_MAX: u64 = 200;
+/// The maximum number of sector infos that may be required to be loaded in a single invocation.
+pub const ADDRESSED_SECTORS_MAX: u64 = 10_000;
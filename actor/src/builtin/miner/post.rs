@@ -0,0 +1,313 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_bitfield::BitField;
+use plum_sector::{PoStProof, RegisteredPoStProof};
+use plum_types::{ChainEpoch, Randomness};
+
+use super::policy::{ADDRESSED_PARTITIONS_MAX, ADDRESSED_SECTORS_MAX, W_POST_PERIOD_DEADLINES};
+
+/// The errors of validating a window PoSt submission.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PoStBatchError {
+    /// The submission has no proof.
+    #[error("window PoSt submission has no proof")]
+    NoProof,
+    /// The proofs of the submission are of different types.
+    #[error("mismatched window PoSt proof type, expected: {expected:?}, got: {actual:?}")]
+    ProofTypeMismatch {
+        /// The type of the first proof.
+        expected: RegisteredPoStProof,
+        /// The type of the mismatched proof.
+        actual: RegisteredPoStProof,
+    },
+    /// The deadline index is out of range.
+    #[error("invalid deadline {0}, must be less than {}", W_POST_PERIOD_DEADLINES)]
+    InvalidDeadline(u64),
+    /// The submission has no partition.
+    #[error("window PoSt submission has no partition")]
+    NoPartition,
+    /// The submission addresses more partitions than allowed for the proof type.
+    #[error("too many partitions {count}, max: {max}")]
+    TooManyPartitions {
+        /// The number of the partitions.
+        count: usize,
+        /// The max number of the partitions.
+        max: usize,
+    },
+    /// The submission skips more sectors than allowed.
+    #[error("too many skipped sectors {count}, max: {max}")]
+    TooManySectors {
+        /// The number of the skipped sectors.
+        count: u64,
+        /// The max number of the skipped sectors.
+        max: u64,
+    },
+}
+
+/// A partition proven by a window PoSt, with the sectors skipped by the proof.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PoStPartition {
+    pub index: u64,
+    pub skipped: BitField,
+}
+
+// Implement CBOR serialization for PoStPartition.
+impl encode::Encode for PoStPartition {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?.u64(self.index)?.encode(&self.skipped)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for PoStPartition.
+impl<'b> decode::Decode<'b> for PoStPartition {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of PoStPartition"));
+        }
+        Ok(PoStPartition {
+            index: d.u64()?,
+            skipped: d.decode::<BitField>()?,
+        })
+    }
+}
+
+/// The params of the SubmitWindowedPoSt method of the miner actor.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SubmitWindowedPoStParams {
+    pub deadline: u64,
+    pub partitions: Vec<PoStPartition>,
+    pub proofs: Vec<PoStProof>,
+    pub chain_commit_epoch: ChainEpoch,
+    pub chain_commit_rand: Randomness,
+}
+
+// Implement CBOR serialization for SubmitWindowedPoStParams.
+impl encode::Encode for SubmitWindowedPoStParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(5)?
+            .u64(self.deadline)?
+            .encode(&self.partitions)?
+            .encode(&self.proofs)?
+            .i64(self.chain_commit_epoch)?
+            .encode(&self.chain_commit_rand)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for SubmitWindowedPoStParams.
+impl<'b> decode::Decode<'b> for SubmitWindowedPoStParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(5) {
+            return Err(decode::Error::Message(
+                "expected 5 fields of SubmitWindowedPoStParams",
+            ));
+        }
+        Ok(SubmitWindowedPoStParams {
+            deadline: d.u64()?,
+            partitions: d.decode::<Vec<PoStPartition>>()?,
+            proofs: d.decode::<Vec<PoStProof>>()?,
+            chain_commit_epoch: d.i64()?,
+            chain_commit_rand: d.decode::<Randomness>()?,
+        })
+    }
+}
+
+impl SubmitWindowedPoStParams {
+    /// Validate the shape of the submission, i.e. the deadline, the proof types and the number
+    /// of the partitions and the skipped sectors. The proofs themselves are not verified.
+    pub fn validate(&self) -> Result<(), PoStBatchError> {
+        if self.deadline >= W_POST_PERIOD_DEADLINES {
+            return Err(PoStBatchError::InvalidDeadline(self.deadline));
+        }
+        let expected = match self.proofs.first() {
+            Some(proof) => proof.post_proof,
+            None => return Err(PoStBatchError::NoProof),
+        };
+        if let Some(proof) = self.proofs.iter().find(|p| p.post_proof != expected) {
+            return Err(PoStBatchError::ProofTypeMismatch {
+                expected,
+                actual: proof.post_proof,
+            });
+        }
+
+        let max = post_partitions_max(expected);
+        match self.partitions.len() {
+            0 => return Err(PoStBatchError::NoPartition),
+            count if count > max => return Err(PoStBatchError::TooManyPartitions { count, max }),
+            _ => {}
+        }
+        let skipped = self
+            .partitions
+            .iter()
+            .map(|partition| partition.skipped.len() as u64)
+            .sum::<u64>();
+        if skipped > ADDRESSED_SECTORS_MAX {
+            return Err(PoStBatchError::TooManySectors {
+                count: skipped,
+                max: ADDRESSED_SECTORS_MAX,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Return the max number of the partitions that can be proven in a single SubmitWindowedPoSt
+/// message with the proof type, so that the message never loads more than
+/// `ADDRESSED_SECTORS_MAX` sectors or `ADDRESSED_PARTITIONS_MAX` partitions.
+pub fn post_partitions_max(proof: RegisteredPoStProof) -> usize {
+    let by_sectors = ADDRESSED_SECTORS_MAX / proof.window_post_partition_sectors();
+    by_sectors.min(ADDRESSED_PARTITIONS_MAX).max(1) as usize
+}
+
+/// Split the partitions of a deadline into batches, each of which fits in a single
+/// SubmitWindowedPoSt message with the proof type. The order of the partitions is kept.
+pub fn batch_partitions<T>(partitions: Vec<T>, proof: RegisteredPoStProof) -> Vec<Vec<T>> {
+    let max = post_partitions_max(proof);
+    let mut batches = Vec::with_capacity((partitions.len() + max - 1) / max);
+    let mut partitions = partitions.into_iter().peekable();
+    while partitions.peek().is_some() {
+        batches.push(partitions.by_ref().take(max).collect());
+    }
+    batches
+}
+
+/// Split the sectors (e.g. the faulty or the recovered sectors to be declared) into bitfields,
+/// each of which contains at most `max` sectors. The sectors are kept in ascending order.
+pub fn chunk_sectors(sectors: &BitField, max: u64) -> Vec<BitField> {
+    let max = max.max(1) as usize;
+    let mut chunks = vec![];
+    let mut iter = sectors.iter().copied().peekable();
+    while iter.peek().is_some() {
+        chunks.push(BitField::from(iter.by_ref().take(max).collect::<Vec<_>>()));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(proof: RegisteredPoStProof, partitions: u64) -> SubmitWindowedPoStParams {
+        SubmitWindowedPoStParams {
+            deadline: 0,
+            partitions: (0..partitions)
+                .map(|index| PoStPartition {
+                    index,
+                    skipped: BitField::new(),
+                })
+                .collect(),
+            proofs: vec![PoStProof {
+                post_proof: proof,
+                proof_bytes: vec![1, 2, 3],
+            }],
+            chain_commit_epoch: 10,
+            chain_commit_rand: vec![1; 32].into(),
+        }
+    }
+
+    #[test]
+    fn test_post_partitions_max() {
+        assert_eq!(
+            post_partitions_max(RegisteredPoStProof::StackedDrgWindow32GiBV1),
+            4
+        );
+        assert_eq!(
+            post_partitions_max(RegisteredPoStProof::StackedDrgWindow64GiBV1),
+            4
+        );
+        assert_eq!(
+            post_partitions_max(RegisteredPoStProof::StackedDrgWindow2KiBV1),
+            ADDRESSED_PARTITIONS_MAX as usize
+        );
+
+        let batches = batch_partitions(
+            (0..10).collect::<Vec<_>>(),
+            RegisteredPoStProof::StackedDrgWindow32GiBV1,
+        );
+        assert_eq!(
+            batches,
+            vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]
+        );
+        assert!(batch_partitions(
+            Vec::<u64>::new(),
+            RegisteredPoStProof::StackedDrgWindow32GiBV1
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_chunk_sectors() {
+        let sectors = BitField::from(vec![1, 3, 5, 7, 9]);
+        let chunks = chunk_sectors(&sectors, 2);
+        assert_eq!(
+            chunks,
+            vec![
+                BitField::from(vec![1, 3]),
+                BitField::from(vec![5, 7]),
+                BitField::from(vec![9]),
+            ]
+        );
+        assert!(chunk_sectors(&BitField::new(), 2).is_empty());
+    }
+
+    #[test]
+    fn test_validate_submit_windowed_post() {
+        let proof = RegisteredPoStProof::StackedDrgWindow32GiBV1;
+        let valid = params(proof, 4);
+        assert_eq!(valid.validate(), Ok(()));
+        let de = minicbor::decode::<SubmitWindowedPoStParams>(&minicbor::to_vec(&valid).unwrap())
+            .unwrap();
+        assert_eq!(de, valid);
+
+        assert_eq!(
+            params(proof, 5).validate(),
+            Err(PoStBatchError::TooManyPartitions { count: 5, max: 4 })
+        );
+        assert_eq!(
+            params(proof, 0).validate(),
+            Err(PoStBatchError::NoPartition)
+        );
+
+        let mut invalid = params(proof, 1);
+        invalid.deadline = W_POST_PERIOD_DEADLINES;
+        assert_eq!(
+            invalid.validate(),
+            Err(PoStBatchError::InvalidDeadline(W_POST_PERIOD_DEADLINES))
+        );
+
+        let mut invalid = params(proof, 1);
+        invalid.proofs.push(PoStProof {
+            post_proof: RegisteredPoStProof::StackedDrgWindow64GiBV1,
+            proof_bytes: vec![],
+        });
+        assert_eq!(
+            invalid.validate(),
+            Err(PoStBatchError::ProofTypeMismatch {
+                expected: proof,
+                actual: RegisteredPoStProof::StackedDrgWindow64GiBV1,
+            })
+        );
+
+        let mut invalid = params(proof, 1);
+        invalid.partitions[0].skipped =
+            BitField::from((0..=ADDRESSED_SECTORS_MAX).collect::<Vec<_>>());
+        assert_eq!(
+            invalid.validate(),
+            Err(PoStBatchError::TooManySectors {
+                count: ADDRESSED_SECTORS_MAX + 1,
+                max: ADDRESSED_SECTORS_MAX,
+            })
+        );
+
+        let mut invalid = params(proof, 1);
+        invalid.proofs.clear();
+        assert_eq!(invalid.validate(), Err(PoStBatchError::NoProof));
+    }
+}
@@ -8,8 +8,12 @@ use plum_address::Address;
 use plum_bigint::bigint_json;
 use plum_bitfield::BitField;
 use plum_peerid::PeerId;
-use plum_sector::{RegisteredSealProof, SectorNumber, SectorSize};
-use plum_types::{ChainEpoch, DealId, DealWeight, TokenAmount};
+use plum_sector::{RegisteredSealProof, SectorSize};
+use plum_types::{ChainEpoch, TokenAmount};
+
+use super::deadlines::{compute_proving_period_deadline, DeadlineInfo};
+
+pub use plum_sector::{SectorOnChainInfo, SectorPreCommitInfo, SectorPreCommitOnChainInfo};
 
 // Balance of Miner Actor should be greater than or equal to
 // the sum of pre_commit_deposits and locked_funds.
@@ -74,6 +78,19 @@ pub struct State {
     pub post_submissions: BitField,
 }
 
+impl State {
+    /// Return the info of the deadline containing the `current_epoch` in the current proving period.
+    pub fn deadline_info(&self, current_epoch: ChainEpoch) -> DeadlineInfo {
+        compute_proving_period_deadline(self.proving_period_start, current_epoch)
+    }
+
+    /// Return the balance that is neither deposited for pre-commits nor locked,
+    /// given the balance of the miner actor.
+    pub fn available_balance(&self, actor_balance: &TokenAmount) -> TokenAmount {
+        actor_balance - &self.pre_commit_deposits - &self.locked_funds
+    }
+}
+
 impl minicbor::Encode for State {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
         e.array(14)?
@@ -195,59 +212,6 @@ pub struct WorkerKeyChange {
     pub effective_at: ChainEpoch,
 }
 
-///
-#[doc(hidden)]
-#[derive(
-    Clone, Debug, PartialEq, Eq, Serialize, Deserialize, minicbor::Encode, minicbor::Decode,
-)]
-#[serde(rename_all = "PascalCase")]
-#[cbor(array)]
-pub struct SectorPreCommitInfo {
-    #[n(0)]
-    pub registered_proof: RegisteredSealProof,
-    #[n(1)]
-    pub sector_number: SectorNumber,
-    /// CommR
-    #[n(2)]
-    #[serde(rename = "SealedCID")]
-    pub sealed_cid: Cid,
-    #[n(3)]
-    pub seal_rand_epoch: ChainEpoch,
-    #[n(4)]
-    #[serde(rename = "DealIDs")]
-    pub deal_ids: Vec<DealId>,
-    /// Sector expiration
-    #[n(5)]
-    pub expiration: ChainEpoch,
-}
-
-///
-#[doc(hidden)]
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct SectorPreCommitOnChainInfo {
-    pub info: SectorPreCommitInfo,
-    #[serde(with = "bigint_json")]
-    pub pre_commit_deposit: TokenAmount,
-    pub pre_commit_epoch: ChainEpoch,
-}
-
-///
-#[doc(hidden)]
-#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct SectorOnChainInfo {
-    pub info: SectorPreCommitInfo,
-    // Epoch at which SectorProveCommit is accepted
-    pub activation_epoch: ChainEpoch,
-    // Integral of active deals over sector lifetime
-    #[serde(with = "bigint_json")]
-    pub deal_weight: DealWeight,
-    // Integral of active verified deals over sector lifetime
-    #[serde(with = "bigint_json")]
-    pub verified_deal_weight: DealWeight,
-}
-
 ///
 #[doc(hidden)]
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
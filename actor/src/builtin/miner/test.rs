@@ -1 +1,78 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use plum_address::Address;
+use plum_peerid::PeerId;
+use plum_sector::RegisteredSealProof;
+
+use super::*;
+
+#[test]
+fn test_change_worker_address_params_cbor() {
+    let params = ChangeWorkerAddressParams {
+        new_worker: Address::new_id_addr(1001).unwrap(),
+        new_control_addrs: vec![Address::new_id_addr(1002).unwrap()],
+    };
+    let cbor = minicbor::to_vec(&params).unwrap();
+    assert_eq!(
+        cbor,
+        vec![0x82, 0x43, 0x00, 0xe9, 0x07, 0x81, 0x43, 0x00, 0xea, 0x07]
+    );
+    assert_eq!(
+        minicbor::decode::<ChangeWorkerAddressParams>(&cbor).unwrap(),
+        params
+    );
+}
+
+#[test]
+fn test_worker_key_rotation_status() {
+    let miner = Address::new_id_addr(1000).unwrap();
+    let old_worker = Address::new_id_addr(1001).unwrap();
+    let new_worker = Address::new_id_addr(1002).unwrap();
+    let rotation = WorkerKeyRotation::new(miner, new_worker.clone(), 100);
+    assert_eq!(rotation.effective_at, 100 + WORKER_KEY_CHANGE_DELAY);
+
+    // Tracked locally only.
+    assert_eq!(rotation.status(None, 101), WorkerRotationStatus::Pending);
+    assert_eq!(
+        rotation.status(None, rotation.effective_at),
+        WorkerRotationStatus::Ready
+    );
+
+    let mut info = MinerInfo {
+        owner: Address::new_id_addr(1003).unwrap(),
+        worker: old_worker,
+        pending_worker_key: WorkerKeyChange {
+            new_worker: Address::new_id_addr(0).unwrap(),
+            effective_at: 0,
+        },
+        peer_id: PeerId::random(),
+        seal_proof_type: RegisteredSealProof::StackedDrg2KiBV1,
+        sector_size: 2048,
+        window_post_partition_sectors: 2,
+    };
+    // The change message is not applied yet.
+    assert_eq!(
+        rotation.status(Some(&info), rotation.effective_at),
+        WorkerRotationStatus::Unknown
+    );
+
+    // The effective epoch on chain wins.
+    info.pending_worker_key = WorkerKeyChange {
+        new_worker: new_worker.clone(),
+        effective_at: rotation.effective_at + 1,
+    };
+    assert_eq!(
+        rotation.status(Some(&info), rotation.effective_at),
+        WorkerRotationStatus::Pending
+    );
+    assert_eq!(
+        rotation.status(Some(&info), rotation.effective_at + 1),
+        WorkerRotationStatus::Ready
+    );
+
+    info.worker = new_worker;
+    assert_eq!(
+        rotation.status(Some(&info), rotation.effective_at + 1),
+        WorkerRotationStatus::Confirmed
+    );
+}
@@ -0,0 +1,115 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_address::Address;
+use plum_types::ChainEpoch;
+
+use super::policy::CHAIN_FINALITYISH;
+use super::state::MinerInfo;
+
+/// The delay between requesting a worker key change and the change taking effect,
+/// the new worker can't be used before the change is confirmed after the delay.
+pub const WORKER_KEY_CHANGE_DELAY: ChainEpoch = CHAIN_FINALITYISH;
+
+/// The params of `ChangeWorkerAddress`, which must be sent by the owner.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChangeWorkerAddressParams {
+    /// The new worker, which takes effect after `WORKER_KEY_CHANGE_DELAY` epochs.
+    pub new_worker: Address,
+    /// The new control addresses, which take effect immediately.
+    pub new_control_addrs: Vec<Address>,
+}
+
+// Implement CBOR serialization for ChangeWorkerAddressParams.
+impl encode::Encode for ChangeWorkerAddressParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(&self.new_worker)?
+            .encode(&self.new_control_addrs)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ChangeWorkerAddressParams.
+impl<'b> decode::Decode<'b> for ChangeWorkerAddressParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of ChangeWorkerAddressParams",
+            ));
+        }
+        Ok(ChangeWorkerAddressParams {
+            new_worker: d.decode::<Address>()?,
+            new_control_addrs: d.decode::<Vec<Address>>()?,
+        })
+    }
+}
+
+/// The status of a worker key rotation, reconciled with the on-chain miner info.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WorkerRotationStatus {
+    /// The change is waiting for `WORKER_KEY_CHANGE_DELAY` epochs.
+    Pending,
+    /// The change can be confirmed by `ConfirmUpdateWorkerKey`.
+    Ready,
+    /// The new worker is the current worker of the miner.
+    Confirmed,
+    /// The change isn't pending on chain, either it's not applied yet or it's replaced by
+    /// another change.
+    Unknown,
+}
+
+/// A worker key rotation requested by the owner, which is tracked locally until confirmed.
+///
+/// The rotation takes two messages: `ChangeWorkerAddress` records the pending change, then
+/// `ConfirmUpdateWorkerKey` applies it at or after the `effective_at` epoch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WorkerKeyRotation {
+    /// The miner actor.
+    pub miner: Address,
+    /// The new worker.
+    pub new_worker: Address,
+    /// The epoch at which `ChangeWorkerAddress` is sent.
+    pub requested_at: ChainEpoch,
+    /// The earliest epoch at which the change can be confirmed.
+    pub effective_at: ChainEpoch,
+}
+
+impl WorkerKeyRotation {
+    /// Create a rotation to the `new_worker`, requested at the `epoch`.
+    pub fn new(miner: Address, new_worker: Address, epoch: ChainEpoch) -> Self {
+        Self {
+            miner,
+            new_worker,
+            requested_at: epoch,
+            effective_at: epoch + WORKER_KEY_CHANGE_DELAY,
+        }
+    }
+
+    /// Return the status of the rotation at the `epoch`.
+    ///
+    /// If the on-chain `info` is given, the effective epoch of the pending change on chain is
+    /// used, which is the authoritative one. Otherwise the locally tracked one is used.
+    pub fn status(&self, info: Option<&MinerInfo>, epoch: ChainEpoch) -> WorkerRotationStatus {
+        let effective_at = match info {
+            Some(info) if info.worker == self.new_worker => return WorkerRotationStatus::Confirmed,
+            Some(info) => {
+                let pending = &info.pending_worker_key;
+                if pending.effective_at == 0 || pending.new_worker != self.new_worker {
+                    return WorkerRotationStatus::Unknown;
+                }
+                pending.effective_at
+            }
+            None => self.effective_at,
+        };
+        if epoch >= effective_at {
+            WorkerRotationStatus::Ready
+        } else {
+            WorkerRotationStatus::Pending
+        }
+    }
+}
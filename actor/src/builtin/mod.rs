@@ -6,6 +6,8 @@
 
 ///
 pub mod account;
+/// The code CIDs of the builtin actors.
+pub mod codes;
 ///
 pub mod cron;
 ///
@@ -24,6 +26,8 @@ pub mod network;
 pub mod paych;
 ///
 pub mod power;
+/// The typed return values of the methods of the builtin actors.
+pub mod returns;
 ///
 pub mod reward;
 ///
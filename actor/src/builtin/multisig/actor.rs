@@ -1 +1,142 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_address::Address;
+use plum_bigint::{bigint_json, BigIntRefWrapper, BigIntWrapper};
+use plum_types::{ChainEpoch, MethodNum, TokenAmount};
+
+use super::state::TxnID;
+
+/// The methods of the multisig actor.
+#[allow(missing_docs)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    Constructor = 1,
+    Propose = 2,
+    Approve = 3,
+    Cancel = 4,
+    AddSigner = 5,
+    RemoveSigner = 6,
+    SwapSigner = 7,
+    ChangeNumApprovalsThreshold = 8,
+}
+
+impl From<Method> for MethodNum {
+    fn from(method: Method) -> Self {
+        method as MethodNum
+    }
+}
+
+/// The params of the constructor of the multisig actor.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConstructorParams {
+    pub signers: Vec<Address>,
+    pub num_approvals_threshold: u64,
+    pub unlock_duration: ChainEpoch,
+}
+
+// Implement CBOR serialization for ConstructorParams.
+impl encode::Encode for ConstructorParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(3)?
+            .encode(&self.signers)?
+            .u64(self.num_approvals_threshold)?
+            .i64(self.unlock_duration)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ConstructorParams.
+impl<'b> decode::Decode<'b> for ConstructorParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(3) {
+            return Err(decode::Error::Message(
+                "expected 3 fields of ConstructorParams",
+            ));
+        }
+        Ok(ConstructorParams {
+            signers: d.decode::<Vec<Address>>()?,
+            num_approvals_threshold: d.u64()?,
+            unlock_duration: d.i64()?,
+        })
+    }
+}
+
+/// The params of the `Propose` method.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProposeParams {
+    pub to: Address,
+    #[serde(with = "bigint_json")]
+    pub value: TokenAmount,
+    pub method: MethodNum,
+    #[serde(with = "plum_bytes::base64")]
+    pub params: Vec<u8>,
+}
+
+// Implement CBOR serialization for ProposeParams.
+impl encode::Encode for ProposeParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(4)?
+            .encode(&self.to)?
+            .encode(BigIntRefWrapper::from(&self.value))?
+            .u64(self.method)?
+            .bytes(&self.params)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ProposeParams.
+impl<'b> decode::Decode<'b> for ProposeParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(4) {
+            return Err(decode::Error::Message("expected 4 fields of ProposeParams"));
+        }
+        Ok(ProposeParams {
+            to: d.decode::<Address>()?,
+            value: d.decode::<BigIntWrapper>()?.into_inner(),
+            method: d.u64()?,
+            params: d.bytes()?.to_vec(),
+        })
+    }
+}
+
+/// The params of the `Approve` and `Cancel` methods.
+///
+/// The `proposal_hash` is optional, if it's not empty, it must match the hash of the pending
+/// transaction, which prevents approving a transaction replaced after a re-org.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TxnIDParams {
+    #[serde(rename = "ID")]
+    pub id: TxnID,
+    #[serde(with = "plum_bytes::base64")]
+    pub proposal_hash: Vec<u8>,
+}
+
+// Implement CBOR serialization for TxnIDParams.
+impl encode::Encode for TxnIDParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?.i64(self.id)?.bytes(&self.proposal_hash)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for TxnIDParams.
+impl<'b> decode::Decode<'b> for TxnIDParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of TxnIDParams"));
+        }
+        Ok(TxnIDParams {
+            id: d.i64()?,
+            proposal_hash: d.bytes()?.to_vec(),
+        })
+    }
+}
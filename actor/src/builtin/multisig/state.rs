@@ -1 +1,169 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use cid::Cid;
+use plum_address::Address;
+use plum_bigint::{bigint_json, BigIntRefWrapper, BigIntWrapper};
+use plum_types::{ChainEpoch, MethodNum, TokenAmount};
+
+/// The ID of the pending transactions of a multisig actor.
+pub type TxnID = i64;
+
+/// The state of the multisig actor.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct State {
+    pub signers: Vec<Address>,
+    pub num_approvals_threshold: u64,
+    #[serde(rename = "NextTxnID")]
+    pub next_txn_id: TxnID,
+
+    // Linear unlock
+    #[serde(with = "bigint_json")]
+    pub initial_balance: TokenAmount,
+    pub start_epoch: ChainEpoch,
+    pub unlock_duration: ChainEpoch,
+
+    /// The root of the HAMT of the pending transactions, keyed by the transaction ID.
+    pub pending_txns: Cid,
+}
+
+impl State {
+    /// Return the amount still locked at the `current_epoch`, which is unlocked linearly
+    /// over the `unlock_duration` since the `start_epoch`.
+    pub fn amount_locked(&self, current_epoch: ChainEpoch) -> TokenAmount {
+        let elapsed = current_epoch - self.start_epoch;
+        if elapsed >= self.unlock_duration {
+            return TokenAmount::from(0);
+        }
+        if elapsed <= 0 {
+            return self.initial_balance.clone();
+        }
+        let remaining = self.unlock_duration - elapsed;
+        &self.initial_balance * remaining / self.unlock_duration
+    }
+
+    /// Return true if the address is one of the signers.
+    pub fn is_signer(&self, address: &Address) -> bool {
+        self.signers.contains(address)
+    }
+}
+
+// Implement CBOR serialization for State.
+impl encode::Encode for State {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(7)?
+            .encode(&self.signers)?
+            .u64(self.num_approvals_threshold)?
+            .i64(self.next_txn_id)?
+            .encode(BigIntRefWrapper::from(&self.initial_balance))?
+            .i64(self.start_epoch)?
+            .i64(self.unlock_duration)?
+            .encode(&self.pending_txns)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for State.
+impl<'b> decode::Decode<'b> for State {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(7) {
+            return Err(decode::Error::Message("expected 7 fields of State"));
+        }
+        Ok(State {
+            signers: d.decode::<Vec<Address>>()?,
+            num_approvals_threshold: d.u64()?,
+            next_txn_id: d.i64()?,
+            initial_balance: d.decode::<BigIntWrapper>()?.into_inner(),
+            start_epoch: d.i64()?,
+            unlock_duration: d.i64()?,
+            pending_txns: d.decode::<Cid>()?,
+        })
+    }
+}
+
+/// The pending transaction of the multisig actor.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Transaction {
+    pub to: Address,
+    #[serde(with = "bigint_json")]
+    pub value: TokenAmount,
+    pub method: MethodNum,
+    #[serde(with = "plum_bytes::base64")]
+    pub params: Vec<u8>,
+
+    // This address at index 0 is the transaction proposer, order of this slice must be preserved.
+    pub approved: Vec<Address>,
+}
+
+impl Transaction {
+    /// Return the number of the approvals still required to execute the transaction.
+    pub fn remaining_approvals(&self, num_approvals_threshold: u64) -> u64 {
+        num_approvals_threshold.saturating_sub(self.approved.len() as u64)
+    }
+
+    /// Return the hash of the proposal, which is the blake2b-256 hash of the CBOR encoded
+    /// `[requester, to, value, method, params]`, the requester is the proposer.
+    pub fn proposal_hash(&self) -> Option<Vec<u8>> {
+        let data = ProposalHashData {
+            requester: self.approved.first()?,
+            txn: self,
+        };
+        let data = minicbor::to_vec(data)
+            .expect("CBOR serialization of ProposalHashData shouldn't fail; qed");
+        let hash = multihash::Blake2b256::digest(&data);
+        Some(hash.digest().to_vec())
+    }
+}
+
+struct ProposalHashData<'a> {
+    requester: &'a Address,
+    txn: &'a Transaction,
+}
+
+// Implement CBOR serialization for ProposalHashData.
+impl<'a> encode::Encode for ProposalHashData<'a> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(5)?
+            .encode(self.requester)?
+            .encode(&self.txn.to)?
+            .encode(BigIntRefWrapper::from(&self.txn.value))?
+            .u64(self.txn.method)?
+            .bytes(&self.txn.params)?
+            .ok()
+    }
+}
+
+// Implement CBOR serialization for Transaction.
+impl encode::Encode for Transaction {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(5)?
+            .encode(&self.to)?
+            .encode(BigIntRefWrapper::from(&self.value))?
+            .u64(self.method)?
+            .bytes(&self.params)?
+            .encode(&self.approved)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for Transaction.
+impl<'b> decode::Decode<'b> for Transaction {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(5) {
+            return Err(decode::Error::Message("expected 5 fields of Transaction"));
+        }
+        Ok(Transaction {
+            to: d.decode::<Address>()?,
+            value: d.decode::<BigIntWrapper>()?.into_inner(),
+            method: d.u64()?,
+            params: d.bytes()?.to_vec(),
+            approved: d.decode::<Vec<Address>>()?,
+        })
+    }
+}
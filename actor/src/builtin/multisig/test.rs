@@ -1 +1,60 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use plum_address::Address;
+use plum_bigint::BigInt;
+
+use super::*;
+
+fn new_state() -> State {
+    State {
+        signers: vec![
+            Address::new_id_addr(100).unwrap(),
+            Address::new_id_addr(101).unwrap(),
+        ],
+        num_approvals_threshold: 2,
+        next_txn_id: 1,
+        initial_balance: BigInt::from(1000),
+        start_epoch: 10,
+        unlock_duration: 100,
+        pending_txns: "bafy2bzacecesrkxghscnq7vatble2hqdvwat6ed23vdu4vvo3uuggsoaya7ki"
+            .parse()
+            .unwrap(),
+    }
+}
+
+#[test]
+fn test_state_cbor() {
+    let state = new_state();
+    let data = minicbor::to_vec(&state).unwrap();
+    assert_eq!(minicbor::decode::<State>(&data).unwrap(), state);
+}
+
+#[test]
+fn test_amount_locked() {
+    let state = new_state();
+    assert_eq!(state.amount_locked(0), BigInt::from(1000));
+    assert_eq!(state.amount_locked(10), BigInt::from(1000));
+    assert_eq!(state.amount_locked(60), BigInt::from(500));
+    assert_eq!(state.amount_locked(110), BigInt::from(0));
+}
+
+#[test]
+fn test_transaction() {
+    let state = new_state();
+    let txn = Transaction {
+        to: Address::new_id_addr(200).unwrap(),
+        value: BigInt::from(10),
+        method: 0,
+        params: vec![],
+        approved: vec![state.signers[0].clone()],
+    };
+    assert_eq!(txn.remaining_approvals(state.num_approvals_threshold), 1);
+    assert_eq!(txn.proposal_hash().unwrap().len(), 32);
+
+    let params = TxnIDParams {
+        id: 0,
+        proposal_hash: txn.proposal_hash().unwrap(),
+    };
+    let data = minicbor::to_vec(&params).unwrap();
+    assert_eq!(minicbor::decode::<TxnIDParams>(&data).unwrap(), params);
+}
@@ -1 +1,116 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_address::Address;
+use plum_bytes::Bytes;
+use plum_peerid::{PeerId, PeerIdRefWrapper, PeerIdWrapper};
+use plum_sector::RegisteredSealProof;
+use plum_types::MethodNum;
+
+/// The ID of the singleton storage power actor.
+pub const STORAGE_POWER_ACTOR_ID: u64 = 4;
+
+/// The methods of the storage power actor.
+#[allow(missing_docs)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Method {
+    Constructor = 1,
+    CreateMiner = 2,
+    UpdateClaimedPower = 3,
+    EnrollCronEvent = 4,
+    OnEpochTickEnd = 5,
+    UpdatePledgeTotal = 6,
+    OnConsensusFault = 7,
+    SubmitPoRepForBulkVerify = 8,
+    CurrentTotalPower = 9,
+}
+
+impl From<Method> for MethodNum {
+    fn from(method: Method) -> Self {
+        method as MethodNum
+    }
+}
+
+/// The params of the `CreateMiner` method.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreateMinerParams {
+    pub owner: Address,
+    pub worker: Address,
+    pub seal_proof_type: RegisteredSealProof,
+    #[serde(with = "plum_peerid")]
+    pub peer: PeerId,
+    pub multiaddrs: Vec<Bytes>,
+}
+
+// Implement CBOR serialization for CreateMinerParams.
+impl encode::Encode for CreateMinerParams {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(5)?
+            .encode(&self.owner)?
+            .encode(&self.worker)?
+            .encode(&self.seal_proof_type)?
+            .encode(PeerIdRefWrapper::from(&self.peer))?
+            .encode(&self.multiaddrs)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for CreateMinerParams.
+impl<'b> decode::Decode<'b> for CreateMinerParams {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(5) {
+            return Err(decode::Error::Message(
+                "expected 5 fields of CreateMinerParams",
+            ));
+        }
+        Ok(CreateMinerParams {
+            owner: d.decode::<Address>()?,
+            worker: d.decode::<Address>()?,
+            seal_proof_type: d.decode::<RegisteredSealProof>()?,
+            peer: d.decode::<PeerIdWrapper>()?.into_inner(),
+            multiaddrs: d.decode::<Vec<Bytes>>()?,
+        })
+    }
+}
+
+/// The return of the `CreateMiner` method.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct CreateMinerReturn {
+    /// The canonical ID-based address of the new miner actor.
+    pub id_address: Address,
+    /// The re-org safe address of the new miner actor.
+    pub robust_address: Address,
+}
+
+// Implement CBOR serialization for CreateMinerReturn.
+impl encode::Encode for CreateMinerReturn {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(&self.id_address)?
+            .encode(&self.robust_address)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for CreateMinerReturn.
+impl<'b> decode::Decode<'b> for CreateMinerReturn {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        // The return is read from the receipts on chain, don't panic on the malformed one.
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of CreateMinerReturn",
+            ));
+        }
+        Ok(CreateMinerReturn {
+            id_address: d.decode::<Address>()?,
+            robust_address: d.decode::<Address>()?,
+        })
+    }
+}
@@ -1,8 +1,9 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
-use plum_bigint::bigint_json;
+use plum_bigint::{bigint_json, BigIntRefWrapper, BigIntWrapper};
 use plum_sector::StoragePower;
 
 ///
@@ -17,3 +18,26 @@ pub struct Claim {
     #[serde(with = "bigint_json")]
     pub quality_adj_power: StoragePower,
 }
+
+// Implement CBOR serialization for Claim.
+impl encode::Encode for Claim {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(BigIntRefWrapper::from(&self.raw_byte_power))?
+            .encode(BigIntRefWrapper::from(&self.quality_adj_power))?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for Claim.
+impl<'b> decode::Decode<'b> for Claim {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of Claim"));
+        }
+        Ok(Claim {
+            raw_byte_power: d.decode::<BigIntWrapper>()?.into_inner(),
+            quality_adj_power: d.decode::<BigIntWrapper>()?.into_inner(),
+        })
+    }
+}
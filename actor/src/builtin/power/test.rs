@@ -1 +1,20 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use plum_address::Address;
+use plum_peerid::PeerId;
+use plum_sector::RegisteredSealProof;
+
+use super::*;
+
+#[test]
+fn test_create_miner_params_cbor() {
+    let params = CreateMinerParams {
+        owner: Address::new_id_addr(100).unwrap(),
+        worker: Address::new_id_addr(101).unwrap(),
+        seal_proof_type: RegisteredSealProof::StackedDrg32GiBV1,
+        peer: PeerId::random(),
+        multiaddrs: vec![vec![4, 127, 0, 0, 1].into()],
+    };
+    let data = minicbor::to_vec(&params).unwrap();
+    assert_eq!(minicbor::decode::<CreateMinerParams>(&data).unwrap(), params);
+}
@@ -0,0 +1,189 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use cid::Cid;
+use minicbor::decode;
+
+use plum_types::MethodNum;
+
+use super::codes::actor_name;
+use super::init::ExecReturn;
+use super::market::PublishStorageDealsReturn;
+use super::methods::MethodSend;
+use super::power::CreateMinerReturn;
+
+// The names of the methods of the builtin actors, indexed by `method - 1`.
+const INIT_METHODS: &[&str] = &["Constructor", "Exec"];
+const MARKET_METHODS: &[&str] = &[
+    "Constructor",
+    "AddBalance",
+    "WithdrawBalance",
+    "PublishStorageDeals",
+    "VerifyDealsForActivation",
+    "ActivateDeals",
+    "OnMinerSectorsTerminate",
+    "ComputeDataCommitment",
+    "CronTick",
+];
+const MINER_METHODS: &[&str] = &[
+    "Constructor",
+    "ControlAddresses",
+    "ChangeWorkerAddress",
+    "ChangePeerID",
+    "SubmitWindowedPoSt",
+    "PreCommitSector",
+    "ProveCommitSector",
+    "ExtendSectorExpiration",
+    "TerminateSectors",
+    "DeclareFaults",
+    "DeclareFaultsRecovered",
+    "OnDeferredCronEvent",
+    "CheckSectorProven",
+    "AddLockedFund",
+    "ReportConsensusFault",
+    "WithdrawBalance",
+    "ConfirmSectorProofsValid",
+    "ChangeMultiaddrs",
+    "CompactPartitions",
+    "CompactSectorNumbers",
+    "ConfirmUpdateWorkerKey",
+    "RepayDebt",
+    "ChangeOwnerAddress",
+];
+const MULTISIG_METHODS: &[&str] = &[
+    "Constructor",
+    "Propose",
+    "Approve",
+    "Cancel",
+    "AddSigner",
+    "RemoveSigner",
+    "SwapSigner",
+    "ChangeNumApprovalsThreshold",
+];
+const POWER_METHODS: &[&str] = &[
+    "Constructor",
+    "CreateMiner",
+    "UpdateClaimedPower",
+    "EnrollCronEvent",
+    "OnEpochTickEnd",
+    "UpdatePledgeTotal",
+    "OnConsensusFault",
+    "SubmitPoRepForBulkVerify",
+    "CurrentTotalPower",
+];
+
+/// Return the name of the `method` of the builtin actor with the code CID, e.g. `Exec`,
+/// `None` if the actor or the method is unknown.
+pub fn method_name(code: &Cid, method: MethodNum) -> Option<&'static str> {
+    if method == MethodSend {
+        return Some("Send");
+    }
+    let methods = match actor_name(code)? {
+        "init" => INIT_METHODS,
+        "storagemarket" => MARKET_METHODS,
+        "storageminer" => MINER_METHODS,
+        "multisig" => MULTISIG_METHODS,
+        "storagepower" => POWER_METHODS,
+        _ => return None,
+    };
+    methods.get((method as usize).checked_sub(1)?).copied()
+}
+
+/// The typed return value of a method of the builtin actors, decoded from the `return` of
+/// the message receipt.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MethodReturn {
+    /// The method returns nothing.
+    None,
+    /// The return of the `Exec` method of the init actor.
+    Exec(ExecReturn),
+    /// The return of the `CreateMiner` method of the storage power actor.
+    CreateMiner(CreateMinerReturn),
+    /// The return of the `PublishStorageDeals` method of the storage market actor.
+    PublishStorageDeals(PublishStorageDealsReturn),
+    /// The return of a method whose return type is unknown, kept as the raw CBOR bytes.
+    Raw(Vec<u8>),
+}
+
+/// Decode the return value `ret` of the `method` of the builtin actor with the code CID.
+///
+/// An empty `ret` is always `MethodReturn::None`, and the return of the unknown actors or
+/// methods is kept as `MethodReturn::Raw`.
+pub fn decode_return(
+    code: &Cid,
+    method: MethodNum,
+    ret: &[u8],
+) -> Result<MethodReturn, decode::Error> {
+    if ret.is_empty() {
+        return Ok(MethodReturn::None);
+    }
+    Ok(match (actor_name(code), method_name(code, method)) {
+        (Some("init"), Some("Exec")) => MethodReturn::Exec(minicbor::decode(ret)?),
+        (Some("storagepower"), Some("CreateMiner")) => {
+            MethodReturn::CreateMiner(minicbor::decode(ret)?)
+        }
+        (Some("storagemarket"), Some("PublishStorageDeals")) => {
+            MethodReturn::PublishStorageDeals(minicbor::decode(ret)?)
+        }
+        _ => MethodReturn::Raw(ret.to_vec()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_address::Address;
+
+    use crate::codes::{
+        ACCOUNT_ACTOR_CODE_ID, INIT_ACTOR_CODE_ID, STORAGE_MARKET_ACTOR_CODE_ID,
+        STORAGE_MINER_ACTOR_CODE_ID,
+    };
+    use crate::{init, market};
+
+    #[test]
+    fn test_method_name() {
+        assert_eq!(method_name(&INIT_ACTOR_CODE_ID, 0), Some("Send"));
+        assert_eq!(
+            method_name(&INIT_ACTOR_CODE_ID, init::Method::Exec.into()),
+            Some("Exec")
+        );
+        assert_eq!(
+            method_name(&STORAGE_MINER_ACTOR_CODE_ID, 23),
+            Some("ChangeOwnerAddress")
+        );
+        assert_eq!(method_name(&STORAGE_MINER_ACTOR_CODE_ID, 24), None);
+        assert_eq!(method_name(&ACCOUNT_ACTOR_CODE_ID, 2), None);
+    }
+
+    #[test]
+    fn test_decode_return() {
+        let exec = ExecReturn {
+            id_address: Address::new_id_addr(1001).unwrap(),
+            robust_address: Address::new_actor_addr(b"actor").unwrap(),
+        };
+        let ret = minicbor::to_vec(&exec).unwrap();
+        assert_eq!(
+            decode_return(&INIT_ACTOR_CODE_ID, init::Method::Exec.into(), &ret).unwrap(),
+            MethodReturn::Exec(exec)
+        );
+        assert_eq!(
+            decode_return(&INIT_ACTOR_CODE_ID, init::Method::Constructor.into(), &[]).unwrap(),
+            MethodReturn::None
+        );
+
+        let publish = market::PublishStorageDealsReturn { ids: vec![1, 2, 3] };
+        let ret = minicbor::to_vec(&publish).unwrap();
+        let method = market::Method::PublishStorageDeals.into();
+        assert_eq!(
+            decode_return(&STORAGE_MARKET_ACTOR_CODE_ID, method, &ret).unwrap(),
+            MethodReturn::PublishStorageDeals(publish)
+        );
+        // The malformed return is an error rather than a panic.
+        assert!(decode_return(&STORAGE_MARKET_ACTOR_CODE_ID, method, &[0x80]).is_err());
+        // The return of the unknown method is kept.
+        assert_eq!(
+            decode_return(&ACCOUNT_ACTOR_CODE_ID, 2, &ret).unwrap(),
+            MethodReturn::Raw(ret)
+        );
+    }
+}
@@ -0,0 +1,150 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The dispatch of the method calls to the actors, which is generated by `actor_methods`.
+
+use minicbor::{decode, encode};
+
+pub use plum_types::MethodNum;
+use plum_vm_exitcode::ExitCode;
+
+/// Errors of invoking the methods of the actors.
+#[derive(Debug, thiserror::Error)]
+pub enum ActorError {
+    /// The actor has no handler of the method.
+    #[error("unhandled method {0}")]
+    UnhandledMethod(MethodNum),
+    /// The params can't be decoded as the params type of the method.
+    #[error("failed to decode the params of method {method}: {err}")]
+    Params {
+        /// The method number.
+        method: MethodNum,
+        /// The CBOR decode error.
+        err: decode::Error,
+    },
+    /// The method aborts with the exit code.
+    #[error("aborted with exit code {code}: {message}")]
+    Abort {
+        /// The exit code.
+        code: ExitCode,
+        /// The reason of the abort.
+        message: String,
+    },
+}
+
+impl ActorError {
+    /// Create the error aborting the method with the exit code.
+    pub fn abort<S: Into<String>>(code: ExitCode, message: S) -> Self {
+        ActorError::Abort {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Return the exit code of the error, which is recorded in the message receipt.
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            ActorError::UnhandledMethod(_) => ExitCode::SysErrInvalidMethod,
+            ActorError::Params { .. } => ExitCode::ErrSerialization,
+            ActorError::Abort { code, .. } => *code,
+        }
+    }
+}
+
+/// Decode the CBOR encoded `params` of the `method`.
+pub fn decode_method_params<'b, P>(method: MethodNum, params: &'b [u8]) -> Result<P, ActorError>
+where
+    P: decode::Decode<'b>,
+{
+    minicbor::decode(params).map_err(|err| ActorError::Params { method, err })
+}
+
+/// Encode the return value of a method into CBOR.
+pub fn encode_return<R: encode::Encode>(ret: &R) -> Vec<u8> {
+    minicbor::to_vec(ret).expect("encoding into a Vec never fails; qed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::actor_methods;
+
+    #[derive(Default)]
+    struct State {
+        value: u64,
+    }
+
+    #[repr(u64)]
+    enum Method {
+        Constructor = 1,
+        Add = 2,
+    }
+
+    impl From<Method> for MethodNum {
+        fn from(method: Method) -> Self {
+            method as MethodNum
+        }
+    }
+
+    struct CounterActor;
+
+    #[actor_methods]
+    impl CounterActor {
+        #[method(Method::Constructor)]
+        fn constructor(state: &mut State) -> Result<(), ActorError> {
+            state.value = 0;
+            Ok(())
+        }
+
+        #[method(Method::Add)]
+        fn add(state: &mut State, delta: u64) -> Result<u64, ActorError> {
+            state.value = state
+                .value
+                .checked_add(delta)
+                .ok_or_else(|| ActorError::abort(ExitCode::ErrIllegalArgument, "overflow"))?;
+            Ok(state.value)
+        }
+
+        #[method(3)]
+        fn history(state: &mut State, len: u64) -> Result<Vec<u64>, ActorError> {
+            Ok(vec![state.value; len as usize])
+        }
+
+        // Not a handler.
+        fn reset(state: &mut State) {
+            state.value = 0;
+        }
+    }
+
+    #[test]
+    fn test_actor_methods() {
+        let mut state = State { value: 10 };
+        assert_eq!(
+            CounterActor::invoke_method(&mut state, 1, &[]).unwrap(),
+            Vec::<u8>::new()
+        );
+        assert_eq!(state.value, 0);
+
+        let params = minicbor::to_vec(5u64).unwrap();
+        let ret = CounterActor::invoke_method(&mut state, 2, &params).unwrap();
+        assert_eq!(minicbor::decode::<u64>(&ret).unwrap(), 5);
+        let ret = CounterActor::invoke_method(&mut state, 3, &minicbor::to_vec(2u64).unwrap());
+        assert_eq!(
+            minicbor::decode::<Vec<u64>>(&ret.unwrap()).unwrap(),
+            vec![5, 5]
+        );
+
+        let err = CounterActor::invoke_method(&mut state, 4, &params).unwrap_err();
+        assert!(matches!(err, ActorError::UnhandledMethod(4)));
+        assert_eq!(err.exit_code(), ExitCode::SysErrInvalidMethod);
+        let err = CounterActor::invoke_method(&mut state, 2, &[0x80]).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::ErrSerialization);
+        let params = minicbor::to_vec(u64::MAX).unwrap();
+        let err = CounterActor::invoke_method(&mut state, 2, &params).unwrap_err();
+        assert_eq!(err.exit_code(), ExitCode::ErrIllegalArgument);
+        assert_eq!(state.value, 5);
+
+        CounterActor::reset(&mut state);
+        assert_eq!(state.value, 0);
+    }
+}
@@ -1,8 +1,15 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+// Make the `::plum_actor` paths generated by `actor_methods` resolve within this crate.
+extern crate self as plum_actor;
+
 mod builtin;
+pub mod dispatch;
+pub mod params;
+pub mod smoothing;
 
 pub use self::builtin::{
-    account, cron, init, market, methods::*, miner, multisig, network::*, paych, power, reward,
-    system, verifreg,
+    account, codes, cron, init, market, methods::*, miner, multisig, network::*, paych, power,
+    returns, reward, system, verifreg,
 };
+pub use plum_actor_macros::actor_methods;
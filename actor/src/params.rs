@@ -0,0 +1,284 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use cid::Cid;
+use lazy_static::lazy_static;
+use serde::Serialize;
+
+use plum_address::Address;
+use plum_types::MethodNum;
+
+use crate::builtin::codes::{
+    actor_name, INIT_ACTOR_CODE_ID, MULTISIG_ACTOR_CODE_ID, STORAGE_MARKET_ACTOR_CODE_ID,
+    STORAGE_MINER_ACTOR_CODE_ID, STORAGE_POWER_ACTOR_CODE_ID,
+};
+use crate::builtin::{init, market, methods::MethodSend, miner, multisig, power};
+
+/// Errors of decoding the params of the actor methods.
+#[derive(Debug, thiserror::Error)]
+pub enum ParamsError {
+    /// The method of the actor is not registered.
+    #[error("unknown method {method} of actor {code}")]
+    UnknownMethod {
+        /// The code CID of the actor.
+        code: Cid,
+        /// The method number.
+        method: MethodNum,
+    },
+    /// CBOR decode error.
+    #[error("CBOR decode error: {0}")]
+    Decode(#[from] minicbor::decode::Error),
+    /// JSON encode error.
+    #[error("JSON encode error: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Decode the CBOR encoded params of a method.
+pub fn decode_params<'b, P>(params: &'b [u8]) -> Result<P, ParamsError>
+where
+    P: minicbor::Decode<'b>,
+{
+    Ok(minicbor::decode(params)?)
+}
+
+type DecodeJsonFn = fn(&[u8]) -> Result<serde_json::Value, ParamsError>;
+
+fn decode_json<P>(params: &[u8]) -> Result<serde_json::Value, ParamsError>
+where
+    P: for<'b> minicbor::Decode<'b> + Serialize,
+{
+    let params = decode_params::<P>(params)?;
+    Ok(serde_json::to_value(params)?)
+}
+
+/// The metadata of a registered actor method.
+#[derive(Copy, Clone)]
+pub struct MethodMeta {
+    /// The name of the method.
+    pub name: &'static str,
+    decode: Option<DecodeJsonFn>,
+}
+
+/// The registry of the methods of the actors, which maps the actor code and the method number
+/// to the method name and the typed params of the method.
+#[derive(Clone, Default)]
+pub struct ParamsRegistry {
+    methods: HashMap<(Cid, MethodNum), MethodMeta>,
+}
+
+impl ParamsRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a registry with the methods of the builtin actors.
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+
+        let code = &*STORAGE_MINER_ACTOR_CODE_ID;
+        registry.register_name(code, miner::Method::Constructor.into(), "Constructor");
+        registry.register_name(
+            code,
+            miner::Method::ControlAddresses.into(),
+            "ControlAddresses",
+        );
+        registry.register::<miner::ChangeWorkerAddressParams>(
+            code,
+            miner::Method::ChangeWorkerAddress.into(),
+            "ChangeWorkerAddress",
+        );
+        registry.register_name(code, miner::Method::ChangePeerID.into(), "ChangePeerID");
+        registry.register_name(
+            code,
+            miner::Method::SubmitWindowedPoSt.into(),
+            "SubmitWindowedPoSt",
+        );
+        registry.register_name(
+            code,
+            miner::Method::PreCommitSector.into(),
+            "PreCommitSector",
+        );
+        registry.register::<miner::ProveCommitSectorParams>(
+            code,
+            miner::Method::ProveCommitSector.into(),
+            "ProveCommitSector",
+        );
+        registry.register_name(
+            code,
+            miner::Method::WithdrawBalance.into(),
+            "WithdrawBalance",
+        );
+        registry.register_name(
+            code,
+            miner::Method::ConfirmUpdateWorkerKey.into(),
+            "ConfirmUpdateWorkerKey",
+        );
+        registry.register::<Address>(
+            code,
+            miner::Method::ChangeOwnerAddress.into(),
+            "ChangeOwnerAddress",
+        );
+
+        let code = &*STORAGE_MARKET_ACTOR_CODE_ID;
+        registry.register_name(code, market::Method::Constructor.into(), "Constructor");
+        registry.register_name(code, market::Method::AddBalance.into(), "AddBalance");
+        registry.register_name(
+            code,
+            market::Method::WithdrawBalance.into(),
+            "WithdrawBalance",
+        );
+        registry.register::<market::PublishStorageDealsParams>(
+            code,
+            market::Method::PublishStorageDeals.into(),
+            "PublishStorageDeals",
+        );
+        registry.register_name(code, market::Method::CronTick.into(), "CronTick");
+
+        let code = &*STORAGE_POWER_ACTOR_CODE_ID;
+        registry.register::<power::CreateMinerParams>(
+            code,
+            power::Method::CreateMiner.into(),
+            "CreateMiner",
+        );
+
+        let code = &*INIT_ACTOR_CODE_ID;
+        registry.register::<init::ExecParams>(code, init::Method::Exec.into(), "Exec");
+
+        let code = &*MULTISIG_ACTOR_CODE_ID;
+        registry.register::<multisig::ConstructorParams>(
+            code,
+            multisig::Method::Constructor.into(),
+            "Constructor",
+        );
+        registry.register::<multisig::ProposeParams>(
+            code,
+            multisig::Method::Propose.into(),
+            "Propose",
+        );
+        registry.register::<multisig::TxnIDParams>(
+            code,
+            multisig::Method::Approve.into(),
+            "Approve",
+        );
+        registry.register::<multisig::TxnIDParams>(code, multisig::Method::Cancel.into(), "Cancel");
+
+        registry
+    }
+
+    /// Register a method whose params are decoded into `P`.
+    pub fn register<P>(&mut self, code: &Cid, method: MethodNum, name: &'static str)
+    where
+        P: for<'b> minicbor::Decode<'b> + Serialize,
+    {
+        let meta = MethodMeta {
+            name,
+            decode: Some(decode_json::<P>),
+        };
+        self.methods.insert((code.clone(), method), meta);
+    }
+
+    /// Register a method whose params are unknown or empty.
+    pub fn register_name(&mut self, code: &Cid, method: MethodNum, name: &'static str) {
+        let meta = MethodMeta { name, decode: None };
+        self.methods.insert((code.clone(), method), meta);
+    }
+
+    /// Return the metadata of the method of the actor.
+    pub fn method(&self, code: &Cid, method: MethodNum) -> Option<&MethodMeta> {
+        self.methods.get(&(code.clone(), method))
+    }
+
+    /// Return the name of the method of the actor, method `0` is always `Send`.
+    pub fn method_name(&self, code: &Cid, method: MethodNum) -> Option<&'static str> {
+        if method == MethodSend {
+            return Some("Send");
+        }
+        self.method(code, method).map(|meta| meta.name)
+    }
+
+    /// Decode the CBOR encoded params of the method of the actor and render them as JSON.
+    ///
+    /// The params of `Send` are always `null`, and the params of the registered methods without
+    /// a typed params are rendered as the hex string of the raw bytes.
+    pub fn decode_json(
+        &self,
+        code: &Cid,
+        method: MethodNum,
+        params: &[u8],
+    ) -> Result<serde_json::Value, ParamsError> {
+        if method == MethodSend {
+            return Ok(serde_json::Value::Null);
+        }
+        let meta = self
+            .method(code, method)
+            .ok_or_else(|| ParamsError::UnknownMethod {
+                code: code.clone(),
+                method,
+            })?;
+        match meta.decode {
+            Some(decode) => decode(params),
+            None if params.is_empty() => Ok(serde_json::Value::Null),
+            None => Ok(serde_json::Value::String(hex::encode(params))),
+        }
+    }
+
+    /// Describe the method of the actor as `<actor>.<method>`, e.g. `storageminer.ProveCommitSector`.
+    pub fn describe(&self, code: &Cid, method: MethodNum) -> String {
+        let actor = actor_name(code).map_or_else(|| code.to_string(), ToString::to_string);
+        match self.method_name(code, method) {
+            Some(name) => format!("{}.{}", actor, name),
+            None => format!("{}.{}", actor, method),
+        }
+    }
+}
+
+lazy_static! {
+    /// The registry of the methods of the builtin actors.
+    pub static ref BUILTIN_PARAMS_REGISTRY: ParamsRegistry = ParamsRegistry::builtin();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::builtin::codes::ACCOUNT_ACTOR_CODE_ID;
+
+    #[test]
+    fn test_decode_json() {
+        let registry = &*BUILTIN_PARAMS_REGISTRY;
+        let code = &*STORAGE_MINER_ACTOR_CODE_ID;
+
+        let params = miner::ProveCommitSectorParams {
+            sector_number: 7,
+            proof: vec![1, 2, 3],
+        };
+        let data = minicbor::to_vec(&params).unwrap();
+        assert_eq!(
+            registry
+                .decode_json(code, miner::Method::ProveCommitSector.into(), &data)
+                .unwrap(),
+            serde_json::json!({"SectorNumber": 7, "Proof": "AQID"})
+        );
+        assert_eq!(
+            registry.describe(code, miner::Method::ProveCommitSector.into()),
+            "storageminer.ProveCommitSector"
+        );
+
+        assert_eq!(
+            registry.decode_json(code, MethodSend, &[]).unwrap(),
+            serde_json::Value::Null
+        );
+        assert_eq!(
+            registry
+                .decode_json(code, miner::Method::ChangePeerID.into(), &[0x80])
+                .unwrap(),
+            serde_json::json!("80")
+        );
+        assert!(matches!(
+            registry.decode_json(&ACCOUNT_ACTOR_CODE_ID, 2, &[]),
+            Err(ParamsError::UnknownMethod { .. })
+        ));
+    }
+}
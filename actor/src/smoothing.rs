@@ -0,0 +1,255 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The alpha-beta filter estimates used for projecting the network reward and power,
+//! shared by the miner, market, power and reward actors.
+//!
+//! See specs-actors/actors/util/smoothing for details.
+
+use lazy_static::lazy_static;
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_bigint::math::{ln, PRECISION};
+use plum_bigint::num_integer::Integer;
+use plum_bigint::num_traits::Zero;
+use plum_bigint::{bigint_json, BigInt, BigIntRefWrapper, BigIntWrapper};
+use plum_sector::StoragePower;
+use plum_types::{ChainEpoch, TokenAmount};
+
+lazy_static! {
+    /// The default alpha of the filter in Q.128 format, ~= 9.25e-4.
+    pub static ref DEFAULT_ALPHA: BigInt = "314760000000000000000000000000000000".parse().unwrap();
+    /// The default beta of the filter in Q.128 format, ~= 2.84e-7.
+    pub static ref DEFAULT_BETA: BigInt = "96640100000000000000000000000000".parse().unwrap();
+    /// The velocity below which the denominator is treated as constant, ~= 8.88e-16 in Q.128 format.
+    static ref EPSILON: BigInt = "302231454903657293676544".parse().unwrap();
+}
+
+/// The estimate of the position and velocity of a value, both are in Q.128 format.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FilterEstimate {
+    /// The position in Q.128 format.
+    #[serde(with = "bigint_json")]
+    pub position: BigInt,
+    /// The velocity in Q.128 format.
+    #[serde(with = "bigint_json")]
+    pub velocity: BigInt,
+}
+
+impl FilterEstimate {
+    /// Create a new filter estimate with the Q.0 position and velocity.
+    pub fn new(position: BigInt, velocity: BigInt) -> Self {
+        Self {
+            position: position << PRECISION,
+            velocity: velocity << PRECISION,
+        }
+    }
+
+    /// Return the Q.0 position estimate.
+    pub fn estimate(&self) -> BigInt {
+        &self.position >> PRECISION
+    }
+
+    /// Extrapolate the position `delta` epochs in the future, the result is in Q.256 format.
+    pub fn extrapolate(&self, delta: ChainEpoch) -> BigInt {
+        let delta_t = BigInt::from(delta) << PRECISION;
+        let position = &self.position << PRECISION;
+        &self.velocity * delta_t + position
+    }
+}
+
+// Implement CBOR serialization for FilterEstimate.
+impl encode::Encode for FilterEstimate {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .encode(BigIntRefWrapper::from(&self.position))?
+            .encode(BigIntRefWrapper::from(&self.velocity))?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for FilterEstimate.
+impl<'b> decode::Decode<'b> for FilterEstimate {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of FilterEstimate",
+            ));
+        }
+        Ok(FilterEstimate {
+            position: d.decode::<BigIntWrapper>()?.into_inner(),
+            velocity: d.decode::<BigIntWrapper>()?.into_inner(),
+        })
+    }
+}
+
+/// The alpha-beta filter that produces the next estimate from the previous one and an observation.
+pub struct AlphaBetaFilter<'a> {
+    alpha: &'a BigInt,
+    beta: &'a BigInt,
+    prev_estimate: &'a FilterEstimate,
+}
+
+impl<'a> AlphaBetaFilter<'a> {
+    /// Load the filter with the previous estimate and the Q.128 alpha and beta.
+    pub fn load(prev_estimate: &'a FilterEstimate, alpha: &'a BigInt, beta: &'a BigInt) -> Self {
+        Self {
+            alpha,
+            beta,
+            prev_estimate,
+        }
+    }
+
+    /// Return the next estimate with the Q.0 `observation` made `epoch_delta` epochs
+    /// after the previous estimate.
+    pub fn next_estimate(&self, observation: &BigInt, epoch_delta: ChainEpoch) -> FilterEstimate {
+        let delta_t = BigInt::from(epoch_delta) << PRECISION;
+        let delta_x = (&delta_t * &self.prev_estimate.velocity) >> PRECISION;
+        let mut position = delta_x + &self.prev_estimate.position;
+
+        let observation = observation << PRECISION;
+        let residual = observation - &position;
+        position += (self.alpha * &residual) >> PRECISION;
+
+        let revision_v = (residual * self.beta).div_floor(&delta_t);
+        let velocity = revision_v + &self.prev_estimate.velocity;
+        FilterEstimate { position, velocity }
+    }
+}
+
+/// Extrapolate the cumulative sum of the ratio of two estimates over `[relative_start, relative_start + delta]`.
+///
+/// Output is in Q.128 format.
+pub fn extrapolated_cum_sum_of_ratio(
+    delta: ChainEpoch,
+    relative_start: ChainEpoch,
+    estimate_num: &FilterEstimate,
+    estimate_denom: &FilterEstimate,
+) -> BigInt {
+    let delta_t = BigInt::from(delta) << PRECISION;
+    let t0 = BigInt::from(relative_start) << PRECISION;
+
+    let position_1 = &estimate_num.position;
+    let velocity_1 = &estimate_num.velocity;
+    let position_2 = &estimate_denom.position;
+    let velocity_2 = &estimate_denom.velocity;
+
+    let squared_velocity_2 = (velocity_2 * velocity_2) >> PRECISION;
+
+    if squared_velocity_2 > *EPSILON {
+        // The denominator changes, integrate the ratio analytically with the logarithms.
+        let x2a = ((velocity_2 * &t0) >> PRECISION) + position_2;
+        let x2b = ((velocity_2 * &delta_t) >> PRECISION) + &x2a;
+        let x2a = ln(&x2a);
+        let x2b = ln(&x2b);
+
+        let m1 = ((&x2b - &x2a) * position_1 * velocity_2) >> PRECISION;
+        let m2 = (((x2a - x2b) * position_2 + velocity_2 * &delta_t) * velocity_1) >> PRECISION;
+        return (m2 + m1).div_floor(&squared_velocity_2);
+    }
+
+    // The denominator is constant, the ratio is linear.
+    let half_delta = &delta_t >> 1;
+    let x1m = ((velocity_1 * (t0 + half_delta)) >> PRECISION) + position_1;
+    (x1m * delta_t).div_floor(position_2)
+}
+
+/// The projected block reward a sector with `qa_sector_power` would earn over `projection_duration`.
+///
+/// BR(t) = ProjectedRewardFraction(t) * SectorQualityAdjustedPower, where ProjectedRewardFraction(t)
+/// is the sum of the estimated reward over the estimated network power in the projection period.
+pub fn expected_reward_for_power(
+    reward_estimate: &FilterEstimate,
+    network_qa_power_estimate: &FilterEstimate,
+    qa_sector_power: &StoragePower,
+    projection_duration: ChainEpoch,
+) -> TokenAmount {
+    let network_qa_power_smoothed = network_qa_power_estimate.estimate();
+    if network_qa_power_smoothed.is_zero() {
+        return reward_estimate.estimate();
+    }
+
+    let expected_reward_for_proving_period = extrapolated_cum_sum_of_ratio(
+        projection_duration,
+        0,
+        reward_estimate,
+        network_qa_power_estimate,
+    );
+    // Q.0 * Q.128 => Q.128
+    let br = qa_sector_power * expected_reward_for_proving_period;
+    std::cmp::max(br >> PRECISION, BigInt::zero())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_estimate_cbor() {
+        let estimate = FilterEstimate::new(BigInt::from(1_000_000u64), BigInt::from(-10));
+        assert_eq!(estimate.estimate(), BigInt::from(1_000_000u64));
+        let bytes = minicbor::to_vec(&estimate).unwrap();
+        assert_eq!(
+            minicbor::decode::<FilterEstimate>(&bytes).unwrap(),
+            estimate
+        );
+        assert_eq!(
+            estimate.extrapolate(100) >> (2 * PRECISION),
+            BigInt::from(999_000u64)
+        );
+    }
+
+    #[test]
+    fn test_next_estimate_rounding() {
+        let estimate = FilterEstimate {
+            position: "12340768897043811082913117521041414330876498465539749838848"
+                .parse()
+                .unwrap(),
+            velocity: "-37396269384748225153347462373739139597454335279104"
+                .parse()
+                .unwrap(),
+        };
+        let filter = AlphaBetaFilter::load(&estimate, &DEFAULT_ALPHA, &DEFAULT_BETA);
+        let next = filter.next_estimate(&BigInt::from(36_266_252_337_034_982_540u128), 3);
+        assert_eq!(
+            next.position.to_string(),
+            "12340768782449774548722755900999027209659079673176744001536"
+        );
+        assert_eq!(
+            next.velocity.to_string(),
+            "-37396515542149801792802995707072472930787668612438"
+        );
+    }
+
+    #[test]
+    fn test_expected_reward_for_power() {
+        let reward = FilterEstimate::new(
+            BigInt::from(36_266_252_337_034_982_540u128),
+            BigInt::from(-1000),
+        );
+        let power = FilterEstimate::new(BigInt::from(10u128 << 60), BigInt::from(10u64.pow(14)));
+        assert_eq!(
+            extrapolated_cum_sum_of_ratio(2880, 0, &reward, &power).to_string(),
+            "3044851780487039909443612163249716779657353"
+        );
+        assert_eq!(
+            expected_reward_for_power(&reward, &power, &BigInt::from(32u64 << 30), 2880),
+            BigInt::from(307_451_460_072_808u64)
+        );
+
+        // constant network power.
+        let power = FilterEstimate::new(BigInt::from(10u128 << 60), BigInt::from(0));
+        assert_eq!(
+            extrapolated_cum_sum_of_ratio(2880, 0, &reward, &power).to_string(),
+            "3082725620922602544555775298344527719301120"
+        );
+
+        // no network power, the whole reward is expected.
+        let power = FilterEstimate::default();
+        assert_eq!(
+            expected_reward_for_power(&reward, &power, &BigInt::from(32u64 << 30), 2880),
+            BigInt::from(36_266_252_337_034_982_540u128)
+        );
+    }
+}
@@ -115,6 +115,7 @@ mod impls {
 
     impl ChainApi for HttpTransport {}
     impl ClientApi for HttpTransport {}
+    impl GasApi for HttpTransport {}
     impl MarketApi for HttpTransport {}
     impl MinerApi for HttpTransport {}
     impl MpoolApi for HttpTransport {}
@@ -132,6 +133,7 @@ mod impls {
 
     impl ChainApi for WebSocketTransport {}
     impl ClientApi for WebSocketTransport {}
+    impl GasApi for WebSocketTransport {}
     impl MarketApi for WebSocketTransport {}
     impl MinerApi for WebSocketTransport {}
     impl MpoolApi for WebSocketTransport {}
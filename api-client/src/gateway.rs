@@ -0,0 +1,483 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The gateway, which relays the safe subset of the API of a full node to the public.
+//!
+//! Only the read-only methods and the untrusted push are relayed, the state queries are limited
+//! to the recent tipsets, and the requests and the connections of each IP are limited.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use parking_lot::Mutex;
+use serde_json::Value;
+
+use jsonrpc_client::{
+    Call, Error as RpcErrorObject, ErrorCode, MethodCall, Params, Request, Response, ResponseOutput,
+};
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+use crate::client::RpcClient;
+use crate::errors::ApiError;
+
+/// The prefix of the method names of the Filecoin API.
+pub const METHOD_PREFIX: &str = "Filecoin.";
+
+/// The methods relayed by the gateway and the number of tokens they cost.
+///
+/// The methods waiting for or searching the chain cost more than the plain queries.
+pub const GATEWAY_METHODS: &[(&str, u32)] = &[
+    ("Version", 1),
+    ("ChainHead", 1),
+    ("ChainGetBlock", 1),
+    ("ChainGetBlockMessages", 1),
+    ("ChainGetMessage", 1),
+    ("ChainGetTipSet", 1),
+    ("ChainGetTipSetByHeight", 2),
+    ("ChainHasObj", 1),
+    ("ChainReadObj", 1),
+    ("GasEstimateMessageGas", 5),
+    ("MpoolGetNonce", 1),
+    ("MpoolPushUntrusted", 5),
+    ("MpoolBatchPushUntrusted", 10),
+    ("StateAccountKey", 1),
+    ("StateGetActor", 1),
+    ("StateLookupID", 1),
+    ("StateMarketBalance", 1),
+    ("StateMarketStorageDeal", 1),
+    ("StateMinerInfo", 1),
+    ("StateMinerPower", 1),
+    ("StateMinerFaults", 1),
+    ("StateMinerRecoveries", 1),
+    ("StateMinerProvingDeadline", 1),
+    ("StateNetworkVersion", 1),
+    ("StateSearchMsg", 10),
+    ("StateWaitMsg", 10),
+    ("StateVerifiedClientStatus", 1),
+    ("WalletBalance", 1),
+];
+
+/// The methods whose last param is the key of the tipset at which the state is queried.
+const TIPSET_KEY_METHODS: &[&str] = &[
+    "ChainGetTipSet",
+    "ChainGetTipSetByHeight",
+    "GasEstimateMessageGas",
+    "StateAccountKey",
+    "StateGetActor",
+    "StateLookupID",
+    "StateMarketBalance",
+    "StateMarketStorageDeal",
+    "StateMinerInfo",
+    "StateMinerPower",
+    "StateMinerFaults",
+    "StateMinerRecoveries",
+    "StateMinerProvingDeadline",
+    "StateNetworkVersion",
+    "StateVerifiedClientStatus",
+];
+
+// The max number of the IPs tracked by the rate limiter before the idle ones are pruned.
+const MAX_TRACKED_IPS: usize = 4096;
+
+/// The default max number of epochs the state queries can look back, which is one day.
+pub const DEFAULT_MAX_LOOKBACK: ChainEpoch = 2880;
+/// The default number of tokens refilled per second for each IP.
+pub const DEFAULT_RATE_LIMIT: u32 = 20;
+/// The default max number of tokens of each IP, i.e. the burst.
+pub const DEFAULT_RATE_BURST: u32 = 100;
+/// The default max number of the connections.
+pub const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+/// The default max number of the connections of each IP.
+pub const DEFAULT_MAX_CONNECTIONS_PER_IP: usize = 16;
+
+/// The configuration of the gateway.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GatewayConfig {
+    /// The max number of epochs the state queries can look back from the head.
+    pub max_lookback: ChainEpoch,
+    /// The number of tokens refilled per second for each IP, each request costs the tokens
+    /// of the method in `GATEWAY_METHODS`.
+    pub rate_limit: u32,
+    /// The max number of tokens of each IP.
+    pub rate_burst: u32,
+    /// The max number of the connections.
+    pub max_connections: usize,
+    /// The max number of the connections of each IP.
+    pub max_connections_per_ip: usize,
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            max_lookback: DEFAULT_MAX_LOOKBACK,
+            rate_limit: DEFAULT_RATE_LIMIT,
+            rate_burst: DEFAULT_RATE_BURST,
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+        }
+    }
+}
+
+/// The errors of the gateway, which are returned to the callers as the RPC errors.
+#[derive(Debug, thiserror::Error)]
+pub enum GatewayError {
+    /// The method isn't relayed by the gateway.
+    #[error("method {0} is not available through the gateway")]
+    MethodNotAllowed(String),
+    /// The params of the method are malformed.
+    #[error("invalid params: {0}")]
+    InvalidParams(String),
+    /// The IP sends too many requests.
+    #[error("too many requests from {0}")]
+    RateLimited(IpAddr),
+    /// The gateway or the IP has too many connections.
+    #[error("too many connections")]
+    TooManyConnections,
+    /// The queried tipset is older than the max lookback.
+    #[error("tipset at height {height} is older than the lookback limit {limit}")]
+    LookbackExceeded {
+        /// The height of the queried tipset.
+        height: ChainEpoch,
+        /// The min height that can be queried.
+        limit: ChainEpoch,
+    },
+    /// The upstream node returns an error.
+    #[error("{0}")]
+    Upstream(#[from] ApiError),
+}
+
+impl From<serde_json::Error> for GatewayError {
+    fn from(err: serde_json::Error) -> Self {
+        GatewayError::InvalidParams(err.to_string())
+    }
+}
+
+impl From<GatewayError> for RpcErrorObject {
+    fn from(err: GatewayError) -> Self {
+        let code = match &err {
+            GatewayError::MethodNotAllowed(_) => ErrorCode::MethodNotFound,
+            GatewayError::InvalidParams(_) | GatewayError::LookbackExceeded { .. } => {
+                ErrorCode::InvalidParams
+            }
+            GatewayError::Upstream(ApiError::RpcResponse(err)) => return err.clone(),
+            _ => ErrorCode::ServerError(-32000),
+        };
+        RpcErrorObject {
+            code,
+            message: err.to_string(),
+            data: None,
+        }
+    }
+}
+
+/// Return the number of tokens the method costs, `None` if the method isn't relayed.
+pub fn method_cost(method: &str) -> Option<u32> {
+    let method = method.trim_start_matches(METHOD_PREFIX);
+    GATEWAY_METHODS
+        .iter()
+        .find(|(name, _)| *name == method)
+        .map(|(_, cost)| *cost)
+}
+
+#[derive(Clone, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// The token bucket rate limiter of the IPs.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: f64,
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Create a limiter refilling `rate` tokens per second up to `burst` tokens for each IP.
+    pub fn new(rate: u32, burst: u32) -> Self {
+        Self {
+            rate: f64::from(rate),
+            burst: f64::from(burst),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take the `cost` tokens of the IP, return false if the IP doesn't have enough tokens.
+    pub fn check(&self, ip: IpAddr, cost: u32) -> bool {
+        self.check_at(ip, cost, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, cost: u32, now: Instant) -> bool {
+        let mut buckets = self.buckets.lock();
+        let (rate, burst) = (self.rate, self.burst);
+        // Forget the IPs whose buckets are full, which are the same as the new ones.
+        if buckets.len() >= MAX_TRACKED_IPS {
+            buckets.retain(|_, bucket| Self::refill(bucket, rate, burst, now) < burst);
+        }
+        let bucket = buckets.entry(ip).or_insert_with(|| Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        let tokens = Self::refill(bucket, rate, burst, now);
+        if tokens < f64::from(cost) {
+            return false;
+        }
+        bucket.tokens = tokens - f64::from(cost);
+        true
+    }
+
+    fn refill(bucket: &mut Bucket, rate: f64, burst: f64, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * rate).min(burst);
+        bucket.updated_at = now;
+        bucket.tokens
+    }
+}
+
+#[derive(Debug, Default)]
+struct Connections {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+/// The connection accepted by the gateway, the connection is released when it's dropped.
+#[derive(Debug)]
+pub struct ConnectionGuard {
+    ip: IpAddr,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl ConnectionGuard {
+    /// Return the IP of the connection.
+    pub fn ip(&self) -> IpAddr {
+        self.ip
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock();
+        connections.total -= 1;
+        if let Some(count) = connections.per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+/// The gateway relaying the requests to the upstream full node.
+pub struct Gateway<C> {
+    upstream: C,
+    config: GatewayConfig,
+    limiter: RateLimiter,
+    connections: Arc<Mutex<Connections>>,
+}
+
+impl<C: RpcClient> Gateway<C> {
+    /// Create a gateway relaying the requests to the `upstream`.
+    pub fn new(upstream: C, config: GatewayConfig) -> Self {
+        Self {
+            upstream,
+            limiter: RateLimiter::new(config.rate_limit, config.rate_burst),
+            config,
+            connections: Arc::new(Mutex::new(Connections::default())),
+        }
+    }
+
+    /// Return the configuration of the gateway.
+    pub fn config(&self) -> &GatewayConfig {
+        &self.config
+    }
+
+    /// Accept a connection from the IP, an error is returned if there are too many connections.
+    pub fn connect(&self, ip: IpAddr) -> Result<ConnectionGuard, GatewayError> {
+        let mut connections = self.connections.lock();
+        let per_ip = connections.per_ip.get(&ip).copied().unwrap_or_default();
+        if connections.total >= self.config.max_connections
+            || per_ip >= self.config.max_connections_per_ip
+        {
+            return Err(GatewayError::TooManyConnections);
+        }
+        connections.total += 1;
+        connections.per_ip.insert(ip, per_ip + 1);
+        Ok(ConnectionGuard {
+            ip,
+            connections: self.connections.clone(),
+        })
+    }
+
+    /// Handle the JSON-RPC request body of the connection, return the response body,
+    /// which is empty if the request only contains the notifications.
+    pub async fn handle_body(&self, conn: &ConnectionGuard, body: &[u8]) -> Vec<u8> {
+        let response = match serde_json::from_slice::<Request>(body) {
+            Ok(Request::Single(Call::MethodCall(call))) => {
+                Response::Single(self.handle(conn, call).await)
+            }
+            Ok(Request::Single(Call::Notification(_))) => return vec![],
+            Ok(Request::Batch(calls)) => {
+                let mut outputs = Vec::with_capacity(calls.len());
+                for call in calls {
+                    if let Call::MethodCall(call) = call {
+                        outputs.push(self.handle(conn, call).await);
+                    }
+                }
+                if outputs.is_empty() {
+                    return vec![];
+                }
+                Response::Batch(outputs)
+            }
+            Err(_) => Response::Single(ResponseOutput::from(
+                None,
+                0,
+                Err(RpcErrorObject::parse_error()),
+            )),
+        };
+        serde_json::to_vec(&response).expect("JSON serialization of Response shouldn't be failed")
+    }
+
+    /// Handle the method call of the connection, return the response to the caller.
+    pub async fn handle(&self, conn: &ConnectionGuard, call: MethodCall) -> ResponseOutput {
+        let result = self
+            .call(conn.ip(), &call.method, call.params)
+            .await
+            .map_err(RpcErrorObject::from);
+        ResponseOutput::from(call.jsonrpc, call.id, result)
+    }
+
+    /// Check the method call from the IP and relay it to the upstream node.
+    pub async fn call(
+        &self,
+        ip: IpAddr,
+        method: &str,
+        params: Params,
+    ) -> Result<Value, GatewayError> {
+        let method = method.trim_start_matches(METHOD_PREFIX);
+        let cost =
+            method_cost(method).ok_or_else(|| GatewayError::MethodNotAllowed(method.into()))?;
+        if !self.limiter.check(ip, cost) {
+            return Err(GatewayError::RateLimited(ip));
+        }
+        let params = match params {
+            Params::Array(params) => params,
+            Params::None => vec![],
+            Params::Map(_) => {
+                return Err(GatewayError::InvalidParams(
+                    "params must be an array".into(),
+                ))
+            }
+        };
+        if TIPSET_KEY_METHODS.contains(&method) {
+            self.check_lookback(method, &params).await?;
+        }
+        Ok(self.upstream.request(method, params).await?)
+    }
+
+    async fn check_lookback(&self, method: &str, params: &[Value]) -> Result<(), GatewayError> {
+        let key = match params.last() {
+            Some(key) => serde_json::from_value::<Option<TipsetKey>>(key.clone())
+                .map_err(|err| GatewayError::InvalidParams(err.to_string()))?,
+            None => return Err(GatewayError::InvalidParams("missing tipset key".into())),
+        };
+        let head: Tipset = self.upstream.request("ChainHead", vec![]).await?;
+        let limit = head.height() - self.config.max_lookback;
+        let height = match key {
+            Some(key) if !key.is_empty() => {
+                let tipset: Tipset = self
+                    .upstream
+                    .request("ChainGetTipSet", vec![serde_json::to_value(&key)?])
+                    .await?;
+                tipset.height()
+            }
+            _ => head.height(),
+        };
+        check_lookback(height, limit)?;
+        if method == "ChainGetTipSetByHeight" {
+            let height = params
+                .first()
+                .and_then(Value::as_i64)
+                .ok_or_else(|| GatewayError::InvalidParams("invalid height".into()))?;
+            check_lookback(height, limit)?;
+        }
+        Ok(())
+    }
+}
+
+fn check_lookback(height: ChainEpoch, limit: ChainEpoch) -> Result<(), GatewayError> {
+    if height < limit {
+        Err(GatewayError::LookbackExceeded { height, limit })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_method_cost() {
+        assert_eq!(method_cost("Filecoin.ChainHead"), Some(1));
+        assert_eq!(method_cost("MpoolPushUntrusted"), Some(5));
+        assert_eq!(method_cost("MpoolPush"), None);
+        assert_eq!(method_cost("WalletSign"), None);
+        assert_eq!(method_cost("AuthNew"), None);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(10, 20);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let other = IpAddr::from([127, 0, 0, 2]);
+        let start = Instant::now();
+        assert!(limiter.check_at(ip, 15, start));
+        assert!(!limiter.check_at(ip, 10, start));
+        // The buckets of the IPs are independent.
+        assert!(limiter.check_at(other, 20, start));
+        // 5 tokens are refilled in half a second.
+        let later = start + Duration::from_millis(500);
+        assert!(limiter.check_at(ip, 10, later));
+        assert!(!limiter.check_at(ip, 1, later));
+        // The tokens are capped by the burst.
+        let much_later = start + Duration::from_secs(60);
+        assert!(!limiter.check_at(ip, 21, much_later));
+        assert!(limiter.check_at(ip, 20, much_later));
+    }
+
+    #[test]
+    fn test_connection_limits() {
+        let config = GatewayConfig {
+            max_connections: 3,
+            max_connections_per_ip: 2,
+            ..Default::default()
+        };
+        let gateway = Gateway::new(crate::HttpTransport::new("http://127.0.0.1:1234"), config);
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let other = IpAddr::from([127, 0, 0, 2]);
+        let first = gateway.connect(ip).unwrap();
+        let _second = gateway.connect(ip).unwrap();
+        assert!(gateway.connect(ip).is_err());
+        let _third = gateway.connect(other).unwrap();
+        assert!(gateway.connect(other).is_err());
+        drop(first);
+        assert!(gateway.connect(ip).is_ok());
+    }
+
+    #[test]
+    fn test_check_lookback() {
+        assert!(check_lookback(100, 100).is_ok());
+        match check_lookback(99, 100) {
+            Err(GatewayError::LookbackExceeded {
+                height: 99,
+                limit: 100,
+            }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
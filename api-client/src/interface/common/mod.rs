@@ -4,6 +4,8 @@ mod types;
 
 pub use self::types::*;
 
+use std::collections::HashMap;
+
 use libp2p_core::PeerId;
 
 use plum_bytes::Bytes;
@@ -29,6 +31,15 @@ pub trait CommonApi: RpcClient {
         Ok(bytes.into_inner())
     }
 
+    async fn auth_list(&self) -> Result<Vec<TokenInfo>> {
+        self.request("AuthList", vec![]).await
+    }
+
+    async fn auth_revoke(&self, id: &str) -> Result<bool> {
+        self.request("AuthRevoke", vec![helper::serialize(&id)])
+            .await
+    }
+
     async fn net_connectedness(&self, peer_id: &PeerId) -> Result<Connectedness> {
         self.request(
             "NetConnectedness",
@@ -41,6 +52,14 @@ pub trait CommonApi: RpcClient {
         self.request("NetPeers", vec![]).await
     }
 
+    async fn net_peer_info(&self, peer_id: &PeerId) -> Result<ExtendedPeerInfo> {
+        self.request(
+            "NetPeerInfo",
+            vec![helper::serialize(&PeerIdRefWrapper::from(peer_id))],
+        )
+        .await
+    }
+
     async fn net_connect(&self, addr_info: &PeerAddrInfo) -> Result<()> {
         self.request("NetConnect", vec![helper::serialize(addr_info)])
             .await
@@ -66,6 +85,21 @@ pub trait CommonApi: RpcClient {
         .await
     }
 
+    // reports the bandwidth usage of all the traffic.
+    async fn net_bandwidth_stats(&self) -> Result<BandwidthStats> {
+        self.request("NetBandwidthStats", vec![]).await
+    }
+
+    // reports the bandwidth usage per peer, keyed by the peer id.
+    async fn net_bandwidth_stats_by_peer(&self) -> Result<HashMap<String, BandwidthStats>> {
+        self.request("NetBandwidthStatsByPeer", vec![]).await
+    }
+
+    // reports the bandwidth usage per protocol, keyed by the protocol id.
+    async fn net_bandwidth_stats_by_protocol(&self) -> Result<HashMap<String, BandwidthStats>> {
+        self.request("NetBandwidthStatsByProtocol", vec![]).await
+    }
+
     // returns peer id of libp2p node backing this API.
     async fn id(&self) -> Result<PeerId> {
         let peer_id: PeerIdWrapper = self.request("ID", vec![]).await?;
@@ -77,6 +111,11 @@ pub trait CommonApi: RpcClient {
         self.request("Version", vec![]).await
     }
 
+    // reports the status of the node, e.g. the disk usage of the repo.
+    async fn node_status(&self) -> Result<NodeStatus> {
+        self.request("NodeStatus", vec![]).await
+    }
+
     async fn log_list(&self) -> Result<Vec<String>> {
         self.request("LogList", vec![]).await
     }
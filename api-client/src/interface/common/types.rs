@@ -6,36 +6,7 @@ use libp2p_core::{Multiaddr, PeerId};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-/// The permission of API.
-#[derive(Eq, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum Permission {
-    /// Read-only permission
-    Read,
-    /// Write permission
-    Write,
-    /// Use wallet keys for signing
-    Sign,
-    /// Manage permissions
-    Admin,
-}
-
-impl Default for Permission {
-    fn default() -> Self {
-        Permission::Read
-    }
-}
-
-impl fmt::Display for Permission {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Permission::Read => f.write_str("read"),
-            Permission::Write => f.write_str("write"),
-            Permission::Sign => f.write_str("sign"),
-            Permission::Admin => f.write_str("admin"),
-        }
-    }
-}
+pub use plum_wallet::{Permission, TokenInfo};
 
 /// Connectedness signals the capacity for a connection with a given node.
 /// It is used to signal to services and other peers whether a node is reachable.
@@ -65,6 +36,22 @@ pub struct PeerAddrInfo {
     pub addrs: Vec<Multiaddr>,
 }
 
+/// ExtendedPeerInfo is the detailed information of a peer, including the identify information.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExtendedPeerInfo {
+    /// peer ID.
+    #[serde(rename = "ID")]
+    #[serde(with = "plum_peerid")]
+    pub id: PeerId,
+    /// The agent version of the peer.
+    pub agent: String,
+    /// The known addresses of the peer.
+    pub addrs: Vec<String>,
+    /// The protocols supported by the peer.
+    pub protocols: Vec<String>,
+}
+
 /// Version provides various build-time information.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -84,6 +71,31 @@ impl fmt::Display for Version {
     }
 }
 
+/// NodeStatus reports the status of the node.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NodeStatus {
+    /// The space used by the repo datastore, in bytes.
+    pub disk_usage: u64,
+    /// The number of the connected peers.
+    pub peers: u64,
+}
+
+/// BandwidthStats reports the bandwidth usage, the totals are in bytes and the rates are in
+/// bytes per second.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BandwidthStats {
+    /// The total number of the received bytes.
+    pub total_in: i64,
+    /// The total number of the sent bytes.
+    pub total_out: i64,
+    /// The rate of receiving.
+    pub rate_in: f64,
+    /// The rate of sending.
+    pub rate_out: f64,
+}
+
 /// BuildVersion is the local build version, set by build system
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct BuildVersion(u32);
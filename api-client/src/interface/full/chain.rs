@@ -11,7 +11,8 @@ use plum_bytes::{Bytes, BytesRef};
 use plum_crypto::DomainSeparationTag;
 use plum_message::{MessageReceipt, SignedMessage, UnsignedMessage};
 use plum_tipset::{Tipset, TipsetKey};
-use plum_types::{ChainEpoch, Randomness};
+use plum_types::{ActorId, ChainEpoch, Randomness};
+use plum_vm::{EventEntry, EventFilter};
 
 use crate::client::RpcClient;
 use crate::errors::Result;
@@ -151,6 +152,11 @@ pub trait ChainApi: RpcClient {
         self.subscribe("ChainExport", vec![helper::serialize(key)])
             .await
     }
+
+    async fn get_actor_events(&self, filter: &EventFilter) -> Result<Vec<ActorEventResult>> {
+        self.request("GetActorEvents", vec![helper::serialize(filter)])
+            .await
+    }
 }
 
 #[doc(hidden)]
@@ -205,3 +211,16 @@ pub struct ObjStat {
     pub size: u64,
     pub links: u64,
 }
+
+// Only For get_actor_events
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorEventResult {
+    pub entries: Vec<EventEntry>,
+    pub emitter: ActorId,
+    pub reverted: bool,
+    pub height: ChainEpoch,
+    pub tip_set_key: TipsetKey,
+    pub msg_cid: Cid,
+}
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 use cid::Cid;
 use plum_address::Address;
 use plum_bigint::{bigint_json, BigInt};
+use plum_markets::storage::SignedStorageAsk;
+use plum_peerid::PeerIdRefWrapper;
 use plum_piece::UnpaddedPieceSize;
 // use plum_types::{ChainEpoch, DealId};
 
@@ -59,12 +61,11 @@ pub trait ClientApi: RpcClient {
         .await
     }
 
-    /*
     async fn client_query_ask(
         &self,
         peer_id: &PeerId,
         miner: &Address,
-    ) -> Result<storagemarket::SignedStorageAsk> {
+    ) -> Result<SignedStorageAsk> {
         self.request(
             "ClientQueryAsk",
             vec![
@@ -74,7 +75,6 @@ pub trait ClientApi: RpcClient {
         )
         .await
     }
-    */
 
     async fn client_calc_comm_p(&self, inpath: &str, miner: &Address) -> Result<CommPRet> {
         self.request(
@@ -0,0 +1,36 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use plum_bigint::{BigInt, BigIntWrapper};
+use plum_message::UnsignedMessage;
+use plum_tipset::TipsetKey;
+
+use crate::client::RpcClient;
+use crate::errors::Result;
+use crate::helper;
+
+/// MethodGroup: Gas.
+/// The Gas methods are for estimating the gas parameters of the messages.
+#[doc(hidden)]
+#[async_trait::async_trait]
+pub trait GasApi: RpcClient {
+    /// Estimate the fee cap that keeps the message includable for `max_queue_blocks` epochs,
+    /// projected from the base fee of the tipset.
+    async fn gas_estimate_fee_cap(
+        &self,
+        msg: &UnsignedMessage,
+        max_queue_blocks: i64,
+        key: &TipsetKey,
+    ) -> Result<BigInt> {
+        let fee_cap: BigIntWrapper = self
+            .request(
+                "GasEstimateFeeCap",
+                vec![
+                    helper::serialize(msg),
+                    helper::serialize(&max_queue_blocks),
+                    helper::serialize(key),
+                ],
+            )
+            .await?;
+        Ok(fee_cap.into_inner())
+    }
+}
@@ -2,6 +2,7 @@
 
 mod chain;
 mod client;
+mod gas;
 mod market;
 mod miner;
 mod mpool;
@@ -13,6 +14,7 @@ mod wallet;
 
 pub use self::chain::*;
 pub use self::client::*;
+pub use self::gas::*;
 pub use self::market::*;
 pub use self::miner::*;
 pub use self::mpool::*;
@@ -31,6 +33,7 @@ pub trait FullNodeApi:
     + StateApi
     + MinerApi
     + MpoolApi
+    + GasApi
     + MarketApi
     + ChainApi
     + MultiSigApi
@@ -41,6 +44,6 @@ pub trait FullNodeApi:
 
 // The priority of implementation: (1 => 2 => 3 => 4)
 // 1. Common, Sync, Wallet,
-// 2. State, Mpool, Market, Chain, MultiSigApi
+// 2. State, Mpool, Gas, Market, Chain, MultiSigApi
 // 3, Paych, StorageMiner
 // 4. Client
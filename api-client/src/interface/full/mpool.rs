@@ -14,6 +14,24 @@ use jsonrpc_client::{NotificationStream, SubscriptionId};
 use crate::client::RpcClient;
 use crate::errors::Result;
 use crate::helper;
+use crate::interface::common::Permission;
+
+/// The permissions required by the Mpool methods, the same as Lotus.
+///
+/// The trusted push skips the stateful validation of the messages, so only the untrusted push
+/// should be relayed for the third parties, e.g. by the gateway.
+pub const MPOOL_PERMISSIONS: &[(&str, Permission)] = &[
+    ("MpoolPending", Permission::Read),
+    ("MpoolGetNonce", Permission::Read),
+    ("MpoolSub", Permission::Read),
+    ("MpoolEstimateGasPrice", Permission::Read),
+    ("MpoolPush", Permission::Write),
+    ("MpoolPushUntrusted", Permission::Write),
+    ("MpoolBatchPush", Permission::Write),
+    ("MpoolBatchPushUntrusted", Permission::Write),
+    ("MpoolPushMessage", Permission::Sign),
+    ("MpoolBatchPushMessage", Permission::Sign),
+];
 
 /// MethodGroup: Mpool.
 /// The Mpool methods are for interacting with the message pool.
@@ -31,12 +49,39 @@ pub trait MpoolApi: RpcClient {
             .await
     }
 
+    // push the message without the trust of the node, which is checked against the head state
+    async fn mpool_push_untrusted(&self, signed_msg: &SignedMessage) -> Result<Cid> {
+        self.request("MpoolPushUntrusted", vec![helper::serialize(signed_msg)])
+            .await
+    }
+
+    async fn mpool_batch_push(&self, signed_msgs: &[SignedMessage]) -> Result<Vec<Cid>> {
+        self.request("MpoolBatchPush", vec![helper::serialize(&signed_msgs)])
+            .await
+    }
+
+    async fn mpool_batch_push_untrusted(&self, signed_msgs: &[SignedMessage]) -> Result<Vec<Cid>> {
+        self.request(
+            "MpoolBatchPushUntrusted",
+            vec![helper::serialize(&signed_msgs)],
+        )
+        .await
+    }
+
     // get nonce, sign, push
     async fn mpool_push_message(&self, msg: &UnsignedMessage) -> Result<SignedMessage> {
         self.request("MpoolPushMessage", vec![helper::serialize(msg)])
             .await
     }
 
+    async fn mpool_batch_push_message(
+        &self,
+        msgs: &[UnsignedMessage],
+    ) -> Result<Vec<SignedMessage>> {
+        self.request("MpoolBatchPushMessage", vec![helper::serialize(&msgs)])
+            .await
+    }
+
     async fn mpool_get_nonce(&self, addr: &Address) -> Result<u64> {
         self.request("MpoolGetNonce", vec![helper::serialize(addr)])
             .await
@@ -156,11 +156,13 @@ pub trait StateApi: RpcClient {
     }
 
     async fn state_miner_faults(&self, addr: &Address, key: &TipsetKey) -> Result<BitField> {
-        self.request(
-            "StateMinerFaults",
-            vec![helper::serialize(addr), helper::serialize(key)],
-        )
-        .await
+        let runs: BitFieldRuns = self
+            .request(
+                "StateMinerFaults",
+                vec![helper::serialize(addr), helper::serialize(key)],
+            )
+            .await?;
+        Ok(runs.0)
     }
 
     // Returns all non-expired Faults that occur within lookback epochs of the given tipset
@@ -177,11 +179,13 @@ pub trait StateApi: RpcClient {
     }
 
     async fn state_miner_recoveries(&self, addr: &Address, key: &TipsetKey) -> Result<BitField> {
-        self.request(
-            "StateMinerRecoveries",
-            vec![helper::serialize(addr), helper::serialize(key)],
-        )
-        .await
+        let runs: BitFieldRuns = self
+            .request(
+                "StateMinerRecoveries",
+                vec![helper::serialize(addr), helper::serialize(key)],
+            )
+            .await?;
+        Ok(runs.0)
     }
 
     async fn state_miner_initial_pledge_collateral(
@@ -382,6 +386,10 @@ pub struct MinerPower {
     pub total_power: power::Claim,
 }
 
+// The bitfield returned by the RPC, which is serialized as the run lengths.
+#[derive(Deserialize)]
+struct BitFieldRuns(#[serde(with = "plum_bitfield::bitfield_json")] BitField);
+
 ///
 #[doc(hidden)]
 #[derive(Clone, Debug, Serialize, Deserialize)]
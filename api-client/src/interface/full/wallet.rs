@@ -1,10 +1,14 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use serde::{Deserialize, Serialize};
+
+use cid::Cid;
 use plum_address::Address;
 use plum_bigint::{BigInt, BigIntWrapper};
 use plum_bytes::BytesRef;
 use plum_crypto::{Signature, SignatureType};
-use plum_message::{SignedMessage, UnsignedMessage};
+use plum_message::{MessageReceipt, SignedMessage, UnsignedMessage};
+use plum_types::ChainEpoch;
 use plum_wallet::KeyInfo;
 
 use crate::client::RpcClient;
@@ -47,6 +51,13 @@ pub trait WalletApi: RpcClient {
         .await
     }
 
+    /// Sign the arbitrary data prefixed with `plum_wallet::SIGN_DATA_DOMAIN_TAG`,
+    /// use `wallet_sign` to sign the raw data.
+    async fn wallet_sign_data(&self, addr: &Address, data: &[u8]) -> Result<Signature> {
+        self.wallet_sign(addr, &plum_wallet::tagged_data(data))
+            .await
+    }
+
     async fn wallet_sign_message(
         &self,
         addr: &Address,
@@ -76,6 +87,17 @@ pub trait WalletApi: RpcClient {
         .await
     }
 
+    /// Verify the signature of the arbitrary data signed by `wallet_sign_data`.
+    async fn wallet_verify_data(
+        &self,
+        addr: &Address,
+        data: &[u8],
+        signature: &Signature,
+    ) -> Result<bool> {
+        self.wallet_verify(addr, &plum_wallet::tagged_data(data), signature)
+            .await
+    }
+
     async fn wallet_default_address(&self) -> Result<Address> {
         self.request("WalletDefaultAddress", vec![]).await
     }
@@ -94,4 +116,25 @@ pub trait WalletApi: RpcClient {
         self.request("WalletImport", vec![helper::serialize(info)])
             .await
     }
+
+    /// Return at most `limit` executed messages sent or received by the address from the
+    /// message index of the node, the latest first.
+    async fn wallet_history(&self, addr: &Address, limit: u64) -> Result<Vec<WalletHistoryEntry>> {
+        self.request(
+            "WalletHistory",
+            vec![helper::serialize(addr), helper::serialize(&limit)],
+        )
+        .await
+    }
+}
+
+///
+#[doc(hidden)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WalletHistoryEntry {
+    pub cid: Cid,
+    pub message: UnsignedMessage,
+    pub receipt: MessageReceipt,
+    pub epoch: ChainEpoch,
 }
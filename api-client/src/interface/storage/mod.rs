@@ -9,7 +9,9 @@ use std::collections::HashMap;
 use cid::Cid;
 use plum_address::Address;
 use plum_bigint::{BigInt, BigIntRefWrapper};
+use plum_markets::{fsm::StateRecord, retrieval, storage};
 use plum_sector::{SectorNumber, SectorSize};
+use plum_sector_storage::SchedulerStatus;
 use plum_tipset::Tipset;
 
 use crate::client::RpcClient;
@@ -79,6 +81,19 @@ pub trait StorageMinerApi: RpcClient {
         self.request("WorkerConnect", vec![helper::serialize(&s)])
             .await
     }
+
+    /// Get the queued and running seal tasks of the scheduler, with the resources in use.
+    async fn sealing_sched_diag(&self) -> Result<SchedulerStatus> {
+        self.request("SealingSchedDiag", vec![]).await
+    }
+
+    /// Switch the precompute mode of the scheduler, in which only the vanilla proofs of
+    /// the window PoSt are precomputed.
+    async fn sealing_set_precompute(&self, precompute: bool) -> Result<()> {
+        self.request("SealingSetPrecompute", vec![helper::serialize(&precompute)])
+            .await
+    }
+
     /*
     async fn worker_stats(&self) -> Result<HashMap<u64, storiface::WorkerStats>> {
         self.request("WorkStats", vec![]).await
@@ -101,6 +116,29 @@ pub trait StorageMinerApi: RpcClient {
         self.request("MarketListIncompleteDeals", vec![]).await
     }
     */
+    /// List the storage deals that have encountered an error or haven't been updated
+    /// for `threshold` seconds, with the last error per deal.
+    async fn market_list_stuck_deals(
+        &self,
+        threshold: u64,
+    ) -> Result<Vec<StateRecord<storage::ProviderDealState>>> {
+        self.request("MarketListStuckDeals", vec![helper::serialize(&threshold)])
+            .await
+    }
+
+    /// List the retrieval deals that have encountered an error or haven't been updated
+    /// for `threshold` seconds, with the last error per deal.
+    async fn market_list_stuck_retrieval_deals(
+        &self,
+        threshold: u64,
+    ) -> Result<Vec<StateRecord<retrieval::ProviderDealState>>> {
+        self.request(
+            "MarketListStuckRetrievalDeals",
+            vec![helper::serialize(&threshold)],
+        )
+        .await
+    }
+
     async fn market_set_price(&self, price: &BigInt) -> Result<()> {
         self.request(
             "MarketSetPrice",
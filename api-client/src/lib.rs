@@ -6,9 +6,15 @@
 
 mod client;
 mod errors;
+mod gateway;
 mod helper;
 mod interface;
 
 pub use self::client::{HttpTransport, WebSocketTransport};
 pub use self::errors::{ApiError, Result};
+pub use self::gateway::{
+    method_cost, ConnectionGuard, Gateway, GatewayConfig, GatewayError, RateLimiter,
+    DEFAULT_MAX_CONNECTIONS, DEFAULT_MAX_CONNECTIONS_PER_IP, DEFAULT_MAX_LOOKBACK,
+    DEFAULT_RATE_BURST, DEFAULT_RATE_LIMIT, GATEWAY_METHODS, METHOD_PREFIX,
+};
 pub use self::interface::*;
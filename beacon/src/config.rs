@@ -1,5 +1,8 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::path::PathBuf;
+use std::time::Duration;
+
 use bls::{PublicKey, Serialize};
 
 use plum_hash::H256;
@@ -30,15 +33,29 @@ impl DrandNetwork {
     }
 }
 
+/// The default timeout of a request to a Drand server.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+/// The default max number of the attempts of fetching an entry, across all the servers.
+pub const DEFAULT_MAX_ATTEMPTS: usize = 6;
+
 /// The configuration of the Drand network.
 #[doc(hidden)]
 #[derive(Clone, Debug)]
 pub struct DrandConfig {
-    /// HTTP servers addresses.
+    /// HTTP servers addresses, which may include a local caching proxy of the Drand relays.
+    /// The server with fewer consecutive failures is preferred, in the order of the list.
     #[cfg(not(feature = "grpc"))]
-    pub servers: Vec<&'static str>,
+    pub servers: Vec<String>,
     pub relays: Vec<&'static str>,
     pub chain_info: DrandChainInfo,
+    /// The timeout of a request to a server.
+    pub request_timeout: Duration,
+    /// The max number of the attempts of fetching an entry, the next healthiest server
+    /// is tried after a failed attempt.
+    pub max_attempts: usize,
+    /// The file storing the verified historical entries, so that they are not fetched again
+    /// after restart.
+    pub cache_path: Option<PathBuf>,
 }
 
 /// The information of the Drand chain.
@@ -56,11 +73,11 @@ impl DrandConfig {
     /// Return the config of the Drand main network.
     pub fn mainnet() -> Self {
         Self {
-            servers: vec![
+            servers: servers(&[
                 "https://api.drand.sh",
                 "https://api2.drand.sh",
                 "https://api3.drand.sh",
-            ],
+            ]),
             relays: vec![
                 "/dnsaddr/api.drand.sh/",
                 "/dnsaddr/api2.drand.sh/",
@@ -72,18 +89,21 @@ impl DrandConfig {
                 genesis_time: 1595431050,
                 hash: "8990e7a9aaed2ffed73dbd7092123d6f289930540d7651336225dc172e51b2ce".parse().unwrap(),
                 group_hash: "176f93498eac9ca337150b46d21dd58673ea4e3581185f869672e59fa4cb390a".parse().unwrap(),
-            }
+            },
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            cache_path: None,
         }
     }
 
     /// Return the config of the Drand test network.
     pub fn testnet() -> Self {
         Self {
-            servers: vec![
+            servers: servers(&[
                 "https://pl-eu.testnet.drand.sh",
                 "https://pl-us.testnet.drand.sh",
                 "https://pl-sin.testnet.drand.sh",
-            ],
+            ]),
             relays: vec![
                 "/dnsaddr/pl-eu.testnet.drand.sh/",
                 "/dnsaddr/pl-us.testnet.drand.sh/",
@@ -95,17 +115,20 @@ impl DrandConfig {
                 genesis_time: 1590445175,
                 hash: "84b2234fb34e835dccd048255d7ad3194b81af7d978c3bf157e3469592ae4e02".parse().unwrap(),
                 group_hash: "4dd408e5fdff9323c76a9b6f087ba8fdc5a6da907bd9217d9d10f2287d081957".parse().unwrap(),
-            }
+            },
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            cache_path: None,
         }
     }
 
     /// Return the config of the Drand develop network.
     pub fn devnet() -> Self {
         Self {
-            servers: vec![
+            servers: servers(&[
                 "https://dev1.drand.sh",
                 "https://dev2.drand.sh",
-            ],
+            ]),
             relays: vec![
                 "/dnsaddr/dev1.drand.sh/",
                 "/dnsaddr/dev2.drand.sh/",
@@ -116,11 +139,18 @@ impl DrandConfig {
                 genesis_time: 1595348225,
                 hash: "e73b7dc3c4f6a236378220c0dd6aa110eb16eed26c11259606e07ee122838d4f".parse().unwrap(),
                 group_hash: "567d4785122a5a3e75a9bc9911d7ea807dd85ff76b78dc4ff06b075712898607".parse().unwrap(),
-            }
+            },
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            cache_path: None,
         }
     }
 }
 
+fn servers(servers: &[&str]) -> Vec<String> {
+    servers.iter().map(|server| server.to_string()).collect()
+}
+
 fn pubkey(s: &str) -> PublicKey {
     let raw = hex::decode(s).unwrap();
     PublicKey::from_bytes(&raw).unwrap()
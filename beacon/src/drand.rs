@@ -1,8 +1,12 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bls::{PublicKey, Serialize};
 use parking_lot::Mutex;
 use reqwest::{Client, ClientBuilder};
@@ -30,6 +34,10 @@ pub trait RandomBeacon {
     fn max_beacon_round_for_epoch(&self, fil_epoch: ChainEpoch) -> u64;
 }
 
+/// The random beacon shared by the components, e.g. the miner and the chain syncer,
+/// which may be a `DrandBeacon` or a `MockBeacon` in the tests and the devnet.
+pub type SharedBeacon = Arc<dyn RandomBeacon + Send + Sync>;
+
 /// DrandBeacon connects Lotus with a drand network in order to provide
 /// randomness to the system in a way that's aligned with Filecoin rounds/epochs.
 ///
@@ -39,7 +47,8 @@ pub trait RandomBeacon {
 /// The root trust for the Drand chain is configured from build.DrandChain.
 pub struct DrandBeacon {
     client: Client,
-    url: String,
+    servers: Servers,
+    max_attempts: usize,
     pubkey: PublicKey,
 
     interval: u64, // time.Duration (i64)
@@ -48,6 +57,7 @@ pub struct DrandBeacon {
     fil_round_time: u64,
 
     local_cache: Mutex<HashMap<u64, BeaconEntry>>,
+    cache_file: Option<Mutex<File>>,
 }
 
 impl DrandBeacon {
@@ -57,18 +67,70 @@ impl DrandBeacon {
             panic!("Genesis timestamp cannot be 0");
         }
 
+        if config.servers.is_empty() {
+            return Err(anyhow!("No Drand server is configured"));
+        }
+
+        let (local_cache, cache_file) = match &config.cache_path {
+            Some(path) => {
+                let entries = load_cache_file(path)?;
+                let file = OpenOptions::new().create(true).append(true).open(path)?;
+                (entries, Some(Mutex::new(file)))
+            }
+            None => (HashMap::new(), None),
+        };
+
         Ok(Self {
-            client: ClientBuilder::new().build()?,
-            url: config.servers[0].to_string(),
+            client: ClientBuilder::new()
+                .timeout(config.request_timeout)
+                .build()?,
+            servers: Servers::new(config.servers),
+            max_attempts: config.max_attempts.max(1),
             pubkey: config.chain_info.public_key,
             interval: config.chain_info.period,
             drand_gen_time: config.chain_info.genesis_time,
             fil_round_time: interval,
             fil_gen_time: genesis_ts,
-            local_cache: Mutex::new(HashMap::new()),
+            local_cache: Mutex::new(local_cache),
+            cache_file,
         })
     }
 
+    async fn fetch_entry(&self, url: &str, round: u64) -> Result<BeaconEntry> {
+        let url = format!("{}/public/{}", url, round);
+        let public_rand_resp = self
+            .client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PublicRandResponse>()
+            .await?;
+        Ok(BeaconEntry::new(
+            public_rand_resp.round,
+            public_rand_resp.signature,
+        ))
+    }
+
+    fn cache_entry(&self, entry: &BeaconEntry) {
+        let mut local_cache = self.local_cache.lock();
+        if local_cache.insert(entry.round(), entry.clone()).is_some() {
+            return;
+        }
+        if let Some(file) = &self.cache_file {
+            let line = serde_json::to_string(entry)
+                .expect("JSON serialization of BeaconEntry shouldn't fail; qed");
+            // The cache file is an optimization only, failing to write it is not fatal.
+            if let Err(err) = writeln!(file.lock(), "{}", line) {
+                log::warn!(
+                    "Failed to write beacon entry {} to the cache file: {}",
+                    entry.round(),
+                    err
+                );
+            }
+        }
+    }
+
     fn verify_beacon_data(&self, round: u64, curr_sig: &[u8], prev_sig: &[u8]) -> Result<bool> {
         let mut message = Vec::with_capacity(prev_sig.len() + 8);
         message.extend_from_slice(prev_sig);
@@ -108,18 +170,27 @@ impl RandomBeacon for DrandBeacon {
             }
         }
 
-        let url = format!("{}/public/{}", self.url, round);
-        let public_rand_resp = self
-            .client
-            .get(&url)
-            .send()
-            .await?
-            .json::<PublicRandResponse>()
-            .await?;
-        Ok(BeaconEntry::new(
-            public_rand_resp.round,
-            public_rand_resp.signature,
-        ))
+        let mut last_err = None;
+        for _ in 0..self.max_attempts {
+            let (index, url) = self.servers.healthiest();
+            match self.fetch_entry(&url, round).await {
+                Ok(entry) => {
+                    self.servers.report_success(index);
+                    return Ok(entry);
+                }
+                Err(err) => {
+                    log::warn!(
+                        "Failed to fetch beacon entry {} from {}: {}",
+                        round,
+                        url,
+                        err
+                    );
+                    self.servers.report_failure(index);
+                    last_err = Some(err);
+                }
+            }
+        }
+        Err(last_err.expect("at least one attempt is made; qed"))
     }
 
     fn verify_entry(&self, curr: &BeaconEntry, prev: &BeaconEntry) -> Result<bool> {
@@ -130,7 +201,7 @@ impl RandomBeacon for DrandBeacon {
 
         let is_match = self.verify_beacon_data(curr.round(), curr.data(), prev.data())?;
         if is_match {
-            self.local_cache.lock().insert(curr.round(), curr.clone());
+            self.cache_entry(curr);
         }
         Ok(is_match)
     }
@@ -142,3 +213,107 @@ impl RandomBeacon for DrandBeacon {
         (latest_ts - self.drand_gen_time) / self.interval
     }
 }
+
+// The Drand servers with the number of their consecutive failures.
+struct Servers {
+    servers: Vec<(String, Mutex<u32>)>,
+}
+
+impl Servers {
+    fn new(urls: Vec<String>) -> Self {
+        Self {
+            servers: urls.into_iter().map(|url| (url, Mutex::new(0))).collect(),
+        }
+    }
+
+    // Return the server with the fewest consecutive failures, the first one wins the tie.
+    fn healthiest(&self) -> (usize, String) {
+        let (index, (url, _)) = self
+            .servers
+            .iter()
+            .enumerate()
+            .min_by_key(|(index, (_, failures))| (*failures.lock(), *index))
+            .expect("servers must not be empty; qed");
+        (index, url.clone())
+    }
+
+    fn report_success(&self, index: usize) {
+        *self.servers[index].1.lock() = 0;
+    }
+
+    fn report_failure(&self, index: usize) {
+        *self.servers[index].1.lock() += 1;
+    }
+}
+
+// Load the entries from the cache file, one JSON encoded entry per line.
+fn load_cache_file(path: &Path) -> Result<HashMap<u64, BeaconEntry>> {
+    let mut entries = HashMap::new();
+    if !path.exists() {
+        return Ok(entries);
+    }
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<BeaconEntry>(&line) {
+            Ok(entry) => {
+                entries.insert(entry.round(), entry);
+            }
+            // The last line may be partially written when the node crashes.
+            Err(err) => log::warn!("Ignored malformed beacon entry in the cache file: {}", err),
+        }
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_servers_failover() {
+        let servers = Servers::new(vec!["a".into(), "b".into(), "c".into()]);
+        assert_eq!(servers.healthiest(), (0, "a".into()));
+        servers.report_failure(0);
+        assert_eq!(servers.healthiest(), (1, "b".into()));
+        servers.report_failure(1);
+        servers.report_failure(2);
+        assert_eq!(servers.healthiest(), (0, "a".into()));
+        servers.report_failure(0);
+        servers.report_success(2);
+        assert_eq!(servers.healthiest(), (2, "c".into()));
+    }
+
+    #[test]
+    fn test_cache_file() {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("plum-beacon-cache-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut config = crate::DrandConfig::testnet();
+        config.cache_path = Some(path.clone());
+        let beacon = DrandBeacon::new(100, 25, config.clone()).unwrap();
+        beacon.cache_entry(&BeaconEntry::new(1, vec![1; 96]));
+        beacon.cache_entry(&BeaconEntry::new(2, vec![2; 96]));
+        beacon.cache_entry(&BeaconEntry::new(2, vec![2; 96]));
+        drop(beacon);
+        // a partially written line.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"Round\":3,")
+            .unwrap();
+
+        let entries = load_cache_file(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[&2].data(), &[2; 96][..]);
+        let beacon = DrandBeacon::new(100, 25, config).unwrap();
+        assert_eq!(beacon.local_cache.lock().len(), 2);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -10,8 +10,8 @@ mod config;
 mod drand;
 mod mock;
 
-pub use self::config::{DrandConfig, DrandNetwork};
-pub use self::drand::{DrandBeacon, RandomBeacon};
+pub use self::config::{DrandConfig, DrandNetwork, DEFAULT_MAX_ATTEMPTS, DEFAULT_REQUEST_TIMEOUT};
+pub use self::drand::{DrandBeacon, RandomBeacon, SharedBeacon};
 pub use self::mock::MockBeacon;
 
 #[cfg(test)]
@@ -1,8 +1,10 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::collections::{HashMap, HashSet};
 use std::time::Duration;
 
 use anyhow::Result;
+use parking_lot::Mutex;
 
 use plum_block::BeaconEntry;
 use plum_hashing::blake2b_256;
@@ -10,14 +12,32 @@ use plum_hashing::blake2b_256;
 use crate::drand::RandomBeacon;
 
 /// MockBeacon assumes that filecoin rounds are 1:1 mapped with the beacon rounds.
+///
+/// The entries are derived from the rounds by default, the tests can pre-program the entries
+/// of the rounds, delay the responses and make the beacon return invalid entries,
+/// so that the consensus can be tested reproducibly without the Drand network.
 pub struct MockBeacon {
     interval: Duration,
+    latency: Duration,
+    entries: Mutex<HashMap<u64, BeaconEntry>>,
+    invalid_rounds: Mutex<HashSet<u64>>,
 }
 
 impl MockBeacon {
     /// Create a new MockBeacon.
     pub fn new(interval: Duration) -> Self {
-        Self { interval }
+        Self {
+            interval,
+            latency: Duration::from_secs(0),
+            entries: Mutex::new(HashMap::new()),
+            invalid_rounds: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Delay every response of the beacon with the given latency.
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
     }
 
     /// Return the round time.
@@ -25,7 +45,29 @@ impl MockBeacon {
         self.interval
     }
 
-    fn entry_for_index(index: u64) -> BeaconEntry {
+    /// Pre-program the data of the entry of the round, which is treated as valid.
+    pub fn set_entry(&self, round: u64, data: Vec<u8>) {
+        self.entries
+            .lock()
+            .insert(round, BeaconEntry::new(round, data));
+    }
+
+    /// Make the beacon return an invalid entry for the round,
+    /// which fails the verification of `verify_entry`.
+    pub fn set_invalid(&self, round: u64) {
+        self.invalid_rounds.lock().insert(round);
+    }
+
+    /// Reset the pre-programmed and invalid entries.
+    pub fn reset(&self) {
+        self.entries.lock().clear();
+        self.invalid_rounds.lock().clear();
+    }
+
+    fn entry_for_index(&self, index: u64) -> BeaconEntry {
+        if let Some(entry) = self.entries.lock().get(&index) {
+            return entry.clone();
+        }
         let data = blake2b_256(index.to_be_bytes());
         BeaconEntry::new(index, data.to_vec())
     }
@@ -34,11 +76,20 @@ impl MockBeacon {
 #[async_trait::async_trait]
 impl RandomBeacon for MockBeacon {
     async fn entry(&self, round: u64) -> Result<BeaconEntry> {
-        Ok(Self::entry_for_index(round))
+        if self.latency > Duration::from_secs(0) {
+            tokio::time::delay_for(self.latency).await;
+        }
+        let entry = self.entry_for_index(round);
+        if self.invalid_rounds.lock().contains(&round) {
+            let mut data = entry.data().to_vec();
+            data.iter_mut().for_each(|byte| *byte = !*byte);
+            return Ok(BeaconEntry::new(round, data));
+        }
+        Ok(entry)
     }
 
     fn verify_entry(&self, curr: &BeaconEntry, _prev: &BeaconEntry) -> Result<bool> {
-        let oe = Self::entry_for_index(curr.round());
+        let oe = self.entry_for_index(curr.round());
         Ok(oe.data() == curr.data())
     }
 
@@ -46,3 +97,47 @@ impl RandomBeacon for MockBeacon {
         fil_epoch as u64
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::time::Instant;
+
+    use crate::drand::SharedBeacon;
+
+    #[tokio::test]
+    async fn test_mock_beacon() {
+        let mock = Arc::new(MockBeacon::new(Duration::from_secs(25)));
+        let beacon: SharedBeacon = mock.clone();
+
+        let genesis = beacon.entry(0).await.unwrap();
+        let entry = beacon.entry(1).await.unwrap();
+        assert_eq!(entry, beacon.entry(1).await.unwrap());
+        assert!(beacon.verify_entry(&entry, &genesis).unwrap());
+
+        mock.set_entry(2, vec![2; 96]);
+        let entry = beacon.entry(2).await.unwrap();
+        assert_eq!(entry.data(), &[2; 96][..]);
+        assert!(beacon.verify_entry(&entry, &genesis).unwrap());
+
+        mock.set_invalid(3);
+        let entry = beacon.entry(3).await.unwrap();
+        assert!(!beacon.verify_entry(&entry, &genesis).unwrap());
+
+        mock.reset();
+        assert_ne!(beacon.entry(2).await.unwrap().data(), &[2; 96][..]);
+        let entry = beacon.entry(3).await.unwrap();
+        assert!(beacon.verify_entry(&entry, &genesis).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_mock_beacon_latency() {
+        let beacon =
+            MockBeacon::new(Duration::from_secs(25)).with_latency(Duration::from_millis(50));
+        let start = Instant::now();
+        beacon.entry(1).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
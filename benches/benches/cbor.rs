@@ -0,0 +1,43 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use plum_benches::{sample_header, sample_message, sample_signed_message};
+use plum_block::BlockHeader;
+use plum_message::{SignedMessage, UnsignedMessage};
+
+fn bench_message(c: &mut Criterion) {
+    let message = sample_message();
+    let bytes = minicbor::to_vec(&message).unwrap();
+    c.bench_function("cbor/message/encode", |b| {
+        b.iter(|| minicbor::to_vec(black_box(&message)).unwrap())
+    });
+    c.bench_function("cbor/message/decode", |b| {
+        b.iter(|| minicbor::decode::<UnsignedMessage>(black_box(&bytes)).unwrap())
+    });
+    c.bench_function("cbor/message/cid", |b| b.iter(|| black_box(&message).cid()));
+
+    let signed = sample_signed_message();
+    let bytes = minicbor::to_vec(&signed).unwrap();
+    c.bench_function("cbor/signed_message/encode", |b| {
+        b.iter(|| minicbor::to_vec(black_box(&signed)).unwrap())
+    });
+    c.bench_function("cbor/signed_message/decode", |b| {
+        b.iter(|| minicbor::decode::<SignedMessage>(black_box(&bytes)).unwrap())
+    });
+}
+
+fn bench_header(c: &mut Criterion) {
+    let header = sample_header();
+    let bytes = minicbor::to_vec(&header).unwrap();
+    c.bench_function("cbor/header/encode", |b| {
+        b.iter(|| minicbor::to_vec(black_box(&header)).unwrap())
+    });
+    c.bench_function("cbor/header/decode", |b| {
+        b.iter(|| minicbor::decode::<BlockHeader>(black_box(&bytes)).unwrap())
+    });
+    c.bench_function("cbor/header/cid", |b| b.iter(|| black_box(&header).cid()));
+}
+
+criterion_group!(benches, bench_message, bench_header);
+criterion_main!(benches);
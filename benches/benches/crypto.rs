@@ -0,0 +1,42 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use plum_address::Address;
+use plum_crypto::{PrivateKey, PublicKey, Signature};
+use plum_hashing::blake2b_256;
+
+fn bench_blake2b(c: &mut Criterion) {
+    let mut group = c.benchmark_group("blake2b_256");
+    for size in [32usize, 1024, 256 * 1024].iter() {
+        let data = vec![0xa5u8; *size];
+        group.throughput(Throughput::Bytes(*size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &data, |b, data| {
+            b.iter(|| blake2b_256(black_box(data)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_verify(c: &mut Criterion) {
+    let msg = plum_benches::sample_message().cid().to_bytes();
+
+    let privkey = PrivateKey::generate_secp256k1_privkey();
+    let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+    let addr = Address::new_secp256k1_addr(&pubkey).unwrap();
+    let signature = Signature::sign_secp256k1(privkey.into_vec(), &msg).unwrap();
+    c.bench_function("verify/secp256k1", |b| {
+        b.iter(|| assert!(signature.verify(&addr, black_box(&msg)).unwrap()))
+    });
+
+    let privkey = PrivateKey::generate_bls_privkey();
+    let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+    let addr = Address::new_bls_addr(&pubkey).unwrap();
+    let signature = Signature::sign_bls(privkey.into_vec(), &msg).unwrap();
+    c.bench_function("verify/bls", |b| {
+        b.iter(|| assert!(signature.verify(&addr, black_box(&msg)).unwrap()))
+    });
+}
+
+criterion_group!(benches, bench_blake2b, bench_verify);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use ipld::hamt::{load_node, put_node, KeyValuePair, Node, Pointer};
+use ipld::{build_amt, load_amt, HamtVersion, DEFAULT_AMT_BIT_WIDTH};
+use plum_benches::mem_store;
+
+const SIZES: [u64; 3] = [100, 1_000, 10_000];
+
+fn bench_amt(c: &mut Criterion) {
+    let mut group = c.benchmark_group("amt");
+    for size in SIZES.iter() {
+        let values = (0..*size).collect::<Vec<u64>>();
+        group.bench_with_input(BenchmarkId::new("build", size), &values, |b, values| {
+            b.iter(|| build_amt(&mem_store(), black_box(values), DEFAULT_AMT_BIT_WIDTH).unwrap())
+        });
+
+        let store = mem_store();
+        let root = build_amt(&store, &values, DEFAULT_AMT_BIT_WIDTH).unwrap();
+        group.bench_with_input(BenchmarkId::new("load", size), &root, |b, root| {
+            b.iter(|| load_amt::<_, u64>(&store, black_box(root)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// A node with `slots` slots taken, every slot holds a full bucket of 3 key/value pairs.
+fn full_node(slots: u32) -> Node<u64> {
+    let mut node = Node::new();
+    for slot in 0..slots {
+        node.bitfield.set_bit(slot);
+        let bucket = (0..3u64)
+            .map(|i| KeyValuePair {
+                key: format!("key-{}-{}", slot, i).into_bytes(),
+                value: u64::from(slot) * 3 + i,
+            })
+            .collect();
+        node.pointers.push(Pointer::Bucket(bucket));
+    }
+    node
+}
+
+fn bench_hamt_node(c: &mut Criterion) {
+    let mut group = c.benchmark_group("hamt_node");
+    for slots in [1u32, 8, 32].iter() {
+        let node = full_node(*slots);
+        let store = mem_store();
+        group.bench_with_input(BenchmarkId::new("put", slots), &node, |b, node| {
+            b.iter(|| put_node(&store, HamtVersion::V3, black_box(node)).unwrap())
+        });
+
+        let cid = put_node(&store, HamtVersion::V3, &node).unwrap();
+        group.bench_with_input(BenchmarkId::new("load", slots), &cid, |b, cid| {
+            b.iter(|| load_node::<_, u64>(&store, HamtVersion::V3, black_box(cid)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_amt, bench_hamt_node);
+criterion_main!(benches);
@@ -0,0 +1,85 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The fixtures shared by the criterion benchmarks of the plum crates.
+//!
+//! Run `make bench-save` to record the baseline and `make bench-check` to compare against it.
+
+#![deny(missing_docs)]
+
+use cid::Cid;
+
+use ipfs_datastore::{Delay, DelayDataStore, MapDataStore, SyncDataStore};
+use plum_address::Address;
+use plum_block::{BlockHeader, ElectionProof, Ticket};
+use plum_crypto::Signature;
+use plum_message::{SignedMessage, UnsignedMessage, MESSAGE_VERSION_FEE_MARKET};
+
+/// The delay of the in-memory store, which never waits.
+#[derive(Clone)]
+pub struct NoDelay;
+
+impl Delay for NoDelay {
+    fn wait(&self) {}
+}
+
+/// The in-memory blockstore used by the benchmarks.
+pub type MemStore = DelayDataStore<NoDelay, SyncDataStore<MapDataStore>>;
+
+/// Create an empty in-memory blockstore.
+pub fn mem_store() -> MemStore {
+    DelayDataStore::new(NoDelay, SyncDataStore::new(MapDataStore::new()))
+}
+
+/// Return a message with typical params.
+pub fn sample_message() -> UnsignedMessage {
+    UnsignedMessage {
+        version: MESSAGE_VERSION_FEE_MARKET,
+        to: Address::new_id_addr(1000).expect("ID address must be valid; qed"),
+        from: Address::new_secp256k1_addr(&[4u8; 65]).expect("public key must be valid; qed"),
+        nonce: 42,
+        value: 1_000_000_000_000_000_000u64.into(),
+        gas_price: 0u64.into(),
+        gas_limit: 2_000_000,
+        gas_fee_cap: 100_000u64.into(),
+        gas_premium: 1_000u64.into(),
+        method: 6,
+        params: vec![0x82; 128],
+    }
+}
+
+/// Return a signed message with a fake secp256k1 signature.
+pub fn sample_signed_message() -> SignedMessage {
+    SignedMessage {
+        message: sample_message(),
+        signature: Signature::new_secp256k1(vec![1u8; 65]),
+    }
+}
+
+/// Return a block header with fake proofs and signatures.
+pub fn sample_header() -> BlockHeader {
+    let cid = sample_cid();
+    BlockHeader {
+        miner: Address::new_id_addr(1000).expect("ID address must be valid; qed"),
+        ticket: Ticket::new(vec![1u8; 96]),
+        election_proof: ElectionProof {
+            win_count: 1,
+            vrf_proof: vec![2u8; 96],
+        },
+        beacon_entries: vec![],
+        win_post_proof: vec![],
+        parents: vec![cid.clone(); 5],
+        parent_message_receipts: cid.clone(),
+        bls_aggregate: Signature::new_bls(vec![3u8; 96]),
+        parent_weight: 1_000_000u64.into(),
+        messages: cid.clone(),
+        height: 100_000,
+        parent_state_root: cid,
+        timestamp: 1_600_000_000,
+        block_sig: Signature::new_bls(vec![4u8; 96]),
+        fork_signaling: 0,
+    }
+}
+
+fn sample_cid() -> Cid {
+    sample_message().cid()
+}
@@ -0,0 +1,287 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::Result;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use plum_bigint::num_integer::Integer;
+use plum_bigint::BigInt;
+use plum_block::BlockHeader;
+use plum_message::UnsignedMessage;
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, INITIAL_BASE_FEE, MINIMUM_BASE_FEE};
+
+use crate::tipset_cache::TipsetLoader;
+
+/// The default number of the base fees kept in the base fee tracker.
+pub const DEFAULT_BASE_FEE_CACHE_SIZE: usize = 2048;
+
+/// The loader of the tipsets and the messages of the blocks.
+pub trait MessageLoader: TipsetLoader {
+    /// Load the messages included in the block, the secp256k1 messages are returned
+    /// without the signatures.
+    fn block_messages(&self, header: &BlockHeader) -> Result<Vec<UnsignedMessage>>;
+}
+
+/// Compute the base fee of the next tipset with the base fee of the current tipset,
+/// the sum of the gas limits of the unique messages of the current tipset and the number of
+/// the blocks of the current tipset. See lotus/chain/store/basefee.go for details.
+pub fn compute_next_base_fee(base_fee: &BigInt, gas_limit_used: &BigInt, blocks: usize) -> BigInt {
+    let target = BigInt::from(BLOCK_GAS_TARGET);
+    let delta = gas_limit_used / BigInt::from(blocks.max(1)) - &target;
+    let delta = delta.max(-target.clone()).min(target.clone());
+
+    // The same as the euclidean division of Go.
+    let change = (base_fee * delta)
+        .div_floor(&target)
+        .div_floor(&BigInt::from(BASE_FEE_MAX_CHANGE_DENOM));
+    (base_fee + change).max(BigInt::from(MINIMUM_BASE_FEE))
+}
+
+/// Estimate the fee cap that keeps a message includable for `max_queue_blocks` epochs,
+/// assuming the base fee goes up by the max change at every epoch, plus the gas premium.
+pub fn estimate_fee_cap(
+    parent_base_fee: &BigInt,
+    max_queue_blocks: u64,
+    gas_premium: Option<&BigInt>,
+) -> BigInt {
+    let increase_factor =
+        (1.0 + 1.0 / BASE_FEE_MAX_CHANGE_DENOM as f64).powf(max_queue_blocks as f64);
+    // Keep 8 bits of the fraction, the same as Lotus.
+    let fee_in_future = parent_base_fee * BigInt::from((increase_factor * 256.0) as u64);
+    let fee_cap = fee_in_future / BigInt::from(256);
+    match gas_premium {
+        Some(gas_premium) => fee_cap + gas_premium,
+        None => fee_cap,
+    }
+}
+
+/// The tracker of the base fees of the tipsets.
+///
+/// The base fee of a tipset is the base fee paid by the messages included in its child,
+/// which is computed from the base fee of its parent and the gas used by its messages.
+/// The base fee of the genesis parent is `INITIAL_BASE_FEE`.
+pub struct BaseFeeTracker<L> {
+    loader: L,
+    cache: Mutex<LruCache<TipsetKey, BigInt>>,
+}
+
+impl<L: MessageLoader> BaseFeeTracker<L> {
+    /// Create a new base fee tracker with the default cache size.
+    pub fn new(loader: L) -> Self {
+        Self::with_capacity(loader, DEFAULT_BASE_FEE_CACHE_SIZE)
+    }
+
+    /// Create a new base fee tracker with the given cache size.
+    pub fn with_capacity(loader: L, size: usize) -> Self {
+        Self {
+            loader,
+            cache: Mutex::new(LruCache::new(size)),
+        }
+    }
+
+    /// Return the base fee paid by the messages included in the blocks of `tipset`.
+    pub fn parent_base_fee(&self, tipset: &Arc<Tipset>) -> Result<BigInt> {
+        if tipset.height() == 0 {
+            return Ok(BigInt::from(INITIAL_BASE_FEE));
+        }
+        let parent = self.loader.load_tipset(&tipset.parents())?;
+        self.base_fee(&parent)
+    }
+
+    /// Return the base fee paid by the messages included in the blocks on top of `tipset`.
+    pub fn base_fee(&self, tipset: &Arc<Tipset>) -> Result<BigInt> {
+        // Walk back until a tipset whose base fee is known, then compute forward.
+        let mut pending = vec![];
+        let mut current = tipset.clone();
+        let mut base_fee = loop {
+            if let Some(base_fee) = self.cache.lock().get(current.key()) {
+                break base_fee.clone();
+            }
+            let parent = if current.height() == 0 {
+                None
+            } else {
+                Some(self.loader.load_tipset(&current.parents())?)
+            };
+            pending.push(current);
+            match parent {
+                Some(parent) => current = parent,
+                None => break BigInt::from(INITIAL_BASE_FEE),
+            }
+        };
+
+        for tipset in pending.into_iter().rev() {
+            let gas_limit_used = self.gas_limit_used(&tipset)?;
+            base_fee = compute_next_base_fee(&base_fee, &gas_limit_used, tipset.blocks().len());
+            self.cache
+                .lock()
+                .put(tipset.key().clone(), base_fee.clone());
+        }
+        trace!(
+            "[base fee] base fee on top of tipset at height {}: {}",
+            tipset.height(),
+            base_fee
+        );
+        Ok(base_fee)
+    }
+
+    /// Estimate the fee cap on top of `tipset`, see `estimate_fee_cap` for details.
+    pub fn estimate_fee_cap(
+        &self,
+        tipset: &Arc<Tipset>,
+        max_queue_blocks: u64,
+        gas_premium: Option<&BigInt>,
+    ) -> Result<BigInt> {
+        let base_fee = self.parent_base_fee(tipset)?;
+        Ok(estimate_fee_cap(&base_fee, max_queue_blocks, gas_premium))
+    }
+
+    /// Clear all the cached base fees.
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+    }
+
+    // The message included by several blocks of the tipset is counted only once.
+    fn gas_limit_used(&self, tipset: &Tipset) -> Result<BigInt> {
+        let mut seen = HashSet::new();
+        let mut gas_limit_used = BigInt::from(0);
+        for header in tipset.blocks() {
+            for message in self.loader.block_messages(header)? {
+                if seen.insert(message.cid()) {
+                    gas_limit_used += BigInt::from(message.gas_limit);
+                }
+            }
+        }
+        Ok(gas_limit_used)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use cid::Cid;
+    use plum_address::Address;
+    use plum_block::Ticket;
+
+    use crate::test_utils::{self, MapLoader};
+
+    // The tipsets, and the gas limit of the message in the blocks at a height.
+    #[derive(Default)]
+    struct GasLimitLoader {
+        tipsets: MapLoader,
+        gas_limits: HashMap<i64, u64>,
+    }
+
+    impl TipsetLoader for GasLimitLoader {
+        fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>> {
+            self.tipsets.load_tipset(key)
+        }
+    }
+
+    impl MessageLoader for GasLimitLoader {
+        fn block_messages(&self, header: &BlockHeader) -> Result<Vec<UnsignedMessage>> {
+            let gas_limit = self.gas_limits.get(&header.height).copied().unwrap_or(0);
+            // The same message in all the blocks of the tipset.
+            Ok(vec![UnsignedMessage {
+                version: 0,
+                to: Address::new_id_addr(100).unwrap(),
+                from: Address::new_id_addr(101).unwrap(),
+                nonce: header.height as u64,
+                value: 0u64.into(),
+                gas_price: 1u64.into(),
+                gas_limit: gas_limit as i64,
+                gas_fee_cap: 0u64.into(),
+                gas_premium: 0u64.into(),
+                method: 0,
+                params: vec![],
+            }])
+        }
+    }
+
+    // The ticket tells the blocks of the same height apart.
+    fn new_header(parents: Vec<Cid>, height: i64, miner: u64) -> BlockHeader {
+        BlockHeader {
+            ticket: Ticket::new(miner.to_be_bytes().to_vec()),
+            ..test_utils::new_header(parents, height, miner)
+        }
+    }
+
+    #[test]
+    fn test_compute_next_base_fee() {
+        let base_fee = BigInt::from(INITIAL_BASE_FEE);
+        let target = BigInt::from(BLOCK_GAS_TARGET);
+        assert_eq!(compute_next_base_fee(&base_fee, &target, 1), base_fee);
+        // at most 1/8 change.
+        let full = BigInt::from(BLOCK_GAS_TARGET * 10);
+        assert_eq!(
+            compute_next_base_fee(&base_fee, &full, 1),
+            BigInt::from(112_500_000u64)
+        );
+        assert_eq!(
+            compute_next_base_fee(&base_fee, &BigInt::from(0), 1),
+            BigInt::from(87_500_000u64)
+        );
+        // the gas used is averaged over the blocks.
+        assert_eq!(compute_next_base_fee(&base_fee, &full, 10), base_fee);
+        assert_eq!(
+            compute_next_base_fee(&BigInt::from(MINIMUM_BASE_FEE), &BigInt::from(0), 1),
+            BigInt::from(MINIMUM_BASE_FEE)
+        );
+    }
+
+    #[test]
+    fn test_estimate_fee_cap() {
+        let base_fee = BigInt::from(1_000_000u64);
+        assert_eq!(estimate_fee_cap(&base_fee, 0, None), base_fee);
+        // 1.125 * 256 = 288
+        assert_eq!(
+            estimate_fee_cap(&base_fee, 1, Some(&BigInt::from(5))),
+            BigInt::from(1_125_005u64)
+        );
+        assert!(estimate_fee_cap(&base_fee, 20, None) > BigInt::from(10_000_000u64));
+    }
+
+    #[test]
+    fn test_base_fee_tracker() {
+        let mut loader = GasLimitLoader::default();
+        let genesis = loader
+            .tipsets
+            .insert(Tipset::new(vec![new_header(vec![], 0, 1000)]).unwrap());
+        // two blocks at height 1 including the same message.
+        let tipset1 = loader.tipsets.insert(
+            Tipset::new(vec![
+                new_header(genesis.cids().to_vec(), 1, 1000),
+                new_header(genesis.cids().to_vec(), 1, 1001),
+            ])
+            .unwrap(),
+        );
+        let tipset2 = loader
+            .tipsets
+            .insert(Tipset::new(vec![new_header(tipset1.cids().to_vec(), 2, 1000)]).unwrap());
+        loader.gas_limits.insert(0, BLOCK_GAS_TARGET);
+        loader.gas_limits.insert(1, BLOCK_GAS_TARGET * 2);
+        loader.gas_limits.insert(2, 0);
+
+        let tracker = BaseFeeTracker::new(loader);
+        let initial = BigInt::from(INITIAL_BASE_FEE);
+        assert_eq!(tracker.parent_base_fee(&genesis).unwrap(), initial);
+        assert_eq!(tracker.base_fee(&genesis).unwrap(), initial);
+        // the duplicated message is counted once, so the average is the target.
+        assert_eq!(tracker.base_fee(&tipset1).unwrap(), initial);
+        assert_eq!(tracker.parent_base_fee(&tipset2).unwrap(), initial);
+        assert_eq!(
+            tracker.base_fee(&tipset2).unwrap(),
+            BigInt::from(87_500_000u64)
+        );
+        assert_eq!(
+            tracker.estimate_fee_cap(&tipset2, 0, None).unwrap(),
+            initial
+        );
+    }
+}
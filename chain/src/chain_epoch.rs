@@ -0,0 +1,151 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The conversion between the chain epochs and the wall-clock time,
+//! and the proving deadline math used by the miner scheduler.
+
+use plum_actor::miner::{compute_proving_period_deadline, DeadlineInfo};
+use plum_types::{ChainEpoch, BLOCK_DELAY};
+
+/// The clock mapping the chain epochs to the unix timestamps in seconds.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ChainEpochClock {
+    genesis_time: u64,
+    block_delay: u64,
+}
+
+impl ChainEpochClock {
+    /// Create a new clock with the timestamp of the genesis block and the default block delay.
+    pub fn new(genesis_time: u64) -> Self {
+        Self::with_block_delay(genesis_time, BLOCK_DELAY)
+    }
+
+    /// Create a new clock with the timestamp of the genesis block and the given block delay.
+    pub fn with_block_delay(genesis_time: u64, block_delay: u64) -> Self {
+        assert!(block_delay > 0, "block delay must be positive");
+        Self {
+            genesis_time,
+            block_delay,
+        }
+    }
+
+    /// Return the timestamp of the genesis block.
+    pub fn genesis_time(&self) -> u64 {
+        self.genesis_time
+    }
+
+    /// Return the block delay in seconds.
+    pub fn block_delay(&self) -> u64 {
+        self.block_delay
+    }
+
+    /// Return the epoch at the unix timestamp `now`, the epochs before genesis are negative.
+    pub fn epoch_at_time(&self, now: u64) -> ChainEpoch {
+        epoch_at_time(self.genesis_time, now, self.block_delay)
+    }
+
+    /// Return the unix timestamp at which `epoch` starts.
+    pub fn time_of_epoch(&self, epoch: ChainEpoch) -> u64 {
+        time_of_epoch(self.genesis_time, epoch, self.block_delay)
+    }
+}
+
+/// Return the epoch at the unix timestamp `now` of the chain whose genesis block is at `genesis_time`.
+///
+/// The epochs before genesis are negative, i.e. the result is rounded towards negative infinity.
+pub fn epoch_at_time(genesis_time: u64, now: u64, block_delay: u64) -> ChainEpoch {
+    let elapsed = now as i128 - genesis_time as i128;
+    let block_delay = block_delay as i128;
+    let epoch = if elapsed >= 0 {
+        elapsed / block_delay
+    } else {
+        (elapsed - block_delay + 1) / block_delay
+    };
+    epoch as ChainEpoch
+}
+
+/// Return the unix timestamp at which `epoch` starts of the chain whose genesis block is at `genesis_time`.
+///
+/// The timestamp saturates at zero for the epochs before the unix epoch.
+pub fn time_of_epoch(genesis_time: u64, epoch: ChainEpoch, block_delay: u64) -> u64 {
+    let time = genesis_time as i128 + epoch as i128 * block_delay as i128;
+    time.max(0) as u64
+}
+
+/// Return the info of the proving deadline containing `current_epoch`
+/// in the proving period starting at `period_start`.
+pub fn deadline_info(current_epoch: ChainEpoch, period_start: ChainEpoch) -> DeadlineInfo {
+    compute_proving_period_deadline(period_start, current_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_actor::miner::{
+        W_POST_CHALLENGE_WINDOW, W_POST_PERIOD_DEADLINES, W_POST_PROVING_PERIOD,
+    };
+
+    #[test]
+    fn test_epoch_time_conversion() {
+        let clock = ChainEpochClock::with_block_delay(1000, 30);
+        assert_eq!(clock.epoch_at_time(1000), 0);
+        assert_eq!(clock.epoch_at_time(1029), 0);
+        assert_eq!(clock.epoch_at_time(1030), 1);
+        assert_eq!(clock.epoch_at_time(999), -1);
+        assert_eq!(clock.epoch_at_time(970), -1);
+        assert_eq!(clock.epoch_at_time(969), -2);
+
+        for epoch in -10..100 {
+            let time = clock.time_of_epoch(epoch);
+            assert_eq!(clock.epoch_at_time(time), epoch);
+            assert_eq!(clock.epoch_at_time(time + 29), epoch);
+        }
+        assert_eq!(clock.time_of_epoch(-100), 0);
+
+        let clock = ChainEpochClock::new(1000);
+        assert_eq!(clock.time_of_epoch(2), 1000 + 2 * BLOCK_DELAY);
+    }
+
+    #[test]
+    fn test_deadline_info() {
+        let window = W_POST_CHALLENGE_WINDOW as ChainEpoch;
+        let period = W_POST_PROVING_PERIOD as ChainEpoch;
+        let period_start = 1000;
+
+        // before the proving period starts.
+        let info = deadline_info(period_start - 1, period_start);
+        assert_eq!(info.index, 0);
+        assert!(!info.period_started());
+        assert!(!info.is_open());
+
+        let info = deadline_info(period_start + 3 * window + 1, period_start);
+        assert_eq!(info.index, 3);
+        assert_eq!(info.open, period_start + 3 * window);
+        assert_eq!(info.close, info.open + window);
+        assert!(info.challenge < info.open);
+        assert!(info.fault_cutoff < info.challenge);
+        assert!(info.is_open());
+        assert!(!info.has_elapsed());
+        assert!(info.fault_cutoff_passed());
+
+        let last = deadline_info(period_start + period - 1, period_start);
+        assert_eq!(last.index, W_POST_PERIOD_DEADLINES - 1);
+        assert_eq!(last.close, last.next_period_start());
+        assert_eq!(last.period_end(), period_start + period - 1);
+
+        // the proving period has elapsed.
+        let info = deadline_info(period_start + period, period_start);
+        assert_eq!(info.index, W_POST_PERIOD_DEADLINES);
+        assert!(info.period_elapsed());
+        assert!(!info.is_open());
+
+        // the same deadline in a later proving period.
+        let info = DeadlineInfo::new(period_start, 3, period_start + 2 * period + 4 * window);
+        assert!(info.has_elapsed());
+        let next = info.next_not_elapsed();
+        assert_eq!(next.index, 3);
+        assert_eq!(next.period_start, period_start + 3 * period);
+        assert!(!next.has_elapsed());
+        assert_eq!(next.next_not_elapsed(), next);
+    }
+}
@@ -0,0 +1,515 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use anyhow::{anyhow, ensure, Result};
+use cid::Cid;
+use minicbor::{decode, encode, Decoder, Encoder};
+use parking_lot::Mutex;
+
+use ipfs_block::Block;
+use ipfs_blockstore::BlockStore;
+use ipfs_datastore::{DataStore, DataStoreRead, DataStoreWrite, Key};
+use ipld::{load_amt, IpldStore};
+use plum_block::{BlockHeader, MsgMeta};
+use plum_message::{ChainMessage, SignedMessage, UnsignedMessage};
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+use crate::tipset_cache::TipsetLoader;
+use crate::tipset_messages::select_tipset_messages;
+
+/// The datastore key of the durable head of the chain.
+pub const HEAD_KEY: &str = "/chain/head";
+/// The datastore key of the write-ahead journal of the validated tipsets.
+pub const JOURNAL_KEY: &str = "/chain/journal";
+
+// A validated tipset in the write-ahead journal, which is not the head yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct JournalEntry {
+    height: ChainEpoch,
+    key: TipsetKey,
+}
+
+// Implement CBOR serialization for JournalEntry.
+impl encode::Encode for JournalEntry {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?.i64(self.height)?.encode(&self.key)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for JournalEntry.
+impl<'b> decode::Decode<'b> for JournalEntry {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let array_len = d.array()?;
+        if array_len != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of journal entry"));
+        }
+        Ok(JournalEntry {
+            height: d.i64()?,
+            key: d.decode::<TipsetKey>()?,
+        })
+    }
+}
+
+/// The result of the startup recovery of the chain store.
+#[derive(Clone, Debug, Default)]
+pub struct Recovery {
+    /// The durable head, `None` if the head has never been set.
+    pub head: Option<Tipset>,
+    /// The journaled tipsets past the head which passed the re-validation, ordered by height.
+    pub revalidated: Vec<Tipset>,
+    /// The journaled tipsets past the head which were dropped from the journal, because they
+    /// were incomplete, not connected to the head or failed the re-validation.
+    pub dropped: Vec<TipsetKey>,
+}
+
+/// The chain store persisting the sync progress in a crash-consistent way.
+///
+/// The validated tipsets are written ahead with their messages before becoming the head:
+/// the block headers and the message sets are committed in a batch first, then the tipsets are
+/// recorded in the journal and the datastore is synced. The head pointer is a single key which
+/// is only updated to a journaled tipset, so a crash at any point leaves either the old head or
+/// the new head, with all the blocks of the head persisted. The journaled tipsets past the durable
+/// head are re-validated by `recover` on startup.
+pub struct ChainStore<S> {
+    store: S,
+    head: Mutex<Option<Tipset>>,
+    journal: Mutex<Vec<JournalEntry>>,
+}
+
+impl<S: BlockStore> ChainStore<S> {
+    /// Create a chain store on the blockstore, `recover` should be called before use.
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            head: Mutex::new(None),
+            journal: Mutex::new(vec![]),
+        }
+    }
+
+    /// Return the underlying blockstore.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Return the current head.
+    pub fn head(&self) -> Option<Tipset> {
+        self.head.lock().clone()
+    }
+
+    /// Return the keys of the journaled tipsets which are not the head yet, ordered by height.
+    pub fn pending(&self) -> Vec<TipsetKey> {
+        self.journal
+            .lock()
+            .iter()
+            .map(|entry| entry.key.clone())
+            .collect()
+    }
+
+    /// Load the durable head and re-validate the journaled tipsets past it.
+    ///
+    /// The journaled tipsets are re-validated by `validate` in the order of height, a tipset
+    /// is only re-validated if all its blocks are persisted and its parent is the head or a
+    /// re-validated tipset. The tipsets at or below the height of the head were left behind by
+    /// an interrupted head update and are pruned. The journal is rewritten with the re-validated
+    /// tipsets only.
+    pub fn recover<F>(&self, mut validate: F) -> Result<Recovery>
+    where
+        F: FnMut(&Tipset) -> Result<()>,
+    {
+        let head = match DataStoreRead::get(&self.store, &Key::new(HEAD_KEY))? {
+            Some(data) => {
+                let key = minicbor::decode::<TipsetKey>(&data)
+                    .map_err(|err| anyhow!("failed to decode the chain head: {}", err))?;
+                Some(self.load_tipset(&key)?)
+            }
+            None => None,
+        };
+        let mut journal = self.load_journal()?;
+        journal.sort_by_key(|entry| entry.height);
+
+        let mut recovery = Recovery {
+            head: head.clone(),
+            ..Default::default()
+        };
+        let mut connected = HashSet::new();
+        if let Some(head) = &head {
+            connected.insert(head.key().clone());
+        }
+        let mut kept = vec![];
+        for entry in journal {
+            if let Some(head) = &head {
+                if entry.height <= head.height() {
+                    debug!(
+                        "[chain] prune journaled tipset {} below the head",
+                        entry.key
+                    );
+                    continue;
+                }
+            }
+            let tipset = match self.load_tipset(&entry.key) {
+                Ok(tipset) => tipset,
+                Err(err) => {
+                    warn!("[chain] drop incomplete tipset {}: {}", entry.key, err);
+                    recovery.dropped.push(entry.key);
+                    continue;
+                }
+            };
+            if !connected.contains(&tipset.parents()) {
+                warn!(
+                    "[chain] drop tipset {} not connected to the head",
+                    entry.key
+                );
+                recovery.dropped.push(entry.key);
+                continue;
+            }
+            if let Err(err) = validate(&tipset) {
+                warn!("[chain] drop invalid tipset {}: {}", entry.key, err);
+                recovery.dropped.push(entry.key);
+                continue;
+            }
+            connected.insert(entry.key.clone());
+            kept.push(entry);
+            recovery.revalidated.push(tipset);
+        }
+
+        self.write_journal(&kept)?;
+        *self.head.lock() = head;
+        *self.journal.lock() = kept;
+        Ok(recovery)
+    }
+
+    /// Persist the validated tipsets with their message sets ahead of updating the head.
+    ///
+    /// The `messages` are the blocks of the message sets of the tipsets, i.e. the messages, the
+    /// message metas and the AMT nodes linking them. The block headers and the messages are
+    /// committed in a batch before the tipsets are recorded in the journal, then all the writes
+    /// are persisted by `DataStore::sync` before returning (e.g. the RocksDB datastore flushes
+    /// its memtables to the disk), so the tipsets can become the head afterwards.
+    pub fn write_ahead(&self, tipsets: &[Tipset], messages: &[Block]) -> Result<()> {
+        let mut blocks = tipsets
+            .iter()
+            .flat_map(|tipset| tipset.blocks().iter().map(Block::new))
+            .collect::<Vec<_>>();
+        blocks.extend_from_slice(messages);
+        self.store.put_many(&blocks)?;
+
+        let mut journal = self.journal.lock();
+        let mut updated = journal.clone();
+        for tipset in tipsets {
+            if !updated.iter().any(|entry| entry.key == *tipset.key()) {
+                updated.push(JournalEntry {
+                    height: tipset.height(),
+                    key: tipset.key().clone(),
+                });
+            }
+        }
+        updated.sort_by_key(|entry| entry.height);
+        self.write_journal(&updated)?;
+        *journal = updated;
+        Ok(())
+    }
+
+    /// Update the head to the tipset, which must be written ahead or be the current head.
+    ///
+    /// The head pointer is updated by a single write and persisted by `DataStore::sync`, then
+    /// the journaled tipsets at or below the height of the new head are pruned.
+    pub fn set_head(&self, tipset: &Tipset) -> Result<()> {
+        let mut head = self.head.lock();
+        let mut journal = self.journal.lock();
+        let is_head = head.as_ref().map(Tipset::key) == Some(tipset.key());
+        ensure!(
+            is_head || journal.iter().any(|entry| entry.key == *tipset.key()),
+            "tipset {} at height {} is not written ahead",
+            tipset.key(),
+            tipset.height()
+        );
+
+        let data = minicbor::to_vec(tipset.key())
+            .expect("CBOR serialization of TipsetKey shouldn't be failed");
+        DataStoreWrite::put(&self.store, Key::new(HEAD_KEY), data)?;
+        self.store.sync(&Key::new(HEAD_KEY))?;
+        *head = Some(tipset.clone());
+
+        let pruned = journal
+            .iter()
+            .filter(|entry| entry.height > tipset.height())
+            .cloned()
+            .collect::<Vec<_>>();
+        // The head is durable already, the journal left behind is pruned by the next recovery
+        // if the pruning fails.
+        self.write_journal(&pruned)?;
+        *journal = pruned;
+        Ok(())
+    }
+
+    /// Load the messages of the block, the BLS messages first, then the secp256k1 messages,
+    /// both in the order of the message AMTs.
+    pub fn block_messages(&self, header: &BlockHeader) -> Result<Vec<ChainMessage>> {
+        let meta = self.load_object::<MsgMeta>(&header.messages, "message meta")?;
+        let mut messages = vec![];
+        for (_, cid) in load_amt::<_, Cid>(&self.store, &meta.bls_messages)? {
+            let message = self.load_object::<UnsignedMessage>(&cid, "BLS message")?;
+            messages.push(ChainMessage::from_unsigned(message));
+        }
+        for (_, cid) in load_amt::<_, Cid>(&self.store, &meta.secpk_messages)? {
+            let message = self.load_object::<SignedMessage>(&cid, "secp256k1 message")?;
+            messages.push(ChainMessage::from_signed(message));
+        }
+        Ok(messages)
+    }
+
+    /// Load the messages to apply of the tipset, along with the indexes of the blocks including
+    /// them, the duplicate and the conflicting messages are skipped, see `select_tipset_messages`.
+    pub fn tipset_messages(&self, tipset: &Tipset) -> Result<Vec<(usize, ChainMessage)>> {
+        let blocks = tipset
+            .blocks()
+            .iter()
+            .map(|header| self.block_messages(header))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(select_tipset_messages(blocks))
+    }
+
+    fn load_object<T>(&self, cid: &Cid, name: &str) -> Result<T>
+    where
+        T: for<'b> decode::Decode<'b>,
+    {
+        IpldStore::get(&self.store, cid)?.ok_or_else(|| anyhow!("{} {} not found", name, cid))
+    }
+
+    fn load_tipset(&self, key: &TipsetKey) -> Result<Tipset> {
+        let headers = key
+            .cids()
+            .iter()
+            .map(|cid| {
+                let block = BlockStore::get(&self.store, cid)?
+                    .ok_or_else(|| anyhow!("block header {} not found", cid))?;
+                minicbor::decode::<BlockHeader>(block.data())
+                    .map_err(|err| anyhow!("failed to decode block header {}: {}", cid, err))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Tipset::new(headers)?)
+    }
+
+    fn load_journal(&self) -> Result<Vec<JournalEntry>> {
+        match DataStoreRead::get(&self.store, &Key::new(JOURNAL_KEY))? {
+            Some(data) => minicbor::decode::<Vec<JournalEntry>>(&data)
+                .map_err(|err| anyhow!("failed to decode the chain journal: {}", err)),
+            None => Ok(vec![]),
+        }
+    }
+
+    // Write the journal and persist it along with all the writes before it.
+    fn write_journal(&self, journal: &[JournalEntry]) -> Result<()> {
+        let data =
+            minicbor::to_vec(journal).expect("CBOR serialization of journal shouldn't be failed");
+        DataStoreWrite::put(&self.store, Key::new(JOURNAL_KEY), data)?;
+        self.store.sync(&Key::new("/"))?;
+        Ok(())
+    }
+}
+
+impl<S: BlockStore> TipsetLoader for ChainStore<S> {
+    fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>> {
+        ChainStore::load_tipset(self, key).map(Arc::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use anyhow::bail;
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use ipld::{build_amt, DEFAULT_AMT_BIT_WIDTH};
+    use plum_address::Address;
+    use plum_bigint::BigInt;
+    use plum_block::Ticket;
+    use plum_crypto::Signature;
+    use plum_message::MESSAGE_VERSION_FEE_MARKET;
+
+    use crate::test_utils::{self, NoDelay, Store};
+
+    fn new_tipset(height: i64, parent: Option<&Tipset>) -> Tipset {
+        let parents = parent.map(|p| p.cids().to_vec()).unwrap_or_default();
+        test_utils::new_tipset(parents, height)
+    }
+
+    // Put the message set into the store, return the CID of the message meta.
+    fn put_messages(store: &Store, bls: &[UnsignedMessage], secp: &[SignedMessage]) -> Cid {
+        let bls = bls
+            .iter()
+            .map(|m| IpldStore::put(store, m).unwrap())
+            .collect::<Vec<_>>();
+        let secp = secp
+            .iter()
+            .map(|m| IpldStore::put(store, m).unwrap())
+            .collect::<Vec<_>>();
+        let meta = MsgMeta {
+            bls_messages: build_amt(store, &bls, DEFAULT_AMT_BIT_WIDTH).unwrap(),
+            secpk_messages: build_amt(store, &secp, DEFAULT_AMT_BIT_WIDTH).unwrap(),
+        };
+        IpldStore::put(store, &meta).unwrap()
+    }
+
+    fn new_chain(len: i64) -> Vec<Tipset> {
+        let mut chain = vec![new_tipset(0, None)];
+        for height in 1..len {
+            let tipset = new_tipset(height, chain.last());
+            chain.push(tipset);
+        }
+        chain
+    }
+
+    #[test]
+    fn test_write_ahead_and_recover() {
+        let store = Store::new(NoDelay, SyncDataStore::new(MapDataStore::new()));
+        let chain = new_chain(5);
+        let cs = ChainStore::new(store.clone());
+        cs.recover(|_| Ok(())).unwrap();
+        assert!(cs.set_head(&chain[0]).is_err());
+        cs.write_ahead(&chain[..1], &[]).unwrap();
+        cs.set_head(&chain[0]).unwrap();
+        assert!(cs.pending().is_empty());
+        assert_eq!(
+            *TipsetLoader::load_tipset(&cs, chain[0].key()).unwrap(),
+            chain[0]
+        );
+        assert!(TipsetLoader::load_tipset(&cs, chain[1].key()).is_err());
+
+        // Crash after writing ahead, before updating the head.
+        cs.write_ahead(&chain[1..4], &[]).unwrap();
+        cs.set_head(&chain[1]).unwrap();
+        assert_eq!(
+            cs.pending(),
+            vec![chain[2].key().clone(), chain[3].key().clone()]
+        );
+        drop(cs);
+
+        let cs = ChainStore::new(store.clone());
+        let mut validated = vec![];
+        let recovery = cs
+            .recover(|tipset| {
+                validated.push(tipset.height());
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(recovery.head.as_ref(), Some(&chain[1]));
+        assert_eq!(recovery.revalidated, chain[2..4].to_vec());
+        assert!(recovery.dropped.is_empty());
+        assert_eq!(validated, vec![2, 3]);
+        cs.set_head(&chain[3]).unwrap();
+        assert!(cs.pending().is_empty());
+
+        // The invalid tipset and the tipset not connected to the head are dropped.
+        cs.write_ahead(&chain[4..], &[]).unwrap();
+        let orphan = new_tipset(5, Some(&new_tipset(4, None)));
+        cs.write_ahead(&[orphan.clone()], &[]).unwrap();
+        let cs = ChainStore::new(store.clone());
+        let recovery = cs
+            .recover(|tipset| {
+                if tipset.height() == 4 {
+                    bail!("invalid tipset");
+                }
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(recovery.head.as_ref(), Some(&chain[3]));
+        assert!(recovery.revalidated.is_empty());
+        assert_eq!(
+            recovery.dropped,
+            vec![chain[4].key().clone(), orphan.key().clone()]
+        );
+        assert!(cs.pending().is_empty());
+        assert!(cs.set_head(&chain[4]).is_err());
+    }
+
+    #[test]
+    fn test_tipset_messages() {
+        let store = Store::new(NoDelay, SyncDataStore::new(MapDataStore::new()));
+        let message = |from, nonce| UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(1000).unwrap(),
+            from: Address::new_id_addr(from).unwrap(),
+            nonce,
+            value: BigInt::from(0),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        };
+        let signed = |from, nonce| SignedMessage {
+            message: message(from, nonce),
+            signature: Signature::new_secp256k1(vec![1; 65]),
+        };
+
+        // Both blocks include the messages of nonce 0, the tickets keep the order of the blocks.
+        let mut headers = vec![];
+        let sets = vec![
+            put_messages(&store, &[message(1001, 0)], &[signed(1002, 0)]),
+            put_messages(
+                &store,
+                &[message(1001, 0), message(1001, 1)],
+                &[signed(1002, 0), signed(1002, 1)],
+            ),
+        ];
+        for (miner, messages) in sets.into_iter().enumerate() {
+            let mut header = new_tipset(1, None).blocks()[0].clone();
+            header.miner = Address::new_id_addr(2000 + miner as u64).unwrap();
+            header.ticket = Ticket::new(vec![miner as u8; 32]);
+            header.messages = messages;
+            headers.push(header);
+        }
+        let tipset = Tipset::new(headers).unwrap();
+        let cs = ChainStore::new(store);
+        assert_eq!(cs.block_messages(&tipset.blocks()[0]).unwrap().len(), 2);
+        assert_eq!(cs.block_messages(&tipset.blocks()[1]).unwrap().len(), 4);
+
+        let messages = cs
+            .tipset_messages(&tipset)
+            .unwrap()
+            .into_iter()
+            .map(|(index, m)| (index, m.is_bls(), m.message().nonce))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            vec![(0, true, 0), (0, false, 0), (1, true, 1), (1, false, 1)]
+        );
+
+        // The message set of the block is missing.
+        let mut header = tipset.blocks()[0].clone();
+        header.messages = new_tipset(0, None).blocks()[0].messages.clone();
+        assert!(cs.block_messages(&header).is_err());
+    }
+
+    #[test]
+    fn test_recover_interrupted_head_update() {
+        let store = Store::new(NoDelay, SyncDataStore::new(MapDataStore::new()));
+        let chain = new_chain(3);
+        let cs = ChainStore::new(store.clone());
+        cs.write_ahead(&chain, &[]).unwrap();
+        cs.set_head(&chain[0]).unwrap();
+
+        // The head is updated, but the journal is not pruned.
+        let data = minicbor::to_vec(chain[2].key()).unwrap();
+        DataStoreWrite::put(&store, Key::new(HEAD_KEY), data).unwrap();
+        let cs = ChainStore::new(store.clone());
+        let recovery = cs.recover(|_| bail!("nothing to re-validate")).unwrap();
+        assert_eq!(recovery.head.as_ref(), Some(&chain[2]));
+        assert!(recovery.revalidated.is_empty());
+        assert!(recovery.dropped.is_empty());
+        assert!(cs.pending().is_empty());
+
+        // The journaled tipset whose header is lost is dropped.
+        let next = new_tipset(3, Some(&chain[2]));
+        cs.write_ahead(&[next.clone()], &[]).unwrap();
+        BlockStore::delete(&store, &next.cids()[0]).unwrap();
+        let cs = ChainStore::new(store);
+        let recovery = cs.recover(|_| Ok(())).unwrap();
+        assert_eq!(recovery.dropped, vec![next.key().clone()]);
+    }
+}
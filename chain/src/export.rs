@@ -0,0 +1,297 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashSet;
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use cid::{Cid, Codec};
+
+use ipfs_blockstore::BlockStore;
+use ipld::car::CarWriter;
+use ipld::Value;
+use plum_block::BlockHeader;
+use plum_tipset::Tipset;
+use plum_types::ChainEpoch;
+
+// The max number of the blocks of a DAG loaded at once, which bounds the memory of the walk.
+const LOAD_BATCH_SIZE: usize = 1024;
+
+/// The options of the chain export, the same as the ones of `lotus chain export`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// The number of the recent epochs whose state trees are exported (`--recent-roots`),
+    /// the state tree of the genesis is always exported.
+    pub recent_roots: ChainEpoch,
+    /// Export only the messages of the recent epochs (`--skip-old-msgs`),
+    /// the messages of all the epochs are exported otherwise.
+    pub skip_old_msgs: bool,
+    /// Export the message receipts of the recent epochs (`--include-receipts`).
+    pub include_receipts: bool,
+}
+
+/// The statistics of a chain export.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExportStats {
+    /// The number of the exported block headers.
+    pub headers: usize,
+    /// The number of the exported blocks, including the block headers.
+    pub blocks: usize,
+}
+
+/// Export the chain from the `head` back to the genesis as a CARv1 file rooted at the
+/// block headers of the `head`.
+///
+/// All the block headers are exported, the messages, the state trees and the message receipts
+/// are exported according to the `options`, only the DAG-CBOR blocks are exported and every
+/// block is exported once. An error is returned if any of the walked blocks is missing.
+/// See `ChainStore.WalkSnapshot` of lotus for details.
+pub fn export_chain<S, W>(
+    store: &S,
+    head: &Tipset,
+    options: &ExportOptions,
+    writer: W,
+) -> Result<ExportStats>
+where
+    S: BlockStore,
+    W: Write,
+{
+    let mut exporter = Exporter {
+        store,
+        writer: CarWriter::new(writer, head.cids().to_vec())?,
+        seen: HashSet::new(),
+        stats: ExportStats::default(),
+    };
+    let recent = head.height() - options.recent_roots;
+
+    // The block headers are walked by generations, the headers of each generation are loaded
+    // at once.
+    let mut headers = head.cids().to_vec();
+    while !headers.is_empty() {
+        let seen = &mut exporter.seen;
+        let cids = headers
+            .drain(..)
+            .filter(|cid| seen.insert(cid.clone()))
+            .collect::<Vec<_>>();
+        for (cid, data) in cids.iter().zip(exporter.load_many(&cids)?) {
+            let header = minicbor::decode::<BlockHeader>(&data)
+                .map_err(|err| anyhow!("failed to decode block header {}: {}", cid, err))?;
+            exporter.writer.write_block(cid, &data)?;
+            exporter.stats.headers += 1;
+            exporter.stats.blocks += 1;
+
+            if !options.skip_old_msgs || header.height > recent {
+                exporter.walk_dag(&header.messages)?;
+            }
+            if header.height > 0 {
+                headers.extend(header.parents.iter().cloned());
+            } else {
+                // The parents of the genesis are not block headers.
+                for parent in &header.parents {
+                    exporter.walk_dag(parent)?;
+                }
+            }
+            if header.height == 0 || header.height > recent {
+                exporter.walk_dag(&header.parent_state_root)?;
+                if options.include_receipts {
+                    exporter.walk_dag(&header.parent_message_receipts)?;
+                }
+            }
+        }
+    }
+
+    exporter.writer.finish()?;
+    debug!(
+        "[export] exported {} headers, {} blocks from height {}",
+        exporter.stats.headers,
+        exporter.stats.blocks,
+        head.height()
+    );
+    Ok(exporter.stats)
+}
+
+struct Exporter<'a, S, W> {
+    store: &'a S,
+    writer: CarWriter<W>,
+    seen: HashSet<Cid>,
+    stats: ExportStats,
+}
+
+impl<'a, S: BlockStore, W: Write> Exporter<'a, S, W> {
+    // Load the blocks of the `cids` at once by `BlockStore::get_many`.
+    fn load_many(&self, cids: &[Cid]) -> Result<Vec<Vec<u8>>> {
+        let blocks = BlockStore::get_many(self.store, cids)?;
+        blocks
+            .into_iter()
+            .zip(cids)
+            .map(|(block, cid)| match block {
+                Some(block) => Ok(block.data().to_vec()),
+                None => Err(anyhow!("block {} is missing", cid)),
+            })
+            .collect()
+    }
+
+    // Export all the DAG-CBOR blocks reachable from the `root`, the pending blocks are loaded
+    // at once in batches of `LOAD_BATCH_SIZE`, the latest pending ones first.
+    fn walk_dag(&mut self, root: &Cid) -> Result<()> {
+        let mut pending = vec![root.clone()];
+        while !pending.is_empty() {
+            let start = pending.len().saturating_sub(LOAD_BATCH_SIZE);
+            let seen = &mut self.seen;
+            let cids = pending
+                .drain(start..)
+                .filter(|cid| cid.codec() == Codec::DagCBOR && seen.insert(cid.clone()))
+                .collect::<Vec<_>>();
+            for (cid, data) in cids.iter().zip(self.load_many(&cids)?) {
+                let value = minicbor::decode::<Value>(&data)
+                    .map_err(|err| anyhow!("failed to decode block {}: {}", cid, err))?;
+                self.writer.write_block(cid, &data)?;
+                self.stats.blocks += 1;
+                collect_links(&value, &mut pending);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn collect_links(value: &Value, links: &mut Vec<Cid>) {
+    match value {
+        Value::Link(cid) => links.push(cid.clone()),
+        Value::List(list) => list.iter().for_each(|value| collect_links(value, links)),
+        Value::Map(map) => map.values().for_each(|value| collect_links(value, links)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use ipld::car::CarReader;
+    use ipld::IpldStore;
+
+    use crate::test_utils::{new_header, NoDelay, Store};
+
+    struct Epoch {
+        header: Cid,
+        messages: Vec<Cid>,
+        state: Vec<Cid>,
+        receipts: Vec<Cid>,
+    }
+
+    // Put a DAG of two blocks, the root links to a leaf, return the CIDs of the DAG.
+    fn put_dag(store: &Store, name: &str, height: i64) -> Vec<Cid> {
+        let leaf = IpldStore::put(store, Value::String(format!("{} {}", name, height))).unwrap();
+        let root = IpldStore::put(
+            store,
+            Value::List(vec![
+                Value::Link(leaf.clone()),
+                Value::Integer(height.into()),
+            ]),
+        )
+        .unwrap();
+        vec![root, leaf]
+    }
+
+    // Build a chain of `len` epochs with one block per tipset.
+    fn new_chain(store: &Store, len: i64) -> Vec<Epoch> {
+        let mut epochs: Vec<Epoch> = vec![];
+        for height in 0..len {
+            let messages = put_dag(store, "messages", height);
+            let state = put_dag(store, "state", height);
+            let receipts = put_dag(store, "receipts", height);
+            let parents = epochs
+                .last()
+                .map(|e| vec![e.header.clone()])
+                .unwrap_or_default();
+            let header = BlockHeader {
+                parent_message_receipts: receipts[0].clone(),
+                messages: messages[0].clone(),
+                parent_state_root: state[0].clone(),
+                ..new_header(parents, height, 1000)
+            };
+            let cid = IpldStore::put(store, &header).unwrap();
+            assert_eq!(cid, header.cid());
+            epochs.push(Epoch {
+                header: cid,
+                messages,
+                state,
+                receipts,
+            });
+        }
+        epochs
+    }
+
+    fn export(store: &Store, epochs: &[Epoch], options: ExportOptions) -> HashSet<Cid> {
+        let head = epochs.last().unwrap();
+        let header = IpldStore::get::<BlockHeader>(store, &head.header)
+            .unwrap()
+            .unwrap();
+        let head = Tipset::new(vec![header]).unwrap();
+        let mut car = vec![];
+        let stats = export_chain(store, &head, &options, &mut car).unwrap();
+        assert_eq!(stats.headers, epochs.len());
+
+        let mut reader = CarReader::new(car.as_slice()).unwrap();
+        assert_eq!(reader.header().roots, head.cids().to_vec());
+        let mut cids = HashSet::new();
+        while let Some((cid, data)) = reader.next_block().unwrap() {
+            assert_eq!(
+                BlockStore::get(store, &cid).unwrap().unwrap().data(),
+                &data[..]
+            );
+            assert!(cids.insert(cid), "block is exported twice");
+        }
+        assert_eq!(cids.len(), stats.blocks);
+        cids
+    }
+
+    #[test]
+    fn test_export_chain() {
+        let store = Store::new(NoDelay, SyncDataStore::new(MapDataStore::new()));
+        let epochs = new_chain(&store, 4);
+        let expected = |messages: &[usize], state: &[usize], receipts: &[usize]| {
+            let mut cids = epochs
+                .iter()
+                .map(|e| e.header.clone())
+                .collect::<HashSet<_>>();
+            cids.extend(messages.iter().flat_map(|i| epochs[*i].messages.clone()));
+            cids.extend(state.iter().flat_map(|i| epochs[*i].state.clone()));
+            cids.extend(receipts.iter().flat_map(|i| epochs[*i].receipts.clone()));
+            cids
+        };
+
+        // All the messages, the genesis state only.
+        let cids = export(&store, &epochs, ExportOptions::default());
+        assert_eq!(cids, expected(&[0, 1, 2, 3], &[0], &[]));
+
+        // The messages and the states of the latest two epochs, the genesis state.
+        let options = ExportOptions {
+            recent_roots: 2,
+            skip_old_msgs: true,
+            include_receipts: false,
+        };
+        let cids = export(&store, &epochs, options);
+        assert_eq!(cids, expected(&[2, 3], &[0, 2, 3], &[]));
+
+        let options = ExportOptions {
+            recent_roots: 2,
+            skip_old_msgs: false,
+            include_receipts: true,
+        };
+        let cids = export(&store, &epochs, options);
+        assert_eq!(cids, expected(&[0, 1, 2, 3], &[0, 2, 3], &[0, 2, 3]));
+
+        // A missing block fails the export.
+        BlockStore::delete(&store, &epochs[3].state[1]).unwrap();
+        let head = IpldStore::get::<BlockHeader>(&store, &epochs[3].header)
+            .unwrap()
+            .unwrap();
+        let head = Tipset::new(vec![head]).unwrap();
+        let options = ExportOptions {
+            recent_roots: 1,
+            ..Default::default()
+        };
+        assert!(export_chain(&store, &head, &options, vec![]).is_err());
+    }
+}
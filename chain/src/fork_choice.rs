@@ -0,0 +1,98 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::cmp::Ordering;
+
+use plum_bigint::BigInt;
+use plum_block::Ticket;
+use plum_tipset::Tipset;
+
+/// Compare the tipsets of equal weight by their tickets.
+///
+/// The tickets of each tipset are sorted by their hashes and compared pairwise, the tipset with
+/// the first smaller ticket wins, i.e. it's ordered as `Ordering::Greater`.
+/// The tie is left unbroken (`Ordering::Equal`) if all the compared tickets are the same.
+pub fn break_weight_tie(a: &Tipset, b: &Tipset) -> Ordering {
+    let a_tickets = sorted_tickets(a);
+    let b_tickets = sorted_tickets(b);
+    a_tickets
+        .iter()
+        .zip(&b_tickets)
+        .map(|(a, b)| b.cmp_by_hash(a))
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Compare the tipsets with their weights, the heavier tipset is ordered as `Ordering::Greater`,
+/// and the tie between the tipsets of equal weight is broken by `break_weight_tie`.
+pub fn compare_tipsets(a_weight: &BigInt, a: &Tipset, b_weight: &BigInt, b: &Tipset) -> Ordering {
+    a_weight.cmp(b_weight).then_with(|| break_weight_tie(a, b))
+}
+
+/// Return true if the `candidate` should replace the `current` heaviest tipset.
+pub fn is_heavier(
+    candidate_weight: &BigInt,
+    candidate: &Tipset,
+    current_weight: &BigInt,
+    current: &Tipset,
+) -> bool {
+    compare_tipsets(candidate_weight, candidate, current_weight, current) == Ordering::Greater
+}
+
+fn sorted_tickets(tipset: &Tipset) -> Vec<&Ticket> {
+    let mut tickets = tipset
+        .blocks()
+        .iter()
+        .map(|block| &block.ticket)
+        .collect::<Vec<_>>();
+    tickets.sort_by(|a, b| a.cmp_by_hash(b));
+    tickets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_block::BlockHeader;
+
+    use crate::test_utils::new_header;
+
+    fn new_tipset(tickets: &[u8]) -> Tipset {
+        let headers = tickets
+            .iter()
+            .map(|ticket| BlockHeader {
+                ticket: Ticket::new(vec![*ticket; 32]),
+                ..new_header(vec![], 1, 1000 + u64::from(*ticket))
+            })
+            .collect();
+        Tipset::new(headers).unwrap()
+    }
+
+    #[test]
+    fn test_compare_tipsets() {
+        let (small, large) = {
+            let (a, b) = (Ticket::new(vec![1; 32]), Ticket::new(vec![2; 32]));
+            if a.cmp_by_hash(&b) == Ordering::Less {
+                (1, 2)
+            } else {
+                (2, 1)
+            }
+        };
+        let a = new_tipset(&[small]);
+        let b = new_tipset(&[large]);
+        let weight = BigInt::from(100);
+
+        assert_eq!(break_weight_tie(&a, &b), Ordering::Greater);
+        assert_eq!(break_weight_tie(&b, &a), Ordering::Less);
+        assert_eq!(break_weight_tie(&a, &a), Ordering::Equal);
+        assert!(is_heavier(&weight, &a, &weight, &b));
+        assert!(!is_heavier(&weight, &b, &weight, &a));
+        assert!(!is_heavier(&weight, &a, &weight, &a));
+        // The weight always takes precedence over the tickets.
+        assert!(is_heavier(&BigInt::from(101), &b, &weight, &a));
+
+        // The tickets are compared in the order of their hashes.
+        let c = new_tipset(&[large, small]);
+        assert_eq!(break_weight_tie(&c, &a), Ordering::Equal);
+        assert_eq!(break_weight_tie(&c, &b), Ordering::Greater);
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{HashSet, VecDeque};
+
+use parking_lot::Mutex;
+
+use plum_bigint::BigInt;
+use plum_message::UnsignedMessage;
+use plum_tipset::Tipset;
+use plum_types::{ChainEpoch, BLOCK_GAS_TARGET};
+
+/// The min gas premium returned by the estimation.
+pub const MIN_GAS_PREMIUM: u64 = 100_000;
+/// The default number of the recent tipsets kept in the gas premium tracker.
+pub const DEFAULT_GAS_PREMIUM_WINDOW: usize = 100;
+
+// The gas premiums and the gas limits of the unique messages included in a tipset.
+struct IncludedPremiums {
+    height: ChainEpoch,
+    blocks: usize,
+    premiums: Vec<(BigInt, BigInt)>,
+}
+
+/// The tracker of the gas premiums of the messages included in the recent tipsets,
+/// which is the data source of the gas premium estimation.
+///
+/// The tipsets are added as the head advances, and removed on reverts, only the latest
+/// `window` tipsets are kept.
+pub struct GasPremiumTracker {
+    window: usize,
+    tipsets: Mutex<VecDeque<IncludedPremiums>>,
+}
+
+impl Default for GasPremiumTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_GAS_PREMIUM_WINDOW)
+    }
+}
+
+impl GasPremiumTracker {
+    /// Create a new tracker keeping the latest `window` tipsets.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "window of gas premium tracker must be positive");
+        Self {
+            window,
+            tipsets: Mutex::new(VecDeque::with_capacity(window)),
+        }
+    }
+
+    /// Add the messages included in the blocks of `tipset`, the message included by several
+    /// blocks is counted only once. The tipsets at or above the height of `tipset` are replaced.
+    pub fn add_tipset(&self, tipset: &Tipset, messages: &[UnsignedMessage]) {
+        let mut seen = HashSet::new();
+        let premiums = messages
+            .iter()
+            .filter(|message| seen.insert(message.cid()))
+            .map(|message| (message.premium().clone(), BigInt::from(message.gas_limit)))
+            .collect();
+
+        let mut tipsets = self.tipsets.lock();
+        while matches!(tipsets.back(), Some(last) if last.height >= tipset.height()) {
+            tipsets.pop_back();
+        }
+        tipsets.push_back(IncludedPremiums {
+            height: tipset.height(),
+            blocks: tipset.blocks().len(),
+            premiums,
+        });
+        while tipsets.len() > self.window {
+            tipsets.pop_front();
+        }
+    }
+
+    /// Remove the tipsets at or above the `height`, e.g. when the head is reverted.
+    pub fn revert_to(&self, height: ChainEpoch) {
+        let mut tipsets = self.tipsets.lock();
+        while matches!(tipsets.back(), Some(last) if last.height >= height) {
+            tipsets.pop_back();
+        }
+    }
+
+    /// Return the number of the tracked tipsets.
+    pub fn len(&self) -> usize {
+        self.tipsets.lock().len()
+    }
+
+    /// Return true if no tipset is tracked.
+    pub fn is_empty(&self) -> bool {
+        self.tipsets.lock().is_empty()
+    }
+
+    /// Estimate the gas premium for a message to be included within `nblocks_wait` epochs.
+    ///
+    /// The messages included in the latest `nblocks_wait * 2` tipsets are sorted by their gas
+    /// premiums in descending order, the premium is the one (the average of the two adjacent
+    /// premiums) at which the accumulated gas limit reaches half of the gas target of the blocks,
+    /// moved 5% further. The estimation is at least `MIN_GAS_PREMIUM`.
+    /// See lotus/node/impl/full/gas.go for details.
+    pub fn estimate_gas_premium(&self, nblocks_wait: u64) -> BigInt {
+        let nblocks_wait = nblocks_wait.max(1) as usize;
+        let tipsets = self.tipsets.lock();
+        let recent = tipsets.iter().rev().take(nblocks_wait * 2);
+
+        let mut blocks = 0;
+        let mut premiums = vec![];
+        for tipset in recent {
+            blocks += tipset.blocks;
+            premiums.extend(tipset.premiums.iter().cloned());
+        }
+        drop(tipsets);
+
+        let premium = median_gas_premium(premiums, blocks);
+        premium.max(BigInt::from(MIN_GAS_PREMIUM))
+    }
+}
+
+fn median_gas_premium(mut premiums: Vec<(BigInt, BigInt)>, blocks: usize) -> BigInt {
+    premiums.sort_by(|a, b| b.0.cmp(&a.0));
+
+    let target = BigInt::from(BLOCK_GAS_TARGET) * BigInt::from(blocks as u64);
+    let mut at = &target / BigInt::from(2) + &target / BigInt::from(2 * 20);
+    let zero = BigInt::from(0);
+    let (mut prev1, mut prev2) = (zero.clone(), zero.clone());
+    for (premium, gas_limit) in premiums {
+        prev2 = std::mem::replace(&mut prev1, premium);
+        at -= gas_limit;
+        if at < zero {
+            break;
+        }
+    }
+
+    if prev2 != zero {
+        (prev1 + prev2) / BigInt::from(2)
+    } else {
+        prev1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_address::Address;
+    use plum_message::MESSAGE_VERSION_FEE_MARKET;
+
+    use crate::test_utils::new_tipset;
+
+    fn new_message(nonce: u64, gas_premium: u64, gas_limit: u64) -> UnsignedMessage {
+        UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(100).unwrap(),
+            from: Address::new_id_addr(101).unwrap(),
+            nonce,
+            value: 0u64.into(),
+            gas_price: 0u64.into(),
+            gas_limit: gas_limit as i64,
+            gas_fee_cap: (gas_premium * 2).into(),
+            gas_premium: gas_premium.into(),
+            method: 0,
+            params: vec![],
+        }
+    }
+
+    #[test]
+    fn test_estimate_gas_premium() {
+        let tracker = GasPremiumTracker::new(4);
+        assert_eq!(
+            tracker.estimate_gas_premium(1),
+            BigInt::from(MIN_GAS_PREMIUM)
+        );
+
+        // Half of the gas target plus 5% is 27.5M, which is reached by the third message.
+        let quarter = BLOCK_GAS_TARGET / 4;
+        let messages = vec![
+            new_message(0, 300_000, quarter),
+            new_message(1, 500_000, quarter),
+            new_message(2, 400_000, quarter),
+            new_message(3, 200_000, quarter),
+        ];
+        tracker.add_tipset(&new_tipset(vec![], 1), &messages);
+        assert_eq!(tracker.estimate_gas_premium(1), BigInt::from(350_000));
+
+        // The duplicated messages are counted once.
+        let mut duplicated = messages.clone();
+        duplicated.extend(messages.iter().cloned());
+        tracker.add_tipset(&new_tipset(vec![], 1), &duplicated);
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(tracker.estimate_gas_premium(1), BigInt::from(350_000));
+
+        // The cheap messages don't lower the estimation below the min gas premium.
+        tracker.add_tipset(&new_tipset(vec![], 2), &[new_message(4, 1, quarter)]);
+        tracker.revert_to(2);
+        assert_eq!(tracker.len(), 1);
+
+        for height in 2..10 {
+            tracker.add_tipset(&new_tipset(vec![], height), &[new_message(4, 1, quarter)]);
+        }
+        assert_eq!(tracker.len(), 4);
+        assert_eq!(
+            tracker.estimate_gas_premium(2),
+            BigInt::from(MIN_GAS_PREMIUM)
+        );
+    }
+}
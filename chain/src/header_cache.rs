@@ -0,0 +1,188 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use plum_block::BlockHeader;
+use plum_tipset::{Tipset, TipsetKey};
+
+use crate::tipset_cache::TipsetLoader;
+
+/// The default number of the decoded block headers kept in the header cache.
+pub const DEFAULT_HEADER_CACHE_SIZE: usize = 8 * 1024;
+
+/// The loader of the raw block headers, which is usually backed by the blockstore.
+///
+/// The loader returns the CBOR encoded header, whatever the header is compressed
+/// or not in the underlying storage.
+pub trait HeaderLoader {
+    /// Load the CBOR encoded block header with the given CID.
+    fn load_header_bytes(&self, cid: &Cid) -> Result<Vec<u8>>;
+}
+
+/// The statistics of the header cache.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HeaderCacheStats {
+    /// The number of the lookups served from the cache.
+    pub hits: u64,
+    /// The number of the lookups which loaded and decoded the header.
+    pub misses: u64,
+}
+
+impl HeaderCacheStats {
+    /// Return the ratio of the lookups served from the cache, `0.0` if there is no lookup.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// The cache of the decoded block headers keyed by CID, bounded by the number of headers,
+/// so that assembling tipsets and computing weights don't decode the same headers repeatedly.
+pub struct HeaderCache<L> {
+    loader: L,
+    headers: Mutex<LruCache<Cid, Arc<BlockHeader>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<L: HeaderLoader> HeaderCache<L> {
+    /// Create a new header cache with the default size.
+    pub fn new(loader: L) -> Self {
+        Self::with_capacity(loader, DEFAULT_HEADER_CACHE_SIZE)
+    }
+
+    /// Create a new header cache with the given size.
+    pub fn with_capacity(loader: L, size: usize) -> Self {
+        Self {
+            loader,
+            headers: Mutex::new(LruCache::new(size)),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Return the decoded block header with the given CID.
+    pub fn get_header(&self, cid: &Cid) -> Result<Arc<BlockHeader>> {
+        if let Some(header) = self.headers.lock().get(cid) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(header.clone());
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let data = self.loader.load_header_bytes(cid)?;
+        let header = minicbor::decode::<BlockHeader>(&data)
+            .map_err(|err| anyhow!("failed to decode block header {}: {}", cid, err))?;
+        let header = Arc::new(header);
+        self.headers.lock().put(cid.clone(), header.clone());
+        Ok(header)
+    }
+
+    /// Put a decoded block header into the cache, e.g. a header received from the network.
+    pub fn put_header(&self, header: BlockHeader) -> Arc<BlockHeader> {
+        let header = Arc::new(header);
+        self.headers.lock().put(header.cid(), header.clone());
+        header
+    }
+
+    /// Return the number of the cached headers.
+    pub fn len(&self) -> usize {
+        self.headers.lock().len()
+    }
+
+    /// Return true if no header is cached.
+    pub fn is_empty(&self) -> bool {
+        self.headers.lock().is_empty()
+    }
+
+    /// Return the statistics of the cache.
+    pub fn stats(&self) -> HeaderCacheStats {
+        HeaderCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<L: HeaderLoader> TipsetLoader for HeaderCache<L> {
+    fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>> {
+        let headers = key
+            .cids()
+            .iter()
+            .map(|cid| self.get_header(cid).map(|header| (*header).clone()))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Arc::new(Tipset::new(headers)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use anyhow::bail;
+
+    use crate::test_utils;
+
+    #[derive(Default)]
+    struct MapLoader {
+        headers: HashMap<Cid, Vec<u8>>,
+        loads: AtomicU64,
+    }
+
+    impl HeaderLoader for MapLoader {
+        fn load_header_bytes(&self, cid: &Cid) -> Result<Vec<u8>> {
+            self.loads.fetch_add(1, Ordering::Relaxed);
+            match self.headers.get(cid) {
+                Some(data) => Ok(data.clone()),
+                None => bail!("block header {} not found", cid),
+            }
+        }
+    }
+
+    fn new_header(height: i64) -> BlockHeader {
+        test_utils::new_header(vec![], height, 1000)
+    }
+
+    #[test]
+    fn test_header_cache() {
+        let mut loader = MapLoader::default();
+        let headers = (1..=3).map(new_header).collect::<Vec<_>>();
+        for header in &headers {
+            loader
+                .headers
+                .insert(header.cid(), minicbor::to_vec(header).unwrap());
+        }
+        let cache = HeaderCache::with_capacity(loader, 2);
+
+        let cid = headers[0].cid();
+        assert_eq!(*cache.get_header(&cid).unwrap(), headers[0]);
+        assert_eq!(*cache.get_header(&cid).unwrap(), headers[0]);
+        assert_eq!(cache.loader.loads.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.stats(), HeaderCacheStats { hits: 1, misses: 1 });
+        assert_eq!(cache.stats().hit_rate(), 0.5);
+
+        // The least recently used header is evicted.
+        cache.get_header(&headers[1].cid()).unwrap();
+        cache.get_header(&headers[2].cid()).unwrap();
+        assert_eq!(cache.len(), 2);
+        cache.get_header(&cid).unwrap();
+        assert_eq!(cache.loader.loads.load(Ordering::Relaxed), 4);
+
+        let tipset = cache
+            .load_tipset(&TipsetKey::new(vec![headers[2].cid()]))
+            .unwrap();
+        assert_eq!(tipset.height(), 3);
+        assert!(cache.get_header(&new_header(4).cid()).is_err());
+    }
+}
@@ -1,5 +1,49 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+#[macro_use]
+extern crate log;
+
+mod base_fee;
+mod chain_epoch;
+mod chain_store;
+mod export;
+mod fork_choice;
+mod gas_premium;
+mod header_cache;
+mod mpool_push;
+mod msg_index;
+mod path;
+mod prefetch;
 mod store;
+#[cfg(test)]
+mod test_utils;
+mod tipset_cache;
+mod tipset_messages;
+mod weight;
 
+pub use base_fee::{
+    compute_next_base_fee, estimate_fee_cap, BaseFeeTracker, MessageLoader,
+    DEFAULT_BASE_FEE_CACHE_SIZE,
+};
+pub use chain_epoch::{deadline_info, epoch_at_time, time_of_epoch, ChainEpochClock};
+pub use chain_store::{ChainStore, Recovery, HEAD_KEY, JOURNAL_KEY};
+pub use export::{export_chain, ExportOptions, ExportStats};
+pub use fork_choice::{break_weight_tie, compare_tipsets, is_heavier};
+pub use gas_premium::{GasPremiumTracker, DEFAULT_GAS_PREMIUM_WINDOW, MIN_GAS_PREMIUM};
+pub use header_cache::{HeaderCache, HeaderCacheStats, HeaderLoader, DEFAULT_HEADER_CACHE_SIZE};
+pub use mpool_push::{
+    MpoolPushError, MpoolPushValidator, MpoolStateLoader, PushTrust, BASE_FEE_LOWER_BOUND_FACTOR,
+    MAX_MESSAGE_SIZE, MAX_UNTRUSTED_NONCE_GAP,
+};
+pub use msg_index::{IndexedMessage, MessageIndex};
+pub use path::{chain_get_path, reorg_ops, HeadChange, HeadChangeType};
+pub use prefetch::{
+    BlockFetcher, PrefetchHandle, PrefetchStats, Prefetcher, DEFAULT_PREFETCH_WORKERS,
+};
 pub use store::*;
+pub use tipset_cache::{TipsetCache, TipsetLoader, DEFAULT_SKIP_LENGTH, DEFAULT_TIPSET_CACHE_SIZE};
+pub use tipset_messages::select_tipset_messages;
+pub use weight::{
+    compute_weight, WeightError, WeightLoader, WeightVerifier, DEFAULT_MAX_VERIFY_DEPTH,
+    W_RATIO_DEN, W_RATIO_NUM,
+};
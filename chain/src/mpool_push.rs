@@ -0,0 +1,329 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use cid::Cid;
+
+use plum_address::Address;
+use plum_bigint::BigInt;
+use plum_message::SignedMessage;
+use plum_types::{Actor, Gas, TokenAmount};
+
+/// The max size of the CBOR encoded message accepted by the message pool.
+pub const MAX_MESSAGE_SIZE: usize = 32 << 10;
+/// The max gap between the nonce of an untrusted message and the next expected nonce.
+pub const MAX_UNTRUSTED_NONCE_GAP: u64 = 4;
+/// The fee cap of an untrusted message must be at least `base_fee / BASE_FEE_LOWER_BOUND_FACTOR`.
+pub const BASE_FEE_LOWER_BOUND_FACTOR: u64 = 10;
+
+/// How much the pushed messages are trusted, which decides the validation path.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PushTrust {
+    /// The messages from the local node or the authorized users (`MpoolPush`, `MpoolBatchPush`),
+    /// only the stateless checks are performed, the stateful ones are left to the block inclusion.
+    Trusted,
+    /// The messages relayed from the third parties (`MpoolPushUntrusted`,
+    /// `MpoolBatchPushUntrusted`), the messages are also checked against the state of the head.
+    Untrusted,
+}
+
+/// The errors of validating a pushed message, the message should be rejected with the reason.
+#[derive(Debug, thiserror::Error)]
+pub enum MpoolPushError {
+    /// The encoded message is too large.
+    #[error("message size {0} exceeds the max size {}", MAX_MESSAGE_SIZE)]
+    TooLarge(usize),
+    /// The message is malformed, e.g. the value is negative.
+    #[error("invalid message: {0}")]
+    Invalid(String),
+    /// The signature doesn't match the sender.
+    #[error("invalid signature of message from {0}")]
+    InvalidSignature(Address),
+    /// The sender doesn't exist in the state of the head.
+    #[error("sender {0} not found")]
+    SenderNotFound(Address),
+    /// The nonce has been used by the sender.
+    #[error("nonce {nonce} too low, the next nonce of {from} is {expected}")]
+    NonceTooLow {
+        /// The sender.
+        from: Address,
+        /// The nonce of the message.
+        nonce: u64,
+        /// The next nonce of the sender.
+        expected: u64,
+    },
+    /// The nonce is too far ahead of the next nonce of the sender.
+    #[error("nonce {nonce} too far ahead of the next nonce {expected} of {from}")]
+    NonceGap {
+        /// The sender.
+        from: Address,
+        /// The nonce of the message.
+        nonce: u64,
+        /// The next nonce of the sender.
+        expected: u64,
+    },
+    /// The sender can't afford the messages.
+    #[error("sender {from} balance {balance} less than required {required}")]
+    InsufficientFunds {
+        /// The sender.
+        from: Address,
+        /// The balance of the sender.
+        balance: TokenAmount,
+        /// The funds required by the pushed messages of the sender.
+        required: TokenAmount,
+    },
+    /// The fee cap is too low to be included in any block soon.
+    #[error("fee cap {fee_cap} less than the lower bound {lower_bound} of base fee")]
+    FeeCapTooLow {
+        /// The fee cap of the message.
+        fee_cap: TokenAmount,
+        /// The lower bound of the base fee.
+        lower_bound: TokenAmount,
+    },
+    /// Failed to load the state of the head.
+    #[error("failed to load the state: {0}")]
+    State(#[from] anyhow::Error),
+}
+
+/// The accessor of the state of the head required for validating the pushed messages.
+pub trait MpoolStateLoader {
+    /// Return the actor in the state of the head, `None` if the actor doesn't exist.
+    fn actor(&self, addr: &Address) -> anyhow::Result<Option<Actor>>;
+
+    /// Return the base fee of the next tipset.
+    fn base_fee(&self) -> anyhow::Result<TokenAmount>;
+
+    /// Return the min gas of including a message of `msg_size` bytes on chain.
+    fn min_gas(&self, msg_size: usize) -> Gas;
+}
+
+// The messages of the sender accepted by the validator, used for checking the batch.
+struct Sender {
+    next_nonce: u64,
+    balance: TokenAmount,
+    required: TokenAmount,
+}
+
+/// The validator of the messages pushed into the message pool.
+///
+/// The validator keeps the messages accepted so far, so that the messages of a batch from the
+/// same sender are checked against each other, e.g. the nonces must be increasing and the
+/// balance must cover all the messages.
+pub struct MpoolPushValidator<L> {
+    loader: L,
+    trust: PushTrust,
+    senders: HashMap<Address, Sender>,
+}
+
+impl<L: MpoolStateLoader> MpoolPushValidator<L> {
+    /// Create a validator of the messages with the `trust`.
+    pub fn new(loader: L, trust: PushTrust) -> Self {
+        Self {
+            loader,
+            trust,
+            senders: HashMap::new(),
+        }
+    }
+
+    /// Validate the message, return the CID of the message if it's accepted.
+    pub fn validate(&mut self, msg: &SignedMessage) -> Result<Cid, MpoolPushError> {
+        let data =
+            minicbor::to_vec(msg).expect("CBOR serialization of SignedMessage shouldn't be failed");
+        if data.len() > MAX_MESSAGE_SIZE {
+            return Err(MpoolPushError::TooLarge(data.len()));
+        }
+        msg.message
+            .validate_for_block_inclusion(self.loader.min_gas(data.len()))
+            .map_err(|err| MpoolPushError::Invalid(err.to_string()))?;
+        match msg.verify_signature() {
+            Ok(true) => {}
+            _ => return Err(MpoolPushError::InvalidSignature(msg.message.from.clone())),
+        }
+        if self.trust == PushTrust::Untrusted {
+            self.validate_state(msg)?;
+        }
+        Ok(msg.cid_with_data(data))
+    }
+
+    /// Validate the messages in order, return the CIDs of the messages if all are accepted.
+    ///
+    /// The batch is rejected at the first invalid message, the same as Lotus.
+    pub fn validate_batch(&mut self, msgs: &[SignedMessage]) -> Result<Vec<Cid>, MpoolPushError> {
+        msgs.iter().map(|msg| self.validate(msg)).collect()
+    }
+
+    fn validate_state(&mut self, msg: &SignedMessage) -> Result<(), MpoolPushError> {
+        let base_fee = self.loader.base_fee()?;
+        let lower_bound = base_fee / BigInt::from(BASE_FEE_LOWER_BOUND_FACTOR);
+        let fee_cap = msg.message.fee_cap();
+        if *fee_cap < lower_bound {
+            return Err(MpoolPushError::FeeCapTooLow {
+                fee_cap: fee_cap.clone(),
+                lower_bound,
+            });
+        }
+
+        let from = &msg.message.from;
+        if !self.senders.contains_key(from) {
+            let actor = self
+                .loader
+                .actor(from)?
+                .ok_or_else(|| MpoolPushError::SenderNotFound(from.clone()))?;
+            let sender = Sender {
+                next_nonce: actor.nonce,
+                balance: actor.balance,
+                required: BigInt::from(0),
+            };
+            self.senders.insert(from.clone(), sender);
+        }
+        let sender = self
+            .senders
+            .get_mut(from)
+            .expect("the sender is inserted above; qed");
+
+        let nonce = msg.message.nonce;
+        if nonce < sender.next_nonce {
+            return Err(MpoolPushError::NonceTooLow {
+                from: from.clone(),
+                nonce,
+                expected: sender.next_nonce,
+            });
+        }
+        if nonce > sender.next_nonce.saturating_add(MAX_UNTRUSTED_NONCE_GAP) {
+            return Err(MpoolPushError::NonceGap {
+                from: from.clone(),
+                nonce,
+                expected: sender.next_nonce,
+            });
+        }
+        let required = &sender.required + msg.message.required_funds();
+        if required > sender.balance {
+            return Err(MpoolPushError::InsufficientFunds {
+                from: from.clone(),
+                balance: sender.balance.clone(),
+                required,
+            });
+        }
+        sender.next_nonce = nonce.saturating_add(1);
+        sender.required = required;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_actor::codes::ACCOUNT_ACTOR_CODE_ID;
+    use plum_crypto::{PrivateKey, PublicKey, Signature};
+    use plum_message::{UnsignedMessage, MESSAGE_VERSION_FEE_MARKET};
+
+    struct MockLoader {
+        actors: HashMap<Address, Actor>,
+    }
+
+    impl MpoolStateLoader for MockLoader {
+        fn actor(&self, addr: &Address) -> anyhow::Result<Option<Actor>> {
+            Ok(self.actors.get(addr).cloned())
+        }
+
+        fn base_fee(&self) -> anyhow::Result<TokenAmount> {
+            Ok(BigInt::from(1000))
+        }
+
+        fn min_gas(&self, msg_size: usize) -> Gas {
+            BigInt::from(msg_size as u64 * 2)
+        }
+    }
+
+    fn new_message(privkey: &PrivateKey, from: &Address, nonce: u64, value: u64) -> SignedMessage {
+        let message = UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(1000).unwrap(),
+            from: from.clone(),
+            nonce,
+            value: BigInt::from(value),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        };
+        let signature =
+            Signature::sign_secp256k1(privkey.clone().into_vec(), message.signing_bytes()).unwrap();
+        SignedMessage { message, signature }
+    }
+
+    #[test]
+    fn test_validate() {
+        let new_key = || {
+            let privkey = PrivateKey::generate_secp256k1_privkey();
+            let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+            (privkey, Address::new_secp256k1_addr(&pubkey).unwrap())
+        };
+        let (privkey, from) = new_key();
+        let mut actors = HashMap::new();
+        actors.insert(
+            from.clone(),
+            Actor {
+                code: ACCOUNT_ACTOR_CODE_ID.clone(),
+                head: ACCOUNT_ACTOR_CODE_ID.clone(),
+                nonce: 5,
+                balance: BigInt::from(250_000),
+            },
+        );
+        let loader = || MockLoader {
+            actors: actors.clone(),
+        };
+
+        // The trusted messages skip the stateful checks.
+        let stale = new_message(&privkey, &from, 0, 0);
+        let mut trusted = MpoolPushValidator::new(loader(), PushTrust::Trusted);
+        assert_eq!(trusted.validate(&stale).unwrap(), stale.cid());
+
+        let mut untrusted = MpoolPushValidator::new(loader(), PushTrust::Untrusted);
+        match untrusted.validate(&stale) {
+            Err(MpoolPushError::NonceTooLow { expected: 5, .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match untrusted.validate(&new_message(&privkey, &from, 10, 0)) {
+            Err(MpoolPushError::NonceGap { expected: 5, .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // The balance covers the first two messages of the batch, but not the third one.
+        let batch = vec![
+            new_message(&privkey, &from, 5, 0),
+            new_message(&privkey, &from, 6, 0),
+            new_message(&privkey, &from, 7, 100_000),
+        ];
+        let mut untrusted = MpoolPushValidator::new(loader(), PushTrust::Untrusted);
+        assert_eq!(untrusted.validate_batch(&batch[..2]).unwrap().len(), 2);
+        match untrusted.validate(&batch[1]) {
+            Err(MpoolPushError::NonceTooLow { expected: 7, .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match untrusted.validate(&batch[2]) {
+            Err(MpoolPushError::InsufficientFunds { .. }) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let mut trusted = MpoolPushValidator::new(loader(), PushTrust::Trusted);
+        assert_eq!(trusted.validate_batch(&batch).unwrap().len(), 3);
+
+        let mut forged = new_message(&privkey, &from, 5, 0);
+        forged.message.value = BigInt::from(1);
+        let mut trusted = MpoolPushValidator::new(loader(), PushTrust::Trusted);
+        match trusted.validate(&forged) {
+            Err(MpoolPushError::InvalidSignature(_)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        let (privkey, unknown) = new_key();
+        let mut untrusted = MpoolPushValidator::new(loader(), PushTrust::Untrusted);
+        match untrusted.validate(&new_message(&privkey, &unknown, 0, 0)) {
+            Err(MpoolPushError::SenderNotFound(addr)) => assert_eq!(addr, unknown),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
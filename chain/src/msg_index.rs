@@ -0,0 +1,231 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use cid::Cid;
+use parking_lot::RwLock;
+
+use plum_address::Address;
+use plum_message::{MessageReceipt, UnsignedMessage};
+use plum_tipset::TipsetKey;
+use plum_types::ChainEpoch;
+
+/// An executed message in the message index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedMessage {
+    /// The CID of the message, see `ChainMessage` for which CID it is.
+    pub cid: Cid,
+    /// The message.
+    pub message: UnsignedMessage,
+    /// The receipt of executing the message.
+    pub receipt: MessageReceipt,
+    /// The epoch of the tipset including the message.
+    pub epoch: ChainEpoch,
+    /// The key of the tipset including the message.
+    pub tipset: TipsetKey,
+}
+
+#[derive(Default)]
+struct Inner {
+    messages: HashMap<Cid, IndexedMessage>,
+    // The messages sent or received by the addresses, in the order they are applied.
+    by_address: HashMap<Address, Vec<Cid>>,
+    by_tipset: HashMap<TipsetKey, Vec<Cid>>,
+}
+
+impl Inner {
+    fn unlink(&mut self, addr: &Address, cid: &Cid) {
+        if let Some(cids) = self.by_address.get_mut(addr) {
+            cids.retain(|c| c != cid);
+            if cids.is_empty() {
+                self.by_address.remove(addr);
+            }
+        }
+    }
+}
+
+/// The index of the executed messages of the canonical chain by their senders and receivers.
+///
+/// The tipsets are applied with their executed messages and reverted following the head
+/// changes, see `chain_get_path`. The messages are indexed by the addresses as they appear
+/// in the messages, so the ID address and the public key address of an account are indexed
+/// separately.
+#[derive(Default)]
+pub struct MessageIndex {
+    inner: RwLock<Inner>,
+}
+
+impl MessageIndex {
+    /// Create an empty message index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of the indexed messages.
+    pub fn len(&self) -> usize {
+        self.inner.read().messages.len()
+    }
+
+    /// Return true if no message is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Index the messages executed in the tipset, along with their receipts.
+    ///
+    /// The messages already indexed by another tipset are skipped.
+    pub fn apply<I>(&self, tipset: &TipsetKey, epoch: ChainEpoch, messages: I)
+    where
+        I: IntoIterator<Item = (Cid, UnsignedMessage, MessageReceipt)>,
+    {
+        let mut inner = self.inner.write();
+        if inner.by_tipset.contains_key(tipset) {
+            return;
+        }
+        let mut cids = vec![];
+        for (cid, message, receipt) in messages {
+            if inner.messages.contains_key(&cid) {
+                continue;
+            }
+            inner
+                .by_address
+                .entry(message.from.clone())
+                .or_default()
+                .push(cid.clone());
+            if message.to != message.from {
+                inner
+                    .by_address
+                    .entry(message.to.clone())
+                    .or_default()
+                    .push(cid.clone());
+            }
+            let indexed = IndexedMessage {
+                cid: cid.clone(),
+                message,
+                receipt,
+                epoch,
+                tipset: tipset.clone(),
+            };
+            inner.messages.insert(cid.clone(), indexed);
+            cids.push(cid);
+        }
+        inner.by_tipset.insert(tipset.clone(), cids);
+    }
+
+    /// Remove the messages indexed by the reverted tipset.
+    pub fn revert(&self, tipset: &TipsetKey) {
+        let mut inner = self.inner.write();
+        let cids = match inner.by_tipset.remove(tipset) {
+            Some(cids) => cids,
+            None => return,
+        };
+        for cid in cids {
+            if let Some(indexed) = inner.messages.remove(&cid) {
+                inner.unlink(&indexed.message.from, &cid);
+                inner.unlink(&indexed.message.to, &cid);
+            }
+        }
+    }
+
+    /// Return the indexed message of the CID.
+    pub fn get(&self, cid: &Cid) -> Option<IndexedMessage> {
+        self.inner.read().messages.get(cid).cloned()
+    }
+
+    /// Return at most `limit` messages sent or received by the address, the latest first.
+    pub fn history(&self, addr: &Address, limit: usize) -> Vec<IndexedMessage> {
+        let inner = self.inner.read();
+        let cids = match inner.by_address.get(addr) {
+            Some(cids) => cids,
+            None => return vec![],
+        };
+        let mut messages = cids
+            .iter()
+            .map(|cid| inner.messages[cid].clone())
+            .collect::<Vec<_>>();
+        // The later messages of a tipset come first after the stable sort.
+        messages.reverse();
+        messages.sort_by_key(|indexed| std::cmp::Reverse(indexed.epoch));
+        messages.truncate(limit);
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_bigint::BigInt;
+    use plum_message::MESSAGE_VERSION_FEE_MARKET;
+    use plum_vm_exitcode::ExitCode;
+
+    fn message(from: u64, to: u64, nonce: u64) -> (Cid, UnsignedMessage, MessageReceipt) {
+        let message = UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(to).unwrap(),
+            from: Address::new_id_addr(from).unwrap(),
+            nonce,
+            value: BigInt::from(100),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        };
+        let receipt = MessageReceipt {
+            exit_code: ExitCode::Ok,
+            r#return: vec![],
+            gas_used: BigInt::from(500),
+        };
+        (message.cid(), message, receipt)
+    }
+
+    fn tipset_key(seed: u64) -> TipsetKey {
+        // Any CID works as the block CID of the tipset key.
+        TipsetKey::new(vec![message(seed, seed, seed).0])
+    }
+
+    #[test]
+    fn test_message_history() {
+        let index = MessageIndex::new();
+        let (first, second) = (tipset_key(1), tipset_key(2));
+        let sent = message(1000, 1001, 0);
+        let received = message(1002, 1000, 0);
+        let other = message(1001, 1002, 0);
+        let own = message(1000, 1000, 1);
+
+        index.apply(&first, 10, vec![sent.clone(), other.clone()]);
+        index.apply(
+            &second,
+            11,
+            vec![received.clone(), own.clone(), sent.clone()],
+        );
+        assert_eq!(index.len(), 4);
+
+        let addr = Address::new_id_addr(1000).unwrap();
+        let history = index
+            .history(&addr, 10)
+            .into_iter()
+            .map(|indexed| (indexed.cid, indexed.epoch))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            vec![
+                (own.0.clone(), 11),
+                (received.0.clone(), 11),
+                (sent.0.clone(), 10)
+            ]
+        );
+        assert_eq!(index.history(&addr, 1).len(), 1);
+        assert_eq!(index.get(&other.0).unwrap().tipset, first);
+
+        // Revert the tipsets in a reorg.
+        index.revert(&second);
+        assert_eq!(index.history(&addr, 10).len(), 1);
+        assert!(index.get(&own.0).is_none());
+        index.revert(&first);
+        assert!(index.is_empty());
+        assert!(index.history(&addr, 10).is_empty());
+    }
+}
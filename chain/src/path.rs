@@ -0,0 +1,171 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+
+use plum_tipset::{Tipset, TipsetKey};
+
+use crate::tipset_cache::TipsetLoader;
+
+/// The type of a head change.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HeadChangeType {
+    /// The tipset is removed from the chain.
+    Revert,
+    /// The tipset is added to the chain.
+    Apply,
+}
+
+/// A step of the path between two tipsets.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HeadChange {
+    /// The type of the change.
+    pub change: HeadChangeType,
+    /// The reverted or applied tipset.
+    pub tipset: Arc<Tipset>,
+}
+
+/// Find the common ancestor of `from` and `to`, return the tipsets to revert from `from` down
+/// to the ancestor (exclusive), and the tipsets to apply from the ancestor (exclusive) up to
+/// `to`, both in the order they should be processed. See ReorgOps of Lotus.
+pub fn reorg_ops<L>(
+    loader: &L,
+    from: Arc<Tipset>,
+    to: Arc<Tipset>,
+) -> Result<(Vec<Arc<Tipset>>, Vec<Arc<Tipset>>)>
+where
+    L: TipsetLoader + ?Sized,
+{
+    let (mut left, mut right) = (from, to);
+    let (mut reverts, mut applies) = (vec![], vec![]);
+    while left.key() != right.key() {
+        // The tipset with the greater height can't be the common ancestor,
+        // the tipsets of the same height are both stepped back, right first.
+        let step_left = left.height() > right.height();
+        let tipset = if step_left { &left } else { &right };
+        if tipset.height() == 0 {
+            return Err(anyhow!(
+                "no common ancestor of tipsets {} and {}",
+                left.key(),
+                right.key()
+            ));
+        }
+        let parent = loader.load_tipset(&tipset.parents())?;
+        if step_left {
+            reverts.push(std::mem::replace(&mut left, parent));
+        } else {
+            applies.push(std::mem::replace(&mut right, parent));
+        }
+    }
+    applies.reverse();
+    Ok((reverts, applies))
+}
+
+/// Return the head changes for moving the head from the tipset `from` to the tipset `to`,
+/// i.e. the reverts down to their common ancestor followed by the applies up to `to`.
+///
+/// It's the implementation of `ChainGetPath`, used by the indexers and the events to catch up
+/// with the chain after a disconnect.
+pub fn chain_get_path<L>(loader: &L, from: &TipsetKey, to: &TipsetKey) -> Result<Vec<HeadChange>>
+where
+    L: TipsetLoader + ?Sized,
+{
+    let from = loader.load_tipset(from)?;
+    let to = loader.load_tipset(to)?;
+    let (reverts, applies) = reorg_ops(loader, from, to)?;
+    let reverts = reverts.into_iter().map(|tipset| HeadChange {
+        change: HeadChangeType::Revert,
+        tipset,
+    });
+    let applies = applies.into_iter().map(|tipset| HeadChange {
+        change: HeadChangeType::Apply,
+        tipset,
+    });
+    Ok(reverts.chain(applies).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_types::ChainEpoch;
+
+    use crate::test_utils::{new_header, MapLoader};
+
+    // Add the child of the parent tipset, the miner tells the forks apart.
+    fn child(
+        loader: &mut MapLoader,
+        parent: Option<&Arc<Tipset>>,
+        height: ChainEpoch,
+        miner: u64,
+    ) -> Arc<Tipset> {
+        let parents = parent
+            .map(|parent| parent.cids().to_vec())
+            .unwrap_or_default();
+        loader.insert(Tipset::new(vec![new_header(parents, height, miner)]).unwrap())
+    }
+
+    fn path(
+        loader: &MapLoader,
+        from: &Arc<Tipset>,
+        to: &Arc<Tipset>,
+    ) -> Vec<(HeadChangeType, ChainEpoch)> {
+        chain_get_path(loader, from.key(), to.key())
+            .unwrap()
+            .into_iter()
+            .map(|change| (change.change, change.tipset.height()))
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_get_path() {
+        use HeadChangeType::*;
+
+        // 0 - 1 - 2 - 3 - 5
+        //      \
+        //       - 2' - 4'
+        let mut loader = MapLoader::default();
+        let genesis = child(&mut loader, None, 0, 1000);
+        let one = child(&mut loader, Some(&genesis), 1, 1000);
+        let two = child(&mut loader, Some(&one), 2, 1000);
+        let three = child(&mut loader, Some(&two), 3, 1000);
+        let five = child(&mut loader, Some(&three), 5, 1000);
+        let fork_two = child(&mut loader, Some(&one), 2, 1001);
+        let fork_four = child(&mut loader, Some(&fork_two), 4, 1001);
+
+        assert!(path(&loader, &five, &five).is_empty());
+        assert_eq!(
+            path(&loader, &five, &fork_four),
+            vec![
+                (Revert, 5),
+                (Revert, 3),
+                (Revert, 2),
+                (Apply, 2),
+                (Apply, 4)
+            ]
+        );
+        assert_eq!(
+            path(&loader, &fork_four, &five),
+            vec![(Revert, 4), (Revert, 2), (Apply, 2), (Apply, 3), (Apply, 5)]
+        );
+        // The same chain.
+        assert_eq!(path(&loader, &one, &three), vec![(Apply, 2), (Apply, 3)]);
+        assert_eq!(
+            path(&loader, &three, &genesis),
+            vec![(Revert, 3), (Revert, 2), (Revert, 1)]
+        );
+
+        // The forks of the same height.
+        let changes = chain_get_path(&loader, two.key(), fork_two.key()).unwrap();
+        assert_eq!(changes[0].tipset, two);
+        assert_eq!(changes[1].tipset, fork_two);
+
+        // No common ancestor.
+        let other = child(&mut loader, None, 0, 1002);
+        assert!(chain_get_path(&loader, five.key(), other.key()).is_err());
+        // Unknown tipset.
+        let unknown = child(&mut MapLoader::default(), Some(&five), 6, 1000);
+        assert!(chain_get_path(&loader, five.key(), unknown.key()).is_err());
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use anyhow::{anyhow, ensure, Result};
+use cid::Cid;
+use parking_lot::{Condvar, Mutex};
+
+use ipfs_block::Block;
+use ipfs_blockstore::BlockStore;
+use ipld::Value;
+use plum_block::BlockHeader;
+
+/// The default number of the worker threads of a prefetch.
+pub const DEFAULT_PREFETCH_WORKERS: usize = 8;
+
+// The multihash prefix of Blake2b-256 (code 0xb220, length 32), the hash function of all the
+// blocks of the chain.
+const BLAKE2B_256_PREFIX: [u8; 4] = [0xa0, 0xe4, 0x02, 0x20];
+
+/// The fetcher of the blocks from the peers of the sync session, e.g. bitswap.
+pub trait BlockFetcher: Send + Sync + 'static {
+    /// The identity of a peer.
+    type Peer: Clone + fmt::Display + Send + Sync + 'static;
+
+    /// Fetch the raw block of the `cid` from the `peer`.
+    fn fetch_block(&self, peer: &Self::Peer, cid: &Cid) -> Result<Vec<u8>>;
+}
+
+/// The statistics of a prefetch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PrefetchStats {
+    /// The number of the blocks fetched from the peers.
+    pub fetched: usize,
+    /// The number of the blocks already in the blockstore.
+    pub local: usize,
+    /// The number of the blocks that no peer returned.
+    pub failed: usize,
+}
+
+/// The prefetcher of the messages of the incoming tipsets.
+///
+/// Given the header CIDs of a tipset, the headers, the message meta, the nodes of the BLS and
+/// secp256k1 message AMTs and the messages are fetched from the session peers by the worker
+/// threads and written to the blockstore, while the headers are still being validated. It's
+/// speculative, a failed fetch is only counted, the sync fetches the missing blocks again.
+pub struct Prefetcher<F, BS> {
+    fetcher: Arc<F>,
+    store: BS,
+    workers: usize,
+}
+
+impl<F, BS> Prefetcher<F, BS>
+where
+    F: BlockFetcher,
+    BS: BlockStore + Clone + Send + Sync + 'static,
+{
+    /// Create a prefetcher of `DEFAULT_PREFETCH_WORKERS` workers writing to the `store`.
+    pub fn new(fetcher: F, store: BS) -> Self {
+        Self {
+            fetcher: Arc::new(fetcher),
+            store,
+            workers: DEFAULT_PREFETCH_WORKERS,
+        }
+    }
+
+    /// Set the number of the worker threads of a prefetch, at least one.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Start prefetching the blocks of the `headers` from the `peers` in the background.
+    ///
+    /// The requests are spread over the peers in turn, a block is requested from the next
+    /// peer if the previous one fails.
+    pub fn prefetch(&self, headers: Vec<Cid>, peers: Vec<F::Peer>) -> PrefetchHandle {
+        let mut state = PrefetchState::default();
+        for cid in headers {
+            if state.seen.insert(cid.clone()) {
+                state.queue.push_back((cid, BlockKind::Header));
+            }
+        }
+        let worker = Arc::new(PrefetchWorker {
+            fetcher: self.fetcher.clone(),
+            store: self.store.clone(),
+            peers,
+            next_peer: AtomicUsize::new(0),
+            shared: Arc::new(Shared {
+                state: Mutex::new(state),
+                cond: Condvar::new(),
+                cancelled: AtomicBool::new(false),
+            }),
+        });
+        let shared = worker.shared.clone();
+        let threads = (0..self.workers)
+            .map(|_| {
+                let worker = worker.clone();
+                thread::spawn(move || worker.run())
+            })
+            .collect();
+        PrefetchHandle { shared, threads }
+    }
+}
+
+/// The handle of a running prefetch.
+pub struct PrefetchHandle {
+    shared: Arc<Shared>,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl PrefetchHandle {
+    /// Stop the prefetch, the blocks being fetched are still written to the blockstore.
+    pub fn cancel(&self) {
+        self.shared.cancelled.store(true, Ordering::SeqCst);
+        self.shared.cond.notify_all();
+    }
+
+    /// Wait for the prefetch to finish, return the statistics.
+    pub fn wait(self) -> PrefetchStats {
+        for thread in self.threads {
+            if thread.join().is_err() {
+                error!("[prefetch] Worker thread panicked");
+            }
+        }
+        self.shared.state.lock().stats
+    }
+}
+
+// The kind of a prefetched block, which decides the links to follow.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BlockKind {
+    // The block header, only the message meta is followed.
+    Header,
+    // The message meta, the AMT nodes and the messages, all the links are followed.
+    Messages,
+}
+
+#[derive(Default)]
+struct PrefetchState {
+    queue: VecDeque<(Cid, BlockKind)>,
+    seen: HashSet<Cid>,
+    in_flight: usize,
+    stats: PrefetchStats,
+}
+
+struct Shared {
+    state: Mutex<PrefetchState>,
+    cond: Condvar,
+    cancelled: AtomicBool,
+}
+
+struct PrefetchWorker<F: BlockFetcher, BS> {
+    fetcher: Arc<F>,
+    store: BS,
+    peers: Vec<F::Peer>,
+    next_peer: AtomicUsize,
+    shared: Arc<Shared>,
+}
+
+impl<F, BS> PrefetchWorker<F, BS>
+where
+    F: BlockFetcher,
+    BS: BlockStore,
+{
+    fn run(&self) {
+        loop {
+            let (cid, kind) = {
+                let mut state = self.shared.state.lock();
+                loop {
+                    if self.shared.cancelled.load(Ordering::SeqCst) {
+                        return;
+                    }
+                    if let Some(next) = state.queue.pop_front() {
+                        state.in_flight += 1;
+                        break next;
+                    }
+                    // Nothing to fetch and no fetch that may find more blocks.
+                    if state.in_flight == 0 {
+                        self.shared.cond.notify_all();
+                        return;
+                    }
+                    self.shared.cond.wait(&mut state);
+                }
+            };
+
+            let result = self.process(&cid, kind);
+            let mut state = self.shared.state.lock();
+            state.in_flight -= 1;
+            match result {
+                Ok((local, links)) => {
+                    if local {
+                        state.stats.local += 1;
+                    } else {
+                        state.stats.fetched += 1;
+                    }
+                    for link in links {
+                        if state.seen.insert(link.clone()) {
+                            state.queue.push_back((link, BlockKind::Messages));
+                        }
+                    }
+                }
+                Err(err) => {
+                    debug!("[prefetch] Failed to prefetch block {}: {}", cid, err);
+                    state.stats.failed += 1;
+                }
+            }
+            self.shared.cond.notify_all();
+        }
+    }
+
+    // Load or fetch the block, return whether it's local and the links to follow.
+    fn process(&self, cid: &Cid, kind: BlockKind) -> Result<(bool, Vec<Cid>)> {
+        let (local, data) = match BlockStore::get(&self.store, cid)? {
+            Some(block) => (true, block.data().to_vec()),
+            None => {
+                let data = self.fetch(cid)?;
+                // The data is verified against the CID by `fetch`.
+                let block = unsafe { Block::new_unchecked(data.clone(), cid.clone()) };
+                BlockStore::put(&self.store, block)?;
+                (false, data)
+            }
+        };
+        let links = match kind {
+            BlockKind::Header => vec![minicbor::decode::<BlockHeader>(&data)?.messages],
+            BlockKind::Messages => {
+                let mut links = vec![];
+                collect_links(&minicbor::decode::<Value>(&data)?, &mut links);
+                links
+            }
+        };
+        Ok((local, links))
+    }
+
+    // Fetch the block from the peers in turn, until a peer returns the valid block.
+    fn fetch(&self, cid: &Cid) -> Result<Vec<u8>> {
+        ensure!(!self.peers.is_empty(), "no peer to fetch from");
+        let start = self.next_peer.fetch_add(1, Ordering::Relaxed);
+        let mut last_err = None;
+        for i in 0..self.peers.len() {
+            let peer = &self.peers[(start + i) % self.peers.len()];
+            match self.fetcher.fetch_block(peer, cid) {
+                Ok(data) if verify_block(cid, &data) => return Ok(data),
+                Ok(_) => last_err = Some(anyhow!("peer {} returned an invalid block", peer)),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.expect("there's at least one peer; qed"))
+    }
+}
+
+// Check that the `data` is the block of the `cid`, only Blake2b-256 is supported.
+fn verify_block(cid: &Cid, data: &[u8]) -> bool {
+    let multihash = cid.hash().as_bytes();
+    multihash.len() == BLAKE2B_256_PREFIX.len() + 32
+        && multihash[..BLAKE2B_256_PREFIX.len()] == BLAKE2B_256_PREFIX
+        && multihash[BLAKE2B_256_PREFIX.len()..] == plum_hashing::blake2b_256(data)
+}
+
+fn collect_links(value: &Value, links: &mut Vec<Cid>) {
+    match value {
+        Value::Link(cid) => links.push(cid.clone()),
+        Value::List(list) => list.iter().for_each(|value| collect_links(value, links)),
+        Value::Map(map) => map.values().for_each(|value| collect_links(value, links)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use ipld::{build_amt, IpldStore, DEFAULT_AMT_BIT_WIDTH};
+    use plum_address::Address;
+    use plum_bigint::BigInt;
+    use plum_block::MsgMeta;
+    use plum_crypto::Signature;
+    use plum_message::{SignedMessage, UnsignedMessage, MESSAGE_VERSION_FEE_MARKET};
+
+    use crate::test_utils::{dummy_cid, new_header};
+
+    type Store = SyncDataStore<MapDataStore>;
+
+    // The peer 0 has nothing, the peer 1 returns the wrong blocks, the others have all the
+    // blocks of the source store.
+    struct StoreFetcher(Store);
+
+    impl BlockFetcher for StoreFetcher {
+        type Peer = usize;
+
+        fn fetch_block(&self, peer: &usize, cid: &Cid) -> Result<Vec<u8>> {
+            match peer {
+                0 => Err(anyhow!("block {} not found", cid)),
+                1 => Ok(b"wrong block".to_vec()),
+                _ => BlockStore::get(&self.0, cid)?
+                    .map(|block| block.data().to_vec())
+                    .ok_or_else(|| anyhow!("block {} not found", cid)),
+            }
+        }
+    }
+
+    fn message(nonce: u64) -> UnsignedMessage {
+        UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(1000).unwrap(),
+            from: Address::new_id_addr(1001).unwrap(),
+            nonce,
+            value: BigInt::from(0),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        }
+    }
+
+    // Put the header with `count` BLS and `count` secp256k1 messages into the store, return
+    // the CID of the header and the number of its blocks.
+    fn put_header(store: &Store, miner: u64, count: u64) -> (Cid, usize) {
+        let bls = (0..count)
+            .map(|nonce| IpldStore::put(store, &message(nonce + miner)).unwrap())
+            .collect::<Vec<_>>();
+        let secp = (0..count)
+            .map(|nonce| {
+                let message = SignedMessage {
+                    message: message(nonce + miner),
+                    signature: Signature::new_secp256k1(vec![1; 65]),
+                };
+                IpldStore::put(store, &message).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let meta = MsgMeta {
+            bls_messages: build_amt(store, &bls, DEFAULT_AMT_BIT_WIDTH).unwrap(),
+            secpk_messages: build_amt(store, &secp, DEFAULT_AMT_BIT_WIDTH).unwrap(),
+        };
+        let messages = IpldStore::put(store, &meta).unwrap();
+        // The parents and the states are not fetched.
+        let header = BlockHeader {
+            messages,
+            ..new_header(vec![dummy_cid()], 1, miner)
+        };
+        let header = IpldStore::put(store, &header).unwrap();
+        // The header, the meta, the AMT roots (a root holds up to 8 links) and the messages.
+        let blocks = 1 + 1 + 2 + 2 * count as usize;
+        (header, blocks)
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let source = Store::new(MapDataStore::new());
+        let (header_a, blocks_a) = put_header(&source, 2000, 3);
+        let (header_b, blocks_b) = put_header(&source, 2001, 2);
+
+        let store = Store::new(MapDataStore::new());
+        let prefetcher = Prefetcher::new(StoreFetcher(source), store.clone()).with_workers(4);
+        let stats = prefetcher
+            .prefetch(vec![header_a.clone(), header_b.clone()], vec![0, 1, 2])
+            .wait();
+        // The messages of nonce 2001 and 2002 are shared by the tipsets.
+        let shared = 4;
+        assert_eq!(
+            stats,
+            PrefetchStats {
+                fetched: blocks_a + blocks_b - shared,
+                local: 0,
+                failed: 0,
+            }
+        );
+        let cs = crate::ChainStore::new(store.clone());
+        let header: BlockHeader = IpldStore::get(&store, &header_a).unwrap().unwrap();
+        assert_eq!(cs.block_messages(&header).unwrap().len(), 6);
+
+        // The local blocks are walked but not fetched, no peer has the missing header.
+        let missing = Block::new(1u64).cid().clone();
+        let stats = prefetcher
+            .prefetch(vec![header_b, missing], vec![0, 1])
+            .wait();
+        assert_eq!(
+            stats,
+            PrefetchStats {
+                fetched: 0,
+                local: blocks_b,
+                failed: 1,
+            }
+        );
+    }
+}
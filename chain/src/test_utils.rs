@@ -0,0 +1,89 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The fixtures shared by the tests of the crate.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use cid::Cid;
+
+use ipfs_datastore::{Delay, DelayDataStore, MapDataStore, SyncDataStore};
+use plum_address::Address;
+use plum_block::{BlockHeader, ElectionProof, Ticket};
+use plum_crypto::Signature;
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+use crate::TipsetLoader;
+
+/// The delay of the datastore that never waits.
+#[derive(Clone)]
+pub struct NoDelay;
+
+impl Delay for NoDelay {
+    fn wait(&self) {}
+}
+
+/// The in-memory store of the tests.
+pub type Store = DelayDataStore<NoDelay, SyncDataStore<MapDataStore>>;
+
+/// The CID of the roots that are not read by the tests.
+pub fn dummy_cid() -> Cid {
+    "bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i"
+        .parse()
+        .unwrap()
+}
+
+/// Create the header of the block mined by the `miner` on top of the `parents`,
+/// all the roots of the header are the dummy CID.
+pub fn new_header(parents: Vec<Cid>, height: ChainEpoch, miner: u64) -> BlockHeader {
+    let cid = dummy_cid();
+    BlockHeader {
+        miner: Address::new_id_addr(miner).unwrap(),
+        ticket: Ticket::new(b"vrf proof".to_vec()),
+        election_proof: ElectionProof {
+            win_count: 1,
+            vrf_proof: b"vrf proof".to_vec(),
+        },
+        beacon_entries: vec![],
+        win_post_proof: vec![],
+        parents,
+        parent_message_receipts: cid.clone(),
+        bls_aggregate: Signature::new_bls("signature"),
+        parent_weight: 0u64.into(),
+        messages: cid.clone(),
+        height,
+        parent_state_root: cid,
+        timestamp: 0u64,
+        block_sig: Signature::new_bls("signature"),
+        fork_signaling: 0u64,
+    }
+}
+
+/// Create the tipset of a single block mined by the miner `1000` on top of the `parents`.
+pub fn new_tipset(parents: Vec<Cid>, height: ChainEpoch) -> Tipset {
+    Tipset::new(vec![new_header(parents, height, 1000)]).unwrap()
+}
+
+/// The tipset loader backed by a map.
+#[derive(Default)]
+pub struct MapLoader(pub HashMap<TipsetKey, Arc<Tipset>>);
+
+impl MapLoader {
+    /// Add the tipset into the loader.
+    pub fn insert(&mut self, tipset: Tipset) -> Arc<Tipset> {
+        let tipset = Arc::new(tipset);
+        self.0.insert(tipset.key().clone(), tipset.clone());
+        tipset
+    }
+}
+
+impl TipsetLoader for MapLoader {
+    fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>> {
+        match self.0.get(key) {
+            Some(tipset) => Ok(tipset.clone()),
+            None => bail!("tipset {} not found", key),
+        }
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+/// The default number of the lookback entries kept in the tipset cache.
+pub const DEFAULT_TIPSET_CACHE_SIZE: usize = 32 * 1024;
+/// The default distance in epochs between two adjacent lookback entries.
+pub const DEFAULT_SKIP_LENGTH: ChainEpoch = 20;
+
+/// The loader of the tipsets, which is usually backed by the blockstore.
+pub trait TipsetLoader {
+    /// Load the tipset with the given key.
+    fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>>;
+}
+
+// The lookback entry of a tipset whose height is a multiple of the skip length,
+// `target` is the tipset at (or above, in case of null rounds) `height - skip_length`.
+struct LookbackEntry {
+    tipset: Arc<Tipset>,
+    parent_height: ChainEpoch,
+    target: TipsetKey,
+    target_height: ChainEpoch,
+}
+
+/// The cache answering the tipset by height queries quickly.
+///
+/// The tipsets whose heights are multiples of the skip length are linked into a skip list,
+/// so a query only walks back through the chain for at most `skip_length` epochs
+/// in addition to hopping through the cached lookback entries.
+/// See lotus/chain/store/index.go for details.
+pub struct TipsetCache<L> {
+    loader: L,
+    skip_length: ChainEpoch,
+    entries: Mutex<LruCache<TipsetKey, Arc<LookbackEntry>>>,
+}
+
+impl<L: TipsetLoader> TipsetCache<L> {
+    /// Create a new tipset cache with the default size and skip length.
+    pub fn new(loader: L) -> Self {
+        Self::with_config(loader, DEFAULT_TIPSET_CACHE_SIZE, DEFAULT_SKIP_LENGTH)
+    }
+
+    /// Create a new tipset cache with the given size and skip length.
+    pub fn with_config(loader: L, size: usize, skip_length: ChainEpoch) -> Self {
+        assert!(skip_length > 0, "skip length must be positive");
+        Self {
+            loader,
+            skip_length,
+            entries: Mutex::new(LruCache::new(size)),
+        }
+    }
+
+    /// Return the tipset at `height` in the chain of `from`.
+    ///
+    /// If `height` is a null round, the first non-null tipset after it is returned
+    /// when `allow_null` is true, otherwise the last non-null tipset before it is returned.
+    pub fn tipset_by_height(
+        &self,
+        height: ChainEpoch,
+        from: &Arc<Tipset>,
+        allow_null: bool,
+    ) -> Result<Arc<Tipset>> {
+        if height > from.height() {
+            bail!(
+                "looking for tipset with height {} greater than start point {}",
+                height,
+                from.height()
+            );
+        }
+        if height == from.height() {
+            return Ok(from.clone());
+        }
+
+        let mut tipset = self.lookback(from, height)?;
+        if tipset.height() < height {
+            warn!(
+                "[tipset cache] returned the wrong tipset at height {}, use slow retrieval",
+                height
+            );
+            tipset = self.walk_back(from, height)?;
+        }
+        if tipset.height() == height || allow_null {
+            Ok(tipset)
+        } else {
+            self.loader.load_tipset(&tipset.parents())
+        }
+    }
+
+    /// Clear all the cached lookback entries, e.g. after the chain is reorganized.
+    pub fn clear(&self) {
+        self.entries.lock().clear();
+    }
+
+    fn lookback(&self, from: &Arc<Tipset>, height: ChainEpoch) -> Result<Arc<Tipset>> {
+        if from.height() - height <= self.skip_length {
+            return self.walk_back(from, height);
+        }
+
+        let rounded = self.round_down(from)?;
+        let mut current = rounded.key().clone();
+        loop {
+            let cached = self.entries.lock().get(&current).cloned();
+            let entry = match cached {
+                Some(entry) => entry,
+                None => self.fill_cache(&current)?,
+            };
+
+            if entry.tipset.height() == height || entry.parent_height < height {
+                return Ok(entry.tipset.clone());
+            } else if height > entry.target_height {
+                return self.walk_back(&entry.tipset, height);
+            }
+            current = entry.target.clone();
+        }
+    }
+
+    fn fill_cache(&self, key: &TipsetKey) -> Result<Arc<LookbackEntry>> {
+        let tipset = self.loader.load_tipset(key)?;
+        if tipset.height() == 0 {
+            return Ok(Arc::new(LookbackEntry {
+                parent_height: 0,
+                target: key.clone(),
+                target_height: 0,
+                tipset,
+            }));
+        }
+
+        // The height of the tipset is either a multiple of the skip length,
+        // or the multiple is a null round and is above the parent height.
+        let parent = self.loader.load_tipset(&tipset.parents())?;
+        let target_height = self.round_height(tipset.height()) - self.skip_length;
+        let target = if parent.height() < target_height {
+            parent.clone()
+        } else {
+            self.walk_back(&parent, target_height)?
+        };
+
+        let entry = Arc::new(LookbackEntry {
+            parent_height: parent.height(),
+            target: target.key().clone(),
+            target_height: target.height(),
+            tipset,
+        });
+        self.entries.lock().put(key.clone(), entry.clone());
+        Ok(entry)
+    }
+
+    fn round_height(&self, height: ChainEpoch) -> ChainEpoch {
+        height / self.skip_length * self.skip_length
+    }
+
+    // Return the tipset at the largest multiple of the skip length that is not above `tipset`,
+    // or the first tipset above it if it's a null round.
+    fn round_down(&self, tipset: &Arc<Tipset>) -> Result<Arc<Tipset>> {
+        self.walk_back(tipset, self.round_height(tipset.height()))
+    }
+
+    // Walk back through the parents, return the tipset at `height`, or the first tipset above
+    // `height` if `height` is a null round.
+    fn walk_back(&self, from: &Arc<Tipset>, height: ChainEpoch) -> Result<Arc<Tipset>> {
+        if height > from.height() {
+            bail!(
+                "looking for tipset with height {} greater than start point {}",
+                height,
+                from.height()
+            );
+        }
+        let mut tipset = from.clone();
+        while tipset.height() > height {
+            let parent = self.loader.load_tipset(&tipset.parents())?;
+            if parent.height() < height {
+                // null rounds
+                break;
+            }
+            tipset = parent;
+        }
+        Ok(tipset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::{new_tipset, MapLoader};
+
+    // Build a chain of [0, head], skipping the null rounds.
+    fn new_chain(head: ChainEpoch, null_rounds: &[ChainEpoch]) -> (MapLoader, Arc<Tipset>) {
+        let mut loader = MapLoader::default();
+        let mut tipset = loader.insert(new_tipset(vec![], 0));
+        for height in 1..=head {
+            if null_rounds.contains(&height) {
+                continue;
+            }
+            tipset = loader.insert(new_tipset(tipset.cids().to_vec(), height));
+        }
+        (loader, tipset)
+    }
+
+    #[test]
+    fn test_tipset_by_height() {
+        let null_rounds = [5, 6, 40, 41, 42, 60, 99];
+        let (loader, head) = new_chain(105, &null_rounds);
+        let cache = TipsetCache::with_config(loader, 16, 10);
+
+        for height in 0..=105 {
+            let above = cache.tipset_by_height(height, &head, true).unwrap();
+            let below = cache.tipset_by_height(height, &head, false).unwrap();
+            if null_rounds.contains(&height) {
+                let mut next = height + 1;
+                while null_rounds.contains(&next) {
+                    next += 1;
+                }
+                let mut prev = height - 1;
+                while null_rounds.contains(&prev) {
+                    prev -= 1;
+                }
+                assert_eq!(above.height(), next);
+                assert_eq!(below.height(), prev);
+            } else {
+                assert_eq!(above.height(), height);
+                assert_eq!(below.height(), height);
+            }
+        }
+
+        assert!(cache.tipset_by_height(106, &head, true).is_err());
+
+        // query from a tipset in the middle of the chain.
+        let from = cache.tipset_by_height(70, &head, true).unwrap();
+        assert_eq!(
+            cache.tipset_by_height(41, &from, true).unwrap().height(),
+            43
+        );
+        assert_eq!(
+            cache.tipset_by_height(41, &from, false).unwrap().height(),
+            39
+        );
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use plum_address::Address;
+use plum_message::ChainMessage;
+
+/// Select the messages to apply from the messages of the blocks of a tipset, return the
+/// messages along with the indexes of the blocks including them.
+///
+/// The `blocks` are the messages of each block in the canonical order of the tipset, with the
+/// BLS messages of a block before its secp256k1 messages. The same message may be included by
+/// several blocks and the messages of the same sender may conflict, so a message is applied
+/// only if its nonce is the next nonce of its sender: the first message of a sender sets the
+/// nonce, then the nonce increments by each applied message of the sender. The duplicates of
+/// the applied messages are skipped as well. It's the same as ApplyBlocks of Lotus, applying
+/// a message twice is a consensus fault.
+pub fn select_tipset_messages<I>(blocks: I) -> Vec<(usize, ChainMessage)>
+where
+    I: IntoIterator<Item = Vec<ChainMessage>>,
+{
+    let mut applied = HashMap::<Address, u64>::new();
+    let mut selected = vec![];
+    for (index, messages) in blocks.into_iter().enumerate() {
+        for message in messages {
+            let unsigned = message.message();
+            // The first message of a sender has the right nonce, the block is invalid otherwise.
+            let nonce = applied
+                .entry(unsigned.from.clone())
+                .or_insert(unsigned.nonce);
+            if *nonce != unsigned.nonce {
+                continue;
+            }
+            *nonce += 1;
+            selected.push((index, message));
+        }
+    }
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_bigint::BigInt;
+    use plum_crypto::Signature;
+    use plum_message::{SignedMessage, UnsignedMessage, MESSAGE_VERSION_FEE_MARKET};
+
+    fn message(from: u64, nonce: u64) -> UnsignedMessage {
+        UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(1000).unwrap(),
+            from: Address::new_id_addr(from).unwrap(),
+            nonce,
+            value: BigInt::from(0),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        }
+    }
+
+    fn secp(from: u64, nonce: u64) -> ChainMessage {
+        ChainMessage::from(SignedMessage {
+            message: message(from, nonce),
+            signature: Signature::new_secp256k1(vec![1; 65]),
+        })
+    }
+
+    #[test]
+    fn test_select_tipset_messages() {
+        let bls = |from, nonce| ChainMessage::from(message(from, nonce));
+        let blocks = vec![
+            vec![bls(1001, 5), bls(1001, 6), secp(1002, 0)],
+            // The duplicates and the message of the same nonce with another signature type.
+            vec![bls(1001, 6), secp(1002, 0), bls(1002, 0), bls(1001, 7)],
+            // The gap of nonce, the first message of a new sender.
+            vec![secp(1002, 2), secp(1002, 1), secp(1003, 9)],
+        ];
+        let selected = select_tipset_messages(blocks)
+            .into_iter()
+            .map(|(index, message)| {
+                (
+                    index,
+                    message.message().from.as_id().unwrap(),
+                    message.message().nonce,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            selected,
+            vec![
+                (0, 1001, 5),
+                (0, 1001, 6),
+                (0, 1002, 0),
+                (1, 1001, 7),
+                (2, 1002, 1),
+                (2, 1003, 9)
+            ]
+        );
+        assert!(select_tipset_messages(vec![vec![], vec![]]).is_empty());
+    }
+}
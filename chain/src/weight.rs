@@ -0,0 +1,289 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::sync::Arc;
+
+use plum_bigint::BigInt;
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::{ChainEpoch, BLOCKS_PER_EPOCH};
+
+use crate::tipset_cache::TipsetLoader;
+
+/// The numerator of the ratio between the weight of the elections and the weight of the power.
+pub const W_RATIO_NUM: u64 = 1;
+/// The denominator of the ratio between the weight of the elections and the weight of the power.
+pub const W_RATIO_DEN: u64 = 2;
+/// The default max number of the tipsets walked back when verifying a claimed head.
+pub const DEFAULT_MAX_VERIFY_DEPTH: usize = 900;
+
+/// The loader of the tipsets and the network power used for computing the chain weight.
+pub trait WeightLoader: TipsetLoader {
+    /// Load the total quality adjusted power of the network in the parent state of the tipset.
+    fn total_power(&self, tipset: &Tipset) -> anyhow::Result<BigInt>;
+}
+
+/// The errors of computing and verifying the chain weight.
+#[derive(Debug, thiserror::Error)]
+pub enum WeightError {
+    /// The network has no power, so the weight can't be computed.
+    #[error("all power in the net is gone, the network might be disconnected or dead")]
+    NoPower,
+    /// The claimed weight doesn't match the weight computed from the chain.
+    #[error("claimed weight {claimed} of tipset at height {height} doesn't match the computed weight {computed}")]
+    Mismatch {
+        /// The height of the tipset whose weight is claimed.
+        height: ChainEpoch,
+        /// The weight claimed by the peer or the child headers.
+        claimed: BigInt,
+        /// The weight computed from the chain.
+        computed: BigInt,
+    },
+    /// The blocks of a tipset disagree on the parent weight.
+    #[error("blocks of tipset at height {0} have different parent weights")]
+    InconsistentParentWeight(ChainEpoch),
+    /// The chain reaches a genesis that isn't known.
+    #[error("claimed chain doesn't share the genesis with the local chain")]
+    UnknownGenesis,
+    /// No known ancestor is found within the max depth.
+    #[error("no known ancestor within {0} tipsets")]
+    TooDeep(usize),
+    /// Failed to load the tipsets or the power.
+    #[error("failed to load the chain: {0}")]
+    Load(#[from] anyhow::Error),
+}
+
+impl WeightError {
+    /// Return true if the error proves that the peer lied about its chain,
+    /// so that the peer should be penalized.
+    pub fn is_invalid_chain(&self) -> bool {
+        match self {
+            WeightError::Mismatch { .. }
+            | WeightError::InconsistentParentWeight(_)
+            | WeightError::UnknownGenesis => true,
+            WeightError::NoPower | WeightError::TooDeep(_) | WeightError::Load(_) => false,
+        }
+    }
+}
+
+/// Compute the weight of a tipset with the weight of its parent, the total power of the network
+/// in its parent state and the number of the elections won by its blocks.
+///
+/// ```text
+/// w = parent_weight + (log2(P) << 8) + (log2(P) * wins * W_RATIO_NUM << 8) / (BLOCKS_PER_EPOCH * W_RATIO_DEN)
+/// ```
+///
+/// See lotus/chain/store/weight.go for details.
+pub fn compute_weight(
+    parent_weight: &BigInt,
+    total_power: &BigInt,
+    win_count: u64,
+) -> Result<BigInt, WeightError> {
+    if total_power <= &BigInt::from(0) {
+        return Err(WeightError::NoPower);
+    }
+    let log2_power = BigInt::from(total_power.bits() as u64 - 1);
+
+    let power_weight = &log2_power << 8;
+    let election_weight = ((log2_power * W_RATIO_NUM) << 8) * win_count
+        / BigInt::from(BLOCKS_PER_EPOCH * W_RATIO_DEN);
+    Ok(parent_weight + power_weight + election_weight)
+}
+
+/// The verifier of the chain weight claimed by a peer, e.g. in a hello message or a gossiped head.
+///
+/// A claimed head is walked back one tipset at a time until a locally known tipset is reached,
+/// and every link is checked as soon as it's loaded: the parent weight recorded in the child
+/// headers must equal the weight computed from the parent. So a spoofed weight is rejected
+/// without fetching the whole chain, before any sync resource is committed to the head.
+pub struct WeightVerifier<L> {
+    loader: L,
+    max_depth: usize,
+}
+
+impl<L: WeightLoader> WeightVerifier<L> {
+    /// Create a new verifier with the default max depth.
+    pub fn new(loader: L) -> Self {
+        Self::with_max_depth(loader, DEFAULT_MAX_VERIFY_DEPTH)
+    }
+
+    /// Create a new verifier walking back at most `max_depth` tipsets from the claimed head.
+    pub fn with_max_depth(loader: L, max_depth: usize) -> Self {
+        Self { loader, max_depth }
+    }
+
+    /// Compute the weight of the tipset.
+    pub fn weight(&self, tipset: &Tipset) -> Result<BigInt, WeightError> {
+        let parent_weight = parent_weight(tipset)?;
+        let total_power = self.loader.total_power(tipset)?;
+        let win_count = tipset
+            .blocks()
+            .iter()
+            .map(|block| block.election_proof.win_count.max(0) as u64)
+            .sum();
+        compute_weight(parent_weight, &total_power, win_count)
+    }
+
+    /// Verify that `claimed` is the weight of `head`, and that the weights recorded in the chain
+    /// of `head` are consistent down to a tipset for which `is_known` returns true.
+    pub fn verify_head<F>(
+        &self,
+        head: &Arc<Tipset>,
+        claimed: &BigInt,
+        is_known: F,
+    ) -> Result<(), WeightError>
+    where
+        F: Fn(&TipsetKey) -> bool,
+    {
+        let computed = self.weight(head)?;
+        if &computed != claimed {
+            return Err(WeightError::Mismatch {
+                height: head.height(),
+                claimed: claimed.clone(),
+                computed,
+            });
+        }
+
+        let mut tipset = head.clone();
+        for _ in 0..self.max_depth {
+            if is_known(tipset.key()) {
+                return Ok(());
+            }
+            if tipset.height() == 0 {
+                return Err(WeightError::UnknownGenesis);
+            }
+
+            let parent = self.loader.load_tipset(&tipset.parents())?;
+            let computed = self.weight(&parent)?;
+            let claimed = parent_weight(&tipset)?;
+            if &computed != claimed {
+                return Err(WeightError::Mismatch {
+                    height: parent.height(),
+                    claimed: claimed.clone(),
+                    computed,
+                });
+            }
+            tipset = parent;
+        }
+
+        if is_known(tipset.key()) {
+            Ok(())
+        } else {
+            Err(WeightError::TooDeep(self.max_depth))
+        }
+    }
+}
+
+fn parent_weight(tipset: &Tipset) -> Result<&BigInt, WeightError> {
+    let parent_weight = tipset.parent_weight();
+    if tipset
+        .blocks()
+        .iter()
+        .any(|block| &block.parent_weight != parent_weight)
+    {
+        return Err(WeightError::InconsistentParentWeight(tipset.height()));
+    }
+    Ok(parent_weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::Cid;
+    use plum_block::BlockHeader;
+
+    use crate::test_utils::{new_header, MapLoader};
+
+    const POWER: u64 = 1 << 40;
+
+    impl WeightLoader for MapLoader {
+        fn total_power(&self, _tipset: &Tipset) -> anyhow::Result<BigInt> {
+            Ok(BigInt::from(POWER))
+        }
+    }
+
+    fn new_tipset(parents: Vec<Cid>, height: ChainEpoch, parent_weight: BigInt) -> Tipset {
+        let header = BlockHeader {
+            parent_weight,
+            ..new_header(parents, height, 1000)
+        };
+        Tipset::new(vec![header]).unwrap()
+    }
+
+    // Build a chain of [0, head], the parent weight of the tipset at `forged` is bumped.
+    fn new_chain(head: ChainEpoch, forged: Option<ChainEpoch>) -> (MapLoader, Vec<Arc<Tipset>>) {
+        let mut loader = MapLoader::default();
+        let mut chain = vec![Arc::new(new_tipset(vec![], 0, 0u64.into()))];
+        for height in 1..=head {
+            let parent = chain.last().unwrap();
+            let mut weight = compute_weight(parent.parent_weight(), &POWER.into(), 1).unwrap();
+            if forged == Some(height) {
+                weight += BigInt::from(1);
+            }
+            chain.push(Arc::new(new_tipset(parent.cids().to_vec(), height, weight)));
+        }
+        for tipset in &chain {
+            loader.0.insert(tipset.key().clone(), tipset.clone());
+        }
+        (loader, chain)
+    }
+
+    #[test]
+    fn test_compute_weight() {
+        // log2(2^40) = 40, 40 << 8 = 10240, (40 << 8) * 5 / 10 = 5120
+        let weight = compute_weight(&100u64.into(), &POWER.into(), 5).unwrap();
+        assert_eq!(weight, BigInt::from(100 + 10240 + 5120));
+        assert!(matches!(
+            compute_weight(&100u64.into(), &0u64.into(), 5),
+            Err(WeightError::NoPower)
+        ));
+    }
+
+    #[test]
+    fn test_verify_head() {
+        let (loader, chain) = new_chain(10, None);
+        let genesis = chain[0].key().clone();
+        let head = chain[10].clone();
+        let verifier = WeightVerifier::new(loader);
+        let weight = verifier.weight(&head).unwrap();
+        verifier
+            .verify_head(&head, &weight, |key| key == &genesis)
+            .unwrap();
+
+        let err = verifier
+            .verify_head(&head, &(&weight + BigInt::from(1)), |key| key == &genesis)
+            .unwrap_err();
+        assert!(err.is_invalid_chain());
+        assert!(matches!(err, WeightError::Mismatch { height: 10, .. }));
+
+        let err = verifier
+            .verify_head(&head, &verifier.weight(&head).unwrap(), |_| false)
+            .unwrap_err();
+        assert!(matches!(err, WeightError::UnknownGenesis));
+    }
+
+    #[test]
+    fn test_verify_forged_chain() {
+        let (loader, chain) = new_chain(10, Some(6));
+        let genesis = chain[0].key().clone();
+        let head = chain[10].clone();
+        let verifier = WeightVerifier::with_max_depth(loader, 20);
+        let weight = verifier.weight(&head).unwrap();
+        let err = verifier
+            .verify_head(&head, &weight, |key| key == &genesis)
+            .unwrap_err();
+        assert!(matches!(err, WeightError::Mismatch { height: 5, .. }));
+
+        // the forged link isn't reached when the ancestor above it is known.
+        let known = chain[7].key().clone();
+        verifier
+            .verify_head(&head, &weight, |key| key == &known)
+            .unwrap();
+
+        let verifier = WeightVerifier::with_max_depth(MapLoader(verifier.loader.0), 2);
+        let err = verifier
+            .verify_head(&head, &weight, |key| key == &genesis)
+            .unwrap_err();
+        assert!(!err.is_invalid_chain());
+        assert!(matches!(err, WeightError::TooDeep(2)));
+    }
+}
@@ -1,14 +1,109 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use cid::Cid;
+use libp2p_core::multiaddr::{Multiaddr, Protocol};
 use structopt::StructOpt;
 
-use plum_network::Multiaddr;
+use ipfs_datastore::Persistent;
+use ipfs_datastore_rocksdb::{DatabaseConfig, OpenMode, RocksDBDataStore, DEFAULT_COLUMN_NAME};
+use ipld::IpldStore;
+
+use plum_actor::{
+    codes, init, miner, multisig, multisig::TxnID, params::BUILTIN_PARAMS_REGISTRY, power,
+};
+use plum_address::Address;
+use plum_api_client::{
+    ChainApi, ClientApi, CommonApi, HttpTransport, PeerAddrInfo, Permission, StateApi, WalletApi,
+};
+use plum_bigint::BigInt;
+use plum_block::BlockHeader;
+use plum_chain::{export_chain, ChainStore, ExportOptions};
+use plum_crypto::{Signature, SignatureType};
+use plum_message::{UnsignedMessage, MESSAGE_VERSION_FEE_MARKET};
+use plum_peerid::PeerId;
+use plum_sector::{readable_sector_size, RegisteredSealProof};
+use plum_statemgr::{
+    diff, ActorChange, ActorDiff, BlockStoreReplayLoader, BlockStoreView, Replayer, StateAuditor,
+    StateManager, TipsetExecutor,
+};
+use plum_tipset::Tipset;
+use plum_types::{ChainEpoch, MethodNum};
+use plum_wallet::{verify_data, Key, KeyInfo, MemKeyStore};
+
+use crate::config::Config;
+use crate::gateway::Gateway;
 
 #[derive(StructOpt, Debug, Clone)]
 pub enum Auth {
     /// Create token
     #[structopt(name = "create-token")]
-    CreateToken,
+    CreateToken {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// The permission of the token, which also grants all the lower permissions
+        #[structopt(long = "perm", possible_values = &["read", "write", "sign", "admin"])]
+        perm: Permission,
+    },
+    /// List the tokens that are not revoked
+    #[structopt(name = "list-tokens")]
+    ListTokens {
+        #[structopt(flatten)]
+        api: NodeApi,
+    },
+    /// Revoke a token by its ID
+    #[structopt(name = "revoke-token")]
+    RevokeToken {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// The ID of the token
+        #[structopt(name = "id")]
+        id: String,
+    },
+}
+
+impl Auth {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute auth command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // The tokens are issued and revoked by the auth manager of the node, which requires
+    // the `admin` permission.
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Auth::CreateToken { api, perm } => {
+                let client = api.client();
+                let token = block_on(client.auth_new(&perm.up_to()))?;
+                let token = String::from_utf8(token).map_err(|err| err.to_string())?;
+                println!("{}", token);
+            }
+            Auth::ListTokens { api } => {
+                let client = api.client();
+                for token in block_on(client.auth_list())? {
+                    let allow = token
+                        .allow
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                    println!("{}  {}", token.id, allow.join(","));
+                }
+            }
+            Auth::RevokeToken { api, id } => {
+                let client = api.client();
+                if !block_on(client.auth_revoke(id))? {
+                    return Err(format!("token {} not found", id));
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -21,6 +116,158 @@ pub enum Chain {
     /// Get and print a message by its cid
     #[structopt(name = "get-message")]
     GetMessage,
+    /// Export the chain from the given tipset to a CAR file
+    #[structopt(name = "export")]
+    Export {
+        /// The path of the repo datastore
+        #[structopt(long = "path", parse(from_os_str))]
+        path: PathBuf,
+        /// The block CIDs of the tipset to export from, separated by commas
+        #[structopt(long = "tipset", required = true, use_delimiter = true)]
+        tipset: Vec<Cid>,
+        /// The number of the recent epochs whose state trees are exported
+        #[structopt(long = "recent-roots", default_value = "0")]
+        recent_roots: ChainEpoch,
+        /// Export only the messages of the recent epochs
+        #[structopt(long = "skip-old-msgs")]
+        skip_old_msgs: bool,
+        /// Export the message receipts of the recent epochs
+        #[structopt(long = "include-receipts")]
+        include_receipts: bool,
+        /// The path of the exported CAR file
+        #[structopt(name = "output", parse(from_os_str))]
+        output: PathBuf,
+    },
+    /// Replay the tipsets of the chain against the states computed by the node, and print the
+    /// first divergence
+    #[structopt(name = "replay")]
+    Replay {
+        /// The path of the repo datastore
+        #[structopt(long = "path", parse(from_os_str))]
+        path: PathBuf,
+        /// The block CIDs of the head tipset, separated by commas
+        #[structopt(long = "tipset", required = true, use_delimiter = true)]
+        tipset: Vec<Cid>,
+        /// The height of the first tipset to replay
+        #[structopt(long = "from", default_value = "0")]
+        from: ChainEpoch,
+    },
+}
+
+impl Chain {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute chain command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Chain::Export {
+                path,
+                tipset,
+                recent_roots,
+                skip_old_msgs,
+                include_receipts,
+                output,
+            } => {
+                if *recent_roots < 0 {
+                    return Err(format!("invalid recent roots: {}", recent_roots));
+                }
+                if *skip_old_msgs && *recent_roots == 0 {
+                    return Err("--skip-old-msgs must be used with --recent-roots".into());
+                }
+                let store = open_repo(path)?;
+                let head = load_tipset(&store, tipset)?;
+
+                let file = std::fs::File::create(output).map_err(|err| err.to_string())?;
+                let options = ExportOptions {
+                    recent_roots: *recent_roots,
+                    skip_old_msgs: *skip_old_msgs,
+                    include_receipts: *include_receipts,
+                };
+                let stats = export_chain(&store, &head, &options, std::io::BufWriter::new(file))
+                    .map_err(|err| err.to_string())?;
+                let summary = serde_json::json!({
+                    "Output": output,
+                    "Height": head.height(),
+                    "Headers": stats.headers,
+                    "Blocks": stats.blocks,
+                });
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&summary).map_err(|err| err.to_string())?
+                );
+            }
+            Chain::Replay { path, tipset, from } => {
+                let store = open_repo(path)?;
+                let head = Arc::new(load_tipset(&store, tipset)?);
+                if *from < 0 || *from >= head.height() {
+                    return Err(format!(
+                        "invalid height {}, expected in [0, {})",
+                        from,
+                        head.height()
+                    ));
+                }
+                let executor = StateManager::new(OfflineExecutor, store.clone());
+                let replayer = Replayer::new(executor, BlockStoreReplayLoader::new(store.clone()));
+                let report = replayer
+                    .replay_range(&ChainStore::new(store), &head, *from)
+                    .map_err(|err| err.to_string())?;
+                println!("Matched tipsets: {}", report.matched);
+                if let Some(divergence) = report.divergence {
+                    return Err(format!("diverged: {}", divergence));
+                }
+            }
+            _ => unimplemented!(),
+        }
+        Ok(())
+    }
+}
+
+// The executor of the offline commands, there is no VM to execute the tipsets, so only the
+// states computed by the node are available through the `StateManager`.
+struct OfflineExecutor;
+
+impl TipsetExecutor for OfflineExecutor {
+    fn execute_tipset(&self, tipset: &Tipset) -> anyhow::Result<(Cid, Cid)> {
+        Err(anyhow::anyhow!(
+            "the state of tipset {} at height {} is not computed by the node",
+            tipset.key(),
+            tipset.height()
+        ))
+    }
+}
+
+// Load the tipset of the block CIDs from the repo datastore.
+fn load_tipset(store: &RocksDBDataStore, cids: &[Cid]) -> Result<Tipset, String> {
+    let headers = cids
+        .iter()
+        .map(|cid| match IpldStore::get::<BlockHeader>(store, cid) {
+            Ok(Some(header)) => Ok(header),
+            Ok(None) => Err(format!("block header {} not found", cid)),
+            Err(err) => Err(err.to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Tipset::new(headers).map_err(|err| err.to_string())
+}
+
+// Open the repo datastore as a read-only instance, so that the repo of a running node can be
+// inspected.
+fn open_repo(path: &Path) -> Result<RocksDBDataStore, String> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| format!("invalid path: {}", path.display()))?;
+    let config = DatabaseConfig {
+        mode: OpenMode::ReadOnly,
+        ..Default::default()
+    };
+    RocksDBDataStore::new(&config, path).map_err(|err| err.to_string())
+}
+
+fn try_parse_address(addr_str: &str) -> Result<Address, &'static str> {
+    addr_str.parse().map_err(|_| "Invalid Address")
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -40,24 +287,296 @@ pub enum Client {
     /// Retrive data from network
     #[structopt(name = "retrieve")]
     Retrive,
-    /// Find a miner to ask
+    /// Query the current storage ask of a miner
     #[structopt(name = "query-ask")]
-    QueryAsk,
+    QueryAsk {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// The libp2p identity of the miner, looked up in the chain state by default
+        #[structopt(long = "peer-id", parse(try_from_str = try_parse_peer_id))]
+        peer_id: Option<PeerId>,
+        /// The miner address to query
+        #[structopt(parse(try_from_str = try_parse_address))]
+        miner: Address,
+    },
     /// List storage market deals
     #[structopt(name = "list-deals")]
     ListDeals,
 }
 
+impl Client {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute client command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Client::QueryAsk {
+                api,
+                peer_id,
+                miner,
+            } => {
+                let client = api.client();
+                let head = block_on(client.chain_head())?;
+                let info = block_on(client.state_miner_info(miner, head.key()))?
+                    .ok_or_else(|| format!("miner {} not found", miner))?;
+                let peer_id = peer_id.clone().unwrap_or(info.peer_id);
+                // The ask is signed by the key address of the worker.
+                let worker = block_on(client.state_account_key(&info.worker, head.key()))?;
+                let signed = block_on(client.client_query_ask(&peer_id, miner))?;
+                signed
+                    .validate(miner, &worker, head.height())
+                    .map_err(|err| err.to_string())?;
+                let ask = signed.ask;
+                println!("Ask: {}", ask.miner);
+                println!("Price per GiB: {}", ask.price);
+                println!("Verified price per GiB: {}", ask.verified_price);
+                println!(
+                    "Min piece size: {}",
+                    readable_sector_size(ask.min_piece_size.0)
+                );
+                println!(
+                    "Max piece size: {}",
+                    readable_sector_size(ask.max_piece_size.0)
+                );
+                println!("Expiry: {}", ask.expiry);
+                println!("Seq no: {}", ask.seq_no);
+            }
+            _ => unimplemented!(),
+        }
+        Ok(())
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum Log {
+    /// List the logging subsystems whose levels are overridden
+    #[structopt(name = "list")]
+    List {
+        #[structopt(flatten)]
+        api: NodeApi,
+    },
+    /// Set the logging level of a subsystem, `*` for all the subsystems
+    #[structopt(name = "set-level")]
+    SetLevel {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// The subsystem, i.e. the log target such as `plum_p2p`
+        #[structopt(name = "subsystem")]
+        subsystem: String,
+        /// The level: off, error, warn, info, debug or trace
+        #[structopt(name = "level")]
+        level: String,
+    },
+}
+
+impl Log {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute log command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Log::List { api } => {
+                let client = api.client();
+                for subsystem in block_on(client.log_list())? {
+                    println!("{}", subsystem);
+                }
+            }
+            Log::SetLevel {
+                api,
+                subsystem,
+                level,
+            } => {
+                let client = api.client();
+                block_on(client.log_set_level(subsystem, level))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn try_parse_actor_code(code_str: &str) -> Result<Cid, &'static str> {
+    match codes::actor_code(code_str) {
+        Some(code) => Ok(code.clone()),
+        None => code_str
+            .parse()
+            .map_err(|_| "Invalid actor name or code CID"),
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum Message {
+    /// Decode the params of a message and print them as JSON
+    #[structopt(name = "decode")]
+    Decode {
+        /// The name of the builtin actor (e.g. storageminer) or the code CID of the receiver
+        #[structopt(long = "actor", parse(try_from_str = try_parse_actor_code))]
+        actor: Cid,
+        /// The method number
+        #[structopt(long = "method")]
+        method: MethodNum,
+        /// The hex encoded params
+        #[structopt(name = "params")]
+        params: String,
+    },
+}
+
+impl Message {
+    pub fn execute(&self) {
+        match self {
+            Message::Decode {
+                actor,
+                method,
+                params,
+            } => {
+                let result = hex::decode(params.trim_start_matches("0x"))
+                    .map_err(|err| err.to_string())
+                    .and_then(|params| {
+                        BUILTIN_PARAMS_REGISTRY
+                            .decode_json(actor, *method, &params)
+                            .map_err(|err| err.to_string())
+                    });
+                match result {
+                    Ok(json) => {
+                        println!("{}", BUILTIN_PARAMS_REGISTRY.describe(actor, *method));
+                        println!(
+                            "{}",
+                            serde_json::to_string_pretty(&json)
+                                .expect("JSON serialization of params shouldn't fail; qed")
+                        );
+                    }
+                    Err(err) => {
+                        eprintln!("Failed to decode the params: {}", err);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn try_parse_seal_proof(size_str: &str) -> Result<RegisteredSealProof, &'static str> {
+    let size = match size_str {
+        "2KiB" => 2 << 10,
+        "8MiB" => 8 << 20,
+        "512MiB" => 512 << 20,
+        "32GiB" => 32 << 30,
+        "64GiB" => 64 << 30,
+        _ => return Err("Invalid sector size"),
+    };
+    RegisteredSealProof::from_sector_size(size).map_err(|_| "Invalid sector size")
+}
+
+fn try_parse_peer_id(peer_id_str: &str) -> Result<PeerId, &'static str> {
+    peer_id_str.parse().map_err(|_| "Invalid PeerId")
+}
+
 #[derive(StructOpt, Debug, Clone)]
 pub enum Miner {
-    /// Create a new storage market actor
-    #[structopt(name = "create")]
-    Create,
+    /// Create a new miner actor via the storage power actor, print the message
+    #[structopt(name = "init")]
+    Init {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The owner of the miner, the sender by default
+        #[structopt(long = "owner", parse(try_from_str = try_parse_address))]
+        owner: Option<Address>,
+        /// The worker of the miner, the owner by default
+        #[structopt(long = "worker", parse(try_from_str = try_parse_address))]
+        worker: Option<Address>,
+        /// The size of the sectors
+        #[structopt(
+            long = "sector-size",
+            default_value = "32GiB",
+            possible_values = &["2KiB", "8MiB", "512MiB", "32GiB", "64GiB"],
+            parse(try_from_str = try_parse_seal_proof)
+        )]
+        seal_proof: RegisteredSealProof,
+        /// The libp2p identity of the miner
+        #[structopt(long = "peer-id", parse(try_from_str = try_parse_peer_id))]
+        peer_id: PeerId,
+        /// The address the miner listens on, can be specified multiple times
+        #[structopt(long = "multiaddr", parse(try_from_str = try_parse_multiaddr))]
+        multiaddrs: Vec<Multiaddr>,
+        /// The value sent along with the message
+        #[structopt(long = "value", default_value = "0", parse(try_from_str = try_parse_bigint))]
+        value: BigInt,
+    },
+    /// Print the info of a miner
+    #[structopt(name = "info")]
+    Info {
+        /// The hex encoded CBOR of the state of the miner actor
+        #[structopt(name = "state")]
+        state: String,
+        /// The balance of the miner actor, used to compute the available balance
+        #[structopt(long = "balance", parse(try_from_str = try_parse_bigint))]
+        balance: Option<BigInt>,
+        /// The current epoch, used to compute the current deadline
+        #[structopt(long = "epoch")]
+        epoch: Option<ChainEpoch>,
+    },
+    /// Inspect the sectors of a miner
+    #[structopt(name = "sectors")]
+    Sectors(Sectors),
+    /// Inspect the proving of a miner
+    #[structopt(name = "proving")]
+    Proving(Proving),
+    /// Rotate the worker key of a miner
+    #[structopt(name = "set-worker")]
+    SetWorker(SetWorker),
+    /// Change the owner of a miner, print the message to the miner, which must be sent by
+    /// the current owner, then by the new owner with the same params to confirm the change
+    #[structopt(name = "set-owner")]
+    SetOwner {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The address of the miner
+        #[structopt(name = "miner", parse(try_from_str = try_parse_address))]
+        miner: Address,
+        /// The new owner, must be an ID address
+        #[structopt(name = "new-owner", parse(try_from_str = try_parse_address))]
+        new_owner: Address,
+    },
     /// Manually unregister miner actor
     #[structopt(name = "unregister")]
     Unregister,
 }
 
+#[derive(StructOpt, Debug, Clone)]
+pub enum Sectors {
+    /// List the sectors
+    #[structopt(name = "list")]
+    List {
+        /// The JSON file of the sectors, as returned by `StateMinerSectors`
+        #[structopt(name = "path", parse(from_os_str))]
+        path: PathBuf,
+        /// The current epoch, the expired sectors are marked
+        #[structopt(long = "epoch")]
+        epoch: Option<ChainEpoch>,
+    },
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum Proving {
+    /// List the deadlines of the current proving period
+    #[structopt(name = "deadlines")]
+    Deadlines {
+        /// The hex encoded CBOR of the state of the miner actor
+        #[structopt(name = "state")]
+        state: String,
+        /// The current epoch
+        #[structopt(long = "epoch")]
+        epoch: ChainEpoch,
+    },
+}
+
 #[derive(StructOpt, Debug, Clone)]
 pub enum MessagePool {
     #[structopt(name = "pending")]
@@ -68,6 +587,622 @@ pub enum MessagePool {
     Subscribe,
 }
 
+#[derive(StructOpt, Debug, Clone)]
+pub enum SetWorker {
+    /// Request the worker key change, print the `ChangeWorkerAddress` message to the miner,
+    /// which must be sent by the owner, and track the pending change in the state file
+    #[structopt(name = "propose")]
+    Propose {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The file tracking the pending change
+        #[structopt(long = "state-file", parse(from_os_str))]
+        state_file: PathBuf,
+        /// The current epoch
+        #[structopt(long = "epoch")]
+        epoch: ChainEpoch,
+        /// The new control address, can be specified multiple times
+        #[structopt(long = "control", parse(try_from_str = try_parse_address))]
+        controls: Vec<Address>,
+        /// The address of the miner
+        #[structopt(name = "miner", parse(try_from_str = try_parse_address))]
+        miner: Address,
+        /// The new worker, must be an ID address
+        #[structopt(name = "new-worker", parse(try_from_str = try_parse_address))]
+        new_worker: Address,
+    },
+    /// Confirm the pending worker key change, print the `ConfirmUpdateWorkerKey` message to
+    /// the miner once the change is effective
+    #[structopt(name = "confirm")]
+    Confirm {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The file tracking the pending change
+        #[structopt(long = "state-file", parse(from_os_str))]
+        state_file: PathBuf,
+        /// The current epoch
+        #[structopt(long = "epoch")]
+        epoch: ChainEpoch,
+        /// The hex encoded CBOR of the state of the miner actor, used to check the change on chain
+        #[structopt(long = "state")]
+        state: Option<String>,
+    },
+    /// Print the status of the pending worker key change
+    #[structopt(name = "status")]
+    Status {
+        /// The file tracking the pending change
+        #[structopt(long = "state-file", parse(from_os_str))]
+        state_file: PathBuf,
+        /// The current epoch
+        #[structopt(long = "epoch")]
+        epoch: ChainEpoch,
+        /// The hex encoded CBOR of the state of the miner actor, used to check the change on chain
+        #[structopt(long = "state")]
+        state: Option<String>,
+    },
+}
+
+impl SetWorker {
+    fn run(&self) -> Result<(), String> {
+        match self {
+            SetWorker::Propose {
+                sender,
+                state_file,
+                epoch,
+                controls,
+                miner,
+                new_worker,
+            } => {
+                if !new_worker.is_id() {
+                    return Err(format!("new worker {} must be an ID address", new_worker));
+                }
+                if let Some(pending) = load_worker_rotation(state_file)? {
+                    return Err(format!(
+                        "worker change of {} to {} is pending, confirm it first",
+                        pending.miner, pending.new_worker
+                    ));
+                }
+                let params = miner::ChangeWorkerAddressParams {
+                    new_worker: new_worker.clone(),
+                    new_control_addrs: controls.clone(),
+                };
+                let msg = sender.message(
+                    miner.clone(),
+                    BigInt::from(0),
+                    miner::Method::ChangeWorkerAddress.into(),
+                    to_cbor(&params),
+                );
+                let rotation =
+                    miner::WorkerKeyRotation::new(miner.clone(), new_worker.clone(), *epoch);
+                let data = serde_json::to_vec_pretty(&rotation).map_err(|err| err.to_string())?;
+                std::fs::write(state_file, data).map_err(|err| err.to_string())?;
+                eprintln!(
+                    "The change can be confirmed at epoch {}",
+                    rotation.effective_at
+                );
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&msg).map_err(|err| err.to_string())?
+                );
+            }
+            SetWorker::Confirm {
+                sender,
+                state_file,
+                epoch,
+                state,
+            } => {
+                let rotation = load_worker_rotation(state_file)?
+                    .ok_or_else(|| "no pending worker change".to_string())?;
+                let state = state.as_deref().map(decode_miner_state).transpose()?;
+                let info = state.as_ref().map(|state| &state.info);
+                match rotation.status(info, *epoch) {
+                    miner::WorkerRotationStatus::Ready => {}
+                    miner::WorkerRotationStatus::Pending => {
+                        return Err(format!(
+                            "the change is not effective until epoch {}",
+                            info.map(|info| info.pending_worker_key.effective_at)
+                                .unwrap_or(rotation.effective_at)
+                        ))
+                    }
+                    miner::WorkerRotationStatus::Confirmed => {
+                        std::fs::remove_file(state_file).map_err(|err| err.to_string())?;
+                        return Err(format!("the worker is already {}", rotation.new_worker));
+                    }
+                    miner::WorkerRotationStatus::Unknown => {
+                        return Err("the change is not pending on chain".into())
+                    }
+                }
+                let msg = sender.message(
+                    rotation.miner.clone(),
+                    BigInt::from(0),
+                    miner::Method::ConfirmUpdateWorkerKey.into(),
+                    vec![],
+                );
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&msg).map_err(|err| err.to_string())?
+                );
+            }
+            SetWorker::Status {
+                state_file,
+                epoch,
+                state,
+            } => {
+                let rotation = match load_worker_rotation(state_file)? {
+                    Some(rotation) => rotation,
+                    None => {
+                        println!("No pending worker change");
+                        return Ok(());
+                    }
+                };
+                let state = state.as_deref().map(decode_miner_state).transpose()?;
+                let status = rotation.status(state.as_ref().map(|state| &state.info), *epoch);
+                println!("Miner: {}", rotation.miner);
+                println!("New worker: {}", rotation.new_worker);
+                println!("Requested at: {}", rotation.requested_at);
+                println!("Effective at: {}", rotation.effective_at);
+                println!("Status: {:?}", status);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn load_worker_rotation(path: &Path) -> Result<Option<miner::WorkerKeyRotation>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+    serde_json::from_reader(file)
+        .map(Some)
+        .map_err(|err| format!("invalid state file: {}", err))
+}
+
+fn try_parse_bigint(value_str: &str) -> Result<BigInt, &'static str> {
+    value_str.parse().map_err(|_| "Invalid amount")
+}
+
+fn decode_hex(hex_str: &str) -> Result<Vec<u8>, String> {
+    hex::decode(hex_str.trim_start_matches("0x")).map_err(|err| err.to_string())
+}
+
+/// The API of the full node that the commands talk to.
+#[derive(StructOpt, Debug, Clone)]
+pub struct NodeApi {
+    /// The URL of the API of the full node
+    #[structopt(long = "api", default_value = "http://127.0.0.1:1234/rpc/v0")]
+    api: String,
+    /// The token of the API, which must grant the permission required by the command
+    #[structopt(long = "token")]
+    token: Option<String>,
+}
+
+impl NodeApi {
+    fn client(&self) -> HttpTransport {
+        match &self.token {
+            Some(token) => HttpTransport::new_with_bearer_auth(self.api.as_str(), token.as_str()),
+            None => HttpTransport::new(self.api.as_str()),
+        }
+    }
+}
+
+// Run the API request to completion.
+fn block_on<T>(
+    request: impl std::future::Future<Output = plum_api_client::Result<T>>,
+) -> Result<T, String> {
+    let mut runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+    runtime.block_on(request).map_err(|err| err.to_string())
+}
+
+/// The sender of the messages constructed by the CLI.
+#[derive(StructOpt, Debug, Clone)]
+pub struct Sender {
+    /// The address of the sender
+    #[structopt(long = "from", parse(try_from_str = try_parse_address))]
+    from: Address,
+    /// The nonce of the sender
+    #[structopt(long = "nonce")]
+    nonce: u64,
+}
+
+impl Sender {
+    fn message(
+        &self,
+        to: Address,
+        value: BigInt,
+        method: MethodNum,
+        params: Vec<u8>,
+    ) -> UnsignedMessage {
+        UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to,
+            from: self.from.clone(),
+            nonce: self.nonce,
+            value,
+            gas_price: BigInt::from(0),
+            gas_limit: 0,
+            gas_fee_cap: BigInt::from(0),
+            gas_premium: BigInt::from(0),
+            method,
+            params,
+        }
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum Msig {
+    /// Create a new multisig wallet, print the message to the init actor
+    #[structopt(name = "create")]
+    Create {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The number of the approvals required, the number of the signers by default
+        #[structopt(long = "required")]
+        required: Option<u64>,
+        /// The number of epochs over which the initial balance is unlocked
+        #[structopt(long = "duration", default_value = "0")]
+        duration: ChainEpoch,
+        /// The initial balance of the wallet
+        #[structopt(long = "value", default_value = "0", parse(try_from_str = try_parse_bigint))]
+        value: BigInt,
+        /// The signers of the wallet
+        #[structopt(name = "signers", required = true, parse(try_from_str = try_parse_address))]
+        signers: Vec<Address>,
+    },
+    /// Propose a multisig transaction, print the message to the multisig wallet
+    #[structopt(name = "propose")]
+    Propose {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The address of the multisig wallet
+        #[structopt(name = "msig", parse(try_from_str = try_parse_address))]
+        msig: Address,
+        /// The receiver of the transaction
+        #[structopt(name = "to", parse(try_from_str = try_parse_address))]
+        to: Address,
+        /// The value of the transaction
+        #[structopt(name = "value", parse(try_from_str = try_parse_bigint))]
+        value: BigInt,
+        /// The method number of the transaction
+        #[structopt(long = "method", default_value = "0")]
+        method: MethodNum,
+        /// The hex encoded params of the transaction
+        #[structopt(long = "params", default_value = "")]
+        params: String,
+    },
+    /// Approve a multisig transaction, print the message to the multisig wallet
+    #[structopt(name = "approve")]
+    Approve {
+        #[structopt(flatten)]
+        sender: Sender,
+        /// The address of the multisig wallet
+        #[structopt(name = "msig", parse(try_from_str = try_parse_address))]
+        msig: Address,
+        /// The ID of the pending transaction
+        #[structopt(name = "txn-id")]
+        txn_id: TxnID,
+        /// The hex encoded hash of the proposal, which is checked against the pending transaction
+        #[structopt(long = "proposal-hash", default_value = "")]
+        proposal_hash: String,
+    },
+    /// Inspect a multisig wallet
+    #[structopt(name = "inspect")]
+    Inspect {
+        /// The hex encoded CBOR of the state of the multisig actor
+        #[structopt(name = "state")]
+        state: String,
+        /// The current epoch, used to compute the locked balance
+        #[structopt(long = "epoch")]
+        epoch: Option<ChainEpoch>,
+        /// The pending transaction as `<id>:<hex encoded CBOR>`, can be specified multiple times
+        #[structopt(long = "txn")]
+        txns: Vec<String>,
+    },
+}
+
+impl Msig {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute msig command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        let msg = match self {
+            Msig::Create {
+                sender,
+                required,
+                duration,
+                value,
+                signers,
+            } => {
+                let required = required.unwrap_or(signers.len() as u64);
+                if required == 0 || required > signers.len() as u64 {
+                    return Err(format!(
+                        "required approvals {} must be in [1, {}]",
+                        required,
+                        signers.len()
+                    ));
+                }
+                let params = multisig::ConstructorParams {
+                    signers: signers.clone(),
+                    num_approvals_threshold: required,
+                    unlock_duration: *duration,
+                };
+                let params = init::ExecParams {
+                    code_cid: codes::MULTISIG_ACTOR_CODE_ID.clone(),
+                    constructor_params: to_cbor(&params),
+                };
+                let init_actor = Address::new_id_addr(init::INIT_ACTOR_ID)
+                    .expect("ID address of init actor must be valid; qed");
+                sender.message(
+                    init_actor,
+                    value.clone(),
+                    init::Method::Exec.into(),
+                    to_cbor(&params),
+                )
+            }
+            Msig::Propose {
+                sender,
+                msig,
+                to,
+                value,
+                method,
+                params,
+            } => {
+                let params = multisig::ProposeParams {
+                    to: to.clone(),
+                    value: value.clone(),
+                    method: *method,
+                    params: decode_hex(params)?,
+                };
+                sender.message(
+                    msig.clone(),
+                    BigInt::from(0),
+                    multisig::Method::Propose.into(),
+                    to_cbor(&params),
+                )
+            }
+            Msig::Approve {
+                sender,
+                msig,
+                txn_id,
+                proposal_hash,
+            } => {
+                let params = multisig::TxnIDParams {
+                    id: *txn_id,
+                    proposal_hash: decode_hex(proposal_hash)?,
+                };
+                sender.message(
+                    msig.clone(),
+                    BigInt::from(0),
+                    multisig::Method::Approve.into(),
+                    to_cbor(&params),
+                )
+            }
+            Msig::Inspect { state, epoch, txns } => {
+                return inspect_msig(state, *epoch, txns);
+            }
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&msg).map_err(|err| err.to_string())?
+        );
+        Ok(())
+    }
+}
+
+fn to_cbor<T: minicbor::Encode>(params: &T) -> Vec<u8> {
+    minicbor::to_vec(params).expect("CBOR serialization of params shouldn't fail; qed")
+}
+
+fn inspect_msig(state: &str, epoch: Option<ChainEpoch>, txns: &[String]) -> Result<(), String> {
+    let state =
+        minicbor::decode::<multisig::State>(&decode_hex(state)?).map_err(|err| err.to_string())?;
+    println!(
+        "Threshold: {} / {}",
+        state.num_approvals_threshold,
+        state.signers.len()
+    );
+    println!("Signers:");
+    for signer in &state.signers {
+        println!("  {}", signer);
+    }
+    println!("Initial balance: {}", state.initial_balance);
+    println!("Start epoch: {}", state.start_epoch);
+    println!("Unlock duration: {}", state.unlock_duration);
+    if let Some(epoch) = epoch {
+        println!("Locked at {}: {}", epoch, state.amount_locked(epoch));
+    }
+    println!("Next transaction ID: {}", state.next_txn_id);
+    println!("Pending transactions: {}", state.pending_txns);
+
+    for entry in txns {
+        let mut parts = entry.splitn(2, ':');
+        let id = parts
+            .next()
+            .and_then(|id| id.parse::<TxnID>().ok())
+            .ok_or_else(|| format!("invalid transaction ID of {}", entry))?;
+        let data = decode_hex(parts.next().unwrap_or_default())?;
+        let txn =
+            minicbor::decode::<multisig::Transaction>(&data).map_err(|err| err.to_string())?;
+        let remaining = txn.remaining_approvals(state.num_approvals_threshold);
+        println!(
+            "  #{}: {} to {}, method {}, approved by {} of {}{}",
+            id,
+            txn.value,
+            txn.to,
+            txn.method,
+            txn.approved.len(),
+            state.num_approvals_threshold,
+            if remaining == 0 { ", ready" } else { "" },
+        );
+        for approver in &txn.approved {
+            let signer = if state.is_signer(approver) {
+                ""
+            } else {
+                " (not a signer)"
+            };
+            println!("    {}{}", approver, signer);
+        }
+    }
+    Ok(())
+}
+
+impl Miner {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute miner command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Miner::Init {
+                sender,
+                owner,
+                worker,
+                seal_proof,
+                peer_id,
+                multiaddrs,
+                value,
+            } => {
+                let owner = owner.clone().unwrap_or_else(|| sender.from.clone());
+                let params = power::CreateMinerParams {
+                    worker: worker.clone().unwrap_or_else(|| owner.clone()),
+                    owner,
+                    seal_proof_type: *seal_proof,
+                    peer: peer_id.clone(),
+                    multiaddrs: multiaddrs.iter().map(|addr| addr.to_vec().into()).collect(),
+                };
+                let power_actor = Address::new_id_addr(power::STORAGE_POWER_ACTOR_ID)
+                    .expect("ID address of storage power actor must be valid; qed");
+                let msg = sender.message(
+                    power_actor,
+                    value.clone(),
+                    power::Method::CreateMiner.into(),
+                    to_cbor(&params),
+                );
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&msg).map_err(|err| err.to_string())?
+                );
+            }
+            Miner::Info {
+                state,
+                balance,
+                epoch,
+            } => {
+                let state = decode_miner_state(state)?;
+                let info = &state.info;
+                println!("Owner: {}", info.owner);
+                println!("Worker: {}", info.worker);
+                if info.pending_worker_key.effective_at != 0 {
+                    println!(
+                        "Pending worker: {} (effective at {})",
+                        info.pending_worker_key.new_worker, info.pending_worker_key.effective_at
+                    );
+                }
+                println!("PeerID: {}", info.peer_id);
+                println!("Sector size: {}", readable_sector_size(info.sector_size));
+                println!(
+                    "Window PoSt partition sectors: {}",
+                    info.window_post_partition_sectors
+                );
+                println!("Pre-commit deposits: {}", state.pre_commit_deposits);
+                println!("Locked funds: {}", state.locked_funds);
+                if let Some(balance) = balance {
+                    println!("Available balance: {}", state.available_balance(balance));
+                }
+                println!("Proving period start: {}", state.proving_period_start);
+                println!("New sectors: {}", state.new_sectors.len());
+                println!("Faults: {}", state.faults.len());
+                println!("Recoveries: {}", state.recoveries.len());
+                if let Some(epoch) = epoch {
+                    let deadline = state.deadline_info(*epoch);
+                    println!(
+                        "Current deadline: {} (open {}, close {})",
+                        deadline.index, deadline.open, deadline.close
+                    );
+                }
+            }
+            Miner::Sectors(Sectors::List { path, epoch }) => {
+                let file = std::fs::File::open(path).map_err(|err| err.to_string())?;
+                let sectors = serde_json::from_reader::<_, Vec<miner::SectorOnChainInfo>>(file)
+                    .map_err(|err| err.to_string())?;
+                println!("Number\tSealedCID\tActivation\tExpiration\tDeals\tInitialPledge");
+                for sector in sectors {
+                    let expired = match epoch {
+                        Some(epoch) if sector.expiration <= *epoch => " (expired)",
+                        _ => "",
+                    };
+                    println!(
+                        "{}\t{}\t{}\t{}{}\t{}\t{}",
+                        sector.sector_number,
+                        sector.sealed_cid,
+                        sector.activation,
+                        sector.expiration,
+                        expired,
+                        sector.deal_ids.len(),
+                        sector.initial_pledge
+                    );
+                }
+            }
+            Miner::Proving(Proving::Deadlines { state, epoch }) => {
+                let state = decode_miner_state(state)?;
+                let current = state.deadline_info(*epoch);
+                println!("Proving period start: {}", current.period_start);
+                println!("Index\tChallenge\tOpen\tClose\tFaultCutoff");
+                for index in 0..miner::W_POST_PERIOD_DEADLINES {
+                    let deadline = miner::DeadlineInfo::new(current.period_start, index, *epoch);
+                    let mark = if index == current.index {
+                        " (current)"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "{}{}\t{}\t{}\t{}\t{}",
+                        index,
+                        mark,
+                        deadline.challenge,
+                        deadline.open,
+                        deadline.close,
+                        deadline.fault_cutoff
+                    );
+                }
+            }
+            Miner::SetWorker(set_worker) => set_worker.run()?,
+            Miner::SetOwner {
+                sender,
+                miner,
+                new_owner,
+            } => {
+                if !new_owner.is_id() {
+                    return Err(format!("new owner {} must be an ID address", new_owner));
+                }
+                let msg = sender.message(
+                    miner.clone(),
+                    BigInt::from(0),
+                    miner::Method::ChangeOwnerAddress.into(),
+                    to_cbor(new_owner),
+                );
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&msg).map_err(|err| err.to_string())?
+                );
+            }
+            Miner::Unregister => unimplemented!(),
+        }
+        Ok(())
+    }
+}
+
+fn decode_miner_state(state: &str) -> Result<miner::State, String> {
+    minicbor::decode::<miner::State>(&decode_hex(state)?).map_err(|err| err.to_string())
+}
+
 fn try_parse_multiaddr(peer_str: &str) -> Result<Multiaddr, &'static str> {
     peer_str.parse().map_err(|_| "Invalid Multiaddr")
 }
@@ -76,31 +1211,144 @@ fn try_parse_multiaddr(peer_str: &str) -> Result<Multiaddr, &'static str> {
 pub enum Network {
     /// Print peers
     #[structopt(name = "peers")]
-    Peers,
+    Peers {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// Print the agent version and the supported protocols of the peers
+        #[structopt(short = "v", long = "verbose")]
+        verbose: bool,
+    },
     /// List listen addresses
     #[structopt(name = "listen")]
-    Listen,
+    Listen {
+        #[structopt(flatten)]
+        api: NodeApi,
+    },
     /// Connect to a peer
     #[structopt(name = "connect")]
     Connect {
-        /// Specify an IPFS peer ip to connect
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// Specify the address of the peer, ending with `/p2p/<peer id>`
         #[structopt(short = "p", long = "peer", parse(try_from_str = try_parse_multiaddr))]
         peer: Multiaddr,
     },
     /// Get node identity
     #[structopt(name = "id")]
-    Id,
+    Id {
+        #[structopt(flatten)]
+        api: NodeApi,
+    },
+    /// Print the bandwidth usage, in total, per peer or per protocol
+    #[structopt(name = "stat")]
+    Stat {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// Print the bandwidth usage of every peer
+        #[structopt(long = "by-peer", conflicts_with = "by-protocol")]
+        by_peer: bool,
+        /// Print the bandwidth usage of every protocol
+        #[structopt(long = "by-protocol")]
+        by_protocol: bool,
+    },
 }
 
 impl Network {
-    /*
     pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute net command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
         match self {
-            Network::Connect { peer } => run_lp2p(Some(peer.to_owned())),
-            _ => unimplemented!(),
+            Network::Peers { api, verbose } => {
+                let client = api.client();
+                let mut peers = block_on(client.net_peers())?;
+                peers.sort_by(|a, b| a.id.to_base58().cmp(&b.id.to_base58()));
+                for peer in peers {
+                    let addrs = peer
+                        .addrs
+                        .iter()
+                        .map(|addr| addr.to_string())
+                        .collect::<Vec<_>>();
+                    if *verbose {
+                        let info = block_on(client.net_peer_info(&peer.id))?;
+                        println!(
+                            "{}, [{}], {}, [{}]",
+                            peer.id,
+                            addrs.join(" "),
+                            info.agent,
+                            info.protocols.join(" ")
+                        );
+                    } else {
+                        println!("{}, [{}]", peer.id, addrs.join(" "));
+                    }
+                }
+            }
+            Network::Listen { api } => {
+                let client = api.client();
+                let info = block_on(client.net_addrs_listen())?;
+                for addr in info.addrs {
+                    println!("{}/p2p/{}", addr, info.id);
+                }
+            }
+            Network::Connect { api, peer } => {
+                let client = api.client();
+                let info = peer_addr_info(peer)?;
+                block_on(client.net_connect(&info))?;
+                println!("connect {}: success", info.id);
+            }
+            Network::Id { api } => {
+                let client = api.client();
+                println!("{}", block_on(client.id())?);
+            }
+            Network::Stat {
+                api,
+                by_peer,
+                by_protocol,
+            } => {
+                let client = api.client();
+                println!(
+                    "{:<54} {:>12} {:>12} {:>12} {:>12}",
+                    "Segment", "TotalIn", "TotalOut", "RateIn", "RateOut"
+                );
+                let stats = if *by_peer {
+                    block_on(client.net_bandwidth_stats_by_peer())?
+                } else if *by_protocol {
+                    block_on(client.net_bandwidth_stats_by_protocol())?
+                } else {
+                    let mut stats = HashMap::new();
+                    stats.insert("Total".to_string(), block_on(client.net_bandwidth_stats())?);
+                    stats
+                };
+                let mut stats = stats.into_iter().collect::<Vec<_>>();
+                stats.sort_by(|a, b| a.0.cmp(&b.0));
+                for (segment, stat) in stats {
+                    println!(
+                        "{:<54} {:>12} {:>12} {:>10.1}/s {:>10.1}/s",
+                        segment, stat.total_in, stat.total_out, stat.rate_in, stat.rate_out
+                    );
+                }
+            }
         }
+        Ok(())
     }
-    */
+}
+
+// Split the address ending with `/p2p/<peer id>` into the peer id and the transport address.
+fn peer_addr_info(addr: &Multiaddr) -> Result<PeerAddrInfo, String> {
+    let mut transport = addr.clone();
+    let id = match transport.pop() {
+        Some(Protocol::P2p(mh)) => PeerId::from_multihash(mh)
+            .map_err(|_| format!("Invalid peer id in address {}", addr))?,
+        _ => return Err(format!("Address {} has no peer id", addr)),
+    };
+    Ok(PeerAddrInfo {
+        id,
+        addrs: vec![transport],
+    })
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -164,6 +1412,144 @@ pub enum State {
     /// Find coresponding ID address
     #[structopt(name = "lookup")]
     Lookup,
+    /// Validate the invariants of a state tree in the repo datastore
+    #[structopt(name = "audit")]
+    Audit {
+        /// The path of the repo datastore
+        #[structopt(long = "path", parse(from_os_str))]
+        path: PathBuf,
+        /// The epoch of the state tree, which the proving periods of the miners are checked at
+        #[structopt(long = "epoch")]
+        epoch: ChainEpoch,
+        /// The expected total supply in attoFIL, the total supply of mainnet by default
+        #[structopt(long = "total-supply", parse(try_from_str = try_parse_bigint))]
+        total_supply: Option<BigInt>,
+        /// Don't walk the blocks reachable from the actor states, which is slow
+        #[structopt(long = "skip-blocks")]
+        skip_blocks: bool,
+        /// The CID of the state root
+        #[structopt(name = "root")]
+        root: Cid,
+    },
+    /// Print the actors changed between two state trees in the repo datastore
+    #[structopt(name = "diff")]
+    Diff {
+        /// The path of the repo datastore
+        #[structopt(long = "path", parse(from_os_str))]
+        path: PathBuf,
+        /// The CID of the state root before the changes
+        #[structopt(name = "root-a")]
+        root_a: Cid,
+        /// The CID of the state root after the changes
+        #[structopt(name = "root-b")]
+        root_b: Cid,
+    },
+}
+
+impl State {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute state command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            State::Audit {
+                path,
+                epoch,
+                total_supply,
+                skip_blocks,
+                root,
+            } => {
+                let view = BlockStoreView::new(open_repo(path)?);
+                let mut auditor = StateAuditor::new(&view).with_check_blocks(!skip_blocks);
+                if let Some(total_supply) = total_supply {
+                    auditor = auditor.with_total_supply(total_supply.clone());
+                }
+                let report = auditor.audit(root, *epoch).map_err(|err| err.to_string())?;
+                println!("Actors: {}", report.actors);
+                println!("Blocks: {}", report.blocks);
+                println!("Total balance: {}", report.total_balance);
+                for violation in &report.violations {
+                    println!("Violation: {}", violation);
+                }
+                if !report.is_ok() {
+                    return Err(format!("{} violations found", report.violations.len()));
+                }
+            }
+            State::Diff {
+                path,
+                root_a,
+                root_b,
+            } => {
+                let view = BlockStoreView::new(open_repo(path)?);
+                let diffs = diff(&view, root_a, root_b).map_err(|err| err.to_string())?;
+                for ActorDiff { address, change } in diffs {
+                    match change {
+                        ActorChange::Added(actor) => println!("+ {}: {}", address, actor),
+                        ActorChange::Removed(actor) => println!("- {}: {}", address, actor),
+                        ActorChange::Modified { fields, .. } => {
+                            println!("~ {}", address);
+                            for field in fields {
+                                println!(
+                                    "    {}: {} -> {}",
+                                    field.path,
+                                    field.before.as_deref().unwrap_or("<none>"),
+                                    field.after.as_deref().unwrap_or("<none>")
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+            _ => unimplemented!(),
+        }
+        Ok(())
+    }
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum Repo {
+    /// Print the disk usage and the number of keys of the repo datastore
+    #[structopt(name = "stat")]
+    Stat {
+        /// The path of the repo datastore
+        #[structopt(long = "path", parse(from_os_str))]
+        path: PathBuf,
+    },
+}
+
+impl Repo {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute repo command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Repo::Stat { path } => {
+                let store = open_repo(path)?;
+                let disk_usage = store.disk_usage().map_err(|err| err.to_string())?;
+                let keys = store
+                    .num_keys(DEFAULT_COLUMN_NAME)
+                    .map_err(|err| err.to_string())?;
+                let stat = serde_json::json!({
+                    "Path": path,
+                    "DiskUsage": disk_usage,
+                    "NumKeys": keys,
+                });
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&stat).map_err(|err| err.to_string())?
+                );
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(StructOpt, Debug, Clone)]
@@ -173,73 +1559,152 @@ pub enum Sync {
     Status,
 }
 
-/*
-arg_enum! {
-    #[derive(Debug, Clone)]
-    pub enum KeyType {
-        Bls,
-        Secp256k1,
-    }
-}
-
 #[derive(StructOpt, Debug, Clone)]
 pub enum Wallet {
-    #[structopt(name = "new")]
-    /// Generate a new key of the given type
-    New {
-        #[structopt(short="t", long="type", possible_values = &KeyType::variants(), case_insensitive = true)]
-        key_type: KeyType,
-    },
-    #[structopt(name = "list")]
-    /// List all the key in keystore
-    List,
-    #[structopt(name = "export")]
-    /// Export key-info by pubkey
-    Export {
-        #[structopt(short = "public-key", long = "public-key", case_insensitive = true)]
-        pubkey: String,
+    /// Sign the arbitrary data, which is prefixed with a domain separation tag before signing,
+    /// so that the signature can't be used as a signature of a chain message
+    #[structopt(name = "sign")]
+    Sign {
+        /// The path of the key file exported by `wallet export` (hex encoded key info)
+        #[structopt(long = "key", parse(from_os_str))]
+        key: PathBuf,
+        /// Sign the raw data without the domain separation tag, only for the data whose
+        /// content is trusted, e.g. the signing payload of a message constructed by yourself
+        #[structopt(long = "raw")]
+        raw: bool,
+        /// The address of the signer
+        #[structopt(name = "address", parse(try_from_str = try_parse_address))]
+        address: Address,
+        /// The hex encoded data
+        #[structopt(name = "data")]
+        data: String,
     },
-    #[structopt(name = "import")]
-    /// Import key-info by pubkey
-    Import {
-        #[structopt(short="t", long="type", possible_values = &KeyType::variants(), case_insensitive = true)]
-        key_type: KeyType,
-        #[structopt(short = "private-key", long = "private-key", case_insensitive = true)]
-        privkey: String,
+    /// Verify the signature of the data signed by `wallet sign`
+    #[structopt(name = "verify")]
+    Verify {
+        /// Verify the signature of the raw data without the domain separation tag
+        #[structopt(long = "raw")]
+        raw: bool,
+        /// The address of the signer
+        #[structopt(name = "address", parse(try_from_str = try_parse_address))]
+        address: Address,
+        /// The hex encoded data
+        #[structopt(name = "data")]
+        data: String,
+        /// The hex encoded signature, the signature type byte followed by the signature bytes
+        #[structopt(name = "signature")]
+        signature: String,
     },
-    #[structopt(name = "balance")]
-    /// Get balance info by address
-    Balance {
-        #[structopt(short = "balance", long = "balance", case_insensitive = true)]
-        address: String,
+    /// List the executed messages sent or received by the address, the latest first
+    #[structopt(name = "history")]
+    History {
+        #[structopt(flatten)]
+        api: NodeApi,
+        /// The max number of the messages listed
+        #[structopt(long = "limit", default_value = "100")]
+        limit: u64,
+        /// The address sending or receiving the messages
+        #[structopt(name = "address", parse(try_from_str = try_parse_address))]
+        address: Address,
     },
 }
 
 impl Wallet {
     pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute wallet command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
         match self {
-            Wallet::New { key_type } => {
-                let keytype = match key_type {
-                    KeyType::Bls => key_types::Bls,
-                    KeyType::Secp256k1 => key_types::Secp256k1,
-                };
-                wallet::Wallet::new_address(keytype.to_owned())
-            }
-            Wallet::List => wallet::Wallet::wallet_list(),
-            Wallet::Export { pubkey } => wallet::Wallet::export(pubkey.to_string()),
-            Wallet::Import { key_type, privkey } => {
-                let keytype = match key_type {
-                    KeyType::Bls => key_types::Bls,
-                    KeyType::Secp256k1 => key_types::Secp256k1,
+            Wallet::Sign {
+                key,
+                raw,
+                address,
+                data,
+            } => {
+                let key = std::fs::read_to_string(key).map_err(|err| err.to_string())?;
+                let info = serde_json::from_slice::<KeyInfo>(&decode_hex(key.trim())?)
+                    .map_err(|err| format!("invalid key file: {}", err))?;
+                let key = Key::new(info).map_err(|err| err.to_string())?;
+                if &key.address != address {
+                    return Err(format!("the key doesn't belong to {}", address));
+                }
+                let wallet = plum_wallet::Wallet::new_with_keys(vec![key], MemKeyStore::new());
+                let data = decode_hex(data)?;
+                let signature = if *raw {
+                    wallet.sign(address, &data)
+                } else {
+                    wallet.sign_data(address, &data)
+                }
+                .map_err(|err| err.to_string())?;
+                let mut bytes = vec![u8::from(signature.r#type())];
+                bytes.extend_from_slice(signature.as_bytes());
+                println!("{}", hex::encode(bytes));
+            }
+            Wallet::Verify {
+                raw,
+                address,
+                data,
+                signature,
+            } => {
+                let data = decode_hex(data)?;
+                let signature = decode_hex(signature)?;
+                if signature.is_empty() {
+                    return Err("empty signature".into());
+                }
+                let ty = SignatureType::try_from(signature[0]).map_err(|err| err.to_string())?;
+                let signature = Signature::new(ty, &signature[1..]);
+                let valid = if *raw {
+                    signature
+                        .verify(address, &data)
+                        .map_err(|err| err.to_string())?
+                } else {
+                    verify_data(&signature, address, &data).map_err(|err| err.to_string())?
                 };
-                wallet::Wallet::import(keytype, privkey.to_string())
+                if !valid {
+                    return Err("invalid signature".into());
+                }
+                println!("valid");
+            }
+            Wallet::History {
+                api,
+                limit,
+                address,
+            } => {
+                let client = api.client();
+                let history = block_on(client.wallet_history(address, *limit))?;
+                println!(
+                    "{:>10}  {:<4}  {:<44}  {:>26}  {:>6}  {:<24}  Cid",
+                    "Epoch", "Dir", "Counterparty", "Value", "Method", "ExitCode"
+                );
+                for entry in history {
+                    let message = &entry.message;
+                    let (direction, counterparty) = if message.from == message.to {
+                        ("self", &message.to)
+                    } else if &message.from == address {
+                        ("out", &message.to)
+                    } else {
+                        ("in", &message.from)
+                    };
+                    println!(
+                        "{:>10}  {:<4}  {:<44}  {:>26}  {:>6}  {:<24}  {}",
+                        entry.epoch,
+                        direction,
+                        counterparty.to_string(),
+                        message.value.to_string(),
+                        message.method,
+                        entry.receipt.exit_code.to_string(),
+                        entry.cid
+                    );
+                }
             }
-            Wallet::Balance { address } => unimplemented!(),
-            _ => unimplemented!(),
         }
+        Ok(())
     }
 }
-*/
 
 #[derive(StructOpt, Debug, Clone)]
 pub enum Command {
@@ -249,18 +1714,36 @@ pub enum Command {
     /// Interact with filecoin blockchain
     #[structopt(name = "chain")]
     Chain(Chain),
+    /// Generate or check the config file of the node
+    #[structopt(name = "config")]
+    Config(Config),
     /// Make deals, store data, retrieve data
     #[structopt(name = "client")]
     Client(Client),
+    /// Run a public gateway relaying the safe subset of the API of a full node
+    #[structopt(name = "gateway")]
+    Gateway(Gateway),
+    /// Manage logging of the daemon
+    #[structopt(name = "log")]
+    Log(Log),
     /// Manage miner actor
     #[structopt(name = "miner")]
     Miner(Miner),
+    /// Inspect the messages
+    #[structopt(name = "msg")]
+    Message(Message),
+    /// Interact with a multisig wallet
+    #[structopt(name = "msig")]
+    Msig(Msig),
     /// Manage message pool
     #[structopt(name = "mpool")]
     MessagePool(MessagePool),
     /// Manage P2P network
-    #[structopt(name = "network")]
+    #[structopt(name = "network", alias = "net")]
     Network(Network),
+    /// Inspect the local repo
+    #[structopt(name = "repo")]
+    Repo(Repo),
     /// Inspect or interact with the chain syncer
     #[structopt(name = "sync")]
     Sync(Sync),
@@ -270,11 +1753,9 @@ pub enum Command {
     /// Interact with and query filecoin chain state
     #[structopt(name = "state")]
     State(State),
-    /*
     /// Manage wallet
     #[structopt(name = "wallet")]
     Wallet(Wallet),
-    */
     /// Send funds between accounts
     #[structopt(name = "transfer")]
     Transfer {
@@ -0,0 +1,397 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use serde::Deserialize;
+use structopt::StructOpt;
+
+use plum_address::Network;
+
+/// The default max number of the files opened by the datastore.
+pub const DEFAULT_MAX_OPEN_FILES: i32 = 512;
+/// The default memory budget of the datastore in MiB.
+pub const DEFAULT_MEMORY_BUDGET: u64 = 512;
+/// The default memory available to the sealing tasks, 16 GiB.
+pub const DEFAULT_SEALING_MEMORY: u64 = 16 << 30;
+
+/// The errors of the node config.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// IO error of the config file.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// The config file doesn't match the schema, the error has the key and the position.
+    #[error("invalid config: {0}")]
+    Parse(#[from] toml::de::Error),
+    /// The value of the field is out of range.
+    #[error("invalid `{field}`: {reason}")]
+    Invalid {
+        /// The dotted path of the field, e.g. `datastore.path`.
+        field: &'static str,
+        /// Why the value is invalid.
+        reason: String,
+    },
+}
+
+/// The network the node joins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkName {
+    /// The main network, whose addresses are prefixed with `f`.
+    Mainnet,
+    /// The test network, whose addresses are prefixed with `t`.
+    Testnet,
+}
+
+impl NetworkName {
+    /// Return the network of the addresses.
+    pub fn address_network(self) -> Network {
+        match self {
+            NetworkName::Mainnet => Network::Main,
+            NetworkName::Testnet => Network::Test,
+        }
+    }
+}
+
+impl fmt::Display for NetworkName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkName::Mainnet => f.write_str("mainnet"),
+            NetworkName::Testnet => f.write_str("testnet"),
+        }
+    }
+}
+
+impl FromStr for NetworkName {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "mainnet" => Ok(NetworkName::Mainnet),
+            "testnet" => Ok(NetworkName::Testnet),
+            other => Err(format!(
+                "unknown network `{}`, expected `mainnet` or `testnet`",
+                other
+            )),
+        }
+    }
+}
+
+/// The datastore section of the config file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DatastoreConfig {
+    /// The path of the datastore, a relative path is relative to the config file.
+    pub path: PathBuf,
+    /// The max number of the files opened by the datastore, `-1` means unlimited.
+    #[serde(default = "default_max_open_files")]
+    pub max_open_files: i32,
+    /// The memory budget of the datastore in MiB.
+    #[serde(default = "default_memory_budget")]
+    pub memory_budget: u64,
+}
+
+fn default_max_open_files() -> i32 {
+    DEFAULT_MAX_OPEN_FILES
+}
+
+fn default_memory_budget() -> u64 {
+    DEFAULT_MEMORY_BUDGET
+}
+
+/// The resources section of the config file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ResourcesConfig {
+    /// The memory in bytes available to the sealing tasks.
+    pub sealing_memory: u64,
+    /// The number of the GPUs available to the sealing tasks.
+    pub gpus: usize,
+}
+
+impl Default for ResourcesConfig {
+    fn default() -> Self {
+        Self {
+            sealing_memory: DEFAULT_SEALING_MEMORY,
+            gpus: 0,
+        }
+    }
+}
+
+/// The config file of the node.
+///
+/// The file is generated by `plum config init`, see `default_config` for the content.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct NodeConfig {
+    /// The network the node joins.
+    pub network: NetworkName,
+    /// The datastore of the chain.
+    pub datastore: DatastoreConfig,
+    /// The resource limits of the node.
+    #[serde(default)]
+    pub resources: ResourcesConfig,
+}
+
+impl NodeConfig {
+    /// Parse and validate the config.
+    pub fn parse(content: &str) -> Result<Self, ConfigError> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load and validate the config from the TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Check the values that the schema can't express.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |field, reason: &str| {
+            Err(ConfigError::Invalid {
+                field,
+                reason: reason.into(),
+            })
+        };
+        if self.datastore.path.as_os_str().is_empty() {
+            return invalid("datastore.path", "the path must not be empty");
+        }
+        if self.datastore.max_open_files == 0 || self.datastore.max_open_files < -1 {
+            return invalid(
+                "datastore.max-open-files",
+                "must be positive, or -1 for unlimited",
+            );
+        }
+        if self.datastore.memory_budget == 0 {
+            return invalid("datastore.memory-budget", "must be positive");
+        }
+        if self.resources.sealing_memory == 0 {
+            return invalid("resources.sealing-memory", "must be positive");
+        }
+        Ok(())
+    }
+
+    /// Return the path of the datastore, a relative path is resolved against the directory
+    /// of the config file at `config_path`.
+    pub fn datastore_path<P: AsRef<Path>>(&self, config_path: P) -> PathBuf {
+        match config_path.as_ref().parent() {
+            Some(dir) if self.datastore.path.is_relative() => dir.join(&self.datastore.path),
+            _ => self.datastore.path.clone(),
+        }
+    }
+}
+
+/// Return the content of the commented default config file.
+pub fn default_config(network: NetworkName, datastore: &Path) -> String {
+    // Quote the path as a TOML string, escaping the backslashes of Windows paths.
+    let datastore = toml::Value::String(datastore.display().to_string());
+    format!(
+        r#"# The config of the plum node, check it with `plum config validate <path>`.
+
+# The network the node joins, "mainnet" or "testnet".
+network = "{network}"
+
+[datastore]
+# The path of the chain datastore, a relative path is relative to this file.
+path = {datastore}
+# The max number of the files opened by the datastore, -1 means unlimited.
+max-open-files = {max_open_files}
+# The memory budget of the datastore in MiB, shared by the block cache and the write buffers.
+memory-budget = {memory_budget}
+
+[resources]
+# The memory in bytes available to the sealing tasks.
+sealing-memory = {sealing_memory}
+# The number of the GPUs available to the sealing tasks.
+gpus = 0
+"#,
+        network = network,
+        datastore = datastore,
+        max_open_files = DEFAULT_MAX_OPEN_FILES,
+        memory_budget = DEFAULT_MEMORY_BUDGET,
+        sealing_memory = DEFAULT_SEALING_MEMORY,
+    )
+}
+
+#[derive(StructOpt, Debug, Clone)]
+pub enum Config {
+    /// Generate the commented default config file, the options not given are prompted for
+    /// when running in a terminal
+    #[structopt(name = "init")]
+    Init {
+        /// The network the node joins, `mainnet` or `testnet`
+        #[structopt(long = "network")]
+        network: Option<NetworkName>,
+        /// The path of the chain datastore
+        #[structopt(long = "datastore", parse(from_os_str))]
+        datastore: Option<PathBuf>,
+        /// Overwrite the existing config file
+        #[structopt(long = "force")]
+        force: bool,
+        /// The path of the config file
+        #[structopt(name = "path", default_value = "config.toml", parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Check the config file against the schema
+    #[structopt(name = "validate")]
+    Validate {
+        /// The path of the config file
+        #[structopt(name = "path", default_value = "config.toml", parse(from_os_str))]
+        path: PathBuf,
+    },
+}
+
+impl Config {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to execute config command: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        match self {
+            Config::Init {
+                network,
+                datastore,
+                force,
+                path,
+            } => {
+                if path.exists() && !force {
+                    return Err(format!(
+                        "{} already exists, use --force to overwrite it",
+                        path.display()
+                    ));
+                }
+                let interactive = atty::is(atty::Stream::Stdin);
+                let network = match network {
+                    Some(network) => *network,
+                    None if interactive => {
+                        prompt("Network (mainnet/testnet)", "testnet")?.parse()?
+                    }
+                    None => NetworkName::Testnet,
+                };
+                let datastore = match datastore {
+                    Some(datastore) => datastore.clone(),
+                    None if interactive => prompt("Datastore path", "datastore")?.into(),
+                    None => PathBuf::from("datastore"),
+                };
+                let content = default_config(network, &datastore);
+                // Never write a config that the node can't load.
+                NodeConfig::parse(&content).map_err(|err| err.to_string())?;
+                fs::write(path, content).map_err(|err| err.to_string())?;
+                println!("Wrote the config to {}", path.display());
+            }
+            Config::Validate { path } => {
+                let config =
+                    NodeConfig::load(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+                println!(
+                    "{} is valid, network: {}, datastore: {}",
+                    path.display(),
+                    config.network,
+                    config.datastore_path(path).display()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+// Ask for a value on the terminal, the empty answer takes the default.
+fn prompt(question: &str, default: &str) -> Result<String, String> {
+    print!("{} [{}]: ", question, default);
+    io::stdout().flush().map_err(|err| err.to_string())?;
+    let mut answer = String::new();
+    io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|err| err.to_string())?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let content = default_config(NetworkName::Mainnet, Path::new("/var/lib/plum"));
+        let config = NodeConfig::parse(&content).unwrap();
+        assert_eq!(config.network, NetworkName::Mainnet);
+        assert_eq!(config.datastore.path, PathBuf::from("/var/lib/plum"));
+        assert_eq!(config.datastore.max_open_files, DEFAULT_MAX_OPEN_FILES);
+        assert_eq!(config.resources, ResourcesConfig::default());
+        assert_eq!(
+            config.datastore_path("/etc/plum/config.toml"),
+            PathBuf::from("/var/lib/plum")
+        );
+
+        let content = default_config(NetworkName::Testnet, Path::new("data\\\"store"));
+        let config = NodeConfig::parse(&content).unwrap();
+        assert_eq!(config.datastore.path, PathBuf::from("data\\\"store"));
+        assert_eq!(
+            config.datastore_path("/etc/plum/config.toml"),
+            PathBuf::from("/etc/plum/data\\\"store")
+        );
+    }
+
+    #[test]
+    fn test_validate_config() {
+        let parse = |content: &str| NodeConfig::parse(content).map_err(|err| err.to_string());
+        let minimal = "network = \"testnet\"\n[datastore]\npath = \"datastore\"\n";
+        assert!(parse(minimal).is_ok());
+
+        // Schema errors have the key and the expected values.
+        let err = parse("network = \"devnet\"\n[datastore]\npath = \"a\"\n").unwrap_err();
+        assert!(
+            err.contains("devnet") && err.contains("`network`"),
+            "{}",
+            err
+        );
+        let err = parse(&format!("{}max-peers = 10\n", minimal)).unwrap_err();
+        assert!(err.contains("max-peers"), "{}", err);
+        let err = parse("network = \"testnet\"\n").unwrap_err();
+        assert!(err.contains("datastore"), "{}", err);
+
+        // Values out of range.
+        let err = parse(&format!("{}max-open-files = 0\n", minimal)).unwrap_err();
+        assert!(err.contains("datastore.max-open-files"), "{}", err);
+        assert!(parse(&format!("{}max-open-files = -1\n", minimal)).is_ok());
+        let err = parse("network = \"testnet\"\n[datastore]\npath = \"\"\n").unwrap_err();
+        assert!(err.contains("datastore.path"), "{}", err);
+        let err = parse(&format!("{}[resources]\nsealing-memory = 0\n", minimal)).unwrap_err();
+        assert!(err.contains("resources.sealing-memory"), "{}", err);
+    }
+
+    #[test]
+    fn test_init_and_validate_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let init = |force| Config::Init {
+            network: Some(NetworkName::Mainnet),
+            datastore: Some(PathBuf::from("chain")),
+            force,
+            path: path.clone(),
+        };
+        init(false).run().unwrap();
+        let config = NodeConfig::load(&path).unwrap();
+        assert_eq!(config.network, NetworkName::Mainnet);
+        assert_eq!(config.datastore_path(&path), dir.path().join("chain"));
+        Config::Validate { path: path.clone() }.run().unwrap();
+
+        // The existing config is only overwritten with `--force`.
+        let err = init(false).run().unwrap_err();
+        assert!(err.contains("--force"), "{}", err);
+        init(true).run().unwrap();
+
+        fs::write(&path, "network = \"mainnet\"\n").unwrap();
+        let err = Config::Validate { path: path.clone() }.run().unwrap_err();
+        assert!(err.contains("datastore"), "{}", err);
+    }
+}
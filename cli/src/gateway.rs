@@ -0,0 +1,177 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::body::HttpBody;
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use structopt::StructOpt;
+
+use plum_api_client::{ConnectionGuard, Gateway as ApiGateway, GatewayConfig, HttpTransport};
+use plum_types::ChainEpoch;
+
+/// The max size of the request body accepted by the gateway.
+const MAX_BODY_SIZE: usize = 1 << 20;
+
+#[derive(StructOpt, Debug, Clone)]
+pub struct Gateway {
+    /// The address the gateway listens on
+    #[structopt(long = "listen", default_value = "0.0.0.0:2346")]
+    listen: SocketAddr,
+    /// The URL of the API of the upstream full node
+    #[structopt(long = "api", default_value = "http://127.0.0.1:1234/rpc/v0")]
+    api: String,
+    /// The token of the upstream API, only the `write` permission is required
+    #[structopt(long = "token")]
+    token: Option<String>,
+    /// The max number of epochs the state queries can look back from the head
+    #[structopt(long = "max-lookback", default_value = "2880")]
+    max_lookback: ChainEpoch,
+    /// The number of request tokens refilled per second for each IP
+    #[structopt(long = "rate-limit", default_value = "20")]
+    rate_limit: u32,
+    /// The max number of request tokens of each IP
+    #[structopt(long = "rate-burst", default_value = "100")]
+    rate_burst: u32,
+    /// The max number of the connections
+    #[structopt(long = "max-conns", default_value = "1024")]
+    max_connections: usize,
+    /// The max number of the connections of each IP
+    #[structopt(long = "max-conns-per-ip", default_value = "16")]
+    max_connections_per_ip: usize,
+}
+
+impl Gateway {
+    pub fn execute(&self) {
+        if let Err(err) = self.run() {
+            eprintln!("Failed to run gateway: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    fn config(&self) -> GatewayConfig {
+        GatewayConfig {
+            max_lookback: self.max_lookback,
+            rate_limit: self.rate_limit,
+            rate_burst: self.rate_burst,
+            max_connections: self.max_connections,
+            max_connections_per_ip: self.max_connections_per_ip,
+        }
+    }
+
+    fn run(&self) -> Result<(), String> {
+        if self.max_lookback < 0 {
+            return Err(format!("invalid max lookback: {}", self.max_lookback));
+        }
+        let upstream = match &self.token {
+            Some(token) => HttpTransport::new_with_bearer_auth(self.api.as_str(), token.as_str()),
+            None => HttpTransport::new(self.api.as_str()),
+        };
+        let gateway = Arc::new(ApiGateway::new(upstream, self.config()));
+        info!(
+            "[gateway] listening on {}, relaying to {}, config: {:?}",
+            self.listen,
+            self.api,
+            gateway.config()
+        );
+
+        let make_service = make_service_fn(move |stream: &AddrStream| {
+            let gateway = gateway.clone();
+            let remote = stream.remote_addr();
+            let conn = match gateway.connect(remote.ip()) {
+                Ok(conn) => Some(Arc::new(conn)),
+                Err(err) => {
+                    debug!("[gateway] reject connection from {}: {}", remote, err);
+                    None
+                }
+            };
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    serve(gateway.clone(), conn.clone(), req)
+                }))
+            }
+        });
+        let mut runtime = tokio::runtime::Runtime::new().map_err(|err| err.to_string())?;
+        runtime
+            .block_on(Server::bind(&self.listen).serve(make_service))
+            .map_err(|err| err.to_string())
+    }
+}
+
+async fn serve(
+    gateway: Arc<ApiGateway<HttpTransport>>,
+    conn: Option<Arc<ConnectionGuard>>,
+    req: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let conn = match conn {
+        Some(conn) => conn,
+        None => return Ok(status(StatusCode::SERVICE_UNAVAILABLE)),
+    };
+    if req.method() != Method::POST {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    if req.body().size_hint().lower() > MAX_BODY_SIZE as u64 {
+        return Ok(status(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+    let body = match hyper::body::to_bytes(req.into_body()).await {
+        Ok(body) if body.len() <= MAX_BODY_SIZE => body,
+        Ok(_) => return Ok(status(StatusCode::PAYLOAD_TOO_LARGE)),
+        Err(_) => return Ok(status(StatusCode::BAD_REQUEST)),
+    };
+    let response = gateway.handle_body(&conn, &body).await;
+    Ok(Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(response))
+        .expect("the response is valid; qed"))
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(Body::empty())
+        .expect("the response is valid; qed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        // The default options must agree with the defaults of the gateway.
+        let gateway = Gateway::from_iter(&["gateway"]);
+        assert_eq!(gateway.config(), GatewayConfig::default());
+    }
+
+    #[test]
+    fn test_serve_rejects_before_relaying() {
+        let upstream = HttpTransport::new("http://127.0.0.1:1234/rpc/v0");
+        let gateway = Arc::new(ApiGateway::new(upstream, GatewayConfig::default()));
+        let conn = Arc::new(gateway.connect("127.0.0.1".parse().unwrap()).unwrap());
+        let mut runtime = tokio::runtime::Runtime::new().unwrap();
+        let mut serve_status = |conn, method, body: Vec<u8>| {
+            let req = Request::builder()
+                .method(method)
+                .body(Body::from(body))
+                .unwrap();
+            let response = runtime.block_on(serve(gateway.clone(), conn, req));
+            response.unwrap().status()
+        };
+
+        assert_eq!(
+            serve_status(None, Method::POST, vec![]),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            serve_status(Some(conn.clone()), Method::GET, vec![]),
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            serve_status(Some(conn), Method::POST, vec![0; MAX_BODY_SIZE + 1]),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+}
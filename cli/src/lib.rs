@@ -4,16 +4,17 @@
 extern crate log;
 
 pub mod cmd;
+pub mod config;
+pub mod gateway;
+pub mod logger;
 
-use std::io::Write;
+use std::path::PathBuf;
 
-use ansi_term::Colour;
-use lazy_static::lazy_static;
-use regex::Regex;
 use structopt::clap::AppSettings;
 use structopt::StructOpt;
 
 use self::cmd::Command;
+use self::logger::{init_logger, LogConfig};
 
 #[derive(StructOpt, Debug, Clone)]
 #[structopt(name = "plum")]
@@ -23,6 +24,10 @@ pub struct Plum {
     #[structopt(short = "l", long = "log", value_name = "LOG_PATTERN")]
     pub log: Option<String>,
 
+    /// Load the logging config from the TOML file.
+    #[structopt(long = "log-config", value_name = "PATH", parse(from_os_str))]
+    pub log_config: Option<PathBuf>,
+
     #[structopt(subcommand)]
     pub cmd: Command,
 }
@@ -30,8 +35,19 @@ pub struct Plum {
 impl Plum {
     pub fn execute(&self) {
         match &self.cmd {
-            /*Command::Network(network) => network.execute(),*/
-            /*Command::Wallet(wallet) => wallet.execute(),*/
+            Command::Auth(auth) => auth.execute(),
+            Command::Chain(chain) => chain.execute(),
+            Command::Client(client) => client.execute(),
+            Command::Config(config) => config.execute(),
+            Command::Gateway(gateway) => gateway.execute(),
+            Command::Log(log) => log.execute(),
+            Command::Message(message) => message.execute(),
+            Command::Miner(miner) => miner.execute(),
+            Command::Msig(msig) => msig.execute(),
+            Command::Repo(repo) => repo.execute(),
+            Command::State(state) => state.execute(),
+            Command::Network(network) => network.execute(),
+            Command::Wallet(wallet) => wallet.execute(),
             _ => unimplemented!(),
         }
     }
@@ -60,81 +76,14 @@ pub fn run_lp2p(peer_ip: Option<Multiaddr>) {
 }
 */
 
-fn kill_color(s: &str) -> String {
-    lazy_static! {
-        static ref RE: Regex = Regex::new("\x1b\\[[^m]+m").expect("Error initializing color regex");
-    }
-    RE.replace_all(s, "").to_string()
-}
-
-fn init_logger(custom_log: Option<String>) {
-    let mut builder = env_logger::Builder::new();
-    // Disable info logging by default for some modules:
-    builder.filter(Some("ws"), log::LevelFilter::Off);
-    builder.filter(Some("hyper"), log::LevelFilter::Warn);
-    // Enable info for others.
-    builder.filter(None, log::LevelFilter::Info);
-
-    if let Ok(lvl) = std::env::var("RUST_LOG") {
-        builder.parse_filters(&lvl);
-    }
-
-    let pattern = custom_log.as_ref().map(|v| v.as_ref()).unwrap_or("");
-    builder.parse_filters(pattern);
-    let isatty = atty::is(atty::Stream::Stderr);
-    let enable_color = isatty;
-
-    builder.format(move |buf, record| {
-        let now = time::now();
-        let timestamp =
-            time::strftime("%Y-%m-%d %H:%M:%S", &now).expect("Error formatting log timestamp");
-
-        let mut output = if custom_log.is_none() {
-            format!(
-                "{} {}",
-                Colour::RGB(96, 96, 96).paint(timestamp),
-                record.args()
-            )
-        } else if log::max_level() <= log::LevelFilter::Info {
-            format!(
-                "{} {} {} {}",
-                Colour::RGB(96, 96, 96).paint(timestamp),
-                Colour::Yellow.paint(format!("{}", record.level())),
-                record.target(),
-                record.args()
-            )
-        } else {
-            let name = ::std::thread::current()
-                .name()
-                .map_or_else(Default::default, |x| {
-                    format!("{}", Colour::Blue.bold().paint(x))
-                });
-            let millis = (now.tm_nsec as f32 / 1_000_000.0).round() as usize;
-            let timestamp = format!("{}.{:03}", timestamp, millis);
-            format!(
-                "{} {} {} {}  {}",
-                Colour::RGB(96, 96, 96).paint(timestamp),
-                name,
-                Colour::Yellow.paint(format!("{}", record.level())),
-                record.target(),
-                record.args()
-            )
-        };
-
-        if !isatty && record.level() <= log::Level::Info && atty::is(atty::Stream::Stdout) {
-            // duplicate INFO/WARN output to console
-            println!("{}", output);
-        }
-
-        if !enable_color {
-            output = kill_color(output.as_ref());
-        }
-
-        writeln!(buf, "{}", output)
-    });
-
-    if builder.try_init().is_err() {
-        info!("Not registering Plum logger, as there is already a global logger registered!");
+fn setup_logger(custom_log: Option<&str>, log_config: Option<&PathBuf>) {
+    let config = match log_config {
+        Some(path) => LogConfig::load(path),
+        None => Ok(LogConfig::default()),
+    };
+    if let Err(err) = config.and_then(|config| init_logger(&config, custom_log)) {
+        eprintln!("Failed to initialize the logger: {}", err);
+        std::process::exit(1);
     }
 }
 
@@ -142,11 +91,11 @@ pub fn run() {
     let args = std::env::args().collect::<Vec<String>>();
 
     if args.len() == 1 {
-        init_logger(None);
+        setup_logger(None, None);
         //run_lp2p(None);
     } else {
         let plum = Plum::from_iter(args.iter());
-        init_logger(plum.log.clone());
+        setup_logger(plum.log.as_deref(), plum.log_config.as_ref());
         plum.execute();
     }
 }
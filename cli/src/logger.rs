@@ -0,0 +1,447 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use ansi_term::Colour;
+use lazy_static::lazy_static;
+use log::{LevelFilter, Log, Metadata, Record};
+use parking_lot::{Mutex, RwLock};
+use regex::Regex;
+use serde::Deserialize;
+
+/// The default max size of a log file before it's rotated, 64 MiB.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+/// The default number of the rotated log files kept besides the current one.
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// The subsystem name that refers to the default level of all the targets.
+pub const ALL_SUBSYSTEMS: &str = "*";
+
+/// The errors of the logger.
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    /// The level can't be parsed.
+    #[error("invalid log level: {0}")]
+    InvalidLevel(String),
+    /// IO error of the log file or the config file.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// The config file can't be parsed.
+    #[error("invalid log config: {0}")]
+    Config(#[from] toml::de::Error),
+}
+
+/// The logging section of the config file.
+///
+/// ```toml
+/// level = "info"
+/// file = "/var/log/plum/plum.log"
+/// max-file-size = 67108864
+/// max-files = 5
+///
+/// [targets]
+/// plum_p2p = "debug"
+/// hyper = "warn"
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LogConfig {
+    /// The default level of all the targets.
+    pub level: String,
+    /// The level overrides of the targets, a target covers all its sub-modules.
+    pub targets: HashMap<String, String>,
+    /// Write the logs to the file besides stderr if it's specified.
+    pub file: Option<PathBuf>,
+    /// The max size of the log file before it's rotated.
+    pub max_file_size: u64,
+    /// The max number of the rotated log files kept besides the current one.
+    pub max_files: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".into(),
+            targets: HashMap::new(),
+            file: None,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+        }
+    }
+}
+
+impl LogConfig {
+    /// Load the config from the TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, LogError> {
+        let content = fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, LogError> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| LogError::InvalidLevel(level.into()))
+}
+
+/// The levels of the targets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Levels {
+    default: LevelFilter,
+    targets: BTreeMap<String, LevelFilter>,
+}
+
+impl Levels {
+    // Return the level of the target, the longest matched target is used.
+    fn level(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .rev()
+            .find(|(prefix, _)| {
+                target == prefix.as_str()
+                    || (target.starts_with(prefix.as_str())
+                        && target[prefix.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.default)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.targets
+            .values()
+            .copied()
+            .fold(self.default, LevelFilter::max)
+    }
+
+    fn set(&mut self, subsystem: &str, level: LevelFilter) {
+        if subsystem == ALL_SUBSYSTEMS || subsystem.is_empty() {
+            self.default = level;
+        } else {
+            self.targets.insert(subsystem.into(), level);
+        }
+    }
+
+    // Parse the filters in the same syntax as `RUST_LOG`, e.g. `info,plum_p2p=debug`.
+    fn parse_filters(&mut self, filters: &str) -> Result<(), LogError> {
+        for directive in filters.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.find('=') {
+                Some(index) => {
+                    let level = parse_level(&directive[index + 1..])?;
+                    self.set(directive[..index].trim(), level);
+                }
+                None => match parse_level(directive) {
+                    Ok(level) => self.default = level,
+                    // A bare target enables all the levels of it.
+                    Err(_) => self.set(directive, LevelFilter::Trace),
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A log file that is rotated when its size exceeds the limit.
+///
+/// The rotated files are named with the suffix `.1`, `.2`, ..., the larger suffix the older.
+pub struct RotatingFile {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl RotatingFile {
+    /// Open the log file in append mode, the file is created if it doesn't exist.
+    pub fn open<P: Into<PathBuf>>(path: P, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            max_size,
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut path = OsString::from(self.path.as_os_str());
+        path.push(format!(".{}", index));
+        path.into()
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            self.file.set_len(0)?;
+        } else {
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn kill_color(s: &str) -> String {
+    lazy_static! {
+        static ref RE: Regex = Regex::new("\x1b\\[[^m]+m").expect("Error initializing color regex");
+    }
+    RE.replace_all(s, "").to_string()
+}
+
+/// The logger of the plum node, the levels of the targets can be changed at runtime.
+pub struct Logger {
+    levels: RwLock<Levels>,
+    file: Option<Mutex<RotatingFile>>,
+    detailed: bool,
+    isatty: bool,
+}
+
+impl Logger {
+    /// Create the logger with the config and the custom filters (e.g. from `--log` and `RUST_LOG`),
+    /// the custom filters override the levels of the config.
+    pub fn new(config: &LogConfig, custom_filters: &[&str]) -> Result<Self, LogError> {
+        let mut levels = Levels {
+            default: parse_level(&config.level)?,
+            targets: BTreeMap::new(),
+        };
+        // Disable info logging by default for some modules.
+        levels.set("ws", LevelFilter::Off);
+        levels.set("hyper", LevelFilter::Warn);
+        for (target, level) in &config.targets {
+            levels.set(target, parse_level(level)?);
+        }
+        for filters in custom_filters {
+            levels.parse_filters(filters)?;
+        }
+
+        let file = match &config.file {
+            Some(path) => Some(Mutex::new(RotatingFile::open(
+                path,
+                config.max_file_size,
+                config.max_files,
+            )?)),
+            None => None,
+        };
+        Ok(Self {
+            levels: RwLock::new(levels),
+            file,
+            detailed: false,
+            isatty: atty::is(atty::Stream::Stderr),
+        })
+    }
+
+    /// Change the level of the subsystem, which is a log target such as `plum_p2p`,
+    /// or `*` for the default level of all the targets.
+    pub fn set_level(&self, subsystem: &str, level: &str) -> Result<(), LogError> {
+        let level = parse_level(level)?;
+        let mut levels = self.levels.write();
+        levels.set(subsystem, level);
+        log::set_max_level(levels.max_level());
+        Ok(())
+    }
+
+    /// Return the subsystems whose levels are overridden.
+    pub fn subsystems(&self) -> Vec<String> {
+        self.levels.read().targets.keys().cloned().collect()
+    }
+
+    fn format(&self, record: &Record) -> String {
+        let now = time::now();
+        let timestamp =
+            time::strftime("%Y-%m-%d %H:%M:%S", &now).expect("Error formatting log timestamp");
+
+        if !self.detailed {
+            format!(
+                "{} {}",
+                Colour::RGB(96, 96, 96).paint(timestamp),
+                record.args()
+            )
+        } else if log::max_level() <= LevelFilter::Info {
+            format!(
+                "{} {} {} {}",
+                Colour::RGB(96, 96, 96).paint(timestamp),
+                Colour::Yellow.paint(format!("{}", record.level())),
+                record.target(),
+                record.args()
+            )
+        } else {
+            let name = ::std::thread::current()
+                .name()
+                .map_or_else(Default::default, |x| {
+                    format!("{}", Colour::Blue.bold().paint(x))
+                });
+            let millis = (now.tm_nsec as f32 / 1_000_000.0).round() as usize;
+            let timestamp = format!("{}.{:03}", timestamp, millis);
+            format!(
+                "{} {} {} {}  {}",
+                Colour::RGB(96, 96, 96).paint(timestamp),
+                name,
+                Colour::Yellow.paint(format!("{}", record.level())),
+                record.target(),
+                record.args()
+            )
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.levels.read().level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let output = self.format(record);
+
+        if !self.isatty && record.level() <= log::Level::Info && atty::is(atty::Stream::Stdout) {
+            // duplicate INFO/WARN output to console
+            println!("{}", output);
+        }
+        if let Some(file) = &self.file {
+            // Write the line at once so that it's never split by the rotation.
+            let line = format!("{}\n", kill_color(&output));
+            // Nowhere to report the failure of writing the log file.
+            let _ = file.lock().write_all(line.as_bytes());
+        }
+        if self.isatty {
+            eprintln!("{}", output);
+        } else {
+            eprintln!("{}", kill_color(&output));
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = &self.file {
+            let _ = file.lock().flush();
+        }
+    }
+}
+
+/// Register the logger as the global logger, return the handle of it,
+/// which is used to change the levels at runtime, e.g. for the `LogSetLevel` RPC method.
+pub fn init_logger(
+    config: &LogConfig,
+    custom_log: Option<&str>,
+) -> Result<&'static Logger, LogError> {
+    let rust_log = std::env::var("RUST_LOG").ok();
+    let custom_filters = rust_log
+        .as_deref()
+        .into_iter()
+        .chain(custom_log)
+        .collect::<Vec<_>>();
+    let mut logger = Logger::new(config, &custom_filters)?;
+    // The detailed format is used only if the custom logging filter is set.
+    logger.detailed = custom_log.is_some();
+    let logger: &'static Logger = Box::leak(Box::new(logger));
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(logger.levels.read().max_level());
+    } else {
+        info!("Not registering Plum logger, as there is already a global logger registered!");
+    }
+    Ok(logger)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        let config = LogConfig {
+            level: "warn".into(),
+            targets: vec![("plum_p2p".to_string(), "debug".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let logger = Logger::new(&config, &["plum_chain=trace,plum_p2p::seen=error"]).unwrap();
+        let level = |target: &str| logger.levels.read().level(target);
+        assert_eq!(level("plum_vm"), LevelFilter::Warn);
+        assert_eq!(level("plum_p2p"), LevelFilter::Debug);
+        assert_eq!(level("plum_p2p::behaviour"), LevelFilter::Debug);
+        assert_eq!(level("plum_p2p::seen"), LevelFilter::Error);
+        assert_eq!(level("plum_p2px"), LevelFilter::Warn);
+        assert_eq!(level("plum_chain"), LevelFilter::Trace);
+        assert_eq!(level("ws::handler"), LevelFilter::Off);
+
+        logger.set_level("plum_vm", "debug").unwrap();
+        logger.set_level(ALL_SUBSYSTEMS, "error").unwrap();
+        assert_eq!(level("plum_vm"), LevelFilter::Debug);
+        assert_eq!(level("plum_block"), LevelFilter::Error);
+        assert!(logger.set_level("plum_vm", "loud").is_err());
+        assert!(logger.subsystems().contains(&"plum_vm".to_string()));
+    }
+
+    #[test]
+    fn test_rotating_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plum.log");
+        let mut file = RotatingFile::open(&path, 10, 2).unwrap();
+        for line in &["aaaaaaaa\n", "bbbbbbbb\n", "cccccccc\n", "dddddddd\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("plum.log"), "dddddddd\n");
+        assert_eq!(read("plum.log.1"), "cccccccc\n");
+        assert_eq!(read("plum.log.2"), "bbbbbbbb\n");
+        assert!(!dir.path().join("plum.log.3").exists());
+    }
+
+    #[test]
+    fn test_log_config() {
+        let config: LogConfig = toml::from_str(
+            r#"
+            level = "debug"
+            file = "/tmp/plum.log"
+
+            [targets]
+            hyper = "info"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.level, "debug");
+        assert_eq!(config.file, Some(PathBuf::from("/tmp/plum.log")));
+        assert_eq!(config.max_files, DEFAULT_MAX_FILES);
+        assert_eq!(config.targets["hyper"], "info");
+    }
+}
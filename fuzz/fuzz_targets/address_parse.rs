@@ -0,0 +1,24 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+#![no_main]
+
+use std::str::FromStr;
+
+use libfuzzer_sys::fuzz_target;
+use plum_address::Address;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(addr) = Address::new_from_bytes(data) {
+        if let Some(id) = addr.as_id() {
+            // The ID payload is canonical, so it's the only encoding of the ID.
+            assert_eq!(Address::new_id_addr(id).ok().as_ref(), Some(&addr));
+        }
+        let _ = addr.to_string();
+    }
+    if let Ok(s) = std::str::from_utf8(data) {
+        if let Ok(addr) = Address::from_str(s) {
+            // The parsed address must be parsed again from its own string.
+            assert_eq!(Address::from_str(&addr.to_string()).ok(), Some(addr));
+        }
+    }
+});
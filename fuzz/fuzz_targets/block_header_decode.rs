@@ -0,0 +1,11 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use plum_block::{BlockHeader, BlockMsg};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = minicbor::decode::<BlockHeader>(data);
+    let _ = minicbor::decode::<BlockMsg>(data);
+});
@@ -0,0 +1,18 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use ipld::hamt::Node;
+use ipld::HamtVersion;
+
+fuzz_target!(|data: &[u8]| {
+    for version in [HamtVersion::V2, HamtVersion::V3].iter() {
+        if let Ok(node) = Node::<u64>::from_bytes(*version, data) {
+            // The decoded node must be decoded again from its own encoding.
+            let bytes = node.to_bytes(*version);
+            assert_eq!(Node::<u64>::from_bytes(*version, &bytes).ok(), Some(node));
+        }
+    }
+});
@@ -0,0 +1,11 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use plum_message::{SignedMessage, UnsignedMessage};
+
+fuzz_target!(|data: &[u8]| {
+    let _ = minicbor::decode::<UnsignedMessage>(data);
+    let _ = minicbor::decode::<SignedMessage>(data);
+});
@@ -0,0 +1,10 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = rle::decode::<u64, _>(data);
+    let _ = rle::decode::<u8, _>(data);
+});
@@ -4,24 +4,28 @@
 
 #![deny(missing_docs)]
 
-use std::io;
-
 use cid::Cid;
 
 use ipfs_block::Block;
-use ipfs_datastore::{DataStoreBatch, DataStoreRead, DataStoreWrite, Key, ToBatchDataStore};
+use ipfs_datastore::{
+    DataStoreBatch, DataStoreRead, DataStoreWrite, Key, Result, ToBatchDataStore,
+};
 
 /// BlockStore wraps a DataStore block-centered methods and provides a layer
 /// of abstraction which allows to add different caching strategies.
+///
+/// All the methods take `&self`, so a block store can be shared between threads
+/// (e.g. wrapped in an `Arc`) and written concurrently without external locking,
+/// the underlying datastore is responsible for the synchronization.
 pub trait BlockStore: ToBatchDataStore {
     /// Return whether the `cid` is mapped to a `block`.
-    fn has(&self, cid: &Cid) -> io::Result<bool> {
+    fn has(&self, cid: &Cid) -> Result<bool> {
         let key = multihash_to_datastore_key(cid.hash().as_bytes());
         <Self as DataStoreRead>::has(self, &key)
     }
 
     /// Retrieve the `block` named by `cid`.
-    fn get(&self, cid: &Cid) -> io::Result<Option<Block>> {
+    fn get(&self, cid: &Cid) -> Result<Option<Block>> {
         let key = multihash_to_datastore_key(cid.hash().as_bytes());
         match <Self as DataStoreRead>::get(self, &key)? {
             Some(data) => Ok(Some(unsafe { Block::new_unchecked(data, cid.clone()) })),
@@ -29,8 +33,25 @@ pub trait BlockStore: ToBatchDataStore {
         }
     }
 
+    /// Retrieve the blocks named by `cids` at once, using the batched reads of the underlying
+    /// datastore whenever possible. The blocks are returned in the order of the `cids`.
+    fn get_many(&self, cids: &[Cid]) -> Result<Vec<Option<Block>>> {
+        let keys = cids
+            .iter()
+            .map(|cid| multihash_to_datastore_key(cid.hash().as_bytes()))
+            .collect::<Vec<_>>();
+        let values = <Self as DataStoreRead>::get_many(self, &keys)?;
+        Ok(values
+            .into_iter()
+            .zip(cids)
+            .map(|(value, cid)| {
+                value.map(|data| unsafe { Block::new_unchecked(data, cid.clone()) })
+            })
+            .collect())
+    }
+
     /// Put a given block to the underlying datastore
-    fn put(&mut self, block: Block) -> io::Result<()> {
+    fn put(&self, block: Block) -> Result<()> {
         let key = multihash_to_datastore_key(block.cid().hash().as_bytes());
         if <Self as DataStoreRead>::has(self, &key)? {
             Ok(()) // already store
@@ -41,7 +62,7 @@ pub trait BlockStore: ToBatchDataStore {
 
     /// Puts a slice of blocks at the same time using batching capabilities of
     /// the underlying datastore whenever possible.
-    fn put_many(&mut self, blocks: &[Block]) -> io::Result<()> {
+    fn put_many(&self, blocks: &[Block]) -> Result<()> {
         let mut batch = self.batch()?;
         for block in blocks {
             let key = multihash_to_datastore_key(block.cid().hash().as_bytes());
@@ -56,7 +77,7 @@ pub trait BlockStore: ToBatchDataStore {
 
     /// Delete the block for given `cid`.
     /// If the `cid` is not in the block store, this method returns no error.
-    fn delete(&mut self, cid: &Cid) -> io::Result<()> {
+    fn delete(&self, cid: &Cid) -> Result<()> {
         let key = multihash_to_datastore_key(cid.hash().as_bytes());
         <Self as DataStoreWrite>::delete(self, &key)
     }
@@ -5,59 +5,95 @@
 #![deny(missing_docs)]
 
 use std::borrow::Borrow;
-use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
-use ipfs_datastore::{DataStore, DataStoreRead, DataStoreWrite};
+use ipfs_datastore::{DataStore, DataStoreError, DataStoreRead, DataStoreWrite, Result};
 use ipfs_datastore::{Key, MapDataStore, SyncDataStore};
 
 /// A thread-safe datastore living in memory, which is generally intended for tests.
+///
+/// All the operations fail with `DataStoreError::Closed` after the datastore is closed.
 #[derive(Clone)]
 pub struct MemoryDataStore {
     datastore: SyncDataStore<MapDataStore>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Default for MemoryDataStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryDataStore {
+    /// Create an empty memory datastore.
+    pub fn new() -> Self {
+        Self {
+            datastore: SyncDataStore::new(MapDataStore::new()),
+            closed: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn ensure_open(&self) -> Result<()> {
+        if self.closed.load(Ordering::Acquire) {
+            Err(DataStoreError::Closed)
+        } else {
+            Ok(())
+        }
+    }
 }
 
 impl DataStore for MemoryDataStore {
-    fn sync<K>(&mut self, prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
+        self.ensure_open()?;
         self.datastore.sync(prefix)
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
         self.datastore.close()
     }
 }
 
 impl DataStoreRead for MemoryDataStore {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
+        self.ensure_open()?;
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
+        self.ensure_open()?;
         self.datastore.has(key)
     }
 }
 
 impl DataStoreWrite for MemoryDataStore {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
+        self.ensure_open()?;
         self.datastore.put(key, value)
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
+        self.ensure_open()?;
         self.datastore.delete(key)
     }
 }
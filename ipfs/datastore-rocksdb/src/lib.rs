@@ -7,7 +7,7 @@
 mod rocks;
 
 pub use self::rocks::{
-    DBKey, DBOp, DBTransaction, DBValue, Database, DatabaseConfig, IoStats, IoStatsKind,
+    DBKey, DBOp, DBTransaction, DBValue, Database, DatabaseConfig, IoStats, IoStatsKind, OpenMode,
     RocksDBStatsValue, DB_DEFAULT_MEMORY_BUDGET_MB, DEFAULT_COLUMN_NAME,
 };
 
@@ -16,8 +16,11 @@ use std::collections::HashMap;
 use std::io;
 use std::sync::Arc;
 
+use parking_lot::Mutex;
+
 use ipfs_datastore::{
-    DataStore, DataStoreBatch, DataStoreRead, DataStoreTxn, DataStoreWrite, Key, ToBatch, ToTxn,
+    DataStore, DataStoreBatch, DataStoreError, DataStoreRead, DataStoreTxn, DataStoreWrite, Key,
+    Persistent, Result, ToBatch, ToTxn,
 };
 
 /// RocksDBDataStore is a datastore with RocksDB as backend.
@@ -38,6 +41,12 @@ impl RocksDBDataStore {
         self.db.clone()
     }
 
+    /// Make the secondary instance catch up with the primary instance,
+    /// it's a no-op if the datastore is not opened as a secondary instance.
+    pub fn try_catch_up_with_primary(&self) -> Result<()> {
+        self.db.try_catch_up_with_primary().map_err(convert_err)
+    }
+
     /// Add a new column family into rocksdb.
     pub fn add_column(&self, col: String) -> io::Result<()> {
         Ok(self.db.add_column(col)?)
@@ -71,43 +80,64 @@ impl RocksDBDataStore {
 }
 
 impl DataStore for RocksDBDataStore {
-    fn sync<K>(&mut self, _prefix: &K) -> io::Result<()>
+    // The writes of all the columns are synced, whatever the prefix is.
+    fn sync<K>(&self, _prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        Ok(())
+        self.db.sync().map_err(convert_err)
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
         self.db.close();
         Ok(())
     }
 }
 
 impl DataStoreRead for RocksDBDataStore {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        Ok(self.db.get(&col, key.as_bytes())?)
+        self.db.get(&col, key.as_bytes()).map_err(convert_err)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        Ok(self.db.get(&col, key.as_bytes())?.is_some())
+        Ok(self
+            .db
+            .get(&col, key.as_bytes())
+            .map_err(convert_err)?
+            .is_some())
+    }
+
+    fn get_many<K>(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>>
+    where
+        K: Borrow<Key>,
+    {
+        let cols = keys
+            .iter()
+            .map(|key| key_column(key.borrow()))
+            .collect::<Vec<_>>();
+        let db_keys = keys
+            .iter()
+            .zip(&cols)
+            .map(|(key, col)| (col.as_str(), key.borrow().as_bytes()))
+            .collect::<Vec<_>>();
+        self.db.get_many(&db_keys).map_err(convert_err)
     }
 }
 
 impl DataStoreWrite for RocksDBDataStore {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
@@ -118,11 +148,11 @@ impl DataStoreWrite for RocksDBDataStore {
 
         let mut txn = self.db.transaction();
         txn.put(&col, key.as_bytes(), value);
-        self.db.write(&txn)?;
+        self.db.write(&txn).map_err(convert_err)?;
         Ok(())
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
@@ -131,17 +161,23 @@ impl DataStoreWrite for RocksDBDataStore {
 
         let mut txn = self.db.transaction();
         txn.delete(&col, key.as_bytes());
-        self.db.write(&txn)?;
+        self.db.write(&txn).map_err(convert_err)?;
         Ok(())
     }
 }
 
+impl Persistent for RocksDBDataStore {
+    fn disk_usage(&self) -> Result<u64> {
+        self.db.disk_usage().map_err(convert_err)
+    }
+}
+
 impl ToBatch for RocksDBDataStore {
     type Batch = RocksDBBatchDataStore;
 
-    fn batch(&self) -> io::Result<Self::Batch> {
+    fn batch(&self) -> Result<Self::Batch> {
         let db = self.db.clone();
-        let txn = db.transaction();
+        let txn = Mutex::new(db.transaction());
         Ok(RocksDBBatchDataStore { db, txn })
     }
 }
@@ -149,9 +185,9 @@ impl ToBatch for RocksDBDataStore {
 impl ToTxn for RocksDBDataStore {
     type Txn = RocksDBTxnDataStore;
 
-    fn txn(&self, _read_only: bool) -> io::Result<Self::Txn> {
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
         let db = self.db.clone();
-        let txn = db.transaction();
+        let txn = Mutex::new(db.transaction());
         Ok(RocksDBTxnDataStore { db, txn })
     }
 }
@@ -159,17 +195,25 @@ impl ToTxn for RocksDBDataStore {
 // ============================================================================
 
 /// RocksDBBatchDataStore is a batch datastore with RocksDB as backend.
-#[derive(Clone)]
 pub struct RocksDBBatchDataStore {
     db: Arc<Database>,
-    txn: DBTransaction,
+    txn: Mutex<DBTransaction>,
+}
+
+impl Clone for RocksDBBatchDataStore {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            txn: Mutex::new(self.txn.lock().clone()),
+        }
+    }
 }
 
 impl RocksDBBatchDataStore {
     /// Create a new rocksdb batch data store.
     pub fn new(config: &DatabaseConfig, path: &str) -> io::Result<Self> {
         let db = Database::open(config, path)?;
-        let txn = db.transaction();
+        let txn = Mutex::new(db.transaction());
         Ok(Self {
             db: Arc::new(db),
             txn,
@@ -183,29 +227,33 @@ impl RocksDBBatchDataStore {
 }
 
 impl DataStoreRead for RocksDBBatchDataStore {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        Ok(self.db.get(&col, key.as_bytes())?)
+        self.db.get(&col, key.as_bytes()).map_err(convert_err)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        Ok(self.db.get(&col, key.as_bytes())?.is_some())
+        Ok(self
+            .db
+            .get(&col, key.as_bytes())
+            .map_err(convert_err)?
+            .is_some())
     }
 }
 
 impl DataStoreWrite for RocksDBBatchDataStore {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
@@ -214,37 +262,44 @@ impl DataStoreWrite for RocksDBBatchDataStore {
         let value = value.into();
         let col = key_column(&key);
 
-        self.txn.put(&col, key.as_bytes(), value);
+        self.txn.lock().put(&col, key.as_bytes(), value);
         Ok(())
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        self.txn.delete(&col, key.borrow().as_bytes());
+        self.txn.lock().delete(&col, key.as_bytes());
         Ok(())
     }
 }
 
 impl DataStoreBatch for RocksDBBatchDataStore {
-    fn commit(&mut self) -> io::Result<()> {
-        self.db.write(&self.txn)?;
-        self.txn.clear();
+    fn commit(&mut self) -> Result<()> {
+        let txn = self.txn.get_mut();
+        self.db.write(txn).map_err(convert_err)?;
+        txn.clear();
         Ok(())
     }
 }
 
+impl Persistent for RocksDBBatchDataStore {
+    fn disk_usage(&self) -> Result<u64> {
+        self.db.disk_usage().map_err(convert_err)
+    }
+}
+
 impl ToTxn for RocksDBBatchDataStore {
     type Txn = RocksDBTxnDataStore;
 
-    fn txn(&self, _read_only: bool) -> io::Result<Self::Txn> {
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
         Ok(RocksDBTxnDataStore {
             db: self.db.clone(),
-            txn: self.txn.clone(),
+            txn: Mutex::new(self.txn.lock().clone()),
         })
     }
 }
@@ -252,17 +307,25 @@ impl ToTxn for RocksDBBatchDataStore {
 // ============================================================================
 
 /// RocksDBBatchDataStore is a txn datastore with RocksDB as backend.
-#[derive(Clone)]
 pub struct RocksDBTxnDataStore {
     db: Arc<Database>,
-    txn: DBTransaction,
+    txn: Mutex<DBTransaction>,
+}
+
+impl Clone for RocksDBTxnDataStore {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            txn: Mutex::new(self.txn.lock().clone()),
+        }
+    }
 }
 
 impl RocksDBTxnDataStore {
     /// Create a new rocksdb batch data store.
     pub fn new(config: &DatabaseConfig, path: &str) -> io::Result<Self> {
         let db = Database::open(config, path)?;
-        let txn = db.transaction();
+        let txn = Mutex::new(db.transaction());
         Ok(Self {
             db: Arc::new(db),
             txn,
@@ -276,29 +339,33 @@ impl RocksDBTxnDataStore {
 }
 
 impl DataStoreRead for RocksDBTxnDataStore {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        Ok(self.db.get(&col, key.as_bytes())?)
+        self.db.get(&col, key.as_bytes()).map_err(convert_err)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        Ok(self.db.get(&col, key.as_bytes())?.is_some())
+        Ok(self
+            .db
+            .get(&col, key.as_bytes())
+            .map_err(convert_err)?
+            .is_some())
     }
 }
 
 impl DataStoreWrite for RocksDBTxnDataStore {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
@@ -307,37 +374,55 @@ impl DataStoreWrite for RocksDBTxnDataStore {
         let value = value.into();
 
         let col = key_column(&key);
-        self.txn.put(&col, key.as_bytes(), value);
+        self.txn.lock().put(&col, key.as_bytes(), value);
         Ok(())
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
         let key = key.borrow();
         let col = key_column(key);
 
-        self.txn.delete(&col, key.as_bytes());
+        self.txn.lock().delete(&col, key.as_bytes());
         Ok(())
     }
 }
 
 impl DataStoreBatch for RocksDBTxnDataStore {
-    fn commit(&mut self) -> io::Result<()> {
-        self.db.write(&self.txn)?;
-        self.txn.clear();
+    fn commit(&mut self) -> Result<()> {
+        let txn = self.txn.get_mut();
+        self.db.write(txn).map_err(convert_err)?;
+        txn.clear();
         Ok(())
     }
 }
 
+impl Persistent for RocksDBTxnDataStore {
+    fn disk_usage(&self) -> Result<u64> {
+        self.db.disk_usage().map_err(convert_err)
+    }
+}
+
 impl DataStoreTxn for RocksDBTxnDataStore {
-    fn discard(&mut self) -> io::Result<()> {
-        self.txn.ops.clear();
+    fn discard(&mut self) -> Result<()> {
+        self.txn.get_mut().ops.clear();
         Ok(())
     }
 }
 
+// Convert the error of the database into the datastore error, the corruption,
+// the closed database and the read-only database are distinguished by the error kind.
+fn convert_err(err: io::Error) -> DataStoreError {
+    match err.kind() {
+        io::ErrorKind::InvalidData => DataStoreError::Corruption(err.to_string()),
+        io::ErrorKind::NotConnected => DataStoreError::Closed,
+        io::ErrorKind::PermissionDenied => DataStoreError::ReadOnly,
+        _ => DataStoreError::Backend(err),
+    }
+}
+
 // TODO: specified col name according to the key.
 // Get column family name according to the key.
 fn key_column(_key: &Key) -> String {
@@ -36,17 +36,40 @@ pub struct DatabaseConfig {
     /// It can have a negative performance impact up to 10% according to
     /// https://github.com/facebook/rocksdb/wiki/Statistics.
     pub enable_statistics: bool,
+    /// The mode to open the database.
+    /// `OpenMode::ReadWrite` by default.
+    pub mode: OpenMode,
+}
+
+/// The mode to open the database.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Open the database as the primary instance, which can be read and written.
+    /// The database is created if it doesn't exist.
+    ReadWrite,
+    /// Open the database as a read-only instance.
+    ///
+    /// A read-only instance doesn't observe the writes of the primary instance after it's opened,
+    /// it's suitable for analysing a snapshot of the database of a live node.
+    /// All the writes fail with `io::ErrorKind::PermissionDenied`.
+    ReadOnly,
     /// Open the database as a secondary instance.
     /// Specify a path for the secondary instance of the database.
     /// Secondary instances are read-only and kept updated by tailing the rocksdb MANIFEST.
-    /// It is up to the user to call `catch_up_with_primary()` manually to update the secondary db.
-    /// Disabled by default.
+    /// It is up to the user to call `try_catch_up_with_primary()` manually to update the secondary db.
     ///
     /// `max_open_files` is overridden to always equal `-1`.
     /// May have a negative performance impact on the secondary instance
     /// if the secondary instance reads and applies state changes before the primary instance compacts them.
     /// More info: https://github.com/facebook/rocksdb/wiki/Secondary-instance
-    pub secondary: Option<String>,
+    Secondary(String),
+}
+
+impl OpenMode {
+    /// Return true if the database can't be written in the mode.
+    pub fn is_read_only(&self) -> bool {
+        !matches!(self, OpenMode::ReadWrite)
+    }
 }
 
 impl Default for DatabaseConfig {
@@ -61,7 +84,7 @@ impl Default for DatabaseConfig {
             columns: default_columns,
             keep_log_file_num: 1,
             enable_statistics: false,
-            secondary: None,
+            mode: OpenMode::ReadWrite,
         }
     }
 }
@@ -17,13 +17,14 @@ use std::path::Path;
 
 use log::warn;
 use parking_lot::RwLock;
+use rayon::prelude::*;
 use rocksdb::{
     BlockBasedOptions, ColumnFamily, ColumnFamilyDescriptor, Error, Options, ReadOptions,
     WriteBatch, WriteOptions, DB,
 };
 
 pub use self::compact::CompactionProfile;
-pub use self::config::{DatabaseConfig, DEFAULT_COLUMN_NAME};
+pub use self::config::{DatabaseConfig, OpenMode, DEFAULT_COLUMN_NAME};
 use self::stats::{parse_rocksdb_stats, RunningDBStats};
 pub use self::stats::{IoStats, IoStatsKind, RocksDBStatsValue};
 pub use self::transaction::{DBKey, DBOp, DBTransaction, DBValue};
@@ -51,6 +52,21 @@ impl DBAndColumns {
     }
 }
 
+// The total size of all the files in the directory, recursively.
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
 #[inline]
 fn check_for_corruption<T, P: AsRef<Path>>(path: P, res: Result<T, Error>) -> io::Result<T> {
     if let Err(ref err) = res {
@@ -60,6 +76,7 @@ fn check_for_corruption<T, P: AsRef<Path>>(path: P, res: Result<T, Error>) -> io
                 err
             );
             let _ = fs::File::create(path.as_ref().join(Database::CORRUPTION_FILE_NAME));
+            return Err(io::Error::new(io::ErrorKind::InvalidData, err.to_string()));
         }
     }
 
@@ -79,6 +96,14 @@ where
     io::Error::new(io::ErrorKind::Other, e)
 }
 
+fn closed_io_err() -> io::Error {
+    io::Error::new(io::ErrorKind::NotConnected, "Database is closed")
+}
+
+fn read_only_io_err() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "Database is read-only")
+}
+
 /// Generate the options for RocksDB, based on the given `DatabaseConfig`.
 fn generate_options(config: &DatabaseConfig) -> Options {
     let mut opts = Options::default();
@@ -88,8 +113,8 @@ fn generate_options(config: &DatabaseConfig) -> Options {
         opts.enable_statistics();
     }
     opts.set_use_fsync(false);
-    opts.create_if_missing(true);
-    if config.secondary.is_some() {
+    opts.create_if_missing(config.mode == OpenMode::ReadWrite);
+    if let OpenMode::Secondary(_) = config.mode {
         opts.set_max_open_files(-1)
     } else {
         opts.set_max_open_files(config.max_open_files);
@@ -166,18 +191,21 @@ impl Database {
         let write_opts = generate_write_options();
         let read_opts = generate_read_options();
 
-        // attempt database repair if it has been previously marked as corrupted
+        // attempt database repair if it has been previously marked as corrupted,
+        // a read-only or secondary instance never repairs the database of the primary instance.
         let db_corrupted = Path::new(path).join(Database::CORRUPTION_FILE_NAME);
-        if db_corrupted.exists() {
+        if db_corrupted.exists() && !config.mode.is_read_only() {
             warn!("DB has been previously marked as corrupted, attempting repair");
             DB::repair(&opts, path).map_err(other_io_err)?;
             fs::remove_file(db_corrupted)?;
         }
 
-        let db = if let Some(secondary_path) = &config.secondary {
-            Self::open_secondary(&opts, path, secondary_path, config)?
-        } else {
-            Self::open_primary(&opts, path, config, &block_opts)?
+        let db = match &config.mode {
+            OpenMode::ReadWrite => Self::open_primary(&opts, path, config, &block_opts)?,
+            OpenMode::ReadOnly => Self::open_read_only(&opts, path, config)?,
+            OpenMode::Secondary(secondary_path) => {
+                Self::open_secondary(&opts, path, secondary_path, config)?
+            }
         };
         let column_names = config.columns.clone();
 
@@ -248,15 +276,28 @@ impl Database {
         secondary_path: &str,
         config: &DatabaseConfig,
     ) -> io::Result<DB> {
-        match DB::open_cf_as_secondary(opts, path, secondary_path, &config.columns) {
-            Ok(db) => Ok(db),
-            Err(ref err) if is_corrupted(err) => {
-                warn!("DB corrupted: {}, attempting repair", err);
-                DB::repair(opts, path).map_err(other_io_err)?;
-                DB::open_cf_as_secondary(opts, path, secondary_path, &config.columns)
-                    .map_err(other_io_err)
-            }
-            Err(err) => Err(other_io_err(err)),
+        DB::open_cf_as_secondary(opts, path, secondary_path, &config.columns).map_err(other_io_err)
+    }
+
+    /// Internal api to open a database in read-only mode.
+    fn open_read_only(opts: &Options, path: &str, config: &DatabaseConfig) -> io::Result<DB> {
+        DB::open_cf_for_read_only(opts, path, &config.columns, false).map_err(other_io_err)
+    }
+
+    /// Return the mode that the database is opened with.
+    pub fn mode(&self) -> &OpenMode {
+        &self.config.mode
+    }
+
+    /// Make the secondary instance catch up with the primary instance,
+    /// it's a no-op for the other modes.
+    pub fn try_catch_up_with_primary(&self) -> io::Result<()> {
+        match *self.db.read() {
+            Some(ref cfs) => match self.config.mode {
+                OpenMode::Secondary(_) => cfs.db.try_catch_up_with_primary().map_err(other_io_err),
+                _ => Ok(()),
+            },
+            None => Err(closed_io_err()),
         }
     }
 
@@ -267,6 +308,9 @@ impl Database {
 
     /// Commit transaction to database.
     pub fn write(&self, txn: &DBTransaction) -> io::Result<()> {
+        if self.config.mode.is_read_only() {
+            return Err(read_only_io_err());
+        }
         match *self.db.read() {
             Some(ref cfs) => {
                 let mut batch = WriteBatch::default();
@@ -294,7 +338,7 @@ impl Database {
                 let res = cfs.db.write_opt(batch, &self.write_opts);
                 check_for_corruption(&self.path, res)
             }
-            None => Err(other_io_err("Database is closed")),
+            None => Err(closed_io_err()),
         }
     }
 
@@ -320,7 +364,62 @@ impl Database {
                 }
                 value
             }
-            None => Ok(None),
+            None => Err(closed_io_err()),
+        }
+    }
+
+    /// Get the values of the keys of the columns, the values are returned in the order of the
+    /// keys.
+    ///
+    /// rocksdb 0.14 has no binding of `MultiGet`, so the keys are read in parallel on the rayon
+    /// thread pool, all under a single lock of the database.
+    pub fn get_many(&self, keys: &[(&str, &[u8])]) -> io::Result<Vec<Option<DBValue>>> {
+        match *self.db.read() {
+            Some(ref cfs) => {
+                if keys.iter().any(|(col, _)| !cfs.column_names.contains(*col)) {
+                    return Err(other_io_err("non-existing column"));
+                }
+                self.stats.tally_reads(keys.len() as u64);
+                let read_opts = &self.read_opts;
+                let values = keys
+                    .par_iter()
+                    .map(|(col, key)| {
+                        cfs.db
+                            .get_pinned_cf_opt(cfs.cf(col), key, read_opts)
+                            .map(|value| value.map(|v| v.to_vec()))
+                            .map_err(other_io_err)
+                    })
+                    .collect::<io::Result<Vec<_>>>()?;
+                let stats_total_bytes = keys
+                    .iter()
+                    .zip(&values)
+                    .map(|((_, key), value)| key.len() + value.as_ref().map_or(0, |v| v.len()))
+                    .sum::<usize>();
+                self.stats.tally_bytes_read(stats_total_bytes as u64);
+                Ok(values)
+            }
+            None => Err(closed_io_err()),
+        }
+    }
+
+    /// Persist all the writes committed before to the disk.
+    ///
+    /// The WAL is not synced on every write, so the memtables of all the columns are flushed
+    /// into the SST files, which are synced before returning, and the writes survive a crash of
+    /// the machine afterwards. It's a no-op for the read-only and secondary instances.
+    pub fn sync(&self) -> io::Result<()> {
+        if self.config.mode.is_read_only() {
+            return Ok(());
+        }
+        match *self.db.read() {
+            Some(ref cfs) => {
+                for col in &cfs.column_names {
+                    let res = cfs.db.flush_cf(cfs.cf(col));
+                    check_for_corruption(&self.path, res)?;
+                }
+                Ok(())
+            }
+            None => Err(closed_io_err()),
         }
     }
 
@@ -360,8 +459,40 @@ impl Database {
         }
     }
 
+    /// The space used by the database, in bytes.
+    ///
+    /// It's the larger one of the size of all the files in the database directory (including
+    /// the WAL and the manifest files) and the sum of the estimated size of the SST files and the
+    /// memtables of all the columns, since the memtables are not flushed to the disk yet.
+    pub fn disk_usage(&self) -> io::Result<u64> {
+        const TOTAL_SST_FILES_SIZE: &str = "rocksdb.total-sst-files-size";
+        const SIZE_ALL_MEM_TABLES: &str = "rocksdb.size-all-mem-tables";
+        let estimate = match *self.db.read() {
+            Some(ref cfs) => {
+                let mut estimate = 0u64;
+                for col in &cfs.column_names {
+                    let cf = cfs.cf(col);
+                    for property in &[TOTAL_SST_FILES_SIZE, SIZE_ALL_MEM_TABLES] {
+                        let size = cfs
+                            .db
+                            .property_int_value_cf(cf, property)
+                            .map_err(other_io_err)?;
+                        estimate += size.unwrap_or_default();
+                    }
+                }
+                estimate
+            }
+            None => return Err(closed_io_err()),
+        };
+        let on_disk = dir_size(Path::new(&self.path))?;
+        Ok(cmp::max(estimate, on_disk))
+    }
+
     /// Add a new column family to the DB.
     pub fn add_column(&self, col: String) -> io::Result<()> {
+        if self.config.mode.is_read_only() {
+            return Err(read_only_io_err());
+        }
         match *self.db.write() {
             Some(DBAndColumns {
                 ref mut db,
@@ -378,6 +509,9 @@ impl Database {
 
     /// Remove the column family in the database. The deletion is definitive.
     pub fn remove_column(&self, col: &str) -> io::Result<()> {
+        if self.config.mode.is_read_only() {
+            return Err(read_only_io_err());
+        }
         match *self.db.write() {
             Some(DBAndColumns {
                 ref mut db,
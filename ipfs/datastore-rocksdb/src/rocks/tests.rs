@@ -4,7 +4,7 @@ use std::collections::HashSet;
 use std::fs::File;
 use std::io::{self, Read};
 
-use super::{Database, DatabaseConfig, DB_DEFAULT_COLUMN_MEMORY_BUDGET_MB, MB};
+use super::{Database, DatabaseConfig, OpenMode, DB_DEFAULT_COLUMN_MEMORY_BUDGET_MB, MB};
 
 fn open_temp_db(columns: Vec<String>) -> io::Result<Database> {
     let tempdir = tempfile::Builder::new().prefix("").tempdir()?;
@@ -38,6 +38,108 @@ fn put_and_get() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn put_and_get_many() -> io::Result<()> {
+    let db = open_temp_db(vec!["0".into()])?;
+
+    let mut transaction = db.transaction();
+    transaction.put("0", b"key1", b"horse".to_vec());
+    transaction.put("0", b"key3", b"cat".to_vec());
+    db.write(&transaction)?;
+
+    let keys: &[(&str, &[u8])] = &[("0", b"key3"), ("0", b"key2"), ("0", b"key1")];
+    assert_eq!(
+        db.get_many(keys)?,
+        vec![Some(b"cat".to_vec()), None, Some(b"horse".to_vec())]
+    );
+    let keys: &[(&str, &[u8])] = &[("0", b"key1"), ("1", b"key1")];
+    assert!(db.get_many(keys).is_err());
+    Ok(())
+}
+
+#[test]
+fn get_many_of_mixed_columns() -> io::Result<()> {
+    let db = open_temp_db(vec!["0".into(), "1".into()])?;
+
+    let mut transaction = db.transaction();
+    transaction.put("0", b"key1", b"horse".to_vec());
+    transaction.put("1", b"key1", b"dog".to_vec());
+    transaction.put("1", b"key2", b"cat".to_vec());
+    db.write(&transaction)?;
+
+    let keys: &[(&str, &[u8])] = &[
+        ("1", b"key2"),
+        ("0", b"key2"),
+        ("0", b"key1"),
+        ("1", b"key3"),
+        ("1", b"key1"),
+    ];
+    assert_eq!(
+        db.get_many(keys)?,
+        vec![
+            Some(b"cat".to_vec()),
+            None,
+            Some(b"horse".to_vec()),
+            None,
+            Some(b"dog".to_vec()),
+        ]
+    );
+    assert!(db.get_many(&[]).unwrap().is_empty());
+    Ok(())
+}
+
+#[test]
+fn disk_usage() -> io::Result<()> {
+    let tempdir = tempfile::Builder::new().prefix("").tempdir()?;
+    let path = tempdir
+        .path()
+        .to_str()
+        .expect("tempdir path is valid unicode");
+    let db = Database::open(&DatabaseConfig::with_columns(vec!["0".into()]), path)?;
+    let empty = db.disk_usage()?;
+    assert!(empty > 0);
+
+    let mut transaction = db.transaction();
+    for i in 0..1000u32 {
+        transaction.put("0", &i.to_be_bytes(), vec![0u8; 1024]);
+    }
+    db.write(&transaction)?;
+    assert!(db.disk_usage()? >= empty + 1000 * 1024);
+
+    db.close();
+    assert!(db.disk_usage().is_err());
+    Ok(())
+}
+
+#[test]
+fn sync() -> io::Result<()> {
+    const NUM_ENTRIES_ACTIVE_MEM_TABLE: &str = "rocksdb.num-entries-active-mem-table";
+    let db = open_temp_db(vec!["0".into(), "1".into()])?;
+    let mut transaction = db.transaction();
+    transaction.put("0", b"key1", b"horse".to_vec());
+    transaction.put("1", b"key2", b"cat".to_vec());
+    db.write(&transaction)?;
+
+    db.sync()?;
+    // All the writes are flushed out of the memtables.
+    {
+        let guard = db.db.read();
+        let cfs = guard.as_ref().unwrap();
+        for col in &["0", "1"] {
+            let entries = cfs
+                .db
+                .property_int_value_cf(cfs.cf(col), NUM_ENTRIES_ACTIVE_MEM_TABLE)
+                .unwrap();
+            assert_eq!(entries, Some(0));
+        }
+    }
+    assert_eq!(db.get("0", b"key1")?.unwrap(), b"horse");
+
+    db.close();
+    assert!(db.sync().is_err());
+    Ok(())
+}
+
 #[test]
 fn delete_and_get() -> io::Result<()> {
     let db = open_temp_db(vec!["0".into()])?;
@@ -187,12 +289,15 @@ fn secondary_db_get() -> io::Result<()> {
     columns.insert("0".to_string());
     let config = DatabaseConfig {
         columns,
-        secondary: tempfile::Builder::new()
-            .prefix("")
-            .tempdir()?
-            .path()
-            .to_str()
-            .map(|s| s.to_string()),
+        mode: OpenMode::Secondary(
+            tempfile::Builder::new()
+                .prefix("")
+                .tempdir()?
+                .path()
+                .to_str()
+                .expect("tempdir path is valid unicode")
+                .to_string(),
+        ),
         ..Default::default()
     };
     let second_db = Database::open(
@@ -206,6 +311,38 @@ fn secondary_db_get() -> io::Result<()> {
     Ok(())
 }
 
+#[test]
+fn read_only_db() -> io::Result<()> {
+    let primary = tempfile::Builder::new().prefix("").tempdir()?;
+    let path = primary
+        .path()
+        .to_str()
+        .expect("tempdir path is valid unicode");
+    let config = DatabaseConfig::with_columns(vec!["0".into()]);
+    let db = Database::open(&config, path)?;
+    let mut transaction = db.transaction();
+    transaction.put("0", b"key1", b"horse".to_vec());
+    db.write(&transaction)?;
+
+    let config = DatabaseConfig {
+        mode: OpenMode::ReadOnly,
+        ..config
+    };
+    let read_only_db = Database::open(&config, path)?;
+    assert_eq!(&*read_only_db.get("0", b"key1")?.unwrap(), b"horse");
+
+    let mut transaction = read_only_db.transaction();
+    transaction.put("0", b"key2", b"cow".to_vec());
+    let err = read_only_db.write(&transaction).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+    assert!(read_only_db.add_column("1".into()).is_err());
+
+    // the database is not created in read-only mode.
+    let missing = tempfile::Builder::new().prefix("").tempdir()?;
+    assert!(Database::open(&config, missing.path().join("db").to_str().unwrap()).is_err());
+    Ok(())
+}
+
 #[test]
 #[should_panic]
 fn db_config_with_zero_columns() {
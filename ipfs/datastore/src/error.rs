@@ -0,0 +1,33 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use crate::key::Key;
+
+/// Alias for a `Result` with the default error type `DataStoreError`.
+pub type Result<T, E = DataStoreError> = std::result::Result<T, E>;
+
+/// The datastore error.
+#[derive(Debug, thiserror::Error)]
+pub enum DataStoreError {
+    /// The key is not found in the datastore.
+    #[error("key not found: {0}")]
+    NotFound(Key),
+    /// The data of the datastore is corrupted.
+    #[error("datastore corrupted: {0}")]
+    Corruption(String),
+    /// The error of the underlying storage.
+    #[error("{0}")]
+    Backend(#[from] std::io::Error),
+    /// The datastore is closed.
+    #[error("datastore closed")]
+    Closed,
+    /// The datastore is read-only.
+    #[error("datastore is read-only")]
+    ReadOnly,
+}
+
+impl DataStoreError {
+    /// Return true if the error is caused by a missing key.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, DataStoreError::NotFound(_))
+    }
+}
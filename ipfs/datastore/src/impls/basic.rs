@@ -2,8 +2,10 @@
 
 use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::io;
 
+use parking_lot::Mutex;
+
+use crate::error::Result;
 use crate::key::Key;
 use crate::store::ToTxn;
 use crate::store::{Check, CheckedDataStore};
@@ -20,31 +22,39 @@ enum Op {
 
 /// BasicBatchDataStore implements the batch interface for data stores
 /// who do not have any sort of underlying batch support.
-#[derive(Clone)]
 pub struct BasicBatchDataStore<DS: DataStore> {
-    ops: HashMap<Key, Op>,
+    ops: Mutex<HashMap<Key, Op>>,
     datastore: DS,
 }
 
+impl<DS: DataStore> Clone for BasicBatchDataStore<DS> {
+    fn clone(&self) -> Self {
+        Self {
+            ops: Mutex::new(self.ops.lock().clone()),
+            datastore: self.datastore.clone(),
+        }
+    }
+}
+
 impl<DS: DataStore> BasicBatchDataStore<DS> {
     /// Create a new basic batching datastore.
     pub fn new(datastore: DS) -> Self {
         Self {
-            ops: HashMap::new(),
+            ops: Mutex::new(HashMap::new()),
             datastore,
         }
     }
 }
 
 impl<DS: DataStore> DataStoreRead for BasicBatchDataStore<DS> {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
@@ -53,57 +63,58 @@ impl<DS: DataStore> DataStoreRead for BasicBatchDataStore<DS> {
 }
 
 impl<DS: DataStore> DataStoreWrite for BasicBatchDataStore<DS> {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
-        self.ops.insert(key.into(), Op::Put(value.into()));
+        self.ops.lock().insert(key.into(), Op::Put(value.into()));
         Ok(())
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        self.ops.insert(key.borrow().to_owned(), Op::Delete);
+        self.ops.lock().insert(key.borrow().to_owned(), Op::Delete);
         Ok(())
     }
 }
 
 impl<DS: DataStore> DataStoreBatch for BasicBatchDataStore<DS> {
-    fn commit(&mut self) -> io::Result<()> {
-        for (key, op) in &self.ops {
+    fn commit(&mut self) -> Result<()> {
+        let ops = self.ops.get_mut();
+        for (key, op) in ops.iter() {
             match op {
                 Op::Put(value) => self.datastore.put(key, value.to_owned())?,
-                Op::Delete => self.datastore.delete(&key)?,
+                Op::Delete => self.datastore.delete(key)?,
             }
         }
-        self.ops.clear();
+        ops.clear();
         Ok(())
     }
 }
 
 impl<DS: CheckedDataStore> Check for BasicBatchDataStore<DS> {
-    fn check(&self) -> io::Result<()> {
+    fn check(&self) -> Result<()> {
         self.datastore.check()
     }
 }
 
 impl<DS: GcDataStore> Gc for BasicBatchDataStore<DS> {
-    fn collect_garbage(&self) -> io::Result<()> {
+    fn collect_garbage(&self) -> Result<()> {
         self.datastore.collect_garbage()
     }
 }
 
 impl<DS: PersistentDataStore> Persistent for BasicBatchDataStore<DS> {
-    fn disk_usage(&self) -> io::Result<u64> {
+    fn disk_usage(&self) -> Result<u64> {
         self.datastore.disk_usage()
     }
 }
 
 impl<DS: ScrubbedDataStore> Scrub for BasicBatchDataStore<DS> {
-    fn scrub(&self) -> io::Result<()> {
+    fn scrub(&self) -> Result<()> {
         self.datastore.scrub()
     }
 }
@@ -111,10 +122,10 @@ impl<DS: ScrubbedDataStore> Scrub for BasicBatchDataStore<DS> {
 impl<DS: DataStore> ToTxn for BasicBatchDataStore<DS> {
     type Txn = BasicTxnDataStore<DS>;
 
-    fn txn(&self, _read_only: bool) -> io::Result<Self::Txn> {
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
         Ok(BasicTxnDataStore {
             datastore: self.datastore.clone(),
-            ops: self.ops.clone(),
+            ops: Mutex::new(self.ops.lock().clone()),
         })
     }
 }
@@ -123,31 +134,39 @@ impl<DS: DataStore> ToTxn for BasicBatchDataStore<DS> {
 
 /// BasicTxnDataStore implements the transaction interface for data stores
 /// who do not have any sort of underlying transaction support.
-#[derive(Clone)]
 pub struct BasicTxnDataStore<DS: DataStore> {
-    ops: HashMap<Key, Op>,
+    ops: Mutex<HashMap<Key, Op>>,
     datastore: DS,
 }
 
+impl<DS: DataStore> Clone for BasicTxnDataStore<DS> {
+    fn clone(&self) -> Self {
+        Self {
+            ops: Mutex::new(self.ops.lock().clone()),
+            datastore: self.datastore.clone(),
+        }
+    }
+}
+
 impl<DS: DataStore> BasicTxnDataStore<DS> {
     /// Create a new basic transaction datastore.
     pub fn new(datastore: DS) -> Self {
         Self {
-            ops: HashMap::new(),
+            ops: Mutex::new(HashMap::new()),
             datastore,
         }
     }
 }
 
 impl<DS: DataStore> DataStoreRead for BasicTxnDataStore<DS> {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
@@ -156,64 +175,65 @@ impl<DS: DataStore> DataStoreRead for BasicTxnDataStore<DS> {
 }
 
 impl<DS: DataStore> DataStoreWrite for BasicTxnDataStore<DS> {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
-        self.ops.insert(key.into(), Op::Put(value.into()));
+        self.ops.lock().insert(key.into(), Op::Put(value.into()));
         Ok(())
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        self.ops.insert(key.borrow().to_owned(), Op::Delete);
+        self.ops.lock().insert(key.borrow().to_owned(), Op::Delete);
         Ok(())
     }
 }
 
 impl<DS: DataStore> DataStoreBatch for BasicTxnDataStore<DS> {
-    fn commit(&mut self) -> io::Result<()> {
-        for (key, op) in &self.ops {
+    fn commit(&mut self) -> Result<()> {
+        let ops = self.ops.get_mut();
+        for (key, op) in ops.iter() {
             match op {
                 Op::Put(value) => self.datastore.put(key, value.to_owned())?,
-                Op::Delete => self.datastore.delete(&key)?,
+                Op::Delete => self.datastore.delete(key)?,
             }
         }
-        self.ops.clear();
+        ops.clear();
         Ok(())
     }
 }
 
 impl<DS: DataStore> DataStoreTxn for BasicTxnDataStore<DS> {
-    fn discard(&mut self) -> io::Result<()> {
-        self.ops.clear();
+    fn discard(&mut self) -> Result<()> {
+        self.ops.get_mut().clear();
         Ok(())
     }
 }
 
 impl<DS: CheckedDataStore> Check for BasicTxnDataStore<DS> {
-    fn check(&self) -> io::Result<()> {
+    fn check(&self) -> Result<()> {
         self.datastore.check()
     }
 }
 
 impl<DS: GcDataStore> Gc for BasicTxnDataStore<DS> {
-    fn collect_garbage(&self) -> io::Result<()> {
+    fn collect_garbage(&self) -> Result<()> {
         self.datastore.collect_garbage()
     }
 }
 
 impl<DS: PersistentDataStore> Persistent for BasicTxnDataStore<DS> {
-    fn disk_usage(&self) -> io::Result<u64> {
+    fn disk_usage(&self) -> Result<u64> {
         self.datastore.disk_usage()
     }
 }
 
 impl<DS: ScrubbedDataStore> Scrub for BasicTxnDataStore<DS> {
-    fn scrub(&self) -> io::Result<()> {
+    fn scrub(&self) -> Result<()> {
         self.datastore.scrub()
     }
 }
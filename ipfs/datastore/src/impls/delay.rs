@@ -1,8 +1,15 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::borrow::Borrow;
-use std::io;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
+use parking_lot::{Mutex, RwLock};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::error::Result;
 use crate::impls::{BasicBatchDataStore, BasicTxnDataStore};
 use crate::key::Key;
 use crate::store::{DataStore, DataStoreRead, DataStoreWrite};
@@ -15,6 +22,111 @@ pub trait Delay: Clone + Sync + Send + 'static {
     fn wait(&self);
 }
 
+impl Delay for Duration {
+    fn wait(&self) {
+        thread::sleep(*self)
+    }
+}
+
+/// The fixed delay, which can be changed at runtime and is shared by the clones.
+#[derive(Clone, Debug, Default)]
+pub struct FixedDelay {
+    duration: Arc<RwLock<Duration>>,
+}
+
+impl FixedDelay {
+    /// Create a fixed delay of the `duration`.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration: Arc::new(RwLock::new(duration)),
+        }
+    }
+
+    /// Return the duration of the delay.
+    pub fn get(&self) -> Duration {
+        *self.duration.read()
+    }
+
+    /// Change the duration of the delay.
+    pub fn set(&self, duration: Duration) {
+        *self.duration.write() = duration;
+    }
+}
+
+impl Delay for FixedDelay {
+    fn wait(&self) {
+        thread::sleep(self.get())
+    }
+}
+
+/// The distribution of the variable delay.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum DelayDistribution {
+    /// The duration is uniformly distributed in `[base, base + spread)`.
+    Uniform {
+        /// The min duration.
+        base: Duration,
+        /// The range of the duration.
+        spread: Duration,
+    },
+    /// The duration is normally distributed, the negative samples are clamped to zero.
+    Normal {
+        /// The mean of the duration.
+        mean: Duration,
+        /// The standard deviation of the duration.
+        std_dev: Duration,
+    },
+}
+
+/// The variable delay sampled from the distribution with a seeded RNG, so that the sequence of
+/// the delays is reproducible. The RNG is shared by the clones.
+#[derive(Clone, Debug)]
+pub struct VariableDelay {
+    distribution: DelayDistribution,
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl VariableDelay {
+    /// Create a variable delay of the `distribution`, the RNG is seeded with the `seed`.
+    pub fn new(distribution: DelayDistribution, seed: u64) -> Self {
+        Self {
+            distribution,
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+        }
+    }
+
+    /// Create a delay uniformly distributed in `[base, base + spread)`.
+    pub fn uniform(base: Duration, spread: Duration, seed: u64) -> Self {
+        Self::new(DelayDistribution::Uniform { base, spread }, seed)
+    }
+
+    /// Create a delay normally distributed with the `mean` and the `std_dev`.
+    pub fn normal(mean: Duration, std_dev: Duration, seed: u64) -> Self {
+        Self::new(DelayDistribution::Normal { mean, std_dev }, seed)
+    }
+
+    /// Sample the duration of the next delay.
+    pub fn sample(&self) -> Duration {
+        let mut rng = self.rng.lock();
+        match self.distribution {
+            DelayDistribution::Uniform { base, spread } => base + spread.mul_f64(rng.gen::<f64>()),
+            DelayDistribution::Normal { mean, std_dev } => {
+                // Box-Muller transform, `1 - u` avoids `ln(0)`.
+                let (u1, u2) = (1.0 - rng.gen::<f64>(), rng.gen::<f64>());
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                let secs = mean.as_secs_f64() + z * std_dev.as_secs_f64();
+                Duration::from_secs_f64(secs.max(0.0))
+            }
+        }
+    }
+}
+
+impl Delay for VariableDelay {
+    fn wait(&self) {
+        thread::sleep(self.sample())
+    }
+}
+
 /// DelayDataStore is an adapter that delays operations on the inner datastore.
 #[derive(Clone)]
 pub struct DelayDataStore<DL: Delay, DS: DataStore> {
@@ -30,7 +142,7 @@ impl<DL: Delay, DS: DataStore> DelayDataStore<DL, DS> {
 }
 
 impl<DL: Delay, DS: DataStore> DataStore for DelayDataStore<DL, DS> {
-    fn sync<K>(&mut self, prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
@@ -38,13 +150,13 @@ impl<DL: Delay, DS: DataStore> DataStore for DelayDataStore<DL, DS> {
         self.datastore.sync(prefix)
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
         self.datastore.close()
     }
 }
 
 impl<DL: Delay, DS: DataStore> DataStoreRead for DelayDataStore<DL, DS> {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
@@ -52,7 +164,7 @@ impl<DL: Delay, DS: DataStore> DataStoreRead for DelayDataStore<DL, DS> {
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
@@ -62,7 +174,7 @@ impl<DL: Delay, DS: DataStore> DataStoreRead for DelayDataStore<DL, DS> {
 }
 
 impl<DL: Delay, DS: DataStore> DataStoreWrite for DelayDataStore<DL, DS> {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
@@ -71,7 +183,7 @@ impl<DL: Delay, DS: DataStore> DataStoreWrite for DelayDataStore<DL, DS> {
         self.datastore.put(key, value)
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
@@ -81,7 +193,7 @@ impl<DL: Delay, DS: DataStore> DataStoreWrite for DelayDataStore<DL, DS> {
 }
 
 impl<DL: Delay, DS: PersistentDataStore> Persistent for DelayDataStore<DL, DS> {
-    fn disk_usage(&self) -> io::Result<u64> {
+    fn disk_usage(&self) -> Result<u64> {
         self.delay.wait();
         self.datastore.disk_usage()
     }
@@ -90,7 +202,7 @@ impl<DL: Delay, DS: PersistentDataStore> Persistent for DelayDataStore<DL, DS> {
 impl<DL: Delay, DS: DataStore> ToBatch for DelayDataStore<DL, DS> {
     type Batch = BasicBatchDataStore<DelayDataStore<DL, DS>>;
 
-    fn batch(&self) -> io::Result<Self::Batch> {
+    fn batch(&self) -> Result<Self::Batch> {
         Ok(BasicBatchDataStore::new(self.clone()))
     }
 }
@@ -98,7 +210,41 @@ impl<DL: Delay, DS: DataStore> ToBatch for DelayDataStore<DL, DS> {
 impl<DL: Delay, DS: DataStore> ToTxn for DelayDataStore<DL, DS> {
     type Txn = BasicTxnDataStore<DelayDataStore<DL, DS>>;
 
-    fn txn(&self, _read_only: bool) -> io::Result<Self::Txn> {
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
         Ok(BasicTxnDataStore::new(self.clone()))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variable_delay() {
+        let (base, spread) = (Duration::from_millis(10), Duration::from_millis(5));
+        let delay = VariableDelay::uniform(base, spread, 42);
+        let samples = (0..100).map(|_| delay.sample()).collect::<Vec<_>>();
+        assert!(samples.iter().all(|d| *d >= base && *d < base + spread));
+        // The same seed gives the same sequence.
+        let delay = VariableDelay::uniform(base, spread, 42);
+        assert_eq!(
+            (0..100).map(|_| delay.sample()).collect::<Vec<_>>(),
+            samples
+        );
+
+        let mean = Duration::from_millis(1);
+        let delay = VariableDelay::normal(mean, Duration::from_millis(2), 7);
+        let samples = (0..1000).map(|_| delay.sample()).collect::<Vec<_>>();
+        assert!(samples.iter().any(|d| *d == Duration::from_secs(0)));
+        assert!(samples.iter().any(|d| *d > mean));
+    }
+
+    #[test]
+    fn test_fixed_delay() {
+        let delay = FixedDelay::new(Duration::from_millis(1));
+        let cloned = delay.clone();
+        cloned.set(Duration::from_millis(0));
+        assert_eq!(delay.get(), Duration::from_millis(0));
+        delay.wait();
+    }
+}
@@ -1,8 +1,12 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::borrow::Borrow;
+use std::collections::HashSet;
 use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
+use crate::error::{DataStoreError, Result};
 use crate::key::Key;
 use crate::store::{BatchDataStore, ToBatch, ToTxn, TxnDataStore};
 use crate::store::{Check, CheckedBatchDataStore, CheckedDataStore, CheckedTxnDataStore};
@@ -13,8 +17,76 @@ use crate::store::{
 };
 use crate::store::{Scrub, ScrubbedBatchDataStore, ScrubbedDataStore, ScrubbedTxnDataStore};
 
-/// The user-provided fail function.
-pub trait FailFn: Fn(&str) -> io::Result<()> + Clone + Sync + Send + 'static {}
+/// The user-provided fail function, which is called with the name of the operation and the key
+/// of the operation if any, e.g. `("put", Some(key))` or `("batch-commit", None)`.
+///
+/// The operations of the batches and the transactions are prefixed with `batch-` and `txn-`.
+pub trait FailFn: Fn(&str, Option<&Key>) -> Result<()> + Clone + Sync + Send + 'static {}
+
+impl<F> FailFn for F where F: Fn(&str, Option<&Key>) -> Result<()> + Clone + Sync + Send + 'static {}
+
+/// Return the error injected by the fail functions for the operation.
+pub fn injected_error(op: &str) -> DataStoreError {
+    DataStoreError::Backend(io::Error::new(
+        io::ErrorKind::Other,
+        format!("injected failure: {}", op),
+    ))
+}
+
+/// Create a fail function failing every `n`th operation, i.e. the `n`th, `2n`th... operations.
+///
+/// The counter is shared by the clones of the function, so the batches and the transactions
+/// created from the datastore are counted together with the datastore.
+pub fn fail_every(n: u64) -> impl FailFn {
+    assert!(n > 0, "fail_every: n must be positive");
+    let count = Arc::new(AtomicU64::new(0));
+    move |op: &str, _key: Option<&Key>| {
+        if (count.fetch_add(1, Ordering::SeqCst) + 1) % n == 0 {
+            Err(injected_error(op))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Create a fail function failing the operations on the `prefix` or the keys under the `prefix`.
+///
+/// The operations without key, e.g. `batch-commit`, never fail.
+pub fn fail_on_prefix<K: Into<Key>>(prefix: K) -> impl FailFn {
+    let prefix = prefix.into();
+    move |op: &str, key: Option<&Key>| match key {
+        Some(key) if *key == prefix || key.is_descendant_of(prefix.clone()) => {
+            Err(injected_error(op))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Create a fail function failing the operations of the given names, e.g. `["put", "txn-put"]`.
+pub fn fail_on_ops<I, S>(ops: I) -> impl FailFn
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let ops = Arc::new(ops.into_iter().map(Into::into).collect::<HashSet<String>>());
+    move |op: &str, _key: Option<&Key>| {
+        if ops.contains(op) {
+            Err(injected_error(op))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Combine two fail functions, the operation fails if either of the functions fails.
+///
+/// The second function isn't called if the first one fails.
+pub fn fail_either<A: FailFn, B: FailFn>(a: A, b: B) -> impl FailFn {
+    move |op: &str, key: Option<&Key>| {
+        a(op, key)?;
+        b(op, key)
+    }
+}
 
 /// FailDataStore is a datastore which fails according to a user-provided function.
 #[derive(Clone)]
@@ -32,80 +104,81 @@ impl<F: FailFn, DS: DataStore> FailDataStore<F, DS> {
 }
 
 impl<F: FailFn, DS: DataStore> DataStore for FailDataStore<F, DS> {
-    fn sync<K>(&mut self, prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("sync")?;
+        (self.fail_fn)("sync", Some(prefix.borrow()))?;
         self.datastore.sync(prefix)
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
         self.datastore.close()
     }
 }
 
 impl<F: FailFn, DS: DataStore> DataStoreRead for FailDataStore<F, DS> {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("put")?;
+        (self.fail_fn)("get", Some(key.borrow()))?;
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("has")?;
+        (self.fail_fn)("has", Some(key.borrow()))?;
         self.datastore.has(key)
     }
 }
 
 impl<F: FailFn, DS: DataStore> DataStoreWrite for FailDataStore<F, DS> {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
-        (self.fail_fn)("put")?;
+        let key = key.into();
+        (self.fail_fn)("put", Some(&key))?;
         self.datastore.put(key, value)
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("delete")?;
+        (self.fail_fn)("delete", Some(key.borrow()))?;
         self.datastore.delete(key)
     }
 }
 
 impl<F: FailFn, DS: CheckedDataStore> Check for FailDataStore<F, DS> {
-    fn check(&self) -> io::Result<()> {
-        (self.fail_fn)("check")?;
+    fn check(&self) -> Result<()> {
+        (self.fail_fn)("check", None)?;
         self.datastore.check()
     }
 }
 
 impl<F: FailFn, DS: GcDataStore> Gc for FailDataStore<F, DS> {
-    fn collect_garbage(&self) -> io::Result<()> {
-        (self.fail_fn)("collect-garbage")?;
+    fn collect_garbage(&self) -> Result<()> {
+        (self.fail_fn)("collect-garbage", None)?;
         self.datastore.collect_garbage()
     }
 }
 
 impl<F: FailFn, DS: PersistentDataStore> Persistent for FailDataStore<F, DS> {
-    fn disk_usage(&self) -> io::Result<u64> {
-        (self.fail_fn)("disk-usage")?;
+    fn disk_usage(&self) -> Result<u64> {
+        (self.fail_fn)("disk-usage", None)?;
         self.datastore.disk_usage()
     }
 }
 
 impl<F: FailFn, DS: ScrubbedDataStore> Scrub for FailDataStore<F, DS> {
-    fn scrub(&self) -> io::Result<()> {
-        (self.fail_fn)("scrub")?;
+    fn scrub(&self) -> Result<()> {
+        (self.fail_fn)("scrub", None)?;
         self.datastore.scrub()
     }
 }
@@ -113,7 +186,7 @@ impl<F: FailFn, DS: ScrubbedDataStore> Scrub for FailDataStore<F, DS> {
 impl<F: FailFn, BDS: BatchDataStore> ToBatch for FailDataStore<F, BDS> {
     type Batch = FailBatchDataStore<F, BDS>;
 
-    fn batch(&self) -> io::Result<Self::Batch> {
+    fn batch(&self) -> Result<Self::Batch> {
         Ok(FailBatchDataStore::new(
             self.fail_fn.clone(),
             self.datastore.clone(),
@@ -124,7 +197,7 @@ impl<F: FailFn, BDS: BatchDataStore> ToBatch for FailDataStore<F, BDS> {
 impl<F: FailFn, TDS: TxnDataStore> ToTxn for FailDataStore<F, TDS> {
     type Txn = FailTxnDataStore<F, TDS>;
 
-    fn txn(&self, _read_only: bool) -> io::Result<Self::Txn> {
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
         Ok(FailTxnDataStore::new(
             self.fail_fn.clone(),
             self.datastore.clone(),
@@ -150,87 +223,88 @@ impl<F: FailFn, BDS: BatchDataStore> FailBatchDataStore<F, BDS> {
 }
 
 impl<F: FailFn, BDS: BatchDataStore> DataStore for FailBatchDataStore<F, BDS> {
-    fn sync<K>(&mut self, prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("batch-sync")?;
+        (self.fail_fn)("batch-sync", Some(prefix.borrow()))?;
         self.datastore.sync(prefix)
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
         self.datastore.close()
     }
 }
 
 impl<F: FailFn, BDS: BatchDataStore> DataStoreRead for FailBatchDataStore<F, BDS> {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("batch-put")?;
+        (self.fail_fn)("batch-get", Some(key.borrow()))?;
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("batch-has")?;
+        (self.fail_fn)("batch-has", Some(key.borrow()))?;
         self.datastore.has(key)
     }
 }
 
 impl<F: FailFn, BDS: BatchDataStore> DataStoreWrite for FailBatchDataStore<F, BDS> {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
-        (self.fail_fn)("batch-put")?;
+        let key = key.into();
+        (self.fail_fn)("batch-put", Some(&key))?;
         self.datastore.put(key, value)
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("batch-delete")?;
+        (self.fail_fn)("batch-delete", Some(key.borrow()))?;
         self.datastore.delete(key)
     }
 }
 
 impl<F: FailFn, BDS: BatchDataStore> DataStoreBatch for FailBatchDataStore<F, BDS> {
-    fn commit(&mut self) -> io::Result<()> {
-        (self.fail_fn)("batch-commit")?;
+    fn commit(&mut self) -> Result<()> {
+        (self.fail_fn)("batch-commit", None)?;
         self.datastore.commit()
     }
 }
 
 impl<F: FailFn, BDS: CheckedBatchDataStore> Check for FailBatchDataStore<F, BDS> {
-    fn check(&self) -> io::Result<()> {
-        (self.fail_fn)("check")?;
+    fn check(&self) -> Result<()> {
+        (self.fail_fn)("check", None)?;
         self.datastore.check()
     }
 }
 
 impl<F: FailFn, BDS: GcBatchDataStore> Gc for FailBatchDataStore<F, BDS> {
-    fn collect_garbage(&self) -> io::Result<()> {
-        (self.fail_fn)("collect-garbage")?;
+    fn collect_garbage(&self) -> Result<()> {
+        (self.fail_fn)("collect-garbage", None)?;
         self.datastore.collect_garbage()
     }
 }
 
 impl<F: FailFn, BDS: PersistentBatchDataStore> Persistent for FailBatchDataStore<F, BDS> {
-    fn disk_usage(&self) -> io::Result<u64> {
-        (self.fail_fn)("disk-usage")?;
+    fn disk_usage(&self) -> Result<u64> {
+        (self.fail_fn)("disk-usage", None)?;
         self.datastore.disk_usage()
     }
 }
 
 impl<F: FailFn, BDS: ScrubbedBatchDataStore> Scrub for FailBatchDataStore<F, BDS> {
-    fn scrub(&self) -> io::Result<()> {
-        (self.fail_fn)("scrub")?;
+    fn scrub(&self) -> Result<()> {
+        (self.fail_fn)("scrub", None)?;
         self.datastore.scrub()
     }
 }
@@ -238,7 +312,7 @@ impl<F: FailFn, BDS: ScrubbedBatchDataStore> Scrub for FailBatchDataStore<F, BDS
 impl<F: FailFn, TDS: TxnDataStore> ToTxn for FailBatchDataStore<F, TDS> {
     type Txn = FailTxnDataStore<F, TDS>;
 
-    fn txn(&self, _read_only: bool) -> io::Result<Self::Txn> {
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
         Ok(FailTxnDataStore::new(
             self.fail_fn.clone(),
             self.datastore.clone(),
@@ -264,94 +338,131 @@ impl<F: FailFn, TDS: TxnDataStore> FailTxnDataStore<F, TDS> {
 }
 
 impl<F: FailFn, TDS: TxnDataStore> DataStore for FailTxnDataStore<F, TDS> {
-    fn sync<K>(&mut self, prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("txn-sync")?;
+        (self.fail_fn)("txn-sync", Some(prefix.borrow()))?;
         self.datastore.sync(prefix)
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
         self.datastore.close()
     }
 }
 
 impl<F: FailFn, TDS: TxnDataStore> DataStoreRead for FailTxnDataStore<F, TDS> {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("txn-put")?;
+        (self.fail_fn)("txn-get", Some(key.borrow()))?;
         self.datastore.get(key)
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("txn-has")?;
+        (self.fail_fn)("txn-has", Some(key.borrow()))?;
         self.datastore.has(key)
     }
 }
 
 impl<F: FailFn, TDS: TxnDataStore> DataStoreWrite for FailTxnDataStore<F, TDS> {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
-        (self.fail_fn)("txn-put")?;
+        let key = key.into();
+        (self.fail_fn)("txn-put", Some(&key))?;
         self.datastore.put(key, value)
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        (self.fail_fn)("txn-delete")?;
+        (self.fail_fn)("txn-delete", Some(key.borrow()))?;
         self.datastore.delete(key)
     }
 }
 
 impl<F: FailFn, TDS: TxnDataStore> DataStoreBatch for FailTxnDataStore<F, TDS> {
-    fn commit(&mut self) -> io::Result<()> {
-        (self.fail_fn)("txn-commit")?;
+    fn commit(&mut self) -> Result<()> {
+        (self.fail_fn)("txn-commit", None)?;
         self.datastore.commit()
     }
 }
 
 impl<F: FailFn, TDS: TxnDataStore> DataStoreTxn for FailTxnDataStore<F, TDS> {
-    fn discard(&mut self) -> io::Result<()> {
-        (self.fail_fn)("txn-discard")?;
+    fn discard(&mut self) -> Result<()> {
+        (self.fail_fn)("txn-discard", None)?;
         self.datastore.discard()
     }
 }
 
 impl<F: FailFn, TDS: CheckedTxnDataStore> Check for FailTxnDataStore<F, TDS> {
-    fn check(&self) -> io::Result<()> {
-        (self.fail_fn)("check")?;
+    fn check(&self) -> Result<()> {
+        (self.fail_fn)("check", None)?;
         self.datastore.check()
     }
 }
 
 impl<F: FailFn, TDS: GcTxnDataStore> Gc for FailTxnDataStore<F, TDS> {
-    fn collect_garbage(&self) -> io::Result<()> {
-        (self.fail_fn)("collect-garbage")?;
+    fn collect_garbage(&self) -> Result<()> {
+        (self.fail_fn)("collect-garbage", None)?;
         self.datastore.collect_garbage()
     }
 }
 
 impl<F: FailFn, TDS: PersistentTxnDataStore> Persistent for FailTxnDataStore<F, TDS> {
-    fn disk_usage(&self) -> io::Result<u64> {
-        (self.fail_fn)("disk-usage")?;
+    fn disk_usage(&self) -> Result<u64> {
+        (self.fail_fn)("disk-usage", None)?;
         self.datastore.disk_usage()
     }
 }
 
 impl<F: FailFn, TDS: ScrubbedTxnDataStore> Scrub for FailTxnDataStore<F, TDS> {
-    fn scrub(&self) -> io::Result<()> {
-        (self.fail_fn)("scrub")?;
+    fn scrub(&self) -> Result<()> {
+        (self.fail_fn)("scrub", None)?;
         self.datastore.scrub()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::impls::{MapDataStore, SyncDataStore};
+
+    #[test]
+    fn test_fail_every() {
+        let datastore = FailDataStore::new(fail_every(3), SyncDataStore::new(MapDataStore::new()));
+        let key = Key::new("/a");
+        datastore.put(key.clone(), b"a".to_vec()).unwrap();
+        assert!(datastore.has(&key).unwrap());
+        assert!(datastore.get(&key).is_err());
+        assert_eq!(datastore.get(&key).unwrap(), Some(b"a".to_vec()));
+
+        // The clones share the counter.
+        let cloned = datastore.clone();
+        cloned.put(Key::new("/b"), b"b".to_vec()).unwrap();
+        assert!(datastore.delete(&key).is_err());
+        assert!(cloned.has(&key).unwrap());
+    }
+
+    #[test]
+    fn test_fail_on_prefix() {
+        let fail_fn = fail_either(fail_on_prefix("/blocks"), fail_on_ops(vec!["delete"]));
+        let datastore = FailDataStore::new(fail_fn, SyncDataStore::new(MapDataStore::new()));
+        assert!(datastore.put(Key::new("/blocks"), b"a".to_vec()).is_err());
+        assert!(datastore.put(Key::new("/blocks/a"), b"a".to_vec()).is_err());
+        assert!(datastore.get(&Key::new("/blocks/a")).is_err());
+        datastore.put(Key::new("/blocksa"), b"a".to_vec()).unwrap();
+        datastore.put(Key::new("/heads/a"), b"a".to_vec()).unwrap();
+        assert!(datastore.delete(&Key::new("/heads/a")).is_err());
+        assert!(datastore.has(&Key::new("/heads/a")).unwrap());
+    }
+}
@@ -2,15 +2,27 @@
 
 use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::io;
 
+use parking_lot::RwLock;
+
+use crate::error::Result;
 use crate::key::Key;
 use crate::store::{DataStore, DataStoreRead, DataStoreWrite};
 
 /// MapDataStore use HashMap for internal storage.
-#[derive(Clone, Debug, Default)]
+///
+/// Cloning a MapDataStore copies all the values, the clones don't share the storage.
+#[derive(Debug, Default)]
 pub struct MapDataStore {
-    values: HashMap<Key, Vec<u8>>,
+    values: RwLock<HashMap<Key, Vec<u8>>>,
+}
+
+impl Clone for MapDataStore {
+    fn clone(&self) -> Self {
+        Self {
+            values: RwLock::new(self.values.read().clone()),
+        }
+    }
 }
 
 impl MapDataStore {
@@ -21,49 +33,49 @@ impl MapDataStore {
 }
 
 impl DataStore for MapDataStore {
-    fn sync<K>(&mut self, _prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, _prefix: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
         Ok(())
     }
 
-    fn close(&mut self) -> io::Result<()> {
+    fn close(&self) -> Result<()> {
         Ok(())
     }
 }
 
 impl DataStoreRead for MapDataStore {
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>,
     {
-        Ok(self.values.get(key.borrow()).cloned())
+        Ok(self.values.read().get(key.borrow()).cloned())
     }
 
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>,
     {
-        Ok(self.values.contains_key(key.borrow()))
+        Ok(self.values.read().contains_key(key.borrow()))
     }
 }
 
 impl DataStoreWrite for MapDataStore {
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>,
     {
-        self.values.insert(key.into(), value.into());
+        self.values.write().insert(key.into(), value.into());
         Ok(())
     }
 
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>,
     {
-        self.values.remove(key.borrow());
+        self.values.write().remove(key.borrow());
         Ok(())
     }
 }
@@ -0,0 +1,472 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+use crate::error::Result;
+use crate::impls::{BasicBatchDataStore, BasicTxnDataStore};
+use crate::key::Key;
+use crate::store::{DataStore, DataStoreRead, DataStoreWrite};
+use crate::store::{Persistent, PersistentDataStore};
+use crate::store::{ToBatch, ToTxn};
+
+/// The upper bounds of the buckets of the latency histograms, in microseconds.
+pub const LATENCY_BUCKETS: [u64; 12] = [
+    10, 25, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 100_000, 1_000_000,
+];
+
+/// The upper bounds of the buckets of the value size histograms, in bytes.
+pub const SIZE_BUCKETS: [u64; 10] = [
+    64,
+    256,
+    1 << 10,
+    4 << 10,
+    16 << 10,
+    64 << 10,
+    256 << 10,
+    1 << 20,
+    4 << 20,
+    16 << 20,
+];
+
+/// The operation of the datastore.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DataStoreOp {
+    /// `get` of `DataStoreRead`.
+    Get,
+    /// `has` of `DataStoreRead`.
+    Has,
+    /// `put` of `DataStoreWrite`.
+    Put,
+    /// `delete` of `DataStoreWrite`.
+    Delete,
+}
+
+impl DataStoreOp {
+    /// Return the name of the operation, e.g. for the labels of the metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataStoreOp::Get => "get",
+            DataStoreOp::Has => "has",
+            DataStoreOp::Put => "put",
+            DataStoreOp::Delete => "delete",
+        }
+    }
+}
+
+impl fmt::Display for DataStoreOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The histogram of the observed values with fixed buckets, the observations greater than the
+/// last bound are counted in the overflow bucket.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: &'static [u64],
+    // The last one is the overflow bucket.
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+}
+
+impl Histogram {
+    /// Create an empty histogram with the ascending upper `bounds` of the buckets.
+    pub fn new(bounds: &'static [u64]) -> Self {
+        debug_assert!(bounds.windows(2).all(|w| w[0] < w[1]));
+        Self {
+            bounds,
+            counts: vec![0; bounds.len() + 1],
+            count: 0,
+            sum: 0,
+        }
+    }
+
+    /// Record the `value`.
+    pub fn observe(&mut self, value: u64) {
+        let index = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[index] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+    }
+
+    /// Return the number of the observations.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Return the sum of the observations.
+    pub fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    /// Return the upper bounds and the counts of the buckets, the bound of the overflow bucket
+    /// is `None`. The counts are not cumulative.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .copied()
+            .map(Some)
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+
+    /// Return the upper bound of the bucket of the `q` quantile, which is in `[0, 1]`.
+    /// `None` is returned if nothing is observed, or the quantile is in the overflow bucket.
+    pub fn quantile(&self, q: f64) -> Option<u64> {
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut cumulative = 0;
+        for (bound, count) in self.buckets() {
+            cumulative += count;
+            if cumulative >= rank {
+                return bound;
+            }
+        }
+        None
+    }
+}
+
+/// The metrics of an operation in a namespace.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpMetrics {
+    /// The latencies of the operations in microseconds, including the failed ones.
+    pub latency: Histogram,
+    /// The sizes of the values in bytes, i.e. the values got by `get` and written by `put`.
+    pub size: Histogram,
+    /// The number of the failed operations.
+    pub errors: u64,
+}
+
+impl Default for OpMetrics {
+    fn default() -> Self {
+        Self {
+            latency: Histogram::new(&LATENCY_BUCKETS),
+            size: Histogram::new(&SIZE_BUCKETS),
+            errors: 0,
+        }
+    }
+}
+
+/// The registry of the metrics of the datastore operations per key namespace, i.e. the first
+/// namespace of the keys, e.g. `blocks` of `/blocks/CIQ...`. The root key is in the `""`
+/// namespace.
+///
+/// The registry is shared by the clones, so one registry can be shared by the datastores of
+/// several subsystems.
+#[derive(Clone, Debug, Default)]
+pub struct DataStoreMetrics {
+    metrics: Arc<Mutex<HashMap<String, HashMap<DataStoreOp, OpMetrics>>>>,
+}
+
+impl DataStoreMetrics {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an operation on the `key`, the `size` of the value is recorded if any.
+    pub fn record(
+        &self,
+        key: &Key,
+        op: DataStoreOp,
+        latency: Duration,
+        size: Option<usize>,
+        failed: bool,
+    ) {
+        let namespace = key.list().first().copied().unwrap_or("");
+        let mut metrics = self.metrics.lock();
+        let ops = match metrics.get_mut(namespace) {
+            Some(ops) => ops,
+            None => metrics.entry(namespace.to_string()).or_default(),
+        };
+        let op = ops.entry(op).or_default();
+        op.latency.observe(latency.as_micros() as u64);
+        if let Some(size) = size {
+            op.size.observe(size as u64);
+        }
+        if failed {
+            op.errors += 1;
+        }
+    }
+
+    /// Return the metrics of the operation in the `namespace`.
+    pub fn get(&self, namespace: &str, op: DataStoreOp) -> Option<OpMetrics> {
+        self.metrics.lock().get(namespace)?.get(&op).cloned()
+    }
+
+    /// Return the recorded namespaces.
+    pub fn namespaces(&self) -> Vec<String> {
+        let mut namespaces = self.metrics.lock().keys().cloned().collect::<Vec<_>>();
+        namespaces.sort();
+        namespaces
+    }
+
+    /// Return a snapshot of all the metrics, keyed by the namespaces.
+    pub fn snapshot(&self) -> HashMap<String, HashMap<DataStoreOp, OpMetrics>> {
+        self.metrics.lock().clone()
+    }
+
+    /// Remove all the recorded metrics.
+    pub fn reset(&self) {
+        self.metrics.lock().clear();
+    }
+}
+
+/// MeteredDataStore is an adapter that records the latencies and the value sizes of the
+/// operations on the inner datastore per key namespace.
+#[derive(Clone)]
+pub struct MeteredDataStore<DS: DataStore> {
+    metrics: DataStoreMetrics,
+    datastore: DS,
+}
+
+impl<DS: DataStore> MeteredDataStore<DS> {
+    /// Create a new MeteredDataStore with its own registry.
+    pub fn new(datastore: DS) -> Self {
+        Self {
+            metrics: DataStoreMetrics::new(),
+            datastore,
+        }
+    }
+
+    /// Record the metrics into the shared `metrics` registry.
+    pub fn with_metrics(mut self, metrics: DataStoreMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Return the registry of the metrics.
+    pub fn metrics(&self) -> &DataStoreMetrics {
+        &self.metrics
+    }
+
+    /// Return the inner datastore.
+    pub fn inner(&self) -> &DS {
+        &self.datastore
+    }
+}
+
+impl<DS: DataStore> DataStore for MeteredDataStore<DS> {
+    fn sync<K>(&self, prefix: &K) -> Result<()>
+    where
+        K: Borrow<Key>,
+    {
+        self.datastore.sync(prefix)
+    }
+
+    fn close(&self) -> Result<()> {
+        self.datastore.close()
+    }
+}
+
+impl<DS: DataStore> DataStoreRead for MeteredDataStore<DS> {
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
+    where
+        K: Borrow<Key>,
+    {
+        let start = Instant::now();
+        let result = self.datastore.get(key);
+        let size = match &result {
+            Ok(Some(value)) => Some(value.len()),
+            _ => None,
+        };
+        self.metrics.record(
+            key.borrow(),
+            DataStoreOp::Get,
+            start.elapsed(),
+            size,
+            result.is_err(),
+        );
+        result
+    }
+
+    fn has<K>(&self, key: &K) -> Result<bool>
+    where
+        K: Borrow<Key>,
+    {
+        let start = Instant::now();
+        let result = self.datastore.has(key);
+        self.metrics.record(
+            key.borrow(),
+            DataStoreOp::Has,
+            start.elapsed(),
+            None,
+            result.is_err(),
+        );
+        result
+    }
+}
+
+impl<DS: DataStore> DataStoreWrite for MeteredDataStore<DS> {
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
+    where
+        K: Into<Key>,
+        V: Into<Vec<u8>>,
+    {
+        let key = key.into();
+        let value = value.into();
+        let size = value.len();
+        // The key is kept for the namespace, as the inner datastore takes it by value.
+        let start = Instant::now();
+        let result = self.datastore.put(key.clone(), value);
+        self.metrics.record(
+            &key,
+            DataStoreOp::Put,
+            start.elapsed(),
+            Some(size),
+            result.is_err(),
+        );
+        result
+    }
+
+    fn delete<K>(&self, key: &K) -> Result<()>
+    where
+        K: Borrow<Key>,
+    {
+        let start = Instant::now();
+        let result = self.datastore.delete(key);
+        self.metrics.record(
+            key.borrow(),
+            DataStoreOp::Delete,
+            start.elapsed(),
+            None,
+            result.is_err(),
+        );
+        result
+    }
+}
+
+impl<DS: PersistentDataStore> Persistent for MeteredDataStore<DS> {
+    fn disk_usage(&self) -> Result<u64> {
+        self.datastore.disk_usage()
+    }
+}
+
+impl<DS: DataStore> ToBatch for MeteredDataStore<DS> {
+    type Batch = BasicBatchDataStore<MeteredDataStore<DS>>;
+
+    fn batch(&self) -> Result<Self::Batch> {
+        Ok(BasicBatchDataStore::new(self.clone()))
+    }
+}
+
+impl<DS: DataStore> ToTxn for MeteredDataStore<DS> {
+    type Txn = BasicTxnDataStore<MeteredDataStore<DS>>;
+
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
+        Ok(BasicTxnDataStore::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::impls::{fail_on_ops, FailDataStore, MapDataStore};
+    use crate::store::DataStoreBatch;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[10, 100]);
+        assert_eq!(histogram.quantile(0.5), None);
+        for value in &[1, 10, 11, 100, 1000] {
+            histogram.observe(*value);
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.sum(), 1122);
+        assert_eq!(
+            histogram.buckets().collect::<Vec<_>>(),
+            vec![(Some(10), 2), (Some(100), 2), (None, 1)]
+        );
+        assert_eq!(histogram.quantile(0.0), Some(10));
+        assert_eq!(histogram.quantile(0.4), Some(10));
+        assert_eq!(histogram.quantile(0.8), Some(100));
+        assert_eq!(histogram.quantile(1.0), None);
+    }
+
+    #[test]
+    fn test_metered_datastore() {
+        let metrics = DataStoreMetrics::new();
+        let datastore = MeteredDataStore::new(MapDataStore::new()).with_metrics(metrics.clone());
+        datastore
+            .put(Key::new("/blocks/a"), vec![0u8; 100])
+            .unwrap();
+        datastore
+            .put(Key::new("/blocks/b"), vec![0u8; 1000])
+            .unwrap();
+        datastore
+            .put(Key::new("/meta/head"), vec![0u8; 10])
+            .unwrap();
+        assert_eq!(
+            datastore
+                .get(&Key::new("/blocks/a"))
+                .unwrap()
+                .unwrap()
+                .len(),
+            100
+        );
+        assert_eq!(datastore.get(&Key::new("/blocks/c")).unwrap(), None);
+        assert!(datastore.has(&Key::new("/meta/head")).unwrap());
+        datastore.delete(&Key::new("/")).unwrap();
+
+        assert_eq!(metrics.namespaces(), vec!["", "blocks", "meta"]);
+        let put = metrics.get("blocks", DataStoreOp::Put).unwrap();
+        assert_eq!(put.latency.count(), 2);
+        assert_eq!(put.size.count(), 2);
+        assert_eq!(put.size.sum(), 1100);
+        // The missing value has no size.
+        let get = metrics.get("blocks", DataStoreOp::Get).unwrap();
+        assert_eq!((get.latency.count(), get.size.count()), (2, 1));
+        assert_eq!(
+            metrics
+                .get("meta", DataStoreOp::Has)
+                .unwrap()
+                .latency
+                .count(),
+            1
+        );
+        assert_eq!(
+            metrics
+                .get("", DataStoreOp::Delete)
+                .unwrap()
+                .latency
+                .count(),
+            1
+        );
+        assert!(metrics.get("meta", DataStoreOp::Get).is_none());
+
+        // The clones and the batches record into the same registry.
+        let mut batch = datastore.batch().unwrap();
+        batch.put(Key::new("/meta/tail"), vec![1u8]).unwrap();
+        batch.commit().unwrap();
+        assert_eq!(
+            metrics
+                .get("meta", DataStoreOp::Put)
+                .unwrap()
+                .latency
+                .count(),
+            2
+        );
+
+        metrics.reset();
+        assert!(metrics.namespaces().is_empty());
+    }
+
+    #[test]
+    fn test_metered_datastore_errors() {
+        let failing = FailDataStore::new(fail_on_ops(vec!["put"]), MapDataStore::new());
+        let datastore = MeteredDataStore::new(failing);
+        assert!(datastore.put(Key::new("/blocks/a"), vec![1u8]).is_err());
+        let put = datastore.metrics().get("blocks", DataStoreOp::Put).unwrap();
+        assert_eq!((put.latency.count(), put.errors), (1, 1));
+    }
+}
@@ -6,15 +6,25 @@ mod dummy;
 mod fail;
 mod log;
 mod map;
+mod metered;
+mod readonly;
 mod sync;
 mod transform;
 
 pub use self::basic::{BasicBatchDataStore, BasicTxnDataStore};
-pub use self::delay::{Delay, DelayDataStore};
+pub use self::delay::{Delay, DelayDataStore, DelayDistribution, FixedDelay, VariableDelay};
 pub use self::dummy::DummyDataStore;
 pub use self::map::MapDataStore;
+pub use self::metered::{
+    DataStoreMetrics, DataStoreOp, Histogram, MeteredDataStore, OpMetrics, LATENCY_BUCKETS,
+    SIZE_BUCKETS,
+};
+pub use self::readonly::ReadOnlyDataStore;
 
-pub use self::fail::{FailBatchDataStore, FailDataStore, FailFn, FailTxnDataStore};
+pub use self::fail::{
+    fail_either, fail_every, fail_on_ops, fail_on_prefix, injected_error, FailBatchDataStore,
+    FailDataStore, FailFn, FailTxnDataStore,
+};
 pub use self::log::{LogBatchDataStore, LogDataStore, LogTxnDataStore};
 pub use self::sync::{SyncBatchDataStore, SyncDataStore, SyncTxnDataStore};
 pub use self::transform::{
@@ -0,0 +1,140 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::borrow::Borrow;
+
+use crate::error::{DataStoreError, Result};
+use crate::impls::{BasicBatchDataStore, BasicTxnDataStore};
+use crate::key::Key;
+use crate::store::{Check, CheckedDataStore};
+use crate::store::{DataStore, DataStoreRead, DataStoreWrite};
+use crate::store::{Persistent, PersistentDataStore};
+use crate::store::{ToBatch, ToTxn};
+
+/// ReadOnlyDataStore is an adapter that forwards the reads to the inner datastore
+/// and rejects all the writes with `DataStoreError::ReadOnly`.
+///
+/// The batches and the transactions created from it fail on commit if they contain any write.
+#[derive(Clone)]
+pub struct ReadOnlyDataStore<DS: DataStore> {
+    datastore: DS,
+}
+
+impl<DS: DataStore> ReadOnlyDataStore<DS> {
+    /// Create a new ReadOnlyDataStore.
+    pub fn new(datastore: DS) -> Self {
+        Self { datastore }
+    }
+
+    /// Return the inner datastore.
+    pub fn into_inner(self) -> DS {
+        self.datastore
+    }
+}
+
+impl<DS: DataStore> DataStore for ReadOnlyDataStore<DS> {
+    fn sync<K>(&self, _prefix: &K) -> Result<()>
+    where
+        K: Borrow<Key>,
+    {
+        // Nothing is written through the adapter.
+        Ok(())
+    }
+
+    fn close(&self) -> Result<()> {
+        self.datastore.close()
+    }
+}
+
+impl<DS: DataStore> DataStoreRead for ReadOnlyDataStore<DS> {
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
+    where
+        K: Borrow<Key>,
+    {
+        self.datastore.get(key)
+    }
+
+    fn has<K>(&self, key: &K) -> Result<bool>
+    where
+        K: Borrow<Key>,
+    {
+        self.datastore.has(key)
+    }
+}
+
+impl<DS: DataStore> DataStoreWrite for ReadOnlyDataStore<DS> {
+    fn put<K, V>(&self, _key: K, _value: V) -> Result<()>
+    where
+        K: Into<Key>,
+        V: Into<Vec<u8>>,
+    {
+        Err(DataStoreError::ReadOnly)
+    }
+
+    fn delete<K>(&self, _key: &K) -> Result<()>
+    where
+        K: Borrow<Key>,
+    {
+        Err(DataStoreError::ReadOnly)
+    }
+}
+
+impl<DS: CheckedDataStore> Check for ReadOnlyDataStore<DS> {
+    fn check(&self) -> Result<()> {
+        self.datastore.check()
+    }
+}
+
+impl<DS: PersistentDataStore> Persistent for ReadOnlyDataStore<DS> {
+    fn disk_usage(&self) -> Result<u64> {
+        self.datastore.disk_usage()
+    }
+}
+
+impl<DS: DataStore> ToBatch for ReadOnlyDataStore<DS> {
+    type Batch = BasicBatchDataStore<ReadOnlyDataStore<DS>>;
+
+    fn batch(&self) -> Result<Self::Batch> {
+        Ok(BasicBatchDataStore::new(self.clone()))
+    }
+}
+
+impl<DS: DataStore> ToTxn for ReadOnlyDataStore<DS> {
+    type Txn = BasicTxnDataStore<ReadOnlyDataStore<DS>>;
+
+    fn txn(&self, _read_only: bool) -> Result<Self::Txn> {
+        Ok(BasicTxnDataStore::new(self.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::impls::{MapDataStore, SyncDataStore};
+    use crate::store::DataStoreBatch;
+
+    #[test]
+    fn test_read_only_datastore() {
+        let inner = SyncDataStore::new(MapDataStore::new());
+        inner.put(Key::new("/a"), b"a".to_vec()).unwrap();
+        let datastore = ReadOnlyDataStore::new(inner.clone());
+
+        assert_eq!(datastore.get(&Key::new("/a")).unwrap(), Some(b"a".to_vec()));
+        assert!(datastore.has(&Key::new("/a")).unwrap());
+        assert!(matches!(
+            datastore.put(Key::new("/b"), b"b".to_vec()),
+            Err(DataStoreError::ReadOnly)
+        ));
+        assert!(matches!(
+            datastore.delete(&Key::new("/a")),
+            Err(DataStoreError::ReadOnly)
+        ));
+
+        let mut batch = datastore.batch().unwrap();
+        batch.commit().unwrap();
+        batch.put(Key::new("/b"), b"b".to_vec()).unwrap();
+        assert!(matches!(batch.commit(), Err(DataStoreError::ReadOnly)));
+        assert!(!inner.has(&Key::new("/b")).unwrap());
+        assert!(inner.has(&Key::new("/a")).unwrap());
+    }
+}
@@ -4,11 +4,13 @@
 
 #![deny(missing_docs)]
 
+mod error;
 mod impls;
 mod key;
 // mod query;
 mod store;
 
+pub use self::error::{DataStoreError, Result};
 pub use self::key::{namespace_type, namespace_value, Key};
 // pub use self::query::*;
 
@@ -27,10 +29,17 @@ pub use self::store::{Scrub, ScrubbedBatchDataStore, ScrubbedDataStore, Scrubbed
 pub use self::store::{Ttl, TtlBatchDataStore, TtlDataStore, TtlTxnDataStore};
 
 pub use self::impls::{BasicBatchDataStore, BasicTxnDataStore};
-pub use self::impls::{Delay, DelayDataStore};
-pub use self::impls::{DummyDataStore, MapDataStore};
+pub use self::impls::{
+    DataStoreMetrics, DataStoreOp, Histogram, MeteredDataStore, OpMetrics, LATENCY_BUCKETS,
+    SIZE_BUCKETS,
+};
+pub use self::impls::{Delay, DelayDataStore, DelayDistribution, FixedDelay, VariableDelay};
+pub use self::impls::{DummyDataStore, MapDataStore, ReadOnlyDataStore};
 
-pub use self::impls::{FailBatchDataStore, FailDataStore, FailFn, FailTxnDataStore};
+pub use self::impls::{
+    fail_either, fail_every, fail_on_ops, fail_on_prefix, injected_error, FailBatchDataStore,
+    FailDataStore, FailFn, FailTxnDataStore,
+};
 pub use self::impls::{
     KeyMapFn, KeyTransform, KeyTransformPair, PrefixTransform, TransformBatchDataStore,
     TransformDataStore, TransformTxnDataStore,
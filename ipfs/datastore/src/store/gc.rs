@@ -1,13 +1,12 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
-use std::io;
-
+use crate::error::Result;
 use crate::store::{BatchDataStore, DataStore, TxnDataStore};
 
 /// An interface that free disk space.
 pub trait Gc {
     /// Free disk space.
-    fn collect_garbage(&self) -> io::Result<()>;
+    fn collect_garbage(&self) -> Result<()>;
 }
 
 /// GcDataStore is an interface that should be implemented by data stores
@@ -1,8 +1,8 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::borrow::Borrow;
-use std::io;
 
+use crate::error::{DataStoreError, Result};
 use crate::key::Key;
 
 /// DataStore represents storage for any key-value pair.
@@ -32,26 +32,50 @@ pub trait DataStore: DataStoreRead + DataStoreWrite + Clone {
     /// If `put/delete` operations already satisfy these requirements then Sync may be a no-op.
     ///
     ///  If the prefix fails to `sync` this method returns an error.
-    fn sync<K>(&mut self, prefix: &K) -> io::Result<()>
+    fn sync<K>(&self, prefix: &K) -> Result<()>
     where
         K: Borrow<Key>;
 
     /// Close I/O.
-    fn close(&mut self) -> io::Result<()>;
+    fn close(&self) -> Result<()>;
 }
 
 /// DataStoreRead is the read-side of the DataStore trait.
 pub trait DataStoreRead {
-    /// Retrieve the object `value` named by `key`.
-    fn get<K>(&self, key: &K) -> io::Result<Option<Vec<u8>>>
+    /// Retrieve the object `value` named by `key`, `None` is returned if the `key` doesn't exist.
+    fn get<K>(&self, key: &K) -> Result<Option<Vec<u8>>>
     where
         K: Borrow<Key>;
 
+    /// Retrieve the object `value` named by `key`,
+    /// `DataStoreError::NotFound` is returned if the `key` doesn't exist.
+    fn get_existing<K>(&self, key: &K) -> Result<Vec<u8>>
+    where
+        K: Borrow<Key>,
+    {
+        match self.get(key)? {
+            Some(value) => Ok(value),
+            None => Err(DataStoreError::NotFound(key.borrow().clone())),
+        }
+    }
+
     /// Return whether the `key` is mapped to a `value`.
-    fn has<K>(&self, key: &K) -> io::Result<bool>
+    fn has<K>(&self, key: &K) -> Result<bool>
     where
         K: Borrow<Key>;
 
+    /// Retrieve the objects named by `keys` at once, the values are returned in the order of
+    /// the `keys`, and `None` is returned for the keys that don't exist.
+    ///
+    /// The default implementation reads the keys one by one,
+    /// the datastores supporting batched reads should override it.
+    fn get_many<K>(&self, keys: &[K]) -> Result<Vec<Option<Vec<u8>>>>
+    where
+        K: Borrow<Key>,
+    {
+        keys.iter().map(|key| self.get(key)).collect()
+    }
+
     // Query searches the datastore and returns a query result. This function
     // may return before the query actually runs.
     // TODO: query
@@ -68,14 +92,14 @@ pub trait DataStoreWrite {
     /// Ultimately, the lowest-level datastore will need to do some value checking
     /// or risk getting incorrect values. It may also be useful to expose a more
     /// type-safe interface to your application, and do the checking up-front.
-    fn put<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>;
 
     /// Remove the value for given `key`.
     /// If the key is not in the datastore, this method returns no error.
-    fn delete<K>(&mut self, key: &K) -> io::Result<()>
+    fn delete<K>(&self, key: &K) -> Result<()>
     where
         K: Borrow<Key>;
 }
@@ -84,7 +108,7 @@ pub trait DataStoreWrite {
 /// to support batch write.
 pub trait DataStoreBatch: DataStoreWrite {
     /// Commit all update operations.
-    fn commit(&mut self) -> io::Result<()>;
+    fn commit(&mut self) -> Result<()>;
 }
 
 /// BatchDataStore is an interface that should be implemented by data stores that
@@ -99,7 +123,7 @@ pub trait ToBatch {
     type Batch: DataStoreBatch;
 
     /// Create a new batching data store.
-    fn batch(&self) -> io::Result<Self::Batch>;
+    fn batch(&self) -> Result<Self::Batch>;
 }
 
 /// ToBatchDataStore is an interface that describe a database have batch feature, it
@@ -114,7 +138,7 @@ pub trait DataStoreTxn: DataStoreRead + DataStoreBatch {
     /// them to the underlying Datastore. Any calls made to Discard after Commit
     /// has been successfully called will have no effect on the transaction and
     /// state of the Datastore, making it safe to defer.
-    fn discard(&mut self) -> io::Result<()>;
+    fn discard(&mut self) -> Result<()>;
 }
 
 /// TxnDataStore is an interface that should be implemented by data stores that support transactions.
@@ -127,7 +151,7 @@ pub trait ToTxn {
     type Txn: DataStoreTxn;
 
     /// Create a new txn data store.
-    fn txn(&self, read_only: bool) -> io::Result<Self::Txn>;
+    fn txn(&self, read_only: bool) -> Result<Self::Txn>;
 }
 
 /// ToTxnDataStore is an interface that describe a database have totxn feature, it
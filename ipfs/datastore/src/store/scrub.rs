@@ -1,13 +1,12 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
-use std::io;
-
+use crate::error::Result;
 use crate::store::{BatchDataStore, DataStore, TxnDataStore};
 
 /// An interface that check data integrity and/or error correction.
 pub trait Scrub {
     /// Check data integrity and/or error correction.
-    fn scrub(&self) -> io::Result<()>;
+    fn scrub(&self) -> Result<()>;
 }
 
 /// ScrubbedDataStore is an interface that should be implemented by data stores
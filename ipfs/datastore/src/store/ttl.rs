@@ -1,25 +1,26 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::borrow::Borrow;
-use std::io;
 use std::time::{Duration, Instant};
 
+use crate::error::Result;
 use crate::key::Key;
 use crate::store::{BatchDataStore, DataStore, TxnDataStore};
 
 /// Ttl encapsulates the methods that deal with entries with time-to-live.
 pub trait Ttl {
     /// Store the object `value` named by `key` with time-to-live.
-    fn put_with_ttl<K, V>(&mut self, key: K, value: V) -> io::Result<()>
+    fn put_with_ttl<K, V>(&self, key: K, value: V) -> Result<()>
     where
         K: Into<Key>,
         V: Into<Vec<u8>>;
 
     /// Set the duration of time-to-live.
-    fn set_ttl(&mut self, ttl: Duration) -> io::Result<()>;
+    fn set_ttl(&self, ttl: Duration) -> Result<()>;
 
-    /// Get the next expiration of time-to-live of the `key`.
-    fn get_expiration<K>(&self, key: &K) -> io::Result<Instant>
+    /// Get the next expiration of time-to-live of the `key`,
+    /// `DataStoreError::NotFound` is returned if the `key` doesn't exist.
+    fn get_expiration<K>(&self, key: &K) -> Result<Instant>
     where
         K: Borrow<Key>;
 }
@@ -0,0 +1,477 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use cid::Cid;
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use crate::error::Result;
+use crate::store::IpldStore;
+
+/// The default bit width of the AMT nodes, i.e. each node has `2^3` slots.
+pub const DEFAULT_AMT_BIT_WIDTH: u32 = 3;
+/// The max bit width of the AMT nodes.
+pub const MAX_AMT_BIT_WIDTH: u32 = 18;
+
+/// Build an AMT (array mapped trie) holding the `values` at the indexes `0..values.len()`,
+/// store all the nodes into the `store` and return the CID of the root.
+///
+/// The layout is the same as go-amt-ipld v3:
+///
+/// ```text
+/// root = [bit_width, height, count, node]
+/// node = [bitmap, links, values]
+/// ```
+pub fn build_amt<S, T>(store: &S, values: &[T], bit_width: u32) -> Result<Cid>
+where
+    S: IpldStore,
+    T: encode::Encode,
+{
+    check_bit_width(bit_width)?;
+    let width = 1usize << bit_width;
+
+    let mut nodes = values
+        .chunks(width)
+        .map(|chunk| Node::leaf(bit_width, chunk))
+        .collect::<Vec<_>>();
+    let mut height = 0u64;
+    while nodes.len() > 1 {
+        let links = nodes
+            .iter()
+            .map(|node| IpldStore::put(store, node))
+            .collect::<Result<Vec<_>>>()?;
+        nodes = links
+            .chunks(width)
+            .map(|chunk| Node::internal(bit_width, chunk.to_vec()))
+            .collect();
+        height += 1;
+    }
+
+    let node = nodes.pop().unwrap_or_else(|| Node::leaf(bit_width, &[]));
+    IpldStore::put(
+        store,
+        Root {
+            bit_width,
+            height,
+            count: values.len() as u64,
+            node,
+        },
+    )
+}
+
+/// The max number of the values preallocated when loading an AMT,
+/// the count of the root isn't trusted.
+const MAX_PREALLOCATED_VALUES: u64 = 4096;
+
+/// The error of an AMT which is malformed or inconsistent, e.g. decoded from an untrusted block.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum AmtError {
+    /// The CBOR structure of the root or the node is malformed.
+    #[error("{0}")]
+    Malformed(String),
+    /// The bit width is zero or larger than `MAX_AMT_BIT_WIDTH`.
+    #[error("bit width {0}")]
+    BitWidth(u64),
+    /// The height is larger than the max height of the bit width.
+    #[error("height {height} exceeds the max height {max_height}")]
+    Height {
+        /// The height of the root.
+        height: u64,
+        /// The max height allowed by the bit width.
+        max_height: u64,
+    },
+    /// The count is larger than the capacity of the AMT with the height.
+    #[error("count {count} exceeds the capacity of height {height}")]
+    CountOverflow {
+        /// The count of the root.
+        count: u64,
+        /// The height of the root.
+        height: u64,
+    },
+    /// The count doesn't match the number of the values.
+    #[error("count {count} doesn't match the number of values {values}")]
+    CountMismatch {
+        /// The count of the root.
+        count: u64,
+        /// The number of the loaded values.
+        values: u64,
+    },
+    /// The length of the bitmap doesn't match the bit width.
+    #[error("bitmap length {0}")]
+    BitmapLength(usize),
+    /// The bitmap sets the slots beyond the width of the node.
+    #[error("bitmap sets slots beyond the width {0}")]
+    BitmapOverflow(u64),
+    /// The number of the links or the values doesn't match the slots set in the bitmap,
+    /// a leaf node must only have values and an internal node must only have links.
+    #[error("{links} links and {values} values don't match {slots} slots at height {height}")]
+    SlotMismatch {
+        /// The height of the node.
+        height: u64,
+        /// The number of the slots set in the bitmap.
+        slots: u64,
+        /// The number of the links.
+        links: u64,
+        /// The number of the values.
+        values: u64,
+    },
+    /// An internal node has no link.
+    #[error("empty internal node at height {0}")]
+    EmptyNode(u64),
+    /// The node is missing in the store.
+    #[error("missing node {0}")]
+    MissingNode(Cid),
+}
+
+/// Return the max height of the AMT with the `bit_width`, so that the indexes fit in u64.
+pub fn max_amt_height(bit_width: u32) -> u64 {
+    64 / u64::from(bit_width) - 1
+}
+
+/// Load all the values of the AMT with the `root`, return the values along with their indexes
+/// in ascending order of the indexes.
+///
+/// The root and the nodes are validated as the untrusted blocks, the height and the count of the
+/// root must be below the max of the bit width, and the bitmap, the links and the values of each
+/// node must be consistent, an `AmtError` is returned otherwise.
+pub fn load_amt<S, T>(store: &S, root: &Cid) -> Result<Vec<(u64, T)>>
+where
+    S: IpldStore,
+    T: for<'b> decode::Decode<'b>,
+{
+    let data = get_block(store, root)?;
+    load_root(store, &data)
+}
+
+fn load_root<S, T>(store: &S, data: &[u8]) -> Result<Vec<(u64, T)>>
+where
+    S: IpldStore,
+    T: for<'b> decode::Decode<'b>,
+{
+    let mut d = Decoder::new(data);
+    expect_array(&mut d, 4)?;
+    let bit_width = d.u64()?;
+    if bit_width == 0 || bit_width > u64::from(MAX_AMT_BIT_WIDTH) {
+        return Err(AmtError::BitWidth(bit_width).into());
+    }
+    let bit_width = bit_width as u32;
+    let height = d.u64()?;
+    let max_height = max_amt_height(bit_width);
+    if height > max_height {
+        return Err(AmtError::Height { height, max_height }.into());
+    }
+    let count = d.u64()?;
+    // The AMT of the height holds `2^(bit_width * (height + 1))` values at most.
+    if u128::from(count) > 1u128 << (u64::from(bit_width) * (height + 1)) {
+        return Err(AmtError::CountOverflow { count, height }.into());
+    }
+
+    let mut values = Vec::with_capacity(count.min(MAX_PREALLOCATED_VALUES) as usize);
+    load_node(store, &mut d, bit_width, height, 0, &mut values)?;
+    if values.len() as u64 != count {
+        return Err(AmtError::CountMismatch {
+            count,
+            values: values.len() as u64,
+        }
+        .into());
+    }
+    Ok(values)
+}
+
+fn load_node<S, T>(
+    store: &S,
+    d: &mut Decoder<'_>,
+    bit_width: u32,
+    height: u64,
+    offset: u64,
+    values: &mut Vec<(u64, T)>,
+) -> Result<()>
+where
+    S: IpldStore,
+    T: for<'b> decode::Decode<'b>,
+{
+    let width = 1u64 << bit_width;
+    expect_array(d, 3)?;
+    let bitmap = d.bytes()?;
+    if bitmap.len() != bitmap_len(bit_width) {
+        return Err(AmtError::BitmapLength(bitmap.len()).into());
+    }
+    let mut slots = vec![];
+    for i in 0..bitmap.len() as u64 * 8 {
+        if bitmap[(i / 8) as usize] & (1 << (i % 8)) != 0 {
+            if i >= width {
+                return Err(AmtError::BitmapOverflow(width).into());
+            }
+            slots.push(i);
+        }
+    }
+
+    // The lengths are checked against the bitmap before decoding the items,
+    // so that the untrusted lengths are never used to allocate.
+    let links_len = d
+        .array()?
+        .ok_or_else(|| AmtError::Malformed("indefinite links".into()))?;
+    let (expected_links, expected_values) = if height == 0 {
+        (0, slots.len() as u64)
+    } else {
+        (slots.len() as u64, 0)
+    };
+    let mismatch = |values: u64| AmtError::SlotMismatch {
+        height,
+        slots: slots.len() as u64,
+        links: links_len,
+        values,
+    };
+    if links_len != expected_links {
+        return Err(mismatch(expected_values).into());
+    }
+    let links = (0..links_len)
+        .map(|_| d.decode::<Cid>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let values_len = d
+        .array()?
+        .ok_or_else(|| AmtError::Malformed("indefinite values".into()))?;
+    if values_len != expected_values {
+        return Err(mismatch(values_len).into());
+    }
+
+    if height == 0 {
+        for slot in slots {
+            values.push((offset + slot, d.decode::<T>()?));
+        }
+    } else {
+        if links.is_empty() {
+            return Err(AmtError::EmptyNode(height).into());
+        }
+        let child_width = width.pow(height as u32);
+        let blocks = ipfs_blockstore::BlockStore::get_many(store, &links)?;
+        for ((slot, link), block) in slots.into_iter().zip(&links).zip(blocks) {
+            let block = block.ok_or_else(|| AmtError::MissingNode(link.clone()))?;
+            let mut child = Decoder::new(block.data());
+            let offset = offset + slot * child_width;
+            load_node(store, &mut child, bit_width, height - 1, offset, values)?;
+        }
+    }
+    Ok(())
+}
+
+struct Root<'a, T> {
+    bit_width: u32,
+    height: u64,
+    count: u64,
+    node: Node<'a, T>,
+}
+
+// Implement CBOR serialization for Root.
+impl<'a, T: encode::Encode> encode::Encode for Root<'a, T> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(4)?
+            .u64(u64::from(self.bit_width))?
+            .u64(self.height)?
+            .u64(self.count)?
+            .encode(&self.node)?
+            .ok()
+    }
+}
+
+struct Node<'a, T> {
+    bitmap: Vec<u8>,
+    links: Vec<Cid>,
+    values: &'a [T],
+}
+
+impl<'a, T> Node<'a, T> {
+    fn leaf(bit_width: u32, values: &'a [T]) -> Self {
+        Self {
+            bitmap: full_bitmap(bit_width, values.len()),
+            links: vec![],
+            values,
+        }
+    }
+
+    fn internal(bit_width: u32, links: Vec<Cid>) -> Self {
+        Self {
+            bitmap: full_bitmap(bit_width, links.len()),
+            links,
+            values: &[],
+        }
+    }
+}
+
+// Implement CBOR serialization for Node.
+impl<'a, T: encode::Encode> encode::Encode for Node<'a, T> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(3)?
+            .bytes(&self.bitmap)?
+            .encode(&self.links)?
+            .array(self.values.len() as u64)?;
+        for value in self.values {
+            e.encode(value)?;
+        }
+        e.ok()
+    }
+}
+
+// The bitmap with the first `len` slots set.
+fn full_bitmap(bit_width: u32, len: usize) -> Vec<u8> {
+    let mut bitmap = vec![0u8; bitmap_len(bit_width)];
+    for i in 0..len {
+        bitmap[i / 8] |= 1 << (i % 8);
+    }
+    bitmap
+}
+
+fn bitmap_len(bit_width: u32) -> usize {
+    if bit_width <= 3 {
+        1
+    } else {
+        1 << (bit_width - 3)
+    }
+}
+
+fn check_bit_width(bit_width: u32) -> Result<()> {
+    if bit_width == 0 || bit_width > MAX_AMT_BIT_WIDTH {
+        return Err(AmtError::BitWidth(u64::from(bit_width)).into());
+    }
+    Ok(())
+}
+
+fn get_block<S: IpldStore>(store: &S, cid: &Cid) -> Result<Vec<u8>> {
+    match ipfs_blockstore::BlockStore::get(store, cid)? {
+        Some(block) => Ok(block.data().to_vec()),
+        None => Err(AmtError::MissingNode(cid.clone()).into()),
+    }
+}
+
+fn expect_array(d: &mut Decoder<'_>, len: u64) -> Result<()> {
+    match d.array()? {
+        Some(array_len) if array_len == len => Ok(()),
+        array_len => Err(AmtError::Malformed(format!(
+            "expected array of {}, got {:?}",
+            len, array_len
+        ))
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_block::Block;
+
+    use crate::error::IpldError;
+    use crate::test_utils::new_store;
+
+    #[test]
+    fn test_amt() {
+        let store = new_store();
+        for len in [0u64, 1, 8, 9, 64, 65, 100].iter() {
+            let values = (0..*len).map(|i| i * 10).collect::<Vec<_>>();
+            let root = build_amt(&store, &values, DEFAULT_AMT_BIT_WIDTH).unwrap();
+            let loaded = load_amt::<_, u64>(&store, &root).unwrap();
+            assert_eq!(
+                loaded,
+                values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| (i as u64, v))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn test_empty_amt() {
+        let store = new_store();
+        let root = build_amt::<_, u64>(&store, &[], DEFAULT_AMT_BIT_WIDTH).unwrap();
+        // The same as the empty AMT of go-amt-ipld v3.
+        let data = ipfs_blockstore::BlockStore::get(&store, &root)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            data.data(),
+            &[0x84, 0x03, 0x00, 0x00, 0x83, 0x41, 0x00, 0x80, 0x80]
+        );
+        assert!(build_amt::<_, u64>(&store, &[], 0).is_err());
+    }
+
+    fn load_error(root: &[u8]) -> AmtError {
+        match load_root::<_, u64>(&new_store(), root) {
+            Err(IpldError::InvalidAmt(err)) => err,
+            result => panic!("unexpected result {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_invalid_amt() {
+        // [bit_width, height, count, [bitmap, links, values]]
+        let valid = [0x84, 0x03, 0x00, 0x01, 0x83, 0x41, 0x01, 0x80, 0x81, 0x07];
+        assert_eq!(
+            load_root::<_, u64>(&new_store(), &valid).unwrap(),
+            vec![(0, 7)]
+        );
+
+        let root = [0x84, 0x13, 0x00, 0x00, 0x83, 0x41, 0x00, 0x80, 0x80];
+        assert_eq!(load_error(&root), AmtError::BitWidth(19));
+        // The bit width is truncated to 3 if it's cast into u32.
+        let root = [
+            0x84, 0x1b, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x83, 0x41,
+            0x00, 0x80, 0x80,
+        ];
+        assert_eq!(load_error(&root), AmtError::BitWidth((1 << 32) + 3));
+        let root = [0x84, 0x03, 0x15, 0x00, 0x83, 0x41, 0x00, 0x80, 0x80];
+        assert_eq!(
+            load_error(&root),
+            AmtError::Height {
+                height: 21,
+                max_height: 20
+            }
+        );
+        // A count of `u64::MAX` is neither preallocated nor loaded.
+        let root = [
+            0x84, 0x03, 0x00, 0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x83, 0x41,
+            0x00, 0x80, 0x80,
+        ];
+        assert_eq!(
+            load_error(&root),
+            AmtError::CountOverflow {
+                count: u64::max_value(),
+                height: 0
+            }
+        );
+        let root = [0x84, 0x03, 0x00, 0x02, 0x83, 0x41, 0x01, 0x80, 0x81, 0x07];
+        assert_eq!(
+            load_error(&root),
+            AmtError::CountMismatch {
+                count: 2,
+                values: 1
+            }
+        );
+        let root = [0x84, 0x03, 0x00, 0x00, 0x83, 0x42, 0x00, 0x00, 0x80, 0x80];
+        assert_eq!(load_error(&root), AmtError::BitmapLength(2));
+        // Only the low 4 bits of the bitmap are the slots of bit width 2.
+        let root = [0x84, 0x02, 0x00, 0x01, 0x83, 0x41, 0x10, 0x80, 0x81, 0x07];
+        assert_eq!(load_error(&root), AmtError::BitmapOverflow(4));
+        // The values don't match the bitmap, and the leaf has a link.
+        let root = [0x84, 0x03, 0x00, 0x01, 0x83, 0x41, 0x03, 0x80, 0x81, 0x07];
+        assert!(matches!(
+            load_error(&root),
+            AmtError::SlotMismatch { slots: 2, .. }
+        ));
+        let mut root = vec![0x84, 0x03, 0x00, 0x01, 0x83, 0x41, 0x01, 0x81];
+        root.extend(minicbor::to_vec(Block::new(1u64).cid()).unwrap());
+        root.extend_from_slice(&[0x81, 0x07]);
+        assert!(matches!(
+            load_error(&root),
+            AmtError::SlotMismatch { links: 1, .. }
+        ));
+        // An internal root without any link.
+        let root = [0x84, 0x03, 0x01, 0x00, 0x83, 0x41, 0x00, 0x80, 0x80];
+        assert_eq!(load_error(&root), AmtError::EmptyNode(1));
+        // An internal root linking to a missing node.
+        let link = Block::new(1u64).cid().clone();
+        let mut root = vec![0x84, 0x03, 0x01, 0x01, 0x83, 0x41, 0x01, 0x81];
+        root.extend(minicbor::to_vec(&link).unwrap());
+        root.push(0x80);
+        assert_eq!(load_error(&root), AmtError::MissingNode(link));
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The MultihashIndexSorted index of CARv2, the entries are grouped by the multihash code,
+//! then by the digest length, and sorted by the digest.
+//!
+//! ```text
+//! index   = varint(0x0401) u32(codes) code*
+//! code    = u64(multihash code) u32(buckets) bucket*
+//! bucket  = u32(digest length + 8) u64(entries length) (digest u64(offset))*
+//! ```
+//!
+//! The integers are little-endian, the offsets are the offsets of the block sections from the
+//! beginning of the CARv1 data payload.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+
+use cid::Cid;
+
+use super::invalid;
+use crate::error::Result;
+
+/// The multicodec of the MultihashIndexSorted index.
+pub const MULTIHASH_INDEX_SORTED_CODEC: u64 = 0x0401;
+
+// The length of the offset of an index entry.
+const OFFSET_LEN: usize = 8;
+// The max length of the multihash digests, e.g. of SHA3-512 and Blake2b-512.
+const MAX_DIGEST_LEN: usize = 64;
+
+/// The index of the blocks of a CAR file, which maps the multihashes of the blocks to the
+/// offsets of their sections in the CARv1 data payload.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CarIndex {
+    // multihash code => digest => offset
+    entries: BTreeMap<u64, BTreeMap<Vec<u8>, u64>>,
+    len: usize,
+}
+
+impl CarIndex {
+    /// Create an empty index.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of the indexed blocks.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return true if no block is indexed.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Index the block of the `cid` at the `offset`, the first offset of a block written twice
+    /// is kept.
+    pub fn insert(&mut self, cid: &Cid, offset: u64) {
+        let (code, digest) =
+            split_multihash(cid.hash().as_bytes()).expect("the multihash of a CID is valid; qed");
+        let digests = self.entries.entry(code).or_default();
+        if !digests.contains_key(digest) {
+            digests.insert(digest.to_vec(), offset);
+            self.len += 1;
+        }
+    }
+
+    /// Return the offset of the block of the `cid`.
+    pub fn get(&self, cid: &Cid) -> Option<u64> {
+        let (code, digest) = split_multihash(cid.hash().as_bytes())?;
+        self.entries.get(&code)?.get(digest).copied()
+    }
+
+    /// Write the index in the MultihashIndexSorted format, including the multicodec.
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        plum_varint::write_u64(writer, MULTIHASH_INDEX_SORTED_CODEC)?;
+        writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for (code, digests) in &self.entries {
+            writer.write_all(&code.to_le_bytes())?;
+            let mut buckets = BTreeMap::<usize, Vec<(&[u8], u64)>>::new();
+            for (digest, offset) in digests {
+                buckets
+                    .entry(digest.len())
+                    .or_default()
+                    .push((digest, *offset));
+            }
+            writer.write_all(&(buckets.len() as u32).to_le_bytes())?;
+            for (digest_len, entries) in buckets {
+                let width = digest_len + OFFSET_LEN;
+                writer.write_all(&(width as u32).to_le_bytes())?;
+                writer.write_all(&((width * entries.len()) as u64).to_le_bytes())?;
+                for (digest, offset) in entries {
+                    writer.write_all(digest)?;
+                    writer.write_all(&offset.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the index in the MultihashIndexSorted format, including the multicodec, the other
+    /// index formats are rejected.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self> {
+        let codec = plum_varint::read_u64_canonical(reader)?
+            .ok_or_else(|| invalid("missing index".into()))?;
+        if codec != MULTIHASH_INDEX_SORTED_CODEC {
+            return Err(invalid(format!("unsupported index codec {:#x}", codec)));
+        }
+        let mut index = Self::new();
+        for _ in 0..read_u32(reader)? {
+            let code = read_u64(reader)?;
+            for _ in 0..read_u32(reader)? {
+                let width = read_u32(reader)? as usize;
+                let len = read_u64(reader)?;
+                // The width is checked before allocating an entry of the width.
+                if width <= OFFSET_LEN
+                    || width > OFFSET_LEN + MAX_DIGEST_LEN
+                    || len % width as u64 != 0
+                {
+                    return Err(invalid(format!(
+                        "invalid index bucket of width {} and length {}",
+                        width, len
+                    )));
+                }
+                // The entries are read one by one, so a corrupted length fails at the end of
+                // the file instead of allocating the length.
+                let mut entry = vec![0u8; width];
+                for _ in 0..len / width as u64 {
+                    reader.read_exact(&mut entry)?;
+                    let (digest, offset) = entry.split_at(width - OFFSET_LEN);
+                    let mut offset_bytes = [0u8; OFFSET_LEN];
+                    offset_bytes.copy_from_slice(offset);
+                    let digests = index.entries.entry(code).or_default();
+                    if !digests.contains_key(digest) {
+                        digests.insert(digest.to_vec(), u64::from_le_bytes(offset_bytes));
+                        index.len += 1;
+                    }
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+// Split the multihash into the code and the digest.
+fn split_multihash(multihash: &[u8]) -> Option<(u64, &[u8])> {
+    let (code, rest) = plum_varint::decode_u64_canonical(multihash).ok()?;
+    let (len, digest) = plum_varint::decode_u64_canonical(rest).ok()?;
+    if digest.len() as u64 == len {
+        Some((code, digest))
+    } else {
+        None
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_block::Block;
+
+    #[test]
+    fn test_index_roundtrip() {
+        let blocks = vec![Block::new(1u64), Block::new("plum"), Block::new(vec![1u8])];
+        let mut index = CarIndex::new();
+        for (i, block) in blocks.iter().enumerate() {
+            index.insert(block.cid(), i as u64 * 100);
+        }
+        // The offset of the first section of a duplicated block is kept.
+        index.insert(blocks[0].cid(), 1000);
+        assert_eq!(index.len(), 3);
+        assert_eq!(index.get(blocks[0].cid()), Some(0));
+        assert_eq!(index.get(blocks[2].cid()), Some(200));
+
+        let mut data = vec![];
+        index.write(&mut data).unwrap();
+        // The codec, one multihash code, one bucket of width 32 + 8 with 3 entries.
+        assert_eq!(&data[..2], &[0x81, 0x08]);
+        assert_eq!(&data[2..6], &1u32.to_le_bytes());
+        assert_eq!(&data[14..18], &1u32.to_le_bytes());
+        assert_eq!(&data[18..22], &40u32.to_le_bytes());
+        assert_eq!(&data[22..30], &120u64.to_le_bytes());
+        assert_eq!(data.len(), 30 + 120);
+        assert_eq!(CarIndex::read(&mut data.as_slice()).unwrap(), index);
+
+        // Truncated entries and unknown codec.
+        assert!(CarIndex::read(&mut &data[..data.len() - 1]).is_err());
+        let mut unknown = vec![0x80, 0x08];
+        unknown.extend_from_slice(&data[2..]);
+        assert!(CarIndex::read(&mut unknown.as_slice()).is_err());
+
+        // The bucket of a huge width is rejected before allocating its entries.
+        let mut huge = data[..18].to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        huge.extend_from_slice(&(u64::from(u32::MAX) * 2).to_le_bytes());
+        assert!(CarIndex::read(&mut huge.as_slice()).is_err());
+        let mut wide = data[..18].to_vec();
+        wide.extend_from_slice(&73u32.to_le_bytes());
+        wide.extend_from_slice(&73u64.to_le_bytes());
+        wide.extend_from_slice(&[0u8; 73]);
+        assert!(CarIndex::read(&mut wide.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The CARv1 (content addressable archive) format, which is used to export and import
+//! the chain snapshots.
+//!
+//! ```text
+//! car     = section(header) section(block)*
+//! section = varint(len) bytes
+//! header  = dag-cbor {"roots": [cid...], "version": 1}
+//! block   = cid data
+//! ```
+//!
+//! The CARv2 format wraps a CARv1 data payload with a fixed header and an optional index of
+//! the blocks, see `v2` for the details. `IndexedCarReader` reads the blocks of both formats
+//! by their CIDs without importing the file.
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use cid::Cid;
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use crate::error::{IpldError, Result};
+
+mod index;
+mod v2;
+
+pub use self::index::{CarIndex, MULTIHASH_INDEX_SORTED_CODEC};
+pub use self::v2::{
+    CarV2Header, CarV2Writer, IndexedCarReader, CAR_V2_HEADER_LEN, CAR_V2_PRAGMA, CAR_V2_VERSION,
+};
+
+/// The version of the CAR format written by `CarWriter`.
+pub const CAR_VERSION: u64 = 1;
+/// The max length of a section, sections longer than this are rejected when reading.
+pub const MAX_CAR_SECTION_LEN: u64 = 32 * 1024 * 1024;
+
+/// The header of a CAR file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CarHeader {
+    /// The roots of the DAGs in the file.
+    pub roots: Vec<Cid>,
+    /// The version of the format.
+    pub version: u64,
+}
+
+impl CarHeader {
+    /// Create a new header of `CAR_VERSION` with the `roots`.
+    pub fn new(roots: Vec<Cid>) -> Self {
+        Self {
+            roots,
+            version: CAR_VERSION,
+        }
+    }
+}
+
+// Implement CBOR serialization for CarHeader.
+impl encode::Encode for CarHeader {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        // The keys are in the canonical order of DAG-CBOR (shorter key first).
+        e.map(2)?.str("roots")?.array(self.roots.len() as u64)?;
+        for root in &self.roots {
+            e.encode(root)?;
+        }
+        e.str("version")?.u64(self.version)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for CarHeader.
+impl<'b> decode::Decode<'b> for CarHeader {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let map_len = d
+            .map()?
+            .ok_or(decode::Error::Message("indefinite CAR header"))?;
+        let mut roots = None;
+        let mut version = None;
+        for _ in 0..map_len {
+            match d.str()? {
+                "roots" => roots = Some(d.decode::<Vec<Cid>>()?),
+                "version" => version = Some(d.u64()?),
+                _ => d.skip()?,
+            }
+        }
+        Ok(CarHeader {
+            roots: roots.unwrap_or_default(),
+            version: version.ok_or(decode::Error::Message("missing version of CAR header"))?,
+        })
+    }
+}
+
+/// The writer of a CARv1 file.
+///
+/// The header is written on creation, the blocks are written as they come, the caller is
+/// responsible for not writing a block twice.
+pub struct CarWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> CarWriter<W> {
+    /// Create a new writer and write the header with the `roots`.
+    pub fn new(mut writer: W, roots: Vec<Cid>) -> Result<Self> {
+        write_header(&mut writer, roots)?;
+        Ok(Self { writer })
+    }
+
+    /// Write the block of the `cid`.
+    pub fn write_block(&mut self, cid: &Cid, data: &[u8]) -> Result<()> {
+        write_block_section(&mut self.writer, cid, data)?;
+        Ok(())
+    }
+
+    /// Flush the underlying writer and return it.
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The reader of a CARv1 file.
+pub struct CarReader<R> {
+    reader: R,
+    header: CarHeader,
+    position: u64,
+}
+
+impl<R: Read> CarReader<R> {
+    /// Create a new reader and read the header, only `CAR_VERSION` is supported.
+    pub fn new(mut reader: R) -> Result<Self> {
+        let data = read_section(&mut reader)?.ok_or_else(|| invalid("missing header".into()))?;
+        let header = minicbor::decode::<CarHeader>(&data)?;
+        if header.version != CAR_VERSION {
+            return Err(invalid(format!("unsupported version {}", header.version)));
+        }
+        Ok(Self {
+            reader,
+            header,
+            position: section_len(data.len()),
+        })
+    }
+
+    /// Return the header of the file.
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// Return the offset of the next section from the beginning of the file, which is the
+    /// offset of the block returned by the next `next_block`.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Read the next block, `None` is returned at the end of the file.
+    pub fn next_block(&mut self) -> Result<Option<(Cid, Vec<u8>)>> {
+        let data = match read_section(&mut self.reader)? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        self.position += section_len(data.len());
+        parse_block(data).map(Some)
+    }
+}
+
+// Write the header section with the `roots`.
+fn write_header<W: Write>(writer: &mut W, roots: Vec<Cid>) -> Result<u64> {
+    let header =
+        minicbor::to_vec(CarHeader::new(roots)).expect("encoding into a Vec never fails; qed");
+    plum_varint::write_u64(writer, header.len() as u64)?;
+    writer.write_all(&header)?;
+    Ok(section_len(header.len()))
+}
+
+// Write the section of the block, return the length of the section.
+fn write_block_section<W: Write>(writer: &mut W, cid: &Cid, data: &[u8]) -> Result<u64> {
+    let cid = cid.to_bytes();
+    let len = cid.len() + data.len();
+    plum_varint::write_u64(writer, len as u64)?;
+    writer.write_all(&cid)?;
+    writer.write_all(data)?;
+    Ok(section_len(len))
+}
+
+// Return the length of the section whose data is `len` bytes, including the varint length.
+fn section_len(len: usize) -> u64 {
+    (plum_varint::encoded_len(len as u64) + len) as u64
+}
+
+// Split the data of a block section into the CID and the block data.
+fn parse_block(mut data: Vec<u8>) -> Result<(Cid, Vec<u8>)> {
+    let cid_len = cid_len(&data).ok_or_else(|| invalid("invalid block CID".into()))?;
+    let cid = Cid::try_from(&data[..cid_len]).map_err(|err| invalid(err.to_string()))?;
+    let data = data.split_off(cid_len);
+    Ok((cid, data))
+}
+
+fn read_section<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    let len = match plum_varint::read_u64_canonical(reader)? {
+        Some(len) => len,
+        None => return Ok(None),
+    };
+    if len == 0 || len > MAX_CAR_SECTION_LEN {
+        return Err(invalid(format!("invalid section length {}", len)));
+    }
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+// Return the length of the CID at the beginning of the `bytes`.
+fn cid_len(bytes: &[u8]) -> Option<usize> {
+    // CIDv0 is a bare sha2-256 multihash.
+    if bytes.len() >= 34 && bytes[0] == 0x12 && bytes[1] == 0x20 {
+        return Some(34);
+    }
+    let mut offset = 0;
+    // version, codec, hash code and digest length
+    let mut digest_len = 0;
+    for _ in 0..4 {
+        let (value, rest) = plum_varint::decode_u64_canonical(&bytes[offset..]).ok()?;
+        offset = bytes.len() - rest.len();
+        digest_len = value as usize;
+    }
+    let len = offset.checked_add(digest_len)?;
+    if len <= bytes.len() {
+        Some(len)
+    } else {
+        None
+    }
+}
+
+fn invalid(reason: String) -> IpldError {
+    IpldError::InvalidCar(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_block::Block;
+
+    #[test]
+    fn test_car_roundtrip() {
+        let blocks = vec![
+            Block::new(1u64),
+            Block::new("plum"),
+            Block::new(vec![1u8, 2]),
+        ];
+        let roots = vec![blocks[0].cid().clone()];
+
+        let mut writer = CarWriter::new(Vec::new(), roots.clone()).unwrap();
+        for block in &blocks {
+            writer.write_block(block.cid(), block.data()).unwrap();
+        }
+        let car = writer.finish().unwrap();
+        // The header section: varint length, then {"roots": [...], "version": 1}.
+        assert_eq!(&car[1..8], &[0xa2, 0x65, b'r', b'o', b'o', b't', b's']);
+
+        let mut reader = CarReader::new(car.as_slice()).unwrap();
+        assert_eq!(reader.header(), &CarHeader::new(roots));
+        assert_eq!(reader.position(), u64::from(car[0]) + 1);
+        for block in &blocks {
+            let position = reader.position() as usize;
+            let (cid, data) = reader.next_block().unwrap().unwrap();
+            assert_eq!(
+                usize::from(car[position]),
+                cid.to_bytes().len() + data.len()
+            );
+            assert_eq!(&cid, block.cid());
+            assert_eq!(data.as_slice(), block.data());
+        }
+        assert!(reader.next_block().unwrap().is_none());
+        assert_eq!(reader.position(), car.len() as u64);
+
+        // Truncated block.
+        let mut reader = CarReader::new(&car[..car.len() - 1]).unwrap();
+        reader.next_block().unwrap();
+        reader.next_block().unwrap();
+        assert!(reader.next_block().is_err());
+    }
+
+    #[test]
+    fn test_non_canonical_section_length() {
+        let block = Block::new("plum");
+        let mut writer = CarWriter::new(Vec::new(), vec![block.cid().clone()]).unwrap();
+        writer.write_block(block.cid(), block.data()).unwrap();
+        let car = writer.finish().unwrap();
+        let header_len = usize::from(car[0]) + 1;
+        assert!(car[header_len] < 0x80);
+
+        // The same section with the over-long varint of its length.
+        let mut padded = car[..header_len].to_vec();
+        padded.extend_from_slice(&[car[header_len] | 0x80, 0x00]);
+        padded.extend_from_slice(&car[header_len + 1..]);
+        let mut reader = CarReader::new(padded.as_slice()).unwrap();
+        assert!(reader.next_block().is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The CARv2 format, a CARv1 data payload wrapped by a fixed header and followed by an
+//! optional index of the blocks.
+//!
+//! ```text
+//! car    = pragma header padding payload padding index?
+//! pragma = section(dag-cbor {"version": 2})
+//! header = characteristics(16) u64(data offset) u64(data size) u64(index offset)
+//! ```
+//!
+//! The integers of the header are little-endian, the index offset is zero if there's no index.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use cid::Cid;
+
+use super::{
+    invalid, parse_block, read_section, write_block_section, write_header, CarHeader, CarIndex,
+    CarReader, CAR_VERSION,
+};
+use crate::error::Result;
+
+/// The version of the CARv2 format.
+pub const CAR_V2_VERSION: u64 = 2;
+/// The pragma of a CARv2 file, i.e. the CARv1 header section of `{"version": 2}`.
+pub const CAR_V2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, b'v', b'e', b'r', b's', b'i', b'o', b'n', 0x02,
+];
+/// The length of the CARv2 header after the pragma.
+pub const CAR_V2_HEADER_LEN: usize = 40;
+
+// The characteristic bit of the index including the blocks of the identity CIDs.
+const FULLY_INDEXED: u8 = 0x80;
+
+/// The header of a CARv2 file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CarV2Header {
+    /// The characteristics of the file, a 128-bit bitfield.
+    pub characteristics: [u8; 16],
+    /// The offset of the CARv1 data payload from the beginning of the file.
+    pub data_offset: u64,
+    /// The length of the CARv1 data payload.
+    pub data_size: u64,
+    /// The offset of the index from the beginning of the file, zero if there's no index.
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    /// Return true if the index includes the blocks of the identity CIDs.
+    pub fn is_fully_indexed(&self) -> bool {
+        self.characteristics[0] & FULLY_INDEXED != 0
+    }
+
+    /// Encode the header into its fixed length bytes.
+    pub fn to_bytes(&self) -> [u8; CAR_V2_HEADER_LEN] {
+        let mut bytes = [0u8; CAR_V2_HEADER_LEN];
+        bytes[..16].copy_from_slice(&self.characteristics);
+        bytes[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        bytes[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        bytes[32..].copy_from_slice(&self.index_offset.to_le_bytes());
+        bytes
+    }
+
+    /// Decode the header from its fixed length bytes.
+    pub fn from_bytes(bytes: &[u8; CAR_V2_HEADER_LEN]) -> Self {
+        let u64_at = |offset: usize| {
+            let mut value = [0u8; 8];
+            value.copy_from_slice(&bytes[offset..offset + 8]);
+            u64::from_le_bytes(value)
+        };
+        let mut characteristics = [0u8; 16];
+        characteristics.copy_from_slice(&bytes[..16]);
+        Self {
+            characteristics,
+            data_offset: u64_at(16),
+            data_size: u64_at(24),
+            index_offset: u64_at(32),
+        }
+    }
+}
+
+/// The writer of a CARv2 file with the index of its blocks.
+///
+/// The blocks are indexed as they are written, the index and the header are written by
+/// `finish`, so the underlying writer must be seekable.
+pub struct CarV2Writer<W> {
+    writer: W,
+    index: CarIndex,
+    data_size: u64,
+}
+
+impl<W: Write + Seek> CarV2Writer<W> {
+    /// Create a new writer, write the pragma and the CARv1 header with the `roots`.
+    pub fn new(mut writer: W, roots: Vec<Cid>) -> Result<Self> {
+        writer.write_all(&CAR_V2_PRAGMA)?;
+        // The header is rewritten by `finish`.
+        writer.write_all(&[0u8; CAR_V2_HEADER_LEN])?;
+        let data_size = write_header(&mut writer, roots)?;
+        Ok(Self {
+            writer,
+            index: CarIndex::new(),
+            data_size,
+        })
+    }
+
+    /// Write and index the block of the `cid`.
+    pub fn write_block(&mut self, cid: &Cid, data: &[u8]) -> Result<()> {
+        self.index.insert(cid, self.data_size);
+        self.data_size += write_block_section(&mut self.writer, cid, data)?;
+        Ok(())
+    }
+
+    /// Write the index and the header, flush the underlying writer and return it.
+    pub fn finish(mut self) -> Result<W> {
+        let data_offset = (CAR_V2_PRAGMA.len() + CAR_V2_HEADER_LEN) as u64;
+        let header = CarV2Header {
+            characteristics: [0u8; 16],
+            data_offset,
+            data_size: self.data_size,
+            index_offset: data_offset + self.data_size,
+        };
+        self.index.write(&mut self.writer)?;
+        self.writer
+            .seek(SeekFrom::Start(CAR_V2_PRAGMA.len() as u64))?;
+        self.writer.write_all(&header.to_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The random access reader of the blocks of a CARv1 or CARv2 file.
+///
+/// The index of a CARv2 file is loaded on creation, the CARv1 files and the CARv2 files
+/// without a supported index are indexed by scanning their blocks once, so the blocks of an
+/// imported snapshot can be served without copying them into the blockstore.
+pub struct IndexedCarReader<R> {
+    reader: R,
+    header: CarHeader,
+    v2_header: Option<CarV2Header>,
+    data_offset: u64,
+    index: CarIndex,
+}
+
+impl<R: Read + Seek> IndexedCarReader<R> {
+    /// Create a new reader, read the headers and load or build the index.
+    pub fn new(mut reader: R) -> Result<Self> {
+        reader.seek(SeekFrom::Start(0))?;
+        let pragma = read_section(&mut reader)?.ok_or_else(|| invalid("missing header".into()))?;
+        let version = minicbor::decode::<CarHeader>(&pragma)?.version;
+        match version {
+            CAR_VERSION => {
+                let (header, index) = scan_blocks(&mut reader, 0, None)?;
+                Ok(Self {
+                    reader,
+                    header,
+                    v2_header: None,
+                    data_offset: 0,
+                    index,
+                })
+            }
+            CAR_V2_VERSION => {
+                let mut bytes = [0u8; CAR_V2_HEADER_LEN];
+                reader.read_exact(&mut bytes)?;
+                let v2_header = CarV2Header::from_bytes(&bytes);
+                let data_offset = v2_header.data_offset;
+                let (header, index) = match load_index(&mut reader, &v2_header) {
+                    Some(index) => {
+                        reader.seek(SeekFrom::Start(data_offset))?;
+                        (CarReader::new(&mut reader)?.header, index)
+                    }
+                    None => scan_blocks(&mut reader, data_offset, Some(v2_header.data_size))?,
+                };
+                Ok(Self {
+                    reader,
+                    header,
+                    v2_header: Some(v2_header),
+                    data_offset,
+                    index,
+                })
+            }
+            version => Err(invalid(format!("unsupported version {}", version))),
+        }
+    }
+
+    /// Return the header of the CARv1 data payload, which includes the roots.
+    pub fn header(&self) -> &CarHeader {
+        &self.header
+    }
+
+    /// Return the CARv2 header, `None` is returned for a CARv1 file.
+    pub fn v2_header(&self) -> Option<&CarV2Header> {
+        self.v2_header.as_ref()
+    }
+
+    /// Return the index of the blocks.
+    pub fn index(&self) -> &CarIndex {
+        &self.index
+    }
+
+    /// Return whether the block of the `cid` is in the file.
+    pub fn has(&self, cid: &Cid) -> bool {
+        self.index.get(cid).is_some()
+    }
+
+    /// Read the block of the `cid`, `None` is returned if the block is not in the file.
+    pub fn get(&mut self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        let offset = match self.index.get(cid) {
+            Some(offset) => offset,
+            None => return Ok(None),
+        };
+        self.reader
+            .seek(SeekFrom::Start(self.data_offset + offset))?;
+        let section = read_section(&mut self.reader)?
+            .ok_or_else(|| invalid(format!("missing block {} at offset {}", cid, offset)))?;
+        let (block_cid, data) = parse_block(section)?;
+        // The index is not trusted, the block must be the indexed one.
+        if block_cid.hash() != cid.hash() {
+            return Err(invalid(format!(
+                "index points block {} to the block {}",
+                cid, block_cid
+            )));
+        }
+        Ok(Some(data))
+    }
+}
+
+// Load the index of the CARv2 file, `None` is returned if there's no index or the index is in
+// an unsupported format, then the blocks are scanned instead.
+fn load_index<R: Read + Seek>(reader: &mut R, header: &CarV2Header) -> Option<CarIndex> {
+    if header.index_offset == 0 {
+        return None;
+    }
+    reader.seek(SeekFrom::Start(header.index_offset)).ok()?;
+    CarIndex::read(reader).ok()
+}
+
+// Index the blocks of the CARv1 data payload at `data_offset` of at most `data_size` bytes.
+fn scan_blocks<R: Read + Seek>(
+    reader: &mut R,
+    data_offset: u64,
+    data_size: Option<u64>,
+) -> Result<(CarHeader, CarIndex)> {
+    reader.seek(SeekFrom::Start(data_offset))?;
+    let payload = reader.take(data_size.unwrap_or(u64::max_value()));
+    let mut car = CarReader::new(payload)?;
+    let mut index = CarIndex::new();
+    loop {
+        let offset = car.position();
+        match car.next_block()? {
+            Some((cid, _)) => index.insert(&cid, offset),
+            None => break,
+        }
+    }
+    Ok((car.header, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    use ipfs_block::Block;
+
+    use crate::car::CarWriter;
+
+    fn blocks() -> Vec<Block> {
+        vec![
+            Block::new(1u64),
+            Block::new("plum"),
+            Block::new(vec![1u8, 2]),
+        ]
+    }
+
+    #[test]
+    fn test_car_v2_roundtrip() {
+        let blocks = blocks();
+        let roots = vec![blocks[0].cid().clone()];
+        let mut writer = CarV2Writer::new(Cursor::new(Vec::new()), roots.clone()).unwrap();
+        for block in &blocks {
+            writer.write_block(block.cid(), block.data()).unwrap();
+        }
+        let car = writer.finish().unwrap().into_inner();
+        assert_eq!(&car[..CAR_V2_PRAGMA.len()], &CAR_V2_PRAGMA);
+
+        let mut reader = IndexedCarReader::new(Cursor::new(car.clone())).unwrap();
+        let header = reader.v2_header().unwrap().clone();
+        assert!(!header.is_fully_indexed());
+        assert_eq!(header.data_offset, 51);
+        assert_eq!(header.index_offset, header.data_offset + header.data_size);
+        assert_eq!(reader.header(), &CarHeader::new(roots.clone()));
+        assert_eq!(reader.index().len(), blocks.len());
+        // Random access in the reverse order.
+        for block in blocks.iter().rev() {
+            let data = reader.get(block.cid()).unwrap().unwrap();
+            assert_eq!(data.as_slice(), block.data());
+        }
+        assert_eq!(reader.get(Block::new(2u64).cid()).unwrap(), None);
+
+        // The data payload is a CARv1 file.
+        let payload = &car[header.data_offset as usize..header.index_offset as usize];
+        let mut v1 = CarReader::new(payload).unwrap();
+        assert_eq!(v1.header(), &CarHeader::new(roots.clone()));
+        assert_eq!(&v1.next_block().unwrap().unwrap().0, blocks[0].cid());
+
+        // The file without the index is indexed by scanning the payload.
+        let mut no_index = car[..header.index_offset as usize].to_vec();
+        let no_index_header = CarV2Header {
+            index_offset: 0,
+            ..header.clone()
+        };
+        no_index[CAR_V2_PRAGMA.len()..header.data_offset as usize]
+            .copy_from_slice(&no_index_header.to_bytes());
+        let mut reader = IndexedCarReader::new(Cursor::new(no_index)).unwrap();
+        assert_eq!(reader.index().len(), blocks.len());
+        assert!(reader.has(blocks[1].cid()));
+        let data = reader.get(blocks[1].cid()).unwrap().unwrap();
+        assert_eq!(data.as_slice(), blocks[1].data());
+    }
+
+    #[test]
+    fn test_indexed_car_v1() {
+        let blocks = blocks();
+        let mut writer = CarWriter::new(Vec::new(), vec![blocks[0].cid().clone()]).unwrap();
+        for block in &blocks {
+            writer.write_block(block.cid(), block.data()).unwrap();
+        }
+        let car = writer.finish().unwrap();
+
+        let mut reader = IndexedCarReader::new(Cursor::new(car)).unwrap();
+        assert!(reader.v2_header().is_none());
+        for block in blocks.iter().rev() {
+            let data = reader.get(block.cid()).unwrap().unwrap();
+            assert_eq!(data.as_slice(), block.data());
+        }
+    }
+
+    #[test]
+    fn test_corrupted_index() {
+        let blocks = blocks();
+        let mut writer = CarV2Writer::new(Cursor::new(Vec::new()), vec![]).unwrap();
+        for block in &blocks {
+            writer.write_block(block.cid(), block.data()).unwrap();
+        }
+        let mut car = writer.finish().unwrap().into_inner();
+
+        // Swap the offsets of the entries, the blocks are checked against the index.
+        let entries_offset = car.len() - 3 * 40;
+        let first = car[entries_offset + 32..entries_offset + 40].to_vec();
+        let second = car[entries_offset + 72..entries_offset + 80].to_vec();
+        car[entries_offset + 32..entries_offset + 40].copy_from_slice(&second);
+        car[entries_offset + 72..entries_offset + 80].copy_from_slice(&first);
+        let mut reader = IndexedCarReader::new(Cursor::new(car)).unwrap();
+        assert!(blocks.iter().any(|block| reader.get(block.cid()).is_err()));
+    }
+}
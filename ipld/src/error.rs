@@ -9,10 +9,28 @@ pub enum IpldError {
     /// IO error.
     #[error("{0}")]
     Io(#[from] std::io::Error),
+    /// Datastore error.
+    #[error("{0}")]
+    DataStore(#[from] ipfs_datastore::DataStoreError),
     /// CBOR decode error.
     #[error("{0}")]
     CborDecode(#[from] minicbor::decode::Error),
     /// JSON Codec error.
     #[error("{0}")]
     JsonCodec(#[from] serde_json::Error),
+    /// Invalid AMT error.
+    #[error("invalid AMT: {0}")]
+    InvalidAmt(#[from] crate::amt::AmtError),
+    /// Invalid HAMT error.
+    #[error("invalid HAMT: {0}")]
+    InvalidHamt(String),
+    /// The HAMT is deeper than the max depth allowed by the bit width.
+    #[error("HAMT exceeds the max depth {0}")]
+    MaxDepth(u32),
+    /// Invalid CAR file error.
+    #[error("invalid CAR: {0}")]
+    InvalidCar(String),
+    /// Invalid path error.
+    #[error("invalid path: {0}")]
+    InvalidPath(String),
 }
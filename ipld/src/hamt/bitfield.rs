@@ -0,0 +1,142 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::fmt;
+
+use crate::error::{IpldError, Result};
+
+/// The max number of bytes of a bitfield, i.e. a node has at most `2^8` slots.
+const MAX_BITFIELD_BYTES: usize = 32;
+
+/// The bitfield of a HAMT node, the bit `i` is set if the slot `i` of the node holds a pointer.
+///
+/// The bitfield is encoded as the big-endian bytes of the unsigned integer without leading
+/// zeros, which is the same as the encoding of `big.Int` used by go-hamt-ipld,
+/// and an empty bitfield is encoded as an empty byte string.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Bitfield([u64; 4]);
+
+impl Bitfield {
+    /// Create an empty bitfield.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return true if the bit `index` is set.
+    pub fn test_bit(&self, index: u32) -> bool {
+        let (word, bit) = Self::position(index);
+        self.0[word] & (1 << bit) != 0
+    }
+
+    /// Set the bit `index`.
+    pub fn set_bit(&mut self, index: u32) {
+        let (word, bit) = Self::position(index);
+        self.0[word] |= 1 << bit;
+    }
+
+    /// Clear the bit `index`.
+    pub fn clear_bit(&mut self, index: u32) {
+        let (word, bit) = Self::position(index);
+        self.0[word] &= !(1 << bit);
+    }
+
+    /// Return the number of the set bits.
+    pub fn count_ones(&self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    /// Return the number of the set bits below the bit `index`,
+    /// i.e. the position of the pointer of the slot `index` in the pointers of the node.
+    pub fn rank(&self, index: u32) -> usize {
+        let (word, bit) = Self::position(index);
+        let below = self.0[..word]
+            .iter()
+            .map(|word| word.count_ones())
+            .sum::<u32>();
+        let mask = (1u64 << bit) - 1;
+        (below + (self.0[word] & mask).count_ones()) as usize
+    }
+
+    /// Return true if no bit is set.
+    pub fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    /// Return the big-endian bytes of the bitfield without leading zeros.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let bytes = self
+            .0
+            .iter()
+            .rev()
+            .flat_map(|word| word.to_be_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let start = bytes
+            .iter()
+            .position(|byte| *byte != 0)
+            .unwrap_or_else(|| bytes.len());
+        bytes[start..].to_vec()
+    }
+
+    /// Create a bitfield from the big-endian bytes, the leading zeros are allowed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() > MAX_BITFIELD_BYTES {
+            return Err(IpldError::InvalidHamt(format!(
+                "bitfield of {} bytes",
+                bytes.len()
+            )));
+        }
+        let mut bitfield = Self::new();
+        for (i, byte) in bytes.iter().rev().enumerate() {
+            bitfield.0[i / 8] |= u64::from(*byte) << ((i % 8) * 8);
+        }
+        Ok(bitfield)
+    }
+
+    fn position(index: u32) -> (usize, u32) {
+        assert!(index < 256, "bitfield index {} out of range", index);
+        ((index / 64) as usize, index % 64)
+    }
+}
+
+impl fmt::Debug for Bitfield {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bitfield(0x")?;
+        for byte in self.to_bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bitfield() {
+        let mut bitfield = Bitfield::new();
+        assert!(bitfield.is_empty());
+        assert_eq!(bitfield.to_bytes(), Vec::<u8>::new());
+
+        bitfield.set_bit(3);
+        assert_eq!(bitfield.to_bytes(), vec![0x08]);
+        bitfield.set_bit(8);
+        bitfield.set_bit(255);
+        assert!(bitfield.test_bit(255));
+        assert_eq!(bitfield.count_ones(), 3);
+        assert_eq!(bitfield.rank(3), 0);
+        assert_eq!(bitfield.rank(8), 1);
+        assert_eq!(bitfield.rank(200), 2);
+        assert_eq!(bitfield.rank(255), 2);
+
+        let bytes = bitfield.to_bytes();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(bytes[0], 0x80);
+        assert_eq!(&bytes[30..], &[0x01, 0x08]);
+        assert_eq!(Bitfield::from_bytes(&bytes).unwrap(), bitfield);
+
+        bitfield.clear_bit(255);
+        assert_eq!(bitfield.to_bytes(), vec![0x01, 0x08]);
+        assert_eq!(Bitfield::from_bytes(&[0, 0x01, 0x08]).unwrap(), bitfield);
+        assert!(Bitfield::from_bytes(&[0; 33]).is_err());
+    }
+}
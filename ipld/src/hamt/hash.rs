@@ -0,0 +1,68 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use super::stats::HASH_DIGEST_LEN;
+
+/// The hash algorithm of the HAMT keys, every `bit_width` bits of the digest select the slot
+/// of the key at each level of the trie.
+pub trait HashAlgorithm {
+    /// The multihash code of the algorithm, which is recorded in the root block.
+    const CODE: u64;
+
+    /// Return the digest of the `key`.
+    fn hash(key: &[u8]) -> [u8; HASH_DIGEST_LEN as usize];
+}
+
+/// The SHA2-256 hash, which is used by the HAMTs of the Filecoin state.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sha256;
+
+impl HashAlgorithm for Sha256 {
+    const CODE: u64 = 0x12;
+
+    fn hash(key: &[u8]) -> [u8; HASH_DIGEST_LEN as usize] {
+        plum_hashing::sha256(key)
+    }
+}
+
+/// The BLAKE2b-256 hash.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Blake2b256;
+
+impl HashAlgorithm for Blake2b256 {
+    const CODE: u64 = 0xb220;
+
+    fn hash(key: &[u8]) -> [u8; HASH_DIGEST_LEN as usize] {
+        plum_hashing::blake2b_256(key)
+    }
+}
+
+/// The identity hash, the digest is the key padded with zeros, so the layout of the trie is
+/// decided by the keys, e.g. for the test fixtures.
+///
+/// The keys longer than the digest are truncated, such keys may collide at the max depth.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Identity;
+
+impl HashAlgorithm for Identity {
+    const CODE: u64 = 0x00;
+
+    fn hash(key: &[u8]) -> [u8; HASH_DIGEST_LEN as usize] {
+        let mut digest = [0; HASH_DIGEST_LEN as usize];
+        let len = key.len().min(digest.len());
+        digest[..len].copy_from_slice(&key[..len]);
+        digest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_hash() {
+        let digest = Identity::hash(&[0xab, 0xcd]);
+        assert_eq!(&digest[..3], &[0xab, 0xcd, 0x00]);
+        assert_eq!(Identity::hash(&[0xff; 40]), [0xff; 32]);
+        assert_ne!(Sha256::hash(b"key"), Blake2b256::hash(b"key"));
+    }
+}
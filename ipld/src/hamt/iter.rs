@@ -0,0 +1,178 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::mem;
+
+use cid::Cid;
+use minicbor::decode;
+
+use super::node::{load_nodes, DirtyNode, KeyValuePair, Node, Pointer, Slot};
+use super::HamtVersion;
+use crate::error::{IpldError, Result};
+use crate::store::IpldStore;
+
+/// The iterator over the key/value pairs of a HAMT, in the order of the slots and then the
+/// keys of each bucket, i.e. the same order as `ForEach` of go-hamt-ipld.
+///
+/// The child nodes are loaded from the store only when their parent is visited, the children
+/// of a node are loaded at once by `BlockStore::get_many` when its first link is visited, so
+/// only the nodes along the current path and their children are kept in memory, while the
+/// dirty child nodes are visited in memory. The iteration ends after the first error.
+pub struct Iter<'a, BS, V: Clone> {
+    store: &'a BS,
+    version: HamtVersion,
+    max_depth: u32,
+    // The visited nodes along the current path.
+    stack: Vec<Frame<'a, V>>,
+    bucket: std::vec::IntoIter<KeyValuePair<V>>,
+}
+
+impl<'a, BS, V: Clone> Iter<'a, BS, V> {
+    pub(super) fn new(
+        store: &'a BS,
+        version: HamtVersion,
+        max_depth: u32,
+        root: &'a DirtyNode<V>,
+    ) -> Self {
+        Self {
+            store,
+            version,
+            max_depth,
+            stack: vec![Frame::new(Visited::Dirty(root))],
+            bucket: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl<'a, BS, V> Iterator for Iter<'a, BS, V>
+where
+    BS: IpldStore,
+    V: Clone + for<'b> decode::Decode<'b>,
+{
+    type Item = Result<(Vec<u8>, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(kv) = self.bucket.next() {
+                return Some(Ok((kv.key, kv.value)));
+            }
+
+            let depth = self.stack.len() as u32;
+            let frame = self.stack.last_mut()?;
+            let index = frame.index;
+            let next = match &mut frame.node {
+                // The root node and its dirty descendants are borrowed from the HAMT, their
+                // buckets are cloned.
+                Visited::Dirty(node) => {
+                    let node: &'a DirtyNode<V> = *node;
+                    node.slots.get(index).map(|slot| match slot {
+                        Slot::Stored(Pointer::Bucket(kvs)) => Next::Bucket(kvs.clone()),
+                        Slot::Stored(Pointer::Link(_)) => Next::Link,
+                        Slot::Dirty(child) => Next::Child(&**child),
+                    })
+                }
+                // The loaded nodes are owned, their buckets are moved out.
+                Visited::Loaded(node) => {
+                    node.pointers.get_mut(index).map(|pointer| match pointer {
+                        Pointer::Bucket(kvs) => Next::Bucket(mem::take(kvs)),
+                        Pointer::Link(_) => Next::Link,
+                    })
+                }
+            };
+            frame.index += 1;
+
+            match next {
+                // All the pointers of the node are visited.
+                None => {
+                    self.stack.pop();
+                }
+                Some(Next::Bucket(kvs)) => self.bucket = kvs.into_iter(),
+                Some(Next::Child(child)) => self.stack.push(Frame::new(Visited::Dirty(child))),
+                Some(Next::Link) => {
+                    let child = if depth >= self.max_depth {
+                        Err(IpldError::MaxDepth(self.max_depth))
+                    } else {
+                        frame.load_child(self.store, self.version, index)
+                    };
+                    match child {
+                        Ok(child) => self.stack.push(Frame::new(Visited::Loaded(child))),
+                        Err(err) => {
+                            self.stack.clear();
+                            return Some(Err(err));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+// A visited node along the current path of the iterator.
+struct Frame<'a, V> {
+    node: Visited<'a, V>,
+    // The index of the next pointer.
+    index: usize,
+    // The child nodes of the remaining links, loaded when the first link is visited.
+    children: Option<std::vec::IntoIter<Node<V>>>,
+}
+
+impl<'a, V> Frame<'a, V>
+where
+    V: Clone + for<'b> decode::Decode<'b>,
+{
+    fn new(node: Visited<'a, V>) -> Self {
+        Self {
+            node,
+            index: 0,
+            children: None,
+        }
+    }
+
+    // Return the child node of the link at `index`, the children of the link and all the
+    // following links are loaded at once.
+    fn load_child<BS: IpldStore>(
+        &mut self,
+        store: &BS,
+        version: HamtVersion,
+        index: usize,
+    ) -> Result<Node<V>> {
+        if self.children.is_none() {
+            let links = match &self.node {
+                Visited::Dirty(node) => node.slots[index..]
+                    .iter()
+                    .filter_map(|slot| match slot {
+                        Slot::Stored(pointer) => link(pointer),
+                        Slot::Dirty(_) => None,
+                    })
+                    .collect::<Vec<_>>(),
+                Visited::Loaded(node) => node.pointers[index..].iter().filter_map(link).collect(),
+            };
+            self.children = Some(load_nodes(store, version, &links)?.into_iter());
+        }
+        Ok(self
+            .children
+            .as_mut()
+            .and_then(Iterator::next)
+            .expect("the children of all the remaining links are loaded; qed"))
+    }
+}
+
+// A node visited by the iterator, either the root node or a dirty node borrowed from the
+// HAMT, or a node loaded from the store.
+enum Visited<'a, V> {
+    Dirty(&'a DirtyNode<V>),
+    Loaded(Node<V>),
+}
+
+// The next pointer visited by the iterator.
+enum Next<'a, V> {
+    Bucket(Vec<KeyValuePair<V>>),
+    Child(&'a DirtyNode<V>),
+    Link,
+}
+
+fn link<V>(pointer: &Pointer<V>) -> Option<Cid> {
+    match pointer {
+        Pointer::Link(cid) => Some(cid.clone()),
+        Pointer::Bucket(_) => None,
+    }
+}
@@ -0,0 +1,845 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::ControlFlow;
+
+use cid::Cid;
+use ipfs_block::Block;
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use super::hash::{HashAlgorithm, Sha256};
+use super::iter::Iter;
+use super::node::{load_node, node_block, DirtyNode, KeyValuePair, Node, Pointer, Slot};
+use super::stats::{max_depth, HASH_DIGEST_LEN};
+use super::{HamtConfig, HamtVersion, DEFAULT_HAMT_BIT_WIDTH, MAX_HAMT_BIT_WIDTH};
+use crate::error::{IpldError, Result};
+use crate::store::IpldStore;
+use crate::value::Value;
+
+/// The HAMT backed by the `store`, which is bit-compatible with go-hamt-ipld, the keys are
+/// hashed by the `H` algorithm.
+///
+/// The root node is kept in memory, a mutation loads the child nodes along the path of the key
+/// and keeps them in memory as the dirty nodes, and `flush` writes the dirty nodes and the root
+/// node into the `store` in one batch and returns the CID of the root node, so a node modified
+/// by many mutations is written only once. The compaction rules of the module docs are kept by
+/// all the mutations, so the same set of the key/value pairs always results in the same root CID.
+///
+/// `flush` and `load` use the bare root node of go-hamt-ipld, while `flush_root` and
+/// `load_root` use the root block of the HAMT spec, which records the hash algorithm and the
+/// bucket size along with the root node.
+pub struct Hamt<BS, V = Value, H = Sha256> {
+    store: BS,
+    version: HamtVersion,
+    config: HamtConfig,
+    root: DirtyNode<V>,
+    _hash: PhantomData<H>,
+}
+
+impl<BS, V> Hamt<BS, V>
+where
+    BS: IpldStore,
+    V: Clone + encode::Encode + for<'b> decode::Decode<'b>,
+{
+    /// Create an empty HAMT of the default version and config, the keys are hashed by SHA-256.
+    pub fn new(store: BS) -> Self {
+        Self::new_with_config(store, HamtConfig::default())
+            .expect("the default config is valid; qed")
+    }
+
+    /// Load the HAMT of the default config with the `root` in the layout of the `version`,
+    /// the keys are hashed by SHA-256 and the bit width is inferred from the root node,
+    /// see `load_with_config`.
+    pub fn load(store: BS, version: HamtVersion, root: &Cid) -> Result<Self> {
+        Self::load_with_config(store, version, HamtConfig::default(), root)
+    }
+}
+
+impl<BS, V, H> Hamt<BS, V, H>
+where
+    BS: IpldStore,
+    V: Clone + encode::Encode + for<'b> decode::Decode<'b>,
+    H: HashAlgorithm,
+{
+    /// Create an empty HAMT of the default version and the `config`.
+    pub fn new_with_config(store: BS, config: HamtConfig) -> Result<Self> {
+        config.validate()?;
+        Ok(Self {
+            store,
+            version: HamtVersion::default(),
+            config,
+            root: DirtyNode::default(),
+            _hash: PhantomData,
+        })
+    }
+
+    /// Use the node layout of the `version`.
+    pub fn with_version(mut self, version: HamtVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Load the HAMT of the `config` with the `root` in the layout of the `version`.
+    ///
+    /// The bit width of the `config` is widened if the `map` of the root node is longer than
+    /// the bit width allows, see `infer_bit_width`, so the HAMTs of the wider bit width are
+    /// read correctly regardless of the configured one.
+    pub fn load_with_config(
+        store: BS,
+        version: HamtVersion,
+        mut config: HamtConfig,
+        root: &Cid,
+    ) -> Result<Self> {
+        config.validate()?;
+        let data = load_block(&store, root)?;
+        let (root, map_len) = Node::decode_with_map_len(version, &mut Decoder::new(&data))?;
+        config.bit_width = infer_bit_width(map_len, config.bit_width)?;
+        Ok(Self {
+            store,
+            version,
+            config,
+            root: root.into(),
+            _hash: PhantomData,
+        })
+    }
+
+    /// Load the HAMT with the root block of the HAMT spec in the layout of the `version`,
+    /// the bucket size is read from the root block and the bit width is inferred.
+    ///
+    /// An error is returned if the hash algorithm of the root block is not `H`.
+    pub fn load_root(store: BS, version: HamtVersion, root: &Cid) -> Result<Self> {
+        let data = load_block(&store, root)?;
+        let mut d = Decoder::new(&data);
+        if d.map()? != Some(3) {
+            return Err(IpldError::InvalidHamt(
+                "root must be a map of 3 entries".into(),
+            ));
+        }
+        let mut hash_alg = None;
+        let mut bucket_size = None;
+        let mut node = None;
+        for _ in 0..3 {
+            match d.str()? {
+                ROOT_HAMT_KEY => node = Some(Node::decode_with_map_len(version, &mut d)?),
+                ROOT_HASH_ALG_KEY => hash_alg = Some(d.u64()?),
+                ROOT_BUCKET_SIZE_KEY => bucket_size = Some(d.u64()?),
+                key => {
+                    return Err(IpldError::InvalidHamt(format!(
+                        "unknown root key {:?}",
+                        key
+                    )))
+                }
+            }
+        }
+        let (hash_alg, bucket_size, (root, map_len)) = match (hash_alg, bucket_size, node) {
+            (Some(hash_alg), Some(bucket_size), Some(node)) => (hash_alg, bucket_size, node),
+            _ => return Err(IpldError::InvalidHamt("incomplete root".into())),
+        };
+        if hash_alg != H::CODE {
+            return Err(IpldError::InvalidHamt(format!(
+                "hash algorithm {:#x}, expected: {:#x}",
+                hash_alg,
+                H::CODE
+            )));
+        }
+        let bit_width = infer_bit_width(map_len, DEFAULT_HAMT_BIT_WIDTH)?;
+        let config = HamtConfig::new(bit_width, bucket_size as usize);
+        config.validate()?;
+        Ok(Self {
+            store,
+            version,
+            config,
+            root: root.into(),
+            _hash: PhantomData,
+        })
+    }
+
+    /// Return the store of the HAMT.
+    pub fn store(&self) -> &BS {
+        &self.store
+    }
+
+    /// Return the config of the HAMT, with the bit width inferred on load.
+    pub fn config(&self) -> HamtConfig {
+        self.config
+    }
+
+    /// Return true if the HAMT has no key.
+    pub fn is_empty(&self) -> bool {
+        self.root.is_empty()
+    }
+
+    /// Return the value of the `key`.
+    pub fn get(&self, key: &[u8]) -> Result<Option<V>> {
+        let digest = H::hash(key);
+        let bit_width = self.config.bit_width;
+        let max_depth = max_depth(bit_width);
+        // The dirty nodes are walked in memory down to the first stored pointer.
+        let mut node = &self.root;
+        let mut depth = 0;
+        let mut pointer = loop {
+            match node.slot(slot_index(&digest, depth, bit_width)) {
+                None => return Ok(None),
+                Some(Slot::Stored(pointer)) => break pointer,
+                Some(Slot::Dirty(child)) => {
+                    depth += 1;
+                    node = child;
+                }
+            }
+        };
+        let mut loaded;
+        loop {
+            let cid = match pointer {
+                Pointer::Bucket(kvs) => {
+                    return Ok(kvs
+                        .iter()
+                        .find(|kv| kv.key == key)
+                        .map(|kv| kv.value.clone()));
+                }
+                Pointer::Link(cid) => cid,
+            };
+            depth += 1;
+            if depth >= max_depth {
+                return Err(IpldError::MaxDepth(max_depth));
+            }
+            loaded = load_node(&self.store, self.version, cid)?;
+            pointer = match loaded.pointer(slot_index(&digest, depth, bit_width)) {
+                None => return Ok(None),
+                Some(pointer) => pointer,
+            };
+        }
+    }
+
+    /// Return the iterator over the key/value pairs, which loads the child nodes lazily.
+    pub fn iter(&self) -> Iter<'_, BS, V> {
+        Iter::new(
+            &self.store,
+            self.version,
+            max_depth(self.config.bit_width),
+            &self.root,
+        )
+    }
+
+    /// Return the iterator over the keys, see `iter`.
+    pub fn keys(&self) -> impl Iterator<Item = Result<Vec<u8>>> + '_ {
+        self.iter().map(|item| item.map(|(key, _)| key))
+    }
+
+    /// Return the iterator over the values, see `iter`.
+    pub fn values(&self) -> impl Iterator<Item = Result<V>> + '_ {
+        self.iter().map(|item| item.map(|(_, value)| value))
+    }
+
+    /// Call `f` with each key/value pair in the order of `iter` until `f` breaks, return the
+    /// break value of `f`, or `ControlFlow::Continue` if all the pairs are visited.
+    pub fn for_each<B, F>(&self, mut f: F) -> Result<ControlFlow<B>>
+    where
+        F: FnMut(&[u8], &V) -> ControlFlow<B>,
+    {
+        for item in self.iter() {
+            let (key, value) = item?;
+            if let ControlFlow::Break(b) = f(&key, &value) {
+                return Ok(ControlFlow::Break(b));
+            }
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Set the `value` of the `key`, return the previous value of the `key`.
+    pub fn set<K: Into<Vec<u8>>>(&mut self, key: K, value: V) -> Result<Option<V>> {
+        let kv = KeyValuePair {
+            key: key.into(),
+            value,
+        };
+        let digest = H::hash(&kv.key);
+        let layout = Layout {
+            store: &self.store,
+            version: self.version,
+            config: self.config,
+            hash: H::hash,
+        };
+        layout.set(&mut self.root, &digest, 0, kv)
+    }
+
+    /// Delete the `key`, return the value of the deleted `key`.
+    pub fn delete(&mut self, key: &[u8]) -> Result<Option<V>> {
+        let digest = H::hash(key);
+        let layout = Layout {
+            store: &self.store,
+            version: self.version,
+            config: self.config,
+            hash: H::hash,
+        };
+        layout.delete(&mut self.root, &digest, 0, key)
+    }
+
+    /// Write the dirty nodes and the root node into the store in one batch, return the CID of
+    /// the root node.
+    pub fn flush(&mut self) -> Result<Cid> {
+        let version = self.version;
+        self.flush_with(|root| node_block(version, root))
+    }
+
+    /// Write the dirty nodes and the root block of the HAMT spec into the store in one batch,
+    /// return the CID of the root block, which records the multihash code of `H` and the
+    /// bucket size along with the root node.
+    pub fn flush_root(&mut self) -> Result<Cid> {
+        let version = self.version;
+        let bucket_size = self.config.bucket_size;
+        self.flush_with(|root| {
+            Block::new(RootBlock {
+                hash_alg: H::CODE,
+                bucket_size,
+                version,
+                node: root,
+            })
+        })
+    }
+
+    // Replace the dirty nodes with the links to them, and write the blocks of the dirty nodes
+    // along with the root block built by `root_block` in one batch.
+    fn flush_with<F>(&mut self, root_block: F) -> Result<Cid>
+    where
+        F: FnOnce(&Node<V>) -> Block,
+    {
+        let mut blocks = vec![];
+        let root = mem::take(&mut self.root).flush(self.version, &mut blocks);
+        let block = root_block(&root);
+        self.root = root.into();
+
+        let cid = block.cid().clone();
+        blocks.push(block);
+        ipfs_blockstore::BlockStore::put_many(&self.store, &blocks)?;
+        Ok(cid)
+    }
+}
+
+// The keys of the root block of the HAMT spec, in the canonical order of DAG-CBOR.
+const ROOT_HAMT_KEY: &str = "hamt";
+const ROOT_HASH_ALG_KEY: &str = "hashAlg";
+const ROOT_BUCKET_SIZE_KEY: &str = "bucketSize";
+
+struct RootBlock<'a, V> {
+    hash_alg: u64,
+    bucket_size: usize,
+    version: HamtVersion,
+    node: &'a Node<V>,
+}
+
+// Implement CBOR serialization for RootBlock.
+impl<'a, V: encode::Encode> encode::Encode for RootBlock<'a, V> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.map(3)?.str(ROOT_HAMT_KEY)?;
+        self.node.encode_with(self.version, e)?;
+        e.str(ROOT_HASH_ALG_KEY)?
+            .u64(self.hash_alg)?
+            .str(ROOT_BUCKET_SIZE_KEY)?
+            .u64(self.bucket_size as u64)?
+            .ok()
+    }
+}
+
+fn load_block<BS: IpldStore>(store: &BS, cid: &Cid) -> Result<Vec<u8>> {
+    match ipfs_blockstore::BlockStore::get(store, cid)? {
+        Some(block) => Ok(block.data().to_vec()),
+        None => Err(IpldError::InvalidHamt(format!("missing node {}", cid))),
+    }
+}
+
+// The store and the parameters of a HAMT, borrowed apart from the root node being modified.
+struct Layout<'a, BS> {
+    store: &'a BS,
+    version: HamtVersion,
+    config: HamtConfig,
+    hash: fn(&[u8]) -> [u8; HASH_DIGEST_LEN as usize],
+}
+
+impl<'a, BS: IpldStore> Layout<'a, BS> {
+    fn set<V>(
+        &self,
+        node: &mut DirtyNode<V>,
+        digest: &[u8],
+        depth: u32,
+        kv: KeyValuePair<V>,
+    ) -> Result<Option<V>>
+    where
+        V: Clone + encode::Encode + for<'b> decode::Decode<'b>,
+    {
+        let index = self.slot_index(digest, depth)?;
+        let rank = node.bitfield.rank(index);
+        if !node.bitfield.test_bit(index) {
+            node.bitfield.set_bit(index);
+            node.slots
+                .insert(rank, Slot::Stored(Pointer::Bucket(vec![kv])));
+            return Ok(None);
+        }
+
+        let (child, old) = match &mut node.slots[rank] {
+            Slot::Dirty(child) => return self.set(child, digest, depth + 1, kv),
+            Slot::Stored(Pointer::Bucket(kvs)) => {
+                match kvs.binary_search_by(|probe| probe.key.cmp(&kv.key)) {
+                    Ok(pos) => return Ok(Some(mem::replace(&mut kvs[pos].value, kv.value))),
+                    Err(pos) if kvs.len() < self.config.bucket_size => {
+                        kvs.insert(pos, kv);
+                        return Ok(None);
+                    }
+                    Err(_) => {
+                        // The bucket is full, push the pairs of the bucket and the new pair down
+                        // into a new child node. The bucket is only replaced after all the pairs
+                        // are pushed down, so it's kept intact if any of them fails.
+                        let mut child = DirtyNode::default();
+                        for kv in kvs.iter().cloned().chain(Some(kv)) {
+                            self.set(&mut child, &(self.hash)(&kv.key), depth + 1, kv)?;
+                        }
+                        (child, None)
+                    }
+                }
+            }
+            Slot::Stored(Pointer::Link(cid)) => {
+                let mut child = DirtyNode::from(load_node(self.store, self.version, cid)?);
+                let old = self.set(&mut child, digest, depth + 1, kv)?;
+                (child, old)
+            }
+        };
+        node.slots[rank] = Slot::Dirty(Box::new(child));
+        Ok(old)
+    }
+
+    fn delete<V>(
+        &self,
+        node: &mut DirtyNode<V>,
+        digest: &[u8],
+        depth: u32,
+        key: &[u8],
+    ) -> Result<Option<V>>
+    where
+        V: Clone + encode::Encode + for<'b> decode::Decode<'b>,
+    {
+        let index = self.slot_index(digest, depth)?;
+        if !node.bitfield.test_bit(index) {
+            return Ok(None);
+        }
+        let rank = node.bitfield.rank(index);
+
+        let old = match &mut node.slots[rank] {
+            Slot::Stored(Pointer::Bucket(kvs)) => {
+                let pos = match kvs.binary_search_by(|probe| probe.key.as_slice().cmp(key)) {
+                    Ok(pos) => pos,
+                    Err(_) => return Ok(None),
+                };
+                let old = kvs.remove(pos).value;
+                if kvs.is_empty() {
+                    node.slots.remove(rank);
+                    node.bitfield.clear_bit(index);
+                }
+                return Ok(Some(old));
+            }
+            Slot::Stored(Pointer::Link(cid)) => {
+                let mut child = DirtyNode::from(load_node(self.store, self.version, cid)?);
+                match self.delete(&mut child, digest, depth + 1, key)? {
+                    Some(old) => {
+                        node.slots[rank] = Slot::Dirty(Box::new(child));
+                        old
+                    }
+                    // Nothing is deleted, the child is not marked dirty.
+                    None => return Ok(None),
+                }
+            }
+            Slot::Dirty(child) => match self.delete(child, digest, depth + 1, key)? {
+                Some(old) => old,
+                None => return Ok(None),
+            },
+        };
+        if let Slot::Dirty(child) = &node.slots[rank] {
+            if let Some(kvs) = collapse(child, self.config.bucket_size)? {
+                node.slots[rank] = Slot::Stored(Pointer::Bucket(kvs));
+            }
+        }
+        Ok(Some(old))
+    }
+
+    fn slot_index(&self, digest: &[u8], depth: u32) -> Result<u32> {
+        let max_depth = max_depth(self.config.bit_width);
+        if depth >= max_depth {
+            return Err(IpldError::MaxDepth(max_depth));
+        }
+        Ok(slot_index(digest, depth, self.config.bit_width))
+    }
+}
+
+// Return the pairs of the `child` if they fit into one bucket and the `child` has no link,
+// which replace the link to the `child` after a deletion, otherwise the `child` is kept.
+fn collapse<V: Clone>(
+    child: &DirtyNode<V>,
+    bucket_size: usize,
+) -> Result<Option<Vec<KeyValuePair<V>>>> {
+    if child.is_empty() {
+        return Err(IpldError::InvalidHamt("empty child node".into()));
+    }
+    let mut kvs = Vec::new();
+    for slot in &child.slots {
+        match slot {
+            Slot::Stored(Pointer::Bucket(bucket)) if kvs.len() + bucket.len() <= bucket_size => {
+                kvs.extend(bucket.iter().cloned())
+            }
+            _ => return Ok(None),
+        }
+    }
+    kvs.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(Some(kvs))
+}
+
+/// Return the bit width of the HAMT whose root node has the `map` of `map_len` bytes.
+///
+/// The HAMT spec encodes `map` in `2^bit_width / 8` bytes, while go-hamt-ipld trims the
+/// leading zeros of it, so the length only tells the least bit width holding all the bits of
+/// `map`. The `bit_width` is returned unless `map` is longer than it allows, a narrower bit
+/// width can't be told apart from a sparse root and must be configured.
+pub fn infer_bit_width(map_len: usize, bit_width: u32) -> Result<u32> {
+    let max_len = (1usize << MAX_HAMT_BIT_WIDTH) / 8;
+    if map_len > max_len {
+        return Err(IpldError::InvalidHamt(format!(
+            "bitfield of {} bytes",
+            map_len
+        )));
+    }
+    // The least bit width of `2^width >= map_len * 8`.
+    let least = (map_len * 8).next_power_of_two().trailing_zeros();
+    Ok(bit_width.max(least))
+}
+
+/// Return the slot selected by the `digest` at the `depth`, i.e. the `depth`-th chunk of
+/// `bit_width` bits of the `digest`, starting from the most significant bit.
+///
+/// The `digest` must have enough bits, see `max_depth`.
+pub fn slot_index(digest: &[u8], depth: u32, bit_width: u32) -> u32 {
+    let start = depth * bit_width;
+    (start..start + bit_width).fold(0, |index, bit| {
+        let byte = digest[(bit / 8) as usize];
+        (index << 1) | u32::from((byte >> (7 - bit % 8)) & 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use ipfs_datastore::{Delay, DelayDataStore, MapDataStore, SyncDataStore};
+
+    use crate::hamt::{hamt_stats, Blake2b256, Identity};
+    use crate::test_utils::new_store;
+
+    fn key(i: u64) -> Vec<u8> {
+        format!("key{}", i).into_bytes()
+    }
+
+    #[test]
+    fn test_slot_index() {
+        let digest = [0b1010_1100, 0b0101_0000, 0xff];
+        assert_eq!(slot_index(&digest, 0, 5), 0b10101);
+        assert_eq!(slot_index(&digest, 1, 5), 0b10001);
+        assert_eq!(slot_index(&digest, 1, 8), 0x50);
+        assert_eq!(slot_index(&digest, 2, 8), 0xff);
+    }
+
+    #[test]
+    fn test_infer_bit_width() {
+        assert_eq!(infer_bit_width(0, 5).unwrap(), 5);
+        assert_eq!(infer_bit_width(1, 5).unwrap(), 5);
+        assert_eq!(infer_bit_width(4, 5).unwrap(), 5);
+        assert_eq!(infer_bit_width(5, 5).unwrap(), 6);
+        assert_eq!(infer_bit_width(32, 5).unwrap(), 8);
+        assert_eq!(infer_bit_width(2, 8).unwrap(), 8);
+        assert!(infer_bit_width(33, 5).is_err());
+    }
+
+    #[test]
+    fn test_set_get_delete() {
+        let mut hamt = Hamt::<_, u64>::new(new_store());
+        assert!(hamt.is_empty());
+        assert_eq!(hamt.set("a", 1).unwrap(), None);
+        assert_eq!(hamt.set("a", 2).unwrap(), Some(1));
+        assert_eq!(hamt.get(b"a").unwrap(), Some(2));
+        assert_eq!(hamt.get(b"b").unwrap(), None);
+        assert_eq!(hamt.delete(b"b").unwrap(), None);
+        assert_eq!(hamt.delete(b"a").unwrap(), Some(2));
+        assert_eq!(hamt.delete(b"a").unwrap(), None);
+        assert!(hamt.is_empty());
+        // The same as the empty map of the Filecoin actors.
+        assert_eq!(
+            hamt.flush().unwrap().to_string(),
+            "bafy2bzaceamp42wmmgr2g2ymg46euououzfyck7szknvfacqscohrvaikwfay"
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let store = new_store();
+        assert!(Hamt::<_, u64>::new_with_config(store.clone(), HamtConfig::new(9, 3)).is_err());
+
+        let config = HamtConfig::new(8, 1);
+        let mut hamt = Hamt::<_, u64>::new_with_config(store.clone(), config).unwrap();
+        let mut default = Hamt::new(store.clone());
+        for i in 0..500 {
+            hamt.set(key(i), i).unwrap();
+            default.set(key(i), i).unwrap();
+        }
+        let root = hamt.flush().unwrap();
+        assert_ne!(root, default.flush().unwrap());
+
+        // The bit width is inferred from the root node of 256 slots, the bucket size is
+        // configured.
+        let loaded = Hamt::<_, u64>::load(store.clone(), HamtVersion::V3, &root).unwrap();
+        assert_eq!(loaded.config().bit_width, 8);
+        for i in 0..500 {
+            assert_eq!(loaded.get(&key(i)).unwrap(), Some(i));
+        }
+        let mut loaded =
+            Hamt::<_, u64>::load_with_config(store, HamtVersion::V3, config, &root).unwrap();
+        assert_eq!(loaded.config(), config);
+        assert_eq!(loaded.delete(&key(0)).unwrap(), Some(0));
+        loaded.set(key(0), 0).unwrap();
+        assert_eq!(loaded.flush().unwrap(), root);
+    }
+
+    #[test]
+    fn test_hash_algorithm() {
+        let store = new_store();
+        let config = HamtConfig::new(5, 1);
+        let mut hamt = Hamt::<_, u64, Identity>::new_with_config(store.clone(), config).unwrap();
+        // The first 5 bits select the slot of the root node: 0b00001 and 0b11111.
+        hamt.set(vec![0x08], 1).unwrap();
+        hamt.set(vec![0xff], 2).unwrap();
+        assert!(hamt.root.bitfield.test_bit(1));
+        assert!(hamt.root.bitfield.test_bit(31));
+        // The keys sharing the first 5 bits are pushed down into a child node.
+        hamt.set(vec![0x09], 3).unwrap();
+        assert_eq!(hamt.root.bitfield.count_ones(), 2);
+        assert!(matches!(hamt.root.slot(1), Some(Slot::Dirty(_))));
+        assert_eq!(hamt.get(&[0x09]).unwrap(), Some(3));
+        hamt.flush().unwrap();
+        assert!(matches!(
+            hamt.root.slot(1),
+            Some(Slot::Stored(Pointer::Link(_)))
+        ));
+
+        let mut sha256 = Hamt::<_, u64>::new(store.clone());
+        let mut blake2b =
+            Hamt::<_, u64, Blake2b256>::new_with_config(store.clone(), HamtConfig::default())
+                .unwrap();
+        for i in 0..100 {
+            sha256.set(key(i), i).unwrap();
+            blake2b.set(key(i), i).unwrap();
+        }
+        assert_ne!(sha256.flush().unwrap(), blake2b.flush().unwrap());
+
+        // The root block records the hash algorithm and the bucket size.
+        let root = blake2b.flush_root().unwrap();
+        let data = load_block(&store, &root).unwrap();
+        assert_eq!(&data[..5], &[0xa3, 0x64, b'h', b'a', b'm']);
+        let loaded =
+            Hamt::<_, u64, Blake2b256>::load_root(store.clone(), HamtVersion::V3, &root).unwrap();
+        assert_eq!(loaded.config(), HamtConfig::default());
+        for i in 0..100 {
+            assert_eq!(loaded.get(&key(i)).unwrap(), Some(i));
+        }
+        assert!(Hamt::<_, u64>::load_root(store, HamtVersion::V3, &root).is_err());
+    }
+
+    #[test]
+    fn test_set_max_depth() {
+        let store = new_store();
+        let config = HamtConfig::new(8, 1);
+        let mut hamt = Hamt::<_, u64, Identity>::new_with_config(store.clone(), config).unwrap();
+        let mut expected = Hamt::<_, u64, Identity>::new_with_config(store, config).unwrap();
+        // The keys sharing the whole digest can't be split into the buckets of one pair.
+        let key = |suffix: u8| {
+            let mut key = vec![0x01; HASH_DIGEST_LEN as usize];
+            key.push(suffix);
+            key
+        };
+        hamt.set(key(1), 1).unwrap();
+        hamt.set(vec![0x02], 2).unwrap();
+        expected.set(key(1), 1).unwrap();
+        expected.set(vec![0x02], 2).unwrap();
+        assert!(matches!(hamt.set(key(2), 3), Err(IpldError::MaxDepth(32))));
+
+        // The full bucket is kept intact.
+        assert_eq!(hamt.get(&key(1)).unwrap(), Some(1));
+        assert_eq!(hamt.get(&key(2)).unwrap(), None);
+        assert_eq!(hamt.get(&[0x02]).unwrap(), Some(2));
+        assert_eq!(hamt.flush().unwrap(), expected.flush().unwrap());
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut hamt = Hamt::<_, u64>::new(new_store());
+        assert!(hamt.iter().next().is_none());
+        for i in 0..500 {
+            hamt.set(key(i), i).unwrap();
+        }
+        // Load the HAMT, so that the child nodes are loaded from the store.
+        let root = hamt.flush().unwrap();
+        let hamt = Hamt::<_, u64>::load(hamt.store().clone(), HamtVersion::V3, &root).unwrap();
+
+        let mut pairs = hamt.iter().collect::<Result<Vec<_>>>().unwrap();
+        pairs.sort();
+        let mut expected = (0..500).map(|i| (key(i), i)).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(pairs, expected);
+        assert_eq!(hamt.keys().count(), 500);
+        assert_eq!(
+            hamt.values().map(|v| v.unwrap()).sum::<u64>(),
+            499 * 500 / 2
+        );
+
+        let mut visited = 0;
+        let flow = hamt
+            .for_each(|_, value| {
+                visited += 1;
+                if visited == 10 {
+                    ControlFlow::Break(*value)
+                } else {
+                    ControlFlow::Continue(())
+                }
+            })
+            .unwrap();
+        assert_eq!(visited, 10);
+        assert_eq!(
+            flow,
+            ControlFlow::Break(hamt.values().nth(9).unwrap().unwrap())
+        );
+        assert_eq!(
+            hamt.for_each(|_, _| ControlFlow::<()>::Continue(()))
+                .unwrap(),
+            ControlFlow::Continue(())
+        );
+
+        // The iteration ends at the error of the missing child nodes.
+        let store = new_store();
+        let block = ipfs_blockstore::BlockStore::get(hamt.store(), &root)
+            .unwrap()
+            .unwrap();
+        ipfs_blockstore::BlockStore::put(&store, block).unwrap();
+        let hamt = Hamt::<_, u64>::load(store, HamtVersion::V3, &root).unwrap();
+        let mut iter = hamt.iter().skip_while(|item| item.is_ok());
+        assert!(iter.next().unwrap().is_err());
+        assert!(iter.next().is_none());
+    }
+
+    // The delay counting the operations of the store.
+    #[derive(Clone, Default)]
+    struct CountingDelay(Arc<AtomicUsize>);
+
+    impl Delay for CountingDelay {
+        fn wait(&self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn test_dirty_flush() {
+        let delay = CountingDelay::default();
+        let store = DelayDataStore::new(delay.clone(), SyncDataStore::new(MapDataStore::new()));
+        let ops = || delay.0.load(Ordering::SeqCst);
+
+        // The mutations of a new HAMT never touch the store before flushing.
+        let mut hamt = Hamt::<_, u64>::new(store.clone());
+        for i in 0..1000 {
+            hamt.set(key(i), i).unwrap();
+        }
+        for i in 500..1000 {
+            assert_eq!(hamt.delete(&key(i)).unwrap(), Some(i));
+        }
+        for i in 0..500 {
+            assert_eq!(hamt.get(&key(i)).unwrap(), Some(i));
+        }
+        assert_eq!(hamt.iter().count(), 500);
+        assert_eq!(ops(), 0);
+
+        let root = hamt.flush().unwrap();
+        assert!(ops() > 0);
+        let mut expected = Hamt::new(new_store());
+        for i in 0..500 {
+            expected.set(key(i), i).unwrap();
+        }
+        assert_eq!(expected.flush().unwrap(), root);
+        assert!(
+            hamt_stats(&store, HamtVersion::V3, DEFAULT_HAMT_BIT_WIDTH, &root)
+                .unwrap()
+                .depth
+                > 0
+        );
+
+        // The loaded nodes are modified in memory as well, a failed deletion doesn't mark the
+        // child node dirty.
+        let mut loaded = Hamt::<_, u64>::load(store, HamtVersion::V3, &root).unwrap();
+        assert_eq!(loaded.delete(&key(1000)).unwrap(), None);
+        assert!(loaded
+            .root
+            .slots
+            .iter()
+            .all(|slot| matches!(slot, Slot::Stored(_))));
+        for i in 0..100 {
+            assert_eq!(loaded.delete(&key(i)).unwrap(), Some(i));
+        }
+        for i in 0..100 {
+            loaded.set(key(i), i).unwrap();
+        }
+        assert_eq!(loaded.flush().unwrap(), root);
+        // Nothing is dirty after flushing.
+        assert_eq!(loaded.flush().unwrap(), root);
+    }
+
+    #[test]
+    fn test_canonical_root() {
+        let store = new_store();
+        for version in [HamtVersion::V2, HamtVersion::V3].iter().copied() {
+            let mut forward = Hamt::new(store.clone()).with_version(version);
+            let mut backward = Hamt::new(store.clone()).with_version(version);
+            for i in 0..500 {
+                forward.set(key(i), i).unwrap();
+                backward.set(key(499 - i), 499 - i).unwrap();
+            }
+            let root = forward.flush().unwrap();
+            assert_eq!(backward.flush().unwrap(), root);
+            // Deep enough to have the child nodes.
+            assert!(
+                hamt_stats(&store, version, DEFAULT_HAMT_BIT_WIDTH, &root)
+                    .unwrap()
+                    .depth
+                    > 0
+            );
+
+            let loaded = Hamt::<_, u64>::load(store.clone(), version, &root).unwrap();
+            for i in 0..500 {
+                assert_eq!(loaded.get(&key(i)).unwrap(), Some(i));
+            }
+            assert_eq!(loaded.get(&key(500)).unwrap(), None);
+
+            // Deleting the keys collapses the child nodes into the same form as if the keys
+            // were never set.
+            let mut half = Hamt::new(store.clone()).with_version(version);
+            for i in 0..250 {
+                half.set(key(i), i).unwrap();
+            }
+            for i in 250..500 {
+                assert_eq!(forward.delete(&key(i)).unwrap(), Some(i));
+            }
+            assert_eq!(forward.flush().unwrap(), half.flush().unwrap());
+
+            for i in 0..250 {
+                assert_eq!(forward.delete(&key(i)).unwrap(), Some(i));
+            }
+            assert!(forward.is_empty());
+            assert_eq!(
+                forward.flush().unwrap(),
+                Hamt::<_, u64>::new(store.clone()).flush().unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The HAMT (hash array mapped trie) used by the Filecoin actors.
+//!
+//! The nodes are bit-compatible with go-hamt-ipld, the keys are hashed and every `bit_width`
+//! bits of the digest select the slot of the node at each level of the trie, a slot holds
+//! either a link to the child node, or a bucket of the key/value pairs.
+//!
+//! The keys are hashed by a `HashAlgorithm`, SHA-256 for the Filecoin state, and the trie is
+//! kept in the canonical form by the compaction rules of go-hamt-ipld (the rules of CHAMP),
+//! so the same set of the key/value pairs always results in the same root CID, regardless of
+//! the order of the mutations:
+//!
+//! - A bucket holds at most `bucket_size` pairs sorted by the keys, and an empty bucket is
+//!   removed from the node along with its bit.
+//! - Setting a key into a full bucket replaces the bucket with a link to a new child node
+//!   holding the pairs of the bucket and the new pair, one level deeper.
+//! - After a key is deleted from a child node, the child is replaced with a bucket of all its
+//!   pairs if it has no link and at most `bucket_size` pairs in total.
+//! - The root node is never collapsed.
+//!
+//! The `bit_width` and the `bucket_size` are given by `HamtConfig`, both must match the ones
+//! the HAMT was built with to get the same root CID, as well as the hash algorithm.
+
+use crate::error::{IpldError, Result};
+
+mod bitfield;
+mod hash;
+mod iter;
+mod map;
+mod node;
+mod stats;
+
+pub use self::bitfield::Bitfield;
+pub use self::hash::{Blake2b256, HashAlgorithm, Identity, Sha256};
+pub use self::iter::Iter;
+pub use self::map::{infer_bit_width, slot_index, Hamt};
+pub use self::node::{load_node, load_nodes, node_block, put_node, KeyValuePair, Node, Pointer};
+pub use self::stats::{hamt_stats, max_depth, HamtStats, HASH_DIGEST_LEN};
+
+/// The default bit width of the HAMT nodes used by the Filecoin actors,
+/// i.e. each node has `2^5` slots.
+pub const DEFAULT_HAMT_BIT_WIDTH: u32 = 5;
+/// The max bit width of the HAMT nodes.
+pub const MAX_HAMT_BIT_WIDTH: u32 = 8;
+/// The default max number of the key/value pairs of a bucket, i.e. `arrayWidth` of
+/// go-hamt-ipld.
+pub const DEFAULT_HAMT_BUCKET_SIZE: usize = 3;
+
+/// The parameters of a HAMT.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HamtConfig {
+    /// The number of the bits of the digest selecting a slot at each level,
+    /// i.e. each node has `2^bit_width` slots.
+    pub bit_width: u32,
+    /// The max number of the key/value pairs of a bucket.
+    pub bucket_size: usize,
+}
+
+impl Default for HamtConfig {
+    fn default() -> Self {
+        Self {
+            bit_width: DEFAULT_HAMT_BIT_WIDTH,
+            bucket_size: DEFAULT_HAMT_BUCKET_SIZE,
+        }
+    }
+}
+
+impl HamtConfig {
+    /// Create the config with the `bit_width` and the `bucket_size`.
+    pub fn new(bit_width: u32, bucket_size: usize) -> Self {
+        Self {
+            bit_width,
+            bucket_size,
+        }
+    }
+
+    /// Check the `bit_width` is in `1..=MAX_HAMT_BIT_WIDTH` and the `bucket_size` isn't zero.
+    pub fn validate(&self) -> Result<()> {
+        if self.bit_width == 0 || self.bit_width > MAX_HAMT_BIT_WIDTH {
+            return Err(IpldError::InvalidHamt(format!(
+                "bit width {}",
+                self.bit_width
+            )));
+        }
+        if self.bucket_size == 0 {
+            return Err(IpldError::InvalidHamt("zero bucket size".into()));
+        }
+        Ok(())
+    }
+}
+
+/// The version of the layout of the HAMT nodes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HamtVersion {
+    /// The layout of go-hamt-ipld v2 (actors v0 and v2),
+    /// the pointers are maps keyed by `"0"` (link) or `"1"` (bucket).
+    V2,
+    /// The layout of go-hamt-ipld v3 (actors v3 and later),
+    /// the pointers are bare links or buckets.
+    V3,
+}
+
+impl Default for HamtVersion {
+    fn default() -> Self {
+        HamtVersion::V3
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_validate() {
+        assert!(HamtConfig::default().validate().is_ok());
+        assert!(HamtConfig::new(MAX_HAMT_BIT_WIDTH, 1).validate().is_ok());
+        assert!(HamtConfig::new(0, 3).validate().is_err());
+        assert!(HamtConfig::new(MAX_HAMT_BIT_WIDTH + 1, 3)
+            .validate()
+            .is_err());
+        assert!(HamtConfig::new(5, 0).validate().is_err());
+    }
+}
@@ -0,0 +1,472 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use cid::Cid;
+use ipfs_block::Block;
+use minicbor::{data::Type, decode, encode, Decoder, Encoder};
+
+use super::bitfield::Bitfield;
+use super::HamtVersion;
+use crate::error::{IpldError, Result};
+use crate::store::IpldStore;
+
+/// The key of the link pointer in the map encoding of the pointers (`HamtVersion::V2`).
+const LINK_KEY: &str = "0";
+/// The key of the bucket pointer in the map encoding of the pointers (`HamtVersion::V2`).
+const BUCKET_KEY: &str = "1";
+
+/// A key/value pair stored in a bucket of a HAMT node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyValuePair<V> {
+    /// The key.
+    pub key: Vec<u8>,
+    /// The value.
+    pub value: V,
+}
+
+/// A pointer of a HAMT node, which is either a link to the child node,
+/// or a bucket of the key/value pairs stored in the node directly.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Pointer<V> {
+    /// The link to the child node.
+    Link(Cid),
+    /// The key/value pairs, sorted by the keys.
+    Bucket(Vec<KeyValuePair<V>>),
+}
+
+/// A HAMT node.
+///
+/// The layout is the same as go-hamt-ipld:
+///
+/// ```text
+/// node    = [bitfield, [pointer...]]
+/// kv      = [key, value]
+/// ; HamtVersion::V2
+/// pointer = {"0": link} / {"1": [kv...]}
+/// ; HamtVersion::V3
+/// pointer = link / [kv...]
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node<V> {
+    /// The bitfield of the slots holding the pointers.
+    pub bitfield: Bitfield,
+    /// The pointers, in ascending order of the slots.
+    pub pointers: Vec<Pointer<V>>,
+}
+
+impl<V> Default for Node<V> {
+    fn default() -> Self {
+        Self {
+            bitfield: Bitfield::new(),
+            pointers: vec![],
+        }
+    }
+}
+
+impl<V> Node<V> {
+    /// Create an empty node.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return true if the node holds no pointer.
+    pub fn is_empty(&self) -> bool {
+        self.pointers.is_empty()
+    }
+
+    /// Return the pointer of the slot `index`.
+    pub fn pointer(&self, index: u32) -> Option<&Pointer<V>> {
+        if self.bitfield.test_bit(index) {
+            self.pointers.get(self.bitfield.rank(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl<V: encode::Encode> Node<V> {
+    /// Encode the node in the layout of the `version`.
+    pub fn encode_with<W: encode::Write>(
+        &self,
+        version: HamtVersion,
+        e: &mut Encoder<W>,
+    ) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .bytes(&self.bitfield.to_bytes())?
+            .array(self.pointers.len() as u64)?;
+        for pointer in &self.pointers {
+            if version == HamtVersion::V2 {
+                e.map(1)?;
+            }
+            match pointer {
+                Pointer::Link(cid) => {
+                    if version == HamtVersion::V2 {
+                        e.str(LINK_KEY)?;
+                    }
+                    e.encode(cid)?;
+                }
+                Pointer::Bucket(kvs) => {
+                    if version == HamtVersion::V2 {
+                        e.str(BUCKET_KEY)?;
+                    }
+                    e.array(kvs.len() as u64)?;
+                    for kv in kvs {
+                        e.array(2)?.bytes(&kv.key)?.encode(&kv.value)?;
+                    }
+                }
+            }
+        }
+        e.ok()
+    }
+
+    /// Return the CBOR encoded bytes of the node in the layout of the `version`.
+    pub fn to_bytes(&self, version: HamtVersion) -> Vec<u8> {
+        let mut e = Encoder::new(Vec::new());
+        self.encode_with(version, &mut e)
+            .expect("encoding into a Vec never fails; qed");
+        e.into_inner()
+    }
+}
+
+impl<V: for<'b> decode::Decode<'b>> Node<V> {
+    /// Decode the node in the layout of the `version`.
+    ///
+    /// The number of the pointers must match the bitfield, and the buckets must not be empty.
+    pub fn decode_with(version: HamtVersion, d: &mut Decoder<'_>) -> Result<Self> {
+        Self::decode_with_map_len(version, d).map(|(node, _)| node)
+    }
+
+    /// Decode the node in the layout of the `version`, along with the length of the bitfield
+    /// bytes, i.e. the length of `map` of the HAMT spec.
+    pub fn decode_with_map_len(version: HamtVersion, d: &mut Decoder<'_>) -> Result<(Self, usize)> {
+        expect_array(d, 2)?;
+        let map = d.bytes()?;
+        let bitfield = Bitfield::from_bytes(map)?;
+        let len = d
+            .array()?
+            .ok_or_else(|| invalid("indefinite pointers".into()))?;
+        if len != u64::from(bitfield.count_ones()) {
+            return Err(invalid(format!(
+                "{} pointers don't match the bitfield {:?}",
+                len, bitfield
+            )));
+        }
+
+        let mut pointers = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let pointer = match version {
+                HamtVersion::V2 => {
+                    if d.map()? != Some(1) {
+                        return Err(invalid("pointer must be a map of one entry".into()));
+                    }
+                    match d.str()? {
+                        LINK_KEY => Pointer::Link(d.decode()?),
+                        BUCKET_KEY => Pointer::Bucket(decode_bucket(d)?),
+                        key => return Err(invalid(format!("unknown pointer key {:?}", key))),
+                    }
+                }
+                HamtVersion::V3 => match d.datatype()? {
+                    Type::Tag => Pointer::Link(d.decode()?),
+                    Type::Array => Pointer::Bucket(decode_bucket(d)?),
+                    ty => return Err(invalid(format!("unexpected pointer type {:?}", ty))),
+                },
+            };
+            pointers.push(pointer);
+        }
+        Ok((Self { bitfield, pointers }, map.len()))
+    }
+
+    /// Decode the node from the CBOR encoded bytes in the layout of the `version`.
+    pub fn from_bytes(version: HamtVersion, bytes: &[u8]) -> Result<Self> {
+        Self::decode_with(version, &mut Decoder::new(bytes))
+    }
+}
+
+fn decode_bucket<V>(d: &mut Decoder<'_>) -> Result<Vec<KeyValuePair<V>>>
+where
+    V: for<'b> decode::Decode<'b>,
+{
+    let len = d
+        .array()?
+        .ok_or_else(|| invalid("indefinite bucket".into()))?;
+    if len == 0 {
+        return Err(invalid("empty bucket".into()));
+    }
+    // The length isn't trusted, don't preallocate with it.
+    let mut kvs = Vec::new();
+    for _ in 0..len {
+        expect_array(d, 2)?;
+        let key = d.bytes()?.to_vec();
+        let value = d.decode()?;
+        kvs.push(KeyValuePair { key, value });
+    }
+    Ok(kvs)
+}
+
+/// Store the node in the layout of the `version` into the `store`, return the CID of the node.
+pub fn put_node<S, V>(store: &S, version: HamtVersion, node: &Node<V>) -> Result<Cid>
+where
+    S: IpldStore,
+    V: encode::Encode,
+{
+    IpldStore::put(store, Versioned { version, node })
+}
+
+/// Return the block of the node in the layout of the `version`.
+pub fn node_block<V: encode::Encode>(version: HamtVersion, node: &Node<V>) -> Block {
+    Block::new(Versioned { version, node })
+}
+
+// A node held by `Hamt` in memory, whose children modified by the mutations are kept in
+// memory as the dirty nodes until they are flushed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct DirtyNode<V> {
+    pub(crate) bitfield: Bitfield,
+    pub(crate) slots: Vec<Slot<V>>,
+}
+
+// A slot of a `DirtyNode`, which is either a pointer as stored, or a child node modified in
+// memory and not written into the store yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Slot<V> {
+    Stored(Pointer<V>),
+    Dirty(Box<DirtyNode<V>>),
+}
+
+impl<V> Default for DirtyNode<V> {
+    fn default() -> Self {
+        Self {
+            bitfield: Bitfield::new(),
+            slots: vec![],
+        }
+    }
+}
+
+impl<V> From<Node<V>> for DirtyNode<V> {
+    fn from(node: Node<V>) -> Self {
+        Self {
+            bitfield: node.bitfield,
+            slots: node.pointers.into_iter().map(Slot::Stored).collect(),
+        }
+    }
+}
+
+impl<V> DirtyNode<V> {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    // Return the slot `index` if it's set in the bitfield.
+    pub(crate) fn slot(&self, index: u32) -> Option<&Slot<V>> {
+        if self.bitfield.test_bit(index) {
+            self.slots.get(self.bitfield.rank(index))
+        } else {
+            None
+        }
+    }
+}
+
+impl<V: encode::Encode> DirtyNode<V> {
+    // Convert the node into a `Node` by replacing the dirty child nodes with the links to them,
+    // the blocks of the dirty child nodes and their dirty descendants are pushed into `blocks`,
+    // children before parents, which are to be written into the store in one batch.
+    pub(crate) fn flush(self, version: HamtVersion, blocks: &mut Vec<Block>) -> Node<V> {
+        let pointers = self
+            .slots
+            .into_iter()
+            .map(|slot| match slot {
+                Slot::Stored(pointer) => pointer,
+                Slot::Dirty(child) => {
+                    let child = child.flush(version, blocks);
+                    let block = node_block(version, &child);
+                    let cid = block.cid().clone();
+                    blocks.push(block);
+                    Pointer::Link(cid)
+                }
+            })
+            .collect();
+        Node {
+            bitfield: self.bitfield,
+            pointers,
+        }
+    }
+}
+
+/// Load the node with the `cid` in the layout of the `version` from the `store`.
+pub fn load_node<S, V>(store: &S, version: HamtVersion, cid: &Cid) -> Result<Node<V>>
+where
+    S: IpldStore,
+    V: for<'b> decode::Decode<'b>,
+{
+    match ipfs_blockstore::BlockStore::get(store, cid)? {
+        Some(block) => Node::from_bytes(version, block.data()),
+        None => Err(invalid(format!("missing node {}", cid))),
+    }
+}
+
+/// Load the nodes with the `cids` in the layout of the `version` from the `store` at once by
+/// `BlockStore::get_many`, the nodes are returned in the order of the `cids`.
+pub fn load_nodes<S, V>(store: &S, version: HamtVersion, cids: &[Cid]) -> Result<Vec<Node<V>>>
+where
+    S: IpldStore,
+    V: for<'b> decode::Decode<'b>,
+{
+    let blocks = ipfs_blockstore::BlockStore::get_many(store, cids)?;
+    blocks
+        .into_iter()
+        .zip(cids)
+        .map(|(block, cid)| match block {
+            Some(block) => Node::from_bytes(version, block.data()),
+            None => Err(invalid(format!("missing node {}", cid))),
+        })
+        .collect()
+}
+
+struct Versioned<'a, V> {
+    version: HamtVersion,
+    node: &'a Node<V>,
+}
+
+// Implement CBOR serialization for Versioned.
+impl<'a, V: encode::Encode> encode::Encode for Versioned<'a, V> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        self.node.encode_with(self.version, e)
+    }
+}
+
+fn expect_array(d: &mut Decoder<'_>, len: u64) -> Result<()> {
+    match d.array()? {
+        Some(array_len) if array_len == len => Ok(()),
+        array_len => Err(invalid(format!(
+            "expected array of {}, got {:?}",
+            len, array_len
+        ))),
+    }
+}
+
+fn invalid(reason: String) -> IpldError {
+    IpldError::InvalidHamt(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_utils::new_store;
+
+    // A node holding a bucket `[["a", 1]]` at the slot 3 and a link at the slot 8.
+    fn sample_node(link: Cid) -> Node<u64> {
+        let mut node = Node::new();
+        node.bitfield.set_bit(3);
+        node.bitfield.set_bit(8);
+        node.pointers = vec![
+            Pointer::Bucket(vec![KeyValuePair {
+                key: b"a".to_vec(),
+                value: 1,
+            }]),
+            Pointer::Link(link),
+        ];
+        node
+    }
+
+    #[test]
+    fn test_empty_node() {
+        let store = new_store();
+        for version in [HamtVersion::V2, HamtVersion::V3].iter() {
+            let node = Node::<u64>::new();
+            assert_eq!(node.to_bytes(*version), vec![0x82, 0x40, 0x80]);
+            // The same as the empty map of the Filecoin actors.
+            let cid = put_node(&store, *version, &node).unwrap();
+            assert_eq!(
+                cid.to_string(),
+                "bafy2bzaceamp42wmmgr2g2ymg46euououzfyck7szknvfacqscohrvaikwfay"
+            );
+            assert_eq!(load_node::<_, u64>(&store, *version, &cid).unwrap(), node);
+        }
+    }
+
+    #[test]
+    fn test_node_encoding() {
+        let store = new_store();
+        let link = put_node(&store, HamtVersion::V3, &Node::<u64>::new()).unwrap();
+        let link_bytes = minicbor::to_vec(&link).unwrap();
+        let node = sample_node(link);
+
+        // go-hamt-ipld v3: the pointers are kinded unions.
+        let mut v3 = vec![0x82, 0x42, 0x01, 0x08, 0x82];
+        v3.extend_from_slice(&[0x81, 0x82, 0x41, b'a', 0x01]);
+        v3.extend_from_slice(&link_bytes);
+        assert_eq!(node.to_bytes(HamtVersion::V3), v3);
+        assert_eq!(Node::from_bytes(HamtVersion::V3, &v3).unwrap(), node);
+
+        // go-hamt-ipld v2: the pointers are maps keyed by "0" or "1".
+        let mut v2 = vec![0x82, 0x42, 0x01, 0x08, 0x82];
+        v2.extend_from_slice(&[0xa1, 0x61, b'1', 0x81, 0x82, 0x41, b'a', 0x01]);
+        v2.extend_from_slice(&[0xa1, 0x61, b'0']);
+        v2.extend_from_slice(&link_bytes);
+        assert_eq!(node.to_bytes(HamtVersion::V2), v2);
+        assert_eq!(Node::from_bytes(HamtVersion::V2, &v2).unwrap(), node);
+
+        // The layouts of the versions are not interchangeable.
+        assert!(Node::<u64>::from_bytes(HamtVersion::V2, &v3).is_err());
+        assert!(Node::<u64>::from_bytes(HamtVersion::V3, &v2).is_err());
+
+        assert_eq!(node.pointer(3), node.pointers.first());
+        assert_eq!(node.pointer(8), node.pointers.get(1));
+        assert_eq!(node.pointer(4), None);
+        let (_, map_len) =
+            Node::<u64>::decode_with_map_len(HamtVersion::V3, &mut Decoder::new(&v3)).unwrap();
+        assert_eq!(map_len, 2);
+    }
+
+    #[test]
+    fn test_flush_dirty() {
+        let store = new_store();
+        let leaf = sample_node(put_node(&store, HamtVersion::V3, &Node::<u64>::new()).unwrap());
+        let mut child = DirtyNode::default();
+        child.bitfield.set_bit(1);
+        child.slots = vec![Slot::Dirty(Box::new(leaf.clone().into()))];
+        let mut root = DirtyNode::default();
+        root.bitfield.set_bit(0);
+        root.slots = vec![Slot::Dirty(Box::new(child))];
+
+        // The descendants are flushed before their parents.
+        let mut blocks = vec![];
+        let root = root.flush(HamtVersion::V3, &mut blocks);
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(
+            blocks[0].cid(),
+            &put_node(&store, HamtVersion::V3, &leaf).unwrap()
+        );
+        assert_eq!(root.pointers, vec![Pointer::Link(blocks[1].cid().clone())]);
+        let child = Node::<u64>::from_bytes(HamtVersion::V3, blocks[1].data()).unwrap();
+        assert_eq!(child.pointers, vec![Pointer::Link(blocks[0].cid().clone())]);
+
+        // The flushed node holds no dirty node.
+        let root = DirtyNode::from(root);
+        assert!(root
+            .slots
+            .iter()
+            .all(|slot| matches!(slot, Slot::Stored(_))));
+        let mut blocks = vec![];
+        root.flush(HamtVersion::V3, &mut blocks);
+        assert!(blocks.is_empty());
+    }
+
+    #[test]
+    fn test_invalid_node() {
+        // The bitfield has two bits set, but there is only one pointer.
+        let bytes = [0x82, 0x41, 0x03, 0x81, 0x81, 0x82, 0x41, b'a', 0x01];
+        assert!(Node::<u64>::from_bytes(HamtVersion::V3, &bytes).is_err());
+        // Empty bucket.
+        let bytes = [0x82, 0x41, 0x01, 0x81, 0x80];
+        assert!(Node::<u64>::from_bytes(HamtVersion::V3, &bytes).is_err());
+        // Unknown pointer key.
+        let bytes = [0x82, 0x41, 0x01, 0x81, 0xa1, 0x61, b'2', 0x80];
+        assert!(Node::<u64>::from_bytes(HamtVersion::V2, &bytes).is_err());
+        // A bucket claiming `u64::MAX` entries is truncated, rather than preallocated.
+        let bytes = [
+            0x82, 0x41, 0x01, 0x81, 0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        ];
+        assert!(Node::<u64>::from_bytes(HamtVersion::V3, &bytes).is_err());
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use cid::Cid;
+
+use super::node::{Node, Pointer};
+use super::{HamtVersion, MAX_HAMT_BIT_WIDTH};
+use crate::error::{IpldError, Result};
+use crate::store::IpldStore;
+use crate::value::Value;
+
+/// The length of the digest of the keys, the keys are hashed by SHA-256 in go-hamt-ipld.
+pub const HASH_DIGEST_LEN: u32 = 32;
+
+/// Return the max depth of the HAMT with the `bit_width`, i.e. the number of the `bit_width`
+/// bits chunks of the digest of the keys.
+///
+/// A node at the max depth or deeper can't be reached by any key, so such a node is rejected
+/// rather than traversed, which bounds the traversal of the maliciously crafted HAMTs.
+pub fn max_depth(bit_width: u32) -> u32 {
+    HASH_DIGEST_LEN * 8 / bit_width
+}
+
+/// The statistics of a HAMT, e.g. for the metrics of the state trees.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct HamtStats {
+    /// The number of the nodes, including the root.
+    pub nodes: usize,
+    /// The number of the buckets.
+    pub buckets: usize,
+    /// The number of the key/value pairs.
+    pub entries: usize,
+    /// The depth of the deepest node, the depth of the root is `0`.
+    pub depth: u32,
+    /// The total size of the encoded nodes in bytes.
+    pub approximate_size: usize,
+}
+
+/// Walk the HAMT with the `root` and return the statistics of it.
+///
+/// The nodes are walked iteratively with the depth tracked, an `IpldError::MaxDepth` error
+/// is returned if a node is linked at `max_depth(bit_width)`. The children of each node are
+/// loaded at once by `BlockStore::get_many`.
+pub fn hamt_stats<S: IpldStore>(
+    store: &S,
+    version: HamtVersion,
+    bit_width: u32,
+    root: &Cid,
+) -> Result<HamtStats> {
+    if bit_width == 0 || bit_width > MAX_HAMT_BIT_WIDTH {
+        return Err(IpldError::InvalidHamt(format!("bit width {}", bit_width)));
+    }
+    let max_depth = max_depth(bit_width);
+
+    let mut stats = HamtStats::default();
+    let mut stack = vec![(load_blocks(store, &[root.clone()])?.remove(0), 0)];
+    while let Some((data, depth)) = stack.pop() {
+        let node = Node::<Value>::from_bytes(version, &data)?;
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+        stats.approximate_size += data.len();
+
+        let mut links = vec![];
+        for pointer in node.pointers {
+            match pointer {
+                Pointer::Link(link) => {
+                    if depth + 1 >= max_depth {
+                        return Err(IpldError::MaxDepth(max_depth));
+                    }
+                    links.push(link);
+                }
+                Pointer::Bucket(kvs) => {
+                    stats.buckets += 1;
+                    stats.entries += kvs.len();
+                }
+            }
+        }
+        for data in load_blocks(store, &links)? {
+            stack.push((data, depth + 1));
+        }
+    }
+    Ok(stats)
+}
+
+// Load the raw nodes of the `cids` at once, in the order of the `cids`.
+fn load_blocks<S: IpldStore>(store: &S, cids: &[Cid]) -> Result<Vec<Vec<u8>>> {
+    let blocks = ipfs_blockstore::BlockStore::get_many(store, cids)?;
+    blocks
+        .into_iter()
+        .zip(cids)
+        .map(|(block, cid)| match block {
+            Some(block) => Ok(block.data().to_vec()),
+            None => Err(IpldError::InvalidHamt(format!("missing node {}", cid))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::hamt::{put_node, KeyValuePair};
+    use crate::test_utils::new_store;
+
+    fn bucket_node(entries: u64) -> Node<u64> {
+        let mut node = Node::new();
+        node.bitfield.set_bit(0);
+        node.pointers = vec![Pointer::Bucket(
+            (0..entries)
+                .map(|i| KeyValuePair {
+                    key: vec![i as u8],
+                    value: i,
+                })
+                .collect(),
+        )];
+        node
+    }
+
+    // A chain of nodes, each linking to the next one, ending with a bucket node.
+    fn put_chain(store: &impl IpldStore, len: u32) -> Cid {
+        let mut cid = put_node(store, HamtVersion::V3, &bucket_node(2)).unwrap();
+        for _ in 1..len {
+            let mut node = Node::<u64>::new();
+            node.bitfield.set_bit(1);
+            node.pointers = vec![Pointer::Link(cid)];
+            cid = put_node(store, HamtVersion::V3, &node).unwrap();
+        }
+        cid
+    }
+
+    #[test]
+    fn test_hamt_stats() {
+        let store = new_store();
+        let leaf = put_node(&store, HamtVersion::V3, &bucket_node(3)).unwrap();
+        let mut root = bucket_node(1);
+        root.bitfield.set_bit(4);
+        root.pointers.push(Pointer::Link(leaf));
+        let root_cid = put_node(&store, HamtVersion::V3, &root).unwrap();
+
+        let stats = hamt_stats(&store, HamtVersion::V3, 5, &root_cid).unwrap();
+        let size =
+            root.to_bytes(HamtVersion::V3).len() + bucket_node(3).to_bytes(HamtVersion::V3).len();
+        assert_eq!(
+            stats,
+            HamtStats {
+                nodes: 2,
+                buckets: 2,
+                entries: 4,
+                depth: 1,
+                approximate_size: size,
+            }
+        );
+        assert!(hamt_stats(&store, HamtVersion::V2, 5, &root_cid).is_err());
+        assert!(hamt_stats(&store, HamtVersion::V3, 0, &root_cid).is_err());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        assert_eq!(max_depth(5), 51);
+        assert_eq!(max_depth(8), 32);
+
+        let store = new_store();
+        let root = put_chain(&store, 32);
+        let stats = hamt_stats(&store, HamtVersion::V3, 8, &root).unwrap();
+        assert_eq!(stats.depth, 31);
+        let root = put_chain(&store, 33);
+        assert!(matches!(
+            hamt_stats(&store, HamtVersion::V3, 8, &root),
+            Err(IpldError::MaxDepth(32))
+        ));
+    }
+}
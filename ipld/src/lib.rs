@@ -4,14 +4,27 @@
 
 #![deny(missing_docs)]
 
+mod amt;
+pub mod car;
 mod error;
+pub mod hamt;
+pub mod patch;
 mod store;
+#[cfg(test)]
+mod test_utils;
 #[macro_use]
 mod value;
 
+pub use self::amt::{
+    build_amt, load_amt, max_amt_height, AmtError, DEFAULT_AMT_BIT_WIDTH, MAX_AMT_BIT_WIDTH,
+};
 pub use self::error::{IpldError, Result};
+pub use self::hamt::{
+    Hamt, HamtConfig, HamtVersion, DEFAULT_HAMT_BIT_WIDTH, DEFAULT_HAMT_BUCKET_SIZE,
+    MAX_HAMT_BIT_WIDTH,
+};
 pub use self::store::IpldStore;
-pub use self::value::{Bytes, Integer, Map, MapKey, Value};
+pub use self::value::{Bytes, Integer, Map, MapKey, Value, CID_CBOR_TAG, MAX_SAFE_JSON_INTEGER};
 
 /// Convert JSON object into an IPLD value.
 pub fn json_to_ipld<T>(value: &T) -> Result<Value, IpldError>
@@ -24,11 +37,13 @@ where
 }
 
 /// Interpret a `Value` as an instance of type `T`.
+///
+/// An error is returned if the `value` contains an integer out of the safe range of JSON.
 pub fn json_from_ipld<T>(value: &Value) -> Result<T, IpldError>
 where
     T: serde::de::DeserializeOwned,
 {
-    let json = serde_json::to_string(value).expect("`value` must be a JSON encoded object; qed");
+    let json = serde_json::to_string(value)?;
     let value = serde_json::from_str::<T>(&json)?;
     Ok(value)
 }
@@ -0,0 +1,203 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! Patch the IPLD data at a path, the path crosses the blocks by following the links.
+//!
+//! The path is a `/` separated list of the segments, each segment is either a key of a map or
+//! an index of a list, e.g. `"actors/0/head"`. Only the blocks along the path are rewritten,
+//! the other blocks are shared by the old root and the new root.
+
+use cid::Cid;
+
+use crate::error::{IpldError, Result};
+use crate::store::IpldStore;
+use crate::value::Value;
+
+/// Set the value at the `path` of the DAG with the `root`, return the new root.
+///
+/// The last segment of the path may be a missing key of a map, which is inserted, or the
+/// length of a list, which is appended. The empty path replaces the root block.
+pub fn patch<S>(store: &S, root: &Cid, path: &str, new_value: Value) -> Result<Cid>
+where
+    S: IpldStore + ?Sized,
+{
+    patch_root(store, root, path, Some(new_value))
+}
+
+/// Remove the value at the `path` of the DAG with the `root`, return the new root.
+///
+/// The removed value must be an entry of a map or an element of a list, the elements after the
+/// removed one are shifted.
+pub fn remove<S>(store: &S, root: &Cid, path: &str) -> Result<Cid>
+where
+    S: IpldStore + ?Sized,
+{
+    patch_root(store, root, path, None)
+}
+
+fn patch_root<S>(store: &S, root: &Cid, path: &str, new_value: Option<Value>) -> Result<Cid>
+where
+    S: IpldStore + ?Sized,
+{
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        return match new_value {
+            Some(value) => IpldStore::put(store, &value),
+            None => Err(invalid(path, "can't remove the root")),
+        };
+    }
+    let mut walk = Walk {
+        store,
+        path,
+        new_value,
+    };
+    walk.patch_block(root, &segments)
+}
+
+struct Walk<'a, S: ?Sized> {
+    store: &'a S,
+    path: &'a str,
+    new_value: Option<Value>,
+}
+
+impl<'a, S: IpldStore + ?Sized> Walk<'a, S> {
+    // Patch the block with the `cid`, the `segments` must not be empty.
+    fn patch_block(&mut self, cid: &Cid, segments: &[&str]) -> Result<Cid> {
+        let mut value = IpldStore::get::<Value>(self.store, cid)?
+            .ok_or_else(|| invalid(self.path, &format!("missing block {}", cid)))?;
+        self.patch_value(&mut value, segments)?;
+        IpldStore::put(self.store, &value)
+    }
+
+    // Patch the container `value`, the `segments` must not be empty.
+    fn patch_value(&mut self, value: &mut Value, segments: &[&str]) -> Result<()> {
+        let (segment, rest) = segments
+            .split_first()
+            .expect("the segments are not empty; qed");
+        match value {
+            Value::Link(cid) => {
+                *cid = self.patch_block(cid, segments)?;
+                Ok(())
+            }
+            Value::Map(map) if rest.is_empty() => {
+                match self.new_value.take() {
+                    Some(new_value) => {
+                        map.insert((*segment).into(), new_value);
+                    }
+                    None => {
+                        if map.remove(*segment).is_none() {
+                            return Err(self.not_found(segment));
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Value::Map(map) => match map.get_mut(*segment) {
+                Some(child) => self.patch_value(child, rest),
+                None => Err(self.not_found(segment)),
+            },
+            Value::List(list) => {
+                let index = segment
+                    .parse::<usize>()
+                    .map_err(|_| invalid(self.path, &format!("invalid list index {}", segment)))?;
+                if rest.is_empty() {
+                    match self.new_value.take() {
+                        Some(new_value) if index < list.len() => list[index] = new_value,
+                        Some(new_value) if index == list.len() => list.push(new_value),
+                        None if index < list.len() => {
+                            list.remove(index);
+                        }
+                        _ => return Err(self.not_found(segment)),
+                    }
+                    Ok(())
+                } else {
+                    match list.get_mut(index) {
+                        Some(child) => self.patch_value(child, rest),
+                        None => Err(self.not_found(segment)),
+                    }
+                }
+            }
+            _ => Err(invalid(
+                self.path,
+                &format!("{} is not a map, a list or a link", segment),
+            )),
+        }
+    }
+
+    fn not_found(&self, segment: &str) -> IpldError {
+        invalid(self.path, &format!("{} not found", segment))
+    }
+}
+
+fn invalid(path: &str, reason: &str) -> IpldError {
+    IpldError::InvalidPath(format!("{}: {}", path, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipld;
+    use crate::test_utils::new_store;
+
+    #[test]
+    fn test_patch() {
+        let store = new_store();
+        let leaf = store.put(&ipld!({"balance": 1, "nonce": 0})).unwrap();
+        let sibling = store.put(&ipld!({"balance": 2})).unwrap();
+        let root = store
+            .put(&ipld!({
+                "actors": [link!(leaf.to_string()), link!(sibling.to_string())],
+                "version": 1,
+            }))
+            .unwrap();
+
+        let new_root = store.patch(&root, "actors/0/balance", ipld!(10)).unwrap();
+        let new_root = store.patch(&new_root, "/version/", ipld!(2)).unwrap();
+        let value = store.get::<Value>(&new_root).unwrap().unwrap();
+        let new_leaf = match &value {
+            Value::Map(map) => match &map["actors"] {
+                Value::List(actors) => {
+                    // The sibling block isn't rewritten.
+                    assert_eq!(actors[1], Value::Link(sibling.clone()));
+                    match &actors[0] {
+                        Value::Link(cid) => cid.clone(),
+                        _ => panic!("expected link"),
+                    }
+                }
+                _ => panic!("expected list"),
+            },
+            _ => panic!("expected map"),
+        };
+        assert_eq!(
+            store.get::<Value>(&new_leaf).unwrap(),
+            Some(ipld!({"balance": 10, "nonce": 0}))
+        );
+        // The old DAG is untouched.
+        assert_eq!(
+            store.get::<Value>(&leaf).unwrap(),
+            Some(ipld!({"balance": 1, "nonce": 0}))
+        );
+
+        let removed = store.patch_remove(&new_root, "actors/0/nonce").unwrap();
+        let restored = store.patch(&removed, "actors/0/nonce", ipld!(0)).unwrap();
+        assert_eq!(restored, new_root);
+        let appended = store.patch(&root, "actors/2", ipld!(null)).unwrap();
+        assert_eq!(store.patch_remove(&appended, "actors/2").unwrap(), root);
+    }
+
+    #[test]
+    fn test_invalid_path() {
+        let store = new_store();
+        let root = store.put(&ipld!({"list": [1], "int": 1})).unwrap();
+        assert!(store.patch(&root, "missing/a", ipld!(1)).is_err());
+        assert!(store.patch(&root, "list/2", ipld!(1)).is_err());
+        assert!(store.patch(&root, "list/a", ipld!(1)).is_err());
+        assert!(store.patch(&root, "int/a", ipld!(1)).is_err());
+        assert!(store.patch_remove(&root, "missing").is_err());
+        assert!(store.patch_remove(&root, "").is_err());
+        let new_root = store.patch(&root, "", ipld!(1)).unwrap();
+        assert_eq!(store.get::<Value>(&new_root).unwrap(), Some(ipld!(1)));
+    }
+}
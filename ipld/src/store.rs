@@ -6,6 +6,8 @@ use ipfs_block::Block;
 use ipfs_blockstore::BlockStore;
 
 use crate::error::IpldError;
+use crate::patch;
+use crate::value::Value;
 
 /// IpldStore wraps block store and provides an interface for storing and retrieving CBOR encoded data.
 pub trait IpldStore: BlockStore {
@@ -24,7 +26,7 @@ pub trait IpldStore: BlockStore {
     }
 
     /// Put an object into the block store.
-    fn put<T>(&mut self, value: T) -> Result<Cid, IpldError>
+    fn put<T>(&self, value: T) -> Result<Cid, IpldError>
     where
         T: minicbor::Encode,
     {
@@ -33,6 +35,20 @@ pub trait IpldStore: BlockStore {
         <Self as BlockStore>::put(self, block)?;
         Ok(cid)
     }
+
+    /// Set the value at the `path` of the DAG with the `root`, return the new root.
+    ///
+    /// Only the blocks along the path are rewritten, see `patch::patch` for the path syntax.
+    fn patch(&self, root: &Cid, path: &str, new_value: Value) -> Result<Cid, IpldError> {
+        patch::patch(self, root, path, new_value)
+    }
+
+    /// Remove the value at the `path` of the DAG with the `root`, return the new root.
+    ///
+    /// Only the blocks along the path are rewritten, see `patch::remove` for the details.
+    fn patch_remove(&self, root: &Cid, path: &str) -> Result<Cid, IpldError> {
+        patch::remove(self, root, path)
+    }
 }
 
 impl<T: BlockStore> IpldStore for T {}
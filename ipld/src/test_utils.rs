@@ -0,0 +1,21 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The fixtures shared by the tests of the crate.
+
+use ipfs_datastore::{Delay, DelayDataStore, MapDataStore, SyncDataStore};
+
+/// The delay of the datastore that never waits.
+#[derive(Clone)]
+pub struct NoDelay;
+
+impl Delay for NoDelay {
+    fn wait(&self) {}
+}
+
+/// The in-memory store of the tests.
+pub type TestStore = DelayDataStore<NoDelay, SyncDataStore<MapDataStore>>;
+
+/// Create an empty in-memory store.
+pub fn new_store() -> TestStore {
+    DelayDataStore::new(NoDelay, SyncDataStore::new(MapDataStore::new()))
+}
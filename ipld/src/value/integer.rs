@@ -1,10 +1,16 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::convert::TryFrom;
 use std::fmt;
+use std::num::TryFromIntError;
 
 use minicbor::{data::Type, decode, encode, Decoder, Encoder};
 use serde::{de, ser};
 
+/// The max absolute value of the integer that can be represented in JSON without losing precision,
+/// i.e. `2^53 - 1`, because most JSON implementations parse numbers as IEEE 754 doubles.
+pub const MAX_SAFE_JSON_INTEGER: i128 = (1 << 53) - 1;
+
 /// The Integer kind of IPLD Data Model.
 #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
 pub struct Integer(i128);
@@ -14,6 +20,11 @@ impl Integer {
     pub fn into_inner(self) -> i128 {
         self.0
     }
+
+    /// Return true if the integer can be represented in JSON without losing precision.
+    pub fn is_json_safe(&self) -> bool {
+        self.0.abs() <= MAX_SAFE_JSON_INTEGER
+    }
 }
 
 impl fmt::Debug for Integer {
@@ -82,6 +93,22 @@ impl From<i128> for Integer {
     }
 }
 
+macro_rules! impl_try_from_integer {
+    ($($ty:ty),+) => {
+        $(
+            impl TryFrom<Integer> for $ty {
+                type Error = TryFromIntError;
+
+                fn try_from(integer: Integer) -> Result<Self, Self::Error> {
+                    <$ty>::try_from(integer.0)
+                }
+            }
+        )+
+    };
+}
+
+impl_try_from_integer!(u8, u16, u32, u64, i8, i16, i32, i64);
+
 // Integer encoding must be as short as possible.
 // See [Strictness](https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#strictness) for details.
 // Implement CBOR serialization for Integer.
@@ -136,12 +163,21 @@ impl<'b> decode::Decode<'b> for Integer {
 }
 
 // Implement JSON serialization for Integer.
+// The integer out of the safe range of JSON is rejected instead of being truncated silently
+// by the JSON implementations that parse numbers as doubles.
 impl ser::Serialize for Integer {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: ser::Serializer,
     {
-        serializer.serialize_i128(self.0)
+        if !self.is_json_safe() {
+            return Err(ser::Error::custom(format!(
+                "integer {} exceeds the safe range of JSON: [-{max}, {max}]",
+                self.0,
+                max = MAX_SAFE_JSON_INTEGER
+            )));
+        }
+        serializer.serialize_i64(self.0 as i64)
     }
 }
 
@@ -189,3 +225,33 @@ impl<'de> de::Deserialize<'de> for Integer {
         deserializer.deserialize_any(IntegerVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_json_safe_range() {
+        let max = Integer::from(MAX_SAFE_JSON_INTEGER);
+        assert!(max.is_json_safe());
+        assert_eq!(serde_json::to_string(&max).unwrap(), "9007199254740991");
+        let min = Integer::from(-MAX_SAFE_JSON_INTEGER);
+        assert_eq!(serde_json::to_string(&min).unwrap(), "-9007199254740991");
+
+        let overflow = Integer::from(MAX_SAFE_JSON_INTEGER + 1);
+        assert!(!overflow.is_json_safe());
+        assert!(serde_json::to_string(&overflow).is_err());
+        assert!(serde_json::to_string(&Integer::from(u64::max_value())).is_err());
+    }
+
+    #[test]
+    fn test_integer_try_from() {
+        assert_eq!(
+            u64::try_from(Integer::from(u64::max_value())),
+            Ok(u64::max_value())
+        );
+        assert!(u32::try_from(Integer::from(u64::max_value())).is_err());
+        assert!(u8::try_from(Integer::from(-1)).is_err());
+        assert_eq!(i8::try_from(Integer::from(-1)), Ok(-1));
+    }
+}
@@ -8,15 +8,20 @@ mod integer;
 mod map;
 
 pub use self::bytes::Bytes;
-pub use self::integer::Integer;
+pub use self::integer::{Integer, MAX_SAFE_JSON_INTEGER};
 pub use self::map::{Map, MapKey};
 
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 use cid::Cid;
-use minicbor::{data::Type, decode, encode, Decoder, Encoder};
+use minicbor::data::{Tag, Type};
+use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{de, ser};
 
+/// The CBOR tag of the IPLD Link, see [DAG-CBOR](https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links).
+pub const CID_CBOR_TAG: u64 = 42;
+
 /// The [IPLD Data Model](https://github.com/ipld/specs/blob/master/data-model-layer/data-model.md).
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 pub enum Value {
@@ -148,7 +153,15 @@ impl encode::Encode for Value {
             Value::String(string) => e.str(string)?.ok(),
             Value::List(list) => e.encode(list)?.ok(),
             Value::Map(map) => e.encode(map)?.ok(),
-            Value::Link(cid) => e.encode(cid)?.ok(),
+            // The Link is encoded as a byte string with the multibase identity prefix (0x00),
+            // tagged with 42.
+            Value::Link(cid) => {
+                let cid = cid.to_bytes();
+                let mut bytes = Vec::with_capacity(cid.len() + 1);
+                bytes.push(0x00);
+                bytes.extend_from_slice(&cid);
+                e.tag(Tag::Unassigned(CID_CBOR_TAG))?.bytes(&bytes)?.ok()
+            }
         }
     }
 }
@@ -175,7 +188,23 @@ impl<'b> decode::Decode<'b> for Value {
             Type::String => Ok(Value::String(d.str()?.to_owned())),
             Type::Array => Ok(Value::List(d.decode::<Vec<Value>>()?)),
             Type::Map => Ok(Value::Map(d.decode::<BTreeMap<MapKey, Value>>()?)),
-            Type::Tag => Ok(Value::Link(d.decode::<Cid>()?)),
+            Type::Tag => match d.tag()? {
+                Tag::Unassigned(CID_CBOR_TAG) => {
+                    let bytes = d.bytes()?;
+                    match bytes.split_first() {
+                        Some((0x00, cid)) => Ok(Value::Link(
+                            Cid::try_from(cid)
+                                .map_err(|_| decode::Error::Message("invalid cid"))?,
+                        )),
+                        _ => Err(decode::Error::Message(
+                            "cid must be prefixed with the multibase identity",
+                        )),
+                    }
+                }
+                _ => Err(decode::Error::Message(
+                    "unexpected tag, only tag 42 is allowed",
+                )),
+            },
             Type::Break | Type::Unknown(_) | Type::Undefined | Type::Simple => {
                 Err(decode::Error::Message("unexpected type"))
             }
@@ -375,3 +404,21 @@ fn test_ipld_value_cbor_and_json() {
         _ => panic!(),
     }
 }
+
+#[test]
+fn test_ipld_link_cbor() {
+    let cid = "QmRgutAxd8t7oGkSm4wmeuByG6M51wcTso6cubDdQtuEfL"
+        .parse::<Cid>()
+        .unwrap();
+    let cbor = minicbor::to_vec(&Value::Link(cid.clone())).unwrap();
+    // tag 42, byte string, multibase identity prefix
+    assert_eq!(&cbor[..2], &[0xd8, 0x2a]);
+    assert_eq!(cbor[4], 0x00);
+    assert_eq!(cbor, minicbor::to_vec(&cid).unwrap());
+    assert_eq!(minicbor::decode::<Value>(&cbor).unwrap(), Value::Link(cid));
+
+    // only tag 42 is allowed.
+    let mut cbor = cbor;
+    cbor[1] = 0x2b;
+    assert!(minicbor::decode::<Value>(&cbor).is_err());
+}
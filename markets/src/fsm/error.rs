@@ -0,0 +1,31 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+/// Type alias to use this module's [`FsmError`] type in a `Result`.
+pub type Result<T> = std::result::Result<T, FsmError>;
+
+/// Errors generated from the state machine framework.
+#[derive(Debug, thiserror::Error)]
+pub enum FsmError {
+    /// Datastore error.
+    #[error("{0}")]
+    DataStore(#[from] ipfs_datastore::DataStoreError),
+    /// CBOR decode error.
+    #[error("{0}")]
+    CborDecode(#[from] minicbor::decode::Error),
+    /// The state machine with the id already exists.
+    #[error("state machine `{0}` already exists")]
+    AlreadyExists(String),
+    /// The state machine with the id is not found.
+    #[error("state machine `{0}` not found")]
+    NotFound(String),
+    /// The event is not allowed in the current state.
+    #[error("invalid transition of state machine `{id}`: event {event} in state {state}")]
+    InvalidTransition {
+        /// The id of the state machine.
+        id: String,
+        /// The current state.
+        state: String,
+        /// The event.
+        event: String,
+    },
+}
@@ -0,0 +1,316 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ipfs_datastore::{DataStore, Key};
+
+use super::error::{FsmError, Result};
+use super::record::StateRecord;
+use super::StateMachine;
+
+/// The default max number of retries before a state machine is no longer resumed.
+pub const DEFAULT_MAX_RETRIES: u64 = 5;
+
+const INDEX_KEY: &str = "index";
+const RECORDS_KEY: &str = "records";
+
+/// A group of state machines with the same definition, persisted into the datastore.
+///
+/// The records are stored under `/fsm/<name>/records/<id>`, and the ids of all
+/// records are stored under `/fsm/<name>/index`.
+///
+/// The id is added into the index before the record is written, and removed from the index
+/// after the record is deleted, so a crash between the two writes leaves at most an id without
+/// a record in the index, which is skipped by `list` and reused by `begin`.
+pub struct StateGroup<M, DS> {
+    machine: M,
+    datastore: DS,
+    namespace: Key,
+    max_retries: u64,
+}
+
+impl<M, DS> StateGroup<M, DS>
+where
+    M: StateMachine,
+    DS: DataStore,
+{
+    /// Create a new state group with the given state machine definition, datastore and group name.
+    pub fn new<N: AsRef<str>>(machine: M, datastore: DS, name: N) -> Self {
+        Self {
+            machine,
+            datastore,
+            namespace: Key::with_namespaces(vec!["fsm", name.as_ref()]),
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+
+    /// Set the max number of retries before a state machine is no longer resumed.
+    pub fn with_max_retries(mut self, max_retries: u64) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Return the state machine definition.
+    pub fn machine(&self) -> &M {
+        &self.machine
+    }
+
+    /// Start tracking a new state machine with the initial state,
+    /// return the effects that should be run for the initial state.
+    pub fn begin<I: Into<String>>(&mut self, id: I, initial: M::State) -> Result<Vec<M::Effect>> {
+        let id = id.into();
+        if self.get(&id)?.is_some() {
+            return Err(FsmError::AlreadyExists(id));
+        }
+
+        let effects = self.machine.on_enter(&initial);
+        let record = StateRecord {
+            id: id.clone(),
+            state: initial,
+            retries: 0,
+            last_error: None,
+            updated_at: unix_now(),
+        };
+        let mut ids = self.ids()?;
+        // The id is left in the index if the record failed to be written before.
+        if !ids.contains(&id) {
+            ids.push(id);
+            self.save_ids(&ids)?;
+        }
+        self.save(&record)?;
+        Ok(effects)
+    }
+
+    /// Get the record of the state machine with the id.
+    pub fn get(&self, id: &str) -> Result<Option<StateRecord<M::State>>> {
+        match self.datastore.get(&self.record_key(id))? {
+            Some(data) => Ok(Some(minicbor::decode(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Send an event to the state machine with the id, the new state will be persisted
+    /// before returning the effects that should be run.
+    pub fn send(&mut self, id: &str, event: M::Event) -> Result<Vec<M::Effect>> {
+        let mut record = self
+            .get(id)?
+            .ok_or_else(|| FsmError::NotFound(id.to_owned()))?;
+
+        let transition = if self.machine.is_final(&record.state) {
+            None
+        } else {
+            self.machine.transition(&record.state, &event)
+        };
+        let (state, effects) = transition.ok_or_else(|| FsmError::InvalidTransition {
+            id: id.to_owned(),
+            state: format!("{:?}", record.state),
+            event: format!("{:?}", event),
+        })?;
+
+        debug!(
+            "[fsm] {} `{}`: {:?} --({:?})--> {:?}",
+            self.namespace, id, record.state, event, state
+        );
+        record.state = state;
+        record.retries = 0;
+        record.last_error = None;
+        record.updated_at = unix_now();
+        self.save(&record)?;
+        Ok(effects)
+    }
+
+    /// Record an error reported by the effects of the state machine with the id,
+    /// the state machine stays in the current state and will be retried after restart.
+    pub fn fail<E: ToString>(&mut self, id: &str, err: E) -> Result<()> {
+        let mut record = self
+            .get(id)?
+            .ok_or_else(|| FsmError::NotFound(id.to_owned()))?;
+        record.retries += 1;
+        record.last_error = Some(err.to_string());
+        record.updated_at = unix_now();
+        warn!(
+            "[fsm] {} `{}` failed in {:?} (retries: {}): {}",
+            self.namespace,
+            id,
+            record.state,
+            record.retries,
+            record.last_error.as_deref().unwrap_or_default()
+        );
+        self.save(&record)
+    }
+
+    /// Resume all the unfinished state machines, return the effects that should be rerun for each one.
+    ///
+    /// The state machines that have exhausted their retries are skipped,
+    /// they can be inspected by `list_stuck`.
+    pub fn restart(&mut self) -> Result<Vec<(String, Vec<M::Effect>)>> {
+        let mut resumed = Vec::new();
+        for record in self.list()? {
+            if self.machine.is_final(&record.state) {
+                continue;
+            }
+            if record.retries >= self.max_retries {
+                warn!(
+                    "[fsm] {} `{}` exhausted retries in {:?}, skip resuming",
+                    self.namespace, record.id, record.state
+                );
+                continue;
+            }
+            let effects = self.machine.on_enter(&record.state);
+            resumed.push((record.id, effects));
+        }
+        Ok(resumed)
+    }
+
+    /// List the records of all the state machines.
+    pub fn list(&self) -> Result<Vec<StateRecord<M::State>>> {
+        let mut records = Vec::new();
+        for id in self.ids()? {
+            if let Some(record) = self.get(&id)? {
+                records.push(record);
+            }
+        }
+        Ok(records)
+    }
+
+    /// List the records of the unfinished state machines that have encountered an error
+    /// or haven't been updated for `threshold` seconds.
+    pub fn list_stuck(&self, threshold: u64) -> Result<Vec<StateRecord<M::State>>> {
+        let now = unix_now();
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|record| !self.machine.is_final(&record.state))
+            .filter(|record| record.is_stuck(now, threshold))
+            .collect())
+    }
+
+    /// Stop tracking the state machine with the id.
+    pub fn remove(&mut self, id: &str) -> Result<()> {
+        self.datastore.delete(&self.record_key(id))?;
+        let mut ids = self.ids()?;
+        ids.retain(|exist| exist != id);
+        self.save_ids(&ids)
+    }
+
+    fn record_key(&self, id: &str) -> Key {
+        self.namespace
+            .child(Key::with_namespaces(vec![RECORDS_KEY, id]))
+    }
+
+    fn ids(&self) -> Result<Vec<String>> {
+        match self.datastore.get(&self.namespace.child(INDEX_KEY))? {
+            Some(data) => Ok(minicbor::decode(&data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn save_ids(&mut self, ids: &[String]) -> Result<()> {
+        let data = minicbor::to_vec(ids).expect("CBOR serialization of ids shouldn't fail; qed");
+        let key = self.namespace.child(INDEX_KEY);
+        self.datastore.put(key, data)?;
+        self.datastore.sync(&self.namespace)?;
+        Ok(())
+    }
+
+    fn save(&mut self, record: &StateRecord<M::State>) -> Result<()> {
+        let data =
+            minicbor::to_vec(record).expect("CBOR serialization of record shouldn't fail; qed");
+        let key = self.record_key(&record.id);
+        self.datastore.put(key, data)?;
+        self.datastore.sync(&self.namespace)?;
+        Ok(())
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is before the unix epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+
+    // A counter counts up to 3.
+    struct Counter;
+
+    impl StateMachine for Counter {
+        type State = u64;
+        type Event = &'static str;
+        type Effect = u64;
+
+        fn transition(&self, state: &u64, event: &&'static str) -> Option<(u64, Vec<u64>)> {
+            match *event {
+                "incr" => Some((state + 1, vec![state + 1])),
+                _ => None,
+            }
+        }
+
+        fn on_enter(&self, state: &u64) -> Vec<u64> {
+            vec![*state]
+        }
+
+        fn is_final(&self, state: &u64) -> bool {
+            *state >= 3
+        }
+    }
+
+    #[test]
+    fn test_state_group() {
+        let datastore = SyncDataStore::new(MapDataStore::new());
+        let mut group = StateGroup::new(Counter, datastore.clone(), "counter").with_max_retries(2);
+
+        assert_eq!(group.begin("a", 0).unwrap(), vec![0]);
+        assert_eq!(group.begin("b", 2).unwrap(), vec![2]);
+        assert!(group.begin("a", 0).is_err());
+
+        assert_eq!(group.send("a", "incr").unwrap(), vec![1]);
+        assert!(group.send("a", "decr").is_err());
+        assert_eq!(group.send("b", "incr").unwrap(), vec![3]);
+        // final state doesn't accept any event.
+        assert!(group.send("b", "incr").is_err());
+        assert!(group.send("c", "incr").is_err());
+
+        group.fail("a", "some error").unwrap();
+        let stuck = group.list_stuck(u64::MAX).unwrap();
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].id, "a");
+        assert_eq!(stuck[0].last_error.as_deref(), Some("some error"));
+
+        // reload from the datastore, the state survives.
+        let mut group = StateGroup::new(Counter, datastore, "counter").with_max_retries(2);
+        assert_eq!(group.list().unwrap().len(), 2);
+        assert_eq!(group.restart().unwrap(), vec![("a".to_string(), vec![1])]);
+
+        group.fail("a", "another error").unwrap();
+        assert!(group.restart().unwrap().is_empty());
+
+        group.remove("a").unwrap();
+        assert!(group.get("a").unwrap().is_none());
+        assert_eq!(group.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_crash_in_begin() {
+        let datastore = SyncDataStore::new(MapDataStore::new());
+        let mut group = StateGroup::new(Counter, datastore.clone(), "counter");
+        group.begin("a", 0).unwrap();
+        // Crash after the id of `b` is added into the index, before its record is written.
+        group.save_ids(&["a".to_string(), "b".to_string()]).unwrap();
+
+        let mut group = StateGroup::new(Counter, datastore, "counter");
+        assert!(group.get("b").unwrap().is_none());
+        assert_eq!(group.list().unwrap().len(), 1);
+        assert_eq!(group.restart().unwrap(), vec![("a".to_string(), vec![0])]);
+
+        // The state machine can be begun again, and is indexed once.
+        assert_eq!(group.begin("b", 1).unwrap(), vec![1]);
+        assert_eq!(group.ids().unwrap(), vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(group.restart().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! A generic persisted finite-state machine framework.
+//!
+//! Every state machine of a group is identified by a string id, its current state
+//! is persisted into the datastore after each transition, so that the deal flows
+//! survive crashes and can be resumed (with retries) after restart.
+
+mod error;
+mod group;
+mod record;
+
+pub use self::error::{FsmError, Result};
+pub use self::group::StateGroup;
+pub use self::record::StateRecord;
+
+use std::fmt::Debug;
+
+use minicbor::{decode, encode};
+
+/// The definition of a finite-state machine.
+///
+/// The state machine itself is pure: `transition` only computes the next state and
+/// the effects that need to be run by the caller, all the persistence is handled
+/// by the `StateGroup`.
+pub trait StateMachine {
+    /// The state of the state machine, which will be persisted.
+    type State: Clone + Debug + encode::Encode + for<'b> decode::Decode<'b>;
+    /// The event that drives the state machine.
+    type Event: Debug;
+    /// The side effect that should be run after a transition.
+    type Effect: Debug;
+
+    /// Compute the next state and the effects according to the current state and the event.
+    ///
+    /// Return `None` if the event is not allowed in the current state.
+    fn transition(
+        &self,
+        state: &Self::State,
+        event: &Self::Event,
+    ) -> Option<(Self::State, Vec<Self::Effect>)>;
+
+    /// Return the effects that should be run when starting a state machine in the given state.
+    fn on_enter(&self, state: &Self::State) -> Vec<Self::Effect>;
+
+    /// Whether the given state is a final state, the state machine in a final state
+    /// won't be resumed or accept any event.
+    fn is_final(&self, state: &Self::State) -> bool;
+}
@@ -0,0 +1,84 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+/// The persisted record of a state machine.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StateRecord<S> {
+    /// The id of the state machine.
+    pub id: String,
+    /// The current state.
+    pub state: S,
+    /// The number of the retries since the last successful transition.
+    pub retries: u64,
+    /// The last error reported by the effects, if any.
+    pub last_error: Option<String>,
+    /// The unix timestamp (in seconds) of the last update.
+    pub updated_at: u64,
+}
+
+impl<S> StateRecord<S> {
+    /// Whether the state machine hasn't been updated for `threshold` seconds
+    /// or has encountered an error.
+    pub fn is_stuck(&self, now: u64, threshold: u64) -> bool {
+        self.last_error.is_some() || now.saturating_sub(self.updated_at) >= threshold
+    }
+}
+
+// Implement CBOR serialization for StateRecord.
+impl<S: encode::Encode> encode::Encode for StateRecord<S> {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(5)?
+            .str(&self.id)?
+            .encode(&self.state)?
+            .u64(self.retries)?
+            .encode(&self.last_error)?
+            .u64(self.updated_at)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for StateRecord.
+impl<'b, S: decode::Decode<'b>> decode::Decode<'b> for StateRecord<S> {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(5) {
+            return Err(decode::Error::Message("expected 5 fields of StateRecord"));
+        }
+        Ok(StateRecord {
+            id: d.str()?.to_owned(),
+            state: d.decode()?,
+            retries: d.u64()?,
+            last_error: d.decode()?,
+            updated_at: d.u64()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_record_cbor_serde() {
+        let record = StateRecord {
+            id: "deal".to_string(),
+            state: 3u64,
+            retries: 1,
+            last_error: Some("boom".to_string()),
+            updated_at: 1_600_000_000,
+        };
+        let ser = minicbor::to_vec(&record).unwrap();
+        let de = minicbor::decode::<StateRecord<u64>>(&ser).unwrap();
+        assert_eq!(de, record);
+
+        assert!(record.is_stuck(1_600_000_000, 60));
+        let record = StateRecord {
+            last_error: None,
+            ..record
+        };
+        assert!(!record.is_stuck(1_600_000_059, 60));
+        assert!(record.is_stuck(1_600_000_060, 60));
+    }
+}
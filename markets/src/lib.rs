@@ -0,0 +1,12 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The implementation of the storage and retrieval markets.
+
+#![deny(missing_docs)]
+
+#[macro_use]
+extern crate log;
+
+pub mod fsm;
+pub mod retrieval;
+pub mod storage;
@@ -0,0 +1,260 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::convert::TryFrom;
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use crate::fsm::StateMachine;
+
+/// The name of the state group of the provider retrieval deals.
+pub const PROVIDER_DEAL_FSM_NAME: &str = "provider-retrieval-deals";
+
+/// The status of a retrieval deal.
+///
+/// This ordering defines mappings to UInt in a way which MUST never change,
+/// see go-fil-markets/retrievalmarket/types.go for details.
+#[doc(hidden)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize_repr, Deserialize_repr)]
+pub enum RetrievalDealStatus {
+    New = 0,
+    PaymentChannelCreating = 1,
+    PaymentChannelAddingFunds = 2,
+    PaymentChannelAllocatingLane = 3,
+    PaymentChannelReady = 4,
+    AwaitingAcceptance = 5,
+    Accepted = 6,
+    Failed = 7,
+    Rejected = 8,
+    FundsNeeded = 9,
+    Ongoing = 10,
+    FundsNeededLastPayment = 11,
+    Completed = 12,
+    DealNotFound = 13,
+    Verified = 14,
+    Errored = 15,
+    BlocksComplete = 16,
+    Finalizing = 17,
+}
+
+impl TryFrom<u64> for RetrievalDealStatus {
+    type Error = &'static str;
+
+    fn try_from(value: u64) -> Result<Self, &'static str> {
+        use RetrievalDealStatus::*;
+        Ok(match value {
+            0 => New,
+            1 => PaymentChannelCreating,
+            2 => PaymentChannelAddingFunds,
+            3 => PaymentChannelAllocatingLane,
+            4 => PaymentChannelReady,
+            5 => AwaitingAcceptance,
+            6 => Accepted,
+            7 => Failed,
+            8 => Rejected,
+            9 => FundsNeeded,
+            10 => Ongoing,
+            11 => FundsNeededLastPayment,
+            12 => Completed,
+            13 => DealNotFound,
+            14 => Verified,
+            15 => Errored,
+            16 => BlocksComplete,
+            17 => Finalizing,
+            _ => return Err("unexpected retrieval deal status"),
+        })
+    }
+}
+
+// Implement CBOR serialization for RetrievalDealStatus.
+impl encode::Encode for RetrievalDealStatus {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.u64(*self as u64)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for RetrievalDealStatus.
+impl<'b> decode::Decode<'b> for RetrievalDealStatus {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let status = d.u64()?;
+        RetrievalDealStatus::try_from(status)
+            .map_err(|e| decode::Error::TypeMismatch(status as u8, e))
+    }
+}
+
+/// The persisted state of a retrieval deal on the provider side.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProviderDealState {
+    /// The status of the deal.
+    pub status: RetrievalDealStatus,
+    /// The total number of bytes sent to the client.
+    pub total_sent: u64,
+    /// The message describing why the deal is in the status.
+    pub message: String,
+}
+
+impl ProviderDealState {
+    /// Create a new state of a retrieval deal that has just been received.
+    pub fn new() -> Self {
+        Self {
+            status: RetrievalDealStatus::New,
+            total_sent: 0,
+            message: String::new(),
+        }
+    }
+
+    fn with_status(&self, status: RetrievalDealStatus) -> Self {
+        Self {
+            status,
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for ProviderDealState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement CBOR serialization for ProviderDealState.
+impl encode::Encode for ProviderDealState {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(3)?
+            .encode(&self.status)?
+            .u64(self.total_sent)?
+            .str(&self.message)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ProviderDealState.
+impl<'b> decode::Decode<'b> for ProviderDealState {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(3) {
+            return Err(decode::Error::Message(
+                "expected 3 fields of ProviderDealState",
+            ));
+        }
+        Ok(ProviderDealState {
+            status: d.decode()?,
+            total_sent: d.u64()?,
+            message: d.str()?.to_owned(),
+        })
+    }
+}
+
+/// The events that drive the retrieval deal on the provider side.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProviderDealEvent {
+    Open,
+    Accepted,
+    Rejected(String),
+    BlockSent(u64),
+    PaymentRequested,
+    PaymentReceived,
+    BlocksCompleted,
+    Completed,
+    Failed(String),
+    CleanedUp,
+}
+
+/// The effects that should be run by the retrieval provider after the transitions.
+#[doc(hidden)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProviderDealEffect {
+    DecideOnDeal,
+    SendResponse,
+    SendBlocks,
+    RequestPayment,
+    Finalize,
+    Cleanup,
+}
+
+/// The state machine of the retrieval deal on the provider side.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProviderDealFsm;
+
+impl StateMachine for ProviderDealFsm {
+    type State = ProviderDealState;
+    type Event = ProviderDealEvent;
+    type Effect = ProviderDealEffect;
+
+    fn transition(
+        &self,
+        state: &ProviderDealState,
+        event: &ProviderDealEvent,
+    ) -> Option<(ProviderDealState, Vec<ProviderDealEffect>)> {
+        use ProviderDealEffect as Effect;
+        use ProviderDealEvent as Event;
+        use RetrievalDealStatus as Status;
+
+        let next = match (state.status, event) {
+            (Status::New, Event::Open) => state.with_status(Status::AwaitingAcceptance),
+            (Status::AwaitingAcceptance, Event::Accepted) => state.with_status(Status::Accepted),
+            (Status::AwaitingAcceptance, Event::Rejected(reason)) => {
+                let mut next = state.with_status(Status::Rejected);
+                next.message = reason.clone();
+                return Some((next, vec![Effect::SendResponse]));
+            }
+            (Status::Accepted, Event::BlockSent(size))
+            | (Status::Ongoing, Event::BlockSent(size)) => {
+                let mut next = state.with_status(Status::Ongoing);
+                next.total_sent += size;
+                return Some((next, vec![]));
+            }
+            (Status::Ongoing, Event::PaymentRequested) => state.with_status(Status::FundsNeeded),
+            (Status::FundsNeeded, Event::PaymentReceived) => state.with_status(Status::Ongoing),
+            (Status::Accepted, Event::BlocksCompleted)
+            | (Status::Ongoing, Event::BlocksCompleted) => {
+                state.with_status(Status::BlocksComplete)
+            }
+            (Status::BlocksComplete, Event::PaymentRequested) => {
+                state.with_status(Status::FundsNeededLastPayment)
+            }
+            (Status::FundsNeededLastPayment, Event::PaymentReceived) => {
+                state.with_status(Status::Finalizing)
+            }
+            (Status::BlocksComplete, Event::Completed) | (Status::Finalizing, Event::Completed) => {
+                state.with_status(Status::Completed)
+            }
+            (Status::Failed, Event::CleanedUp) => state.with_status(Status::Errored),
+            (_, Event::Failed(err)) => {
+                let mut next = state.with_status(Status::Failed);
+                next.message = err.clone();
+                next
+            }
+            _ => return None,
+        };
+        let effects = self.on_enter(&next);
+        Some((next, effects))
+    }
+
+    fn on_enter(&self, state: &ProviderDealState) -> Vec<ProviderDealEffect> {
+        use ProviderDealEffect as Effect;
+        use RetrievalDealStatus as Status;
+
+        match state.status {
+            Status::AwaitingAcceptance => vec![Effect::DecideOnDeal],
+            Status::Accepted => vec![Effect::SendResponse, Effect::SendBlocks],
+            Status::Ongoing => vec![Effect::SendBlocks],
+            Status::FundsNeeded | Status::FundsNeededLastPayment => vec![Effect::RequestPayment],
+            Status::BlocksComplete | Status::Finalizing => vec![Effect::Finalize],
+            Status::Failed => vec![Effect::Cleanup],
+            _ => vec![],
+        }
+    }
+
+    fn is_final(&self, state: &ProviderDealState) -> bool {
+        matches!(
+            state.status,
+            RetrievalDealStatus::Completed
+                | RetrievalDealStatus::Rejected
+                | RetrievalDealStatus::Errored
+        )
+    }
+}
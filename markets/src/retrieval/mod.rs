@@ -0,0 +1,10 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The retrieval market.
+
+mod deal;
+
+pub use self::deal::{
+    ProviderDealEffect, ProviderDealEvent, ProviderDealFsm, ProviderDealState, RetrievalDealStatus,
+    PROVIDER_DEAL_FSM_NAME,
+};
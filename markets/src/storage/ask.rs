@@ -0,0 +1,276 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_address::Address;
+use plum_bigint::{bigint_json, BigIntRefWrapper, BigIntWrapper};
+use plum_crypto::{CryptoError, Signature, SignatureType};
+use plum_piece::PaddedPieceSize;
+use plum_types::{ChainEpoch, TokenAmount};
+
+/// The errors of validating a signed storage ask.
+#[derive(Debug, thiserror::Error)]
+pub enum AskError {
+    /// The ask is not signed by the expected miner worker.
+    #[error("invalid signature of the storage ask of miner {0}")]
+    InvalidSignature(Address),
+    /// The ask has expired.
+    #[error("storage ask of miner {miner} expired at epoch {expiry}, current epoch: {current}")]
+    Expired {
+        /// The miner of the ask.
+        miner: Address,
+        /// The expiry epoch of the ask.
+        expiry: ChainEpoch,
+        /// The current epoch.
+        current: ChainEpoch,
+    },
+    /// The ask is from another miner.
+    #[error("storage ask is from miner {actual}, expected: {expected}")]
+    MinerMismatch {
+        /// The miner that is queried.
+        expected: Address,
+        /// The miner in the ask.
+        actual: Address,
+    },
+    /// The ask is older than the latest one seen from the miner.
+    #[error("storage ask of miner {miner} is stale, seq no: {seq_no}, latest: {latest}")]
+    StaleSeqNo {
+        /// The miner of the ask.
+        miner: Address,
+        /// The sequence number of the ask.
+        seq_no: u64,
+        /// The sequence number of the latest ask seen.
+        latest: u64,
+    },
+    /// Crypto error.
+    #[error("{0}")]
+    Crypto(#[from] CryptoError),
+    /// Datastore error.
+    #[error("{0}")]
+    DataStore(#[from] ipfs_datastore::DataStoreError),
+    /// CBOR decode error.
+    #[error("{0}")]
+    CborDecode(#[from] minicbor::decode::Error),
+}
+
+/// The storage ask, which is the current storage price of a miner.
+///
+/// See go-fil-markets/storagemarket/types.go for details.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StorageAsk {
+    /// The price per GiB per epoch.
+    #[serde(with = "bigint_json")]
+    pub price: TokenAmount,
+    /// The price per GiB per epoch for the verified deals.
+    #[serde(with = "bigint_json")]
+    pub verified_price: TokenAmount,
+    /// The min size of the piece.
+    pub min_piece_size: PaddedPieceSize,
+    /// The max size of the piece.
+    pub max_piece_size: PaddedPieceSize,
+    /// The miner address.
+    pub miner: Address,
+    /// The epoch when the ask is created.
+    pub timestamp: ChainEpoch,
+    /// The epoch when the ask expires.
+    pub expiry: ChainEpoch,
+    /// The sequence number of the ask, increased when the ask is updated.
+    pub seq_no: u64,
+}
+
+impl StorageAsk {
+    /// Return the bytes to be signed by the miner worker, which is the CBOR of the ask.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        minicbor::to_vec(self).expect("CBOR serialization of StorageAsk shouldn't fail; qed")
+    }
+
+    /// Sign the ask with the private key of the miner worker.
+    pub fn sign<K: AsRef<[u8]>>(
+        self,
+        ty: SignatureType,
+        privkey: K,
+    ) -> Result<SignedStorageAsk, CryptoError> {
+        let signature = Signature::sign(ty, privkey, self.signing_bytes())?;
+        Ok(SignedStorageAsk {
+            ask: self,
+            signature,
+        })
+    }
+}
+
+// Implement CBOR serialization for StorageAsk.
+impl encode::Encode for StorageAsk {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(8)?
+            .encode(BigIntRefWrapper::from(&self.price))?
+            .encode(BigIntRefWrapper::from(&self.verified_price))?
+            .u64(self.min_piece_size.0)?
+            .u64(self.max_piece_size.0)?
+            .encode(&self.miner)?
+            .i64(self.timestamp)?
+            .i64(self.expiry)?
+            .u64(self.seq_no)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for StorageAsk.
+impl<'b> decode::Decode<'b> for StorageAsk {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(8) {
+            return Err(decode::Error::Message("expected 8 fields of StorageAsk"));
+        }
+        Ok(StorageAsk {
+            price: d.decode::<BigIntWrapper>()?.into_inner(),
+            verified_price: d.decode::<BigIntWrapper>()?.into_inner(),
+            min_piece_size: PaddedPieceSize(d.u64()?),
+            max_piece_size: PaddedPieceSize(d.u64()?),
+            miner: d.decode()?,
+            timestamp: d.i64()?,
+            expiry: d.i64()?,
+            seq_no: d.u64()?,
+        })
+    }
+}
+
+/// The storage ask signed by the miner worker.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SignedStorageAsk {
+    /// The storage ask.
+    pub ask: StorageAsk,
+    /// The signature of the ask.
+    pub signature: Signature,
+}
+
+impl SignedStorageAsk {
+    /// Validate the signed ask queried from the `miner` against the miner worker and current epoch.
+    pub fn validate(
+        &self,
+        miner: &Address,
+        worker: &Address,
+        current: ChainEpoch,
+    ) -> Result<(), AskError> {
+        if &self.ask.miner != miner {
+            return Err(AskError::MinerMismatch {
+                expected: miner.clone(),
+                actual: self.ask.miner.clone(),
+            });
+        }
+        if self.ask.expiry <= current {
+            return Err(AskError::Expired {
+                miner: miner.clone(),
+                expiry: self.ask.expiry,
+                current,
+            });
+        }
+        self.verify(worker)
+    }
+
+    /// Verify the signature of the ask against the miner `worker`.
+    pub fn verify(&self, worker: &Address) -> Result<(), AskError> {
+        if !self.signature.verify(worker, self.ask.signing_bytes())? {
+            return Err(AskError::InvalidSignature(self.ask.miner.clone()));
+        }
+        Ok(())
+    }
+
+    /// Check the ask is not older than the one with the `latest` sequence number seen from
+    /// the same miner, the same ask may be queried more than once.
+    pub fn validate_seq_no(&self, latest: u64) -> Result<(), AskError> {
+        if self.ask.seq_no < latest {
+            return Err(AskError::StaleSeqNo {
+                miner: self.ask.miner.clone(),
+                seq_no: self.ask.seq_no,
+                latest,
+            });
+        }
+        Ok(())
+    }
+}
+
+// Implement CBOR serialization for SignedStorageAsk.
+impl encode::Encode for SignedStorageAsk {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?.encode(&self.ask)?.encode(&self.signature)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for SignedStorageAsk.
+impl<'b> decode::Decode<'b> for SignedStorageAsk {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of SignedStorageAsk",
+            ));
+        }
+        Ok(SignedStorageAsk {
+            ask: d.decode()?,
+            signature: d.decode()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_bigint::BigInt;
+    use plum_crypto::{PrivateKey, PublicKey};
+
+    fn new_ask(miner: Address, expiry: ChainEpoch) -> StorageAsk {
+        StorageAsk {
+            price: BigInt::from(500_000_000u64),
+            verified_price: BigInt::from(50_000_000u64),
+            min_piece_size: PaddedPieceSize(256),
+            max_piece_size: PaddedPieceSize(32 << 30),
+            miner,
+            timestamp: 100,
+            expiry,
+            seq_no: 0,
+        }
+    }
+
+    #[test]
+    fn test_signed_storage_ask() {
+        let privkey = PrivateKey::generate_secp256k1_privkey();
+        let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+        let worker = Address::new_secp256k1_addr(&pubkey).unwrap();
+        let miner = Address::new_id_addr(1000).unwrap();
+
+        let signed = new_ask(miner.clone(), 200)
+            .sign(SignatureType::Secp256k1, privkey.into_vec())
+            .unwrap();
+        let bytes = minicbor::to_vec(&signed).unwrap();
+        let decoded: SignedStorageAsk = minicbor::decode(&bytes).unwrap();
+        assert_eq!(decoded, signed);
+        // The truncated ask of a remote peer is an error instead of a panic.
+        assert!(minicbor::decode::<SignedStorageAsk>(&[0x82, 0x80, 0xf6]).is_err());
+
+        assert!(signed.validate(&miner, &worker, 150).is_ok());
+        assert!(matches!(
+            signed.validate(&miner, &worker, 200),
+            Err(AskError::Expired { .. })
+        ));
+        let other = Address::new_id_addr(1001).unwrap();
+        assert!(matches!(
+            signed.validate(&other, &worker, 150),
+            Err(AskError::MinerMismatch { .. })
+        ));
+
+        assert!(signed.validate_seq_no(0).is_ok());
+        assert!(matches!(
+            signed.validate_seq_no(1),
+            Err(AskError::StaleSeqNo { .. })
+        ));
+
+        let mut tampered = signed;
+        tampered.ask.price = BigInt::from(1u64);
+        assert!(matches!(
+            tampered.validate(&miner, &worker, 150),
+            Err(AskError::InvalidSignature(_))
+        ));
+    }
+}
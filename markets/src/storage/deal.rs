@@ -0,0 +1,369 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::convert::TryFrom;
+
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+
+use plum_types::DealId;
+
+use crate::fsm::StateMachine;
+
+/// The name of the state group of the provider storage deals.
+pub const PROVIDER_DEAL_FSM_NAME: &str = "provider-storage-deals";
+
+/// The status of a storage deal.
+///
+/// This ordering defines mappings to UInt in a way which MUST never change,
+/// see go-fil-markets/storagemarket/dealstatus.go for details.
+#[doc(hidden)]
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize_repr, Deserialize_repr)]
+pub enum StorageDealStatus {
+    Unknown = 0,
+    ProposalNotFound = 1,
+    ProposalRejected = 2,
+    ProposalAccepted = 3,
+    Staged = 4,
+    Sealing = 5,
+    Active = 6,
+    Failing = 7,
+    NotFound = 8,
+    FundsEnsured = 9,
+    WaitingForDataRequest = 10,
+    Validating = 11,
+    AcceptWait = 12,
+    Transferring = 13,
+    WaitingForData = 14,
+    VerifyData = 15,
+    EnsureProviderFunds = 16,
+    EnsureClientFunds = 17,
+    ProviderFunding = 18,
+    ClientFunding = 19,
+    Publish = 20,
+    Publishing = 21,
+    Error = 22,
+    Completed = 23,
+}
+
+impl TryFrom<u64> for StorageDealStatus {
+    type Error = &'static str;
+
+    fn try_from(value: u64) -> Result<Self, &'static str> {
+        use StorageDealStatus::*;
+        Ok(match value {
+            0 => Unknown,
+            1 => ProposalNotFound,
+            2 => ProposalRejected,
+            3 => ProposalAccepted,
+            4 => Staged,
+            5 => Sealing,
+            6 => Active,
+            7 => Failing,
+            8 => NotFound,
+            9 => FundsEnsured,
+            10 => WaitingForDataRequest,
+            11 => Validating,
+            12 => AcceptWait,
+            13 => Transferring,
+            14 => WaitingForData,
+            15 => VerifyData,
+            16 => EnsureProviderFunds,
+            17 => EnsureClientFunds,
+            18 => ProviderFunding,
+            19 => ClientFunding,
+            20 => Publish,
+            21 => Publishing,
+            22 => Error,
+            23 => Completed,
+            _ => return Err("unexpected storage deal status"),
+        })
+    }
+}
+
+// Implement CBOR serialization for StorageDealStatus.
+impl encode::Encode for StorageDealStatus {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.u64(*self as u64)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for StorageDealStatus.
+impl<'b> decode::Decode<'b> for StorageDealStatus {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let status = d.u64()?;
+        StorageDealStatus::try_from(status)
+            .map_err(|e| decode::Error::TypeMismatch(status as u8, e))
+    }
+}
+
+/// The persisted state of a storage deal on the provider side.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProviderDealState {
+    /// The status of the deal.
+    pub status: StorageDealStatus,
+    /// The on-chain deal id, available after the deal is published.
+    #[serde(rename = "DealID")]
+    pub deal_id: Option<DealId>,
+    /// The message describing why the deal is in the status.
+    pub message: String,
+}
+
+impl ProviderDealState {
+    /// Create a new state of a storage deal that has just been received.
+    pub fn new() -> Self {
+        Self {
+            status: StorageDealStatus::Unknown,
+            deal_id: None,
+            message: String::new(),
+        }
+    }
+
+    fn with_status(&self, status: StorageDealStatus) -> Self {
+        Self {
+            status,
+            ..self.clone()
+        }
+    }
+}
+
+impl Default for ProviderDealState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Implement CBOR serialization for ProviderDealState.
+impl encode::Encode for ProviderDealState {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(3)?
+            .encode(&self.status)?
+            .encode(&self.deal_id)?
+            .str(&self.message)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for ProviderDealState.
+impl<'b> decode::Decode<'b> for ProviderDealState {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(3) {
+            return Err(decode::Error::Message(
+                "expected 3 fields of ProviderDealState",
+            ));
+        }
+        Ok(ProviderDealState {
+            status: d.decode()?,
+            deal_id: d.decode()?,
+            message: d.str()?.to_owned(),
+        })
+    }
+}
+
+/// The events that drive the storage deal on the provider side.
+#[doc(hidden)]
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProviderDealEvent {
+    Open,
+    Validated,
+    Accepted,
+    Rejected(String),
+    DataTransferInitiated,
+    DataTransferCompleted,
+    DataVerified,
+    FundingInitiated,
+    FundsEnsured,
+    PublishInitiated,
+    DealPublished(DealId),
+    DealHandedOff,
+    DealActivated,
+    DealCompleted,
+    Failed(String),
+    CleanedUp,
+}
+
+/// The effects that should be run by the storage provider after the transitions.
+#[doc(hidden)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProviderDealEffect {
+    ValidateDeal,
+    DecideOnProposal,
+    SendResponse,
+    VerifyData,
+    EnsureProviderFunds,
+    WaitForFunding,
+    PublishDeal,
+    WaitForPublish,
+    HandoffDeal,
+    WaitForActivation,
+    WaitForExpiry,
+    FailDeal,
+}
+
+/// The state machine of the storage deal on the provider side.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ProviderDealFsm;
+
+impl StateMachine for ProviderDealFsm {
+    type State = ProviderDealState;
+    type Event = ProviderDealEvent;
+    type Effect = ProviderDealEffect;
+
+    fn transition(
+        &self,
+        state: &ProviderDealState,
+        event: &ProviderDealEvent,
+    ) -> Option<(ProviderDealState, Vec<ProviderDealEffect>)> {
+        use ProviderDealEffect as Effect;
+        use ProviderDealEvent as Event;
+        use StorageDealStatus as Status;
+
+        let next = match (state.status, event) {
+            (Status::Unknown, Event::Open) => state.with_status(Status::Validating),
+            (Status::Validating, Event::Validated) => state.with_status(Status::AcceptWait),
+            (Status::AcceptWait, Event::Accepted) => state.with_status(Status::WaitingForData),
+            (Status::Validating, Event::Rejected(reason))
+            | (Status::AcceptWait, Event::Rejected(reason)) => {
+                let mut next = state.with_status(Status::Failing);
+                next.message = reason.clone();
+                return Some((next, vec![Effect::SendResponse, Effect::FailDeal]));
+            }
+            (Status::WaitingForData, Event::DataTransferInitiated) => {
+                state.with_status(Status::Transferring)
+            }
+            (Status::Transferring, Event::DataTransferCompleted) => {
+                state.with_status(Status::VerifyData)
+            }
+            (Status::VerifyData, Event::DataVerified) => {
+                state.with_status(Status::EnsureProviderFunds)
+            }
+            (Status::EnsureProviderFunds, Event::FundingInitiated) => {
+                state.with_status(Status::ProviderFunding)
+            }
+            (Status::EnsureProviderFunds, Event::FundsEnsured)
+            | (Status::ProviderFunding, Event::FundsEnsured) => state.with_status(Status::Publish),
+            (Status::Publish, Event::PublishInitiated) => state.with_status(Status::Publishing),
+            (Status::Publishing, Event::DealPublished(deal_id)) => {
+                let mut next = state.with_status(Status::Staged);
+                next.deal_id = Some(*deal_id);
+                next
+            }
+            (Status::Staged, Event::DealHandedOff) => state.with_status(Status::Sealing),
+            (Status::Sealing, Event::DealActivated) => state.with_status(Status::Active),
+            (Status::Active, Event::DealCompleted) => state.with_status(Status::Completed),
+            (Status::Failing, Event::CleanedUp) => state.with_status(Status::Error),
+            (_, Event::Failed(err)) => {
+                let mut next = state.with_status(Status::Failing);
+                next.message = err.clone();
+                next
+            }
+            _ => return None,
+        };
+        let effects = self.on_enter(&next);
+        Some((next, effects))
+    }
+
+    fn on_enter(&self, state: &ProviderDealState) -> Vec<ProviderDealEffect> {
+        use ProviderDealEffect as Effect;
+        use StorageDealStatus as Status;
+
+        match state.status {
+            Status::Validating => vec![Effect::ValidateDeal],
+            Status::AcceptWait => vec![Effect::DecideOnProposal],
+            Status::WaitingForData => vec![Effect::SendResponse],
+            Status::VerifyData => vec![Effect::VerifyData],
+            Status::EnsureProviderFunds => vec![Effect::EnsureProviderFunds],
+            Status::ProviderFunding => vec![Effect::WaitForFunding],
+            Status::Publish => vec![Effect::PublishDeal],
+            Status::Publishing => vec![Effect::WaitForPublish],
+            Status::Staged => vec![Effect::HandoffDeal],
+            Status::Sealing => vec![Effect::WaitForActivation],
+            Status::Active => vec![Effect::WaitForExpiry],
+            Status::Failing => vec![Effect::FailDeal],
+            _ => vec![],
+        }
+    }
+
+    fn is_final(&self, state: &ProviderDealState) -> bool {
+        matches!(
+            state.status,
+            StorageDealStatus::Completed | StorageDealStatus::Error
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+
+    use crate::fsm::StateGroup;
+
+    #[test]
+    fn provider_deal_state_cbor_serde() {
+        let state = ProviderDealState {
+            status: StorageDealStatus::Staged,
+            deal_id: Some(7),
+            message: "ok".into(),
+        };
+        let ser = minicbor::to_vec(&state).unwrap();
+        assert_eq!(ser, vec![0x83, 0x04, 0x07, 0x62, 0x6f, 0x6b]);
+        let de = minicbor::decode::<ProviderDealState>(&ser).unwrap();
+        assert_eq!(de, state);
+    }
+
+    #[test]
+    fn provider_deal_flow() {
+        use ProviderDealEvent as Event;
+
+        let datastore = SyncDataStore::new(MapDataStore::new());
+        let mut group = StateGroup::new(ProviderDealFsm, datastore.clone(), PROVIDER_DEAL_FSM_NAME);
+        let id = "bafyreigxsnlgi5ynubx3fxnysdcq3tpo4p2y4ie2k3iibvxbgnxhd2wbvu";
+        assert!(group
+            .begin(id, ProviderDealState::new())
+            .unwrap()
+            .is_empty());
+
+        let events = vec![
+            (Event::Open, ProviderDealEffect::ValidateDeal),
+            (Event::Validated, ProviderDealEffect::DecideOnProposal),
+            (Event::Accepted, ProviderDealEffect::SendResponse),
+        ];
+        for (event, effect) in events {
+            assert_eq!(group.send(id, event).unwrap(), vec![effect]);
+        }
+        assert!(group.send(id, Event::DealActivated).is_err());
+
+        // crash and resume while transferring data.
+        group.send(id, Event::DataTransferInitiated).unwrap();
+        group.send(id, Event::DataTransferCompleted).unwrap();
+        let mut group = StateGroup::new(ProviderDealFsm, datastore, PROVIDER_DEAL_FSM_NAME);
+        assert_eq!(
+            group.restart().unwrap(),
+            vec![(id.to_string(), vec![ProviderDealEffect::VerifyData])]
+        );
+
+        group.send(id, Event::DataVerified).unwrap();
+        group.send(id, Event::FundsEnsured).unwrap();
+        group.send(id, Event::PublishInitiated).unwrap();
+        group.send(id, Event::DealPublished(100)).unwrap();
+        let record = group.get(id).unwrap().unwrap();
+        assert_eq!(record.state.status, StorageDealStatus::Staged);
+        assert_eq!(record.state.deal_id, Some(100));
+
+        assert_eq!(
+            group
+                .send(id, Event::Failed("handoff failed".into()))
+                .unwrap(),
+            vec![ProviderDealEffect::FailDeal]
+        );
+        assert_eq!(group.list_stuck(u64::MAX).unwrap().len(), 0);
+        group.fail(id, "cleanup failed").unwrap();
+        assert_eq!(group.list_stuck(u64::MAX).unwrap().len(), 1);
+        group.send(id, Event::CleanedUp).unwrap();
+        assert!(group.restart().unwrap().is_empty());
+    }
+}
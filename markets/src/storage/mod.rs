@@ -0,0 +1,18 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The storage market.
+
+mod ask;
+mod deal;
+mod store;
+mod validation;
+
+pub use self::ask::{AskError, SignedStorageAsk, StorageAsk};
+pub use self::deal::{
+    ProviderDealEffect, ProviderDealEvent, ProviderDealFsm, ProviderDealState, StorageDealStatus,
+    PROVIDER_DEAL_FSM_NAME,
+};
+pub use self::store::AskStore;
+pub use self::validation::{
+    DealFilter, DealValidationConfig, DealValidationError, DealValidationNode, DealValidator,
+};
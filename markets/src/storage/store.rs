@@ -0,0 +1,142 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use ipfs_datastore::{DataStore, Key};
+
+use plum_address::Address;
+use plum_crypto::SignatureType;
+use plum_types::ChainEpoch;
+
+use super::ask::{AskError, SignedStorageAsk, StorageAsk};
+
+/// The persisted storage asks of the provider, which the storage ask protocol serves from.
+///
+/// The latest signed ask of each miner is stored under `/storage/ask/<miner>`.
+pub struct AskStore<DS> {
+    datastore: DS,
+    namespace: Key,
+}
+
+impl<DS: DataStore> AskStore<DS> {
+    /// Create the ask store persisted into the datastore.
+    pub fn new(datastore: DS) -> Self {
+        Self {
+            datastore,
+            namespace: Key::with_namespaces(vec!["storage", "ask"]),
+        }
+    }
+
+    /// Get the latest signed ask of the miner, including the expired one.
+    pub fn get(&self, miner: &Address) -> Result<Option<SignedStorageAsk>, AskError> {
+        match self.datastore.get(&self.ask_key(miner))? {
+            Some(data) => Ok(Some(minicbor::decode(&data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Get the signed ask of the miner to answer the storage ask requests with,
+    /// `None` if the miner has no ask or the ask has expired at the `current` epoch.
+    pub fn current(
+        &self,
+        miner: &Address,
+        current: ChainEpoch,
+    ) -> Result<Option<SignedStorageAsk>, AskError> {
+        Ok(self
+            .get(miner)?
+            .filter(|signed| signed.ask.expiry > current))
+    }
+
+    /// Replace the ask of the miner with the `ask` signed by the private key of the miner
+    /// worker and persist it.
+    ///
+    /// The sequence number of the `ask` is set to the next one of the stored ask,
+    /// so that the clients can tell the updated ask from the stale ones.
+    pub fn set_ask<K: AsRef<[u8]>>(
+        &mut self,
+        mut ask: StorageAsk,
+        ty: SignatureType,
+        privkey: K,
+    ) -> Result<SignedStorageAsk, AskError> {
+        ask.seq_no = match self.get(&ask.miner)? {
+            Some(latest) => latest.ask.seq_no + 1,
+            None => 0,
+        };
+        let signed = ask.sign(ty, privkey)?;
+        let data = minicbor::to_vec(&signed)
+            .expect("CBOR serialization of SignedStorageAsk shouldn't fail; qed");
+        self.datastore.put(self.ask_key(&signed.ask.miner), data)?;
+        self.datastore.sync(&self.namespace)?;
+        debug!(
+            "[ask] miner {} updated the storage ask, seq no: {}",
+            signed.ask.miner, signed.ask.seq_no
+        );
+        Ok(signed)
+    }
+
+    fn ask_key(&self, miner: &Address) -> Key {
+        self.namespace.child(miner.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use plum_bigint::BigInt;
+    use plum_crypto::{PrivateKey, PublicKey};
+    use plum_piece::PaddedPieceSize;
+
+    fn new_ask(miner: Address, price: u64) -> StorageAsk {
+        StorageAsk {
+            price: BigInt::from(price),
+            verified_price: BigInt::from(price / 10),
+            min_piece_size: PaddedPieceSize(256),
+            max_piece_size: PaddedPieceSize(32 << 30),
+            miner,
+            timestamp: 100,
+            expiry: 200,
+            // Overwritten by the store.
+            seq_no: 100,
+        }
+    }
+
+    #[test]
+    fn test_ask_store() {
+        let privkey = PrivateKey::generate_secp256k1_privkey();
+        let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+        let worker = Address::new_secp256k1_addr(&pubkey).unwrap();
+        let privkey = privkey.into_vec();
+        let miner = Address::new_id_addr(1000).unwrap();
+
+        let datastore = SyncDataStore::new(MapDataStore::new());
+        let mut store = AskStore::new(datastore.clone());
+        assert!(store.get(&miner).unwrap().is_none());
+
+        let first = store
+            .set_ask(
+                new_ask(miner.clone(), 500),
+                SignatureType::Secp256k1,
+                &privkey,
+            )
+            .unwrap();
+        assert_eq!(first.ask.seq_no, 0);
+        let second = store
+            .set_ask(
+                new_ask(miner.clone(), 400),
+                SignatureType::Secp256k1,
+                &privkey,
+            )
+            .unwrap();
+        assert_eq!(second.ask.seq_no, 1);
+        assert!(second.validate(&miner, &worker, 150).is_ok());
+        assert!(first.validate_seq_no(second.ask.seq_no).is_err());
+
+        // reload from the datastore, the ask survives.
+        let store = AskStore::new(datastore);
+        assert_eq!(store.get(&miner).unwrap(), Some(second.clone()));
+        assert_eq!(store.current(&miner, 150).unwrap(), Some(second));
+        assert_eq!(store.current(&miner, 200).unwrap(), None);
+        let other = Address::new_id_addr(1001).unwrap();
+        assert_eq!(store.current(&other, 150).unwrap(), None);
+    }
+}
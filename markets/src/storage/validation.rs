@@ -0,0 +1,508 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::fmt;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+use plum_actor::{market::ClientDealProposal, EPOCH_IN_DAY};
+use plum_address::Address;
+use plum_bigint::BigInt;
+use plum_crypto::CryptoError;
+use plum_piece::PieceSizeError;
+use plum_sector::{SectorSize, StoragePower};
+use plum_types::{ChainEpoch, TokenAmount};
+
+const GIB: u64 = 1 << 30;
+
+/// The errors of validating a deal proposal, the deal should be rejected with the reason.
+#[derive(Debug, thiserror::Error)]
+pub enum DealValidationError {
+    /// The deal is proposed to another provider.
+    #[error("incorrect provider for deal, expected: {expected}, actual: {actual}")]
+    WrongProvider {
+        /// The address of this provider.
+        expected: Address,
+        /// The provider in the proposal.
+        actual: Address,
+    },
+    /// The proposal is not signed by the client.
+    #[error("invalid client signature of the deal proposal")]
+    InvalidSignature,
+    /// Crypto error.
+    #[error("{0}")]
+    Crypto(#[from] CryptoError),
+    /// Invalid piece size.
+    #[error("{0}")]
+    PieceSize(#[from] PieceSizeError),
+    /// The piece can't fit into a sector.
+    #[error("piece size {piece} exceeds sector size {sector}")]
+    PieceTooLarge {
+        /// The padded size of the piece.
+        piece: u64,
+        /// The sector size of the provider.
+        sector: SectorSize,
+    },
+    /// The duration of the deal overflows.
+    #[error("invalid deal epochs, start: {start}, end: {end}")]
+    InvalidEpochs {
+        /// The start epoch of the deal.
+        start: ChainEpoch,
+        /// The end epoch of the deal.
+        end: ChainEpoch,
+    },
+    /// The duration of the deal is out of the bounds.
+    #[error("deal duration {duration} out of bounds [{min}, {max}]")]
+    DurationOutOfBounds {
+        /// The duration of the deal.
+        duration: ChainEpoch,
+        /// The min duration accepted by the provider.
+        min: ChainEpoch,
+        /// The max duration accepted by the provider.
+        max: ChainEpoch,
+    },
+    /// The start epoch of the deal has elapsed.
+    #[error("deal start epoch {start} has already elapsed, current epoch: {current}")]
+    StartEpochElapsed {
+        /// The start epoch of the deal.
+        start: ChainEpoch,
+        /// The current epoch.
+        current: ChainEpoch,
+    },
+    /// The price of the deal is below the price floor of the provider.
+    #[error("storage price per epoch {price} less than asking price {min}")]
+    PriceTooLow {
+        /// The storage price per epoch of the deal.
+        price: TokenAmount,
+        /// The min storage price per epoch for the piece.
+        min: TokenAmount,
+    },
+    /// The client doesn't have enough available funds in the storage market.
+    #[error("client available funds {available} less than required {required}")]
+    InsufficientFunds {
+        /// The available funds of the client.
+        available: TokenAmount,
+        /// The funds required by the deal.
+        required: TokenAmount,
+    },
+    /// The verified deal is proposed by a client that is not verified.
+    #[error("client {0} is not a verified client")]
+    NotVerifiedClient(Address),
+    /// The client doesn't have enough datacap for the verified deal.
+    #[error("verified deal piece size {piece} exceeds client datacap {datacap}")]
+    InsufficientDataCap {
+        /// The padded size of the piece.
+        piece: u64,
+        /// The remaining datacap of the client.
+        datacap: StoragePower,
+    },
+    /// The deal is rejected by the user filter.
+    #[error("deal rejected by filter: {0}")]
+    Filtered(String),
+    /// The node can't provide the chain state for validating the deal.
+    #[error("node error: {0}")]
+    Node(String),
+}
+
+/// The chain state accessor required for validating the deal proposals.
+pub trait DealValidationNode {
+    /// Return the current epoch of the chain.
+    fn current_epoch(&self) -> Result<ChainEpoch, DealValidationError>;
+
+    /// Resolve the address into the key address that signs for it.
+    fn resolve_to_key_address(&self, addr: &Address) -> Result<Address, DealValidationError>;
+
+    /// Return the funds of the address in the storage market that haven't been locked.
+    fn market_available_balance(&self, addr: &Address) -> Result<TokenAmount, DealValidationError>;
+
+    /// Return the remaining datacap of the address, `None` if it's not a verified client.
+    fn verified_client_datacap(
+        &self,
+        addr: &Address,
+    ) -> Result<Option<StoragePower>, DealValidationError>;
+}
+
+/// The user-pluggable filter to decide whether to accept a deal.
+#[derive(Clone)]
+pub enum DealFilter {
+    /// Run the external command with the JSON of the deal proposal as the stdin,
+    /// the deal is accepted only if the command exits successfully.
+    Command(String),
+    /// Call the callback with the deal proposal, the deal is rejected with the returned reason.
+    Callback(Arc<dyn Fn(&ClientDealProposal) -> Result<(), String> + Send + Sync>),
+}
+
+impl fmt::Debug for DealFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DealFilter::Command(cmd) => f.debug_tuple("Command").field(cmd).finish(),
+            DealFilter::Callback(_) => f.debug_tuple("Callback").finish(),
+        }
+    }
+}
+
+impl DealFilter {
+    /// Check the deal proposal, return the reason if the deal is rejected.
+    pub fn check(&self, deal: &ClientDealProposal) -> Result<(), String> {
+        match self {
+            DealFilter::Command(cmd) => run_filter_command(cmd, deal),
+            DealFilter::Callback(callback) => callback(deal),
+        }
+    }
+}
+
+fn run_filter_command(cmd: &str, deal: &ClientDealProposal) -> Result<(), String> {
+    let mut args = cmd.split_whitespace();
+    let program = args.next().ok_or("empty deal filter command")?;
+    let input = serde_json::to_vec(deal).map_err(|e| e.to_string())?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("failed to run deal filter `{}`: {}", cmd, e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&input).map_err(|e| e.to_string())?;
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let reason = String::from_utf8_lossy(&output.stdout);
+        let reason = reason.trim();
+        if reason.is_empty() {
+            Err(format!(
+                "deal filter `{}` exited with {}",
+                cmd, output.status
+            ))
+        } else {
+            Err(reason.to_owned())
+        }
+    }
+}
+
+/// The provider config for accepting the deals.
+#[derive(Clone, Debug)]
+pub struct DealValidationConfig {
+    /// The sector size of the provider.
+    pub sector_size: SectorSize,
+    /// The min storage price per GiB per epoch.
+    pub price_per_gib: TokenAmount,
+    /// The min storage price per GiB per epoch for the verified deals.
+    pub verified_price_per_gib: TokenAmount,
+    /// The min duration of the deal in epochs.
+    pub min_duration: ChainEpoch,
+    /// The max duration of the deal in epochs.
+    pub max_duration: ChainEpoch,
+    /// The filter that is run after all the other checks passed.
+    pub filter: Option<DealFilter>,
+}
+
+impl Default for DealValidationConfig {
+    fn default() -> Self {
+        Self {
+            sector_size: 32 * GIB,
+            price_per_gib: BigInt::from(500_000_000u64),
+            verified_price_per_gib: BigInt::from(50_000_000u64),
+            min_duration: 180 * EPOCH_IN_DAY as ChainEpoch,
+            max_duration: 540 * EPOCH_IN_DAY as ChainEpoch,
+            filter: None,
+        }
+    }
+}
+
+/// The validator of the deal proposals on the provider side.
+pub struct DealValidator<N> {
+    provider: Address,
+    node: N,
+    config: DealValidationConfig,
+}
+
+impl<N: DealValidationNode> DealValidator<N> {
+    /// Create a new deal validator for the provider.
+    pub fn new(provider: Address, node: N, config: DealValidationConfig) -> Self {
+        Self {
+            provider,
+            node,
+            config,
+        }
+    }
+
+    /// Return the config of the validator.
+    pub fn config(&self) -> &DealValidationConfig {
+        &self.config
+    }
+
+    /// Validate the deal proposal, the deal should only be accepted if `Ok` is returned.
+    pub fn validate(&self, deal: &ClientDealProposal) -> Result<(), DealValidationError> {
+        let proposal = &deal.proposal;
+        if proposal.provider != self.provider {
+            return Err(DealValidationError::WrongProvider {
+                expected: self.provider.clone(),
+                actual: proposal.provider.clone(),
+            });
+        }
+
+        let client = self.node.resolve_to_key_address(&proposal.client)?;
+        if !deal
+            .client_signature
+            .verify(&client, proposal.signing_bytes())?
+        {
+            return Err(DealValidationError::InvalidSignature);
+        }
+
+        proposal.piece_size.validate()?;
+        if proposal.piece_size.0 > self.config.sector_size {
+            return Err(DealValidationError::PieceTooLarge {
+                piece: proposal.piece_size.0,
+                sector: self.config.sector_size,
+            });
+        }
+
+        let invalid_epochs = || DealValidationError::InvalidEpochs {
+            start: proposal.start_epoch,
+            end: proposal.end_epoch,
+        };
+        let duration = proposal.duration().ok_or_else(invalid_epochs)?;
+        if duration < self.config.min_duration || duration > self.config.max_duration {
+            return Err(DealValidationError::DurationOutOfBounds {
+                duration,
+                min: self.config.min_duration,
+                max: self.config.max_duration,
+            });
+        }
+
+        let current = self.node.current_epoch()?;
+        if proposal.start_epoch <= current {
+            return Err(DealValidationError::StartEpochElapsed {
+                start: proposal.start_epoch,
+                current,
+            });
+        }
+
+        let price_per_gib = if proposal.verified_deal {
+            &self.config.verified_price_per_gib
+        } else {
+            &self.config.price_per_gib
+        };
+        let min_price = price_per_gib * proposal.piece_size.0 / GIB;
+        if proposal.storage_price_per_epoch < min_price {
+            return Err(DealValidationError::PriceTooLow {
+                price: proposal.storage_price_per_epoch.clone(),
+                min: min_price,
+            });
+        }
+
+        let available = self.node.market_available_balance(&proposal.client)?;
+        let required = proposal
+            .client_balance_requirement()
+            .ok_or_else(invalid_epochs)?;
+        if available < required {
+            return Err(DealValidationError::InsufficientFunds {
+                available,
+                required,
+            });
+        }
+
+        if proposal.verified_deal {
+            let datacap = self
+                .node
+                .verified_client_datacap(&proposal.client)?
+                .ok_or_else(|| DealValidationError::NotVerifiedClient(proposal.client.clone()))?;
+            if datacap < BigInt::from(proposal.piece_size.0) {
+                return Err(DealValidationError::InsufficientDataCap {
+                    piece: proposal.piece_size.0,
+                    datacap,
+                });
+            }
+        }
+
+        if let Some(filter) = &self.config.filter {
+            filter.check(deal).map_err(DealValidationError::Filtered)?;
+        }
+
+        debug!(
+            "[deal] accept deal proposal (client: {}, piece: {}, size: {})",
+            proposal.client, proposal.piece_cid, proposal.piece_size.0
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::{Cid, Codec};
+    use multihash::Blake2b256;
+    use plum_actor::market::DealProposal;
+    use plum_crypto::{PrivateKey, PublicKey, Signature};
+    use plum_piece::PaddedPieceSize;
+
+    struct MockNode {
+        client_key: Address,
+        balance: TokenAmount,
+        datacap: Option<StoragePower>,
+    }
+
+    impl DealValidationNode for MockNode {
+        fn current_epoch(&self) -> Result<ChainEpoch, DealValidationError> {
+            Ok(100)
+        }
+
+        fn resolve_to_key_address(&self, _addr: &Address) -> Result<Address, DealValidationError> {
+            Ok(self.client_key.clone())
+        }
+
+        fn market_available_balance(
+            &self,
+            _addr: &Address,
+        ) -> Result<TokenAmount, DealValidationError> {
+            Ok(self.balance.clone())
+        }
+
+        fn verified_client_datacap(
+            &self,
+            _addr: &Address,
+        ) -> Result<Option<StoragePower>, DealValidationError> {
+            Ok(self.datacap.clone())
+        }
+    }
+
+    fn sign(privkey: &PrivateKey, proposal: DealProposal) -> ClientDealProposal {
+        let client_signature =
+            Signature::sign_secp256k1(privkey.clone().into_vec(), proposal.signing_bytes())
+                .unwrap();
+        ClientDealProposal {
+            proposal,
+            client_signature,
+        }
+    }
+
+    #[test]
+    fn test_validate_deal() {
+        let privkey = PrivateKey::generate_secp256k1_privkey();
+        let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+        let client_key = Address::new_secp256k1_addr(&pubkey).unwrap();
+        let provider = Address::new_id_addr(1000).unwrap();
+        let node = MockNode {
+            client_key,
+            balance: BigInt::from(10_000_000_000_000u64),
+            datacap: None,
+        };
+        let config = DealValidationConfig {
+            sector_size: 2048,
+            price_per_gib: BigInt::from(GIB),
+            min_duration: 100,
+            max_duration: 1000,
+            ..Default::default()
+        };
+        let validator = DealValidator::new(provider.clone(), node, config);
+
+        let proposal = DealProposal {
+            piece_cid: Cid::new_v1(Codec::Raw, Blake2b256::digest(b"piece")),
+            piece_size: PaddedPieceSize(2048),
+            verified_deal: false,
+            client: Address::new_id_addr(1001).unwrap(),
+            provider,
+            start_epoch: 200,
+            end_epoch: 500,
+            storage_price_per_epoch: BigInt::from(2048u64),
+            provider_collateral: BigInt::from(0u64),
+            client_collateral: BigInt::from(0u64),
+        };
+        assert!(validator
+            .validate(&sign(&privkey, proposal.clone()))
+            .is_ok());
+
+        let mut tampered = sign(&privkey, proposal.clone());
+        tampered.proposal.end_epoch = 600;
+        assert!(matches!(
+            validator.validate(&tampered),
+            Err(DealValidationError::InvalidSignature)
+        ));
+
+        let mut too_large = proposal.clone();
+        too_large.piece_size = PaddedPieceSize(4096);
+        assert!(matches!(
+            validator.validate(&sign(&privkey, too_large)),
+            Err(DealValidationError::PieceTooLarge { .. })
+        ));
+
+        let mut too_short = proposal.clone();
+        too_short.end_epoch = 250;
+        assert!(matches!(
+            validator.validate(&sign(&privkey, too_short)),
+            Err(DealValidationError::DurationOutOfBounds { .. })
+        ));
+
+        let mut overflow = proposal.clone();
+        overflow.start_epoch = ChainEpoch::MIN;
+        assert!(matches!(
+            validator.validate(&sign(&privkey, overflow)),
+            Err(DealValidationError::InvalidEpochs { .. })
+        ));
+
+        let mut elapsed = proposal.clone();
+        elapsed.start_epoch = 50;
+        assert!(matches!(
+            validator.validate(&sign(&privkey, elapsed)),
+            Err(DealValidationError::StartEpochElapsed { .. })
+        ));
+
+        let mut cheap = proposal.clone();
+        cheap.storage_price_per_epoch = BigInt::from(2047u64);
+        assert!(matches!(
+            validator.validate(&sign(&privkey, cheap)),
+            Err(DealValidationError::PriceTooLow { .. })
+        ));
+
+        let mut verified = proposal.clone();
+        verified.verified_deal = true;
+        assert!(matches!(
+            validator.validate(&sign(&privkey, verified)),
+            Err(DealValidationError::NotVerifiedClient(_))
+        ));
+
+        let mut expensive = proposal;
+        expensive.client_collateral = BigInt::from(10_000_000_000_000u64);
+        assert!(matches!(
+            validator.validate(&sign(&privkey, expensive)),
+            Err(DealValidationError::InsufficientFunds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_deal_filter() {
+        let filter = DealFilter::Callback(Arc::new(|deal: &ClientDealProposal| {
+            if deal.proposal.verified_deal {
+                Ok(())
+            } else {
+                Err("only verified deals are accepted".to_owned())
+            }
+        }));
+        let privkey = PrivateKey::generate_secp256k1_privkey();
+        let deal = sign(
+            &privkey,
+            DealProposal {
+                piece_cid: Cid::new_v1(Codec::Raw, Blake2b256::digest(b"piece")),
+                piece_size: PaddedPieceSize(2048),
+                verified_deal: false,
+                client: Address::new_id_addr(1001).unwrap(),
+                provider: Address::new_id_addr(1000).unwrap(),
+                start_epoch: 200,
+                end_epoch: 500,
+                storage_price_per_epoch: BigInt::from(0u64),
+                provider_collateral: BigInt::from(0u64),
+                client_collateral: BigInt::from(0u64),
+            },
+        );
+        assert!(filter.check(&deal).is_err());
+        assert!(DealFilter::Command("true".to_owned()).check(&deal).is_ok());
+        assert!(DealFilter::Command("false".to_owned())
+            .check(&deal)
+            .is_err());
+    }
+}
@@ -0,0 +1,285 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+use cid::Cid;
+use libp2p_core::PeerId;
+
+use crate::error::{BitswapError, Result};
+
+/// The default max number of the pending requests of a peer.
+pub const DEFAULT_MAX_QUEUE_LEN: usize = 1024;
+/// The default max upload bandwidth to a peer in bytes per second.
+pub const DEFAULT_MAX_BYTES_PER_SEC: u64 = 1 << 20;
+/// The default max burst of the upload to a peer in bytes.
+pub const DEFAULT_BURST_BYTES: u64 = 2 << 20;
+
+/// The config of the decision engine, which limits the resources a single peer can use.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DecisionConfig {
+    /// The max number of the pending requests of a peer.
+    pub max_queue_len: usize,
+    /// The max upload bandwidth to a peer in bytes per second.
+    pub max_bytes_per_sec: u64,
+    /// The max burst of the upload to a peer in bytes.
+    pub burst_bytes: u64,
+}
+
+impl Default for DecisionConfig {
+    fn default() -> Self {
+        Self {
+            max_queue_len: DEFAULT_MAX_QUEUE_LEN,
+            max_bytes_per_sec: DEFAULT_MAX_BYTES_PER_SEC,
+            burst_bytes: DEFAULT_BURST_BYTES,
+        }
+    }
+}
+
+/// The token bucket limiting the bandwidth.
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    rate: u64,
+    capacity: u64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl RateLimiter {
+    /// Create a full token bucket with the rate in bytes per second and the capacity in bytes.
+    pub fn new(rate: u64, capacity: u64, now: Instant) -> Self {
+        Self {
+            rate,
+            capacity,
+            tokens: capacity as f64,
+            last_refill: now,
+        }
+    }
+
+    /// Try to consume `amount` bytes, return false if there are not enough tokens.
+    ///
+    /// The amount larger than the capacity is allowed when the bucket is full,
+    /// the tokens go negative and must be paid back before the next consumption.
+    pub fn try_consume(&mut self, amount: u64, now: Instant) -> bool {
+        self.refill(now);
+        let required = amount.min(self.capacity) as f64;
+        if self.tokens < required {
+            return false;
+        }
+        self.tokens -= amount as f64;
+        true
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now <= self.last_refill {
+            return;
+        }
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate as f64).min(self.capacity as f64);
+        self.last_refill = now;
+    }
+}
+
+/// The task of sending a block to a peer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Task {
+    /// The CID of the block.
+    pub cid: Cid,
+    /// The priority of the task, the higher the more urgent.
+    pub priority: i32,
+    /// The size of the block in bytes.
+    pub size: u64,
+}
+
+struct Ledger {
+    tasks: Vec<Task>,
+    limiter: RateLimiter,
+}
+
+/// The decision layer of the bitswap engine, deciding which block should be sent to which peer.
+///
+/// The peers are served in a round-robin way, and the pending requests and the upload bandwidth
+/// of each peer are limited, to prevent a single peer from monopolizing the upload capacity.
+pub struct DecisionEngine {
+    config: DecisionConfig,
+    ledgers: HashMap<PeerId, Ledger>,
+    round_robin: VecDeque<PeerId>,
+}
+
+impl DecisionEngine {
+    /// Create a new decision engine with the config.
+    pub fn new(config: DecisionConfig) -> Self {
+        Self {
+            config,
+            ledgers: HashMap::new(),
+            round_robin: VecDeque::new(),
+        }
+    }
+
+    /// Queue a task for the peer, the task of the same CID is replaced.
+    pub fn push_task(&mut self, peer: &PeerId, task: Task, now: Instant) -> Result<()> {
+        let config = self.config;
+        let round_robin = &mut self.round_robin;
+        let ledger = self.ledgers.entry(peer.clone()).or_insert_with(|| {
+            round_robin.push_back(peer.clone());
+            Ledger {
+                tasks: vec![],
+                limiter: RateLimiter::new(config.max_bytes_per_sec, config.burst_bytes, now),
+            }
+        });
+
+        if let Some(existing) = ledger.tasks.iter_mut().find(|t| t.cid == task.cid) {
+            *existing = task;
+            return Ok(());
+        }
+        if ledger.tasks.len() >= config.max_queue_len {
+            return Err(BitswapError::QueueFull {
+                peer: peer.clone(),
+                max: config.max_queue_len,
+            });
+        }
+        ledger.tasks.push(task);
+        Ok(())
+    }
+
+    /// Cancel the task of the `cid` for the peer, return false if there is no such task.
+    pub fn cancel_task(&mut self, peer: &PeerId, cid: &Cid) -> bool {
+        match self.ledgers.get_mut(peer) {
+            Some(ledger) => {
+                let len = ledger.tasks.len();
+                ledger.tasks.retain(|task| &task.cid != cid);
+                ledger.tasks.len() != len
+            }
+            None => false,
+        }
+    }
+
+    /// Pop the next task to run, which is the most urgent task of the next peer
+    /// that is within its bandwidth limit.
+    pub fn pop_task(&mut self, now: Instant) -> Option<(PeerId, Task)> {
+        for _ in 0..self.round_robin.len() {
+            let peer = self.round_robin.pop_front()?;
+            self.round_robin.push_back(peer.clone());
+
+            let ledger = self
+                .ledgers
+                .get_mut(&peer)
+                .expect("ledger of the peer in the round robin must exist; qed");
+            let index = match ledger
+                .tasks
+                .iter()
+                .enumerate()
+                .max_by(|(i, a), (j, b)| a.priority.cmp(&b.priority).then_with(|| j.cmp(i)))
+            {
+                Some((index, _)) => index,
+                None => continue,
+            };
+            if !ledger.limiter.try_consume(ledger.tasks[index].size, now) {
+                trace!("[bitswap] Peer {} exceeds the bandwidth limit", peer);
+                continue;
+            }
+            let task = ledger.tasks.remove(index);
+            return Some((peer, task));
+        }
+        None
+    }
+
+    /// Return the number of the pending tasks of the peer.
+    pub fn queue_len(&self, peer: &PeerId) -> usize {
+        self.ledgers
+            .get(peer)
+            .map(|ledger| ledger.tasks.len())
+            .unwrap_or_default()
+    }
+
+    /// Remove the peer and all of its pending tasks, e.g. when it's disconnected.
+    pub fn remove_peer(&mut self, peer: &PeerId) {
+        if self.ledgers.remove(peer).is_some() {
+            self.round_robin.retain(|p| p != peer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    use cid::Codec;
+    use multihash::Blake2b256;
+
+    fn new_task(data: &[u8], priority: i32, size: u64) -> Task {
+        Task {
+            cid: Cid::new_v1(Codec::Raw, Blake2b256::digest(data)),
+            priority,
+            size,
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let now = Instant::now();
+        let mut limiter = RateLimiter::new(100, 200, now);
+        assert!(limiter.try_consume(150, now));
+        assert!(!limiter.try_consume(100, now));
+        assert!(limiter.try_consume(100, now + Duration::from_millis(500)));
+        // larger than the capacity, only when the bucket is full.
+        assert!(!limiter.try_consume(500, now + Duration::from_millis(500)));
+        assert!(limiter.try_consume(500, now + Duration::from_secs(3)));
+        assert!(!limiter.try_consume(1, now + Duration::from_secs(5)));
+        assert!(limiter.try_consume(1, now + Duration::from_secs(7)));
+    }
+
+    #[test]
+    fn test_decision_engine() {
+        let now = Instant::now();
+        let config = DecisionConfig {
+            max_queue_len: 2,
+            max_bytes_per_sec: 100,
+            burst_bytes: 100,
+        };
+        let mut engine = DecisionEngine::new(config);
+        let greedy = PeerId::random();
+        let other = PeerId::random();
+
+        engine
+            .push_task(&greedy, new_task(b"a", 1, 60), now)
+            .unwrap();
+        engine
+            .push_task(&greedy, new_task(b"b", 5, 60), now)
+            .unwrap();
+        // replace the task of the same cid.
+        engine
+            .push_task(&greedy, new_task(b"b", 3, 60), now)
+            .unwrap();
+        assert!(matches!(
+            engine.push_task(&greedy, new_task(b"c", 1, 60), now),
+            Err(BitswapError::QueueFull { max: 2, .. })
+        ));
+        engine
+            .push_task(&other, new_task(b"d", 1, 60), now)
+            .unwrap();
+
+        // round robin, the most urgent task first.
+        let (peer, task) = engine.pop_task(now).unwrap();
+        assert_eq!((peer, task.priority), (greedy.clone(), 3));
+        let (peer, _) = engine.pop_task(now).unwrap();
+        assert_eq!(peer, other);
+        // the greedy peer is out of bandwidth.
+        assert!(engine.pop_task(now).is_none());
+        assert_eq!(engine.queue_len(&greedy), 1);
+
+        let (peer, task) = engine.pop_task(now + Duration::from_secs(1)).unwrap();
+        assert_eq!((peer, task.priority), (greedy.clone(), 1));
+
+        engine
+            .push_task(&greedy, new_task(b"e", 1, 60), now)
+            .unwrap();
+        assert!(engine.cancel_task(&greedy, &new_task(b"e", 1, 60).cid));
+        assert!(!engine.cancel_task(&other, &new_task(b"e", 1, 60).cid));
+        engine.remove_peer(&greedy);
+        assert_eq!(engine.queue_len(&greedy), 0);
+        assert!(engine.pop_task(now + Duration::from_secs(10)).is_none());
+    }
+}
@@ -0,0 +1,25 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use libp2p_core::PeerId;
+
+/// Type alias to use this module's [`BitswapError`] type in a `Result`.
+pub type Result<T> = std::result::Result<T, BitswapError>;
+
+/// Errors generated from the bitswap.
+#[derive(Debug, thiserror::Error)]
+pub enum BitswapError {
+    /// Datastore error.
+    #[error("{0}")]
+    DataStore(#[from] ipfs_datastore::DataStoreError),
+    /// CBOR decode error.
+    #[error("{0}")]
+    CborDecode(#[from] minicbor::decode::Error),
+    /// The request queue of the peer is full.
+    #[error("request queue of peer {peer} is full, max length: {max}")]
+    QueueFull {
+        /// The peer that sends the requests.
+        peer: PeerId,
+        /// The max length of the queue.
+        max: usize,
+    },
+}
@@ -0,0 +1,19 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The bitswap want-list and the decision engine.
+
+#![deny(missing_docs)]
+
+#[macro_use]
+extern crate log;
+
+mod decision;
+mod error;
+mod wantlist;
+
+pub use self::decision::{
+    DecisionConfig, DecisionEngine, RateLimiter, Task, DEFAULT_BURST_BYTES,
+    DEFAULT_MAX_BYTES_PER_SEC, DEFAULT_MAX_QUEUE_LEN,
+};
+pub use self::error::{BitswapError, Result};
+pub use self::wantlist::{PersistentWantList, WantEntry, WantList, WantType};
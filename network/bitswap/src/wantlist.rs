@@ -0,0 +1,280 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use cid::Cid;
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use ipfs_datastore::{DataStore, Key};
+
+use crate::error::BitswapError;
+
+/// The type of the want.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WantType {
+    /// Want the block.
+    Block = 0,
+    /// Want to know whether the peer has the block.
+    Have = 1,
+}
+
+/// An entry of the want-list.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WantEntry {
+    /// The CID of the wanted block.
+    pub cid: Cid,
+    /// The priority of the want, the higher the more urgent.
+    pub priority: i32,
+    /// The type of the want.
+    pub want_type: WantType,
+}
+
+// Implement CBOR serialization for WantEntry.
+impl encode::Encode for WantEntry {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(3)?
+            .encode(&self.cid)?
+            .i32(self.priority)?
+            .u8(self.want_type as u8)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for WantEntry.
+impl<'b> decode::Decode<'b> for WantEntry {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(3) {
+            return Err(decode::Error::Message("expected 3 fields of WantEntry"));
+        }
+        Ok(WantEntry {
+            cid: d.decode()?,
+            priority: d.i32()?,
+            want_type: match d.u8()? {
+                0 => WantType::Block,
+                1 => WantType::Have,
+                _ => return Err(decode::Error::Message("unknown want type")),
+            },
+        })
+    }
+}
+
+/// The list of the blocks wanted by the local node.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WantList {
+    entries: HashMap<Cid, WantEntry>,
+}
+
+impl WantList {
+    /// Create an empty want-list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the want of the `cid`, return false if the same want already exists.
+    ///
+    /// The want of a block overrides the want-have of the same CID.
+    pub fn add(&mut self, cid: Cid, priority: i32, want_type: WantType) -> bool {
+        if let Some(entry) = self.entries.get_mut(&cid) {
+            if entry.priority == priority && entry.want_type == want_type {
+                return false;
+            }
+            // Don't downgrade the want-block to the want-have.
+            if entry.want_type == WantType::Block && want_type == WantType::Have {
+                return false;
+            }
+            entry.priority = priority;
+            entry.want_type = want_type;
+            return true;
+        }
+        self.entries.insert(
+            cid.clone(),
+            WantEntry {
+                cid,
+                priority,
+                want_type,
+            },
+        );
+        true
+    }
+
+    /// Remove the want of the `cid`, return false if the `cid` is not wanted.
+    pub fn remove(&mut self, cid: &Cid) -> bool {
+        self.entries.remove(cid).is_some()
+    }
+
+    /// Return the want of the `cid`.
+    pub fn get(&self, cid: &Cid) -> Option<&WantEntry> {
+        self.entries.get(cid)
+    }
+
+    /// Return true if the `cid` is wanted.
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.entries.contains_key(cid)
+    }
+
+    /// Return the wants ordered by the priority, the most urgent one first.
+    pub fn entries(&self) -> Vec<&WantEntry> {
+        let mut entries = self.entries.values().collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then_with(|| a.cid.to_bytes().cmp(&b.cid.to_bytes()))
+        });
+        entries
+    }
+
+    /// Return the number of the wants.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if nothing is wanted.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// Implement CBOR serialization for WantList.
+impl encode::Encode for WantList {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        let entries = self.entries();
+        e.array(entries.len() as u64)?;
+        for entry in entries {
+            e.encode(entry)?;
+        }
+        e.ok()
+    }
+}
+
+// Implement CBOR deserialization for WantList.
+impl<'b> decode::Decode<'b> for WantList {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        let entries = d.decode::<Vec<WantEntry>>()?;
+        Ok(WantList {
+            entries: entries
+                .into_iter()
+                .map(|entry| (entry.cid.clone(), entry))
+                .collect(),
+        })
+    }
+}
+
+/// The want-list persisted into the datastore, so that the pending fetches
+/// are not lost after restart.
+///
+/// The want-list is stored under `/bitswap/wantlist`.
+pub struct PersistentWantList<DS> {
+    wantlist: WantList,
+    datastore: DS,
+    key: Key,
+}
+
+impl<DS: DataStore> PersistentWantList<DS> {
+    /// Load the want-list from the datastore, an empty want-list is created if there is none.
+    pub fn load(datastore: DS) -> Result<Self, BitswapError> {
+        let key = Key::with_namespaces(vec!["bitswap", "wantlist"]);
+        let wantlist = match datastore.get(&key)? {
+            Some(data) => minicbor::decode(&data)?,
+            None => WantList::new(),
+        };
+        debug!("[bitswap] Loaded {} wants", wantlist.len());
+        Ok(Self {
+            wantlist,
+            datastore,
+            key,
+        })
+    }
+
+    /// Return the in-memory want-list.
+    pub fn wantlist(&self) -> &WantList {
+        &self.wantlist
+    }
+
+    /// Add the want of the `cid` and persist the want-list if it's changed,
+    /// return false if the same want already exists.
+    pub fn add(
+        &mut self,
+        cid: Cid,
+        priority: i32,
+        want_type: WantType,
+    ) -> Result<bool, BitswapError> {
+        if !self.wantlist.add(cid, priority, want_type) {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    /// Remove the want of the `cid` and persist the want-list if it's changed,
+    /// return false if the `cid` is not wanted.
+    pub fn remove(&mut self, cid: &Cid) -> Result<bool, BitswapError> {
+        if !self.wantlist.remove(cid) {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<(), BitswapError> {
+        let data = minicbor::to_vec(&self.wantlist)
+            .expect("CBOR serialization of WantList shouldn't fail; qed");
+        self.datastore.put(self.key.clone(), data)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::Codec;
+    use ipfs_datastore::MapDataStore;
+    use multihash::Blake2b256;
+
+    fn new_cid(data: &[u8]) -> Cid {
+        Cid::new_v1(Codec::Raw, Blake2b256::digest(data))
+    }
+
+    #[test]
+    fn test_wantlist() {
+        let mut wantlist = WantList::new();
+        assert!(wantlist.add(new_cid(b"a"), 1, WantType::Have));
+        assert!(wantlist.add(new_cid(b"b"), 5, WantType::Block));
+        assert!(!wantlist.add(new_cid(b"b"), 5, WantType::Block));
+        // upgrade want-have to want-block, but not downgrade.
+        assert!(wantlist.add(new_cid(b"a"), 1, WantType::Block));
+        assert!(!wantlist.add(new_cid(b"a"), 1, WantType::Have));
+        assert_eq!(
+            wantlist.get(&new_cid(b"a")).unwrap().want_type,
+            WantType::Block
+        );
+
+        let entries = wantlist.entries();
+        assert_eq!(entries[0].cid, new_cid(b"b"));
+        assert_eq!(entries[1].cid, new_cid(b"a"));
+
+        let bytes = minicbor::to_vec(&wantlist).unwrap();
+        assert_eq!(minicbor::decode::<WantList>(&bytes).unwrap(), wantlist);
+        // A truncated entry.
+        assert!(minicbor::decode::<WantEntry>(&[0x82, 0x00, 0x00]).is_err());
+
+        assert!(wantlist.remove(&new_cid(b"a")));
+        assert!(!wantlist.remove(&new_cid(b"a")));
+        assert_eq!(wantlist.len(), 1);
+    }
+
+    #[test]
+    fn test_persistent_wantlist() {
+        let datastore = MapDataStore::new();
+        let mut wantlist = PersistentWantList::load(datastore.clone()).unwrap();
+        assert!(wantlist.wantlist().is_empty());
+        assert!(wantlist.add(new_cid(b"a"), 1, WantType::Block).unwrap());
+        assert!(wantlist.add(new_cid(b"b"), 2, WantType::Have).unwrap());
+        assert!(wantlist.remove(&new_cid(b"b")).unwrap());
+
+        // reload after restart.
+        let wantlist = PersistentWantList::load(wantlist.datastore).unwrap();
+        assert_eq!(wantlist.wantlist().len(), 1);
+        assert!(wantlist.wantlist().contains(&new_cid(b"a")));
+    }
+}
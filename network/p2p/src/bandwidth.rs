@@ -0,0 +1,215 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use libp2p::core::PeerId;
+use libp2p::request_response::{ProtocolName, RequestResponseCodec};
+
+use plum_peermgr::{BandwidthCounter, Direction};
+
+/// The protocol ID of gossipsub, under which the gossip payloads are accounted.
+pub const GOSSIPSUB_PROTOCOL_ID: &str = "/meshsub/1.0.0";
+
+#[derive(Clone, Debug)]
+enum Target {
+    Peer(PeerId),
+    Protocol(String),
+}
+
+/// The stream counting the bytes read and written, for the peer or the protocol.
+pub struct MeteredStream<S> {
+    inner: S,
+    counter: Arc<BandwidthCounter>,
+    target: Target,
+}
+
+impl<S> MeteredStream<S> {
+    /// Create a stream counting the traffic with the peer, e.g. the secured connection.
+    pub fn with_peer(inner: S, peer_id: PeerId, counter: Arc<BandwidthCounter>) -> Self {
+        Self {
+            inner,
+            counter,
+            target: Target::Peer(peer_id),
+        }
+    }
+
+    /// Create a stream counting the traffic of the protocol, e.g. the substream of a request.
+    pub fn with_protocol(inner: S, protocol: String, counter: Arc<BandwidthCounter>) -> Self {
+        Self {
+            inner,
+            counter,
+            target: Target::Protocol(protocol),
+        }
+    }
+
+    fn record(&self, direction: Direction, bytes: usize) {
+        if bytes == 0 {
+            return;
+        }
+        match &self.target {
+            Target::Peer(peer_id) => self.counter.record_peer(peer_id, direction, bytes as u64),
+            Target::Protocol(protocol) => {
+                self.counter
+                    .record_protocol(protocol, direction, bytes as u64)
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for MeteredStream<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let result = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(read)) = &result {
+            this.record(Direction::Inbound, *read);
+        }
+        result
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for MeteredStream<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let result = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = &result {
+            this.record(Direction::Outbound, *written);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// The request-response codec counting the traffic of the protocol of the inner codec.
+#[derive(Clone)]
+pub struct MeteredCodec<C> {
+    inner: C,
+    counter: Arc<BandwidthCounter>,
+}
+
+impl<C> MeteredCodec<C> {
+    /// Wrap the codec with the bandwidth counter.
+    pub fn new(inner: C, counter: Arc<BandwidthCounter>) -> Self {
+        Self { inner, counter }
+    }
+
+    fn meter<S>(&self, io: S, protocol: &impl ProtocolName) -> MeteredStream<S> {
+        let protocol = String::from_utf8_lossy(protocol.protocol_name()).into_owned();
+        MeteredStream::with_protocol(io, protocol, self.counter.clone())
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> RequestResponseCodec for MeteredCodec<C>
+where
+    C: RequestResponseCodec + Send,
+    C::Protocol: Sync,
+{
+    type Protocol = C::Protocol;
+    type Request = C::Request;
+    type Response = C::Response;
+
+    async fn read_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut io = self.meter(io, protocol);
+        self.inner.read_request(protocol, &mut io).await
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut io = self.meter(io, protocol);
+        self.inner.read_response(protocol, &mut io).await
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let mut io = self.meter(io, protocol);
+        self.inner.write_request(protocol, &mut io, req).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let mut io = self.meter(io, protocol);
+        self.inner.write_response(protocol, &mut io, res).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::io::{AsyncReadExt, AsyncWriteExt, Cursor};
+
+    #[test]
+    fn test_metered_stream() {
+        let counter = Arc::new(BandwidthCounter::new());
+        let peer_id = PeerId::random();
+        let mut stream =
+            MeteredStream::with_peer(Cursor::new(vec![0u8; 10]), peer_id.clone(), counter.clone());
+        futures::executor::block_on(async {
+            let mut buf = [0u8; 4];
+            stream.read_exact(&mut buf).await.unwrap();
+            stream.write_all(&[1u8; 3]).await.unwrap();
+        });
+        let stats = counter.peer_stats(&peer_id).unwrap();
+        assert_eq!((stats.total_in, stats.total_out), (4, 3));
+        assert_eq!(counter.totals().total_in, 4);
+
+        let mut stream = MeteredStream::with_protocol(
+            Cursor::new(vec![0u8; 10]),
+            GOSSIPSUB_PROTOCOL_ID.to_string(),
+            counter.clone(),
+        );
+        futures::executor::block_on(async {
+            let mut buf = vec![];
+            stream.read_to_end(&mut buf).await.unwrap();
+        });
+        assert_eq!(counter.by_protocol()[GOSSIPSUB_PROTOCOL_ID].total_in, 10);
+        // The protocol traffic is not counted in total.
+        assert_eq!(counter.totals().total_in, 4);
+    }
+}
@@ -1,8 +1,11 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
+use cid::Cid;
 use libp2p::{
     core::{identity::Keypair, PeerId},
     gossipsub::{
@@ -10,20 +13,49 @@ use libp2p::{
         Topic, TopicHash,
     },
     identify::{Identify, IdentifyEvent},
-    kad::{record::store::MemoryStore, Kademlia, KademliaEvent},
+    kad::{
+        record::{store, store::MemoryStore, Key},
+        GetProvidersError, GetProvidersOk, Kademlia, KademliaEvent, QueryId, QueryResult,
+    },
     mdns::{Mdns, MdnsEvent},
     ping::{Ping, PingEvent, PingFailure, PingSuccess},
     request_response::{
-        ProtocolSupport, RequestId, RequestResponse, RequestResponseConfig, RequestResponseEvent,
-        RequestResponseMessage, ResponseChannel,
+        ProtocolName, ProtocolSupport, RequestId, RequestResponse, RequestResponseConfig,
+        RequestResponseEvent, RequestResponseMessage, ResponseChannel,
+    },
+    swarm::{
+        DialPeerCondition, NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters,
     },
-    swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters},
     NetworkBehaviour,
 };
 
+use plum_address::Address;
+use plum_markets::storage::AskError;
+use plum_peermgr::{
+    BandwidthCounter, Direction, DisconnectReason, DisconnectTracker, IdentifyInfo, LatencyTracker,
+    PeerBook, DEFAULT_LATENCY_ALPHA,
+};
+use plum_types::ChainEpoch;
+
+use crate::bandwidth::{MeteredCodec, GOSSIPSUB_PROTOCOL_ID};
+
 use crate::config::Libp2pConfig;
+use crate::protocol::{AskCodec, AskProtocolName, AskRequest, AskResponse};
 use crate::protocol::{BlockSyncCodec, BlockSyncProtocolName, BlockSyncRequest, BlockSyncResponse};
+use crate::protocol::{
+    DisconnectCodec, DisconnectProtocolName, DisconnectRequest, DisconnectResponse,
+};
 use crate::protocol::{HelloCodec, HelloProtocolName, HelloRequest, HelloResponse};
+use crate::protocol::{PexCodec, PexPeer, PexProtocolName, PexRequest, PexResponse, MAX_PEX_PEERS};
+use crate::seen::SeenCache;
+
+/// The agent version reported to the other peers over the identify protocol.
+const AGENT_VERSION: &str = concat!("plum/", env!("CARGO_PKG_VERSION"));
+
+/// The peers not seen within the TTL are removed from the address book, unless connected.
+const PEER_BOOK_TTL: Duration = Duration::from_secs(60 * 60);
+/// The interval of pruning the address book.
+const PEER_BOOK_PRUNE_INTERVAL: Duration = Duration::from_secs(60);
 
 /// The behaviour for the network. Allows customizing the swarm.
 #[derive(NetworkBehaviour)]
@@ -34,12 +66,53 @@ pub struct Behaviour {
     mdns: Mdns,
     kademlia: Kademlia<MemoryStore>,
     gossipsub: Gossipsub,
-    hello: RequestResponse<HelloCodec>,
-    blocksync: RequestResponse<BlockSyncCodec>,
+    hello: RequestResponse<MeteredCodec<HelloCodec>>,
+    blocksync: RequestResponse<MeteredCodec<BlockSyncCodec>>,
+    ask: RequestResponse<MeteredCodec<AskCodec>>,
+    pex: RequestResponse<MeteredCodec<PexCodec>>,
+    disconnect: RequestResponse<MeteredCodec<DisconnectCodec>>,
     #[behaviour(ignore)]
     events: Vec<BehaviourEvent>,
     #[behaviour(ignore)]
     peers: HashSet<PeerId>,
+    #[behaviour(ignore)]
+    provider_queries: HashMap<QueryId, Cid>,
+    #[behaviour(ignore)]
+    peer_book: PeerBook,
+    #[behaviour(ignore)]
+    last_prune: Instant,
+    #[behaviour(ignore)]
+    latencies: LatencyTracker,
+    #[behaviour(ignore)]
+    seen: SeenCache,
+    #[behaviour(ignore)]
+    bandwidth: Arc<BandwidthCounter>,
+    #[behaviour(ignore)]
+    local_peer_id: PeerId,
+    #[behaviour(ignore)]
+    pex_protocol: PexProtocolName,
+    #[behaviour(ignore)]
+    pex_max_peers: u64,
+    #[behaviour(ignore)]
+    pex_requested: HashSet<PeerId>,
+    #[behaviour(ignore)]
+    pending_dials: VecDeque<PeerId>,
+    #[behaviour(ignore)]
+    disconnect_protocol: DisconnectProtocolName,
+    #[behaviour(ignore)]
+    disconnects: DisconnectTracker,
+    #[behaviour(ignore)]
+    pending_disconnects: HashMap<RequestId, PeerId>,
+    #[behaviour(ignore)]
+    pending_asks: HashMap<RequestId, PendingAsk>,
+}
+
+// The outbound storage ask request waiting for the response, the ask is validated against
+// the key address of the miner worker and the epoch when the request is sent.
+struct PendingAsk {
+    miner: Address,
+    worker: Address,
+    current: ChainEpoch,
 }
 
 /// Event that can happen on the behaviour.
@@ -48,6 +121,7 @@ pub struct Behaviour {
 pub enum BehaviourEvent {
     PeerDialed(PeerId),
     PeerDisconnected(PeerId),
+    PeerUnresponsive(PeerId),
     MdnsDiscoveredPeer(PeerId),
     MdnsExpiredPeer(PeerId),
     GossipsubMessage {
@@ -83,6 +157,37 @@ pub enum BehaviourEvent {
         request_id: RequestId,
         response: BlockSyncResponse,
     },
+    AskRequest {
+        peer: PeerId,
+        request: AskRequest,
+        channel: ResponseChannel<AskResponse>,
+    },
+    AskResponse {
+        peer: PeerId,
+        request_id: RequestId,
+        response: AskResponse,
+    },
+    InvalidAskResponse {
+        peer: PeerId,
+        request_id: RequestId,
+        error: AskError,
+    },
+    ProvidersResolved {
+        query_id: QueryId,
+        cid: Cid,
+        providers: HashSet<PeerId>,
+    },
+    PeersExchanged {
+        peer: PeerId,
+        peers: Vec<PeerId>,
+    },
+    /// The peer is closing the connection with us for the reason.
+    PeerDisconnecting {
+        peer: PeerId,
+        reason: DisconnectReason,
+    },
+    /// The peer has been notified of the disconnection, the connection can be closed.
+    DisconnectReady(PeerId),
 }
 
 impl NetworkBehaviourEventProcess<PingEvent> for Behaviour {
@@ -94,9 +199,12 @@ impl NetworkBehaviourEventProcess<PingEvent> for Behaviour {
                     event.peer,
                     rtt.as_millis()
                 );
+                self.latencies.record_success(&event.peer, rtt);
+                return;
             }
             Ok(PingSuccess::Pong) => {
                 debug!("[ping] PingSuccess::Pong from peer: {}", event.peer);
+                return;
             }
             Err(PingFailure::Timeout) => {
                 debug!("[ping] PingFailure::Timeout from peer: {}", event.peer);
@@ -105,6 +213,15 @@ impl NetworkBehaviourEventProcess<PingEvent> for Behaviour {
                 debug!("[ping] PingFailure::Other from {}: {}", event.peer, error);
             }
         }
+
+        // The connection is closed by the ping handler after too many consecutive failures.
+        if self.latencies.record_failure(&event.peer) {
+            warn!("[ping] Peer {} is unresponsive", event.peer);
+            self.latencies.remove(&event.peer);
+            self.peers.remove(&event.peer);
+            self.events
+                .push(BehaviourEvent::PeerUnresponsive(event.peer));
+        }
     }
 }
 
@@ -122,9 +239,31 @@ impl NetworkBehaviourEventProcess<IdentifyEvent> for Behaviour {
                 debug!("[identify] listening_ addresses {:?}", info.listen_addrs);
                 debug!("[identify] observed_address {:?}", observed_addr);
                 debug!("[identify] protocols {:?}", info.protocols);
+                for addr in &info.listen_addrs {
+                    self.kademlia.add_address(&peer_id, addr.clone());
+                }
+                let supports_pex = info
+                    .protocols
+                    .iter()
+                    .any(|protocol| protocol.as_bytes() == self.pex_protocol.protocol_name());
+                if supports_pex {
+                    self.request_peers(&peer_id);
+                }
+                self.peer_book.set_identify(
+                    &peer_id,
+                    IdentifyInfo {
+                        agent_version: info.agent_version,
+                        protocol_version: info.protocol_version,
+                        protocols: info.protocols,
+                        observed_addr,
+                    },
+                    info.listen_addrs,
+                );
             }
             IdentifyEvent::Sent { .. } => (),
-            IdentifyEvent::Error { .. } => (),
+            IdentifyEvent::Error { peer_id, error } => {
+                debug!("[identify] Error (peer: {}): {:?}", peer_id, error);
+            }
         }
     }
 }
@@ -133,8 +272,9 @@ impl NetworkBehaviourEventProcess<MdnsEvent> for Behaviour {
     fn inject_event(&mut self, event: MdnsEvent) {
         match event {
             MdnsEvent::Discovered(discovered_addrs) => {
-                for (peer_id, _addr) in discovered_addrs {
+                for (peer_id, addr) in discovered_addrs {
                     debug!("[mdns] Discovered (peer: {})", peer_id);
+                    self.peer_book.add_address(&peer_id, addr);
                     self.peers.insert(peer_id.clone());
                     self.events
                         .push(BehaviourEvent::MdnsDiscoveredPeer(peer_id));
@@ -160,6 +300,36 @@ impl NetworkBehaviourEventProcess<KademliaEvent> for Behaviour {
                 debug!("[kad] RoutingUpdated (peer: {})", peer);
                 self.peers.insert(peer);
             }
+            KademliaEvent::QueryResult { id, result, .. } => match result {
+                QueryResult::GetProviders(result) => {
+                    let cid = match self.provider_queries.remove(&id) {
+                        Some(cid) => cid,
+                        None => return,
+                    };
+                    let providers = match result {
+                        Ok(GetProvidersOk { providers, .. }) => providers,
+                        // The providers found before the timeout are still useful.
+                        Err(GetProvidersError::Timeout { providers, .. }) => {
+                            warn!("[kad] GetProviders timeout (cid: {})", cid);
+                            providers
+                        }
+                    };
+                    debug!("[kad] Providers of {}: {:?}", cid, providers);
+                    self.dial_providers(&providers);
+                    self.events.push(BehaviourEvent::ProvidersResolved {
+                        query_id: id,
+                        cid,
+                        providers,
+                    });
+                }
+                QueryResult::StartProviding(Err(err)) => {
+                    warn!("[kad] StartProviding error: {:?}", err);
+                }
+                QueryResult::RepublishProvider(Err(err)) => {
+                    warn!("[kad] RepublishProvider error: {:?}", err);
+                }
+                result => debug!("[kad] QueryResult (id: {:?}): {:?}", id, result),
+            },
             event => debug!("[kad] {:?}", event),
         }
     }
@@ -169,6 +339,24 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for Behaviour {
     fn inject_event(&mut self, event: GossipsubEvent) {
         match event {
             GossipsubEvent::Message(peer_id, message_id, message) => {
+                self.bandwidth.record_protocol(
+                    GOSSIPSUB_PROTOCOL_ID,
+                    Direction::Inbound,
+                    message.data.len() as u64,
+                );
+                // The same block or message may be gossiped by many peers with different
+                // message ids, e.g. during a reorg, only the first one is processed.
+                if !self
+                    .seen
+                    .insert(SeenCache::key(&message.data), Instant::now())
+                {
+                    trace!(
+                        "[gossipsub] Duplicate message (peer: {}, message_id: {:?})",
+                        peer_id,
+                        message_id
+                    );
+                    return;
+                }
                 debug!(
                     "[gossipsub] Message (peer: {}, message_id: {:?}): {:?}",
                     peer_id, message_id, message
@@ -301,6 +489,184 @@ impl NetworkBehaviourEventProcess<RequestResponseEvent<BlockSyncRequest, BlockSy
     }
 }
 
+impl NetworkBehaviourEventProcess<RequestResponseEvent<AskRequest, AskResponse>> for Behaviour {
+    fn inject_event(&mut self, event: RequestResponseEvent<AskRequest, AskResponse>) {
+        match event {
+            RequestResponseEvent::Message { peer, message } => match message {
+                RequestResponseMessage::Request { request, channel } => {
+                    debug!(
+                        "[request-response] ask request (peer: {}): {:?}",
+                        peer, request
+                    );
+                    self.events.push(BehaviourEvent::AskRequest {
+                        peer,
+                        request,
+                        channel,
+                    });
+                }
+                RequestResponseMessage::Response {
+                    request_id,
+                    response,
+                } => {
+                    debug!(
+                        "[request-response] ask response (peer: {}, request_id: {:?}): {:?}",
+                        peer, request_id, response
+                    );
+                    let pending = match self.pending_asks.remove(&request_id) {
+                        Some(pending) => pending,
+                        None => {
+                            warn!(
+                                "[request-response] unsolicited ask response (peer: {}, request_id: {:?})",
+                                peer, request_id
+                            );
+                            return;
+                        }
+                    };
+                    match response.validate(&pending.miner, &pending.worker, pending.current) {
+                        Ok(()) => self.events.push(BehaviourEvent::AskResponse {
+                            peer,
+                            request_id,
+                            response,
+                        }),
+                        Err(error) => {
+                            warn!(
+                                "[request-response] invalid ask response (peer: {}, request_id: {:?}): {}",
+                                peer, request_id, error
+                            );
+                            self.events.push(BehaviourEvent::InvalidAskResponse {
+                                peer,
+                                request_id,
+                                error,
+                            });
+                        }
+                    }
+                }
+            },
+            RequestResponseEvent::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                warn!(
+                    "[request-response] ask outbound failure (peer: {}, request id: {:?}): {:?}",
+                    peer, request_id, error
+                );
+                self.pending_asks.remove(&request_id);
+            }
+            RequestResponseEvent::InboundFailure { peer, error } => {
+                warn!(
+                    "[request-response] ask inbound failure (peer: {}): {:?}",
+                    peer, error
+                );
+            }
+        }
+    }
+}
+
+impl NetworkBehaviourEventProcess<RequestResponseEvent<PexRequest, PexResponse>> for Behaviour {
+    fn inject_event(&mut self, event: RequestResponseEvent<PexRequest, PexResponse>) {
+        match event {
+            RequestResponseEvent::Message { peer, message } => match message {
+                RequestResponseMessage::Request { request, channel } => {
+                    debug!(
+                        "[request-response] pex request (peer: {}): {:?}",
+                        peer, request
+                    );
+                    let limit = request.max_peers.min(self.pex_max_peers).min(MAX_PEX_PEERS);
+                    let peers = self
+                        .peer_book
+                        .recent_peers(limit as usize, &peer)
+                        .into_iter()
+                        .map(|(peer_id, addrs)| PexPeer { peer_id, addrs })
+                        .collect();
+                    self.pex.send_response(channel, PexResponse { peers });
+                }
+                RequestResponseMessage::Response {
+                    request_id,
+                    response,
+                } => {
+                    debug!(
+                        "[request-response] pex response (peer: {}, request_id: {:?}): {} peers",
+                        peer,
+                        request_id,
+                        response.peers.len()
+                    );
+                    self.add_exchanged_peers(peer, response.peers);
+                }
+            },
+            RequestResponseEvent::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                debug!(
+                    "[request-response] pex outbound failure (peer: {}, request id: {:?}): {:?}",
+                    peer, request_id, error
+                );
+            }
+            RequestResponseEvent::InboundFailure { peer, error } => {
+                debug!(
+                    "[request-response] pex inbound failure (peer: {}): {:?}",
+                    peer, error
+                );
+            }
+        }
+    }
+}
+
+impl NetworkBehaviourEventProcess<RequestResponseEvent<DisconnectRequest, DisconnectResponse>>
+    for Behaviour
+{
+    fn inject_event(&mut self, event: RequestResponseEvent<DisconnectRequest, DisconnectResponse>) {
+        match event {
+            RequestResponseEvent::Message { peer, message } => match message {
+                RequestResponseMessage::Request { request, channel } => {
+                    debug!(
+                        "[request-response] disconnect request (peer: {}): {}",
+                        peer, request.reason
+                    );
+                    self.disconnects.record(&peer, request.reason);
+                    self.peers.remove(&peer);
+                    self.disconnect.send_response(channel, DisconnectResponse);
+                    self.events.push(BehaviourEvent::PeerDisconnecting {
+                        peer,
+                        reason: request.reason,
+                    });
+                }
+                RequestResponseMessage::Response { request_id, .. } => {
+                    debug!(
+                        "[request-response] disconnect response (peer: {}, request_id: {:?})",
+                        peer, request_id
+                    );
+                    if let Some(peer) = self.pending_disconnects.remove(&request_id) {
+                        self.events.push(BehaviourEvent::DisconnectReady(peer));
+                    }
+                }
+            },
+            RequestResponseEvent::OutboundFailure {
+                peer,
+                request_id,
+                error,
+            } => {
+                debug!(
+                    "[request-response] disconnect outbound failure (peer: {}, request id: {:?}): {:?}",
+                    peer, request_id, error
+                );
+                // The connection is closed anyway.
+                if let Some(peer) = self.pending_disconnects.remove(&request_id) {
+                    self.events.push(BehaviourEvent::DisconnectReady(peer));
+                }
+            }
+            RequestResponseEvent::InboundFailure { peer, error } => {
+                debug!(
+                    "[request-response] disconnect inbound failure (peer: {}): {:?}",
+                    peer, error
+                );
+            }
+        }
+    }
+}
+
 impl Behaviour {
     /// Consumes the event list when polled.
     fn poll<TBehaviourIn>(
@@ -311,40 +677,104 @@ impl Behaviour {
         if !self.events.is_empty() {
             return Poll::Ready(NetworkBehaviourAction::GenerateEvent(self.events.remove(0)));
         }
+        if let Some(peer_id) = self.pending_dials.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::DialPeer {
+                peer_id,
+                condition: DialPeerCondition::Disconnected,
+            });
+        }
+        let now = Instant::now();
+        if now.duration_since(self.last_prune) >= PEER_BOOK_PRUNE_INTERVAL {
+            self.last_prune = now;
+            let peers = &self.peers;
+            let pruned = self
+                .peer_book
+                .prune(now, PEER_BOOK_TTL, |peer_id| peers.contains(peer_id));
+            if pruned > 0 {
+                debug!("[peer_book] Pruned {} expired peers", pruned);
+            }
+        }
 
         Poll::Pending
     }
 }
 
 impl Behaviour {
-    /// Create a new network behaviour.
-    pub fn new(local_key_pair: Keypair, config: &Libp2pConfig) -> Self {
+    /// Create a new network behaviour, the traffic of the protocols is recorded by `bandwidth`.
+    pub fn new(
+        local_key_pair: Keypair,
+        config: &Libp2pConfig,
+        bandwidth: Arc<BandwidthCounter>,
+    ) -> Self {
         let local_public = local_key_pair.public();
         let local_peer_id = local_public.clone().into_peer_id();
 
         // Create the Kademlia DHT service with the bootnodes from the config.
-        let mut kademlia = config.build_kademlia(local_peer_id, &config.network_name);
+        let mut kademlia = config.build_kademlia(local_peer_id.clone(), &config.network_name);
         if let Err(err) = kademlia.bootstrap() {
             warn!("Kademlia bootstrap error: {}", err);
         }
 
         // Create hello request-response service.
         let hello = RequestResponse::new(
-            HelloCodec,
-            vec![(HelloProtocolName, ProtocolSupport::Full)],
+            MeteredCodec::new(HelloCodec, bandwidth.clone()),
+            vec![(
+                HelloProtocolName::with_prefix(&config.protocol_prefix),
+                ProtocolSupport::Full,
+            )],
             RequestResponseConfig::default(),
         );
 
-        // Create blocksync request-response service.
+        // Create blocksync request-response service, the compressed protocol is preferred.
+        let mut blocksync_protocols = vec![];
+        if config.blocksync_compression {
+            blocksync_protocols.push((
+                BlockSyncProtocolName::zstd_with_prefix(&config.protocol_prefix),
+                ProtocolSupport::Full,
+            ));
+        }
+        blocksync_protocols.push((
+            BlockSyncProtocolName::with_prefix(&config.protocol_prefix),
+            ProtocolSupport::Full,
+        ));
         let blocksync = RequestResponse::new(
-            BlockSyncCodec,
-            vec![(BlockSyncProtocolName, ProtocolSupport::Full)],
+            MeteredCodec::new(
+                BlockSyncCodec::new(config.blocksync_response_budget),
+                bandwidth.clone(),
+            ),
+            blocksync_protocols,
+            RequestResponseConfig::default(),
+        );
+
+        // Create storage ask request-response service.
+        let ask = RequestResponse::new(
+            MeteredCodec::new(AskCodec, bandwidth.clone()),
+            vec![(
+                AskProtocolName::with_prefix(&config.protocol_prefix),
+                ProtocolSupport::Full,
+            )],
+            RequestResponseConfig::default(),
+        );
+
+        // Create peer exchange request-response service.
+        let pex_protocol = PexProtocolName::with_prefix(&config.protocol_prefix);
+        let pex = RequestResponse::new(
+            MeteredCodec::new(PexCodec, bandwidth.clone()),
+            vec![(pex_protocol.clone(), ProtocolSupport::Full)],
+            RequestResponseConfig::default(),
+        );
+
+        // Create graceful disconnection request-response service.
+        let disconnect_protocol = DisconnectProtocolName::with_prefix(&config.protocol_prefix);
+        let disconnect = RequestResponse::new(
+            MeteredCodec::new(DisconnectCodec, bandwidth.clone()),
+            vec![(disconnect_protocol.clone(), ProtocolSupport::Full)],
             RequestResponseConfig::default(),
         );
 
         Self {
-            ping: Ping::default(),
-            identify: Identify::new("ipfs/0.1.0".into(), "plum/0.1.0".into(), local_public),
+            ping: Ping::new(config.build_ping_config()),
+            identify: Identify::new("ipfs/0.1.0".into(), AGENT_VERSION.into(), local_public),
             mdns: Mdns::new().expect("Failed to create mDNS service"),
             kademlia,
             gossipsub: Gossipsub::new(
@@ -353,13 +783,45 @@ impl Behaviour {
             ),
             hello,
             blocksync,
+            ask,
+            pex,
+            disconnect,
             events: vec![],
             peers: HashSet::default(),
+            provider_queries: HashMap::default(),
+            peer_book: PeerBook::new(),
+            last_prune: Instant::now(),
+            latencies: LatencyTracker::new(DEFAULT_LATENCY_ALPHA, config.ping_max_failures.get()),
+            seen: SeenCache::new(config.seen_cache_size, config.seen_cache_ttl),
+            bandwidth,
+            local_peer_id,
+            pex_protocol,
+            pex_max_peers: config.pex_max_peers,
+            pex_requested: HashSet::default(),
+            pending_dials: VecDeque::default(),
+            disconnect_protocol,
+            disconnects: DisconnectTracker::new(),
+            pending_disconnects: HashMap::default(),
+            pending_asks: HashMap::default(),
         }
     }
 
     /// Publish message to the network over gossipsub protocol.
+    ///
+    /// Return `PublishError::Duplicate` if the same data is seen recently,
+    /// either received from the network or published by the local node.
     pub fn publish(&mut self, topic: &Topic, data: impl Into<Vec<u8>>) -> Result<(), PublishError> {
+        let data = data.into();
+        if !self.seen.insert(SeenCache::key(&data), Instant::now()) {
+            return Err(PublishError::Duplicate);
+        }
+        // Only the payload published by the local node is counted, not the copies forwarded to
+        // the mesh peers, which are counted by the peers at the transport layer.
+        self.bandwidth.record_protocol(
+            GOSSIPSUB_PROTOCOL_ID,
+            Direction::Outbound,
+            data.len() as u64,
+        );
         self.gossipsub.publish(topic, data)
     }
 
@@ -405,8 +867,211 @@ impl Behaviour {
         self.blocksync.send_response(channel, response)
     }
 
+    /// Initiates sending a storage ask request, the ask of the response is validated against
+    /// the key address of the miner `worker` and the `current` epoch.
+    pub fn send_ask_request(
+        &mut self,
+        peer: &PeerId,
+        request: AskRequest,
+        worker: Address,
+        current: ChainEpoch,
+    ) -> RequestId {
+        let miner = request.miner.clone();
+        let request_id = self.ask.send_request(peer, request);
+        self.pending_asks.insert(
+            request_id,
+            PendingAsk {
+                miner,
+                worker,
+                current,
+            },
+        );
+        request_id
+    }
+
+    /// Initiates sending a storage ask response to an inbound storage ask request.
+    pub fn send_ask_response(
+        &mut self,
+        channel: ResponseChannel<AskResponse>,
+        response: AskResponse,
+    ) {
+        self.ask.send_response(channel, response)
+    }
+
+    // Request the peers known by the `peer` once, e.g. after it's identified.
+    fn request_peers(&mut self, peer: &PeerId) {
+        if self.pex_max_peers == 0 || !self.pex_requested.insert(peer.clone()) {
+            return;
+        }
+        let request = PexRequest {
+            max_peers: self.pex_max_peers,
+        };
+        let request_id = self.pex.send_request(peer, request);
+        debug!(
+            "[pex] Request peers (peer: {}, request_id: {:?})",
+            peer, request_id
+        );
+    }
+
+    // Add the peers learned from the `peer` to the address book and the DHT routing table,
+    // and dial the ones that are not connected yet.
+    fn add_exchanged_peers(&mut self, peer: PeerId, exchanged: Vec<PexPeer>) {
+        let mut peers = Vec::with_capacity(exchanged.len());
+        for PexPeer { peer_id, addrs } in exchanged {
+            if peer_id == self.local_peer_id || peer_id == peer || addrs.is_empty() {
+                continue;
+            }
+            for addr in addrs {
+                self.kademlia.add_address(&peer_id, addr.clone());
+                self.peer_book.add_address(&peer_id, addr);
+            }
+            // The peers that dropped us recently are not redialed.
+            if !self.peers.contains(&peer_id)
+                && !self.pending_dials.contains(&peer_id)
+                && self.disconnects.can_dial(&peer_id)
+            {
+                self.pending_dials.push_back(peer_id.clone());
+            }
+            peers.push(peer_id);
+        }
+        debug!("[pex] Learned {} peers from {}", peers.len(), peer);
+        self.events
+            .push(BehaviourEvent::PeersExchanged { peer, peers });
+    }
+
+    /// Notify the peer of the disconnection for the `reason`, the `DisconnectReady` event is
+    /// generated when the connection with the peer can be closed, i.e. the peer acknowledges
+    /// the notice, fails to, or doesn't support the disconnection protocol.
+    pub fn disconnect_peer(&mut self, peer: &PeerId, reason: DisconnectReason) {
+        self.peers.remove(peer);
+        let supported = self.peer_book.get(peer).map_or(false, |entry| {
+            entry
+                .protocols()
+                .iter()
+                .any(|protocol| protocol.as_bytes() == self.disconnect_protocol.protocol_name())
+        });
+        if supported {
+            let request_id = self
+                .disconnect
+                .send_request(peer, DisconnectRequest { reason });
+            debug!(
+                "[disconnect] Notify peer {} of {} (request_id: {:?})",
+                peer, reason, request_id
+            );
+            self.pending_disconnects.insert(request_id, peer.clone());
+        } else {
+            self.events
+                .push(BehaviourEvent::DisconnectReady(peer.clone()));
+        }
+    }
+
+    /// Forget the disconnection received from the peer, e.g. when the peer dials us again.
+    pub fn forget_disconnect(&mut self, peer: &PeerId) {
+        self.disconnects.remove(peer);
+    }
+
+    /// Return the disconnection reasons received from the peers.
+    pub fn disconnect_tracker(&self) -> &DisconnectTracker {
+        &self.disconnects
+    }
+
+    /// Announce to the DHT that the local node provides the data of the `cid`, e.g. a piece
+    /// that can be retrieved. The provider record is republished periodically until
+    /// `stop_providing` is called.
+    pub fn start_providing(&mut self, cid: &Cid) -> Result<QueryId, store::Error> {
+        self.kademlia.start_providing(provider_key(cid))
+    }
+
+    /// Stop announcing that the local node provides the data of the `cid`,
+    /// the published record expires after the provider record TTL.
+    pub fn stop_providing(&mut self, cid: &Cid) {
+        self.kademlia.stop_providing(&provider_key(cid))
+    }
+
+    /// Initiates looking up the providers of the `cid` in the DHT,
+    /// the result is reported by the `ProvidersResolved` event.
+    ///
+    /// It's the fallback of fetching the blocks that no connected peer has over bitswap or
+    /// graphsync, the providers that are not connected are dialed once they are resolved,
+    /// so that the blocks can be requested from them.
+    pub fn get_providers(&mut self, cid: &Cid) -> QueryId {
+        let query_id = self.kademlia.get_providers(provider_key(cid));
+        self.provider_queries.insert(query_id, cid.clone());
+        query_id
+    }
+
+    // Dial the resolved providers that are not connected yet.
+    fn dial_providers(&mut self, providers: &HashSet<PeerId>) {
+        for provider in providers {
+            // The peers that dropped us recently are not redialed.
+            if *provider != self.local_peer_id
+                && !self.peers.contains(provider)
+                && !self.pending_dials.contains(provider)
+                && self.disconnects.can_dial(provider)
+            {
+                self.pending_dials.push_back(provider.clone());
+            }
+        }
+    }
+
     /// Return the peer set.
     pub fn peers(&self) -> &HashSet<PeerId> {
         &self.peers
     }
+
+    /// Return the latency tracker of the peers.
+    pub fn latency_tracker(&self) -> &LatencyTracker {
+        &self.latencies
+    }
+
+    /// Return the peers sorted by the preference for fetching, i.e. the responsive peers
+    /// with lower latency first, which is used for selecting the peers of the blocksync requests.
+    pub fn sorted_peers(&self) -> Vec<PeerId> {
+        let mut peers = self.peers.iter().cloned().collect::<Vec<_>>();
+        self.latencies.sort_peers(&mut peers);
+        peers
+    }
+
+    /// Return the address book, which stores the addresses and the identify information of the peers.
+    pub fn peer_book(&self) -> &PeerBook {
+        &self.peer_book
+    }
+
+    /// Return the bandwidth counter of the peers and the protocols.
+    pub fn bandwidth(&self) -> &BandwidthCounter {
+        &self.bandwidth
+    }
+}
+
+// The provider records are keyed by the multihash of the CID, the same as go-libp2p-kad-dht,
+// so that the providers are shared by the CIDs of the same data with different versions or codecs.
+fn provider_key(cid: &Cid) -> Key {
+    Key::new(&cid.hash().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::Codec;
+    use multihash::Sha2_256;
+
+    #[test]
+    fn test_provider_key() {
+        let hash = Sha2_256::digest(b"block");
+        let v0 = Cid::new_v0(hash.clone()).unwrap();
+        assert_eq!(provider_key(&v0), Key::new(&hash.as_bytes()));
+        assert_eq!(
+            provider_key(&Cid::new_v1(Codec::DagProtobuf, hash.clone())),
+            provider_key(&v0)
+        );
+        assert_eq!(
+            provider_key(&Cid::new_v1(Codec::Raw, hash)),
+            provider_key(&v0)
+        );
+        assert_ne!(
+            provider_key(&Cid::new_v1(Codec::Raw, Sha2_256::digest(b"other"))),
+            provider_key(&v0)
+        );
+    }
 }
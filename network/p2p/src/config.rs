@@ -1,14 +1,24 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::num::NonZeroU32;
 use std::time::Duration;
 
 use libp2p::{
     core::{Multiaddr, PeerId},
     gossipsub::Topic,
-    kad::{record::store::MemoryStore, Kademlia, KademliaConfig},
+    kad::{
+        record::store::{MemoryStore, MemoryStoreConfig},
+        Kademlia, KademliaConfig,
+    },
     multiaddr::Protocol,
+    ping::PingConfig,
+    pnet::PreSharedKey,
 };
 
+use plum_peermgr::DEFAULT_MAX_PING_FAILURES;
+
+use crate::protocol::{DEFAULT_BLOCKSYNC_RESPONSE_BUDGET, DEFAULT_PROTOCOL_PREFIX};
+
 // See lotus/build/bootstrap/bootstrappers.pi
 const BOOTSTRAP_NODES: &[&str] = &[
     "/dns4/bootstrap-0-sin.fil-test.net/tcp/1347/p2p/12D3KooWPdUquftaQvoQEtEdsRBAhwD6jopbF2oweVTzR59VbHEd",
@@ -28,6 +38,22 @@ const BOOTSTRAP_NODES: &[&str] = &[
 // See https://filecoin-project.github.io/specs/#systems__filecoin_nodes__network for details.
 const PUBSUB_TOPICS: &[&str] = &["/fil/blocks", "/fil/msgs"];
 
+// The same as the defaults of go-libp2p-kad-dht.
+const PROVIDER_RECORD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+const PROVIDER_PUBLICATION_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+const MAX_PROVIDED_KEYS: usize = 64 * 1024;
+
+// The same as the `bls_signature_cache_size` of the params, assuming 4000 messages per round,
+// the messages of a 10 block reorg are not processed twice.
+const SEEN_CACHE_SIZE: usize = 40_000;
+const SEEN_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+// The number of the peers shared with a newly connected peer.
+const PEX_MAX_PEERS: u64 = 32;
+
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+const PING_TIMEOUT: Duration = Duration::from_secs(20);
+
 /// The config of p2p network.
 #[derive(Debug)]
 pub struct Libp2pConfig {
@@ -40,8 +66,54 @@ pub struct Libp2pConfig {
     /// The network name.
     pub network_name: String,
 
+    /// The prefix of the protocol IDs, e.g. `/fil` of `/fil/hello/1.0.0`.
+    ///
+    /// A private network uses a custom prefix so that its nodes never talk to the public network.
+    pub protocol_prefix: String,
+
+    /// The pre-shared key of the private network, the connections of the nodes without the same
+    /// key are refused at the transport layer. `None` means the public network.
+    pub psk: Option<PreSharedKey>,
+
     /// The pubsub topics.
     pub pubsub_topics: Vec<Topic>,
+
+    /// The time after which the provider records published to the DHT expire.
+    pub provider_record_ttl: Duration,
+
+    /// The interval at which the provider records of the local node are republished.
+    pub provider_publication_interval: Duration,
+
+    /// The max number of the keys that the local node can provide, e.g. the piece CIDs.
+    pub max_provided_keys: usize,
+
+    /// The max number of the gossip messages remembered for the deduplication.
+    pub seen_cache_size: usize,
+
+    /// The time during which a gossip message is remembered for the deduplication.
+    pub seen_cache_ttl: Duration,
+
+    /// The interval between two pings of a peer.
+    pub ping_interval: Duration,
+
+    /// The timeout of a ping.
+    pub ping_timeout: Duration,
+
+    /// The number of consecutive ping failures after which the connection to the peer is closed.
+    pub ping_max_failures: NonZeroU32,
+
+    /// The max number of the peers requested from a newly connected peer over the peer exchange
+    /// protocol, which is also the max number of the peers shared with a requester.
+    /// `0` disables the peer exchange.
+    pub pex_max_peers: u64,
+
+    /// Whether to negotiate the blocksync protocol with the zstd compressed responses,
+    /// the peers not supporting it fall back to the uncompressed protocol.
+    pub blocksync_compression: bool,
+
+    /// The max size in bytes of the CBOR encoded blocksync response served to the peers,
+    /// the response is truncated with the partial status if it exceeds the budget.
+    pub blocksync_response_budget: usize,
 }
 
 impl Default for Libp2pConfig {
@@ -54,27 +126,76 @@ impl Default for Libp2pConfig {
                 .map(|node| node.parse().unwrap())
                 .collect(),
             network_name: network_name.into(),
-            pubsub_topics: PUBSUB_TOPICS
-                .iter()
-                .map(|topic| Topic::new(format!("{}/{}", topic, network_name)))
-                .collect(),
+            protocol_prefix: DEFAULT_PROTOCOL_PREFIX.into(),
+            psk: None,
+            pubsub_topics: pubsub_topics(DEFAULT_PROTOCOL_PREFIX, network_name),
+            provider_record_ttl: PROVIDER_RECORD_TTL,
+            provider_publication_interval: PROVIDER_PUBLICATION_INTERVAL,
+            max_provided_keys: MAX_PROVIDED_KEYS,
+            seen_cache_size: SEEN_CACHE_SIZE,
+            seen_cache_ttl: SEEN_CACHE_TTL,
+            ping_interval: PING_INTERVAL,
+            ping_timeout: PING_TIMEOUT,
+            ping_max_failures: NonZeroU32::new(DEFAULT_MAX_PING_FAILURES)
+                .expect("max ping failures must be non-zero; qed"),
+            pex_max_peers: PEX_MAX_PEERS,
+            blocksync_compression: true,
+            blocksync_response_budget: DEFAULT_BLOCKSYNC_RESPONSE_BUDGET,
         }
     }
 }
 
+fn pubsub_topics(protocol_prefix: &str, network_name: &str) -> Vec<Topic> {
+    PUBSUB_TOPICS
+        .iter()
+        .map(|topic| {
+            let topic = topic.trim_start_matches(DEFAULT_PROTOCOL_PREFIX);
+            let prefix = protocol_prefix.trim_end_matches('/');
+            Topic::new(format!("{}{}/{}", prefix, topic, network_name))
+        })
+        .collect()
+}
+
 impl Libp2pConfig {
+    /// Configure a private network with the protocol prefix and the pre-shared key,
+    /// the pubsub topics are also renamed with the prefix.
+    pub fn with_private_network(mut self, protocol_prefix: &str, psk: PreSharedKey) -> Self {
+        self.protocol_prefix = protocol_prefix.into();
+        self.psk = Some(psk);
+        self.pubsub_topics = pubsub_topics(protocol_prefix, &self.network_name);
+        self
+    }
+
+    /// Create the ping config.
+    pub fn build_ping_config(&self) -> PingConfig {
+        PingConfig::new()
+            .with_interval(self.ping_interval)
+            .with_timeout(self.ping_timeout)
+            .with_max_failures(self.ping_max_failures)
+            .with_keep_alive(true)
+    }
+
     /// Create a Kademlia DHT.
     pub fn build_kademlia(
         &self,
         local_peer_id: PeerId,
         network_name: &str,
     ) -> Kademlia<MemoryStore> {
-        let store = MemoryStore::new(local_peer_id.clone());
+        let store = MemoryStore::with_config(
+            local_peer_id.clone(),
+            MemoryStoreConfig {
+                max_provided_keys: self.max_provided_keys,
+                ..Default::default()
+            },
+        );
 
         let mut kad_cfg = KademliaConfig::default();
         // see https://filecoin-project.github.io/specs/#systems__filecoin_nodes__network
-        kad_cfg.set_protocol_name(format!("fil/{}/kad/1.0.0", network_name).into_bytes());
+        let prefix = self.protocol_prefix.trim_matches('/');
+        kad_cfg.set_protocol_name(format!("{}/{}/kad/1.0.0", prefix, network_name).into_bytes());
         kad_cfg.set_query_timeout(Duration::from_secs(5 * 60));
+        kad_cfg.set_provider_record_ttl(Some(self.provider_record_ttl));
+        kad_cfg.set_provider_publication_interval(Some(self.provider_publication_interval));
 
         let mut kad = Kademlia::with_config(local_peer_id, store, kad_cfg);
         for multiaddr in &self.boot_nodes {
@@ -7,18 +7,34 @@
 #[macro_use]
 extern crate log;
 
+mod bandwidth;
 mod behaviour;
 mod config;
 mod protocol;
+mod seen;
 mod service;
 
+pub use self::bandwidth::{MeteredCodec, MeteredStream, GOSSIPSUB_PROTOCOL_ID};
 pub use self::behaviour::{Behaviour, BehaviourEvent};
 pub use self::config::Libp2pConfig;
+pub use self::protocol::DEFAULT_PROTOCOL_PREFIX;
+pub use self::protocol::{AskCodec, AskProtocolName, AskRequest, AskResponse, ASK_PROTOCOL_ID};
 pub use self::protocol::{
     BlockSyncCodec, BlockSyncProtocolName, BlockSyncRequest, BlockSyncResponse, BlockSyncTipset,
-    BLOCKSYNC_PROTOCOL_ID,
+    BLOCKSYNC_PROTOCOL_ID, BLOCKSYNC_STATUS_BAD_REQUEST, BLOCKSYNC_STATUS_GO_AWAY,
+    BLOCKSYNC_STATUS_INTERNAL_ERROR, BLOCKSYNC_STATUS_NOT_FOUND, BLOCKSYNC_STATUS_OK,
+    BLOCKSYNC_STATUS_PARTIAL, BLOCKSYNC_ZSTD_PROTOCOL_ID, DEFAULT_BLOCKSYNC_RESPONSE_BUDGET,
+    MAX_BLOCKSYNC_RESPONSE_SIZE,
+};
+pub use self::protocol::{
+    DisconnectCodec, DisconnectProtocolName, DisconnectRequest, DisconnectResponse,
+    DISCONNECT_PROTOCOL_ID,
 };
 pub use self::protocol::{
     HelloCodec, HelloProtocolName, HelloRequest, HelloResponse, HELLO_PROTOCOL_ID,
 };
+pub use self::protocol::{
+    PexCodec, PexPeer, PexProtocolName, PexRequest, PexResponse, MAX_PEX_PEERS, PEX_PROTOCOL_ID,
+};
+pub use self::seen::SeenCache;
 pub use self::service::{build_transport, generate_new_keypair, Libp2pEvent, Libp2pService};
@@ -0,0 +1,275 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::io;
+
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response::{ProtocolName, RequestResponseCodec};
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use plum_address::Address;
+use plum_markets::storage::{AskError, SignedStorageAsk};
+use plum_types::ChainEpoch;
+
+use super::{other_io_error, with_protocol_prefix};
+
+/// The protocol ID of storage ask.
+pub const ASK_PROTOCOL_ID: &[u8] = b"/fil/storage/ask/1.0.1";
+
+/// The max size of a storage ask message, far larger than any valid signed ask.
+const MAX_ASK_SIZE: usize = 4 * 1024;
+
+/// The protocol name of storage ask protocol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AskProtocolName(Vec<u8>);
+
+impl Default for AskProtocolName {
+    fn default() -> Self {
+        Self(ASK_PROTOCOL_ID.to_vec())
+    }
+}
+
+impl AskProtocolName {
+    /// Create the protocol name with the `/fil` prefix replaced by the `prefix`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self(with_protocol_prefix(prefix, ASK_PROTOCOL_ID))
+    }
+}
+
+impl ProtocolName for AskProtocolName {
+    fn protocol_name(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The request for the current storage ask of a miner.
+///
+/// See go-fil-markets/storagemarket/network/types.go for details.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AskRequest {
+    /// The miner whose ask is queried.
+    pub miner: Address,
+}
+
+// Implement CBOR serialization for AskRequest.
+impl encode::Encode for AskRequest {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.encode(&self.miner)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for AskRequest.
+impl<'b> decode::Decode<'b> for AskRequest {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message("expected 1 field of AskRequest"));
+        }
+        Ok(Self { miner: d.decode()? })
+    }
+}
+
+/// The response to a storage ask request.
+///
+/// See go-fil-markets/storagemarket/network/types.go for details.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AskResponse {
+    /// The signed storage ask, `None` if the provider has no ask for the miner.
+    pub ask: Option<SignedStorageAsk>,
+}
+
+impl AskResponse {
+    /// Validate the ask of the response to the request for the `miner`, against the key address
+    /// of the miner `worker` and the `current` epoch, see `SignedStorageAsk::validate`.
+    pub fn validate(
+        &self,
+        miner: &Address,
+        worker: &Address,
+        current: ChainEpoch,
+    ) -> Result<(), AskError> {
+        match &self.ask {
+            Some(ask) => ask.validate(miner, worker, current),
+            None => Ok(()),
+        }
+    }
+}
+
+// Implement CBOR serialization for AskResponse.
+impl encode::Encode for AskResponse {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.encode(&self.ask)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for AskResponse.
+impl<'b> decode::Decode<'b> for AskResponse {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message("expected 1 field of AskResponse"));
+        }
+        Ok(Self { ask: d.decode()? })
+    }
+}
+
+// Read the whole message, reject the one exceeding `MAX_ASK_SIZE`.
+async fn read_message<T>(io: &mut T) -> io::Result<Vec<u8>>
+where
+    T: AsyncRead + Unpin + Send,
+{
+    let mut message = Vec::new();
+    io.take(MAX_ASK_SIZE as u64 + 1)
+        .read_to_end(&mut message)
+        .await?;
+    if message.len() > MAX_ASK_SIZE {
+        return Err(other_io_error(format!(
+            "storage ask message exceeds {} bytes",
+            MAX_ASK_SIZE
+        )));
+    }
+    Ok(message)
+}
+
+/// The codec to be used for storage ask protocol.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct AskCodec;
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for AskCodec {
+    type Protocol = AskProtocolName;
+    type Request = AskRequest;
+    type Response = AskResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let request = read_message(io).await?;
+        minicbor::decode(&request).map_err(|e| other_io_error(e.to_string()))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let response = read_message(io).await?;
+        minicbor::decode(&response).map_err(|e| other_io_error(e.to_string()))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let request = minicbor::to_vec(req).map_err(|e| other_io_error(e.to_string()))?;
+        io.write_all(&request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let response = minicbor::to_vec(res).map_err(|e| other_io_error(e.to_string()))?;
+        io.write_all(&response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+
+    use plum_bigint::BigInt;
+    use plum_crypto::{PrivateKey, PublicKey, SignatureType};
+    use plum_markets::storage::StorageAsk;
+    use plum_piece::PaddedPieceSize;
+
+    #[test]
+    fn test_read_ask_message() {
+        let protocol = AskProtocolName::default();
+        let request = AskRequest {
+            miner: Address::new_id_addr(1000).unwrap(),
+        };
+        let mut io = Cursor::new(minicbor::to_vec(&request).unwrap());
+        assert_eq!(
+            block_on(AskCodec.read_request(&protocol, &mut io)).unwrap(),
+            request
+        );
+        let mut io = Cursor::new(vec![0x81, 0xf6]);
+        assert_eq!(
+            block_on(AskCodec.read_response(&protocol, &mut io)).unwrap(),
+            AskResponse { ask: None }
+        );
+
+        // The malformed and the oversized messages are rejected.
+        let mut io = Cursor::new(vec![0x82, 0xf6, 0xf6]);
+        assert!(block_on(AskCodec.read_response(&protocol, &mut io)).is_err());
+        let mut io = Cursor::new(vec![0u8; MAX_ASK_SIZE + 1]);
+        assert!(block_on(AskCodec.read_request(&protocol, &mut io)).is_err());
+        let mut io = Cursor::new(vec![0u8; MAX_ASK_SIZE + 1]);
+        assert!(block_on(AskCodec.read_response(&protocol, &mut io)).is_err());
+    }
+
+    #[test]
+    fn test_validate_ask_response() {
+        let privkey = PrivateKey::generate_secp256k1_privkey();
+        let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+        let worker = Address::new_secp256k1_addr(&pubkey).unwrap();
+        let miner = Address::new_id_addr(1000).unwrap();
+        let ask = StorageAsk {
+            price: BigInt::from(500_000_000u64),
+            verified_price: BigInt::from(50_000_000u64),
+            min_piece_size: PaddedPieceSize(256),
+            max_piece_size: PaddedPieceSize(32 << 30),
+            miner: miner.clone(),
+            timestamp: 100,
+            expiry: 200,
+            seq_no: 0,
+        };
+        let signed = ask
+            .sign(SignatureType::Secp256k1, privkey.into_vec())
+            .unwrap();
+
+        assert!(AskResponse { ask: None }
+            .validate(&miner, &worker, 150)
+            .is_ok());
+        let response = AskResponse {
+            ask: Some(signed.clone()),
+        };
+        assert!(response.validate(&miner, &worker, 150).is_ok());
+        assert!(matches!(
+            response.validate(&miner, &worker, 200),
+            Err(AskError::Expired { .. })
+        ));
+        assert!(matches!(
+            response.validate(&miner, &miner, 150),
+            Err(AskError::InvalidSignature(_)) | Err(AskError::Crypto(_))
+        ));
+
+        let mut tampered = signed;
+        tampered.ask.price = BigInt::from(1u64);
+        let response = AskResponse {
+            ask: Some(tampered),
+        };
+        assert!(matches!(
+            response.validate(&miner, &worker, 150),
+            Err(AskError::InvalidSignature(_))
+        ));
+    }
+}
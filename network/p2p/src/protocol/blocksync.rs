@@ -1,6 +1,6 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
-use std::io;
+use std::io::{self, Read};
 
 use cid::Cid;
 use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
@@ -10,18 +10,78 @@ use minicbor::{decode, encode, Decoder, Encoder};
 use plum_block::BlockHeader;
 use plum_message::{SignedMessage, UnsignedMessage};
 
-use super::other_io_error;
+use super::{other_io_error, with_protocol_prefix};
 
 /// The protocol ID of blocksync.
 pub const BLOCKSYNC_PROTOCOL_ID: &[u8] = b"/fil/sync/blk/0.0.1";
+/// The protocol ID of blocksync whose responses are compressed with zstd.
+pub const BLOCKSYNC_ZSTD_PROTOCOL_ID: &[u8] = b"/fil/sync/blk/0.0.1/zstd";
+
+/// The default max size in bytes of the CBOR encoded blocksync response sent by the local node.
+pub const DEFAULT_BLOCKSYNC_RESPONSE_BUDGET: usize = 32 << 20;
+/// The max size in bytes of the blocksync response accepted from the peers, both before and
+/// after the decompression.
+pub const MAX_BLOCKSYNC_RESPONSE_SIZE: usize = 128 << 20;
+
+// The compression level of zstd, the default level of the zstd library.
+const ZSTD_LEVEL: i32 = 3;
+
+/// The status of the complete blocksync response.
+pub const BLOCKSYNC_STATUS_OK: u64 = 0;
+/// The status of the blocksync response with only a part of the requested tipsets.
+pub const BLOCKSYNC_STATUS_PARTIAL: u64 = 101;
+/// The status of the blocksync response that the requested tipsets are not found.
+pub const BLOCKSYNC_STATUS_NOT_FOUND: u64 = 201;
+/// The status of the blocksync response that the peer refuses to serve the request.
+pub const BLOCKSYNC_STATUS_GO_AWAY: u64 = 202;
+/// The status of the blocksync response that the peer fails to serve the request.
+pub const BLOCKSYNC_STATUS_INTERNAL_ERROR: u64 = 203;
+/// The status of the blocksync response that the request is invalid.
+pub const BLOCKSYNC_STATUS_BAD_REQUEST: u64 = 204;
 
 /// The protocol name of blocksync protocol.
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub struct BlockSyncProtocolName;
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BlockSyncProtocolName {
+    name: Vec<u8>,
+    compressed: bool,
+}
+
+impl Default for BlockSyncProtocolName {
+    fn default() -> Self {
+        Self {
+            name: BLOCKSYNC_PROTOCOL_ID.to_vec(),
+            compressed: false,
+        }
+    }
+}
+
+impl BlockSyncProtocolName {
+    /// Create the protocol name with the `/fil` prefix replaced by the `prefix`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            name: with_protocol_prefix(prefix, BLOCKSYNC_PROTOCOL_ID),
+            compressed: false,
+        }
+    }
+
+    /// Create the name of the protocol with the zstd compressed responses, with the `/fil`
+    /// prefix replaced by the `prefix`.
+    pub fn zstd_with_prefix(prefix: &str) -> Self {
+        Self {
+            name: with_protocol_prefix(prefix, BLOCKSYNC_ZSTD_PROTOCOL_ID),
+            compressed: true,
+        }
+    }
+
+    /// Return true if the responses of the protocol are compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed
+    }
+}
 
 impl ProtocolName for BlockSyncProtocolName {
     fn protocol_name(&self) -> &[u8] {
-        BLOCKSYNC_PROTOCOL_ID
+        &self.name
     }
 }
 
@@ -75,6 +135,62 @@ pub struct BlockSyncResponse {
     pub message: String,
 }
 
+impl BlockSyncResponse {
+    /// Drop the tipsets at the end of the chain until the CBOR encoded response fits into the
+    /// `budget` in bytes, the truncated response gets the `BLOCKSYNC_STATUS_PARTIAL` status.
+    ///
+    /// Return true if the response is truncated.
+    pub fn truncate(&mut self, budget: usize) -> bool {
+        if encoded_len(&*self) <= budget {
+            return false;
+        }
+        let requested = self.chain.len();
+        let mut chain = std::mem::replace(&mut self.chain, vec![]);
+        self.status = BLOCKSYNC_STATUS_PARTIAL;
+        self.message = format!(
+            "response exceeds {} bytes, truncated from {} tipsets",
+            budget, requested
+        );
+        // The length of the chain array takes at most 8 more bytes than the empty one.
+        let mut size = encoded_len(&*self) + 8;
+        let fit = chain
+            .iter()
+            .take_while(|tipset| {
+                size += encoded_len(*tipset);
+                size <= budget
+            })
+            .count();
+        chain.truncate(fit);
+        self.chain = chain;
+        true
+    }
+}
+
+fn encoded_len<T: encode::Encode>(value: &T) -> usize {
+    minicbor::to_vec(value)
+        .expect("CBOR serialization of blocksync response shouldn't be failed")
+        .len()
+}
+
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::stream::encode_all(data, ZSTD_LEVEL)
+}
+
+// Decompress the data, fail if the decompressed data is larger than `max_size`.
+fn decompress(data: &[u8], max_size: usize) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    zstd::stream::read::Decoder::new(data)?
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut decompressed)?;
+    if decompressed.len() > max_size {
+        return Err(other_io_error(format!(
+            "decompressed blocksync response exceeds {} bytes",
+            max_size
+        )));
+    }
+    Ok(decompressed)
+}
+
 // Implement CBOR serialization for BlockSyncResponse.
 impl encode::Encode for BlockSyncResponse {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
@@ -145,8 +261,29 @@ impl<'b> decode::Decode<'b> for BlockSyncTipset {
 }
 
 /// The codec to be used for blocksync protocol.
+///
+/// The responses are compressed with zstd if the zstd protocol is negotiated, and the
+/// responses written are truncated to the budget, see `BlockSyncResponse::truncate`.
 #[derive(Copy, Clone, Eq, PartialEq)]
-pub struct BlockSyncCodec;
+pub struct BlockSyncCodec {
+    response_budget: usize,
+}
+
+impl Default for BlockSyncCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_BLOCKSYNC_RESPONSE_BUDGET)
+    }
+}
+
+impl BlockSyncCodec {
+    /// Create the codec with the max size of the responses written, which is capped by
+    /// `MAX_BLOCKSYNC_RESPONSE_SIZE`, otherwise the peers would reject the responses.
+    pub fn new(response_budget: usize) -> Self {
+        Self {
+            response_budget: response_budget.min(MAX_BLOCKSYNC_RESPONSE_SIZE),
+        }
+    }
+}
 
 #[async_trait::async_trait]
 impl RequestResponseCodec for BlockSyncCodec {
@@ -169,14 +306,25 @@ impl RequestResponseCodec for BlockSyncCodec {
 
     async fn read_response<T>(
         &mut self,
-        _protocol: &Self::Protocol,
+        protocol: &Self::Protocol,
         io: &mut T,
     ) -> io::Result<Self::Response>
     where
         T: AsyncRead + Unpin + Send,
     {
         let mut response = Vec::new();
-        io.read_to_end(&mut response).await?;
+        io.take(MAX_BLOCKSYNC_RESPONSE_SIZE as u64 + 1)
+            .read_to_end(&mut response)
+            .await?;
+        if response.len() > MAX_BLOCKSYNC_RESPONSE_SIZE {
+            return Err(other_io_error(format!(
+                "blocksync response exceeds {} bytes",
+                MAX_BLOCKSYNC_RESPONSE_SIZE
+            )));
+        }
+        if protocol.is_compressed() {
+            response = decompress(&response, MAX_BLOCKSYNC_RESPONSE_SIZE)?;
+        }
         minicbor::decode(&response).map_err(|e| other_io_error(e.to_string()))
     }
 
@@ -195,14 +343,84 @@ impl RequestResponseCodec for BlockSyncCodec {
 
     async fn write_response<T>(
         &mut self,
-        _protocol: &Self::Protocol,
+        protocol: &Self::Protocol,
         io: &mut T,
-        res: Self::Response,
+        mut res: Self::Response,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
     {
-        let response = minicbor::to_vec(res).map_err(|e| other_io_error(e.to_string()))?;
+        if res.truncate(self.response_budget) {
+            debug!(
+                "Truncated blocksync response to {} tipsets",
+                res.chain.len()
+            );
+        }
+        let mut response = minicbor::to_vec(res).map_err(|e| other_io_error(e.to_string()))?;
+        if protocol.is_compressed() {
+            response = compress(&response)?;
+        }
         io.write_all(&response).await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tipset(includes: usize) -> BlockSyncTipset {
+        BlockSyncTipset {
+            blocks: vec![],
+            bls_msgs: vec![],
+            bls_msg_includes: vec![vec![0; includes]],
+            secp_msgs: vec![],
+            secp_msg_includes: vec![],
+        }
+    }
+
+    #[test]
+    fn test_truncate_response() {
+        let mut response = BlockSyncResponse {
+            chain: vec![tipset(100); 10],
+            status: BLOCKSYNC_STATUS_OK,
+            message: String::new(),
+        };
+        let tipset_len = encoded_len(&tipset(100));
+        let len = encoded_len(&response);
+        assert!(!response.clone().truncate(len));
+
+        let mut truncated = response.clone();
+        assert!(truncated.truncate(len - 1));
+        assert_eq!(truncated.status, BLOCKSYNC_STATUS_PARTIAL);
+        assert!(!truncated.chain.is_empty() && truncated.chain.len() < 10);
+        assert_eq!(truncated.chain[..], response.chain[..truncated.chain.len()]);
+        assert!(encoded_len(&truncated) <= len - 1);
+
+        assert!(response.truncate(tipset_len * 3 + 100));
+        assert_eq!(response.chain.len(), 3);
+        assert!(encoded_len(&response) <= tipset_len * 3 + 100);
+        // Not even a tipset fits.
+        assert!(response.truncate(tipset_len));
+        assert!(response.chain.is_empty());
+    }
+
+    #[test]
+    fn test_compression() {
+        let response = BlockSyncResponse {
+            chain: vec![tipset(1000); 10],
+            status: BLOCKSYNC_STATUS_OK,
+            message: String::new(),
+        };
+        let data = minicbor::to_vec(&response).unwrap();
+        let compressed = compress(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed, data.len()).unwrap(), data);
+        // The decompression bomb.
+        assert!(decompress(&compressed, data.len() - 1).is_err());
+
+        assert!(!BlockSyncProtocolName::with_prefix("/fil").is_compressed());
+        let zstd = BlockSyncProtocolName::zstd_with_prefix("/consortium");
+        assert!(zstd.is_compressed());
+        assert_eq!(zstd.protocol_name(), b"/consortium/sync/blk/0.0.1/zstd");
+    }
+}
@@ -0,0 +1,187 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::io;
+
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::request_response::{ProtocolName, RequestResponseCodec};
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use plum_peermgr::DisconnectReason;
+
+use super::{other_io_error, with_protocol_prefix};
+
+/// The protocol ID of graceful disconnection.
+pub const DISCONNECT_PROTOCOL_ID: &[u8] = b"/fil/disconnect/1.0.0";
+
+/// The max size of a disconnection message, which only carries the reason code.
+const MAX_DISCONNECT_MESSAGE_SIZE: u64 = 64;
+
+/// The protocol name of graceful disconnection protocol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DisconnectProtocolName(Vec<u8>);
+
+impl Default for DisconnectProtocolName {
+    fn default() -> Self {
+        Self(DISCONNECT_PROTOCOL_ID.to_vec())
+    }
+}
+
+impl DisconnectProtocolName {
+    /// Create the protocol name with the `/fil` prefix replaced by the `prefix`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self(with_protocol_prefix(prefix, DISCONNECT_PROTOCOL_ID))
+    }
+}
+
+impl ProtocolName for DisconnectProtocolName {
+    fn protocol_name(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The notice sent to a peer before closing the connection with it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisconnectRequest {
+    /// The reason of the disconnection.
+    pub reason: DisconnectReason,
+}
+
+// Implement CBOR serialization for DisconnectRequest.
+impl encode::Encode for DisconnectRequest {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.u64(self.reason.code())?.ok()
+    }
+}
+
+// Implement CBOR deserialization for DisconnectRequest.
+impl<'b> decode::Decode<'b> for DisconnectRequest {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message("expected array of 1"));
+        }
+        Ok(Self {
+            reason: DisconnectReason::from_code(d.u64()?),
+        })
+    }
+}
+
+/// The acknowledgement of a disconnection notice, after which the connection is closed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DisconnectResponse;
+
+// Implement CBOR serialization for DisconnectResponse.
+impl encode::Encode for DisconnectResponse {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(0)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for DisconnectResponse.
+impl<'b> decode::Decode<'b> for DisconnectResponse {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(0) {
+            return Err(decode::Error::Message("expected empty array"));
+        }
+        Ok(Self)
+    }
+}
+
+/// The codec to be used for graceful disconnection protocol.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct DisconnectCodec;
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for DisconnectCodec {
+    type Protocol = DisconnectProtocolName;
+    type Request = DisconnectRequest;
+    type Response = DisconnectResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut request = Vec::new();
+        io.take(MAX_DISCONNECT_MESSAGE_SIZE)
+            .read_to_end(&mut request)
+            .await?;
+        minicbor::decode(&request).map_err(|e| other_io_error(e.to_string()))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut response = Vec::new();
+        io.take(MAX_DISCONNECT_MESSAGE_SIZE)
+            .read_to_end(&mut response)
+            .await?;
+        minicbor::decode(&response).map_err(|e| other_io_error(e.to_string()))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let request = minicbor::to_vec(req).map_err(|e| other_io_error(e.to_string()))?;
+        io.write_all(&request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let response = minicbor::to_vec(res).map_err(|e| other_io_error(e.to_string()))?;
+        io.write_all(&response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disconnect_encoding() {
+        let request = DisconnectRequest {
+            reason: DisconnectReason::TooManyPeers,
+        };
+        let bytes = minicbor::to_vec(&request).unwrap();
+        assert_eq!(bytes, vec![0x81, 0x03]);
+        assert_eq!(
+            minicbor::decode::<DisconnectRequest>(&bytes).unwrap(),
+            request
+        );
+        // The unknown reasons of the newer versions are kept.
+        assert_eq!(
+            minicbor::decode::<DisconnectRequest>(&[0x81, 0x18, 0x64])
+                .unwrap()
+                .reason,
+            DisconnectReason::Unknown(100)
+        );
+        assert!(minicbor::decode::<DisconnectRequest>(&[0x80]).is_err());
+
+        let bytes = minicbor::to_vec(&DisconnectResponse).unwrap();
+        assert_eq!(bytes, vec![0x80]);
+        assert_eq!(
+            minicbor::decode::<DisconnectResponse>(&bytes).unwrap(),
+            DisconnectResponse
+        );
+    }
+}
@@ -10,18 +10,31 @@ use minicbor::{decode, encode, Decoder, Encoder};
 use plum_bigint::{BigInt, BigIntRefWrapper, BigIntWrapper};
 use plum_types::ChainEpoch;
 
-use super::other_io_error;
+use super::{other_io_error, with_protocol_prefix};
 
 /// The protocol ID of hello.
 pub const HELLO_PROTOCOL_ID: &[u8] = b"/fil/hello/1.0.0";
 
 /// The protocol name of hello protocol.
-#[derive(Copy, Clone, Eq, PartialEq)]
-pub struct HelloProtocolName;
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct HelloProtocolName(Vec<u8>);
+
+impl Default for HelloProtocolName {
+    fn default() -> Self {
+        Self(HELLO_PROTOCOL_ID.to_vec())
+    }
+}
+
+impl HelloProtocolName {
+    /// Create the protocol name with the `/fil` prefix replaced by the `prefix`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self(with_protocol_prefix(prefix, HELLO_PROTOCOL_ID))
+    }
+}
 
 impl ProtocolName for HelloProtocolName {
     fn protocol_name(&self) -> &[u8] {
-        HELLO_PROTOCOL_ID
+        &self.0
     }
 }
 
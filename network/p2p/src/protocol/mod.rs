@@ -1,16 +1,81 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+mod ask;
 mod blocksync;
+mod disconnect;
 mod hello;
+mod pex;
 
+pub use self::ask::{AskCodec, AskProtocolName, AskRequest, AskResponse, ASK_PROTOCOL_ID};
 pub use self::blocksync::{
     BlockSyncCodec, BlockSyncProtocolName, BlockSyncRequest, BlockSyncResponse, BlockSyncTipset,
-    BLOCKSYNC_PROTOCOL_ID,
+    BLOCKSYNC_PROTOCOL_ID, BLOCKSYNC_STATUS_BAD_REQUEST, BLOCKSYNC_STATUS_GO_AWAY,
+    BLOCKSYNC_STATUS_INTERNAL_ERROR, BLOCKSYNC_STATUS_NOT_FOUND, BLOCKSYNC_STATUS_OK,
+    BLOCKSYNC_STATUS_PARTIAL, BLOCKSYNC_ZSTD_PROTOCOL_ID, DEFAULT_BLOCKSYNC_RESPONSE_BUDGET,
+    MAX_BLOCKSYNC_RESPONSE_SIZE,
+};
+pub use self::disconnect::{
+    DisconnectCodec, DisconnectProtocolName, DisconnectRequest, DisconnectResponse,
+    DISCONNECT_PROTOCOL_ID,
 };
 pub use self::hello::{
     HelloCodec, HelloProtocolName, HelloRequest, HelloResponse, HELLO_PROTOCOL_ID,
 };
+pub use self::pex::{
+    PexCodec, PexPeer, PexProtocolName, PexRequest, PexResponse, MAX_PEX_PEERS, PEX_PROTOCOL_ID,
+};
+
+/// The default prefix of the protocol IDs, i.e. the protocols of the public Filecoin network.
+pub const DEFAULT_PROTOCOL_PREFIX: &str = "/fil";
+
+/// Replace the default `/fil` prefix of the protocol ID with the `prefix`.
+fn with_protocol_prefix(prefix: &str, protocol_id: &[u8]) -> Vec<u8> {
+    let default_prefix = DEFAULT_PROTOCOL_PREFIX.as_bytes();
+    let suffix = if protocol_id.starts_with(default_prefix) {
+        &protocol_id[default_prefix.len()..]
+    } else {
+        protocol_id
+    };
+    let mut id = prefix.trim_end_matches('/').as_bytes().to_vec();
+    id.extend_from_slice(suffix);
+    id
+}
 
 fn other_io_error(err: String) -> std::io::Error {
     std::io::Error::new(std::io::ErrorKind::Other, err)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use libp2p::request_response::ProtocolName;
+
+    #[test]
+    fn test_protocol_prefix() {
+        assert_eq!(
+            HelloProtocolName::default().protocol_name(),
+            HELLO_PROTOCOL_ID
+        );
+        assert_eq!(
+            HelloProtocolName::with_prefix(DEFAULT_PROTOCOL_PREFIX).protocol_name(),
+            HELLO_PROTOCOL_ID
+        );
+        assert_eq!(
+            HelloProtocolName::with_prefix("/consortium/").protocol_name(),
+            b"/consortium/hello/1.0.0"
+        );
+        assert_eq!(
+            AskProtocolName::with_prefix("/consortium").protocol_name(),
+            b"/consortium/storage/ask/1.0.1"
+        );
+        assert_eq!(
+            PexProtocolName::with_prefix("/consortium").protocol_name(),
+            b"/consortium/pex/1.0.0"
+        );
+        assert_eq!(
+            DisconnectProtocolName::with_prefix("/consortium").protocol_name(),
+            b"/consortium/disconnect/1.0.0"
+        );
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::convert::TryFrom;
+use std::io;
+
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use libp2p::core::{Multiaddr, PeerId};
+use libp2p::request_response::{ProtocolName, RequestResponseCodec};
+use minicbor::{decode, encode, Decoder, Encoder};
+
+use super::{other_io_error, with_protocol_prefix};
+
+/// The protocol ID of peer exchange.
+pub const PEX_PROTOCOL_ID: &[u8] = b"/fil/pex/1.0.0";
+
+/// The max number of the peers in a peer exchange response.
+pub const MAX_PEX_PEERS: u64 = 64;
+
+/// The max size of a peer exchange message, which is large enough for `MAX_PEX_PEERS` peers.
+const MAX_PEX_MESSAGE_SIZE: u64 = 64 * 1024;
+
+/// The protocol name of peer exchange protocol.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PexProtocolName(Vec<u8>);
+
+impl Default for PexProtocolName {
+    fn default() -> Self {
+        Self(PEX_PROTOCOL_ID.to_vec())
+    }
+}
+
+impl PexProtocolName {
+    /// Create the protocol name with the `/fil` prefix replaced by the `prefix`.
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self(with_protocol_prefix(prefix, PEX_PROTOCOL_ID))
+    }
+}
+
+impl ProtocolName for PexProtocolName {
+    fn protocol_name(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// The request for the peers known by the remote peer, which is sent to the newly connected
+/// peers, so that a node learns more peers than the bootstrap nodes quickly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PexRequest {
+    /// The max number of the peers to return, which is capped by `MAX_PEX_PEERS`.
+    pub max_peers: u64,
+}
+
+// Implement CBOR serialization for PexRequest.
+impl encode::Encode for PexRequest {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.u64(self.max_peers)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for PexRequest.
+impl<'b> decode::Decode<'b> for PexRequest {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message("expected 1 field of PexRequest"));
+        }
+        Ok(Self {
+            max_peers: d.u64()?,
+        })
+    }
+}
+
+/// A peer and its addresses in the peer exchange response.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PexPeer {
+    /// The peer ID.
+    pub peer_id: PeerId,
+    /// The known addresses of the peer.
+    pub addrs: Vec<Multiaddr>,
+}
+
+// Implement CBOR serialization for PexPeer.
+impl encode::Encode for PexPeer {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?
+            .bytes(self.peer_id.as_bytes())?
+            .array(self.addrs.len() as u64)?;
+        for addr in &self.addrs {
+            e.bytes(addr.as_ref())?;
+        }
+        e.ok()
+    }
+}
+
+// Implement CBOR deserialization for PexPeer.
+impl<'b> decode::Decode<'b> for PexPeer {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of PexPeer"));
+        }
+        let peer_id = PeerId::from_bytes(d.bytes()?.to_vec())
+            .map_err(|_| decode::Error::Message("invalid peer id"))?;
+        let len = d
+            .array()?
+            .ok_or(decode::Error::Message("indefinite addresses"))?;
+        let mut addrs = Vec::with_capacity(len as usize);
+        for _ in 0..len {
+            let addr = Multiaddr::try_from(d.bytes()?.to_vec())
+                .map_err(|_| decode::Error::Message("invalid multiaddr"))?;
+            addrs.push(addr);
+        }
+        Ok(Self { peer_id, addrs })
+    }
+}
+
+/// The response to a peer exchange request.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PexResponse {
+    /// The peers known by the responder, the most recently seen first.
+    pub peers: Vec<PexPeer>,
+}
+
+// Implement CBOR serialization for PexResponse.
+impl encode::Encode for PexResponse {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(1)?.encode(&self.peers)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for PexResponse.
+impl<'b> decode::Decode<'b> for PexResponse {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(1) {
+            return Err(decode::Error::Message("expected 1 field of PexResponse"));
+        }
+        let peers = d.decode::<Vec<PexPeer>>()?;
+        if peers.len() as u64 > MAX_PEX_PEERS {
+            return Err(decode::Error::Message("too many peers"));
+        }
+        Ok(Self { peers })
+    }
+}
+
+/// The codec to be used for peer exchange protocol.
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct PexCodec;
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for PexCodec {
+    type Protocol = PexProtocolName;
+    type Request = PexRequest;
+    type Response = PexResponse;
+
+    async fn read_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut request = Vec::new();
+        io.take(MAX_PEX_MESSAGE_SIZE)
+            .read_to_end(&mut request)
+            .await?;
+        minicbor::decode(&request).map_err(|e| other_io_error(e.to_string()))
+    }
+
+    async fn read_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let mut response = Vec::new();
+        io.take(MAX_PEX_MESSAGE_SIZE)
+            .read_to_end(&mut response)
+            .await?;
+        minicbor::decode(&response).map_err(|e| other_io_error(e.to_string()))
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let request = minicbor::to_vec(req).map_err(|e| other_io_error(e.to_string()))?;
+        io.write_all(&request).await
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _protocol: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let response = minicbor::to_vec(res).map_err(|e| other_io_error(e.to_string()))?;
+        io.write_all(&response).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pex_encoding() {
+        let request = PexRequest { max_peers: 16 };
+        let bytes = minicbor::to_vec(&request).unwrap();
+        assert_eq!(bytes, vec![0x81, 0x10]);
+        assert_eq!(minicbor::decode::<PexRequest>(&bytes).unwrap(), request);
+
+        let response = PexResponse {
+            peers: vec![PexPeer {
+                peer_id: PeerId::random(),
+                addrs: vec!["/ip4/127.0.0.1/tcp/1347".parse().unwrap()],
+            }],
+        };
+        let bytes = minicbor::to_vec(&response).unwrap();
+        assert_eq!(minicbor::decode::<PexResponse>(&bytes).unwrap(), response);
+
+        // The malformed messages of the remote peers are errors instead of panics.
+        assert!(minicbor::decode::<PexRequest>(&[0x82, 0x10, 0x10]).is_err());
+        assert!(minicbor::decode::<PexResponse>(&[0x80]).is_err());
+        assert!(minicbor::decode::<PexResponse>(&[0x81, 0x81, 0x80]).is_err());
+    }
+}
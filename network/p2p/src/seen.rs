@@ -0,0 +1,120 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use cid::{Cid, Codec};
+
+/// The bounded cache of the gossip messages seen recently, keyed by the CID of the message data.
+///
+/// An entry is dropped when it's older than the TTL, or when the cache is full,
+/// in which case the oldest entry is dropped first.
+#[derive(Clone, Debug)]
+pub struct SeenCache {
+    capacity: usize,
+    ttl: Duration,
+    seen: HashMap<Cid, Instant>,
+    order: VecDeque<Cid>,
+}
+
+impl SeenCache {
+    /// Create an empty cache with the max number of the entries and the TTL of an entry.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            seen: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Return the key of the gossip message data, which is the CID of the data as a DAG-CBOR
+    /// block. Both blocks and messages are gossiped in the deterministic CBOR encoding,
+    /// so the same object is always mapped to the same key.
+    pub fn key(data: &[u8]) -> Cid {
+        Cid::new_v1(Codec::DagCBOR, multihash::Blake2b256::digest(data))
+    }
+
+    /// Mark the `cid` as seen at `now`, return false if it's already seen within the TTL.
+    ///
+    /// The time of the entry is not refreshed when it's seen again.
+    pub fn insert(&mut self, cid: Cid, now: Instant) -> bool {
+        self.expire(now);
+        if self.seen.contains_key(&cid) {
+            return false;
+        }
+        while self.order.len() >= self.capacity.max(1) {
+            self.pop_oldest();
+        }
+        self.seen.insert(cid.clone(), now);
+        self.order.push_back(cid);
+        true
+    }
+
+    /// Return true if the `cid` is seen within the TTL.
+    pub fn contains(&self, cid: &Cid, now: Instant) -> bool {
+        match self.seen.get(cid) {
+            Some(seen_at) => now.duration_since(*seen_at) < self.ttl,
+            None => false,
+        }
+    }
+
+    /// Return the number of the entries, including the expired ones that are not dropped yet.
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    /// Return true if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn expire(&mut self, now: Instant) {
+        while let Some(cid) = self.order.front() {
+            let seen_at = self.seen[cid];
+            if now.duration_since(seen_at) < self.ttl {
+                break;
+            }
+            self.pop_oldest();
+        }
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some(cid) = self.order.pop_front() {
+            self.seen.remove(&cid);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seen_cache() {
+        let now = Instant::now();
+        let mut cache = SeenCache::new(2, Duration::from_secs(10));
+        let (a, b, c) = (
+            SeenCache::key(b"a"),
+            SeenCache::key(b"b"),
+            SeenCache::key(b"c"),
+        );
+
+        assert!(cache.insert(a.clone(), now));
+        assert!(!cache.insert(a.clone(), now + Duration::from_secs(1)));
+        assert!(cache.insert(b.clone(), now + Duration::from_secs(2)));
+        // the oldest entry is dropped when the cache is full.
+        assert!(cache.insert(c.clone(), now + Duration::from_secs(3)));
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&a, now + Duration::from_secs(3)));
+        assert!(cache.contains(&b, now + Duration::from_secs(3)));
+
+        // the entries expire after the TTL.
+        assert!(!cache.contains(&b, now + Duration::from_secs(12)));
+        assert!(cache.insert(b, now + Duration::from_secs(12)));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.insert(a, now + Duration::from_secs(14)));
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&c, now + Duration::from_secs(14)));
+    }
+}
@@ -1,25 +1,37 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::io;
+use std::sync::Arc;
 use std::time::Duration;
 
+use cid::Cid;
 use libp2p::{
     core::{
+        either::EitherTransport,
         identity::Keypair,
         multiaddr::Multiaddr,
         muxing::StreamMuxerBox,
         transport::{boxed::Boxed, Transport},
-        upgrade, PeerId,
+        upgrade, ConnectedPoint, PeerId,
     },
-    dns, mplex,
+    dns,
+    kad::{record::store, QueryId},
+    mplex,
+    pnet::{PnetConfig, PreSharedKey},
     request_response::{RequestId, ResponseChannel},
     secio,
     swarm::{Swarm, SwarmEvent},
     tcp, yamux,
 };
 
+use plum_address::Address;
+use plum_peermgr::{BandwidthCounter, DisconnectReason, PeerBook};
+use plum_types::ChainEpoch;
+
+use crate::bandwidth::MeteredStream;
 use crate::behaviour::{Behaviour, BehaviourEvent};
 use crate::config::Libp2pConfig;
+use crate::protocol::{AskRequest, AskResponse};
 use crate::protocol::{BlockSyncRequest, BlockSyncResponse};
 use crate::protocol::{HelloRequest, HelloResponse};
 
@@ -46,9 +58,10 @@ impl Libp2pService {
         let local_peer_id = local_key_pair.public().into_peer_id();
         info!("Local peer id: {}", local_peer_id);
 
+        let bandwidth = Arc::new(BandwidthCounter::new());
         let mut swarm = {
-            let transport = build_transport(local_key_pair.clone());
-            let behaviour = Behaviour::new(local_key_pair, &config);
+            let transport = build_transport(local_key_pair.clone(), config.psk, bandwidth.clone());
+            let behaviour = Behaviour::new(local_key_pair, &config, bandwidth);
             Swarm::new(transport, behaviour, local_peer_id)
         };
 
@@ -105,14 +118,92 @@ impl Libp2pService {
         self.swarm.send_blocksync_response(channel, response)
     }
 
+    /// Sends a storage ask request to a peer, return a request Id.
+    ///
+    /// The ask of the response is validated against the key address of the miner `worker` and
+    /// the `current` epoch, `BehaviourEvent::InvalidAskResponse` is emitted if it's invalid.
+    pub fn send_ask_request(
+        &mut self,
+        peer: &PeerId,
+        request: AskRequest,
+        worker: Address,
+        current: ChainEpoch,
+    ) -> RequestId {
+        self.swarm.send_ask_request(peer, request, worker, current)
+    }
+
+    /// Sends a storage ask response to a peer over the channel.
+    pub fn send_ask_response(
+        &mut self,
+        channel: ResponseChannel<AskResponse>,
+        response: AskResponse,
+    ) {
+        self.swarm.send_ask_response(channel, response)
+    }
+
+    /// Announces that the local node provides the data of the cid.
+    pub fn start_providing(&mut self, cid: &Cid) -> Result<QueryId, store::Error> {
+        self.swarm.start_providing(cid)
+    }
+
+    /// Stops announcing that the local node provides the data of the cid.
+    pub fn stop_providing(&mut self, cid: &Cid) {
+        self.swarm.stop_providing(cid)
+    }
+
+    /// Looks up the providers of the cid, return a query Id.
+    pub fn get_providers(&mut self, cid: &Cid) -> QueryId {
+        self.swarm.get_providers(cid)
+    }
+
+    /// Sends a blocksync request to the most preferred peer,
+    /// return the peer and the request Id, or `None` if there is no peer.
+    pub fn send_blocksync_request_to_best_peer(
+        &mut self,
+        request: BlockSyncRequest,
+    ) -> Option<(PeerId, RequestId)> {
+        let peer = self.swarm.sorted_peers().into_iter().next()?;
+        let request_id = self.swarm.send_blocksync_request(&peer, request);
+        Some((peer, request_id))
+    }
+
+    /// Notifies the peer of the disconnection for the reason, then closes the connection.
+    pub fn disconnect_peer(&mut self, peer: &PeerId, reason: DisconnectReason) {
+        self.swarm.disconnect_peer(peer, reason)
+    }
+
+    /// Returns the address book of the peers.
+    pub fn peer_book(&self) -> &PeerBook {
+        self.swarm.peer_book()
+    }
+
+    /// Returns the bandwidth counter, in total, per peer and per protocol.
+    pub fn bandwidth(&self) -> &BandwidthCounter {
+        self.swarm.bandwidth()
+    }
+
     /// Returns the next event that happens in the `Swarm`.
     pub async fn next_event(&mut self) -> Libp2pEvent {
         loop {
             match self.swarm.next_event().await {
+                SwarmEvent::Behaviour(BehaviourEvent::DisconnectReady(peer_id)) => {
+                    debug!("Close the connections with peer {}", peer_id);
+                    // The swarm can only close the connections of a peer by banning it,
+                    // so the ban is lifted at once.
+                    Swarm::ban_peer_id(&mut self.swarm, peer_id.clone());
+                    Swarm::unban_peer_id(&mut self.swarm, peer_id);
+                }
                 SwarmEvent::Behaviour(behaviour) => return Libp2pEvent::Behaviour(behaviour),
                 // A connection could be established with a banned peer.
                 // This is handled inside the behaviour.
-                SwarmEvent::ConnectionEstablished { .. } => {}
+                SwarmEvent::ConnectionEstablished {
+                    peer_id, endpoint, ..
+                } => {
+                    // The peer that dropped us dials us again, so it's willing to connect.
+                    if let ConnectedPoint::Listener { .. } = endpoint {
+                        self.swarm.forget_disconnect(&peer_id);
+                    }
+                }
                 SwarmEvent::ConnectionClosed {
                     peer_id,
                     endpoint,
@@ -180,13 +271,48 @@ impl Libp2pService {
 }
 
 /// Builds the transport that serves as a common ground for all connections.
-pub fn build_transport(local_key_pair: Keypair) -> Boxed<(PeerId, StreamMuxerBox), io::Error> {
+///
+/// If the pre-shared key is given, all the connections are encrypted with the key before the
+/// other upgrades, so that the nodes without the same key are refused (private network).
+///
+/// The traffic of the secured connections is recorded by `bandwidth` per peer.
+pub fn build_transport(
+    local_key_pair: Keypair,
+    psk: Option<PreSharedKey>,
+    bandwidth: Arc<BandwidthCounter>,
+) -> Boxed<(PeerId, StreamMuxerBox), io::Error> {
     let transport = tcp::TcpConfig::new().nodelay(true);
     let transport = dns::DnsConfig::new(transport).unwrap();
+    let transport = match psk {
+        Some(psk) => {
+            info!(
+                "Private network enabled, fingerprint: {}",
+                psk.fingerprint()
+            );
+            EitherTransport::Left(
+                transport.and_then(move |socket, _| PnetConfig::new(psk).handshake(socket)),
+            )
+        }
+        None => EitherTransport::Right(transport),
+    };
+
+    // Meter the secured connection, the peer is known once it's authenticated.
+    let inbound_bandwidth = bandwidth.clone();
+    let secio = upgrade::MapInboundUpgrade::new(
+        secio::SecioConfig::new(local_key_pair),
+        move |(peer, io)| {
+            let io = MeteredStream::with_peer(io, peer.clone(), inbound_bandwidth);
+            (peer, io)
+        },
+    );
+    let secio = upgrade::MapOutboundUpgrade::new(secio, move |(peer, io)| {
+        let io = MeteredStream::with_peer(io, peer.clone(), bandwidth);
+        (peer, io)
+    });
 
     transport
         .upgrade(upgrade::Version::V1)
-        .authenticate(secio::SecioConfig::new(local_key_pair))
+        .authenticate(secio)
         .multiplex(upgrade::SelectUpgrade::new(
             yamux::Config::default(),
             mplex::MplexConfig::new(),
@@ -0,0 +1,254 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p_core::PeerId;
+use parking_lot::Mutex;
+
+/// The interval at which the rates are sampled.
+const RATE_INTERVAL: Duration = Duration::from_secs(1);
+/// The weight of the latest sample in the rate EWMA, `1 - e^-1`, the same as go-flow-metrics.
+const RATE_ALPHA: f64 = 0.632_120_558_828_557_7;
+
+/// The direction of the traffic.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// The bytes received from the remote.
+    Inbound,
+    /// The bytes sent to the remote.
+    Outbound,
+}
+
+/// The bandwidth statistics, the totals are in bytes and the rates are in bytes per second.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BandwidthStats {
+    /// The total number of the received bytes.
+    pub total_in: u64,
+    /// The total number of the sent bytes.
+    pub total_out: u64,
+    /// The rate of receiving.
+    pub rate_in: f64,
+    /// The rate of sending.
+    pub rate_out: f64,
+}
+
+#[derive(Clone, Debug)]
+struct Meter {
+    total: u64,
+    rate: f64,
+    // The bytes recorded since the last sample.
+    pending: u64,
+    sampled_at: Instant,
+    sampled: bool,
+}
+
+impl Meter {
+    fn new(now: Instant) -> Self {
+        Self {
+            total: 0,
+            rate: 0.0,
+            pending: 0,
+            sampled_at: now,
+            sampled: false,
+        }
+    }
+
+    fn record(&mut self, bytes: u64, now: Instant) {
+        self.update(now);
+        self.total += bytes;
+        self.pending += bytes;
+    }
+
+    // Sample the rate once per elapsed interval, the pending bytes are spread over the intervals.
+    fn update(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.sampled_at);
+        let intervals = (elapsed.as_nanos() / RATE_INTERVAL.as_nanos()) as i32;
+        if intervals == 0 {
+            return;
+        }
+        let sample = self.pending as f64 / elapsed.as_secs_f64();
+        self.rate = if self.sampled {
+            sample + (self.rate - sample) * (1.0 - RATE_ALPHA).powi(intervals)
+        } else {
+            sample
+        };
+        self.sampled = true;
+        self.pending = 0;
+        self.sampled_at = now;
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Traffic {
+    inbound: Meter,
+    outbound: Meter,
+}
+
+impl Traffic {
+    fn new(now: Instant) -> Self {
+        Self {
+            inbound: Meter::new(now),
+            outbound: Meter::new(now),
+        }
+    }
+
+    fn record(&mut self, direction: Direction, bytes: u64, now: Instant) {
+        match direction {
+            Direction::Inbound => self.inbound.record(bytes, now),
+            Direction::Outbound => self.outbound.record(bytes, now),
+        }
+    }
+
+    fn stats(&mut self, now: Instant) -> BandwidthStats {
+        self.inbound.update(now);
+        self.outbound.update(now);
+        BandwidthStats {
+            total_in: self.inbound.total,
+            total_out: self.outbound.total,
+            rate_in: self.inbound.rate,
+            rate_out: self.outbound.rate,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Counters {
+    total: Traffic,
+    peers: HashMap<PeerId, Traffic>,
+    protocols: HashMap<String, Traffic>,
+}
+
+/// The counter of the network traffic, in total, per peer and per protocol.
+///
+/// The traffic of the peers is recorded at the transport layer, which is also counted in total,
+/// while the traffic of the protocols is recorded at the protocol layer, which only covers the
+/// payload of the protocols. The counter is shared by the connections, all the methods take `&self`.
+#[derive(Debug)]
+pub struct BandwidthCounter {
+    counters: Mutex<Counters>,
+}
+
+impl Default for BandwidthCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BandwidthCounter {
+    /// Create a new counter.
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            counters: Mutex::new(Counters {
+                total: Traffic::new(now),
+                peers: HashMap::new(),
+                protocols: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Record the bytes transferred over the connection with the peer.
+    pub fn record_peer(&self, peer_id: &PeerId, direction: Direction, bytes: u64) {
+        self.record_peer_at(peer_id, direction, bytes, Instant::now())
+    }
+
+    /// Record the bytes transferred over the protocol.
+    pub fn record_protocol(&self, protocol: &str, direction: Direction, bytes: u64) {
+        self.record_protocol_at(protocol, direction, bytes, Instant::now())
+    }
+
+    /// Return the statistics of all the traffic.
+    pub fn totals(&self) -> BandwidthStats {
+        self.counters.lock().total.stats(Instant::now())
+    }
+
+    /// Return the statistics of the peer, `None` if no traffic with the peer is recorded.
+    pub fn peer_stats(&self, peer_id: &PeerId) -> Option<BandwidthStats> {
+        let now = Instant::now();
+        let mut counters = self.counters.lock();
+        counters
+            .peers
+            .get_mut(peer_id)
+            .map(|traffic| traffic.stats(now))
+    }
+
+    /// Return the statistics of all the peers.
+    pub fn by_peer(&self) -> HashMap<PeerId, BandwidthStats> {
+        let now = Instant::now();
+        let mut counters = self.counters.lock();
+        counters
+            .peers
+            .iter_mut()
+            .map(|(peer_id, traffic)| (peer_id.clone(), traffic.stats(now)))
+            .collect()
+    }
+
+    /// Return the statistics of all the protocols, keyed by the protocol IDs.
+    pub fn by_protocol(&self) -> HashMap<String, BandwidthStats> {
+        let now = Instant::now();
+        let mut counters = self.counters.lock();
+        counters
+            .protocols
+            .iter_mut()
+            .map(|(protocol, traffic)| (protocol.clone(), traffic.stats(now)))
+            .collect()
+    }
+
+    fn record_peer_at(&self, peer_id: &PeerId, direction: Direction, bytes: u64, now: Instant) {
+        let mut counters = self.counters.lock();
+        counters.total.record(direction, bytes, now);
+        counters
+            .peers
+            .entry(peer_id.clone())
+            .or_insert_with(|| Traffic::new(now))
+            .record(direction, bytes, now);
+    }
+
+    fn record_protocol_at(&self, protocol: &str, direction: Direction, bytes: u64, now: Instant) {
+        let mut counters = self.counters.lock();
+        if let Some(traffic) = counters.protocols.get_mut(protocol) {
+            traffic.record(direction, bytes, now);
+        } else {
+            let mut traffic = Traffic::new(now);
+            traffic.record(direction, bytes, now);
+            counters.protocols.insert(protocol.to_string(), traffic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_counter() {
+        let counter = BandwidthCounter::new();
+        let peer_id = PeerId::random();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        counter.record_peer_at(&peer_id, Direction::Inbound, 1000, start);
+        counter.record_peer_at(&peer_id, Direction::Outbound, 300, start);
+        counter.record_protocol_at("/fil/hello/1.0.0", Direction::Inbound, 100, start);
+
+        let mut counters = counter.counters.lock();
+        let stats = counters.peers.get_mut(&peer_id).unwrap().stats(at(1));
+        assert_eq!(stats.total_in, 1000);
+        assert_eq!(stats.total_out, 300);
+        assert!((stats.rate_in - 1000.0).abs() < 1e-6);
+        assert!((stats.rate_out - 300.0).abs() < 1e-6);
+        // The protocol traffic is not counted in total again.
+        assert_eq!(counters.total.stats(at(1)).total_in, 1000);
+
+        // The rate decays when idle.
+        let stats = counters.total.stats(at(3));
+        let expected = 1000.0 * (1.0 - RATE_ALPHA).powi(2);
+        assert!((stats.rate_in - expected).abs() < 1e-6);
+        drop(counters);
+
+        assert_eq!(counter.by_peer().len(), 1);
+        assert_eq!(counter.by_protocol()["/fil/hello/1.0.0"].total_in, 100);
+        assert_eq!(counter.peer_stats(&PeerId::random()), None);
+    }
+}
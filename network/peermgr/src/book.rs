@@ -0,0 +1,270 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libp2p_core::{Multiaddr, PeerId};
+
+/// The max number of the addresses kept for a peer.
+pub const MAX_ADDRS_PER_PEER: usize = 32;
+
+/// The information that a peer reports about itself over the identify protocol.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdentifyInfo {
+    /// The agent version of the peer, e.g. `lotus-0.5.4+git.xxx`.
+    pub agent_version: String,
+    /// The protocol version of the peer, e.g. `ipfs/0.1.0`.
+    pub protocol_version: String,
+    /// The protocols supported by the peer.
+    pub protocols: Vec<String>,
+    /// Our address observed by the peer.
+    pub observed_addr: Multiaddr,
+}
+
+/// The entry of a peer in the address book.
+#[derive(Clone, Debug)]
+pub struct PeerEntry {
+    /// The known addresses of the peer, the most recently seen one is the last.
+    pub addrs: Vec<Multiaddr>,
+    /// The latest information reported by the identify protocol.
+    pub identify: Option<IdentifyInfo>,
+    /// The last time the entry is updated.
+    pub last_seen: Instant,
+}
+
+impl PeerEntry {
+    fn new() -> Self {
+        Self {
+            addrs: vec![],
+            identify: None,
+            last_seen: Instant::now(),
+        }
+    }
+
+    fn add_address(&mut self, addr: Multiaddr) {
+        if let Some(index) = self.addrs.iter().position(|known| known == &addr) {
+            self.addrs.remove(index);
+        } else if self.addrs.len() >= MAX_ADDRS_PER_PEER {
+            self.addrs.remove(0);
+        }
+        self.addrs.push(addr);
+        self.last_seen = Instant::now();
+    }
+
+    /// Return the agent version of the peer if it's identified.
+    pub fn agent_version(&self) -> Option<&str> {
+        self.identify
+            .as_ref()
+            .map(|info| info.agent_version.as_str())
+    }
+
+    /// Return the protocols supported by the peer, which is empty if the peer is not identified.
+    pub fn protocols(&self) -> &[String] {
+        self.identify
+            .as_ref()
+            .map(|info| info.protocols.as_slice())
+            .unwrap_or(&[])
+    }
+}
+
+/// The address book of the peers, storing the addresses and the metadata of the peers.
+#[derive(Clone, Debug, Default)]
+pub struct PeerBook {
+    peers: HashMap<PeerId, PeerEntry>,
+}
+
+impl PeerBook {
+    /// Create an empty address book.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an address of the peer.
+    pub fn add_address(&mut self, peer_id: &PeerId, addr: Multiaddr) {
+        self.peers
+            .entry(peer_id.clone())
+            .or_insert_with(PeerEntry::new)
+            .add_address(addr);
+    }
+
+    /// Update the identify information and the listen addresses of the peer.
+    pub fn set_identify(
+        &mut self,
+        peer_id: &PeerId,
+        info: IdentifyInfo,
+        listen_addrs: impl IntoIterator<Item = Multiaddr>,
+    ) {
+        let entry = self
+            .peers
+            .entry(peer_id.clone())
+            .or_insert_with(PeerEntry::new);
+        for addr in listen_addrs {
+            entry.add_address(addr);
+        }
+        if entry.identify.as_ref() != Some(&info) {
+            debug!(
+                target: "peermgr",
+                "[set_identify] peer {} identified, agent version: {}",
+                peer_id, info.agent_version
+            );
+        }
+        entry.identify = Some(info);
+        entry.last_seen = Instant::now();
+    }
+
+    /// Return the entry of the peer.
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerEntry> {
+        self.peers.get(peer_id)
+    }
+
+    /// Return the known addresses of the peer.
+    pub fn addresses(&self, peer_id: &PeerId) -> &[Multiaddr] {
+        self.peers
+            .get(peer_id)
+            .map(|entry| entry.addrs.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Return the agent version of the peer if it's identified.
+    pub fn agent_version(&self, peer_id: &PeerId) -> Option<&str> {
+        self.peers.get(peer_id).and_then(PeerEntry::agent_version)
+    }
+
+    /// Return at most `limit` peers with their addresses, the most recently seen peers first,
+    /// which are shared with the other peers over the peer exchange protocol.
+    /// The `excluded` peer (usually the requester) and the peers without address are skipped.
+    pub fn recent_peers(&self, limit: usize, excluded: &PeerId) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        let mut peers = self
+            .peers
+            .iter()
+            .filter(|(peer_id, entry)| *peer_id != excluded && !entry.addrs.is_empty())
+            .collect::<Vec<_>>();
+        peers.sort_by(|a, b| b.1.last_seen.cmp(&a.1.last_seen));
+        peers
+            .into_iter()
+            .take(limit)
+            .map(|(peer_id, entry)| (peer_id.clone(), entry.addrs.clone()))
+            .collect()
+    }
+
+    /// Remove the peer from the address book.
+    pub fn remove(&mut self, peer_id: &PeerId) -> Option<PeerEntry> {
+        self.peers.remove(peer_id)
+    }
+
+    /// Remove the peers not seen within the `ttl` at `now`, except the ones to `keep`,
+    /// e.g. the connected peers, return the number of the removed peers.
+    pub fn prune(&mut self, now: Instant, ttl: Duration, keep: impl Fn(&PeerId) -> bool) -> usize {
+        let len = self.peers.len();
+        self.peers.retain(|peer_id, entry| {
+            now.saturating_duration_since(entry.last_seen) < ttl || keep(peer_id)
+        });
+        len - self.peers.len()
+    }
+
+    /// Return an iterator over all the peers in the address book.
+    pub fn iter(&self) -> impl Iterator<Item = (&PeerId, &PeerEntry)> {
+        self.peers.iter()
+    }
+
+    /// Return the number of the peers in the address book.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Return true if the address book is empty.
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identify_info(agent_version: &str) -> IdentifyInfo {
+        IdentifyInfo {
+            agent_version: agent_version.into(),
+            protocol_version: "ipfs/0.1.0".into(),
+            protocols: vec!["/fil/hello/1.0.0".into()],
+            observed_addr: "/ip4/127.0.0.1/tcp/1347".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_peer_book() {
+        let mut book = PeerBook::new();
+        let peer_id = PeerId::random();
+        let addr1: Multiaddr = "/ip4/1.2.3.4/tcp/1347".parse().unwrap();
+        let addr2: Multiaddr = "/ip4/1.2.3.5/tcp/1347".parse().unwrap();
+
+        book.add_address(&peer_id, addr1.clone());
+        assert_eq!(book.len(), 1);
+        assert_eq!(book.agent_version(&peer_id), None);
+        assert!(book.get(&peer_id).unwrap().protocols().is_empty());
+
+        // the known address is moved to the last.
+        book.set_identify(
+            &peer_id,
+            identify_info("lotus-0.5.4"),
+            vec![addr2.clone(), addr1.clone()],
+        );
+        assert_eq!(book.addresses(&peer_id), &[addr2, addr1][..]);
+        assert_eq!(book.agent_version(&peer_id), Some("lotus-0.5.4"));
+        assert_eq!(
+            book.get(&peer_id).unwrap().protocols(),
+            &["/fil/hello/1.0.0".to_string()][..]
+        );
+
+        book.set_identify(&peer_id, identify_info("lotus-0.5.6"), vec![]);
+        assert_eq!(book.agent_version(&peer_id), Some("lotus-0.5.6"));
+
+        for port in 0..MAX_ADDRS_PER_PEER as u16 {
+            let addr = format!("/ip4/1.2.3.6/tcp/{}", port).parse().unwrap();
+            book.add_address(&peer_id, addr);
+        }
+        assert_eq!(book.addresses(&peer_id).len(), MAX_ADDRS_PER_PEER);
+
+        assert!(book.remove(&peer_id).is_some());
+        assert!(book.is_empty());
+        assert!(book.addresses(&peer_id).is_empty());
+    }
+
+    #[test]
+    fn test_recent_peers() {
+        let mut book = PeerBook::new();
+        let peers = (0..4).map(|_| PeerId::random()).collect::<Vec<_>>();
+        for (i, peer_id) in peers.iter().enumerate() {
+            let addr = format!("/ip4/1.2.3.{}/tcp/1347", i).parse().unwrap();
+            book.add_address(peer_id, addr);
+        }
+        // The identified peer without address is not shared.
+        let no_addr = PeerId::random();
+        book.set_identify(&no_addr, identify_info("lotus-0.5.4"), vec![]);
+
+        let recent = book.recent_peers(10, &peers[0]);
+        assert_eq!(recent.len(), 3);
+        assert!(recent.iter().all(|(peer_id, addrs)| {
+            peer_id != &peers[0] && peer_id != &no_addr && addrs.len() == 1
+        }));
+        assert_eq!(book.recent_peers(2, &no_addr).len(), 2);
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut book = PeerBook::new();
+        let peers = (0..3).map(|_| PeerId::random()).collect::<Vec<_>>();
+        for peer_id in &peers {
+            book.add_address(peer_id, "/ip4/1.2.3.4/tcp/1347".parse().unwrap());
+        }
+        let ttl = Duration::from_secs(60);
+        assert_eq!(book.prune(Instant::now(), ttl, |_| false), 0);
+        assert_eq!(book.len(), 3);
+
+        // The expired peers are removed, except the kept one.
+        let later = Instant::now() + ttl;
+        assert_eq!(book.prune(later, ttl, |peer_id| peer_id == &peers[1]), 2);
+        assert_eq!(book.len(), 1);
+        assert!(book.get(&peers[1]).is_some());
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use libp2p_core::PeerId;
+
+/// The reason of a graceful disconnection, which is sent to the peer before the connection is
+/// closed, so that the peer doesn't redial immediately.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DisconnectReason {
+    /// The node is shutting down.
+    ShuttingDown,
+    /// The peer is banned for its misbehaviours.
+    Banned,
+    /// The node has too many peers.
+    TooManyPeers,
+    /// The reason of an unknown code, e.g. sent by a newer version.
+    Unknown(u64),
+}
+
+impl DisconnectReason {
+    /// Return the code of the reason on the wire.
+    pub fn code(self) -> u64 {
+        match self {
+            DisconnectReason::ShuttingDown => 1,
+            DisconnectReason::Banned => 2,
+            DisconnectReason::TooManyPeers => 3,
+            DisconnectReason::Unknown(code) => code,
+        }
+    }
+
+    /// Return the reason of the `code` on the wire.
+    pub fn from_code(code: u64) -> Self {
+        match code {
+            1 => DisconnectReason::ShuttingDown,
+            2 => DisconnectReason::Banned,
+            3 => DisconnectReason::TooManyPeers,
+            code => DisconnectReason::Unknown(code),
+        }
+    }
+
+    /// Return the duration during which the peer disconnecting us for the reason is not
+    /// redialed.
+    pub fn backoff(self) -> Duration {
+        match self {
+            DisconnectReason::ShuttingDown => Duration::from_secs(60),
+            // The ban of the peer won't be lifted soon.
+            DisconnectReason::Banned => Duration::from_secs(60 * 60),
+            DisconnectReason::TooManyPeers => Duration::from_secs(5 * 60),
+            DisconnectReason::Unknown(_) => Duration::from_secs(60),
+        }
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisconnectReason::ShuttingDown => f.write_str("shutting down"),
+            DisconnectReason::Banned => f.write_str("banned"),
+            DisconnectReason::TooManyPeers => f.write_str("too many peers"),
+            DisconnectReason::Unknown(code) => write!(f, "unknown reason {}", code),
+        }
+    }
+}
+
+/// The disconnection received from a peer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ReceivedDisconnect {
+    /// The reason sent by the peer.
+    pub reason: DisconnectReason,
+    /// The time before which the peer is not redialed.
+    pub until: Instant,
+}
+
+/// The tracker of the disconnection reasons received from the peers, which prevents redialing
+/// the peers that intentionally dropped us until the backoff of the reason elapses.
+#[derive(Clone, Debug, Default)]
+pub struct DisconnectTracker {
+    peers: HashMap<PeerId, ReceivedDisconnect>,
+}
+
+impl DisconnectTracker {
+    /// Create an empty tracker.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the disconnection reason received from the peer.
+    pub fn record(&mut self, peer_id: &PeerId, reason: DisconnectReason) {
+        self.record_at(peer_id, reason, Instant::now())
+    }
+
+    /// Return the disconnection received from the peer if its backoff hasn't elapsed.
+    pub fn get(&self, peer_id: &PeerId) -> Option<&ReceivedDisconnect> {
+        self.get_at(peer_id, Instant::now())
+    }
+
+    /// Return true if the peer can be dialed, i.e. it hasn't dropped us recently.
+    pub fn can_dial(&self, peer_id: &PeerId) -> bool {
+        self.get(peer_id).is_none()
+    }
+
+    /// Remove the disconnection of the peer, e.g. when the peer dials us again.
+    pub fn remove(&mut self, peer_id: &PeerId) -> Option<ReceivedDisconnect> {
+        self.peers.remove(peer_id)
+    }
+
+    /// Remove the disconnections whose backoff has elapsed.
+    pub fn prune(&mut self) {
+        self.prune_at(Instant::now())
+    }
+
+    /// Return the number of the tracked disconnections, including the elapsed ones not pruned.
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    /// Return true if no disconnection is tracked.
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    fn record_at(&mut self, peer_id: &PeerId, reason: DisconnectReason, now: Instant) {
+        debug!(
+            target: "peermgr",
+            "[record_disconnect] peer {} disconnected us: {}",
+            peer_id, reason
+        );
+        let until = now + reason.backoff();
+        self.peers
+            .insert(peer_id.clone(), ReceivedDisconnect { reason, until });
+    }
+
+    fn get_at(&self, peer_id: &PeerId, now: Instant) -> Option<&ReceivedDisconnect> {
+        self.peers
+            .get(peer_id)
+            .filter(|disconnect| disconnect.until > now)
+    }
+
+    fn prune_at(&mut self, now: Instant) {
+        self.peers.retain(|_, disconnect| disconnect.until > now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reason_code() {
+        for reason in &[
+            DisconnectReason::ShuttingDown,
+            DisconnectReason::Banned,
+            DisconnectReason::TooManyPeers,
+            DisconnectReason::Unknown(100),
+        ] {
+            assert_eq!(DisconnectReason::from_code(reason.code()), *reason);
+        }
+        assert_eq!(DisconnectReason::from_code(0), DisconnectReason::Unknown(0));
+    }
+
+    #[test]
+    fn test_disconnect_tracker() {
+        let mut tracker = DisconnectTracker::new();
+        let busy = PeerId::random();
+        let banning = PeerId::random();
+        assert!(tracker.can_dial(&busy));
+
+        let now = Instant::now();
+        tracker.record_at(&busy, DisconnectReason::TooManyPeers, now);
+        tracker.record_at(&banning, DisconnectReason::Banned, now);
+        assert!(!tracker.can_dial(&busy));
+        assert_eq!(
+            tracker.get(&banning).unwrap().reason,
+            DisconnectReason::Banned
+        );
+
+        // The busy peer can be redialed after its backoff, while the banning one can't.
+        let later = now + DisconnectReason::TooManyPeers.backoff();
+        assert!(tracker.get_at(&busy, later).is_none());
+        assert!(tracker.get_at(&banning, later).is_some());
+        tracker.prune_at(later);
+        assert_eq!(tracker.len(), 1);
+
+        assert!(tracker.remove(&banning).is_some());
+        assert!(tracker.is_empty());
+        assert!(tracker.can_dial(&banning));
+    }
+}
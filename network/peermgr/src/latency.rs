@@ -0,0 +1,155 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use libp2p_core::PeerId;
+
+/// The default weight of the latest sample in the latency EWMA.
+pub const DEFAULT_LATENCY_ALPHA: f64 = 0.2;
+/// The default number of consecutive ping failures after which a peer is deemed unresponsive.
+pub const DEFAULT_MAX_PING_FAILURES: u32 = 3;
+
+/// The liveness and latency statistic of a peer.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PeerLatency {
+    /// The exponentially weighted moving average of the round trip time,
+    /// `None` if no ping succeeded.
+    pub ewma: Option<Duration>,
+    /// The number of the consecutive ping failures.
+    pub failures: u32,
+}
+
+/// The tracker of the ping round trip time of the peers, used for preferring the responsive
+/// peers when selecting a peer to fetch from.
+#[derive(Clone, Debug)]
+pub struct LatencyTracker {
+    alpha: f64,
+    max_failures: u32,
+    peers: HashMap<PeerId, PeerLatency>,
+}
+
+impl Default for LatencyTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_LATENCY_ALPHA, DEFAULT_MAX_PING_FAILURES)
+    }
+}
+
+impl LatencyTracker {
+    /// Create a new tracker with the weight of the latest sample and the max consecutive failures.
+    pub fn new(alpha: f64, max_failures: u32) -> Self {
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be within (0, 1]");
+        Self {
+            alpha,
+            max_failures,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Record a successful ping of the peer with the round trip time.
+    pub fn record_success(&mut self, peer_id: &PeerId, rtt: Duration) {
+        let alpha = self.alpha;
+        let latency = self.peers.entry(peer_id.clone()).or_default();
+        latency.ewma = Some(match latency.ewma {
+            Some(ewma) => ewma.mul_f64(1.0 - alpha) + rtt.mul_f64(alpha),
+            None => rtt,
+        });
+        latency.failures = 0;
+    }
+
+    /// Record a failed ping of the peer, return true if the peer has failed too many times
+    /// consecutively and should be disconnected.
+    pub fn record_failure(&mut self, peer_id: &PeerId) -> bool {
+        let latency = self.peers.entry(peer_id.clone()).or_default();
+        latency.failures += 1;
+        latency.failures >= self.max_failures
+    }
+
+    /// Return the latency statistic of the peer.
+    pub fn get(&self, peer_id: &PeerId) -> Option<&PeerLatency> {
+        self.peers.get(peer_id)
+    }
+
+    /// Return the latency EWMA of the peer.
+    pub fn latency(&self, peer_id: &PeerId) -> Option<Duration> {
+        self.peers.get(peer_id).and_then(|latency| latency.ewma)
+    }
+
+    /// Remove the peer, e.g. when it's disconnected.
+    pub fn remove(&mut self, peer_id: &PeerId) -> Option<PeerLatency> {
+        self.peers.remove(peer_id)
+    }
+
+    /// Sort the candidate peers by preference: the peers without failures first, then
+    /// the lower latency first. The peers without any latency sample are placed after
+    /// the measured ones with the same number of failures.
+    pub fn sort_peers(&self, peers: &mut [PeerId]) {
+        peers.sort_by_key(|peer_id| self.preference(peer_id))
+    }
+
+    /// Select the most preferred peer among the candidates.
+    pub fn select_peer<'a>(
+        &self,
+        peers: impl IntoIterator<Item = &'a PeerId>,
+    ) -> Option<&'a PeerId> {
+        peers
+            .into_iter()
+            .min_by_key(|peer_id| self.preference(peer_id))
+    }
+
+    // The smaller the more preferred.
+    fn preference(&self, peer_id: &PeerId) -> (u32, bool, Duration) {
+        let latency = self.peers.get(peer_id).copied().unwrap_or_default();
+        (
+            latency.failures,
+            latency.ewma.is_none(),
+            latency.ewma.unwrap_or_default(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_ewma() {
+        let mut tracker = LatencyTracker::new(0.5, 2);
+        let peer_id = PeerId::random();
+        assert_eq!(tracker.latency(&peer_id), None);
+
+        tracker.record_success(&peer_id, Duration::from_millis(100));
+        assert_eq!(tracker.latency(&peer_id), Some(Duration::from_millis(100)));
+        tracker.record_success(&peer_id, Duration::from_millis(200));
+        assert_eq!(tracker.latency(&peer_id), Some(Duration::from_millis(150)));
+
+        assert!(!tracker.record_failure(&peer_id));
+        // the failures must be consecutive.
+        tracker.record_success(&peer_id, Duration::from_millis(150));
+        assert!(!tracker.record_failure(&peer_id));
+        assert!(tracker.record_failure(&peer_id));
+        assert_eq!(tracker.get(&peer_id).unwrap().failures, 2);
+
+        assert!(tracker.remove(&peer_id).is_some());
+        assert!(tracker.get(&peer_id).is_none());
+    }
+
+    #[test]
+    fn test_sort_peers() {
+        let mut tracker = LatencyTracker::default();
+        let fast = PeerId::random();
+        let slow = PeerId::random();
+        let unknown = PeerId::random();
+        let failed = PeerId::random();
+        tracker.record_success(&fast, Duration::from_millis(10));
+        tracker.record_success(&slow, Duration::from_millis(500));
+        tracker.record_success(&failed, Duration::from_millis(1));
+        tracker.record_failure(&failed);
+
+        let mut peers = vec![failed.clone(), unknown.clone(), slow.clone(), fast.clone()];
+        tracker.sort_peers(&mut peers);
+        assert_eq!(peers, vec![fast.clone(), slow, unknown, failed]);
+        assert_eq!(tracker.select_peer(&peers), Some(&fast));
+        assert_eq!(tracker.select_peer(&[]), None);
+    }
+}
@@ -1,5 +1,22 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+#[macro_use]
+extern crate log;
+
+mod bandwidth;
+mod book;
+mod disconnect;
+mod latency;
+mod score;
+
+pub use self::bandwidth::{BandwidthCounter, BandwidthStats, Direction};
+pub use self::book::{IdentifyInfo, PeerBook, PeerEntry, MAX_ADDRS_PER_PEER};
+pub use self::disconnect::{DisconnectReason, DisconnectTracker, ReceivedDisconnect};
+pub use self::latency::{
+    LatencyTracker, PeerLatency, DEFAULT_LATENCY_ALPHA, DEFAULT_MAX_PING_FAILURES,
+};
+pub use self::score::{Misbehaviour, PeerScores, DEFAULT_BAN_THRESHOLD};
+
 /*
 use std::collections::HashMap;
 use std::time::Instant;
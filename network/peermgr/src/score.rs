@@ -0,0 +1,104 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use libp2p_core::PeerId;
+
+/// The default score at or below which a peer is banned.
+pub const DEFAULT_BAN_THRESHOLD: i32 = -100;
+
+/// The misbehaviours of the peers that are penalized.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Misbehaviour {
+    /// The peer claims a chain weight that doesn't match the weight computed from its chain.
+    InvalidChainWeight,
+    /// The peer serves a block or a tipset that fails the validation.
+    InvalidBlock,
+    /// The peer fails to serve a request that it's expected to serve.
+    UnservedRequest,
+}
+
+impl Misbehaviour {
+    /// Return the score deducted from the peer for the misbehaviour.
+    pub fn penalty(self) -> i32 {
+        match self {
+            // A spoofed weight could redirect the syncer, so the peer is banned at once.
+            Misbehaviour::InvalidChainWeight => 100,
+            Misbehaviour::InvalidBlock => 50,
+            Misbehaviour::UnservedRequest => 10,
+        }
+    }
+}
+
+/// The scores of the peers, lowered by the misbehaviours of the peers.
+#[derive(Clone, Debug)]
+pub struct PeerScores {
+    ban_threshold: i32,
+    scores: HashMap<PeerId, i32>,
+}
+
+impl Default for PeerScores {
+    fn default() -> Self {
+        Self::new(DEFAULT_BAN_THRESHOLD)
+    }
+}
+
+impl PeerScores {
+    /// Create new peer scores with the score at or below which a peer is banned.
+    pub fn new(ban_threshold: i32) -> Self {
+        assert!(ban_threshold < 0, "ban threshold must be negative");
+        Self {
+            ban_threshold,
+            scores: HashMap::new(),
+        }
+    }
+
+    /// Penalize the peer for the misbehaviour, return true if the peer should be banned.
+    pub fn penalize(&mut self, peer_id: &PeerId, misbehaviour: Misbehaviour) -> bool {
+        let score = self.scores.entry(peer_id.clone()).or_default();
+        *score = score.saturating_sub(misbehaviour.penalty());
+        debug!(
+            target: "peermgr",
+            "[penalize] peer {} penalized for {:?}, score: {}",
+            peer_id, misbehaviour, score
+        );
+        *score <= self.ban_threshold
+    }
+
+    /// Return the score of the peer, a peer without any misbehaviour has a zero score.
+    pub fn score(&self, peer_id: &PeerId) -> i32 {
+        self.scores.get(peer_id).copied().unwrap_or_default()
+    }
+
+    /// Return true if the peer should be banned.
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.score(peer_id) <= self.ban_threshold
+    }
+
+    /// Remove the score of the peer.
+    pub fn remove(&mut self, peer_id: &PeerId) -> Option<i32> {
+        self.scores.remove(peer_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_scores() {
+        let mut scores = PeerScores::default();
+        let honest = PeerId::random();
+        let liar = PeerId::random();
+
+        assert!(!scores.penalize(&honest, Misbehaviour::UnservedRequest));
+        assert_eq!(scores.score(&honest), -10);
+        assert!(!scores.is_banned(&honest));
+
+        assert!(scores.penalize(&liar, Misbehaviour::InvalidChainWeight));
+        assert!(scores.is_banned(&liar));
+
+        assert_eq!(scores.remove(&liar), Some(-100));
+        assert!(!scores.is_banned(&liar));
+    }
+}
@@ -1,36 +1,58 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
 use std::convert::TryFrom;
-use std::fmt::{self, Display};
+use std::fmt::{self, Display, Write};
+use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 
 use plum_hashing::blake2b_variable;
 
 use crate::constant;
-use crate::errors::AddressError;
-use crate::network::{Network, NETWORK_DEFAULT, NETWORK_MAINNET_PREFIX, NETWORK_TESTNET_PREFIX};
+use crate::errors::{AddressError, Result};
+use crate::network::{Network, NETWORK_DEFAULT};
 use crate::protocol::Protocol;
 
+lazy_static::lazy_static! {
+    // The lowercase base32 encoding without padding of the addresses, the uppercase letters are
+    // accepted by the decoding too. Building the encoding is costly, so it's built only once.
+    static ref ADDRESS_ENCODING: data_encoding::Encoding = {
+        let mut spec = data_encoding::Specification::new();
+        spec.symbols.push_str("abcdefghijklmnopqrstuvwxyz234567");
+        spec.translate.from.push_str("ABCDEFGHIJKLMNOPQRSTUVWXYZ");
+        spec.translate.to.push_str("abcdefghijklmnopqrstuvwxyz");
+        spec.encoding()
+            .expect("the base32 specification of address is valid; qed")
+    };
+}
+
 /// The general address structure.
-#[derive(PartialEq, Eq, Clone, Debug, Hash)]
+///
+/// The encoded bytes (protocol + payload) are stored inline, so creating, cloning and hashing
+/// an address never allocate, and the accessors return the slices of the inline buffer.
+#[derive(Clone)]
 pub struct Address {
+    // The protocol byte followed by the payload, the bytes after `len` are always zero.
+    //
     // `ID` protocol: payload is VarInt encoding.
     // `Secp256k1` protocol: payload is the hash of pubkey (length = 20)
     // `Actor` protocol: payload length = 20
     // `BLS` protocol: payload is pubkey (length = 48)
-    protocol: Protocol,
-    payload: Vec<u8>,
+    // `Delegated` protocol: payload is VarInt encoding namespace + sub-address (length <= 54)
+    bytes: [u8; constant::MAX_ADDRESS_BYTES_LEN],
+    len: u8,
 }
 
 impl Address {
     /// Create an address with the given protocol and payload
-    pub(crate) fn new<T: Into<Vec<u8>>>(
-        protocol: Protocol,
-        payload: T,
-    ) -> Result<Self, AddressError> {
-        let payload = payload.into();
+    pub(crate) fn new(protocol: Protocol, payload: &[u8]) -> Result<Self> {
         match protocol {
-            Protocol::Id => {}
+            Protocol::Id => {
+                // The payload must be exactly one canonical varint, which is decoded by `as_id`.
+                match plum_varint::decode_u64_canonical(payload) {
+                    Ok((_, rest)) if rest.is_empty() => {}
+                    _ => return Err(AddressError::InvalidPayload),
+                }
+            }
             Protocol::Secp256k1 | Protocol::Actor => {
                 if payload.len() != constant::PAYLOAD_HASH_LEN {
                     return Err(AddressError::InvalidPayload);
@@ -41,41 +63,93 @@ impl Address {
                     return Err(AddressError::InvalidPayload);
                 }
             }
+            Protocol::Delegated => {
+                // The namespace must be one canonical varint, followed by the sub-address.
+                match plum_varint::decode_u64_canonical(payload) {
+                    Ok((_, subaddress)) if subaddress.len() <= constant::MAX_SUBADDRESS_LEN => {}
+                    _ => return Err(AddressError::InvalidPayload),
+                }
+            }
         }
 
-        Ok(Self { protocol, payload })
+        Ok(Self::from_parts(protocol, payload))
+    }
+
+    // Copy the protocol and the payload into the inline buffer, the payload must be validated.
+    fn from_parts(protocol: Protocol, payload: &[u8]) -> Self {
+        let mut bytes = [0u8; constant::MAX_ADDRESS_BYTES_LEN];
+        bytes[0] = protocol as u8;
+        bytes[1..=payload.len()].copy_from_slice(payload);
+        Self {
+            bytes,
+            len: (1 + payload.len()) as u8,
+        }
     }
 
     /// Create an address using the `Id` protocol.
-    pub fn new_id_addr(id: u64) -> Result<Self, AddressError> {
-        let mut payload_buf = unsigned_varint::encode::u64_buffer();
-        let payload = unsigned_varint::encode::u64(id, &mut payload_buf);
+    pub fn new_id_addr(id: u64) -> Result<Self> {
+        let mut payload_buf = plum_varint::u64_buffer();
+        let payload = plum_varint::encode_u64(id, &mut payload_buf);
         Self::new(Protocol::Id, payload)
     }
 
-    /// Create an address using the `Secp256k1` protocol.
-    pub fn new_secp256k1_addr(pubkey: &[u8]) -> Result<Self, AddressError> {
-        if pubkey.len() != constant::SECP256K1_FULL_PUBLIC_KEY_LEN
-            && pubkey.len() != constant::SECP256K1_RAW_PUBLIC_KEY_LEN
-            && pubkey.len() != constant::SECP256K1_COMPRESSED_PUBLIC_KEY_LEN
-        {
-            return Err(AddressError::InvalidPayload);
+    /// Create an address using the `Secp256k1` protocol, the payload is the hash of `pubkey`.
+    ///
+    /// The `pubkey` is either the uncompressed public key (65 bytes, or 64 bytes without the
+    /// prefix) or the compressed public key (33 bytes), `InvalidPublicKeyLength` is returned
+    /// otherwise. Note that the compressed and the uncompressed forms of the same key are
+    /// different addresses, Filecoin uses the uncompressed form.
+    pub fn new_secp256k1_addr(pubkey: &[u8]) -> Result<Self> {
+        match pubkey.len() {
+            constant::SECP256K1_FULL_PUBLIC_KEY_LEN
+            | constant::SECP256K1_RAW_PUBLIC_KEY_LEN
+            | constant::SECP256K1_COMPRESSED_PUBLIC_KEY_LEN => {
+                Self::new(Protocol::Secp256k1, &address_hash(pubkey))
+            }
+            len => Err(AddressError::InvalidPublicKeyLength {
+                protocol: Protocol::Secp256k1,
+                len,
+            }),
         }
-        Self::new(Protocol::Secp256k1, address_hash(pubkey))
     }
 
-    /// Create an address using the `Actor` protocol.
-    pub fn new_actor_addr(data: &[u8]) -> Result<Self, AddressError> {
-        Self::new(Protocol::Actor, address_hash(data))
+    /// Create an address using the `Actor` protocol, the payload is the hash of `data`, which
+    /// can be of any length.
+    pub fn new_actor_addr(data: &[u8]) -> Result<Self> {
+        Self::new(Protocol::Actor, &address_hash(data))
     }
 
-    /// Create an address using the `BLS` protocol.
-    pub fn new_bls_addr(pubkey: &[u8]) -> Result<Self, AddressError> {
+    /// Create an address using the `BLS` protocol, the payload is the 48 bytes `pubkey`,
+    /// `InvalidPublicKeyLength` is returned otherwise.
+    pub fn new_bls_addr(pubkey: &[u8]) -> Result<Self> {
+        if pubkey.len() != constant::BLS_PUBLIC_KEY_LEN {
+            return Err(AddressError::InvalidPublicKeyLength {
+                protocol: Protocol::Bls,
+                len: pubkey.len(),
+            });
+        }
         Self::new(Protocol::Bls, pubkey)
     }
 
+    /// Create an address using the `Delegated` protocol, the `namespace` is the ID of the
+    /// address manager actor, which interprets the `subaddress`, e.g. the Ethereum address.
+    pub fn new_delegated_addr(namespace: u64, subaddress: &[u8]) -> Result<Self> {
+        if subaddress.len() > constant::MAX_SUBADDRESS_LEN {
+            return Err(AddressError::InvalidPayload);
+        }
+        let mut namespace_buf = plum_varint::u64_buffer();
+        let namespace = plum_varint::encode_u64(namespace, &mut namespace_buf);
+        let mut payload = [0u8; constant::MAX_ADDRESS_BYTES_LEN - 1];
+        payload[..namespace.len()].copy_from_slice(namespace);
+        payload[namespace.len()..namespace.len() + subaddress.len()].copy_from_slice(subaddress);
+        Self::new(
+            Protocol::Delegated,
+            &payload[..namespace.len() + subaddress.len()],
+        )
+    }
+
     /// Create an address represented by the encoding bytes `addr` (protocol + payload).
-    pub fn new_from_bytes(addr: &[u8]) -> Result<Self, AddressError> {
+    pub fn new_from_bytes(addr: &[u8]) -> Result<Self> {
         if addr.len() <= 1 {
             return Err(AddressError::InvalidLength);
         }
@@ -83,26 +157,27 @@ impl Address {
         Self::new(protocol, &addr[1..])
     }
 
-    /// Return the network type of the address.
+    /// Return the network type of the address, i.e. the network of the current thread, see
+    /// `NETWORK_DEFAULT`.
     pub fn network(&self) -> Network {
-        **NETWORK_DEFAULT
+        NETWORK_DEFAULT.get()
     }
 
     /// Return the protocol of the address.
     pub fn protocol(&self) -> Protocol {
-        self.protocol
+        Protocol::try_from(self.bytes[0]).expect("the protocol of address is validated; qed")
     }
 
     /// Return the payload of the address.
     pub fn payload(&self) -> &[u8] {
-        &self.payload
+        &self.bytes[1..self.len as usize]
     }
 
     /// If the `Address` is an ID address, return the ID of Address if possible.
     /// Returns None otherwise.
     pub fn as_id(&self) -> Option<u64> {
-        if let Protocol::Id = self.protocol {
-            let id = unsigned_varint::decode::u64(&self.payload)
+        if let Protocol::Id = self.protocol() {
+            let id = plum_varint::decode_u64_canonical(self.payload())
                 .expect("unsigned varint decode payload of ID Address shouldn't be fail; qed")
                 .0;
             Some(id)
@@ -111,104 +186,226 @@ impl Address {
         }
     }
 
+    /// Return the ID of the address, `NotIdAddress` is returned if it's not an ID address.
+    pub fn id(&self) -> Result<u64> {
+        self.as_id().ok_or(AddressError::NotIdAddress)
+    }
+
+    /// Return true if the address is an ID address.
+    pub fn is_id(&self) -> bool {
+        self.protocol() == Protocol::Id
+    }
+
+    /// Return true if the address is a key address, i.e. a `Secp256k1` or `BLS` address, which
+    /// is the signer of the messages and is resolved to an ID address by the InitActor.
+    pub fn is_key_address(&self) -> bool {
+        matches!(self.protocol(), Protocol::Secp256k1 | Protocol::Bls)
+    }
+
+    /// If the `Address` is a `Delegated` address, return the namespace and the sub-address.
+    /// Returns None otherwise.
+    pub fn as_delegated(&self) -> Option<(u64, &[u8])> {
+        if let Protocol::Delegated = self.protocol() {
+            let delegated = plum_varint::decode_u64_canonical(self.payload()).expect(
+                "unsigned varint decode namespace of Delegated Address shouldn't be fail; qed",
+            );
+            Some(delegated)
+        } else {
+            None
+        }
+    }
+
     /// Return the encoded bytes of address (protocol + payload).
-    pub fn as_bytes(&self) -> Vec<u8> {
-        let mut bytes = Vec::with_capacity(1 + self.payload.len());
-        bytes.push(self.protocol as u8);
-        bytes.extend_from_slice(self.payload());
-        bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len as usize]
     }
 
     /// Return the checksum of (protocol + payload).
     pub fn checksum(&self) -> Vec<u8> {
-        checksum(&self.as_bytes())
+        checksum(self.as_bytes())
+    }
+
+    /// Parse the addresses from their strings, e.g. the addresses of a state dump.
+    ///
+    /// The error of the first invalid address is returned.
+    pub fn parse_many<I, S>(strs: I) -> Result<Vec<Self>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        strs.into_iter().map(|s| s.as_ref().parse()).collect()
+    }
+
+    /// Format the addresses into their strings, sharing the scratch buffer of the checksums.
+    pub fn format_many<'a, I>(addrs: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = &'a Address>,
+    {
+        let mut scratch = Vec::new();
+        addrs
+            .into_iter()
+            .map(|addr| {
+                let mut out = String::with_capacity(constant::MAX_ADDRESS_STRING_LEN);
+                addr.format_into(&mut out, &mut scratch);
+                out
+            })
+            .collect()
+    }
+
+    // Append the string of the address to the `out`, the `scratch` is used to hold the payload
+    // and the checksum.
+    fn format_into(&self, out: &mut String, scratch: &mut Vec<u8>) {
+        let protocol = self.protocol();
+        out.push_str(NETWORK_DEFAULT.prefix());
+        out.push(char::from(b'0' + protocol as u8));
+        match protocol {
+            Protocol::Id => {
+                let id = plum_varint::decode_u64_canonical(self.payload())
+                    .expect("unsigned varint decode shouldn't be fail")
+                    .0;
+                write!(out, "{}", id).expect("writing into a String never fails; qed");
+            }
+            Protocol::Secp256k1 | Protocol::Actor | Protocol::Bls => {
+                scratch.clear();
+                scratch.extend_from_slice(self.payload());
+                scratch.extend_from_slice(&self.checksum());
+                ADDRESS_ENCODING.encode_append(scratch, out);
+            }
+            Protocol::Delegated => {
+                let (namespace, subaddress) = plum_varint::decode_u64_canonical(self.payload())
+                    .expect("unsigned varint decode shouldn't be fail");
+                write!(out, "{}f", namespace).expect("writing into a String never fails; qed");
+                scratch.clear();
+                scratch.extend_from_slice(subaddress);
+                scratch.extend_from_slice(&self.checksum());
+                ADDRESS_ENCODING.encode_append(scratch, out);
+            }
+        }
+    }
+
+    // A helper function for `from_str`, the `raw` is `{namespace}f{sub-address + checksum}`.
+    fn new_delegated_with_check(raw: &str) -> Result<Self> {
+        let (namespace, raw) = match raw.find('f') {
+            Some(index) => (&raw[..index], &raw[index + 1..]),
+            None => return Err(AddressError::InvalidPayload),
+        };
+        if namespace.len() > constant::MAX_U64_LEN {
+            return Err(AddressError::InvalidLength);
+        }
+        let namespace = namespace
+            .parse::<u64>()
+            .map_err(|_| AddressError::InvalidPayload)?;
+
+        let decoded = base32_decode(raw)?;
+        if decoded.len() < constant::CHECKSUM_HASH_LEN {
+            return Err(AddressError::InvalidLength);
+        }
+        let (subaddress, checksum) = decoded.split_at(decoded.len() - constant::CHECKSUM_HASH_LEN);
+        let addr = Self::new_delegated_addr(namespace, subaddress)?;
+        if !validate_checksum(addr.as_bytes(), checksum) {
+            return Err(AddressError::InvalidChecksum);
+        }
+        Ok(addr)
     }
 
     // A helper function for `from_str`.
-    fn new_with_check(
-        protocol: Protocol,
-        raw: &[u8],
-        payload_size: usize,
-    ) -> Result<Self, AddressError> {
+    fn new_with_check(protocol: Protocol, raw: &[u8], payload_size: usize) -> Result<Self> {
         let decoded = base32_decode(raw)?;
+        if decoded.len() < constant::CHECKSUM_HASH_LEN {
+            return Err(AddressError::InvalidLength);
+        }
         let (payload, checksum) = decoded.split_at(decoded.len() - constant::CHECKSUM_HASH_LEN);
         if payload.len() != payload_size {
             return Err(AddressError::InvalidPayload);
         }
 
-        let mut bytes = Vec::with_capacity(1 + payload_size);
-        bytes.push(protocol as u8);
-        bytes.extend_from_slice(payload);
-        if !validate_checksum(&bytes, checksum) {
+        let addr = Self::from_parts(protocol, payload);
+        if !validate_checksum(addr.as_bytes(), checksum) {
             return Err(AddressError::InvalidChecksum);
         }
+        Ok(addr)
+    }
+}
 
-        Ok(Self {
-            protocol,
-            payload: payload.to_vec(),
-        })
+impl PartialEq for Address {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for Address {}
+
+impl Hash for Address {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state)
+    }
+}
+
+impl fmt::Debug for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Address")
+            .field("protocol", &self.protocol())
+            .field("payload", &self.payload())
+            .finish()
     }
 }
 
 impl Display for Address {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self.protocol() {
-            Protocol::Id => {
-                let id = unsigned_varint::decode::u64(self.payload())
-                    .expect("unsigned varint decode shouldn't be fail")
-                    .0;
-                write!(
-                    f,
-                    "{}{}{}",
-                    NETWORK_DEFAULT.prefix(),
-                    self.protocol() as u8,
-                    id
-                )
-            }
-            Protocol::Secp256k1 | Protocol::Actor | Protocol::Bls => {
-                let mut payload_and_checksum = self.payload().to_vec();
-                payload_and_checksum.extend_from_slice(&checksum(&self.as_bytes()));
-                let base32 = base32_encode(payload_and_checksum);
-                write!(
-                    f,
-                    "{}{}{}",
-                    NETWORK_DEFAULT.prefix(),
-                    self.protocol() as u8,
-                    base32
-                )
-            }
-        }
+        let mut out = String::with_capacity(constant::MAX_ADDRESS_STRING_LEN);
+        self.format_into(&mut out, &mut Vec::new());
+        f.write_str(&out)
     }
 }
 
 impl FromStr for Address {
     type Err = AddressError;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
+    /// Parse the address of either network, see `from_str_on_network` for rejecting the
+    /// addresses of the other network.
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse_with_network(s).map(|(_, addr)| addr)
+    }
+}
+
+impl Address {
+    /// Parse the address, the `MismatchNetwork` error is returned if the address is not of
+    /// the `network`, e.g. a mainnet address given to a testnet node.
+    pub fn from_str_on_network(s: &str, network: Network) -> Result<Self> {
+        let (parsed, addr) = Self::parse_with_network(s)?;
+        if parsed != network {
+            return Err(AddressError::MismatchNetwork);
+        }
+        Ok(addr)
+    }
+
+    // Parse the address along with the network of its prefix.
+    fn parse_with_network(s: &str) -> Result<(Network, Self)> {
         if s.len() < 3 || s.len() > constant::MAX_ADDRESS_STRING_LEN {
             return Err(AddressError::InvalidLength);
         }
 
-        match &s[0..1] {
-            NETWORK_MAINNET_PREFIX | NETWORK_TESTNET_PREFIX => {
-                if &s[0..1] != NETWORK_DEFAULT.prefix() {
-                    return Err(AddressError::MismatchNetwork);
-                }
-            }
-            _ => return Err(AddressError::UnknownNetwork),
+        // The network and the protocol must be ASCII, otherwise the slicing below panics.
+        if !s.is_char_boundary(1) {
+            return Err(AddressError::UnknownNetwork);
         }
+        let network = Network::from_prefix(&s[0..1]).ok_or(AddressError::UnknownNetwork)?;
 
+        if !s.is_char_boundary(2) {
+            return Err(AddressError::UnknownProtocol);
+        }
         let protocol = match &s[1..2] {
             "0" => Protocol::Id,
             "1" => Protocol::Secp256k1,
             "2" => Protocol::Actor,
             "3" => Protocol::Bls,
+            "4" => Protocol::Delegated,
             _ => return Err(AddressError::UnknownProtocol),
         };
 
         let raw = &s[2..];
 
-        match protocol {
+        let addr = match protocol {
             Protocol::Id => {
                 if raw.len() > constant::MAX_U64_LEN {
                     return Err(AddressError::InvalidLength);
@@ -229,7 +426,34 @@ impl FromStr for Address {
             Protocol::Bls => {
                 Self::new_with_check(Protocol::Bls, raw.as_bytes(), constant::BLS_PUBLIC_KEY_LEN)
             }
-        }
+            Protocol::Delegated => Self::new_delegated_with_check(raw),
+        }?;
+        Ok((network, addr))
+    }
+}
+
+impl TryFrom<&[u8]> for Address {
+    type Error = AddressError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        Self::new_from_bytes(bytes)
+    }
+}
+
+impl TryFrom<Vec<u8>> for Address {
+    type Error = AddressError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self> {
+        Self::new_from_bytes(&bytes)
+    }
+}
+
+/// Convert the ID address to the actor ID, see `Address::id`.
+impl TryFrom<&Address> for u64 {
+    type Error = AddressError;
+
+    fn try_from(addr: &Address) -> Result<Self> {
+        addr.id()
     }
 }
 
@@ -248,14 +472,8 @@ fn address_hash(ingest: &[u8]) -> Vec<u8> {
     blake2b_variable(ingest, constant::PAYLOAD_HASH_LEN)
 }
 
-fn base32_encode(input: impl AsRef<[u8]>) -> String {
-    data_encoding::BASE32_NOPAD
-        .encode(input.as_ref())
-        .to_ascii_lowercase()
-}
-
-fn base32_decode(input: impl AsRef<[u8]>) -> Result<Vec<u8>, AddressError> {
-    Ok(data_encoding::BASE32_NOPAD.decode(&input.as_ref().to_ascii_uppercase())?)
+fn base32_decode(input: impl AsRef<[u8]>) -> Result<Vec<u8>> {
+    Ok(ADDRESS_ENCODING.decode(input.as_ref())?)
 }
 
 #[cfg(test)]
@@ -268,12 +486,206 @@ mod tests {
         assert_eq!(id_addr.payload(), [191, 214, 251, 5]);
     }
 
+    #[test]
+    fn test_id_accessors() {
+        let id_addr = Address::new_id_addr(1024).unwrap();
+        assert_eq!(id_addr.id(), Ok(1024));
+        assert_eq!(u64::try_from(&id_addr), Ok(1024));
+        assert!(id_addr.is_id());
+        assert!(!id_addr.is_key_address());
+
+        let bls_addr = Address::new_bls_addr(&[1; constant::BLS_PUBLIC_KEY_LEN]).unwrap();
+        let secp_addr = Address::new_secp256k1_addr(&[4; 65]).unwrap();
+        let actor_addr = Address::new_actor_addr(b"actor").unwrap();
+        let delegated_addr = Address::new_delegated_addr(10, &[1; 20]).unwrap();
+        for addr in &[&bls_addr, &secp_addr, &actor_addr, &delegated_addr] {
+            assert_eq!(addr.id(), Err(AddressError::NotIdAddress));
+            assert_eq!(u64::try_from(*addr), Err(AddressError::NotIdAddress));
+            assert!(!addr.is_id());
+        }
+        assert!(bls_addr.is_key_address());
+        assert!(secp_addr.is_key_address());
+        assert!(!actor_addr.is_key_address());
+        assert!(!delegated_addr.is_key_address());
+    }
+
+    #[test]
+    fn test_inline_bytes() {
+        assert_eq!(
+            std::mem::size_of::<Address>(),
+            constant::MAX_ADDRESS_BYTES_LEN + 1
+        );
+        let addr = Address::new_actor_addr(b"actor").unwrap();
+        assert_eq!(addr.as_bytes().len(), 1 + constant::PAYLOAD_HASH_LEN);
+        assert_eq!(addr.as_bytes()[0], Protocol::Actor as u8);
+        assert_eq!(&addr.as_bytes()[1..], addr.payload());
+        // The longest address fills the buffer.
+        let longest =
+            Address::new_delegated_addr(u64::max_value(), &[1; constant::MAX_SUBADDRESS_LEN])
+                .unwrap();
+        assert_eq!(longest.as_bytes().len(), constant::MAX_ADDRESS_BYTES_LEN);
+        assert_eq!(Address::new_from_bytes(longest.as_bytes()), Ok(longest));
+    }
+
+    #[test]
+    fn test_public_key_length() {
+        for len in &[
+            constant::SECP256K1_FULL_PUBLIC_KEY_LEN,
+            constant::SECP256K1_RAW_PUBLIC_KEY_LEN,
+            constant::SECP256K1_COMPRESSED_PUBLIC_KEY_LEN,
+        ] {
+            let addr = Address::new_secp256k1_addr(&vec![4; *len]).unwrap();
+            assert_eq!(addr.protocol(), Protocol::Secp256k1);
+        }
+        for len in &[0, 32, 48, 66] {
+            assert_eq!(
+                Address::new_secp256k1_addr(&vec![4; *len]),
+                Err(AddressError::InvalidPublicKeyLength {
+                    protocol: Protocol::Secp256k1,
+                    len: *len,
+                })
+            );
+        }
+        // The uncompressed and the compressed forms of a key are different addresses.
+        assert_ne!(
+            Address::new_secp256k1_addr(&[2; 33]).unwrap(),
+            Address::new_secp256k1_addr(&[2; 65]).unwrap()
+        );
+
+        assert!(Address::new_bls_addr(&[1; constant::BLS_PUBLIC_KEY_LEN]).is_ok());
+        assert_eq!(
+            Address::new_bls_addr(&[1; 96]),
+            Err(AddressError::InvalidPublicKeyLength {
+                protocol: Protocol::Bls,
+                len: 96,
+            })
+        );
+        assert_eq!(
+            Address::new_bls_addr(&[1; 96]).unwrap_err().to_string(),
+            "invalid Bls public key length: 96"
+        );
+    }
+
+    #[test]
+    fn test_malformed_address() {
+        // An unterminated varint, and trailing bytes after the varint.
+        assert!(Address::new_from_bytes(&[0, 0x80]).is_err());
+        assert!(Address::new_from_bytes(&[0, 0x01, 0x02]).is_err());
+        // An over-long varint of ID 1, which would be another encoding of `t01`.
+        assert!(Address::new_from_bytes(&[0, 0x81, 0x00]).is_err());
+        // A multibyte character in place of the network or the protocol.
+        assert!(Address::from_str("é0123").is_err());
+        assert!(Address::from_str("té123").is_err());
+        // The base32 string is shorter than the checksum.
+        let _guard = crate::with_network(Network::Test);
+        assert!(Address::from_str("t1aaaa").is_err());
+    }
+
+    #[test]
+    fn test_network() {
+        let addr = Address::new_id_addr(1024).unwrap();
+        {
+            let _guard = crate::with_network(Network::Main);
+            assert_eq!(addr.to_string(), "f01024");
+            {
+                let _guard = crate::with_network(Network::Test);
+                assert_eq!(addr.network(), Network::Test);
+                assert_eq!(addr.to_string(), "t01024");
+            }
+            assert_eq!(addr.network(), Network::Main);
+        }
+
+        // Either network is accepted unless the network is given.
+        assert_eq!(Address::from_str("f01024"), Ok(addr.clone()));
+        assert_eq!(Address::from_str("t01024"), Ok(addr.clone()));
+        assert_eq!(
+            Address::from_str_on_network("t01024", Network::Test),
+            Ok(addr)
+        );
+        assert_eq!(
+            Address::from_str_on_network("f01024", Network::Test),
+            Err(AddressError::MismatchNetwork)
+        );
+        assert_eq!(
+            Address::from_str("x01024"),
+            Err(AddressError::UnknownNetwork)
+        );
+    }
+
+    #[test]
+    fn test_try_from_bytes() {
+        let addr = Address::new_id_addr(1024).unwrap();
+        let bytes = addr.as_bytes().to_vec();
+        assert_eq!(Address::try_from(&bytes[..]), Ok(addr.clone()));
+        assert_eq!(Address::try_from(bytes), Ok(addr));
+        assert_eq!(
+            Address::try_from(vec![0u8]),
+            Err(AddressError::InvalidLength)
+        );
+        assert_eq!(
+            Address::try_from(&[5u8, 0][..]),
+            Err(AddressError::UnknownProtocol)
+        );
+        assert_eq!(
+            Address::try_from(vec![1u8; 20]),
+            Err(AddressError::InvalidPayload)
+        );
+    }
+
+    #[test]
+    fn test_delegated_address() {
+        let _guard = crate::with_network(Network::Test);
+        // The f4 address of the Ethereum address 0xd4c5fb16488aa48081296299d54b0c648c9333da.
+        let eth_addr = [
+            212, 197, 251, 22, 72, 138, 164, 128, 129, 41, 98, 153, 213, 75, 12, 100, 140, 147, 51,
+            218,
+        ];
+        let addr = Address::new_delegated_addr(10, &eth_addr).unwrap();
+        assert_eq!(addr.protocol(), Protocol::Delegated);
+        assert_eq!(addr.as_delegated(), Some((10, &eth_addr[..])));
+        assert_eq!(addr.as_id(), None);
+        assert_eq!(
+            addr.to_string(),
+            "t410f2tc7wfsirksibajjmkm5ksymmsgjgm62hjnomwa"
+        );
+        assert_eq!(
+            Address::from_str("t410f2tc7wfsirksibajjmkm5ksymmsgjgm62hjnomwa"),
+            Ok(addr.clone())
+        );
+        let mut bytes = vec![4, 10];
+        bytes.extend_from_slice(&eth_addr);
+        assert_eq!(addr.as_bytes(), &bytes[..]);
+        assert_eq!(Address::new_from_bytes(&bytes), Ok(addr));
+
+        // The empty and the longest sub-addresses.
+        for subaddress in &[vec![], vec![0xff; constant::MAX_SUBADDRESS_LEN]] {
+            let addr = Address::new_delegated_addr(u64::max_value(), subaddress).unwrap();
+            let s = addr.to_string();
+            assert!(s.len() <= constant::MAX_ADDRESS_STRING_LEN);
+            assert_eq!(Address::from_str(&s), Ok(addr));
+        }
+        assert_eq!(
+            Address::new_delegated_addr(10, &[0; constant::MAX_SUBADDRESS_LEN + 1]),
+            Err(AddressError::InvalidPayload)
+        );
+
+        // Bad checksum, missing separator and namespace.
+        assert_eq!(
+            Address::from_str("t410f3tc7wfsirksibajjmkm5ksymmsgjgm62hjnomwa"),
+            Err(AddressError::InvalidChecksum)
+        );
+        assert!(Address::from_str("t4102tc7wfsirksibajjmkm5ksymmsgjgm62hjnomwa").is_err());
+        assert!(Address::from_str("t4f2tc7wfsirksibajjmkm5ksymmsgjgm62hjnomwa").is_err());
+        // An over-long varint of the namespace.
+        assert!(Address::new_from_bytes(&[4, 0x8a, 0x00, 1]).is_err());
+    }
+
     #[test]
     fn test_checksum() {
-        unsafe { crate::set_network(Network::Test) };
+        let _guard = crate::with_network(Network::Test);
         let addr = Address::from_str("t24dd4ox4c2vpf5vk5wkadgyyn6qtuvgcpxxon64a").unwrap();
         let checksum = addr.checksum();
-        assert!(validate_checksum(&addr.as_bytes(), checksum.as_slice()));
+        assert!(validate_checksum(addr.as_bytes(), checksum.as_slice()));
     }
 
     #[test]
@@ -292,9 +704,30 @@ mod tests {
             253, 29, 15, 77, 252, 215, 233, 154, 252, 185, 154, 131, 38, 183, 220, 69, 157, 50,
             198, 40, 148, 236, 248, 227,
         ];
-        let encoded = base32_encode(input);
+        let encoded = ADDRESS_ENCODING.encode(&input);
         assert_eq!(encoded, "7uoq6tp427uzv7fztkbsnn64iwotfrristwpryy");
         let decoded = base32_decode(encoded).unwrap();
         assert_eq!(decoded, input);
+        // The uppercase letters are accepted too.
+        let decoded = base32_decode("7UOQ6TP427UZV7FZTKBSNN64IWOTFRRISTWPRYY").unwrap();
+        assert_eq!(decoded, input);
+    }
+
+    #[test]
+    fn test_parse_and_format_many() {
+        let _guard = crate::with_network(Network::Test);
+        let strs = vec![
+            "t01024",
+            "t15ihq5ibzwki2b4ep2f46avlkrqzhpqgtga7pdrq",
+            "t24dd4ox4c2vpf5vk5wkadgyyn6qtuvgcpxxon64a",
+        ];
+        let addrs = Address::parse_many(&strs).unwrap();
+        assert_eq!(addrs[0], Address::new_id_addr(1024).unwrap());
+        assert_eq!(Address::format_many(&addrs), strs);
+        assert_eq!(
+            addrs.iter().map(Address::to_string).collect::<Vec<_>>(),
+            strs
+        );
+        assert!(Address::parse_many(vec!["t01024", "t1aaaa"]).is_err());
     }
 }
@@ -2,7 +2,10 @@
 
 use thiserror::Error;
 
-use crate::network::NETWORK_DEFAULT;
+use crate::protocol::Protocol;
+
+/// Alias for a `Result` with the default error type `AddressError`.
+pub type Result<T, E = AddressError> = std::result::Result<T, E>;
 
 /// Errors generated from this library.
 #[derive(PartialEq, Eq, Debug, Error)]
@@ -11,14 +14,25 @@ pub enum AddressError {
     #[error("unknown network")]
     UnknownNetwork,
     /// Mismatch network.
-    #[error("Network do not match default network (current: {})", NETWORK_DEFAULT.prefix())]
+    #[error("network of address doesn't match the expected network")]
     MismatchNetwork,
     /// Unknown address protocol.
     #[error("unknown protocol")]
     UnknownProtocol,
+    /// Expect an ID address.
+    #[error("expected ID address")]
+    NotIdAddress,
     /// Invalid address payload.
     #[error("invalid address payload")]
     InvalidPayload,
+    /// Invalid length of the public key of a `Secp256k1` or `BLS` address.
+    #[error("invalid {protocol:?} public key length: {len}")]
+    InvalidPublicKeyLength {
+        /// The protocol of the address.
+        protocol: Protocol,
+        /// The length of the public key.
+        len: usize,
+    },
     /// Invalid address length.
     #[error("invalid address length")]
     InvalidLength,
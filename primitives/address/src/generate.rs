@@ -0,0 +1,185 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+// The generators of the valid addresses for the property tests and the fuzzers, see the
+// `arbitrary` and `proptest` features.
+
+use plum_hashing::blake2b_variable;
+
+use crate::address::Address;
+use crate::constant;
+use crate::protocol::Protocol;
+
+/// All the protocols of the addresses.
+const PROTOCOLS: [Protocol; 5] = [
+    Protocol::Id,
+    Protocol::Secp256k1,
+    Protocol::Actor,
+    Protocol::Bls,
+    Protocol::Delegated,
+];
+
+/// The valid BLS public keys (compressed G1 points) of the Lotus test vectors, a random 48
+/// bytes payload is rarely a point on the curve.
+const BLS_PUBLIC_KEYS: [[u8; constant::BLS_PUBLIC_KEY_LEN]; 5] = [
+    [
+        173, 88, 223, 105, 110, 45, 78, 145, 234, 134, 200, 129, 233, 56, 186, 78, 168, 27, 57, 94,
+        18, 121, 123, 132, 185, 207, 49, 75, 149, 70, 112, 94, 131, 156, 122, 153, 214, 6, 178, 71,
+        221, 180, 249, 172, 122, 52, 20, 221,
+    ],
+    [
+        179, 41, 79, 10, 46, 41, 224, 198, 110, 188, 35, 93, 47, 237, 202, 86, 151, 191, 120, 74,
+        246, 5, 199, 90, 246, 8, 230, 166, 61, 92, 211, 142, 168, 92, 168, 152, 158, 14, 253, 233,
+        24, 139, 56, 47, 147, 114, 70, 13,
+    ],
+    [
+        150, 161, 163, 228, 234, 122, 20, 212, 153, 133, 230, 97, 178, 36, 1, 212, 79, 237, 64, 45,
+        29, 9, 37, 178, 67, 201, 35, 88, 156, 15, 188, 126, 50, 205, 4, 226, 158, 215, 141, 21,
+        211, 125, 58, 170, 63, 230, 218, 51,
+    ],
+    [
+        134, 180, 84, 37, 140, 88, 148, 117, 247, 209, 111, 90, 172, 1, 138, 121, 246, 193, 22,
+        157, 32, 252, 51, 146, 29, 216, 181, 206, 28, 172, 108, 52, 143, 144, 163, 96, 54, 36, 246,
+        174, 185, 27, 100, 81, 140, 46, 128, 149,
+    ],
+    [
+        167, 114, 107, 3, 128, 34, 247, 90, 56, 70, 23, 88, 83, 96, 206, 230, 41, 7, 10, 45, 157,
+        40, 113, 41, 101, 229, 242, 110, 204, 64, 133, 131, 130, 128, 55, 36, 237, 52, 242, 114, 3,
+        54, 240, 157, 182, 49, 240, 116,
+    ],
+];
+
+// Build a valid address of the `protocol` from the random `number` and `data`:
+// - `ID`: the ID is the `number`.
+// - `Secp256k1` and `Actor`: the payload is the hash of the `data`, i.e. the public key or the
+//   data of the actor address.
+// - `BLS`: the public key is selected from the valid keys by the `number`.
+// - `Delegated`: the namespace is the `number`, the sub-address is the `data` truncated to the
+//   max sub-address length.
+fn generate(protocol: Protocol, number: u64, data: &[u8]) -> Address {
+    match protocol {
+        Protocol::Id => Address::new_id_addr(number),
+        Protocol::Secp256k1 => Address::new(
+            Protocol::Secp256k1,
+            &blake2b_variable(data, constant::PAYLOAD_HASH_LEN),
+        ),
+        Protocol::Actor => Address::new_actor_addr(data),
+        Protocol::Bls => {
+            let index = (number % BLS_PUBLIC_KEYS.len() as u64) as usize;
+            Address::new_bls_addr(&BLS_PUBLIC_KEYS[index])
+        }
+        Protocol::Delegated => {
+            let len = data.len().min(constant::MAX_SUBADDRESS_LEN);
+            Address::new_delegated_addr(number, &data[..len])
+        }
+    }
+    .expect("the generated payload is valid for the protocol; qed")
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl {
+    use arbitrary::{Arbitrary, Result, Unstructured};
+
+    use super::*;
+
+    impl Arbitrary for Address {
+        fn arbitrary(u: &mut Unstructured<'_>) -> Result<Self> {
+            let protocol = *u.choose(&PROTOCOLS)?;
+            let number = u64::arbitrary(u)?;
+            let len = u.int_in_range(0..=constant::MAX_SUBADDRESS_LEN)?;
+            let data = u.get_bytes(len)?;
+            Ok(generate(protocol, number, data))
+        }
+
+        fn size_hint(_depth: usize) -> (usize, Option<usize>) {
+            // The protocol, the number, the length and the data.
+            (1 + 8 + 1, Some(1 + 8 + 1 + constant::MAX_SUBADDRESS_LEN))
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+mod proptest_impl {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Return the strategy generating the valid addresses of the `protocol`.
+    pub fn address_strategy(protocol: Protocol) -> impl Strategy<Value = Address> {
+        (
+            any::<u64>(),
+            proptest::collection::vec(any::<u8>(), 0..=constant::MAX_SUBADDRESS_LEN),
+        )
+            .prop_map(move |(number, data)| generate(protocol, number, &data))
+    }
+
+    impl Arbitrary for Address {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        /// Generate the valid addresses of all the protocols.
+        fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+            proptest::sample::select(&PROTOCOLS[..])
+                .prop_flat_map(address_strategy)
+                .boxed()
+        }
+    }
+}
+
+#[cfg(feature = "proptest")]
+pub use self::proptest_impl::address_strategy;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        for protocol in PROTOCOLS.iter().copied() {
+            for (number, data) in &[(0, vec![]), (u64::MAX, vec![0xff; 100])] {
+                let addr = generate(protocol, *number, data);
+                assert_eq!(addr.protocol(), protocol);
+                // The generated addresses are valid, i.e. decoded from their bytes and string.
+                assert_eq!(Address::new_from_bytes(addr.as_bytes()).unwrap(), addr);
+                assert_eq!(addr.to_string().parse::<Address>().unwrap(), addr);
+            }
+        }
+        for key in BLS_PUBLIC_KEYS.iter() {
+            assert!(Address::new_bls_addr(key).is_ok());
+        }
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn test_arbitrary() {
+        use arbitrary::{Arbitrary, Unstructured};
+
+        let data = (0..=255u8).cycle().take(8192).collect::<Vec<_>>();
+        let mut u = Unstructured::new(&data);
+        for _ in 0..100 {
+            let addr = Address::arbitrary(&mut u).unwrap();
+            assert_eq!(Address::new_from_bytes(addr.as_bytes()).unwrap(), addr);
+        }
+    }
+
+    #[cfg(feature = "proptest")]
+    mod proptests {
+        use proptest::prelude::*;
+
+        use super::*;
+
+        proptest! {
+            #[test]
+            fn test_proptest_address(addr in any::<Address>()) {
+                prop_assert_eq!(Address::new_from_bytes(addr.as_bytes()).unwrap(), addr.clone());
+                let mut encoded = vec![];
+                addr.marshal_cbor(&mut encoded).unwrap();
+                prop_assert_eq!(Address::unmarshal_cbor(&mut &encoded[..]).unwrap(), addr);
+            }
+
+            #[test]
+            fn test_proptest_protocol(addr in address_strategy(Protocol::Bls)) {
+                prop_assert_eq!(addr.protocol(), Protocol::Bls);
+            }
+        }
+    }
+}
@@ -6,6 +6,8 @@
 
 mod address;
 mod errors;
+#[cfg(any(feature = "arbitrary", feature = "proptest", test))]
+mod generate;
 mod network;
 mod protocol;
 mod serde;
@@ -30,9 +32,16 @@ mod constant {
     /// The length of a secp256k1 compressed public key.
     pub const SECP256K1_COMPRESSED_PUBLIC_KEY_LEN: usize = 33;
 
-    /// The max length of an address encoded as a string,
-    /// which includes the network prefix, protocol, and `BLS` public key.
-    pub const MAX_ADDRESS_STRING_LEN: usize = 2 + 84;
+    /// The max length of the sub-address of a `Delegated` address.
+    pub const MAX_SUBADDRESS_LEN: usize = 54;
+    /// The max length of an address encoded as bytes (protocol + payload), i.e. the longest
+    /// `Delegated` address.
+    pub const MAX_ADDRESS_BYTES_LEN: usize = 1 + plum_varint::MAX_U64_LEN + MAX_SUBADDRESS_LEN;
+
+    /// The max length of an address encoded as a string, which includes the network prefix,
+    /// protocol, and the longest payload, i.e. the namespace, the separator and the base32
+    /// encoded sub-address with the checksum of a `Delegated` address.
+    pub const MAX_ADDRESS_STRING_LEN: usize = 2 + MAX_U64_LEN + 1 + 93;
     /// The hash length taken over addresses using the `Actor` and `Secp256k1` protocols.
     pub const PAYLOAD_HASH_LEN: usize = 20;
     /// The hash length used for calculating address checksums.
@@ -44,6 +53,10 @@ mod constant {
 
 pub use self::address::{checksum, validate_checksum, Address};
 pub use self::constant::*;
-pub use self::errors::AddressError;
-pub use self::network::{set_network, Network, NETWORK_DEFAULT};
+pub use self::errors::{AddressError, Result};
+#[cfg(feature = "proptest")]
+pub use self::generate::address_strategy;
+pub use self::network::{
+    set_network, with_network, DefaultNetwork, Network, NetworkGuard, NETWORK_DEFAULT,
+};
 pub use self::protocol::Protocol;
@@ -1,21 +1,65 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
-lazy_static::lazy_static! {
-    /// The default network type.
-    pub static ref NETWORK_DEFAULT: Box<Network> = Box::new(Network::Main);
-}
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// The process-wide default network, which is used to format the addresses.
+///
+/// The network of the current thread is overridden by `with_network`.
+pub static NETWORK_DEFAULT: DefaultNetwork = DefaultNetwork(AtomicU8::new(Network::Main as u8));
 
 pub(crate) const NETWORK_MAINNET_PREFIX: &str = "f";
 pub(crate) const NETWORK_TESTNET_PREFIX: &str = "t";
 
-/// # Safety
-/// this function should set at the beginning of programing, and only set once.
-/// could not change it in runtime.
-pub unsafe fn set_network(network: Network) {
-    if network != **NETWORK_DEFAULT {
-        let n = &**NETWORK_DEFAULT as *const Network as *mut Network;
-        let n = &mut *n;
-        *n = network;
+thread_local! {
+    // The network of the current thread set by `with_network`.
+    static NETWORK_OVERRIDE: Cell<Option<Network>> = Cell::new(None);
+}
+
+/// Set the process-wide default network, which should be set at the beginning of the program.
+pub fn set_network(network: Network) {
+    NETWORK_DEFAULT.0.store(network as u8, Ordering::Relaxed);
+}
+
+/// Override the network of the current thread until the returned guard is dropped, so the
+/// tests running in parallel don't interfere with each other through the default network.
+pub fn with_network(network: Network) -> NetworkGuard {
+    let previous = NETWORK_OVERRIDE.with(|cell| cell.replace(Some(network)));
+    NetworkGuard { previous }
+}
+
+/// The guard of the network override of `with_network`, the previous network of the current
+/// thread is restored when dropped.
+#[must_use = "the network override is dropped immediately if the guard is unused"]
+pub struct NetworkGuard {
+    previous: Option<Network>,
+}
+
+impl Drop for NetworkGuard {
+    fn drop(&mut self) {
+        NETWORK_OVERRIDE.with(|cell| cell.set(self.previous));
+    }
+}
+
+/// The default network of the addresses, see `NETWORK_DEFAULT`.
+pub struct DefaultNetwork(AtomicU8);
+
+impl DefaultNetwork {
+    /// Return the network of the current thread, i.e. the network of the innermost
+    /// `with_network` guard, or the process-wide default network.
+    pub fn get(&self) -> Network {
+        if let Some(network) = NETWORK_OVERRIDE.with(Cell::get) {
+            return network;
+        }
+        match self.0.load(Ordering::Relaxed) {
+            0 => Network::Main,
+            _ => Network::Test,
+        }
+    }
+
+    /// Return the prefix identifier of the network of the current thread.
+    pub fn prefix(&self) -> &'static str {
+        self.get().prefix()
     }
 }
 
@@ -23,9 +67,9 @@ pub unsafe fn set_network(network: Network) {
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
 pub enum Network {
     /// Main network, prefix: 'f'.
-    Main,
+    Main = 0,
     /// Test network, prefix: 't'.
-    Test,
+    Test = 1,
 }
 
 impl Default for Network {
@@ -42,4 +86,13 @@ impl Network {
             Network::Test => NETWORK_TESTNET_PREFIX,
         }
     }
+
+    // Return the network of the prefix identifier.
+    pub(crate) fn from_prefix(prefix: &str) -> Option<Self> {
+        match prefix {
+            NETWORK_MAINNET_PREFIX => Some(Network::Main),
+            NETWORK_TESTNET_PREFIX => Some(Network::Test),
+            _ => None,
+        }
+    }
 }
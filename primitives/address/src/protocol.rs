@@ -3,7 +3,7 @@
 use std::convert;
 use std::fmt;
 
-use crate::errors::AddressError;
+use crate::errors::{AddressError, Result};
 
 /// Protocol Identifier.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
@@ -16,6 +16,8 @@ pub enum Protocol {
     Actor = 2,
     /// `BLS` protocol, identifier: 3.
     Bls = 3,
+    /// `Delegated` protocol, identifier: 4.
+    Delegated = 4,
 }
 
 impl Default for Protocol {
@@ -27,12 +29,13 @@ impl Default for Protocol {
 impl convert::TryFrom<u8> for Protocol {
     type Error = AddressError;
 
-    fn try_from(value: u8) -> Result<Self, Self::Error> {
+    fn try_from(value: u8) -> Result<Self> {
         match value {
             0 => Ok(Protocol::Id),
             1 => Ok(Protocol::Secp256k1),
             2 => Ok(Protocol::Actor),
             3 => Ok(Protocol::Bls),
+            4 => Ok(Protocol::Delegated),
             _ => Err(AddressError::UnknownProtocol),
         }
     }
@@ -45,6 +48,7 @@ impl From<Protocol> for u8 {
             Protocol::Secp256k1 => 1,
             Protocol::Actor => 2,
             Protocol::Bls => 3,
+            Protocol::Delegated => 4,
         }
     }
 }
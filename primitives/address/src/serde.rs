@@ -1,14 +1,142 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{de, ser};
 
 use crate::address::Address;
+use crate::constant::{
+    BLS_PUBLIC_KEY_LEN, MAX_ADDRESS_BYTES_LEN, MAX_SUBADDRESS_LEN, PAYLOAD_HASH_LEN,
+};
+use crate::protocol::Protocol;
+
+// The CBOR major type of byte strings, in the high 3 bits of the header.
+const CBOR_MAJOR_BYTES: u8 = 2 << 5;
+
+impl Address {
+    /// Write the address as a CBOR byte string of the protocol and the payload,
+    /// which is the same as `MarshalCBOR` of go-address.
+    pub fn marshal_cbor<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let bytes = self.as_bytes();
+        // The address is never longer than 255 bytes, so the length takes at most 1 byte.
+        if bytes.len() < 24 {
+            writer.write_all(&[CBOR_MAJOR_BYTES | bytes.len() as u8])?;
+        } else {
+            writer.write_all(&[CBOR_MAJOR_BYTES | 24, bytes.len() as u8])?;
+        }
+        writer.write_all(bytes)
+    }
+
+    /// Read an address written by `marshal_cbor`, which is the same as `UnmarshalCBOR` of
+    /// go-address. Nothing after the address is read from the reader.
+    pub fn unmarshal_cbor<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut header = [0u8; 1];
+        reader.read_exact(&mut header)?;
+        if header[0] & 0xe0 != CBOR_MAJOR_BYTES {
+            return Err(invalid("expected CBOR byte string of address"));
+        }
+        let len = match header[0] & 0x1f {
+            len @ 0..=23 => len as usize,
+            24 => {
+                reader.read_exact(&mut header)?;
+                if header[0] < 24 {
+                    return Err(invalid("non-minimal CBOR length of address"));
+                }
+                header[0] as usize
+            }
+            _ => return Err(invalid("too many bytes to unmarshal for an address")),
+        };
+        if len > MAX_ADDRESS_BYTES_LEN {
+            return Err(invalid("too many bytes to unmarshal for an address"));
+        }
+        let mut bytes = [0u8; MAX_ADDRESS_BYTES_LEN];
+        reader.read_exact(&mut bytes[..len])?;
+        Address::new_from_bytes(&bytes[..len])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Write the binary form of the address (protocol + payload) without any framing, e.g.
+    /// into the content of the CBOR byte string being encoded.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+
+    /// Read an address in the binary form written by `write_to` without allocation.
+    ///
+    /// The length of the payload is implied by the protocol, i.e. the varint of the ID, the
+    /// hash, or the BLS public key. The sub-address of a `Delegated` address takes the rest of
+    /// the reader, so the reader should end with the address, e.g. the cursor of the content of
+    /// a CBOR byte string.
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut bytes = [0u8; MAX_ADDRESS_BYTES_LEN];
+        reader.read_exact(&mut bytes[..1])?;
+        let protocol = Protocol::try_from(bytes[0])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let len = match protocol {
+            Protocol::Id => 1 + read_varint(reader, &mut bytes[1..])?,
+            Protocol::Secp256k1 | Protocol::Actor => {
+                reader.read_exact(&mut bytes[1..=PAYLOAD_HASH_LEN])?;
+                1 + PAYLOAD_HASH_LEN
+            }
+            Protocol::Bls => {
+                reader.read_exact(&mut bytes[1..=BLS_PUBLIC_KEY_LEN])?;
+                1 + BLS_PUBLIC_KEY_LEN
+            }
+            Protocol::Delegated => {
+                let start = 1 + read_varint(reader, &mut bytes[1..])?;
+                let subaddress_len =
+                    read_rest(reader, &mut bytes[start..start + MAX_SUBADDRESS_LEN])?;
+                // Anything after the longest sub-address is not a part of the address.
+                if subaddress_len == MAX_SUBADDRESS_LEN && reader.read(&mut [0u8; 1])? != 0 {
+                    return Err(invalid("too many bytes to read for a delegated address"));
+                }
+                start + subaddress_len
+            }
+        };
+        Address::new_from_bytes(&bytes[..len])
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+// Read the bytes of a varint into the `buf`, return the length of the varint. The varint is
+// validated by the address.
+fn read_varint<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    for i in 0..plum_varint::MAX_U64_LEN {
+        reader.read_exact(&mut buf[i..=i])?;
+        if buf[i] & 0x80 == 0 {
+            return Ok(i + 1);
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "varint of address is too long",
+    ))
+}
+
+// Read the rest of the reader into the `buf` until the reader ends or the `buf` is full,
+// return the length read.
+fn read_rest<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(len)
+}
 
 // Implement CBOR serialization for Address.
 impl encode::Encode for Address {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
-        e.bytes(&self.as_bytes())?.ok()
+        e.bytes(self.as_bytes())?.ok()
     }
 }
 
@@ -21,30 +149,71 @@ impl<'b> decode::Decode<'b> for Address {
     }
 }
 
-// Implement JSON serialization for Address.
+// Implement serde serialization for Address, the human-readable formats (e.g. JSON) use the
+// string form of Lotus JSON-RPC, the others use the raw bytes like the CBOR encoding.
 impl ser::Serialize for Address {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: ser::Serializer,
     {
-        self.to_string().serialize(serializer)
+        if serializer.is_human_readable() {
+            self.to_string().serialize(serializer)
+        } else {
+            serializer.serialize_bytes(self.as_bytes())
+        }
     }
 }
 
-// Implement JSON deserialization for Address.
+// Implement serde deserialization for Address, see the serialization.
 impl<'de> de::Deserialize<'de> for Address {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        let addr = String::deserialize(deserializer)?;
-        addr.parse::<Address>().map_err(de::Error::custom)
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(AddressVisitor)
+        } else {
+            deserializer.deserialize_bytes(AddressVisitor)
+        }
+    }
+}
+
+struct AddressVisitor;
+
+impl<'de> de::Visitor<'de> for AddressVisitor {
+    type Value = Address;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an address string or bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        v.parse::<Address>().map_err(E::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Address::new_from_bytes(v).map_err(E::custom)
+    }
+
+    // The bytes serialized as a sequence, e.g. a `Vec<u8>` in CBOR, are accepted as well.
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut bytes = [0u8; MAX_ADDRESS_BYTES_LEN];
+        let mut len = 0;
+        while let Some(byte) = seq.next_element::<u8>()? {
+            if len == bytes.len() {
+                return Err(de::Error::invalid_length(len + 1, &self));
+            }
+            bytes[len] = byte;
+            len += 1;
+        }
+        self.visit_bytes(&bytes[..len])
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{set_network, Address, Network};
+    use crate::constant::MAX_ADDRESS_BYTES_LEN;
+    use crate::{with_network, Address, Network};
 
     #[test]
     fn address_cbor_serde() {
@@ -53,16 +222,148 @@ mod tests {
         assert_eq!(ser, [69, 0, 191, 214, 251, 5]);
         let de = minicbor::decode::<Address>(&ser).unwrap();
         assert_eq!(de, id_addr);
+
+        let delegated_addr = Address::new_delegated_addr(10, &[1, 2, 3]).unwrap();
+        let ser = minicbor::to_vec(&delegated_addr).unwrap();
+        assert_eq!(ser, [69, 4, 10, 1, 2, 3]);
+        let de = minicbor::decode::<Address>(&ser).unwrap();
+        assert_eq!(de, delegated_addr);
+    }
+
+    #[test]
+    fn address_cbor_reader_writer() {
+        let addrs = vec![
+            Address::new_id_addr(12_512_063u64).unwrap(),
+            Address::new_actor_addr(b"actor").unwrap(),
+            Address::new_bls_addr(&[1; 48]).unwrap(),
+            Address::new_delegated_addr(u64::MAX, &[2; 54]).unwrap(),
+        ];
+        let mut buf = vec![];
+        for addr in &addrs {
+            let len = buf.len();
+            addr.marshal_cbor(&mut buf).unwrap();
+            // Agree with the CBOR serialization.
+            assert_eq!(buf[len..], minicbor::to_vec(addr).unwrap()[..]);
+        }
+        buf.push(0xff);
+        let mut reader = &buf[..];
+        for addr in &addrs {
+            assert_eq!(&Address::unmarshal_cbor(&mut reader).unwrap(), addr);
+        }
+        // The trailing byte is left in the reader.
+        assert_eq!(reader, [0xff]);
+
+        // Not a byte string, non-minimal length, too long, invalid address and truncated.
+        for invalid in &[
+            &[0x65, b'h', b'e', b'l', b'l', b'o'][..],
+            &[0x58, 2, 0, 1],
+            &[0x59, 0, 2, 0, 1],
+            &[0x58, 66],
+            &[0x42, 5, 1],
+            &[0x43, 0, 1],
+        ] {
+            assert!(Address::unmarshal_cbor(&mut &invalid[..]).is_err());
+        }
+    }
+
+    #[test]
+    fn address_binary_reader_writer() {
+        let addrs = vec![
+            Address::new_id_addr(12_512_063u64).unwrap(),
+            Address::new_id_addr(0).unwrap(),
+            Address::new_secp256k1_addr(&[4; 65]).unwrap(),
+            Address::new_actor_addr(b"actor").unwrap(),
+            Address::new_bls_addr(&[1; 48]).unwrap(),
+            Address::new_delegated_addr(u64::MAX, &[2; 54]).unwrap(),
+        ];
+        // The delegated address takes the rest, so it's the last one.
+        let mut buf = vec![];
+        for addr in &addrs {
+            addr.write_to(&mut buf).unwrap();
+        }
+        let mut reader = &buf[..];
+        for addr in &addrs {
+            assert_eq!(&Address::read_from(&mut reader).unwrap(), addr);
+        }
+        assert!(reader.is_empty());
+
+        // The ID is read without the trailing bytes.
+        let mut reader = &[0x00, 0x80, 0x01, 0xff][..];
+        assert_eq!(
+            Address::read_from(&mut reader).unwrap(),
+            Address::new_id_addr(128).unwrap()
+        );
+        assert_eq!(reader, [0xff]);
+        let delegated = Address::new_delegated_addr(10, &[]).unwrap();
+        assert_eq!(
+            Address::read_from(&mut delegated.as_bytes()).unwrap(),
+            delegated
+        );
+
+        // Unknown protocol, non-minimal varint, too long varint, truncated hash, too long
+        // sub-address and empty.
+        let mut too_long = vec![0x04, 0x0a];
+        too_long.extend_from_slice(&[1; 55]);
+        for invalid in &[
+            &[0x05, 0x01][..],
+            &[0x00, 0x80, 0x00],
+            &[
+                0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+            ],
+            &[0x02, 0x01, 0x02],
+            &too_long,
+            &[],
+        ] {
+            assert!(Address::read_from(&mut &invalid[..]).is_err());
+        }
     }
 
     #[test]
     fn address_json_serde() {
-        unsafe { set_network(Network::Test) };
+        let _guard = with_network(Network::Test);
         let id_addr = Address::new_id_addr(1024).unwrap();
         assert_eq!(id_addr.to_string(), "t01024");
         let ser = serde_json::to_string(&id_addr).unwrap();
         assert_eq!(ser, "\"t01024\"");
         let de = serde_json::from_str::<Address>(&ser).unwrap();
         assert_eq!(de, id_addr);
+
+        // Lotus JSON-RPC accepts both the mainnet and the testnet prefixes.
+        let mainnet = serde_json::from_str::<Address>("\"f01024\"").unwrap();
+        assert_eq!(mainnet, id_addr);
+        assert!(serde_json::from_str::<Address>("[0, 128, 8]").is_err());
+        assert!(serde_json::from_str::<Address>("\"x01024\"").is_err());
+    }
+
+    #[test]
+    fn address_binary_serde() {
+        let addrs = vec![
+            Address::new_id_addr(1024).unwrap(),
+            Address::new_secp256k1_addr(&[4; 65]).unwrap(),
+            Address::new_bls_addr(&[1; 48]).unwrap(),
+            Address::new_delegated_addr(10, &[2; 20]).unwrap(),
+        ];
+        for addr in &addrs {
+            // The CBOR of serde is the same as the CBOR encoding of the address.
+            let cbor = serde_cbor::to_vec(addr).unwrap();
+            let mut expected = vec![];
+            addr.marshal_cbor(&mut expected).unwrap();
+            assert_eq!(cbor, expected);
+            assert_eq!(&serde_cbor::from_slice::<Address>(&cbor).unwrap(), addr);
+
+            // The length prefixed raw bytes.
+            let bin = bincode::serialize(addr).unwrap();
+            assert_eq!(&bin[8..], addr.as_bytes());
+            assert_eq!(&bincode::deserialize::<Address>(&bin).unwrap(), addr);
+            // The raw bytes serialized as an array of integers.
+            let seq = serde_cbor::to_vec(&addr.as_bytes().to_vec()).unwrap();
+            assert_eq!(&serde_cbor::from_slice::<Address>(&seq).unwrap(), addr);
+        }
+
+        // Unknown protocol and too long array.
+        let cbor = serde_cbor::to_vec(&serde_cbor::Value::Bytes(vec![0x05, 0x01])).unwrap();
+        assert!(serde_cbor::from_slice::<Address>(&cbor).is_err());
+        let cbor = serde_cbor::to_vec(&vec![0u8; MAX_ADDRESS_BYTES_LEN + 1]).unwrap();
+        assert!(serde_cbor::from_slice::<Address>(&cbor).is_err());
     }
 }
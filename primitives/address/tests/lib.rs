@@ -16,7 +16,7 @@ fn test_random_id_address() {
 
 #[test]
 fn test_id_address() {
-    unsafe { set_network(Network::Test) };
+    let _guard = with_network(Network::Test);
     let test_cases = vec![
         (0, "t00"),
         (1, "t01"),
@@ -49,9 +49,7 @@ fn test_random_secp256k1_address() {
 
 #[test]
 fn test_secp256k1_address() {
-    unsafe {
-        set_network(Network::Test);
-    }
+    let _guard = with_network(Network::Test);
     let test_cases = vec![
         (
             [
@@ -128,9 +126,7 @@ fn test_random_actor_address() {
 
 #[test]
 fn test_actor_address() {
-    unsafe {
-        set_network(Network::Test);
-    }
+    let _guard = with_network(Network::Test);
     let test_cases = vec![
         (
             [
@@ -190,9 +186,7 @@ fn test_random_bls_address() {
 
 #[test]
 fn test_bls_address() {
-    unsafe {
-        set_network(Network::Test);
-    }
+    let _guard = with_network(Network::Test);
     let test_cases = vec![
         (
             [
@@ -250,16 +244,14 @@ fn test_bls_address() {
 
 #[test]
 fn test_invalid_string_address() {
-    unsafe {
-        set_network(Network::Test);
-    }
+    let _guard = with_network(Network::Test);
     let test_cases = vec![
         (
             "Q2gfvuyh7v2sx3patm5k23wdzmhyhtmqctasbr23y",
             AddressError::UnknownNetwork,
         ),
         (
-            "t4gfvuyh7v2sx3patm5k23wdzmhyhtmqctasbr23y",
+            "t5gfvuyh7v2sx3patm5k23wdzmhyhtmqctasbr23y",
             AddressError::UnknownProtocol,
         ),
         (
@@ -294,7 +286,7 @@ fn test_invalid_string_address() {
 #[test]
 fn test_invalid_bytes_address() {
     let test_cases = vec![
-        (vec![4, 4, 4], AddressError::UnknownProtocol),
+        (vec![5, 4, 4], AddressError::UnknownProtocol),
         (vec![0], AddressError::InvalidLength),
         (
             vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
@@ -338,6 +330,44 @@ fn test_invalid_bytes_address() {
     }
 }
 
+#[test]
+fn test_cbor_address() {
+    let _guard = with_network(Network::Test);
+    // The CBOR encodings of go-address.
+    let test_cases = vec![
+        ("t00", "420000"),
+        ("t01024", "43008008"),
+        ("t018446744073709551615", "4b00ffffffffffffffffff01"),
+        (
+            "t15ihq5ibzwki2b4ep2f46avlkrqzhpqgtga7pdrq",
+            "5501ea0f0ea039b291a0f08fd179e0556a8c3277c0d3",
+        ),
+        (
+            "t24vg6ut43yw2h2jqydgbg2xq7x6f4kub3bg6as6i",
+            "5502e54dea4f9bc5b47d261819826d5e1fbf8bc5503b",
+        ),
+        (
+            "t3vvmn62lofvhjd2ugzca6sof2j2ubwok6cj4xxbfzz4yuxfkgobpihhd2thlanmsh3w2ptld2gqkn2jvlss4a",
+            "583103ad58df696e2d4e91ea86c881e938ba4ea81b395e12797b84b9cf314b9546705e839c7a99d606b247ddb4f9ac7a3414dd",
+        ),
+        (
+            "t410f2tc7wfsirksibajjmkm5ksymmsgjgm62hjnomwa",
+            "56040ad4c5fb16488aa48081296299d54b0c648c9333da",
+        ),
+    ];
+
+    for (addr, expect) in test_cases {
+        let addr = addr.parse::<Address>().unwrap();
+        let expect = hex::decode(expect).unwrap();
+        assert_eq!(minicbor::to_vec(&addr).unwrap(), expect);
+        assert_eq!(minicbor::decode::<Address>(&expect).unwrap(), addr);
+        let mut buf = vec![];
+        addr.marshal_cbor(&mut buf).unwrap();
+        assert_eq!(buf, expect);
+        assert_eq!(Address::unmarshal_cbor(&mut &expect[..]).unwrap(), addr);
+    }
+}
+
 #[test]
 fn test_checksum() {
     let data = "helloworld";
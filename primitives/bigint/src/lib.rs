@@ -9,8 +9,10 @@
 
 mod bigint;
 mod biguint;
+pub mod math;
 
 pub use num_bigint::{self, BigInt, BigUint, Sign};
+pub use num_integer;
 pub use num_traits;
 
 pub use self::bigint::bigint_size_str;
@@ -0,0 +1,203 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! Fixed-point math in Q.128 format, i.e. a `BigInt` `x` represents the real number `x / 2^128`.
+//!
+//! These are the helpers used by the reward actor's baseline function and the smoothing estimates,
+//! the results must be bit-for-bit the same as the specs-actors (actors/util/math),
+//! so all the divisions are floor divisions and all the right shifts round down.
+
+use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use num_integer::Integer;
+
+/// The number of fractional bits of the Q.128 format.
+pub const PRECISION: usize = 128;
+
+lazy_static! {
+    /// The numerator coefficients of the rational approximation of `ln(x)` on `[1, 2]`.
+    static ref LN_NUM_COEF: Vec<BigInt> = poly_parse(&[
+        "261417938209272870992496419296200268025",
+        "7266615505142943436908456158054846846897",
+        "32458783941900493142649393804518050491988",
+        "17078670566130897220338060387082146864806",
+        "-35150353308172866634071793531642638290419",
+        "-20351202052858059355702509232125230498980",
+        "-1563932590352680681114104005183375350999",
+    ]);
+    /// The denominator coefficients of the rational approximation of `ln(x)` on `[1, 2]`.
+    static ref LN_DENOM_COEF: Vec<BigInt> = poly_parse(&[
+        "49928077726659937662124949977867279384",
+        "2508163877009111928787629628566491583994",
+        "21757751789594546643737445330202599887121",
+        "53400635271583923415775576342898617051826",
+        "41248834748603606604000911015235164348839",
+        "9015227820322455780436733526367238305537",
+        "340282366920938463463374607431768211456",
+    ]);
+    /// The numerator coefficients of the rational approximation of `e^-x`.
+    static ref EXP_NUM_COEF: Vec<BigInt> = poly_parse(&[
+        "-648770010757830093818553637600",
+        "67469480939593786226847644286976",
+        "-3197587544499098424029388939001856",
+        "89244641121992890118377641805348864",
+        "-1579656163641440567800982336819953664",
+        "17685496037279256458459817590917169152",
+        "-115682590513835356866803355398940131328",
+        "340282366920938463463374607431768211456",
+    ]);
+    /// The denominator coefficients of the rational approximation of `e^-x`.
+    static ref EXP_DENOM_COEF: Vec<BigInt> = poly_parse(&[
+        "1225524182432722209606361",
+        "114095592300906098243859450",
+        "5665570424063336070530214243",
+        "194450132448609991765137938448",
+        "5068267641632683791026134915072",
+        "104716890604972796896895427629056",
+        "1748338658439454459487681798864896",
+        "23704654329841312470660182937960448",
+        "259380097567996910282699886670381056",
+        "2250336698853390384720606936038375424",
+        "14978272436876548034486263159246028800",
+        "72144088983913131323343765784380833792",
+        "224599776407103106596571252037123047424",
+        "340282366920938463463374607431768211456",
+    ]);
+    /// `ln(2)` in Q.128 format.
+    pub static ref LN_2: BigInt = "235865763225513294137944142764154484399".parse().unwrap();
+}
+
+/// Parse the decimal coefficients of a polynomial.
+///
+/// # Panics
+///
+/// Panics if any of the coefficients is not a valid decimal integer.
+pub fn poly_parse(coefs: &[&str]) -> Vec<BigInt> {
+    coefs
+        .iter()
+        .map(|coef| coef.parse().expect("coefficient must be a decimal integer"))
+        .collect()
+}
+
+/// Evaluate the polynomial given by the Q.128 coefficients `poly` at the Q.128 point `x`
+/// by Horner's method. The coefficients are ordered from the highest order to the lowest.
+///
+/// Output is in Q.128 format.
+pub fn poly_val(poly: &[BigInt], x: &BigInt) -> BigInt {
+    poly.iter()
+        .fold(BigInt::from(0), |res, coef| ((res * x) >> PRECISION) + coef)
+}
+
+/// Compute `e^-x` for the Q.128 `x`.
+///
+/// It is most precise within `[0, 1.725)`, where the error is less than 3.4e-30.
+/// Over `[0, 5)` the error is less than 4.6e-15.
+///
+/// Output is in Q.128 format.
+pub fn expneg(x: &BigInt) -> BigInt {
+    let num = poly_val(&EXP_NUM_COEF, x);
+    let denom = poly_val(&EXP_DENOM_COEF, x);
+    (num << PRECISION).div_floor(&denom)
+}
+
+/// Compute the natural log of the Q.128 `z`, `z` must be positive.
+///
+/// Output is in Q.128 format.
+pub fn ln(z: &BigInt) -> BigInt {
+    // ln(z) = ln(x * 2^k) = ln(x) + k * ln(2), where 1 <= x < 2
+    let k = z.bits() as i64 - 1 - PRECISION as i64;
+    let x = if k > 0 {
+        z >> k as usize
+    } else {
+        z << (-k) as usize
+    };
+    BigInt::from(k) * &*LN_2 + ln_between_one_and_two(&x)
+}
+
+/// Compute the natural log of the Q.128 `x`, `x` must be within `[1, 2]`.
+///
+/// Output is in Q.128 format.
+fn ln_between_one_and_two(x: &BigInt) -> BigInt {
+    let num = poly_val(&LN_NUM_COEF, x);
+    let denom = poly_val(&LN_DENOM_COEF, x);
+    (num << PRECISION).div_floor(&denom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn q128(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ln() {
+        let cases = [
+            // ln(1) = 0, within the error of the approximation.
+            ("340282366920938463463374607431768211456", "878"),
+            // ln(2.71828) ~= 1
+            (
+                "924990000000000000000000000000000000000",
+                "340284804280443304164293247058531330872",
+            ),
+            // ln(100e18) ~= 46.0517
+            (
+                "34028236692093846346337460743176821145600000000000000000000",
+                "15670582109617661334887667755326480932333",
+            ),
+            // ln(2e22) ~= 51.3500
+            (
+                "6805647338418769269267492148635364229120000000000000000000000",
+                "17473506083804940761941523871902574111299",
+            ),
+            // ln(0.0006) ~= -7.4186
+            (
+                "204169000000000000000000000000000000",
+                "-2524412969036597619420532515031224429582",
+            ),
+            // ln(1e-10) ~= -23.0259
+            (
+                "34028236692093846346337460743",
+                "-7835291054808830682858417768212398378565",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(ln(&q128(input)), q128(expected), "ln({})", input);
+        }
+    }
+
+    #[test]
+    fn test_expneg() {
+        let cases = [
+            // e^0 = 1
+            ("0", "340282366920938463463374607431768211456"),
+            // e^-1 ~= 0.367879
+            (
+                "340282366920938463463374607431768211456",
+                "125182886983370532117250726296964572306",
+            ),
+            // e^-0.5 ~= 0.606531
+            (
+                "170141183460469231731687303715884105728",
+                "206391688497133195273760705511362611462",
+            ),
+            // e^-3 ~= 0.049787
+            (
+                "1020847100762815390390123822295304634368",
+                "16941661466271327115961417188622941329",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            assert_eq!(expneg(&q128(input)), q128(expected), "expneg({})", input);
+        }
+    }
+
+    #[test]
+    fn test_poly_val() {
+        // 2x^2 + 3x + 1 at x = 0.5
+        let one = BigInt::from(1) << PRECISION;
+        let poly = vec![&one * 2, &one * 3, one.clone()];
+        let x = &one >> 1;
+        assert_eq!(poly_val(&poly, &x), one * 3);
+    }
+}
@@ -12,9 +12,6 @@ pub const BYTE_BITS_COUNT: usize = 0x08;
 /// Value of the long block
 pub const LONG_BLOCK_VALUE: usize = 0x10;
 
-/// Bitwise mask for unpack long block value
-pub const UNPACK_BYTE_MASK: usize = 0x7F;
-
 /// MSB value
 pub const BYTE_SLICE_VALUE: usize = 0x80;
 
@@ -1,7 +1,10 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::convert::TryFrom;
 use std::ops::{Deref, DerefMut};
 
+use plum_varint::VarintError;
+
 use crate::bitset::DynamicBitSet;
 use crate::config;
 use crate::error::*;
@@ -54,7 +57,7 @@ pub fn decode<Item: Number, T: Into<Vec<u8>>>(data: T) -> Result<Vec<Item>> {
     while helper.find_next(helper.index - 1).is_some() {
         let header: Item = get_span(helper, 1)?;
         if header == Item::one() {
-            decode_single_block(helper, &mut value, &mut output);
+            decode_single_block(helper, &mut value, &mut output)?;
         } else if header == Item::zero() {
             let block_header: Item = get_span(helper, 1)?;
             if block_header == Item::zero() {
@@ -64,14 +67,39 @@ pub fn decode<Item: Number, T: Into<Vec<u8>>>(data: T) -> Result<Vec<Item>> {
             }
         }
     }
-    let max_size = config::OBJECT_MAX_SIZE / std::mem::size_of::<Item>();
-    if output.len() > max_size {
-        return Err(RleDecodeError::MaxSizeExceed);
-    }
 
     Ok(output)
 }
 
+// Append a run of `length` bits to the output if it's a run of ones, then skip the run.
+//
+// The size of the output is checked before appending, so that a malicious run length can't
+// exhaust the memory, and the overflow of the value is rejected instead of panicking.
+fn decode_run<Item: Number>(
+    helper: &mut BitSetHelper,
+    current_value: &mut Item,
+    output: &mut Vec<Item>,
+    length: Item,
+) -> Result<()> {
+    let end = current_value
+        .checked_add(&length)
+        .ok_or(RleDecodeError::UnpackOverflow)?;
+    if helper.magnitude {
+        let max_size = config::OBJECT_MAX_SIZE / std::mem::size_of::<Item>();
+        let length: usize = length.into();
+        if length > max_size - output.len() {
+            return Err(RleDecodeError::MaxSizeExceed);
+        }
+        for _ in 0..length {
+            output.push(*current_value);
+            *current_value += Item::one();
+        }
+    }
+    *current_value = end;
+    helper.magnitude = !helper.magnitude;
+    Ok(())
+}
+
 fn get_span<Item: Number>(helper: &mut BitSetHelper, count: usize) -> Result<Item> {
     let end = helper.index + count;
     if helper.size() < end {
@@ -96,12 +124,8 @@ fn decode_single_block<Item: Number>(
     helper: &mut BitSetHelper,
     current_value: &mut Item,
     output: &mut Vec<Item>,
-) {
-    if helper.magnitude {
-        output.push(*current_value);
-    }
-    helper.magnitude = !helper.magnitude;
-    *current_value += Item::one();
+) -> Result<()> {
+    decode_run(helper, current_value, output, Item::one())
 }
 
 fn decode_small_block<Item: Number>(
@@ -110,16 +134,7 @@ fn decode_small_block<Item: Number>(
     output: &mut Vec<Item>,
 ) -> Result<()> {
     let length: Item = get_span(helper, config::SMALL_BLOCK_LENGTH)?;
-    if helper.magnitude {
-        for _ in 0_usize..length.into() {
-            output.push(*current_value);
-            *current_value += Item::one();
-        }
-    } else {
-        *current_value += length;
-    }
-    helper.magnitude = !helper.magnitude;
-    Ok(())
+    decode_run(helper, current_value, output, length)
 }
 
 fn decode_long_block<Item: Number>(
@@ -127,44 +142,31 @@ fn decode_long_block<Item: Number>(
     current_value: &mut Item,
     output: &mut Vec<Item>,
 ) -> Result<()> {
-    // let mut slice: u8 = 0;
-    let mut bytes: Vec<u8> = vec![];
+    let mut bytes: Vec<u8> = Vec::with_capacity(plum_varint::MAX_U64_LEN);
     loop {
         let slice: u8 = get_span::<Item>(helper, config::BYTE_BITS_COUNT)?.into();
         bytes.push(slice);
-        if (slice & config::BYTE_SLICE_VALUE as u8) == 0 {
+        if (slice & config::BYTE_SLICE_VALUE as u8) == 0 || bytes.len() == plum_varint::MAX_U64_LEN
+        {
             break;
         }
     }
-    let length: Item = unpack(bytes)?;
-    if helper.magnitude {
-        for _ in 0_usize..length.into() {
-            output.push(*current_value);
-            *current_value += Item::one();
-        }
-    } else {
-        *current_value += length;
-    }
-    helper.magnitude = !helper.magnitude;
-    Ok(())
+    let length: Item = unpack(&bytes)?;
+    decode_run(helper, current_value, output, length)
 }
 
-fn unpack<Item: Number>(data: Vec<u8>) -> Result<Item> {
-    let max_shift = std::mem::size_of::<Item>() * config::BYTE_BITS_COUNT;
-    let mut shift: usize = 0;
-    let mut value = Item::zero();
-    for byte in data {
-        if shift > max_shift {
-            return Err(RleDecodeError::UnpackOverflow);
-        }
-        let byte = byte as usize;
-        if byte < config::BYTE_SLICE_VALUE {
-            value |= Cast::from(byte << shift);
-            break;
-        }
-
-        value |= Cast::from((byte & config::UNPACK_BYTE_MASK) << shift);
-        shift += config::PACK_BYTE_SHIFT;
+// Decode the varint of a long block, the over-long encodings and the values out of the range
+// of the item are rejected.
+fn unpack<Item: Number>(data: &[u8]) -> Result<Item> {
+    let (value, _) = plum_varint::decode_u64_canonical(data).map_err(|err| match err {
+        VarintError::NotMinimal => RleDecodeError::NotMinimal,
+        VarintError::Truncated | VarintError::Overflow => RleDecodeError::UnpackOverflow,
+    })?;
+    let value = usize::try_from(value).map_err(|_| RleDecodeError::UnpackOverflow)?;
+    let item: Item = Cast::from(value);
+    let back: usize = Cast::into(item);
+    if back != value {
+        return Err(RleDecodeError::UnpackOverflow);
     }
-    Ok(value)
+    Ok(item)
 }
@@ -16,6 +16,9 @@ pub enum RleDecodeError {
     #[error("RLE+ invalid encoding")]
     UnpackOverflow,
     ///
+    #[error("RLE+ long block is not minimally encoded")]
+    NotMinimal,
+    ///
     #[error("RLE+ object size too large")]
     MaxSizeExceed,
 }
@@ -112,6 +112,36 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_malformed() {
+        // 10_000_000_000 doesn't fit in u32.
+        let bytes = vec![0, 253, 32, 151, 192, 175, 160, 37, 1];
+        assert!(matches!(
+            decode::<u32, _>(bytes),
+            Err(RleDecodeError::UnpackOverflow)
+        ));
+
+        // A run of 20 ones, whose long block is encoded as the over-long varint of 20.
+        let bytes = vec![132, 2];
+        assert_eq!(
+            decode::<u64, _>(bytes).unwrap(),
+            (0..20).collect::<Vec<_>>()
+        );
+        let bytes = vec![132, 18, 0];
+        assert!(matches!(
+            decode::<u64, _>(bytes),
+            Err(RleDecodeError::NotMinimal)
+        ));
+
+        // A run of ones larger than the max object size.
+        let max = (config::OBJECT_MAX_SIZE / std::mem::size_of::<u64>()) as u64;
+        let bytes = encode((0..=max).collect::<std::collections::BTreeSet<_>>().iter());
+        assert!(matches!(
+            decode::<u64, _>(bytes),
+            Err(RleDecodeError::MaxSizeExceed)
+        ));
+    }
+
     fn test_roundtrip(set: std::collections::BTreeSet<u64>, expect: Vec<u8>) {
         let r = encode(set.iter());
         assert_eq!(r, expect);
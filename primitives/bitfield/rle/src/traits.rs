@@ -4,11 +4,12 @@ use std::ops::{
     BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Shl, ShlAssign, Shr, ShrAssign,
 };
 
-use num::traits::NumAssign;
+use num::traits::{CheckedAdd, NumAssign};
 
 ///
 pub trait Number:
     NumAssign
+    + CheckedAdd
     + Shl<Output = Self>
     + ShlAssign
     + Shr<Output = Self>
@@ -32,6 +33,7 @@ pub trait Number:
 
 impl<T> Number for T where
     T: NumAssign
+        + CheckedAdd
         + Shl<Output = Self>
         + ShlAssign
         + Shr<Output = Self>
@@ -11,6 +11,25 @@ use std::ops::{Deref, DerefMut};
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{de, ser};
 
+pub use self::json as bitfield_json;
+
+/// The max size of the RLE+ encoding of a bitfield, the same as go-bitfield.
+pub const MAX_ENCODED_SIZE: usize = 32 << 10;
+
+/// The error of encoding or decoding the RLE+ bytes of a bitfield.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+pub enum BitFieldError {
+    /// The RLE+ encoding is larger than `MAX_ENCODED_SIZE`.
+    #[error("RLE+ encoding of bitfield is too large: {0} bytes")]
+    TooLarge(usize),
+    /// The bytes are not valid RLE+.
+    #[error("{0}")]
+    Rle(#[from] rle::RleDecodeError),
+    /// The bytes are valid RLE+ but not the canonical encoding of the bits.
+    #[error("RLE+ encoding of bitfield is not canonical")]
+    NotCanonical,
+}
+
 ///
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
 pub struct BitField(BTreeSet<u64>);
@@ -20,6 +39,31 @@ impl BitField {
     pub fn new() -> Self {
         BitField(BTreeSet::new())
     }
+
+    /// Encode the bitfield into the RLE+ bytes, fail if the encoding exceeds `MAX_ENCODED_SIZE`.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, BitFieldError> {
+        let bytes = rle::encode(self.0.iter());
+        if bytes.len() > MAX_ENCODED_SIZE {
+            return Err(BitFieldError::TooLarge(bytes.len()));
+        }
+        Ok(bytes)
+    }
+
+    /// Decode the bitfield from the RLE+ bytes.
+    ///
+    /// Only the canonical encoding no larger than `MAX_ENCODED_SIZE` is accepted, so that a
+    /// bitfield has a single encoding, which is hashed into the CIDs of the actor states.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BitFieldError> {
+        if bytes.len() > MAX_ENCODED_SIZE {
+            return Err(BitFieldError::TooLarge(bytes.len()));
+        }
+        let set: Vec<u64> = rle::decode(bytes)?;
+        let bitfield = BitField(set.into_iter().collect());
+        if rle::encode(bitfield.0.iter()) != bytes {
+            return Err(BitFieldError::NotCanonical);
+        }
+        Ok(bitfield)
+    }
 }
 
 impl AsRef<BTreeSet<u64>> for BitField {
@@ -73,6 +117,8 @@ impl From<BTreeSet<u64>> for BitField {
 }
 
 // Implement CBOR serialization for BitField.
+// The encoding can't fail, the bitfield exceeding `MAX_ENCODED_SIZE` would be rejected by
+// the decoder, use `BitField::to_bytes` to check the size before sending it.
 impl encode::Encode for BitField {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
         e.bytes(&rle::encode(self.0.iter()))?.ok()
@@ -82,33 +128,90 @@ impl encode::Encode for BitField {
 // Implement CBOR deserialization for BitField.
 impl<'b> decode::Decode<'b> for BitField {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
-        let bytes = d.bytes()?;
-        let set: Vec<u64> =
-            rle::decode(bytes).map_err(|_| decode::Error::Message("RLE+ decode error"))?;
-        Ok(BitField(set.into_iter().collect()))
+        BitField::from_bytes(d.bytes()?).map_err(|err| match err {
+            BitFieldError::TooLarge(_) => decode::Error::Message("RLE+ encoding is too large"),
+            BitFieldError::Rle(_) => decode::Error::Message("RLE+ decode error"),
+            BitFieldError::NotCanonical => decode::Error::Message("RLE+ encoding is not canonical"),
+        })
     }
 }
 
-// Implement JSON serialization for BitField.
+// Implement JSON serialization for BitField, as the run lengths like Lotus.
 impl ser::Serialize for BitField {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: ser::Serializer,
     {
-        let bytes = rle::encode(self.0.iter());
-        serde_bytes::serialize(&bytes, serializer)
+        json::serialize(self, serializer)
     }
 }
 
-// Implement JSON serialization for BitField.
+// Implement JSON deserialization for BitField, as the run lengths like Lotus.
 impl<'de> de::Deserialize<'de> for BitField {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: de::Deserializer<'de>,
     {
-        let bytes: Vec<u8> = serde_bytes::deserialize(deserializer)?;
-        let set: Vec<u64> = rle::decode(bytes).map_err(de::Error::custom)?;
-        Ok(BitField(set.into_iter().collect()))
+        json::deserialize(deserializer)
+    }
+}
+
+/// JSON serialization/deserialization of the run lengths, the same as go-bitfield.
+///
+/// The runs alternate between the unset bits and the set bits, starting with the unset bits,
+/// e.g. `{0, 1, 2, 5}` is `[0, 3, 2, 1]`, and the empty bitfield is `[0]`.
+pub mod json {
+    use serde::{de, ser, Deserialize, Serialize};
+
+    use super::BitField;
+
+    /// The max number of the set bits of the deserialized bitfield.
+    pub const MAX_SET_BITS: u64 = 1 << 20;
+
+    /// JSON serialization
+    pub fn serialize<S>(bitfield: &BitField, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut runs = vec![];
+        let mut next = 0;
+        for &bit in bitfield.iter() {
+            if bit == next && !runs.is_empty() {
+                *runs.last_mut().expect("runs is not empty; qed") += 1;
+            } else {
+                runs.push(bit - next);
+                runs.push(1);
+            }
+            next = bit.saturating_add(1);
+        }
+        if runs.is_empty() {
+            runs.push(0);
+        }
+        runs.serialize(serializer)
+    }
+
+    /// JSON deserialization
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BitField, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let runs = Vec::<u64>::deserialize(deserializer)?;
+        let mut bitfield = BitField::new();
+        let (mut next, mut count) = (0u64, 0u64);
+        for (i, &len) in runs.iter().enumerate() {
+            let end = next
+                .checked_add(len)
+                .ok_or_else(|| de::Error::custom("bitfield run overflow"))?;
+            if i % 2 == 1 {
+                count += len;
+                if count > MAX_SET_BITS {
+                    return Err(de::Error::custom("too many set bits of bitfield"));
+                }
+                bitfield.extend(next..end);
+            }
+            next = end;
+        }
+        Ok(bitfield)
     }
 }
 
@@ -149,4 +252,61 @@ mod tests {
         assert_eq!(v, vec![67, 80, 74, 1]);
         let _ = roundtrip_codec(&bf);
     }
+
+    #[test]
+    fn test_json_runs() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Runs(#[serde(with = "json")] BitField);
+
+        let cases = vec![
+            (vec![], "[0]"),
+            (vec![0, 1, 2, 5], "[0,3,2,1]"),
+            (vec![2, 7], "[2,1,4,1]"),
+        ];
+        for (bits, expected) in cases {
+            let bitfield = Runs(BitField::from(bits));
+            let ser = serde_json::to_string(&bitfield).unwrap();
+            assert_eq!(ser, expected);
+            assert_eq!(serde_json::from_str::<Runs>(&ser).unwrap(), bitfield);
+        }
+        assert!(serde_json::from_str::<Runs>("[0,18446744073709551615]").is_err());
+
+        // The runs are the default JSON of the bitfield.
+        let bitfield = BitField::from(vec![0, 1, 2, 5]);
+        assert_eq!(serde_json::to_string(&bitfield).unwrap(), "[0,3,2,1]");
+        assert_eq!(
+            serde_json::from_str::<BitField>("[0,3,2,1]").unwrap(),
+            bitfield
+        );
+    }
+
+    #[test]
+    fn test_decode_validation() {
+        // The CBOR byte string of the RLE+ bytes.
+        let bitfield = BitField::from(vec![2, 7]);
+        assert_eq!(bitfield.to_bytes().unwrap(), vec![80, 74, 1]);
+        assert_eq!(BitField::from_bytes(&[80, 74, 1]).unwrap(), bitfield);
+
+        // The same bits with the trailing zero byte.
+        assert!(matches!(
+            BitField::from_bytes(&[80, 74, 1, 0]),
+            Err(BitFieldError::NotCanonical)
+        ));
+        assert!(minicbor::decode::<BitField>(&[68, 80, 74, 1, 0]).is_err());
+        // The invalid version.
+        assert!(BitField::from_bytes(&[1]).is_err());
+
+        // Every other bit is set, each run takes 2 bits, so the encoding exceeds the cap.
+        let bitfield = BitField::from((0..200_000).map(|i| i * 2).collect::<Vec<_>>());
+        let bytes = rle::encode(bitfield.iter());
+        assert!(bytes.len() > MAX_ENCODED_SIZE);
+        assert!(matches!(
+            bitfield.to_bytes(),
+            Err(BitFieldError::TooLarge(_))
+        ));
+        assert!(matches!(
+            BitField::from_bytes(&bytes),
+            Err(BitFieldError::TooLarge(_))
+        ));
+    }
 }
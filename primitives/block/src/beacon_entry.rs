@@ -40,7 +40,9 @@ impl encode::Encode for BeaconEntry {
 impl<'b> decode::Decode<'b> for BeaconEntry {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(2));
+        if array_len != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of beacon entry"));
+        }
         Ok(BeaconEntry {
             round: d.u64()?,
             data: d.bytes()?.to_vec(),
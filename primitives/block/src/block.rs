@@ -1,11 +1,16 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::collections::HashSet;
+
 use cid::Cid;
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
-use plum_message::{SignedMessage, UnsignedMessage};
+use plum_address::Address;
+use plum_crypto::{CryptoError, Signature, SignatureType};
+use plum_message::{ChainMessage, SignedMessage, UnsignedMessage};
 
+use crate::block_msg::BlockMsg;
 use crate::header::BlockHeader;
 
 /// The complete block.
@@ -25,6 +30,58 @@ impl Block {
     pub fn cid(&self) -> Cid {
         self.header.cid()
     }
+
+    /// Assemble the block with the header and the signed messages, the `BLS` messages are
+    /// stripped of their signatures, which are aggregated into the `bls_aggregate` of the header.
+    ///
+    /// The order of the messages is kept in `bls_messages` and `secpk_messages`, and only the
+    /// first one of the messages with the same CID (see `ChainMessage`) is included.
+    pub fn assemble(
+        mut header: BlockHeader,
+        messages: Vec<SignedMessage>,
+    ) -> Result<Self, CryptoError> {
+        let mut seen = HashSet::new();
+        let (bls, secpk): (Vec<_>, Vec<_>) = messages
+            .into_iter()
+            .map(ChainMessage::from)
+            .filter(|msg| seen.insert(msg.cid().clone()))
+            .filter_map(ChainMessage::into_signed)
+            .partition(|msg| msg.signature.r#type() == SignatureType::Bls);
+        header.bls_aggregate = Self::aggregate_bls_signatures(&bls)?;
+        Ok(Self {
+            header,
+            bls_messages: bls.into_iter().map(|msg| msg.message).collect(),
+            secpk_messages: secpk,
+        })
+    }
+
+    /// Aggregate the signatures of the `BLS` messages, in the order of the messages.
+    pub fn aggregate_bls_signatures(messages: &[SignedMessage]) -> Result<Signature, CryptoError> {
+        Signature::aggregate_bls(messages.iter().map(|msg| &msg.signature))
+    }
+
+    /// Verify the `bls_aggregate` of the header over the CIDs of the `BLS` messages.
+    ///
+    /// `signers` are the `BLS` key addresses of the senders of `bls_messages`, in the same order.
+    pub fn verify_bls_aggregate(&self, signers: &[Address]) -> Result<bool, CryptoError> {
+        let msgs = self
+            .bls_messages
+            .iter()
+            .map(UnsignedMessage::signing_bytes)
+            .collect::<Vec<_>>();
+        self.header
+            .bls_aggregate
+            .verify_bls_aggregate(signers, &msgs)
+    }
+
+    /// Convert to the block message, which only includes the CIDs of the messages.
+    pub fn to_block_msg(&self) -> BlockMsg {
+        BlockMsg {
+            header: self.header.clone(),
+            bls_messages: self.bls_messages.iter().map(UnsignedMessage::cid).collect(),
+            secpk_messages: self.secpk_messages.iter().map(SignedMessage::cid).collect(),
+        }
+    }
 }
 
 // Implement CBOR serialization for Block.
@@ -42,7 +99,9 @@ impl encode::Encode for Block {
 impl<'b> decode::Decode<'b> for Block {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(3));
+        if array_len != Some(3) {
+            return Err(decode::Error::Message("expected 3 fields of block"));
+        }
         Ok(Block {
             header: d.decode::<BlockHeader>()?,
             bls_messages: d.decode::<Vec<UnsignedMessage>>()?,
@@ -4,6 +4,9 @@ use cid::Cid;
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
+use plum_address::Address;
+use plum_crypto::CryptoError;
+
 use crate::header::BlockHeader;
 
 /// The block message.
@@ -23,6 +26,20 @@ impl BlockMsg {
     pub fn cid(&self) -> Cid {
         self.header.cid()
     }
+
+    /// Verify the `bls_aggregate` of the header over the CIDs of the included `BLS` messages.
+    ///
+    /// `signers` are the `BLS` key addresses of the senders of `bls_messages`, in the same order.
+    pub fn verify_bls_aggregate(&self, signers: &[Address]) -> Result<bool, CryptoError> {
+        let msgs = self
+            .bls_messages
+            .iter()
+            .map(Cid::to_bytes)
+            .collect::<Vec<_>>();
+        self.header
+            .bls_aggregate
+            .verify_bls_aggregate(signers, &msgs)
+    }
 }
 
 // Implement CBOR serialization for BlockMsg.
@@ -40,7 +57,9 @@ impl encode::Encode for BlockMsg {
 impl<'b> decode::Decode<'b> for BlockMsg {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(3));
+        if array_len != Some(3) {
+            return Err(decode::Error::Message("expected 3 fields of block message"));
+        }
         Ok(BlockMsg {
             header: d.decode::<BlockHeader>()?,
             bls_messages: d.decode::<Vec<Cid>>()?,
@@ -1,21 +1,157 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use lazy_static::lazy_static;
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
+use plum_bigint::math::poly_parse;
+use plum_bigint::num_integer::Integer;
+use plum_bigint::{BigInt, Sign};
+use plum_hashing::blake2b_256;
+use plum_types::BLOCKS_PER_EPOCH;
+
+use crate::vrf_proof;
+
+/// The number of fractional bits of the fixed-point numbers used by the win count computation.
+const PRECISION: usize = 256;
+
+/// The max number of elections a block can win in an epoch.
+pub const MAX_WIN_COUNT: i64 = 3 * BLOCKS_PER_EPOCH as i64;
+
+lazy_static! {
+    /// The numerator coefficients of the rational approximation of `e^-x`, in Q.256 format.
+    static ref EXP_NUM_COEF: Vec<BigInt> = to_q256(&poly_parse(&[
+        "-648770010757830093818553637600",
+        "67469480939593786226847644286976",
+        "-3197587544499098424029388939001856",
+        "89244641121992890118377641805348864",
+        "-1579656163641440567800982336819953664",
+        "17685496037279256458459817590917169152",
+        "-115682590513835356866803355398940131328",
+        "340282366920938463463374607431768211456",
+    ]));
+    /// The denominator coefficients of the rational approximation of `e^-x`, in Q.256 format.
+    static ref EXP_DENOM_COEF: Vec<BigInt> = to_q256(&poly_parse(&[
+        "1225524182432722209606361",
+        "114095592300906098243859450",
+        "5665570424063336070530214243",
+        "194450132448609991765137938448",
+        "5068267641632683791026134915072",
+        "104716890604972796896895427629056",
+        "1748338658439454459487681798864896",
+        "23704654329841312470660182937960448",
+        "259380097567996910282699886670381056",
+        "2250336698853390384720606936038375424",
+        "14978272436876548034486263159246028800",
+        "72144088983913131323343765784380833792",
+        "224599776407103106596571252037123047424",
+        "340282366920938463463374607431768211456",
+    ]));
+}
+
 /// The PoSt election proof of space/time
 #[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
 pub struct ElectionProof {
+    /// The number of elections won by the block.
+    pub win_count: i64,
     /// VRF proof
     #[serde(rename = "VRFProof")]
-    #[serde(with = "plum_bytes::base64")]
+    #[serde(with = "crate::vrf_proof::json")]
     pub vrf_proof: Vec<u8>,
 }
 
+impl ElectionProof {
+    /// Compute the number of elections won with the VRF proof by a miner with `power`
+    /// out of `total_power` of the network.
+    ///
+    /// It's Algorand's sortition with the binomial distribution replaced by the Poisson
+    /// distribution of rate `λ = power * BLOCKS_PER_EPOCH / total_power`: the win count is
+    /// the number of times the hash of the VRF proof (as a number in `[0, 1)`) is below the
+    /// inverted CDF `1 - cdf(k, λ)`, at most `MAX_WIN_COUNT`.
+    /// All the fixed-point math is the same as lotus/chain/types/electionproof.go.
+    pub fn compute_win_count(&self, power: &BigInt, total_power: &BigInt) -> i64 {
+        if total_power <= &BigInt::from(0) {
+            return 0;
+        }
+        let hash = blake2b_256(&self.vrf_proof);
+        // 256 bits, as a Q.256 number in [0, 1).
+        let lhs = BigInt::from_bytes_be(Sign::Plus, &hash);
+
+        let lambda = lambda(power, total_power);
+        let mut poisson = Poisson::new(lambda);
+        let mut rhs = poisson.icdf.clone();
+        let mut win_count = 0;
+        while lhs < rhs && win_count < MAX_WIN_COUNT {
+            rhs = poisson.next();
+            win_count += 1;
+        }
+        win_count
+    }
+}
+
+// Compute `λ = power * BLOCKS_PER_EPOCH / total_power` in Q.256 format.
+fn lambda(power: &BigInt, total_power: &BigInt) -> BigInt {
+    ((power * BigInt::from(BLOCKS_PER_EPOCH)) << PRECISION).div_floor(total_power)
+}
+
+// The inverted CDF of the Poisson distribution, computed incrementally.
+struct Poisson {
+    lambda: BigInt,
+    // pmf(k) = λ^k * e^-λ / k!
+    pmf: BigInt,
+    // icdf(k) = 1 - ∑ pmf(i) for i in [0, k]
+    icdf: BigInt,
+    k: u64,
+}
+
+impl Poisson {
+    fn new(lambda: BigInt) -> Self {
+        let pmf = expneg(&lambda);
+        let icdf = (BigInt::from(1) << PRECISION) - &pmf;
+        Self {
+            lambda,
+            pmf,
+            icdf,
+            k: 0,
+        }
+    }
+
+    // Compute `1 - cdf(k + 1, λ)` with `pmf(k + 1) = pmf(k) * λ / (k + 1)`.
+    fn next(&mut self) -> BigInt {
+        self.k += 1;
+        self.pmf = self.pmf.div_floor(&BigInt::from(self.k));
+        self.pmf = (&self.pmf * &self.lambda) >> PRECISION;
+        self.icdf = &self.icdf - &self.pmf;
+        self.icdf.clone()
+    }
+}
+
+// Convert the Q.128 coefficients into Q.256 format.
+fn to_q256(coefs: &[BigInt]) -> Vec<BigInt> {
+    coefs.iter().map(|coef| coef << (PRECISION - 128)).collect()
+}
+
+// Evaluate the polynomial with the Q.256 coefficients at the Q.256 point `x` by Horner's method.
+fn poly_val(poly: &[BigInt], x: &BigInt) -> BigInt {
+    poly.iter()
+        .fold(BigInt::from(0), |res, coef| ((res * x) >> PRECISION) + coef)
+}
+
+// Compute `e^-x` for the Q.256 `x`, output is in Q.256 format.
+fn expneg(x: &BigInt) -> BigInt {
+    let num = poly_val(&EXP_NUM_COEF, x);
+    let denom = poly_val(&EXP_DENOM_COEF, x);
+    (num << PRECISION).div_floor(&denom)
+}
+
 // Implement CBOR serialization for ElectionProof.
 impl encode::Encode for ElectionProof {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
-        e.array(1)?.bytes(&self.vrf_proof)?.ok()
+        e.array(2)?
+            .i64(self.win_count)?
+            .bytes(&self.vrf_proof)?
+            .ok()
     }
 }
 
@@ -23,25 +159,32 @@ impl encode::Encode for ElectionProof {
 impl<'b> decode::Decode<'b> for ElectionProof {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(1));
+        if array_len != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of election proof",
+            ));
+        }
         Ok(ElectionProof {
-            vrf_proof: d.bytes()?.to_vec(),
+            win_count: d.i64()?,
+            vrf_proof: vrf_proof::decode_cbor(d)?,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::ElectionProof;
+    use super::*;
+    use crate::MAX_VRF_PROOF_LEN;
 
     #[test]
     fn election_proof_cbor_serde() {
         let cases = vec![(
             ElectionProof {
+                win_count: 1,
                 vrf_proof: b"vrf proof0000000vrf proof0000000".to_vec(),
             },
             vec![
-                129, 88, 32, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48, 48, 48,
+                130, 1, 88, 32, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48, 48, 48,
                 48, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48, 48, 48, 48,
             ],
         )];
@@ -58,9 +201,10 @@ mod tests {
     fn election_proof_json_serde() {
         let cases = vec![(
             ElectionProof {
+                win_count: 1,
                 vrf_proof: b"vrf proof0000000vrf proof0000000".to_vec(),
             },
-            r#"{"VRFProof":"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA="}"#,
+            r#"{"WinCount":1,"VRFProof":"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA="}"#,
         )];
 
         for (election_proof, expected) in cases {
@@ -70,4 +214,89 @@ mod tests {
             assert_eq!(de, election_proof);
         }
     }
+
+    #[test]
+    fn election_proof_golden() {
+        // The election proof of a full length VRF proof and the max win count, encoded by Lotus.
+        let election_proof = ElectionProof {
+            win_count: MAX_WIN_COUNT,
+            vrf_proof: vec![0xab; MAX_VRF_PROOF_LEN],
+        };
+        let mut cbor = vec![0x82, 0x0f, 0x58, 0x60];
+        cbor.extend_from_slice(&[0xab; MAX_VRF_PROOF_LEN]);
+        assert_eq!(minicbor::to_vec(&election_proof).unwrap(), cbor);
+        assert_eq!(
+            minicbor::decode::<ElectionProof>(&cbor).unwrap(),
+            election_proof
+        );
+        let json = format!(r#"{{"WinCount":15,"VRFProof":"{}"}}"#, "q6ur".repeat(32));
+        assert_eq!(serde_json::to_string(&election_proof).unwrap(), json);
+        assert_eq!(
+            serde_json::from_str::<ElectionProof>(&json).unwrap(),
+            election_proof
+        );
+
+        // The nil VRF proof of Lotus.
+        assert_eq!(
+            serde_json::from_str::<ElectionProof>(r#"{"WinCount":0,"VRFProof":null}"#).unwrap(),
+            ElectionProof {
+                win_count: 0,
+                vrf_proof: vec![],
+            }
+        );
+
+        // The VRF proofs longer than a BLS signature are rejected.
+        let mut cbor = vec![0x82, 0x01, 0x58, 0x61];
+        cbor.extend_from_slice(&[0xab; MAX_VRF_PROOF_LEN + 1]);
+        assert!(minicbor::decode::<ElectionProof>(&cbor).is_err());
+        let json = format!(r#"{{"WinCount":1,"VRFProof":"{}qw=="}}"#, "q6ur".repeat(32));
+        assert!(serde_json::from_str::<ElectionProof>(&json).is_err());
+    }
+
+    #[test]
+    fn test_expneg() {
+        let one = BigInt::from(1) << PRECISION;
+        assert_eq!(expneg(&BigInt::from(0)), one);
+        // e^-1 ~= 0.367879
+        assert_eq!(
+            expneg(&one),
+            "42597529080697662913911602080197270017224605406643086589378214572018159359656"
+                .parse()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_compute_win_count() {
+        let total_power = BigInt::from(100);
+        let cases = [(0, 0), (1, 0), (10, 1), (20, 2), (50, 5), (100, 8)];
+        let proof = ElectionProof {
+            win_count: 0,
+            vrf_proof: vec![7; 32],
+        };
+        for (power, expected) in cases.iter() {
+            assert_eq!(
+                proof.compute_win_count(&BigInt::from(*power), &total_power),
+                *expected,
+                "power {}",
+                power
+            );
+        }
+
+        let proofs = (0u8..10)
+            .map(|i| ElectionProof {
+                win_count: 0,
+                vrf_proof: vec![i; 32],
+            })
+            .collect::<Vec<_>>();
+        let win_counts = proofs
+            .iter()
+            .map(|proof| proof.compute_win_count(&total_power, &total_power))
+            .collect::<Vec<_>>();
+        assert_eq!(win_counts, vec![5, 2, 3, 1, 4, 1, 3, 8, 3, 7]);
+        assert_eq!(
+            proofs[0].compute_win_count(&total_power, &BigInt::from(0)),
+            0
+        );
+    }
 }
@@ -108,7 +108,9 @@ impl encode::Encode for BlockHeader {
 impl<'b> decode::Decode<'b> for BlockHeader {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(15));
+        if array_len != Some(15) {
+            return Err(decode::Error::Message("expected 15 fields of block header"));
+        }
         Ok(BlockHeader {
             miner: d.decode::<Address>()?,
             ticket: d.decode::<Ticket>()?,
@@ -134,7 +136,7 @@ impl<'b> decode::Decode<'b> for BlockHeader {
 mod tests {
     use cid::Cid;
 
-    use plum_address::{set_network, Address, Network};
+    use plum_address::{with_network, Address, Network};
     use plum_crypto::Signature;
 
     use super::BlockHeader;
@@ -152,6 +154,7 @@ mod tests {
                 vrf_proof: b"vrf proof0000000vrf proof0000000".to_vec(),
             },
             election_proof: ElectionProof {
+                win_count: 1,
                 vrf_proof: b"vrf proof0000000vrf proof0000000".to_vec(),
             },
             beacon_entries: vec![],
@@ -176,7 +179,7 @@ mod tests {
         let expected = vec![
             143, 69, 0, 191, 214, 251, 5, 129, 88, 32, 118, 114, 102, 32, 112, 114, 111, 111, 102,
             48, 48, 48, 48, 48, 48, 48, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48,
-            48, 48, 48, 129, 88, 32, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48,
+            48, 48, 48, 130, 1, 88, 32, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48,
             48, 48, 48, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48, 48, 48, 48, 48, 48, 48,
             128, 128, 130, 216, 42, 88, 37, 0, 1, 113, 18, 32, 76, 2, 122, 115, 187, 29, 97, 161,
             80, 48, 167, 49, 47, 124, 18, 38, 183, 206, 50, 72, 232, 201, 142, 225, 217, 73, 55,
@@ -198,18 +201,22 @@ mod tests {
         assert_eq!(ser, expected);
         let de = minicbor::decode::<BlockHeader>(&ser).unwrap();
         assert_eq!(de, header);
+
+        // The malformed headers are rejected without panicking.
+        let mut wrong_len = ser.clone();
+        wrong_len[0] = 142;
+        assert!(minicbor::decode::<BlockHeader>(&wrong_len).is_err());
+        assert!(minicbor::decode::<BlockHeader>(&ser[..ser.len() - 1]).is_err());
     }
 
     #[test]
     fn block_header_json_serde() {
-        unsafe {
-            set_network(Network::Test);
-        }
+        let _guard = with_network(Network::Test);
         let header = dummy_block_header();
         let expected = "{\
             \"Miner\":\"t012512063\",\
                 \"Ticket\":{\"VRFProof\":\"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA=\"},\
-                \"ElectionProof\":{\"VRFProof\":\"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA=\"},\
+                \"ElectionProof\":{\"WinCount\":1,\"VRFProof\":\"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA=\"},\
                 \"BeaconEntries\":[],\
                 \"WinPoStProof\":[],\
                 \"Parents\":[\
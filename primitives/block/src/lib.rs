@@ -12,6 +12,7 @@ mod election_proof;
 mod header;
 mod msg_meta;
 mod ticket;
+mod vrf_proof;
 
 pub use self::beacon_entry::BeaconEntry;
 pub use self::block::Block;
@@ -20,3 +21,4 @@ pub use self::election_proof::ElectionProof;
 pub use self::header::BlockHeader;
 pub use self::msg_meta::MsgMeta;
 pub use self::ticket::Ticket;
+pub use self::vrf_proof::MAX_VRF_PROOF_LEN;
@@ -46,7 +46,9 @@ impl encode::Encode for MsgMeta {
 impl<'b> decode::Decode<'b> for MsgMeta {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(2));
+        if array_len != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of message meta"));
+        }
         Ok(MsgMeta {
             bls_messages: d.decode::<Cid>()?,
             secpk_messages: d.decode::<Cid>()?,
@@ -1,8 +1,14 @@
 // Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
 
+use std::cmp::Ordering;
+
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
+use plum_hashing::blake2b_256;
+
+use crate::vrf_proof;
+
 /// A Ticket is a marker of a tick of the blockchain's clock.
 /// It is the source of randomness for proofs of storage and leader election.
 /// It is generated by the miner of a block using a VRF and a VDF.
@@ -10,7 +16,7 @@ use serde::{Deserialize, Serialize};
 pub struct Ticket {
     /// VRF proof
     #[serde(rename = "VRFProof")]
-    #[serde(with = "plum_bytes::base64")]
+    #[serde(with = "crate::vrf_proof::json")]
     pub vrf_proof: Vec<u8>,
 }
 
@@ -21,6 +27,28 @@ impl Ticket {
             vrf_proof: vrf_proof.into(),
         }
     }
+
+    /// Return the blake2b-256 hash of the VRF proof, which is used to compare the tickets.
+    pub fn hash(&self) -> [u8; 32] {
+        blake2b_256(&self.vrf_proof)
+    }
+
+    /// Return the quality of the ticket in `(0, 1]`, i.e. `1 - hash / 2^256`,
+    /// the smaller the hash of the VRF proof, the higher the quality.
+    pub fn quality(&self) -> f64 {
+        let ratio = self
+            .hash()
+            .iter()
+            .rev()
+            .fold(0f64, |ratio, byte| (ratio + f64::from(*byte)) / 256.0);
+        1.0 - ratio
+    }
+
+    /// Compare the tickets by the hashes of the VRF proofs, the smaller ticket wins
+    /// when breaking the tie between the tipsets of equal weight.
+    pub fn cmp_by_hash(&self, other: &Self) -> Ordering {
+        self.hash().cmp(&other.hash())
+    }
 }
 
 // Implement CBOR serialization for Ticket.
@@ -34,16 +62,19 @@ impl encode::Encode for Ticket {
 impl<'b> decode::Decode<'b> for Ticket {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(1));
+        if array_len != Some(1) {
+            return Err(decode::Error::Message("expected 1 field of ticket"));
+        }
         Ok(Ticket {
-            vrf_proof: d.bytes()?.to_vec(),
+            vrf_proof: vrf_proof::decode_cbor(d)?,
         })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Ticket;
+    use super::*;
+    use crate::MAX_VRF_PROOF_LEN;
 
     #[test]
     fn ticket_cbor_serde() {
@@ -81,4 +112,54 @@ mod tests {
             assert_eq!(de, ticket);
         }
     }
+
+    #[test]
+    fn ticket_golden() {
+        // The ticket of a full length VRF proof, encoded by Lotus.
+        let ticket = Ticket::new(vec![0xab; MAX_VRF_PROOF_LEN]);
+        let mut cbor = vec![0x81, 0x58, 0x60];
+        cbor.extend_from_slice(&[0xab; MAX_VRF_PROOF_LEN]);
+        assert_eq!(minicbor::to_vec(&ticket).unwrap(), cbor);
+        assert_eq!(minicbor::decode::<Ticket>(&cbor).unwrap(), ticket);
+        let json = format!(r#"{{"VRFProof":"{}"}}"#, "q6ur".repeat(32));
+        assert_eq!(serde_json::to_string(&ticket).unwrap(), json);
+        assert_eq!(serde_json::from_str::<Ticket>(&json).unwrap(), ticket);
+
+        // The nil VRF proof of Lotus.
+        assert_eq!(
+            serde_json::from_str::<Ticket>(r#"{"VRFProof":null}"#).unwrap(),
+            Ticket::new(vec![])
+        );
+        assert_eq!(
+            minicbor::to_vec(&Ticket::new(vec![])).unwrap(),
+            [0x81, 0x40]
+        );
+
+        // The VRF proofs longer than a BLS signature are rejected.
+        let mut cbor = vec![0x81, 0x58, 0x61];
+        cbor.extend_from_slice(&[0xab; MAX_VRF_PROOF_LEN + 1]);
+        assert!(minicbor::decode::<Ticket>(&cbor).is_err());
+        let json = format!(r#"{{"VRFProof":"{}qw=="}}"#, "q6ur".repeat(32));
+        assert!(serde_json::from_str::<Ticket>(&json).is_err());
+    }
+
+    #[test]
+    fn ticket_quality() {
+        let tickets = (0u8..4)
+            .map(|i| Ticket::new(vec![i; 32]))
+            .collect::<Vec<_>>();
+        for ticket in &tickets {
+            let quality = ticket.quality();
+            assert!(quality > 0.0 && quality <= 1.0);
+        }
+        for a in &tickets {
+            for b in &tickets {
+                // The smaller ticket has the higher quality.
+                if a.cmp_by_hash(b) == Ordering::Less {
+                    assert!(a.quality() >= b.quality());
+                }
+            }
+        }
+        assert_eq!(tickets[0].cmp_by_hash(&tickets[0]), Ordering::Equal);
+    }
 }
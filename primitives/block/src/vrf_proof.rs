@@ -0,0 +1,48 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+// The VRF proofs of the tickets and the election proofs are BLS signatures, the proofs longer
+// than a BLS signature are rejected by decoding, so a malicious block can't carry arbitrary
+// large proofs.
+
+use minicbor::{decode, Decoder};
+use serde::{de, Deserialize};
+
+/// The max length of a VRF proof, i.e. the length of a BLS signature.
+pub const MAX_VRF_PROOF_LEN: usize = plum_crypto::BLS_SIGNATURE_SIZE;
+
+// Decode the CBOR byte string of the VRF proof, rejecting the over-long proofs.
+pub(crate) fn decode_cbor(d: &mut Decoder<'_>) -> Result<Vec<u8>, decode::Error> {
+    let vrf_proof = d.bytes()?;
+    if vrf_proof.len() > MAX_VRF_PROOF_LEN {
+        return Err(decode::Error::Message("VRF proof is too long"));
+    }
+    Ok(vrf_proof.to_vec())
+}
+
+/// Implement JSON serialization/deserialization of the VRF proof using base64 like Lotus.
+pub(crate) mod json {
+    use super::*;
+
+    pub use plum_bytes::base64::serialize;
+
+    #[derive(Deserialize)]
+    struct Base64(#[serde(with = "plum_bytes::base64")] Vec<u8>);
+
+    /// The nil proof of Lotus is `null` in JSON, which is deserialized as an empty proof.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let vrf_proof = Option::<Base64>::deserialize(deserializer)?
+            .map(|proof| proof.0)
+            .unwrap_or_default();
+        if vrf_proof.len() > MAX_VRF_PROOF_LEN {
+            return Err(de::Error::custom(format!(
+                "VRF proof is too long: {} > {}",
+                vrf_proof.len(),
+                MAX_VRF_PROOF_LEN
+            )));
+        }
+        Ok(vrf_proof)
+    }
+}
@@ -21,6 +21,15 @@ pub enum CryptoError {
     /// Signature and Address are not match
     #[error("signature and address is not same type, signature:{:0?}, addr:{1}")]
     NotSameType(SignatureType, Protocol),
+    /// Expect a `BLS` signature.
+    #[error("expected bls signature, found: {0:?}")]
+    NotBlsSignature(SignatureType),
+    /// The numbers of the public keys and the messages of an aggregate signature are not match.
+    #[error("aggregate signature has {0} public keys but {1} messages")]
+    AggregateLengthMismatch(usize, usize),
+    /// The length of the signature bytes is invalid for the signature type.
+    #[error("invalid signature length: {0}")]
+    InvalidSignatureLength(usize),
     /// Signature verify failed
     #[error("signature verify failed")]
     VerifyFailed,
@@ -47,6 +47,9 @@ impl From<SignatureType> for u8 {
     }
 }
 
+/// The size of a `BLS` signature in bytes.
+pub const BLS_SIGNATURE_SIZE: usize = 96;
+
 /// The general signature structure.
 #[derive(Eq, PartialEq, Debug, Clone, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -138,7 +141,7 @@ impl Signature {
             (SignatureType::Secp256k1, Protocol::Secp256k1) => {
                 let hashed_msg = blake2b_256(msg);
                 let message = secp256k1::Message::parse(&hashed_msg);
-                assert_eq!(self.data.len(), secp256k1::util::SIGNATURE_SIZE + 1);
+                self.check_secp256k1_len()?;
                 let mut signature = [0u8; secp256k1::util::SIGNATURE_SIZE];
                 signature.copy_from_slice(&self.data[..secp256k1::util::SIGNATURE_SIZE]);
                 let signature = secp256k1::Signature::parse(&signature);
@@ -175,13 +178,21 @@ impl Signature {
     {
         let hashed_msg = blake2b_256(msg);
         let message = secp256k1::Message::parse(&hashed_msg);
-        assert_eq!(self.data.len(), secp256k1::util::SIGNATURE_SIZE + 1);
+        self.check_secp256k1_len()?;
         let signature = &self.data[..secp256k1::util::SIGNATURE_SIZE];
         let signature = secp256k1::Signature::parse_slice(&signature)?;
         let pubkey = secp256k1::PublicKey::parse_slice(pubkey.as_ref(), None)?;
         Ok(secp256k1::verify(&message, &signature, &pubkey))
     }
 
+    // The secp256k1 signature is the signature (64 bytes) followed by the recovery id (1 byte).
+    fn check_secp256k1_len(&self) -> Result<(), CryptoError> {
+        if self.data.len() != secp256k1::util::SIGNATURE_SIZE + 1 {
+            return Err(CryptoError::InvalidSignatureLength(self.data.len()));
+        }
+        Ok(())
+    }
+
     /// Verify the `BLS` signature with the given `BLS` public key and message.
     fn verify_bls<K, M>(&self, pubkey: K, msg: M) -> Result<bool, CryptoError>
     where
@@ -197,6 +208,73 @@ impl Signature {
         Ok(bls::verify(&signature, &[hashed_msg], &[pubkey]))
     }
 
+    /// Aggregate the `BLS` signatures into one signature, the order of the signatures
+    /// must be the same as the order of the messages when verifying the aggregate signature.
+    ///
+    /// The aggregate of no signature is the zero signature (the compressed point at infinity),
+    /// which is the same as Lotus.
+    pub fn aggregate_bls<'a, I>(signatures: I) -> Result<Self, CryptoError>
+    where
+        I: IntoIterator<Item = &'a Signature>,
+    {
+        use bls::Serialize;
+        let signatures = signatures
+            .into_iter()
+            .map(|signature| match signature.r#type {
+                SignatureType::Bls => Ok(bls::Signature::from_bytes(&signature.data)?),
+                ty => Err(CryptoError::NotBlsSignature(ty)),
+            })
+            .collect::<Result<Vec<_>, CryptoError>>()?;
+        if signatures.is_empty() {
+            let mut data = vec![0u8; BLS_SIGNATURE_SIZE];
+            // the flags of compression and infinity.
+            data[0] = 0xc0;
+            return Ok(Self::new_bls(data));
+        }
+        let signature = bls::aggregate(&signatures)?;
+        Ok(Self::new_bls(signature.as_bytes()))
+    }
+
+    /// Verify the aggregate `BLS` signature with the given `BLS` addresses and messages,
+    /// the i-th message is signed by the i-th address.
+    ///
+    /// The aggregate signature of no message is always valid, which is the same as Lotus.
+    pub fn verify_bls_aggregate<M>(
+        &self,
+        addrs: &[Address],
+        msgs: &[M],
+    ) -> Result<bool, CryptoError>
+    where
+        M: AsRef<[u8]>,
+    {
+        use bls::Serialize;
+        if self.r#type != SignatureType::Bls {
+            return Err(CryptoError::NotBlsSignature(self.r#type));
+        }
+        if addrs.len() != msgs.len() {
+            return Err(CryptoError::AggregateLengthMismatch(
+                addrs.len(),
+                msgs.len(),
+            ));
+        }
+        if msgs.is_empty() {
+            return Ok(true);
+        }
+        let pubkeys = addrs
+            .iter()
+            .map(|addr| match addr.protocol() {
+                Protocol::Bls => Ok(bls::PublicKey::from_bytes(addr.payload())?),
+                protocol => Err(CryptoError::NotSameType(self.r#type, protocol)),
+            })
+            .collect::<Result<Vec<_>, CryptoError>>()?;
+        let hashed_msgs = msgs
+            .iter()
+            .map(|msg| bls::hash(msg.as_ref()))
+            .collect::<Vec<_>>();
+        let signature = bls::Signature::from_bytes(&self.data)?;
+        Ok(bls::verify(&signature, &hashed_msgs, &pubkeys))
+    }
+
     /// Return the signature type.
     pub fn r#type(&self) -> SignatureType {
         self.r#type
@@ -222,6 +300,9 @@ impl encode::Encode for Signature {
 impl<'b> decode::Decode<'b> for Signature {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let bytes = d.bytes()?;
+        if bytes.is_empty() {
+            return Err(decode::Error::Message("expected signature type"));
+        }
         let r#type = SignatureType::try_from(bytes[0])
             .map_err(|_| decode::Error::Message("expected signature type"))?;
         Ok(Signature {
@@ -233,7 +314,7 @@ impl<'b> decode::Decode<'b> for Signature {
 
 #[cfg(test)]
 mod tests {
-    use super::{Address, Signature, SignatureType};
+    use super::{Address, CryptoError, Signature, SignatureType, BLS_SIGNATURE_SIZE};
     use crate::key::{PrivateKey, PublicKey};
 
     #[test]
@@ -266,6 +347,41 @@ mod tests {
         assert_eq!(res, Ok(true));
     }
 
+    #[test]
+    fn aggregate_and_verify_bls() {
+        let mut addrs = vec![];
+        let mut signatures = vec![];
+        let msgs = vec!["a", "b", "c"];
+        for msg in &msgs {
+            let privkey = PrivateKey::generate_bls_privkey();
+            let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+            addrs.push(Address::new_bls_addr(&pubkey).unwrap());
+            signatures.push(Signature::sign_bls(privkey.into_vec(), msg).unwrap());
+        }
+
+        let aggregate = Signature::aggregate_bls(&signatures).unwrap();
+        assert_eq!(aggregate.verify_bls_aggregate(&addrs, &msgs), Ok(true));
+        let reordered = vec!["b", "a", "c"];
+        assert_eq!(
+            aggregate.verify_bls_aggregate(&addrs, &reordered),
+            Ok(false)
+        );
+        assert_eq!(
+            aggregate.verify_bls_aggregate(&addrs[..2], &msgs),
+            Err(CryptoError::AggregateLengthMismatch(2, 3))
+        );
+
+        let empty = Signature::aggregate_bls(&[]).unwrap();
+        assert_eq!(empty.as_bytes().len(), BLS_SIGNATURE_SIZE);
+        assert_eq!(empty.verify_bls_aggregate::<&str>(&[], &[]), Ok(true));
+
+        let secp = Signature::new_secp256k1(vec![0u8; 65]);
+        assert_eq!(
+            Signature::aggregate_bls(vec![&signatures[0], &secp]),
+            Err(CryptoError::NotBlsSignature(SignatureType::Secp256k1))
+        );
+    }
+
     #[test]
     fn signature_cbor_serde() {
         let cases = vec![(
@@ -285,6 +401,21 @@ mod tests {
             let de = minicbor::decode::<Signature>(&ser).unwrap();
             assert_eq!(signature, de);
         }
+
+        // Empty bytes without the signature type.
+        assert!(minicbor::decode::<Signature>(&[0x40]).is_err());
+    }
+
+    #[test]
+    fn verify_invalid_secp256k1_length() {
+        let privkey = PrivateKey::generate_secp256k1_privkey();
+        let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+        let addr = Address::new_secp256k1_addr(&pubkey).unwrap();
+        let signature = Signature::new_secp256k1(vec![0u8; 64]);
+        assert_eq!(
+            signature.verify(&addr, "hello, world"),
+            Err(CryptoError::InvalidSignatureLength(64))
+        );
     }
 
     #[test]
@@ -11,12 +11,83 @@ pub use self::commcid::*;
 use plum_address::{Address, AddressError};
 use plum_types::ActorId;
 
-/// Convert actorid to prove id
+/// Convert actorid to prove id, which is the payload of the ID address padded with zeros.
 pub fn to_prove_id(actor_id: ActorId) -> Result<[u8; 32], AddressError> {
     let addr = Address::new_id_addr(actor_id)?;
     let mut res: [u8; 32] = Default::default();
     let payload = addr.payload();
-    let len = std::cmp::min(payload.len(), res.len());
-    res[..len].copy_from_slice(&payload[..len]);
+    // The varint of u64 is 10 bytes at most.
+    assert!(
+        payload.len() <= res.len(),
+        "payload of ID address must fit in prove id"
+    );
+    res[..payload.len()].copy_from_slice(payload);
     Ok(res)
 }
+
+/// Convert the ID address to prove id, the other addresses are rejected,
+/// since the proofs must be generated against the ID of the miner actor.
+pub fn address_to_prove_id(addr: &Address) -> Result<[u8; 32], AddressError> {
+    to_prove_id(addr.id()?)
+}
+
+/// Convert prove id back to actorid, the inverse of `to_prove_id`.
+///
+/// The prove id that can't be produced by `to_prove_id` is rejected, e.g. the non-zero bytes
+/// after the payload or the non-minimal varint encoding.
+pub fn from_prove_id(prove_id: &[u8; 32]) -> Result<ActorId, AddressError> {
+    let (actor_id, _) =
+        plum_varint::decode_u64_canonical(prove_id).map_err(|_| AddressError::InvalidPayload)?;
+    if &to_prove_id(actor_id)? != prove_id {
+        return Err(AddressError::InvalidPayload);
+    }
+    Ok(actor_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prove_id_round_trip() {
+        let mut ids = vec![0, 1, 127, 128, 255, 256, 16383, 16384, u64::max_value()];
+        ids.extend((0..64).map(|shift| 1u64 << shift));
+        ids.extend((0..64).map(|shift| (1u64 << shift) - 1));
+        // A simple LCG for the reproducible pseudo-random ids.
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for _ in 0..1000 {
+            seed = seed
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            ids.push(seed >> (seed % 64));
+        }
+
+        for id in ids {
+            let prove_id = to_prove_id(id).unwrap();
+            assert_eq!(from_prove_id(&prove_id), Ok(id));
+            let addr = Address::new_id_addr(id).unwrap();
+            assert_eq!(address_to_prove_id(&addr), Ok(prove_id));
+        }
+    }
+
+    #[test]
+    fn test_invalid_prove_id() {
+        let addr = Address::new_actor_addr(b"actor").unwrap();
+        assert_eq!(address_to_prove_id(&addr), Err(AddressError::NotIdAddress));
+
+        // trailing garbage.
+        let mut prove_id = to_prove_id(1000).unwrap();
+        prove_id[31] = 1;
+        assert_eq!(from_prove_id(&prove_id), Err(AddressError::InvalidPayload));
+        // non-minimal varint of 1.
+        let mut prove_id = [0u8; 32];
+        prove_id[0] = 0x81;
+        prove_id[1] = 0x00;
+        assert_eq!(from_prove_id(&prove_id), Err(AddressError::InvalidPayload));
+        // unterminated varint.
+        assert_eq!(
+            from_prove_id(&[0xff; 32]),
+            Err(AddressError::InvalidPayload)
+        );
+    }
+}
@@ -0,0 +1,196 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::hash::{Hash, Hasher};
+
+use cid::Cid;
+
+use plum_crypto::SignatureType;
+
+use crate::signed_message::SignedMessage;
+use crate::unsigned_message::UnsignedMessage;
+
+/// The message as it's included in the chain, along with its CID computed once.
+///
+/// The CID is the identity of the message everywhere, e.g. the message pool, the message set
+/// of a block and the message index, and two messages with the same CID are the same message:
+///
+/// - The `BLS` message is included without its signature, which is aggregated into the block
+///   header, so it's identified by the CID of the unsigned message. The same message with
+///   different signatures is the same message.
+/// - The `Secp256k1` message is included with its signature, so it's identified by the CID of
+///   the signed message.
+///
+/// The message can't be modified once it's wrapped, so the cached CID can't get stale.
+#[derive(Clone, Debug)]
+pub struct ChainMessage {
+    message: Message,
+    cid: Cid,
+}
+
+#[derive(Clone, Debug)]
+enum Message {
+    Unsigned(UnsignedMessage),
+    Signed(SignedMessage),
+}
+
+impl ChainMessage {
+    /// Wrap the `BLS` message included by a block, whose signature is stripped.
+    pub fn from_unsigned(message: UnsignedMessage) -> Self {
+        let cid = message.cid();
+        Self {
+            message: Message::Unsigned(message),
+            cid,
+        }
+    }
+
+    /// Wrap the signed message of any signature type.
+    pub fn from_signed(message: SignedMessage) -> Self {
+        let cid = message.cid();
+        Self {
+            message: Message::Signed(message),
+            cid,
+        }
+    }
+
+    /// Return the CID of the message, see the type docs for which CID it is.
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    /// Return the unsigned message, which is executed by the VM.
+    pub fn message(&self) -> &UnsignedMessage {
+        match &self.message {
+            Message::Unsigned(message) => message,
+            Message::Signed(message) => &message.message,
+        }
+    }
+
+    /// Return the signed message, `None` if the message is wrapped without the signature.
+    pub fn signed(&self) -> Option<&SignedMessage> {
+        match &self.message {
+            Message::Unsigned(_) => None,
+            Message::Signed(message) => Some(message),
+        }
+    }
+
+    /// Return true if the message is a `BLS` message, which is included without the signature.
+    pub fn is_bls(&self) -> bool {
+        match &self.message {
+            Message::Unsigned(_) => true,
+            Message::Signed(message) => message.signature.r#type() == SignatureType::Bls,
+        }
+    }
+
+    /// Unwrap the unsigned message.
+    pub fn into_message(self) -> UnsignedMessage {
+        match self.message {
+            Message::Unsigned(message) => message,
+            Message::Signed(message) => message.message,
+        }
+    }
+
+    /// Unwrap the signed message, `None` if the message is wrapped without the signature.
+    pub fn into_signed(self) -> Option<SignedMessage> {
+        match self.message {
+            Message::Unsigned(_) => None,
+            Message::Signed(message) => Some(message),
+        }
+    }
+}
+
+impl From<UnsignedMessage> for ChainMessage {
+    fn from(message: UnsignedMessage) -> Self {
+        Self::from_unsigned(message)
+    }
+}
+
+impl From<SignedMessage> for ChainMessage {
+    fn from(message: SignedMessage) -> Self {
+        Self::from_signed(message)
+    }
+}
+
+impl PartialEq for ChainMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.cid == other.cid
+    }
+}
+
+impl Eq for ChainMessage {}
+
+impl Hash for ChainMessage {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cid.hash(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use plum_address::Address;
+    use plum_bigint::BigInt;
+    use plum_crypto::Signature;
+
+    use super::*;
+    use crate::MESSAGE_VERSION_FEE_MARKET;
+
+    fn message(nonce: u64) -> UnsignedMessage {
+        UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(1000).unwrap(),
+            from: Address::new_id_addr(1001).unwrap(),
+            nonce,
+            value: BigInt::from(0),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        }
+    }
+
+    #[test]
+    fn test_chain_message_cid() {
+        let bls = SignedMessage {
+            message: message(0),
+            signature: Signature::new_bls(vec![1; 96]),
+        };
+        let secp = SignedMessage {
+            message: message(1),
+            signature: Signature::new_secp256k1(vec![1; 65]),
+        };
+
+        // The BLS message is identified by the unsigned message, with or without the signature.
+        let signed = ChainMessage::from(bls.clone());
+        let unsigned = ChainMessage::from(bls.message.clone());
+        assert_eq!(signed.cid(), &bls.message.cid());
+        assert_eq!(signed, unsigned);
+        assert!(signed.is_bls() && unsigned.is_bls());
+        assert!(unsigned.signed().is_none());
+        let resigned = SignedMessage {
+            signature: Signature::new_bls(vec![2; 96]),
+            ..bls
+        };
+        assert_eq!(ChainMessage::from(resigned), signed);
+
+        // The secp256k1 message is identified by the signed message.
+        let chain_secp = ChainMessage::from(secp.clone());
+        assert_eq!(chain_secp.cid(), &secp.cid());
+        assert_ne!(chain_secp.cid(), &secp.message.cid());
+        assert!(!chain_secp.is_bls());
+        let resigned = SignedMessage {
+            signature: Signature::new_secp256k1(vec![2; 65]),
+            ..secp.clone()
+        };
+        assert_ne!(ChainMessage::from(resigned), chain_secp);
+        assert_eq!(chain_secp.message(), &secp.message);
+        assert_eq!(chain_secp.into_signed(), Some(secp));
+
+        let set = vec![signed, unsigned]
+            .into_iter()
+            .collect::<HashSet<ChainMessage>>();
+        assert_eq!(set.len(), 1);
+    }
+}
@@ -4,10 +4,14 @@
 
 #![deny(missing_docs)]
 
+mod chain_message;
 mod message_receipt;
 mod signed_message;
 mod unsigned_message;
 
-pub use self::message_receipt::MessageReceipt;
+pub use self::chain_message::ChainMessage;
+pub use self::message_receipt::{MessageReceipt, ReceiptError};
 pub use self::signed_message::SignedMessage;
-pub use self::unsigned_message::UnsignedMessage;
+pub use self::unsigned_message::{
+    UnsignedMessage, MESSAGE_VERSION_FEE_MARKET, MESSAGE_VERSION_LEGACY,
+};
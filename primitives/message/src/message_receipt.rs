@@ -3,9 +3,21 @@
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
-use plum_bigint::{bigint_json, BigInt, BigIntRefWrapper, BigIntWrapper};
+use plum_bigint::{BigInt, BigIntRefWrapper, BigIntWrapper};
+use plum_types::gas_json;
 use plum_vm_exitcode::ExitCode;
 
+/// The errors of the message receipt.
+#[derive(Debug, thiserror::Error)]
+pub enum ReceiptError {
+    /// The message failed with the non-zero exit code.
+    #[error("message failed with exit code {0}: {}", .0.description())]
+    Failed(ExitCode),
+    /// The return value can't be decoded as the expected type.
+    #[error("failed to decode the return value: {0}")]
+    Decode(#[from] decode::Error),
+}
+
 /// The receipt of applying message.
 #[derive(Eq, PartialEq, Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -16,10 +28,27 @@ pub struct MessageReceipt {
     #[serde(with = "plum_bytes::base64")]
     pub r#return: Vec<u8>,
     /// The used number of gas.
-    #[serde(with = "bigint_json")]
+    #[serde(with = "gas_json")]
     pub gas_used: BigInt,
 }
 
+impl MessageReceipt {
+    /// Return an error describing the exit code if the message failed.
+    pub fn check(&self) -> Result<(), ReceiptError> {
+        if self.exit_code.is_success() {
+            Ok(())
+        } else {
+            Err(ReceiptError::Failed(self.exit_code))
+        }
+    }
+
+    /// Decode the return value of the succeeded message as the type `T`.
+    pub fn decode_return<'b, T: decode::Decode<'b>>(&'b self) -> Result<T, ReceiptError> {
+        self.check()?;
+        Ok(minicbor::decode(&self.r#return)?)
+    }
+}
+
 // Implement CBOR serialization for MessageReceipt.
 impl encode::Encode for MessageReceipt {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
@@ -35,7 +64,11 @@ impl encode::Encode for MessageReceipt {
 impl<'b> decode::Decode<'b> for MessageReceipt {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(3));
+        if array_len != Some(3) {
+            return Err(decode::Error::Message(
+                "expected 3 fields of message receipt",
+            ));
+        }
         Ok(MessageReceipt {
             exit_code: d.decode()?,
             r#return: d.bytes()?.to_vec(),
@@ -63,6 +96,31 @@ mod tests {
         assert_eq!(de, receipt);
     }
 
+    #[test]
+    fn message_receipt_return() {
+        let receipt = MessageReceipt {
+            exit_code: ExitCode::Ok,
+            r#return: minicbor::to_vec(&[1u64, 2, 3]).unwrap(),
+            gas_used: BigInt::from(1_776_234),
+        };
+        assert_eq!(receipt.decode_return::<Vec<u64>>().unwrap(), vec![1, 2, 3]);
+        assert!(matches!(
+            receipt.decode_return::<String>(),
+            Err(ReceiptError::Decode(_))
+        ));
+
+        let receipt = MessageReceipt {
+            exit_code: ExitCode::SysErrOutOfGas,
+            r#return: vec![],
+            gas_used: BigInt::from(1_776_234),
+        };
+        let err = receipt.decode_return::<Vec<u64>>().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "message failed with exit code SysErrOutOfGas(7): the execution ran out of the gas limit"
+        );
+    }
+
     #[test]
     fn message_receipt_json_serde() {
         let receipt = MessageReceipt {
@@ -70,7 +128,7 @@ mod tests {
             r#return: b"ret".to_vec(),
             gas_used: BigInt::from(1_776_234),
         };
-        let expected = "{\"ExitCode\":0,\"Return\":\"cmV0\",\"GasUsed\":\"1776234\"}";
+        let expected = "{\"ExitCode\":0,\"Return\":\"cmV0\",\"GasUsed\":1776234}";
 
         let ser = serde_json::to_string(&receipt).unwrap();
         assert_eq!(ser, expected);
@@ -4,7 +4,7 @@ use cid::{Cid, Codec};
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
-use plum_crypto::{Signature, SignatureType};
+use plum_crypto::{CryptoError, Signature, SignatureType};
 
 use crate::unsigned_message::UnsignedMessage;
 
@@ -19,7 +19,8 @@ pub struct SignedMessage {
 }
 
 impl SignedMessage {
-    /// Convert to the CID.
+    /// Convert to the CID, which is the CID of the unsigned message for the `BLS` message,
+    /// see `ChainMessage` for why and for caching the CID.
     pub fn cid(&self) -> Cid {
         if self.signature.r#type() == SignatureType::Bls {
             return self.message.cid();
@@ -49,6 +50,15 @@ impl SignedMessage {
         data.len()
     }
 
+    /// Verify the signature against the signing payload of the message.
+    ///
+    /// The `from` address of the message must be a public key address,
+    /// the ID address should be resolved before the verification.
+    pub fn verify_signature(&self) -> Result<bool, CryptoError> {
+        self.signature
+            .verify(&self.message.from, self.message.signing_bytes())
+    }
+
     /// Returns the reference of inner message to be used in vm.
     pub fn vm_message(&self) -> &UnsignedMessage {
         &self.message
@@ -69,7 +79,11 @@ impl encode::Encode for SignedMessage {
 impl<'b> decode::Decode<'b> for SignedMessage {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(2));
+        if array_len != Some(2) {
+            return Err(decode::Error::Message(
+                "expected 2 fields of signed message",
+            ));
+        }
         Ok(SignedMessage {
             message: d.decode::<UnsignedMessage>()?,
             signature: d.decode::<Signature>()?,
@@ -6,10 +6,22 @@ use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
 
 use plum_address::Address;
+use plum_bigint::num_traits::ToPrimitive;
 use plum_bigint::{bigint_json, BigInt, BigIntRefWrapper, BigIntWrapper};
 use plum_types::{Gas, MethodNum};
 
+/// The version of the message format before the fee market, whose gas is paid by `gas_price`.
+pub const MESSAGE_VERSION_LEGACY: i64 = 0;
+/// The version of the message format with the fee market, whose gas is paid by
+/// `gas_fee_cap` and `gas_premium`, the base fee is burnt and the premium is paid to the miner.
+pub const MESSAGE_VERSION_FEE_MARKET: i64 = 1;
+
 /// The unsigned message.
+///
+/// The CBOR encoding depends on the version, the legacy message is encoded as a 9-field tuple
+/// with `gas_price` and the `gas_limit` as a big integer, and the fee market message is encoded
+/// as a 10-field tuple with `gas_fee_cap` and `gas_premium` and the `gas_limit` as an int64, the
+/// same as Lotus. The fields not belonging to the version are ignored.
 #[derive(Eq, PartialEq, Clone, Debug, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct UnsignedMessage {
@@ -26,12 +38,19 @@ pub struct UnsignedMessage {
     #[serde(with = "bigint_json")]
     pub value: BigInt,
 
-    /// The price of gas.
+    /// The price of gas, only used by the legacy message.
     #[serde(with = "bigint_json")]
     pub gas_price: BigInt,
-    /// The limit of gas.
-    #[serde(with = "bigint_json")]
-    pub gas_limit: BigInt,
+    /// The limit of gas, which is an int64 in Lotus.
+    pub gas_limit: i64,
+    /// The max price of gas the sender is willing to pay, including the base fee,
+    /// only used by the fee market message.
+    #[serde(with = "bigint_json", default)]
+    pub gas_fee_cap: BigInt,
+    /// The price of gas paid to the miner on top of the base fee,
+    /// only used by the fee market message.
+    #[serde(with = "bigint_json", default)]
+    pub gas_premium: BigInt,
 
     /// The method.
     pub method: MethodNum,
@@ -57,14 +76,51 @@ impl UnsignedMessage {
         Cid::new_v1(Codec::DagCBOR, hash)
     }
 
+    /// Return the payload to be signed by the sender, which is the bytes of the message CID.
+    ///
+    /// The payload is the same for all the signature types, the difference is how it's signed:
+    ///
+    /// - secp256k1 signs the blake2b-256 digest of the payload.
+    /// - BLS signs the payload directly, and the aggregate signature of the BLS messages
+    ///   in a block is verified against the payloads of these messages.
+    ///
+    /// The wallet, the message pool and the block validator must all use this payload,
+    /// otherwise the signatures produced by one can't be verified by the others.
+    pub fn signing_bytes(&self) -> Vec<u8> {
+        self.cid().to_bytes()
+    }
+
+    /// Return true if the message uses the legacy format, i.e. it's paid by `gas_price`.
+    pub fn is_legacy(&self) -> bool {
+        self.version == MESSAGE_VERSION_LEGACY
+    }
+
+    /// Return the max price of gas the sender pays, which is `gas_price` for the legacy message.
+    pub fn fee_cap(&self) -> &BigInt {
+        if self.is_legacy() {
+            &self.gas_price
+        } else {
+            &self.gas_fee_cap
+        }
+    }
+
+    /// Return the price of gas on top of the base fee, which is `gas_price` for the legacy message.
+    pub fn premium(&self) -> &BigInt {
+        if self.is_legacy() {
+            &self.gas_price
+        } else {
+            &self.gas_premium
+        }
+    }
+
     /// Return the required funds.
     pub fn required_funds(&self) -> BigInt {
-        self.value.clone() + (&self.gas_price * &self.gas_limit)
+        self.value.clone() + (self.fee_cap() * BigInt::from(self.gas_limit))
     }
 
     /// Returns true if this message is valid to be include in a block.
     pub fn validate_for_block_inclusion(&self, min_gas: Gas) -> Result<()> {
-        if self.version != 0 {
+        if self.version != MESSAGE_VERSION_LEGACY && self.version != MESSAGE_VERSION_FEE_MARKET {
             return Err(anyhow!("version unsupported"));
         }
 
@@ -82,14 +138,28 @@ impl UnsignedMessage {
             return Err(anyhow!("gas_price field cannot be negative"));
         }
 
-        if self.gas_limit > plum_types::BLOCK_GAS_LIMIT.into() {
+        if self.gas_fee_cap < 0.into() {
+            return Err(anyhow!("gas_fee_cap field cannot be negative"));
+        }
+
+        if self.gas_premium < 0.into() {
+            return Err(anyhow!("gas_premium field cannot be negative"));
+        }
+
+        if self.premium() > self.fee_cap() {
+            return Err(anyhow!(
+                "gas_premium field cannot be greater than gas_fee_cap"
+            ));
+        }
+
+        if self.gas_limit > plum_types::BLOCK_GAS_LIMIT as i64 {
             return Err(anyhow!(
                 "gas_limit field cannot be greater than a block's gas limit"
             ));
         }
 
         // since prices might vary with time, this is technically semantic validation
-        if self.gas_limit < min_gas {
+        if BigInt::from(self.gas_limit) < min_gas {
             return Err(anyhow!(
                 "gas_limit field cannot be less than the cost of storing a message on chain",
             ));
@@ -102,17 +172,32 @@ impl UnsignedMessage {
 // Implement CBOR serialization for UnsignedMessage.
 impl encode::Encode for UnsignedMessage {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
-        e.array(9)?
-            .i64(self.version)?
-            .encode(&self.to)?
-            .encode(&self.from)?
-            .u64(self.nonce)?
-            .encode(BigIntRefWrapper::from(&self.value))?
-            .encode(BigIntRefWrapper::from(&self.gas_price))?
-            .encode(BigIntRefWrapper::from(&self.gas_limit))?
-            .u64(self.method)?
-            .bytes(&self.params)?
-            .ok()
+        if self.is_legacy() {
+            e.array(9)?
+                .i64(self.version)?
+                .encode(&self.to)?
+                .encode(&self.from)?
+                .u64(self.nonce)?
+                .encode(BigIntRefWrapper::from(&self.value))?
+                .encode(BigIntRefWrapper::from(&self.gas_price))?
+                .encode(BigIntWrapper::from(BigInt::from(self.gas_limit)))?
+                .u64(self.method)?
+                .bytes(&self.params)?
+                .ok()
+        } else {
+            e.array(10)?
+                .i64(self.version)?
+                .encode(&self.to)?
+                .encode(&self.from)?
+                .u64(self.nonce)?
+                .encode(BigIntRefWrapper::from(&self.value))?
+                .i64(self.gas_limit)?
+                .encode(BigIntRefWrapper::from(&self.gas_fee_cap))?
+                .encode(BigIntRefWrapper::from(&self.gas_premium))?
+                .u64(self.method)?
+                .bytes(&self.params)?
+                .ok()
+        }
     }
 }
 
@@ -120,15 +205,45 @@ impl encode::Encode for UnsignedMessage {
 impl<'b> decode::Decode<'b> for UnsignedMessage {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(9));
+        let legacy = match array_len {
+            Some(9) => true,
+            Some(10) => false,
+            _ => return Err(decode::Error::Message("expected 9 or 10 fields of message")),
+        };
+        let version = d.i64()?;
+        if legacy != (version == MESSAGE_VERSION_LEGACY) {
+            return Err(decode::Error::Message(
+                "the fields of message don't match the version",
+            ));
+        }
+        let to = d.decode::<Address>()?;
+        let from = d.decode::<Address>()?;
+        let nonce = d.u64()?;
+        let value = d.decode::<BigIntWrapper>()?.into_inner();
+        let (gas_price, gas_limit, gas_fee_cap, gas_premium) = if legacy {
+            let gas_price = d.decode::<BigIntWrapper>()?.into_inner();
+            let gas_limit = d
+                .decode::<BigIntWrapper>()?
+                .into_inner()
+                .to_i64()
+                .ok_or(decode::Error::Message("gas limit of message out of range"))?;
+            (gas_price, gas_limit, BigInt::default(), BigInt::default())
+        } else {
+            let gas_limit = d.i64()?;
+            let gas_fee_cap = d.decode::<BigIntWrapper>()?.into_inner();
+            let gas_premium = d.decode::<BigIntWrapper>()?.into_inner();
+            (BigInt::default(), gas_limit, gas_fee_cap, gas_premium)
+        };
         Ok(UnsignedMessage {
-            version: d.i64()?,
-            to: d.decode::<Address>()?,
-            from: d.decode::<Address>()?,
-            nonce: d.u64()?,
-            value: d.decode::<BigIntWrapper>()?.into_inner(),
-            gas_price: d.decode::<BigIntWrapper>()?.into_inner(),
-            gas_limit: d.decode::<BigIntWrapper>()?.into_inner(),
+            version,
+            to,
+            from,
+            nonce,
+            value,
+            gas_price,
+            gas_limit,
+            gas_fee_cap,
+            gas_premium,
             method: d.u64()?,
             params: d.bytes()?.to_vec(),
         })
@@ -137,9 +252,10 @@ impl<'b> decode::Decode<'b> for UnsignedMessage {
 
 #[cfg(test)]
 mod tests {
-    use plum_address::{set_network, Address, Network};
+    use plum_address::{with_network, Address, Network};
+    use plum_bigint::BigInt;
 
-    use super::UnsignedMessage;
+    use super::{UnsignedMessage, MESSAGE_VERSION_FEE_MARKET};
 
     fn new_unsigned_message() -> UnsignedMessage {
         let to_pubkey = [
@@ -159,8 +275,10 @@ mod tests {
             from: Address::new_bls_addr(&from_pubkey).unwrap(),
             nonce: 197u64,
             value: Default::default(),
-            gas_limit: 126_723u64.into(),
+            gas_limit: 126_723,
             gas_price: 1_776_234u64.into(),
+            gas_fee_cap: Default::default(),
+            gas_premium: Default::default(),
             method: 1_231_254u64,
             params: b"some bytes, idk. probably at least ten of them".to_vec(),
         }
@@ -168,9 +286,7 @@ mod tests {
 
     #[test]
     fn unsigned_message_cbor_serde() {
-        unsafe {
-            set_network(Network::Test);
-        }
+        let _guard = with_network(Network::Test);
         let unsigned_message = new_unsigned_message();
         let expected = vec![
             137, 0, 88, 49, 3, 82, 253, 252, 7, 33, 130, 101, 79, 22, 63, 95, 15, 154, 98, 29, 114,
@@ -192,9 +308,7 @@ mod tests {
 
     #[test]
     fn unsigned_message_json_serde() {
-        unsafe {
-            set_network(Network::Test);
-        }
+        let _guard = with_network(Network::Test);
         let unsigned_message = new_unsigned_message();
         let expected = "{\
             \"Version\":0,\
@@ -203,7 +317,9 @@ mod tests {
             \"Nonce\":197,\
             \"Value\":\"0\",\
             \"GasPrice\":\"1776234\",\
-            \"GasLimit\":\"126723\",\
+            \"GasLimit\":126723,\
+            \"GasFeeCap\":\"0\",\
+            \"GasPremium\":\"0\",\
             \"Method\":1231254,\
             \"Params\":\"c29tZSBieXRlcywgaWRrLiBwcm9iYWJseSBhdCBsZWFzdCB0ZW4gb2YgdGhlbQ==\"\
         }";
@@ -213,4 +329,61 @@ mod tests {
         let de = serde_json::from_str::<UnsignedMessage>(&ser).unwrap();
         assert_eq!(de, unsigned_message);
     }
+
+    #[test]
+    fn fee_market_message_cbor_serde() {
+        let _guard = with_network(Network::Test);
+        let legacy = new_unsigned_message();
+        let mut message = legacy.clone();
+        message.version = MESSAGE_VERSION_FEE_MARKET;
+        message.gas_price = Default::default();
+        message.gas_fee_cap = 2_000_000u64.into();
+        message.gas_premium = 1_000u64.into();
+
+        // The encoding of Lotus, the gas limit is an int64 instead of a big integer.
+        let expected = vec![
+            138, 1, 88, 49, 3, 82, 253, 252, 7, 33, 130, 101, 79, 22, 63, 95, 15, 154, 98, 29, 114,
+            149, 102, 199, 77, 16, 3, 124, 77, 123, 187, 4, 7, 209, 226, 198, 73, 129, 133, 90,
+            216, 104, 29, 13, 134, 209, 233, 30, 0, 22, 121, 57, 203, 88, 49, 3, 47, 130, 130, 203,
+            226, 249, 105, 111, 49, 68, 192, 170, 76, 237, 86, 219, 217, 103, 220, 40, 151, 128,
+            106, 243, 190, 216, 166, 58, 202, 22, 225, 139, 104, 107, 160, 220, 32, 140, 254, 206,
+            101, 189, 112, 162, 61, 160, 2, 107, 24, 197, 64, 26, 0, 1, 239, 3, 68, 0, 30, 132,
+            128, 67, 0, 3, 232, 26, 0, 18, 201, 150, 88, 46, 115, 111, 109, 101, 32, 98, 121, 116,
+            101, 115, 44, 32, 105, 100, 107, 46, 32, 112, 114, 111, 98, 97, 98, 108, 121, 32, 97,
+            116, 32, 108, 101, 97, 115, 116, 32, 116, 101, 110, 32, 111, 102, 32, 116, 104, 101,
+            109,
+        ];
+        let ser = minicbor::to_vec(&message).unwrap();
+        assert_eq!(ser, expected);
+        let de = minicbor::decode::<UnsignedMessage>(&ser).unwrap();
+        assert_eq!(de, message);
+        assert_eq!(de.fee_cap(), &message.gas_fee_cap);
+        assert_eq!(de.premium(), &message.gas_premium);
+        assert_eq!(
+            de.required_funds(),
+            &message.gas_fee_cap * BigInt::from(message.gas_limit)
+        );
+
+        // the legacy message is still encoded as 9 fields and paid by the gas price.
+        let ser = minicbor::to_vec(&legacy).unwrap();
+        assert_eq!(ser[0], 0x89);
+        assert_eq!(legacy.fee_cap(), &legacy.gas_price);
+
+        // the gas limit of the legacy message doesn't fit in an int64.
+        let mut zero = legacy.clone();
+        zero.gas_limit = 0;
+        let ser = minicbor::to_vec(&zero).unwrap();
+        // The zero gas limit is followed by the method and the params.
+        let pos = ser.len() - legacy.params.len() - 2 - 5 - 1;
+        assert_eq!(ser[pos], 0x40);
+        let mut huge = ser[..pos].to_vec();
+        huge.extend_from_slice(&[0x49, 0, 0x80, 0, 0, 0, 0, 0, 0, 0]);
+        huge.extend_from_slice(&ser[pos + 1..]);
+        assert!(minicbor::decode::<UnsignedMessage>(&huge).is_err());
+
+        // the version doesn't match the fields.
+        let mut ser = minicbor::to_vec(&legacy).unwrap();
+        ser[1] = MESSAGE_VERSION_FEE_MARKET as u8;
+        assert!(minicbor::decode::<UnsignedMessage>(&ser).is_err());
+    }
 }
@@ -4,10 +4,12 @@
 
 #![deny(missing_docs)]
 
+mod onchain;
 mod posting;
 mod sealing;
 mod sector;
 
+pub use self::onchain::{SectorOnChainInfo, SectorPreCommitInfo, SectorPreCommitOnChainInfo};
 pub use self::posting::{PoStProof, WindowPoStVerifyInfo, WinningPoStVerifyInfo};
 pub use self::sealing::SealVerifyInfo;
 pub use self::sector::{
@@ -116,11 +118,115 @@ mod tests {
             interactive_randomness: vec![2; 32].into(),
             proof: vec![1, 2, 3, 4, 5, 6, 7, 8],
             sealed_cid: cid.clone(),
-            unsealed_cid: cid,
+            unsealed_cid: cid.clone(),
             deal_ids: vec![8, 7, 6],
         };
         // TODO need check
 
+        // SectorPreCommitInfo, SectorPreCommitOnChainInfo and SectorOnChainInfo
+        let cid_bytes = minicbor::to_vec(&cid).unwrap();
+        let pre_commit_info = SectorPreCommitInfo {
+            seal_proof,
+            sector_number: 1111,
+            sealed_cid: cid.clone(),
+            seal_rand_epoch: 10,
+            deal_ids: vec![8, 7],
+            expiration: 1000,
+            replace_capacity: false,
+            replace_sector_deadline: 0,
+            replace_sector_partition: 0,
+            replace_sector_number: 0,
+        };
+        let pre_commit_info_bytes = [
+            &[138, 2, 25, 4, 87][..],
+            &cid_bytes,
+            &[10, 130, 8, 7, 25, 3, 232, 244, 0, 0, 0],
+        ]
+        .concat();
+        asset_cbor(&pre_commit_info, pre_commit_info_bytes.clone());
+        let pre_commit_info_json = "{\
+            \"SealProof\":2,\
+            \"SectorNumber\":1111,\
+            \"SealedCID\":{\"/\":\"bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i\"},\
+            \"SealRandEpoch\":10,\
+            \"DealIDs\":[8,7],\
+            \"Expiration\":1000,\
+            \"ReplaceCapacity\":false,\
+            \"ReplaceSectorDeadline\":0,\
+            \"ReplaceSectorPartition\":0,\
+            \"ReplaceSectorNumber\":0\
+        }";
+        assert_json(&pre_commit_info, pre_commit_info_json);
+
+        let pre_commit_on_chain_info = SectorPreCommitOnChainInfo {
+            info: pre_commit_info,
+            pre_commit_deposit: 100.into(),
+            pre_commit_epoch: 20,
+            deal_weight: 0.into(),
+            verified_deal_weight: 0.into(),
+        };
+        asset_cbor(
+            &pre_commit_on_chain_info,
+            [
+                &[133][..],
+                &pre_commit_info_bytes,
+                &[66, 0, 100, 20, 64, 64],
+            ]
+            .concat(),
+        );
+        assert_json(
+            &pre_commit_on_chain_info,
+            &format!(
+                "{{\
+                    \"Info\":{},\
+                    \"PreCommitDeposit\":\"100\",\
+                    \"PreCommitEpoch\":20,\
+                    \"DealWeight\":\"0\",\
+                    \"VerifiedDealWeight\":\"0\"\
+                }}",
+                pre_commit_info_json
+            ),
+        );
+
+        let on_chain_info = SectorOnChainInfo {
+            sector_number: 1111,
+            seal_proof,
+            sealed_cid: cid.clone(),
+            deal_ids: vec![],
+            activation: 30,
+            expiration: 1000,
+            deal_weight: 0.into(),
+            verified_deal_weight: 0.into(),
+            initial_pledge: 256.into(),
+            expected_day_reward: 1.into(),
+            expected_storage_pledge: 0.into(),
+        };
+        asset_cbor(
+            &on_chain_info,
+            [
+                &[139, 25, 4, 87, 2][..],
+                &cid_bytes,
+                &[128, 24, 30, 25, 3, 232, 64, 64, 67, 0, 1, 0, 66, 0, 1, 64],
+            ]
+            .concat(),
+        );
+        assert_json(
+            &on_chain_info,
+            "{\
+                \"SectorNumber\":1111,\
+                \"SealProof\":2,\
+                \"SealedCID\":{\"/\":\"bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i\"},\
+                \"DealIDs\":[],\
+                \"Activation\":30,\
+                \"Expiration\":1000,\
+                \"DealWeight\":\"0\",\
+                \"VerifiedDealWeight\":\"0\",\
+                \"InitialPledge\":\"256\",\
+                \"ExpectedDayReward\":\"1\",\
+                \"ExpectedStoragePledge\":\"0\"\
+            }",
+        );
+
         // RegisteredSealProof
         let post_proof = RegisteredPoStProof::StackedDrgWinning64GiBV1;
         asset_cbor(&post_proof, vec![4]);
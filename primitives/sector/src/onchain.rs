@@ -0,0 +1,209 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use cid::Cid;
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use plum_bigint::{bigint_json, BigIntRefWrapper, BigIntWrapper};
+use plum_types::{ChainEpoch, DealId, DealWeight, TokenAmount};
+
+use crate::sector::{RegisteredSealProof, SectorNumber};
+
+/// Information provided by a miner when pre-committing a sector.
+///
+/// See specs-actors/actors/builtin/miner/miner_state.go for details.
+#[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorPreCommitInfo {
+    /// The seal proof type of the sector.
+    pub seal_proof: RegisteredSealProof,
+    /// The number of the sector.
+    pub sector_number: SectorNumber,
+    /// CommR.
+    #[serde(rename = "SealedCID")]
+    pub sealed_cid: Cid,
+    /// The epoch of the randomness used to seal the sector.
+    pub seal_rand_epoch: ChainEpoch,
+    /// The deals stored in the sector.
+    #[serde(rename = "DealIDs")]
+    pub deal_ids: Vec<DealId>,
+    /// The epoch when the sector expires.
+    pub expiration: ChainEpoch,
+    /// Whether to replace a committed capacity sector with this sector.
+    pub replace_capacity: bool,
+    /// The deadline of the committed capacity sector to be replaced.
+    pub replace_sector_deadline: u64,
+    /// The partition of the committed capacity sector to be replaced.
+    pub replace_sector_partition: u64,
+    /// The number of the committed capacity sector to be replaced.
+    pub replace_sector_number: SectorNumber,
+}
+
+// Implement CBOR serialization for SectorPreCommitInfo.
+impl encode::Encode for SectorPreCommitInfo {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(10)?
+            .encode(&self.seal_proof)?
+            .u64(self.sector_number)?
+            .encode(&self.sealed_cid)?
+            .i64(self.seal_rand_epoch)?
+            .encode(&self.deal_ids)?
+            .i64(self.expiration)?
+            .bool(self.replace_capacity)?
+            .u64(self.replace_sector_deadline)?
+            .u64(self.replace_sector_partition)?
+            .u64(self.replace_sector_number)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for SectorPreCommitInfo.
+impl<'b> decode::Decode<'b> for SectorPreCommitInfo {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(10) {
+            return Err(decode::Error::Message(
+                "expected 10 fields of SectorPreCommitInfo",
+            ));
+        }
+        Ok(SectorPreCommitInfo {
+            seal_proof: d.decode::<RegisteredSealProof>()?,
+            sector_number: d.u64()?,
+            sealed_cid: d.decode::<Cid>()?,
+            seal_rand_epoch: d.i64()?,
+            deal_ids: d.decode::<Vec<DealId>>()?,
+            expiration: d.i64()?,
+            replace_capacity: d.bool()?,
+            replace_sector_deadline: d.u64()?,
+            replace_sector_partition: d.u64()?,
+            replace_sector_number: d.u64()?,
+        })
+    }
+}
+
+/// Information stored on-chain for a pre-committed sector.
+#[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorPreCommitOnChainInfo {
+    /// The pre-commit information provided by the miner.
+    pub info: SectorPreCommitInfo,
+    /// The deposit locked for the pre-committed sector.
+    #[serde(with = "bigint_json")]
+    pub pre_commit_deposit: TokenAmount,
+    /// The epoch when the sector is pre-committed.
+    pub pre_commit_epoch: ChainEpoch,
+    /// Integral of active deals over sector lifetime.
+    #[serde(with = "bigint_json")]
+    pub deal_weight: DealWeight,
+    /// Integral of active verified deals over sector lifetime.
+    #[serde(with = "bigint_json")]
+    pub verified_deal_weight: DealWeight,
+}
+
+// Implement CBOR serialization for SectorPreCommitOnChainInfo.
+impl encode::Encode for SectorPreCommitOnChainInfo {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(5)?
+            .encode(&self.info)?
+            .encode(BigIntRefWrapper::from(&self.pre_commit_deposit))?
+            .i64(self.pre_commit_epoch)?
+            .encode(BigIntRefWrapper::from(&self.deal_weight))?
+            .encode(BigIntRefWrapper::from(&self.verified_deal_weight))?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for SectorPreCommitOnChainInfo.
+impl<'b> decode::Decode<'b> for SectorPreCommitOnChainInfo {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(5) {
+            return Err(decode::Error::Message(
+                "expected 5 fields of SectorPreCommitOnChainInfo",
+            ));
+        }
+        Ok(SectorPreCommitOnChainInfo {
+            info: d.decode::<SectorPreCommitInfo>()?,
+            pre_commit_deposit: d.decode::<BigIntWrapper>()?.into_inner(),
+            pre_commit_epoch: d.i64()?,
+            deal_weight: d.decode::<BigIntWrapper>()?.into_inner(),
+            verified_deal_weight: d.decode::<BigIntWrapper>()?.into_inner(),
+        })
+    }
+}
+
+/// Information stored on-chain for a proven sector.
+#[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorOnChainInfo {
+    /// The number of the sector.
+    pub sector_number: SectorNumber,
+    /// The seal proof type of the sector.
+    pub seal_proof: RegisteredSealProof,
+    /// CommR.
+    #[serde(rename = "SealedCID")]
+    pub sealed_cid: Cid,
+    /// The deals stored in the sector.
+    #[serde(rename = "DealIDs")]
+    pub deal_ids: Vec<DealId>,
+    /// The epoch when the proof of replication is accepted.
+    pub activation: ChainEpoch,
+    /// The epoch when the sector expires.
+    pub expiration: ChainEpoch,
+    /// Integral of active deals over sector lifetime.
+    #[serde(with = "bigint_json")]
+    pub deal_weight: DealWeight,
+    /// Integral of active verified deals over sector lifetime.
+    #[serde(with = "bigint_json")]
+    pub verified_deal_weight: DealWeight,
+    /// The pledge collateral committed for the sector.
+    #[serde(with = "bigint_json")]
+    pub initial_pledge: TokenAmount,
+    /// The expected daily block reward of the sector when it's activated.
+    #[serde(with = "bigint_json")]
+    pub expected_day_reward: TokenAmount,
+    /// The expected pledge of the sector when it's activated.
+    #[serde(with = "bigint_json")]
+    pub expected_storage_pledge: TokenAmount,
+}
+
+// Implement CBOR serialization for SectorOnChainInfo.
+impl encode::Encode for SectorOnChainInfo {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(11)?
+            .u64(self.sector_number)?
+            .encode(&self.seal_proof)?
+            .encode(&self.sealed_cid)?
+            .encode(&self.deal_ids)?
+            .i64(self.activation)?
+            .i64(self.expiration)?
+            .encode(BigIntRefWrapper::from(&self.deal_weight))?
+            .encode(BigIntRefWrapper::from(&self.verified_deal_weight))?
+            .encode(BigIntRefWrapper::from(&self.initial_pledge))?
+            .encode(BigIntRefWrapper::from(&self.expected_day_reward))?
+            .encode(BigIntRefWrapper::from(&self.expected_storage_pledge))?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for SectorOnChainInfo.
+impl<'b> decode::Decode<'b> for SectorOnChainInfo {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(11) {
+            return Err(decode::Error::Message(
+                "expected 11 fields of SectorOnChainInfo",
+            ));
+        }
+        Ok(SectorOnChainInfo {
+            sector_number: d.u64()?,
+            seal_proof: d.decode::<RegisteredSealProof>()?,
+            sealed_cid: d.decode::<Cid>()?,
+            deal_ids: d.decode::<Vec<DealId>>()?,
+            activation: d.i64()?,
+            expiration: d.i64()?,
+            deal_weight: d.decode::<BigIntWrapper>()?.into_inner(),
+            verified_deal_weight: d.decode::<BigIntWrapper>()?.into_inner(),
+            initial_pledge: d.decode::<BigIntWrapper>()?.into_inner(),
+            expected_day_reward: d.decode::<BigIntWrapper>()?.into_inner(),
+            expected_storage_pledge: d.decode::<BigIntWrapper>()?.into_inner(),
+        })
+    }
+}
@@ -32,7 +32,9 @@ impl encode::Encode for PoStProof {
 impl<'b> decode::Decode<'b> for PoStProof {
     fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
         let array_len = d.array()?;
-        assert_eq!(array_len, Some(2));
+        if array_len != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of PoSt proof"));
+        }
         Ok(PoStProof {
             post_proof: d.decode::<RegisteredPoStProof>()?,
             proof_bytes: d.bytes()?.to_vec(),
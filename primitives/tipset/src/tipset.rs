@@ -176,7 +176,7 @@ impl<'b> decode::Decode<'b> for Tipset {
 mod tests {
     use cid::Cid;
 
-    use plum_address::{set_network, Address, Network};
+    use plum_address::{with_network, Address, Network};
     use plum_block::{BlockHeader, ElectionProof, Ticket};
     use plum_crypto::Signature;
 
@@ -194,6 +194,7 @@ mod tests {
                 vrf_proof: b"vrf proof0000000vrf proof0000000".to_vec(),
             },
             election_proof: ElectionProof {
+                win_count: 1,
                 vrf_proof: b"vrf proof0000000vrf proof0000000".to_vec(),
             },
             beacon_entries: vec![],
@@ -225,7 +226,7 @@ mod tests {
             48, 167, 49, 47, 124, 18, 38, 183, 206, 50, 72, 232, 201, 142, 225, 217, 73, 55, 160,
             199, 184, 78, 250, 129, 143, 69, 0, 191, 214, 251, 5, 129, 88, 32, 118, 114, 102, 32,
             112, 114, 111, 111, 102, 48, 48, 48, 48, 48, 48, 48, 118, 114, 102, 32, 112, 114, 111,
-            111, 102, 48, 48, 48, 48, 48, 48, 48, 129, 88, 32, 118, 114, 102, 32, 112, 114, 111,
+            111, 102, 48, 48, 48, 48, 48, 48, 48, 130, 1, 88, 32, 118, 114, 102, 32, 112, 114, 111,
             111, 102, 48, 48, 48, 48, 48, 48, 48, 118, 114, 102, 32, 112, 114, 111, 111, 102, 48,
             48, 48, 48, 48, 48, 48, 128, 128, 130, 216, 42, 88, 37, 0, 1, 113, 18, 32, 76, 2, 122,
             115, 187, 29, 97, 161, 80, 48, 167, 49, 47, 124, 18, 38, 183, 206, 50, 72, 232, 201,
@@ -251,9 +252,7 @@ mod tests {
 
     #[test]
     fn tipset_json_serde() {
-        unsafe {
-            set_network(Network::Test);
-        }
+        let _guard = with_network(Network::Test);
 
         let tipset = dummy_tipset();
         let expected = "{\
@@ -261,7 +260,7 @@ mod tests {
             \"Blocks\":[{\
                 \"Miner\":\"t012512063\",\
                 \"Ticket\":{\"VRFProof\":\"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA=\"},\
-                \"ElectionProof\":{\"VRFProof\":\"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA=\"},\
+                \"ElectionProof\":{\"WinCount\":1,\"VRFProof\":\"dnJmIHByb29mMDAwMDAwMHZyZiBwcm9vZjAwMDAwMDA=\"},\
                 \"BeaconEntries\":[],\
                 \"WinPoStProof\":[],\
                 \"Parents\":[\
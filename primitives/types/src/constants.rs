@@ -33,3 +33,16 @@ pub const BLOCKS_PER_EPOCH: u64 = 5;
 pub const BLOCK_MESSAGE_LIMIT: u64 = 512;
 ///
 pub const BLOCK_GAS_LIMIT: u64 = 100_000_000;
+/// The target of the gas used by a block, the base fee goes up when the blocks of a tipset
+/// use more gas than the target on average, and goes down when they use less.
+pub const BLOCK_GAS_TARGET: u64 = BLOCK_GAS_LIMIT / 2;
+
+///////////////
+// Base fee
+///////////////
+/// The base fee changes by at most `1 / BASE_FEE_MAX_CHANGE_DENOM` between two tipsets.
+pub const BASE_FEE_MAX_CHANGE_DENOM: u64 = 8;
+/// The base fee of the genesis.
+pub const INITIAL_BASE_FEE: u64 = 100_000_000;
+/// The base fee never goes below the minimum.
+pub const MINIMUM_BASE_FEE: u64 = 100;
@@ -4,6 +4,8 @@
 
 #![deny(missing_docs)]
 
+use std::fmt;
+
 use cid::Cid;
 use minicbor::{decode, encode, Decoder, Encoder};
 use serde::{Deserialize, Serialize};
@@ -18,21 +20,37 @@ pub use self::constants::*;
 /// A sequential number assigned to an actor when created by the InitActor.
 /// This ID is embedded in ID-type addresses.
 pub type ActorId = u64;
+/// The on-chain state of an actor.
+///
+/// The JSON representation is the same as Lotus, e.g.
 ///
+/// ```json
+/// {"Code":{"/":"bafk..."},"Head":{"/":"bafy..."},"Nonce":0,"Balance":"100"}
+/// ```
 #[derive(Clone, Ord, PartialOrd, Eq, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Actor {
-    ///
+    /// The CID of the code of the actor.
     pub code: Cid,
-    ///
+    /// The CID of the root of the state of the actor.
     pub head: Cid,
-    ///
+    /// The nonce, i.e. the number of the messages sent by the actor.
     pub nonce: u64,
-    ///
+    /// The balance in attoFIL, as a decimal string in JSON.
     #[serde(with = "bigint_json")]
     pub balance: BigInt,
 }
 
+impl fmt::Display for Actor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Actor {{ code: {}, head: {}, nonce: {}, balance: {} }}",
+            self.code, self.head, self.nonce, self.balance
+        )
+    }
+}
+
 // Implement CBOR serialization for Actor.
 impl encode::Encode for Actor {
     fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
@@ -98,5 +116,86 @@ pub type DealId = u64;
 /// units: byte-epochs
 pub type DealWeight = BigInt;
 
-///
+/// The amount of gas, which is a JSON number (instead of a decimal string) in Lotus.
 pub type Gas = BigInt;
+
+/// JSON serialization/deserialization of `Gas` as a number, which is the same as Lotus.
+///
+/// The decimal string representation is still accepted by the deserialization.
+pub mod gas_json {
+    use plum_bigint::num_traits::ToPrimitive;
+    use plum_bigint::BigInt;
+    use serde::{de, ser, Deserialize, Serialize};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum GasRepr {
+        Number(i64),
+        String(String),
+    }
+
+    /// JSON serialization
+    pub fn serialize<S>(gas: &BigInt, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        gas.to_i64()
+            .ok_or_else(|| ser::Error::custom(format!("gas {} out of range", gas)))?
+            .serialize(serializer)
+    }
+
+    /// JSON deserialization
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<BigInt, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match GasRepr::deserialize(deserializer)? {
+            GasRepr::Number(gas) => Ok(BigInt::from(gas)),
+            GasRepr::String(gas) => gas
+                .parse::<BigInt>()
+                .map_err(|e| de::Error::custom(e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actor_json_serde() {
+        let code: Cid = "bafkqadlgnfwc6mjpon2g64tbm5sw22lomvza".parse().unwrap();
+        let head: Cid = "bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i"
+            .parse()
+            .unwrap();
+        let actor = Actor {
+            code,
+            head,
+            nonce: 3,
+            balance: BigInt::from(10u128.pow(19)),
+        };
+        let expected = "{\
+            \"Code\":{\"/\":\"bafkqadlgnfwc6mjpon2g64tbm5sw22lomvza\"},\
+            \"Head\":{\"/\":\"bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i\"},\
+            \"Nonce\":3,\
+            \"Balance\":\"10000000000000000000\"\
+        }";
+
+        let ser = serde_json::to_string(&actor).unwrap();
+        assert_eq!(ser, expected);
+        let de = serde_json::from_str::<Actor>(&ser).unwrap();
+        assert_eq!(de, actor);
+    }
+
+    #[test]
+    fn gas_json_serde() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct GasLimit(#[serde(with = "gas_json")] Gas);
+
+        let gas = GasLimit(BigInt::from(126_723));
+        assert_eq!(serde_json::to_string(&gas).unwrap(), "126723");
+        assert_eq!(serde_json::from_str::<GasLimit>("126723").unwrap(), gas);
+        assert_eq!(serde_json::from_str::<GasLimit>("\"126723\"").unwrap(), gas);
+        assert!(serde_json::to_string(&GasLimit(BigInt::from(u64::max_value()))).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The unsigned LEB128 varint used by the addresses, the RLE+ bitfields and the CAR files.
+//!
+//! The decoding only accepts the canonical form, i.e. the minimal encoding of the value,
+//! the same as `go-varint`. Otherwise a value would have multiple encodings, which breaks
+//! the consensus on anything hashed or compared in the encoded form.
+
+#![deny(missing_docs)]
+
+use std::io::{self, Read, Write};
+
+/// The max length of the varint of u64.
+pub const MAX_U64_LEN: usize = 10;
+
+/// The error of decoding a varint.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+pub enum VarintError {
+    /// The input ends before the last byte of the varint.
+    #[error("varint is truncated")]
+    Truncated,
+    /// The value of the varint is larger than u64.
+    #[error("varint overflows u64")]
+    Overflow,
+    /// The varint is not the minimal encoding of its value.
+    #[error("varint is not minimally encoded")]
+    NotMinimal,
+}
+
+impl From<VarintError> for io::Error {
+    fn from(err: VarintError) -> Self {
+        let kind = match err {
+            VarintError::Truncated => io::ErrorKind::UnexpectedEof,
+            VarintError::Overflow | VarintError::NotMinimal => io::ErrorKind::InvalidData,
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+/// Return a buffer large enough for the varint of any u64.
+pub fn u64_buffer() -> [u8; MAX_U64_LEN] {
+    [0; MAX_U64_LEN]
+}
+
+/// Encode the `value` into the `buf`, return the encoded bytes.
+pub fn encode_u64(mut value: u64, buf: &mut [u8; MAX_U64_LEN]) -> &[u8] {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf[len] = byte;
+            len += 1;
+            break;
+        }
+        buf[len] = byte | 0x80;
+        len += 1;
+    }
+    &buf[..len]
+}
+
+/// Return the length of the varint of the `value`.
+pub fn encoded_len(value: u64) -> usize {
+    let bits = 64 - value.leading_zeros() as usize;
+    if bits == 0 {
+        1
+    } else {
+        (bits - 1) / 7 + 1
+    }
+}
+
+/// Write the varint of the `value` into the `writer`.
+pub fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    let mut buf = u64_buffer();
+    writer.write_all(encode_u64(value, &mut buf))
+}
+
+// Accumulate the `i`th byte of a varint into the `value`,
+// return true if the byte is the last one of the varint.
+fn push_byte(value: &mut u64, i: usize, byte: u8) -> Result<bool, VarintError> {
+    if i == MAX_U64_LEN - 1 && byte > 1 {
+        return Err(VarintError::Overflow);
+    }
+    *value |= u64::from(byte & 0x7f) << (7 * i);
+    if byte & 0x80 != 0 {
+        return Ok(false);
+    }
+    // The last byte of a multibyte varint being zero means the value fits in fewer bytes.
+    if byte == 0 && i > 0 {
+        return Err(VarintError::NotMinimal);
+    }
+    Ok(true)
+}
+
+/// Decode a canonical varint from the beginning of the `bytes`, return the value and the rest.
+pub fn decode_u64_canonical(bytes: &[u8]) -> Result<(u64, &[u8]), VarintError> {
+    let mut value = 0;
+    for (i, &byte) in bytes.iter().take(MAX_U64_LEN).enumerate() {
+        if push_byte(&mut value, i, byte)? {
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+    if bytes.len() < MAX_U64_LEN {
+        Err(VarintError::Truncated)
+    } else {
+        Err(VarintError::Overflow)
+    }
+}
+
+/// Read a canonical varint from the `reader`, `None` is returned if the reader is at the end.
+///
+/// The reader at the end in the middle of the varint is an `UnexpectedEof` error, and the
+/// invalid varint is an `InvalidData` error wrapping the `VarintError`.
+pub fn read_u64_canonical<R: Read>(reader: &mut R) -> io::Result<Option<u64>> {
+    let mut value = 0;
+    for i in 0..MAX_U64_LEN {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(VarintError::Truncated.into());
+        }
+        if push_byte(&mut value, i, byte[0])? {
+            return Ok(Some(value));
+        }
+    }
+    Err(VarintError::Overflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let mut values = vec![0, 1, 127, 128, 255, 256, 16383, 16384, u64::max_value()];
+        values.extend((0..64).map(|shift| 1u64 << shift));
+        values.extend((0..64).map(|shift| (1u64 << shift) - 1));
+        for value in values {
+            let mut buf = u64_buffer();
+            let bytes = encode_u64(value, &mut buf);
+            assert_eq!(bytes.len(), encoded_len(value));
+            assert_eq!(decode_u64_canonical(bytes), Ok((value, &[][..])));
+            let mut reader = bytes;
+            assert_eq!(read_u64_canonical(&mut reader).unwrap(), Some(value));
+        }
+        assert_eq!(encode_u64(300, &mut u64_buffer()), &[0xac, 0x02]);
+        assert_eq!(decode_u64_canonical(&[0x01, 0x02]), Ok((1, &[0x02][..])));
+    }
+
+    #[test]
+    fn test_non_canonical() {
+        assert_eq!(decode_u64_canonical(&[]), Err(VarintError::Truncated));
+        assert_eq!(decode_u64_canonical(&[0x80]), Err(VarintError::Truncated));
+        // The over-long encodings of 1 and 0.
+        assert_eq!(
+            decode_u64_canonical(&[0x81, 0x00]),
+            Err(VarintError::NotMinimal)
+        );
+        assert_eq!(
+            decode_u64_canonical(&[0x80, 0x80, 0x00]),
+            Err(VarintError::NotMinimal)
+        );
+        // The 10th byte can only carry the highest bit of u64.
+        let mut max = vec![0xff; 9];
+        max.push(0x01);
+        assert_eq!(decode_u64_canonical(&max), Ok((u64::max_value(), &[][..])));
+        max[9] = 0x02;
+        assert_eq!(decode_u64_canonical(&max), Err(VarintError::Overflow));
+        assert_eq!(
+            decode_u64_canonical(&[0xff; 11]),
+            Err(VarintError::Overflow)
+        );
+
+        let mut reader = &[0x81, 0x00][..];
+        let err = read_u64_canonical(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mut reader = &[0x81][..];
+        let err = read_u64_canonical(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let mut reader = &[][..];
+        assert_eq!(read_u64_canonical(&mut reader).unwrap(), None);
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The interfaces of the sector storage of the storage miner.
+
+#![deny(missing_docs)]
+
+#[macro_use]
+extern crate log;
+
+mod prover;
+mod sched;
+mod storage;
+#[cfg(test)]
+mod test_utils;
+
+pub use self::prover::{PoStBackend, PrecomputeStats, WindowPoStOutput, WindowPoStProver};
+pub use self::sched::{
+    Resources, Scheduler, SchedulerConfig, SchedulerStatus, SealTask, SealTaskId, SealTaskType,
+};
+pub use self::storage::{check_piece_range, PaddedByteIndex, Storage, UnpaddedByteIndex};
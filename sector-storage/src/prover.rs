@@ -0,0 +1,324 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, ensure, Result};
+use parking_lot::Mutex;
+
+use plum_sector::{PoStProof, RegisteredPoStProof, SectorInfo, SectorNumber};
+use plum_types::{ActorId, Randomness};
+
+/// The proofs implementation of the window PoSt, split into the two phases.
+pub trait PoStBackend {
+    /// Generate the vanilla proof of the challenged sector, which reads the sealed replica
+    /// and the cache of the sector.
+    fn generate_vanilla_proof(
+        &self,
+        proof_type: RegisteredPoStProof,
+        prover: ActorId,
+        sector: &SectorInfo,
+        randomness: &Randomness,
+    ) -> Result<Vec<u8>>;
+
+    /// Wrap the vanilla proofs of the sectors into the SNARK proofs.
+    fn generate_snark(
+        &self,
+        proof_type: RegisteredPoStProof,
+        prover: ActorId,
+        randomness: &Randomness,
+        vanilla_proofs: &[Vec<u8>],
+    ) -> Result<Vec<PoStProof>>;
+}
+
+/// The output of the window PoSt generation.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct WindowPoStOutput {
+    /// The SNARK proofs of the sectors that are not skipped.
+    pub proofs: Vec<PoStProof>,
+    /// The sectors whose vanilla proofs failed, which should be declared faulty.
+    pub skipped: Vec<SectorNumber>,
+}
+
+/// The statistics of the vanilla proofs precomputation.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct PrecomputeStats {
+    /// The number of the vanilla proofs computed.
+    pub computed: usize,
+    /// The number of the vanilla proofs already in the cache.
+    pub cached: usize,
+    /// The sectors whose vanilla proofs failed.
+    pub failed: Vec<SectorNumber>,
+}
+
+// The vanilla proof only depends on the prover, the sealed sector and the challenge randomness.
+type VanillaKey = (ActorId, SectorInfo, Randomness);
+
+/// The window PoSt prover caching the vanilla proofs of the sectors.
+///
+/// The vanilla proofs read the challenged leaves of every sector from the disk, which
+/// dominates the time of the window PoSt. They are cached by the sector and the randomness,
+/// so that a retry after a bad sector only recomputes the SNARK, and the vanilla proofs can be
+/// precomputed by `precompute` as soon as the randomness of the deadline is known.
+pub struct WindowPoStProver<B> {
+    backend: B,
+    cache: Mutex<HashMap<VanillaKey, Vec<u8>>>,
+}
+
+impl<B: PoStBackend> WindowPoStProver<B> {
+    /// Create a prover with the proofs backend.
+    pub fn new(backend: B) -> Self {
+        Self {
+            backend,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return the proofs backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Return the number of the cached vanilla proofs.
+    pub fn cached(&self) -> usize {
+        self.cache.lock().len()
+    }
+
+    /// Remove the cached vanilla proofs challenged by the randomness other than `randomness`,
+    /// which will never be used again once the deadline of `randomness` is open.
+    pub fn prune(&self, randomness: &Randomness) {
+        self.cache
+            .lock()
+            .retain(|(_, _, rand), _| rand == randomness);
+    }
+
+    /// Remove all the cached vanilla proofs.
+    pub fn clear(&self) {
+        self.cache.lock().clear();
+    }
+
+    // Return the vanilla proof of the sector and whether it's from the cache.
+    fn vanilla_proof(
+        &self,
+        prover: ActorId,
+        sector: &SectorInfo,
+        randomness: &Randomness,
+    ) -> Result<(Vec<u8>, bool)> {
+        let key = (prover, sector.clone(), randomness.clone());
+        if let Some(proof) = self.cache.lock().get(&key) {
+            return Ok((proof.clone(), true));
+        }
+        let proof_type = sector.seal_proof.registered_window_post_proof();
+        let proof = self
+            .backend
+            .generate_vanilla_proof(proof_type, prover, sector, randomness)?;
+        self.cache.lock().insert(key, proof.clone());
+        Ok((proof, false))
+    }
+
+    /// Compute the vanilla proofs of the sectors into the cache, without the SNARK.
+    pub fn precompute(
+        &self,
+        prover: ActorId,
+        sectors: &[SectorInfo],
+        randomness: &Randomness,
+    ) -> PrecomputeStats {
+        let mut stats = PrecomputeStats::default();
+        for sector in sectors {
+            match self.vanilla_proof(prover, sector, randomness) {
+                Ok((_, true)) => stats.cached += 1,
+                Ok((_, false)) => stats.computed += 1,
+                Err(err) => {
+                    warn!(
+                        "Failed to precompute vanilla proof of sector {}: {}",
+                        sector.sector_number, err
+                    );
+                    stats.failed.push(sector.sector_number);
+                }
+            }
+        }
+        stats
+    }
+
+    /// Generate the window PoSt of the sectors.
+    ///
+    /// The sectors whose vanilla proofs fail are skipped, and the rest are wrapped into the
+    /// SNARK. The vanilla proofs stay in the cache even if the SNARK fails.
+    pub fn generate_window_post(
+        &self,
+        prover: ActorId,
+        sectors: &[SectorInfo],
+        randomness: &Randomness,
+    ) -> Result<WindowPoStOutput> {
+        let proof_type = sectors
+            .first()
+            .ok_or_else(|| anyhow!("no sectors to prove"))?
+            .seal_proof
+            .registered_window_post_proof();
+        ensure!(
+            sectors
+                .iter()
+                .all(|sector| sector.seal_proof.registered_window_post_proof() == proof_type),
+            "sectors of different proof types"
+        );
+
+        let mut vanilla_proofs = Vec::with_capacity(sectors.len());
+        let mut skipped = vec![];
+        for sector in sectors {
+            match self.vanilla_proof(prover, sector, randomness) {
+                Ok((proof, _)) => vanilla_proofs.push(proof),
+                Err(err) => {
+                    warn!(
+                        "Skip sector {} of window PoSt: {}",
+                        sector.sector_number, err
+                    );
+                    skipped.push(sector.sector_number);
+                }
+            }
+        }
+        ensure!(
+            !vanilla_proofs.is_empty(),
+            "all {} sectors failed to generate vanilla proofs",
+            sectors.len()
+        );
+
+        let proofs =
+            self.backend
+                .generate_snark(proof_type, prover, randomness, &vanilla_proofs)?;
+        Ok(WindowPoStOutput { proofs, skipped })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use anyhow::bail;
+
+    use plum_sector::RegisteredSealProof;
+
+    use super::*;
+    use crate::test_utils::dummy_cid;
+
+    #[derive(Default)]
+    struct MockBackend {
+        bad_sectors: HashSet<SectorNumber>,
+        vanilla_calls: Mutex<usize>,
+        snark_calls: Mutex<usize>,
+    }
+
+    impl PoStBackend for MockBackend {
+        fn generate_vanilla_proof(
+            &self,
+            _proof_type: RegisteredPoStProof,
+            _prover: ActorId,
+            sector: &SectorInfo,
+            randomness: &Randomness,
+        ) -> Result<Vec<u8>> {
+            *self.vanilla_calls.lock() += 1;
+            if self.bad_sectors.contains(&sector.sector_number) {
+                bail!("sector {} is corrupted", sector.sector_number);
+            }
+            let mut proof = randomness.as_inner().to_vec();
+            proof.extend_from_slice(&sector.sector_number.to_be_bytes());
+            Ok(proof)
+        }
+
+        fn generate_snark(
+            &self,
+            proof_type: RegisteredPoStProof,
+            _prover: ActorId,
+            _randomness: &Randomness,
+            vanilla_proofs: &[Vec<u8>],
+        ) -> Result<Vec<PoStProof>> {
+            *self.snark_calls.lock() += 1;
+            Ok(vec![PoStProof {
+                post_proof: proof_type,
+                proof_bytes: vanilla_proofs.concat(),
+            }])
+        }
+    }
+
+    fn sectors(numbers: &[SectorNumber]) -> Vec<SectorInfo> {
+        let sealed_cid = dummy_cid();
+        numbers
+            .iter()
+            .map(|&sector_number| SectorInfo {
+                seal_proof: RegisteredSealProof::StackedDrg2KiBV1,
+                sector_number,
+                sealed_cid: sealed_cid.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_window_post_retry_with_cache() {
+        let backend = MockBackend {
+            bad_sectors: vec![2].into_iter().collect(),
+            ..Default::default()
+        };
+        let prover = WindowPoStProver::new(backend);
+        let randomness = Randomness::from(vec![1; 32]);
+
+        let output = prover
+            .generate_window_post(1000, &sectors(&[1, 2, 3]), &randomness)
+            .unwrap();
+        assert_eq!(output.skipped, vec![2]);
+        assert_eq!(
+            output.proofs[0].post_proof,
+            RegisteredPoStProof::StackedDrgWindow2KiBV1
+        );
+        assert_eq!(*prover.backend().vanilla_calls.lock(), 3);
+        assert_eq!(prover.cached(), 2);
+
+        // The retry without the bad sector only redoes the SNARK.
+        let retry = prover
+            .generate_window_post(1000, &sectors(&[1, 3]), &randomness)
+            .unwrap();
+        assert!(retry.skipped.is_empty());
+        assert_eq!(retry.proofs, output.proofs);
+        assert_eq!(*prover.backend().vanilla_calls.lock(), 3);
+        assert_eq!(*prover.backend().snark_calls.lock(), 2);
+
+        // All the sectors are bad.
+        assert!(prover
+            .generate_window_post(1000, &sectors(&[2]), &randomness)
+            .is_err());
+        assert!(prover.generate_window_post(1000, &[], &randomness).is_err());
+    }
+
+    #[test]
+    fn test_precompute() {
+        let backend = MockBackend {
+            bad_sectors: vec![4].into_iter().collect(),
+            ..Default::default()
+        };
+        let prover = WindowPoStProver::new(backend);
+        let old = Randomness::from(vec![1; 32]);
+        let new = Randomness::from(vec![2; 32]);
+
+        let stats = prover.precompute(1000, &sectors(&[1, 2]), &old);
+        assert_eq!(stats.computed, 2);
+        let stats = prover.precompute(1000, &sectors(&[1, 2, 3, 4]), &old);
+        assert_eq!(
+            stats,
+            PrecomputeStats {
+                computed: 1,
+                cached: 2,
+                failed: vec![4],
+            }
+        );
+        prover
+            .generate_window_post(1000, &sectors(&[1, 2, 3]), &old)
+            .unwrap();
+        assert_eq!(*prover.backend().vanilla_calls.lock(), 5);
+
+        // The vanilla proofs depend on the randomness.
+        prover.precompute(1000, &sectors(&[1]), &new);
+        assert_eq!(*prover.backend().vanilla_calls.lock(), 6);
+        assert_eq!(prover.cached(), 4);
+        prover.prune(&new);
+        assert_eq!(prover.cached(), 1);
+        prover.clear();
+        assert_eq!(prover.cached(), 0);
+    }
+}
@@ -0,0 +1,466 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+
+use anyhow::{ensure, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use plum_sector::{SectorId, SectorSize};
+use plum_types::ChainEpoch;
+
+const GIB: u64 = 1 << 30;
+
+/// The identifier of a seal task, assigned by the scheduler.
+pub type SealTaskId = u64;
+
+/// The type of the seal tasks.
+#[derive(Clone, Copy, Ord, PartialOrd, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+pub enum SealTaskType {
+    /// Add a piece into the unsealed sector.
+    #[serde(rename = "seal/v0/addpiece")]
+    AddPiece,
+    /// The first phase of the pre-commit, i.e. the SDR layers.
+    #[serde(rename = "seal/v0/precommit/1")]
+    PreCommit1,
+    /// The second phase of the pre-commit, i.e. the tree building.
+    #[serde(rename = "seal/v0/precommit/2")]
+    PreCommit2,
+    /// The first phase of the prove-commit, i.e. the vanilla proofs.
+    #[serde(rename = "seal/v0/commit/1")]
+    Commit1,
+    /// The second phase of the prove-commit, i.e. the SNARK on the GPU.
+    #[serde(rename = "seal/v0/commit/2")]
+    Commit2,
+    /// Remove the cache of the sealed sector.
+    #[serde(rename = "seal/v0/finalize")]
+    Finalize,
+    /// Unseal a range of the sealed sector for the retrieval.
+    #[serde(rename = "seal/v0/unseal")]
+    Unseal,
+    /// Precompute the vanilla proofs of the window PoSt, only started in the precompute mode.
+    #[serde(rename = "post/v0/precompute")]
+    PoStPrecompute,
+}
+
+impl SealTaskType {
+    /// Return the priority of the task type, the tasks closer to the end of the sealing
+    /// are started first, so that the sectors in the pipeline get done before new ones.
+    pub fn priority(self) -> u8 {
+        match self {
+            SealTaskType::PoStPrecompute => 7,
+            SealTaskType::Finalize => 6,
+            SealTaskType::Commit2 => 5,
+            SealTaskType::Commit1 => 4,
+            SealTaskType::PreCommit2 => 3,
+            SealTaskType::PreCommit1 => 2,
+            SealTaskType::AddPiece => 1,
+            SealTaskType::Unseal => 0,
+        }
+    }
+
+    /// Return the resources required by the task of the sector of `sector_size`.
+    ///
+    /// The memory is a rough minimum of the proofs implementation, the SDR layers of
+    /// PC1 (and Unseal, which recomputes them) dominate the memory usage of a sealing box.
+    pub fn resources(self, sector_size: SectorSize) -> Resources {
+        let (memory, gpu) = match self {
+            SealTaskType::AddPiece
+            | SealTaskType::Commit1
+            | SealTaskType::Finalize
+            | SealTaskType::PoStPrecompute => (sector_size.min(GIB), false),
+            SealTaskType::PreCommit1 | SealTaskType::Unseal => (sector_size / 4 * 7, false),
+            SealTaskType::PreCommit2 => (sector_size, false),
+            SealTaskType::Commit2 => (sector_size, true),
+        };
+        Resources { memory, gpu }
+    }
+}
+
+/// The resources required by a seal task.
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Resources {
+    /// The memory in bytes.
+    pub memory: u64,
+    /// Whether a GPU is required, a GPU only runs one task at a time.
+    pub gpu: bool,
+}
+
+/// The resources of the sealing box available to the scheduler.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct SchedulerConfig {
+    /// The sector size of the miner.
+    pub sector_size: SectorSize,
+    /// The memory in bytes available to the seal tasks.
+    pub memory: u64,
+    /// The number of the GPUs.
+    pub gpus: usize,
+    /// Start in the precompute mode, see `Scheduler::set_precompute`.
+    pub precompute: bool,
+}
+
+/// A seal task.
+#[derive(Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SealTask {
+    /// The identifier of the task.
+    #[serde(rename = "ID")]
+    pub id: SealTaskId,
+    /// The sector of the task.
+    pub sector: SectorId,
+    /// The type of the task.
+    pub task_type: SealTaskType,
+    /// The epoch the task of the sector must be done before, e.g. the expiration of
+    /// the pre-commit for the prove-commit tasks.
+    pub expiration: ChainEpoch,
+    /// The resources the task requires.
+    pub resources: Resources,
+    /// The GPU the task runs on, only for the started tasks that require a GPU.
+    pub gpu: Option<usize>,
+}
+
+/// The state of the queue of the scheduler.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchedulerStatus {
+    /// The queued tasks in the order they will be started.
+    pub queued: Vec<SealTask>,
+    /// The running tasks in the order they were started.
+    pub running: Vec<SealTask>,
+    /// The memory used by the running tasks.
+    pub memory_used: u64,
+    /// The memory available to the seal tasks.
+    pub memory_total: u64,
+    /// The number of the GPUs in use.
+    pub gpus_used: usize,
+    /// The number of the GPUs.
+    pub gpus_total: usize,
+    /// Whether the scheduler is in the precompute mode.
+    pub precompute: bool,
+}
+
+// The order of the queued tasks, the higher priority and the closer expiration go first,
+// and the tasks of the same priority and expiration are started in FIFO order.
+type QueueKey = (Reverse<u8>, ChainEpoch, SealTaskId);
+
+#[derive(Default)]
+struct SchedulerState {
+    next_id: SealTaskId,
+    queue: BTreeMap<QueueKey, SealTask>,
+    running: BTreeMap<SealTaskId, SealTask>,
+    memory_used: u64,
+    // The running task of each GPU.
+    gpus: Vec<Option<SealTaskId>>,
+    precompute: bool,
+}
+
+/// The scheduler of the seal tasks, which starts the queued tasks only when the sealing box
+/// has the resources to run them.
+///
+/// The scheduler doesn't run the tasks, the caller runs the tasks returned by `start_ready`
+/// and reports them to `finish` when they are done, which releases their resources.
+pub struct Scheduler {
+    config: SchedulerConfig,
+    state: Mutex<SchedulerState>,
+}
+
+impl Scheduler {
+    /// Create a scheduler for the sealing box of the `config`.
+    pub fn new(config: SchedulerConfig) -> Self {
+        let state = SchedulerState {
+            gpus: vec![None; config.gpus],
+            precompute: config.precompute,
+            ..Default::default()
+        };
+        Self {
+            config,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// Return the config of the scheduler.
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    /// Switch the precompute mode, the maintenance mode of the sealing box.
+    ///
+    /// In the precompute mode only the `PoStPrecompute` tasks are started, and the seal tasks
+    /// stay in the queue until the mode is switched off, so the vanilla proofs of the window
+    /// PoSt get the whole box. Out of the precompute mode, the `PoStPrecompute` tasks are not
+    /// started. The running tasks are not affected.
+    pub fn set_precompute(&self, precompute: bool) {
+        self.state.lock().precompute = precompute;
+    }
+
+    /// Queue the task of the sector, return the identifier of the task.
+    ///
+    /// The task that can never run on the sealing box is rejected.
+    pub fn schedule(
+        &self,
+        sector: SectorId,
+        task_type: SealTaskType,
+        expiration: ChainEpoch,
+    ) -> Result<SealTaskId> {
+        let resources = task_type.resources(self.config.sector_size);
+        ensure!(
+            resources.memory <= self.config.memory,
+            "{:?} of sector {:?} requires {} bytes of memory, only {} available",
+            task_type,
+            sector,
+            resources.memory,
+            self.config.memory
+        );
+        ensure!(
+            !resources.gpu || self.config.gpus > 0,
+            "{:?} of sector {:?} requires a GPU",
+            task_type,
+            sector
+        );
+
+        let mut state = self.state.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        let task = SealTask {
+            id,
+            sector,
+            task_type,
+            expiration,
+            resources,
+            gpu: None,
+        };
+        state
+            .queue
+            .insert((Reverse(task_type.priority()), expiration, id), task);
+        Ok(id)
+    }
+
+    /// Start the queued tasks that fit into the free resources in the priority order,
+    /// return the started tasks.
+    pub fn start_ready(&self) -> Vec<SealTask> {
+        let mut state = self.state.lock();
+        let mut started = vec![];
+        let keys = state.queue.keys().copied().collect::<Vec<_>>();
+        for key in keys {
+            let task = &state.queue[&key];
+            if (task.task_type == SealTaskType::PoStPrecompute) != state.precompute {
+                continue;
+            }
+            if state.memory_used + task.resources.memory > self.config.memory {
+                continue;
+            }
+            let gpu = if task.resources.gpu {
+                match state.gpus.iter().position(Option::is_none) {
+                    Some(gpu) => Some(gpu),
+                    None => continue,
+                }
+            } else {
+                None
+            };
+
+            let mut task = state.queue.remove(&key).expect("key is in the queue; qed");
+            task.gpu = gpu;
+            if let Some(gpu) = gpu {
+                state.gpus[gpu] = Some(task.id);
+            }
+            state.memory_used += task.resources.memory;
+            state.running.insert(task.id, task.clone());
+            started.push(task);
+        }
+        started
+    }
+
+    /// Release the resources of the running task, return false if the task is not running.
+    pub fn finish(&self, id: SealTaskId) -> bool {
+        let mut state = self.state.lock();
+        let task = match state.running.remove(&id) {
+            Some(task) => task,
+            None => return false,
+        };
+        state.memory_used -= task.resources.memory;
+        if let Some(gpu) = task.gpu {
+            state.gpus[gpu] = None;
+        }
+        true
+    }
+
+    /// Remove the queued task, return false if the task is not queued.
+    pub fn cancel(&self, id: SealTaskId) -> bool {
+        let mut state = self.state.lock();
+        let key = state
+            .queue
+            .iter()
+            .find(|(_, task)| task.id == id)
+            .map(|(key, _)| *key);
+        match key {
+            Some(key) => state.queue.remove(&key).is_some(),
+            None => false,
+        }
+    }
+
+    /// Return the state of the queue.
+    pub fn status(&self) -> SchedulerStatus {
+        let state = self.state.lock();
+        SchedulerStatus {
+            queued: state.queue.values().cloned().collect(),
+            running: state.running.values().cloned().collect(),
+            memory_used: state.memory_used,
+            memory_total: self.config.memory,
+            gpus_used: state.gpus.iter().filter(|gpu| gpu.is_some()).count(),
+            gpus_total: self.config.gpus,
+            precompute: state.precompute,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTOR_SIZE: SectorSize = 32 * GIB;
+
+    fn sector(number: u64) -> SectorId {
+        SectorId {
+            miner: 1000,
+            number,
+        }
+    }
+
+    fn started(scheduler: &Scheduler) -> Vec<(u64, SealTaskType)> {
+        scheduler
+            .start_ready()
+            .into_iter()
+            .map(|task| (task.sector.number, task.task_type))
+            .collect()
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // Enough memory for two PC1 of 32GiB sectors.
+        let scheduler = Scheduler::new(SchedulerConfig {
+            sector_size: SECTOR_SIZE,
+            memory: 128 * GIB,
+            gpus: 1,
+            precompute: false,
+        });
+        let ids = (0..4)
+            .map(|number| {
+                scheduler
+                    .schedule(sector(number), SealTaskType::PreCommit1, 100)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            started(&scheduler),
+            vec![(0, SealTaskType::PreCommit1), (1, SealTaskType::PreCommit1)]
+        );
+        assert!(started(&scheduler).is_empty());
+
+        let status = scheduler.status();
+        assert_eq!(status.queued.len(), 2);
+        assert_eq!(status.running.len(), 2);
+        assert_eq!(status.memory_used, 112 * GIB);
+
+        // The small tasks still fit into the rest of the memory.
+        scheduler
+            .schedule(sector(4), SealTaskType::AddPiece, 100)
+            .unwrap();
+        assert_eq!(started(&scheduler), vec![(4, SealTaskType::AddPiece)]);
+
+        assert!(scheduler.finish(ids[0]));
+        assert!(!scheduler.finish(ids[0]));
+        assert_eq!(started(&scheduler), vec![(2, SealTaskType::PreCommit1)]);
+        assert!(scheduler.cancel(ids[3]));
+        assert!(!scheduler.cancel(ids[3]));
+        assert!(scheduler.status().queued.is_empty());
+
+        // The task that never fits is rejected.
+        let scheduler = Scheduler::new(SchedulerConfig {
+            sector_size: SECTOR_SIZE,
+            memory: 32 * GIB,
+            gpus: 0,
+            precompute: false,
+        });
+        assert!(scheduler
+            .schedule(sector(0), SealTaskType::PreCommit1, 100)
+            .is_err());
+        assert!(scheduler
+            .schedule(sector(0), SealTaskType::Commit2, 100)
+            .is_err());
+    }
+
+    #[test]
+    fn test_gpu_and_priority() {
+        let scheduler = Scheduler::new(SchedulerConfig {
+            sector_size: SECTOR_SIZE,
+            memory: 1024 * GIB,
+            gpus: 1,
+            precompute: false,
+        });
+        scheduler
+            .schedule(sector(0), SealTaskType::PreCommit1, 100)
+            .unwrap();
+        let c2 = scheduler
+            .schedule(sector(1), SealTaskType::Commit2, 300)
+            .unwrap();
+        scheduler
+            .schedule(sector(2), SealTaskType::Commit2, 200)
+            .unwrap();
+        scheduler
+            .schedule(sector(3), SealTaskType::Commit1, 400)
+            .unwrap();
+
+        // The C2 closest to the expiration goes first, and only one C2 runs on the GPU.
+        let status = scheduler.status();
+        let queued = status
+            .queued
+            .iter()
+            .map(|task| task.sector.number)
+            .collect::<Vec<_>>();
+        assert_eq!(queued, vec![2, 1, 3, 0]);
+        let tasks = scheduler.start_ready();
+        assert_eq!(tasks[0].sector, sector(2));
+        assert_eq!(tasks[0].gpu, Some(0));
+        assert_eq!(
+            tasks
+                .iter()
+                .map(|task| task.sector.number)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 0]
+        );
+        assert_eq!(scheduler.status().gpus_used, 1);
+
+        assert!(scheduler.finish(tasks[0].id));
+        let tasks = scheduler.start_ready();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].id, c2);
+        assert_eq!(tasks[0].gpu, Some(0));
+    }
+    #[test]
+    fn test_precompute_mode() {
+        let scheduler = Scheduler::new(SchedulerConfig {
+            sector_size: SECTOR_SIZE,
+            memory: 1024 * GIB,
+            gpus: 1,
+            precompute: true,
+        });
+        scheduler
+            .schedule(sector(0), SealTaskType::PreCommit1, 100)
+            .unwrap();
+        scheduler
+            .schedule(sector(1), SealTaskType::PoStPrecompute, 200)
+            .unwrap();
+        assert!(scheduler.status().precompute);
+        assert_eq!(started(&scheduler), vec![(1, SealTaskType::PoStPrecompute)]);
+        assert!(started(&scheduler).is_empty());
+
+        scheduler.set_precompute(false);
+        scheduler
+            .schedule(sector(2), SealTaskType::PoStPrecompute, 200)
+            .unwrap();
+        assert_eq!(started(&scheduler), vec![(0, SealTaskType::PreCommit1)]);
+        assert_eq!(scheduler.status().queued.len(), 1);
+    }
+}
@@ -0,0 +1,287 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::io::{Read, Write};
+
+use anyhow::{ensure, Result};
+use cid::Cid;
+
+use plum_piece::{PieceInfo, UnpaddedPieceSize};
+use plum_sector::{SectorId, SectorSize};
+use plum_types::Randomness;
+
+/// The byte offset into the unpadded data of a sector.
+#[derive(Clone, Copy, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct UnpaddedByteIndex(pub u64);
+
+impl UnpaddedByteIndex {
+    /// Convert the unpadded offset into the offset of the padded data of the sector.
+    pub fn padded(self) -> PaddedByteIndex {
+        PaddedByteIndex(UnpaddedPieceSize(self.0).padded().0)
+    }
+}
+
+impl From<UnpaddedPieceSize> for UnpaddedByteIndex {
+    fn from(size: UnpaddedPieceSize) -> Self {
+        UnpaddedByteIndex(size.0)
+    }
+}
+
+/// The byte offset into the padded data of a sector.
+#[derive(Clone, Copy, Debug, Default, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct PaddedByteIndex(pub u64);
+
+/// Check that the unpadded range `[offset, offset + length)` is a valid piece range of
+/// the sector of `sector_size`.
+///
+/// The length must be a valid unpadded piece size, the offset must be aligned to the
+/// 127-byte chunks of the Fr32 padding, and the range must be within the sector.
+pub fn check_piece_range(
+    sector_size: SectorSize,
+    offset: UnpaddedByteIndex,
+    length: UnpaddedPieceSize,
+) -> Result<()> {
+    length.validate()?;
+    ensure!(
+        offset.0 % 127 == 0,
+        "offset {} is not aligned to the 127-byte chunks",
+        offset.0
+    );
+    let max = sector_size - sector_size / 128;
+    let end = offset.0.checked_add(length.0);
+    ensure!(
+        end.map_or(false, |end| end <= max),
+        "range [{}, {} + {}) exceeds the unpadded sector size {}",
+        offset.0,
+        offset.0,
+        length.0,
+        max
+    );
+    Ok(())
+}
+
+/// The storage of the sectors of a storage miner.
+///
+/// The deals add pieces into the sectors before they are sealed, and the retrieval provider
+/// unseals the sealed sectors to read the payload of the pieces back.
+pub trait Storage {
+    /// Create a new sector for adding the pieces.
+    fn new_sector(&self, sector: SectorId) -> Result<()>;
+
+    /// Add the piece of `new_piece_size` read from `piece_data` into the sector,
+    /// after the pieces of `existing_piece_sizes`.
+    fn add_piece(
+        &self,
+        sector: SectorId,
+        existing_piece_sizes: &[UnpaddedPieceSize],
+        new_piece_size: UnpaddedPieceSize,
+        piece_data: &mut dyn Read,
+    ) -> Result<PieceInfo>;
+
+    /// Unseal the range `[offset, offset + length)` of the sealed sector, so that it can be
+    /// read by `read_piece` later.
+    ///
+    /// The `ticket` is the seal randomness and the `commd` is the unsealed CID of the sector,
+    /// both of them are the ones the sector was sealed with.
+    fn unseal_piece(
+        &self,
+        sector: SectorId,
+        offset: UnpaddedByteIndex,
+        length: UnpaddedPieceSize,
+        ticket: &Randomness,
+        commd: &Cid,
+    ) -> Result<()>;
+
+    /// Write the unsealed range `[offset, offset + length)` of the sector into the `writer`.
+    ///
+    /// Return false without writing anything if the range has not been unsealed.
+    fn read_piece(
+        &self,
+        writer: &mut dyn Write,
+        sector: SectorId,
+        offset: UnpaddedByteIndex,
+        length: UnpaddedPieceSize,
+    ) -> Result<bool>;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use anyhow::{anyhow, bail};
+    use parking_lot::Mutex;
+
+    use plum_piece::PaddedPieceSize;
+
+    use super::*;
+    use crate::test_utils::dummy_cid;
+
+    const SECTOR_SIZE: SectorSize = 2048;
+
+    #[derive(Default)]
+    struct MockSector {
+        // The unpadded data of the sealed sector, a real sealed replica can't be read directly.
+        sealed: Vec<u8>,
+        unsealed: Vec<(u64, u64)>,
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        sectors: Mutex<HashMap<SectorId, MockSector>>,
+    }
+
+    impl Storage for MockStorage {
+        fn new_sector(&self, sector: SectorId) -> Result<()> {
+            self.sectors.lock().insert(sector, MockSector::default());
+            Ok(())
+        }
+
+        fn add_piece(
+            &self,
+            sector: SectorId,
+            existing_piece_sizes: &[UnpaddedPieceSize],
+            new_piece_size: UnpaddedPieceSize,
+            piece_data: &mut dyn Read,
+        ) -> Result<PieceInfo> {
+            let offset = existing_piece_sizes.iter().map(|size| size.0).sum::<u64>();
+            check_piece_range(SECTOR_SIZE, UnpaddedByteIndex(offset), new_piece_size)?;
+            let mut sectors = self.sectors.lock();
+            let data = &mut sectors
+                .get_mut(&sector)
+                .ok_or_else(|| anyhow!("unknown sector {:?}", sector))?
+                .sealed;
+            ensure!(data.len() as u64 == offset, "unexpected existing pieces");
+            piece_data.take(new_piece_size.0).read_to_end(data)?;
+            data.resize((offset + new_piece_size.0) as usize, 0);
+            Ok(PieceInfo {
+                size: new_piece_size.padded(),
+                piece_cid: dummy_cid(),
+            })
+        }
+
+        fn unseal_piece(
+            &self,
+            sector: SectorId,
+            offset: UnpaddedByteIndex,
+            length: UnpaddedPieceSize,
+            _ticket: &Randomness,
+            commd: &Cid,
+        ) -> Result<()> {
+            check_piece_range(SECTOR_SIZE, offset, length)?;
+            ensure!(*commd == dummy_cid(), "unexpected commd {}", commd);
+            let mut sectors = self.sectors.lock();
+            match sectors.get_mut(&sector) {
+                Some(sector) => sector.unsealed.push((offset.0, offset.0 + length.0)),
+                None => bail!("unknown sector {:?}", sector),
+            }
+            Ok(())
+        }
+
+        fn read_piece(
+            &self,
+            writer: &mut dyn Write,
+            sector: SectorId,
+            offset: UnpaddedByteIndex,
+            length: UnpaddedPieceSize,
+        ) -> Result<bool> {
+            check_piece_range(SECTOR_SIZE, offset, length)?;
+            let sectors = self.sectors.lock();
+            let sector = match sectors.get(&sector) {
+                Some(sector) => sector,
+                None => return Ok(false),
+            };
+            let (start, end) = (offset.0, offset.0 + length.0);
+            if !sector.unsealed.iter().any(|&(s, e)| s <= start && end <= e) {
+                return Ok(false);
+            }
+            match sector.sealed.get(start as usize..end as usize) {
+                Some(data) => writer.write_all(data)?,
+                None => bail!("range [{}, {}) has no data", start, end),
+            }
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn test_check_piece_range() {
+        let check = |offset: u64, length: u64| {
+            check_piece_range(
+                SECTOR_SIZE,
+                UnpaddedByteIndex(offset),
+                UnpaddedPieceSize(length),
+            )
+        };
+        assert!(check(0, 2032).is_ok());
+        assert!(check(1016, 1016).is_ok());
+        assert!(check(127, 127).is_ok());
+        // Invalid piece size.
+        assert!(check(0, 128).is_err());
+        // Unaligned offset.
+        assert!(check(1, 127).is_err());
+        // Out of the sector.
+        assert!(check(1016, 2032).is_err());
+        assert!(check(u64::max_value() / 127 * 127, 127).is_err());
+
+        assert_eq!(UnpaddedByteIndex(1016).padded(), PaddedByteIndex(1024));
+        assert_eq!(UnpaddedPieceSize(1016).padded(), PaddedPieceSize(1024));
+    }
+
+    #[test]
+    fn test_unseal_and_read_piece() {
+        let storage = MockStorage::default();
+        let sector = SectorId {
+            miner: 1000,
+            number: 1,
+        };
+        let ticket = Randomness::from(vec![1; 32]);
+        storage.new_sector(sector).unwrap();
+        let first = vec![1u8; 1016];
+        let second = vec![2u8; 508];
+        let info = storage
+            .add_piece(sector, &[], UnpaddedPieceSize(1016), &mut &first[..])
+            .unwrap();
+        assert_eq!(info.size, PaddedPieceSize(1024));
+        storage
+            .add_piece(
+                sector,
+                &[UnpaddedPieceSize(1016)],
+                UnpaddedPieceSize(508),
+                &mut &second[..],
+            )
+            .unwrap();
+
+        // The piece must be unsealed before it's read.
+        let (offset, length) = (UnpaddedByteIndex(1016), UnpaddedPieceSize(508));
+        let mut buf = vec![];
+        assert!(!storage
+            .read_piece(&mut buf, sector, offset, length)
+            .unwrap());
+        assert!(buf.is_empty());
+
+        storage
+            .unseal_piece(sector, offset, length, &ticket, &dummy_cid())
+            .unwrap();
+        assert!(storage
+            .read_piece(&mut buf, sector, offset, length)
+            .unwrap());
+        assert_eq!(buf, second);
+        // The first piece is still sealed.
+        let mut buf = vec![];
+        let first_range = (UnpaddedByteIndex(0), UnpaddedPieceSize(1016));
+        assert!(!storage
+            .read_piece(&mut buf, sector, first_range.0, first_range.1)
+            .unwrap());
+
+        // Invalid ranges are rejected.
+        assert!(storage
+            .unseal_piece(sector, UnpaddedByteIndex(1), length, &ticket, &dummy_cid())
+            .is_err());
+        assert!(storage
+            .read_piece(
+                &mut buf,
+                sector,
+                UnpaddedByteIndex(1016),
+                UnpaddedPieceSize(2032)
+            )
+            .is_err());
+    }
+}
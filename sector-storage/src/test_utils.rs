@@ -0,0 +1,12 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The fixtures shared by the tests of the crate.
+
+use cid::Cid;
+
+/// The CID of the pieces and the sealed sectors, whose content is not checked by the tests.
+pub fn dummy_cid() -> Cid {
+    "bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i"
+        .parse()
+        .unwrap()
+}
@@ -0,0 +1,431 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use anyhow::Result;
+use cid::{Cid, Codec};
+
+use ipld::Value;
+use plum_actor::miner;
+use plum_address::Address;
+use plum_bigint::BigInt;
+use plum_types::{Actor, ChainEpoch, TokenAmount, TOTAL_FILECOIN};
+
+use crate::view::StateView;
+
+/// The violation of a state invariant found by the auditor.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Violation {
+    /// The sum of the actor balances doesn't equal to the total supply.
+    SupplyMismatch {
+        /// The expected total supply.
+        expected: TokenAmount,
+        /// The sum of the actor balances.
+        actual: TokenAmount,
+    },
+    /// The balance of the actor is negative.
+    NegativeBalance {
+        /// The address of the actor.
+        address: Address,
+        /// The balance of the actor.
+        balance: TokenAmount,
+    },
+    /// A block reachable from the actor state is missing.
+    MissingBlock {
+        /// The address of the actor.
+        address: Address,
+        /// The CID of the missing block.
+        cid: Cid,
+    },
+    /// A block reachable from the actor state can't be decoded as DAG-CBOR.
+    MalformedBlock {
+        /// The address of the actor.
+        address: Address,
+        /// The CID of the malformed block.
+        cid: Cid,
+        /// The decoding error.
+        reason: String,
+    },
+    /// The pre-commit deposits or the locked funds of the miner are negative,
+    /// or their sum exceeds the balance of the miner.
+    MinerFundsInconsistent {
+        /// The address of the miner.
+        address: Address,
+        /// The pre-commit deposits of the miner.
+        pre_commit_deposits: TokenAmount,
+        /// The locked funds of the miner.
+        locked_funds: TokenAmount,
+        /// The balance of the miner.
+        balance: TokenAmount,
+    },
+    /// The recovering sectors of the miner are not faulty.
+    MinerRecoveriesNotFaulty {
+        /// The address of the miner.
+        address: Address,
+        /// The sector numbers that are recovering but not faulty.
+        sectors: Vec<u64>,
+    },
+    /// The proving period of the miner is not positive, or is not advanced in time.
+    MinerInvalidProvingPeriod {
+        /// The address of the miner.
+        address: Address,
+        /// The start of the current proving period of the miner.
+        period_start: ChainEpoch,
+        /// The epoch of the audited state.
+        epoch: ChainEpoch,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::SupplyMismatch { expected, actual } => write!(
+                f,
+                "total balance {} doesn't equal to total supply {}",
+                actual, expected
+            ),
+            Violation::NegativeBalance { address, balance } => {
+                write!(f, "actor {} has negative balance {}", address, balance)
+            }
+            Violation::MissingBlock { address, cid } => {
+                write!(f, "actor {}: block {} is missing", address, cid)
+            }
+            Violation::MalformedBlock {
+                address,
+                cid,
+                reason,
+            } => write!(f, "actor {}: block {} is malformed: {}", address, cid, reason),
+            Violation::MinerFundsInconsistent {
+                address,
+                pre_commit_deposits,
+                locked_funds,
+                balance,
+            } => write!(
+                f,
+                "miner {}: pre-commit deposits {} and locked funds {} are inconsistent with balance {}",
+                address, pre_commit_deposits, locked_funds, balance
+            ),
+            Violation::MinerRecoveriesNotFaulty { address, sectors } => write!(
+                f,
+                "miner {}: recovering sectors {:?} are not faulty",
+                address, sectors
+            ),
+            Violation::MinerInvalidProvingPeriod {
+                address,
+                period_start,
+                epoch,
+            } => write!(
+                f,
+                "miner {}: invalid proving period start {} at epoch {}",
+                address, period_start, epoch
+            ),
+        }
+    }
+}
+
+/// The result of auditing a state tree.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AuditReport {
+    /// The number of the audited actors.
+    pub actors: usize,
+    /// The number of the visited blocks reachable from the actor states.
+    pub blocks: usize,
+    /// The sum of the actor balances.
+    pub total_balance: TokenAmount,
+    /// The violations found.
+    pub violations: Vec<Violation>,
+}
+
+impl AuditReport {
+    /// Return true if no violation is found.
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// The auditor walking a state tree and validating the invariants:
+///
+/// - the sum of the actor balances equals to the total supply;
+/// - no actor has a negative balance;
+/// - every block reachable from the actor states exists and is well-formed;
+/// - the funds and the proving period of the miners are consistent.
+pub struct StateAuditor<'a, V> {
+    view: &'a V,
+    total_supply: TokenAmount,
+    check_blocks: bool,
+}
+
+impl<'a, V: StateView> StateAuditor<'a, V> {
+    /// Create a new auditor expecting the total supply to be `TOTAL_FILECOIN`.
+    pub fn new(view: &'a V) -> Self {
+        Self {
+            view,
+            total_supply: TOTAL_FILECOIN.clone(),
+            check_blocks: true,
+        }
+    }
+
+    /// Set the expected total supply, e.g. for the networks with a different genesis.
+    pub fn with_total_supply(mut self, total_supply: TokenAmount) -> Self {
+        self.total_supply = total_supply;
+        self
+    }
+
+    /// Set whether to walk all the blocks reachable from the actor states, which is slow
+    /// for a large state tree.
+    pub fn with_check_blocks(mut self, check_blocks: bool) -> Self {
+        self.check_blocks = check_blocks;
+        self
+    }
+
+    /// Audit the state tree of the `root` at the `epoch`.
+    pub fn audit(&self, root: &Cid, epoch: ChainEpoch) -> Result<AuditReport> {
+        let mut report = AuditReport::default();
+        let mut visited = HashSet::new();
+
+        for (address, actor) in self.view.actors(root)? {
+            report.actors += 1;
+            report.total_balance += &actor.balance;
+            if actor.balance < BigInt::from(0) {
+                report.violations.push(Violation::NegativeBalance {
+                    address: address.clone(),
+                    balance: actor.balance.clone(),
+                });
+            }
+            if self.check_blocks {
+                self.walk_blocks(&address, &actor.head, &mut visited, &mut report)?;
+            }
+            if let Some(state) = self.view.miner_state(&address, &actor)? {
+                check_miner(&address, &actor, &state, epoch, &mut report.violations);
+            }
+        }
+
+        if report.total_balance != self.total_supply {
+            report.violations.push(Violation::SupplyMismatch {
+                expected: self.total_supply.clone(),
+                actual: report.total_balance.clone(),
+            });
+        }
+        debug!(
+            "[audit] state {}: {} actors, {} blocks, {} violations",
+            root,
+            report.actors,
+            report.blocks,
+            report.violations.len()
+        );
+        Ok(report)
+    }
+
+    // Walk through the DAG from `head`, checking that every block exists and
+    // every DAG-CBOR block can be decoded.
+    fn walk_blocks(
+        &self,
+        address: &Address,
+        head: &Cid,
+        visited: &mut HashSet<Cid>,
+        report: &mut AuditReport,
+    ) -> Result<()> {
+        let mut pending = vec![head.clone()];
+        while let Some(cid) = pending.pop() {
+            if !visited.insert(cid.clone()) {
+                continue;
+            }
+            report.blocks += 1;
+            let data = match self.view.get_block(&cid)? {
+                Some(data) => data,
+                None => {
+                    report.violations.push(Violation::MissingBlock {
+                        address: address.clone(),
+                        cid,
+                    });
+                    continue;
+                }
+            };
+            if cid.codec() != Codec::DagCBOR {
+                continue;
+            }
+            match minicbor::decode::<Value>(&data) {
+                Ok(value) => collect_links(&value, &mut pending),
+                Err(err) => report.violations.push(Violation::MalformedBlock {
+                    address: address.clone(),
+                    cid,
+                    reason: err.to_string(),
+                }),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn collect_links(value: &Value, links: &mut Vec<Cid>) {
+    match value {
+        Value::Link(cid) => links.push(cid.clone()),
+        Value::List(list) => list.iter().for_each(|value| collect_links(value, links)),
+        Value::Map(map) => map.values().for_each(|value| collect_links(value, links)),
+        _ => {}
+    }
+}
+
+fn check_miner(
+    address: &Address,
+    actor: &Actor,
+    state: &miner::State,
+    epoch: ChainEpoch,
+    violations: &mut Vec<Violation>,
+) {
+    let zero = BigInt::from(0);
+    if state.pre_commit_deposits < zero
+        || state.locked_funds < zero
+        || &state.pre_commit_deposits + &state.locked_funds > actor.balance
+    {
+        violations.push(Violation::MinerFundsInconsistent {
+            address: address.clone(),
+            pre_commit_deposits: state.pre_commit_deposits.clone(),
+            locked_funds: state.locked_funds.clone(),
+            balance: actor.balance.clone(),
+        });
+    }
+
+    let sectors = state
+        .recoveries
+        .difference(&state.faults)
+        .copied()
+        .collect::<Vec<_>>();
+    if !sectors.is_empty() {
+        violations.push(Violation::MinerRecoveriesNotFaulty {
+            address: address.clone(),
+            sectors,
+        });
+    }
+
+    // The proving period is advanced by the cron at the end of every period, the period start
+    // may be greater than the current epoch only for the genesis miners.
+    let period_start = state.proving_period_start;
+    if period_start <= 0 || epoch >= period_start + miner::W_POST_PROVING_PERIOD as ChainEpoch {
+        violations.push(Violation::MinerInvalidProvingPeriod {
+            address: address.clone(),
+            period_start,
+            epoch,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use multihash::Blake2b256;
+    use plum_actor::power;
+
+    #[derive(Default)]
+    struct MemoryView {
+        actors: Vec<(Address, Actor)>,
+        blocks: HashMap<Cid, Vec<u8>>,
+    }
+
+    impl MemoryView {
+        fn put(&mut self, value: &Value) -> Cid {
+            let data = minicbor::to_vec(value).unwrap();
+            let cid = Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(&data));
+            self.blocks.insert(cid.clone(), data);
+            cid
+        }
+    }
+
+    impl StateView for MemoryView {
+        fn actors(&self, _root: &Cid) -> Result<Vec<(Address, Actor)>> {
+            Ok(self.actors.clone())
+        }
+
+        fn get_block(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+            Ok(self.blocks.get(cid).cloned())
+        }
+
+        fn miner_state(&self, _address: &Address, _actor: &Actor) -> Result<Option<miner::State>> {
+            Ok(None)
+        }
+
+        fn power_claim(&self, _root: &Cid, _miner: &Address) -> Result<Option<power::Claim>> {
+            Ok(None)
+        }
+
+        fn total_power(&self, _root: &Cid) -> Result<power::Claim> {
+            Ok(power::Claim {
+                raw_byte_power: BigInt::from(0),
+                quality_adj_power: BigInt::from(0),
+            })
+        }
+    }
+
+    fn new_actor(head: Cid, balance: i64) -> Actor {
+        Actor {
+            code: head.clone(),
+            head,
+            nonce: 0,
+            balance: BigInt::from(balance),
+        }
+    }
+
+    #[test]
+    fn test_audit() {
+        let mut view = MemoryView::default();
+        let missing = Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(b"missing"));
+        let leaf = view.put(&Value::Integer(1.into()));
+        let head = view.put(&Value::List(vec![
+            Value::Link(leaf.clone()),
+            Value::Link(leaf),
+            Value::Null,
+        ]));
+        let broken = view.put(&Value::List(vec![Value::Link(missing.clone())]));
+
+        view.actors = vec![
+            (
+                Address::new_id_addr(100).unwrap(),
+                new_actor(head.clone(), 70),
+            ),
+            (
+                Address::new_id_addr(101).unwrap(),
+                new_actor(head.clone(), 30),
+            ),
+        ];
+        let report = StateAuditor::new(&view)
+            .with_total_supply(BigInt::from(100))
+            .audit(&head, 1)
+            .unwrap();
+        assert!(report.is_ok(), "{:?}", report.violations);
+        assert_eq!(report.actors, 2);
+        assert_eq!(report.blocks, 2);
+
+        view.actors = vec![
+            (
+                Address::new_id_addr(100).unwrap(),
+                new_actor(head.clone(), 120),
+            ),
+            (Address::new_id_addr(101).unwrap(), new_actor(broken, -10)),
+        ];
+        let report = StateAuditor::new(&view)
+            .with_total_supply(BigInt::from(100))
+            .audit(&head, 1)
+            .unwrap();
+        assert_eq!(
+            report.violations,
+            vec![
+                Violation::NegativeBalance {
+                    address: Address::new_id_addr(101).unwrap(),
+                    balance: BigInt::from(-10),
+                },
+                Violation::MissingBlock {
+                    address: Address::new_id_addr(101).unwrap(),
+                    cid: missing,
+                },
+                Violation::SupplyMismatch {
+                    expected: BigInt::from(100),
+                    actual: BigInt::from(110),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::Result;
+use cid::Cid;
+use serde_json::Value as JsonValue;
+
+use ipld::Value;
+use plum_address::Address;
+use plum_types::Actor;
+
+use crate::view::StateView;
+
+/// The change of a field of an actor, `None` means the field is absent on that side.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldChange {
+    /// The path of the field, e.g. `Balance`, `State.LockedFunds` or `State[1].Key`.
+    pub path: String,
+    /// The value before the change.
+    pub before: Option<String>,
+    /// The value after the change.
+    pub after: Option<String>,
+}
+
+/// The change of an actor between two state trees.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ActorChange {
+    /// The actor only exists in the second state tree.
+    Added(Actor),
+    /// The actor only exists in the first state tree.
+    Removed(Actor),
+    /// The actor exists in both state trees but is changed.
+    Modified {
+        /// The actor in the first state tree.
+        before: Actor,
+        /// The actor in the second state tree.
+        after: Actor,
+        /// The changed fields, including the fields of the actor state.
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// The change of the actor with the address.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActorDiff {
+    /// The address of the actor.
+    pub address: Address,
+    /// The change of the actor.
+    pub change: ActorChange,
+}
+
+/// Compare the state trees of `root_a` and `root_b`, return the changed actors.
+///
+/// The state of the known actor types is compared field by field, the state of the other actors
+/// is compared as generic IPLD values of the head blocks.
+pub fn diff<V: StateView>(view: &V, root_a: &Cid, root_b: &Cid) -> Result<Vec<ActorDiff>> {
+    let mut diffs = vec![];
+    if root_a == root_b {
+        return Ok(diffs);
+    }
+
+    let actors_b = view.actors(root_b)?;
+    let mut index_b = actors_b
+        .iter()
+        .enumerate()
+        .map(|(index, (address, _))| (address.clone(), index))
+        .collect::<HashMap<_, _>>();
+
+    for (address, before) in view.actors(root_a)? {
+        let change = match index_b.remove(&address) {
+            Some(index) => {
+                let after = &actors_b[index].1;
+                if &before == after {
+                    continue;
+                }
+                let fields = diff_actor(view, &address, &before, after)?;
+                ActorChange::Modified {
+                    before,
+                    after: after.clone(),
+                    fields,
+                }
+            }
+            None => ActorChange::Removed(before),
+        };
+        diffs.push(ActorDiff { address, change });
+    }
+    for (address, after) in actors_b {
+        if index_b.contains_key(&address) {
+            diffs.push(ActorDiff {
+                address,
+                change: ActorChange::Added(after),
+            });
+        }
+    }
+
+    debug!(
+        "[diff] state {} -> {}: {} actors changed",
+        root_a,
+        root_b,
+        diffs.len()
+    );
+    Ok(diffs)
+}
+
+fn diff_actor<V: StateView>(
+    view: &V,
+    address: &Address,
+    before: &Actor,
+    after: &Actor,
+) -> Result<Vec<FieldChange>> {
+    let mut fields = vec![];
+    diff_field(&mut fields, "Code", &before.code, &after.code);
+    diff_field(&mut fields, "Head", &before.head, &after.head);
+    diff_field(&mut fields, "Nonce", &before.nonce, &after.nonce);
+    diff_field(&mut fields, "Balance", &before.balance, &after.balance);
+    if before.head == after.head {
+        return Ok(fields);
+    }
+
+    let states = (
+        view.miner_state(address, before)?,
+        view.miner_state(address, after)?,
+    );
+    if let (Some(state_a), Some(state_b)) = states {
+        let state_a = serde_json::to_value(&state_a)?;
+        let state_b = serde_json::to_value(&state_b)?;
+        diff_json(&mut fields, "State".into(), Some(&state_a), Some(&state_b));
+        return Ok(fields);
+    }
+
+    // Fallback to compare the head blocks as generic IPLD values.
+    let state_a = load_value(view, &before.head)?;
+    let state_b = load_value(view, &after.head)?;
+    diff_ipld(
+        &mut fields,
+        "State".into(),
+        state_a.as_ref(),
+        state_b.as_ref(),
+    );
+    Ok(fields)
+}
+
+fn load_value<V: StateView>(view: &V, cid: &Cid) -> Result<Option<Value>> {
+    match view.get_block(cid)? {
+        Some(data) => Ok(Some(minicbor::decode::<Value>(&data)?)),
+        None => Ok(None),
+    }
+}
+
+fn diff_field<T: PartialEq + ToString>(
+    fields: &mut Vec<FieldChange>,
+    path: &str,
+    before: &T,
+    after: &T,
+) {
+    if before != after {
+        fields.push(FieldChange {
+            path: path.into(),
+            before: Some(before.to_string()),
+            after: Some(after.to_string()),
+        });
+    }
+}
+
+fn diff_json(
+    fields: &mut Vec<FieldChange>,
+    path: String,
+    before: Option<&JsonValue>,
+    after: Option<&JsonValue>,
+) {
+    match (before, after) {
+        (Some(JsonValue::Object(a)), Some(JsonValue::Object(b))) => {
+            let keys = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                diff_json(fields, format!("{}.{}", path, key), a.get(key), b.get(key));
+            }
+        }
+        (Some(JsonValue::Array(a)), Some(JsonValue::Array(b))) => {
+            for index in 0..a.len().max(b.len()) {
+                diff_json(
+                    fields,
+                    format!("{}[{}]", path, index),
+                    a.get(index),
+                    b.get(index),
+                );
+            }
+        }
+        (a, b) if a != b => fields.push(FieldChange {
+            path,
+            before: a.map(JsonValue::to_string),
+            after: b.map(JsonValue::to_string),
+        }),
+        _ => {}
+    }
+}
+
+fn diff_ipld(
+    fields: &mut Vec<FieldChange>,
+    path: String,
+    before: Option<&Value>,
+    after: Option<&Value>,
+) {
+    match (before, after) {
+        (Some(Value::Map(a)), Some(Value::Map(b))) => {
+            let keys = a.keys().chain(b.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                diff_ipld(fields, format!("{}.{}", path, key), a.get(key), b.get(key));
+            }
+        }
+        (Some(Value::List(a)), Some(Value::List(b))) => {
+            for index in 0..a.len().max(b.len()) {
+                diff_ipld(
+                    fields,
+                    format!("{}[{}]", path, index),
+                    a.get(index),
+                    b.get(index),
+                );
+            }
+        }
+        (a, b) if a != b => fields.push(FieldChange {
+            path,
+            before: a.map(render_ipld),
+            after: b.map(render_ipld),
+        }),
+        _ => {}
+    }
+}
+
+// Render the IPLD value as DAG-JSON, the value that can't be represented in JSON
+// (e.g. the integer out of the safe range of JSON) is rendered in the debug format.
+fn render_ipld(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::Codec;
+    use ipld::ipld;
+    use multihash::Blake2b256;
+    use plum_actor::{miner, power};
+    use plum_bigint::BigInt;
+
+    #[derive(Default)]
+    struct MemoryView {
+        roots: HashMap<Cid, Vec<(Address, Actor)>>,
+        blocks: HashMap<Cid, Vec<u8>>,
+    }
+
+    impl MemoryView {
+        fn put(&mut self, value: &Value) -> Cid {
+            let data = minicbor::to_vec(value).unwrap();
+            let cid = Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(&data));
+            self.blocks.insert(cid.clone(), data);
+            cid
+        }
+    }
+
+    impl StateView for MemoryView {
+        fn actors(&self, root: &Cid) -> Result<Vec<(Address, Actor)>> {
+            Ok(self.roots[root].clone())
+        }
+
+        fn get_block(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+            Ok(self.blocks.get(cid).cloned())
+        }
+
+        fn miner_state(&self, _address: &Address, _actor: &Actor) -> Result<Option<miner::State>> {
+            Ok(None)
+        }
+
+        fn power_claim(&self, _root: &Cid, _miner: &Address) -> Result<Option<power::Claim>> {
+            Ok(None)
+        }
+
+        fn total_power(&self, _root: &Cid) -> Result<power::Claim> {
+            Ok(power::Claim {
+                raw_byte_power: BigInt::from(0),
+                quality_adj_power: BigInt::from(0),
+            })
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut view = MemoryView::default();
+        let head_a = view.put(&ipld!({"count": 1, "owners": [100, 101]}));
+        let head_b = view.put(&ipld!({"count": 2, "owners": [100], "locked": true}));
+        let actor = |head: &Cid, balance: u64| Actor {
+            code: head.clone(),
+            head: head.clone(),
+            nonce: 0,
+            balance: BigInt::from(balance),
+        };
+        let root_a = view.put(&ipld!("a"));
+        let root_b = view.put(&ipld!("b"));
+        let (unchanged, modified, removed, added) = (
+            Address::new_id_addr(100).unwrap(),
+            Address::new_id_addr(101).unwrap(),
+            Address::new_id_addr(102).unwrap(),
+            Address::new_id_addr(103).unwrap(),
+        );
+        view.roots.insert(
+            root_a.clone(),
+            vec![
+                (unchanged.clone(), actor(&head_a, 1)),
+                (modified.clone(), actor(&head_a, 1)),
+                (removed.clone(), actor(&head_a, 1)),
+            ],
+        );
+        view.roots.insert(
+            root_b.clone(),
+            vec![
+                (added.clone(), actor(&head_a, 1)),
+                (unchanged, actor(&head_a, 1)),
+                (modified.clone(), actor(&head_b, 1)),
+            ],
+        );
+
+        assert!(diff(&view, &root_a, &root_a).unwrap().is_empty());
+        let diffs = diff(&view, &root_a, &root_b).unwrap();
+        assert_eq!(diffs.len(), 3);
+        assert_eq!(diffs[0].address, modified);
+        match &diffs[0].change {
+            ActorChange::Modified { fields, .. } => {
+                let paths = fields.iter().map(|f| f.path.as_str()).collect::<Vec<_>>();
+                assert_eq!(
+                    paths,
+                    vec![
+                        "Code",
+                        "Head",
+                        "State.count",
+                        "State.locked",
+                        "State.owners[1]"
+                    ]
+                );
+                assert_eq!(fields[2].before.as_deref(), Some("1"));
+                assert_eq!(fields[2].after.as_deref(), Some("2"));
+                assert_eq!(fields[3].before, None);
+                assert_eq!(fields[4].after, None);
+            }
+            change => panic!("unexpected change: {:?}", change),
+        }
+        assert_eq!(diffs[1].address, removed);
+        assert!(matches!(diffs[1].change, ActorChange::Removed(_)));
+        assert_eq!(diffs[2].address, added);
+        assert!(matches!(diffs[2].change, ActorChange::Added(_)));
+    }
+}
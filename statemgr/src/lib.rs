@@ -0,0 +1,42 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The state manager and the tools inspecting the state trees.
+
+#![deny(missing_docs)]
+
+#[macro_use]
+extern crate log;
+
+mod audit;
+mod diff;
+mod manager;
+mod migration;
+mod miner;
+mod randomness;
+mod replay;
+mod sigverify;
+#[cfg(test)]
+mod test_utils;
+mod view;
+
+pub use self::audit::{AuditReport, StateAuditor, Violation};
+pub use self::diff::{diff, ActorChange, ActorDiff, FieldChange};
+pub use self::manager::{StateManager, TipsetExecutor, DEFAULT_STATE_CACHE_SIZE};
+pub use self::migration::{
+    ActorMigration, CodeMigration, MigrationInput, MigrationProgress, MigrationStore,
+    StateMigration, UpgradeSchedule, DEFAULT_PROGRESS_INTERVAL,
+};
+pub use self::miner::{
+    miner_faults, miner_power, miner_proving_deadline, miner_recoveries, miner_state, MinerPower,
+};
+pub use self::randomness::{
+    state_get_randomness_from_beacon, state_get_randomness_from_tickets, LookbackParams,
+    BEACON_ENTRY_SEARCH_DEPTH,
+};
+pub use self::replay::{
+    BlockStoreReplayLoader, Divergence, ReceiptField, ReplayLoader, ReplayReport, Replayer,
+};
+pub use self::sigverify::{
+    verify_block_signatures, verify_tipset_signatures, SignatureLoader, SignatureVerifyingExecutor,
+};
+pub use self::view::{BlockStoreView, StateView};
@@ -0,0 +1,259 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{ensure, Result};
+use cid::Cid;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+use ipfs_datastore::{DataStore, Key};
+use plum_chain::{TipsetCache, TipsetLoader};
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+/// The default number of the tipset states kept in memory.
+pub const DEFAULT_STATE_CACHE_SIZE: usize = 1024;
+
+/// The executor of the messages of the tipsets, which is usually backed by the VM.
+pub trait TipsetExecutor {
+    /// Apply the messages of the tipset on its parent state,
+    /// return the root of the post-state and the root of the receipts.
+    fn execute_tipset(&self, tipset: &Tipset) -> Result<(Cid, Cid)>;
+}
+
+/// The manager of the states after the tipsets.
+///
+/// Computing the state after a tipset requires executing all of its messages, so the result is
+/// memoized in memory and persisted into the datastore under `/statemgr/tipset-state/<key>`.
+/// Concurrent queries of the same tipset wait for a single computation.
+pub struct StateManager<E, DS> {
+    executor: E,
+    datastore: DS,
+    namespace: Key,
+    cache: Mutex<LruCache<TipsetKey, (Cid, Cid)>>,
+    computing: Mutex<HashMap<TipsetKey, Arc<Mutex<()>>>>,
+}
+
+impl<E, DS> StateManager<E, DS>
+where
+    E: TipsetExecutor,
+    DS: DataStore,
+{
+    /// Create a new state manager with the default cache size.
+    pub fn new(executor: E, datastore: DS) -> Self {
+        Self::with_capacity(executor, datastore, DEFAULT_STATE_CACHE_SIZE)
+    }
+
+    /// Create a new state manager with the given cache size.
+    pub fn with_capacity(executor: E, datastore: DS, size: usize) -> Self {
+        Self {
+            executor,
+            datastore,
+            namespace: Key::with_namespaces(vec!["statemgr", "tipset-state"]),
+            cache: Mutex::new(LruCache::new(size)),
+            computing: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Return the root of the state and the root of the receipts after the tipset.
+    ///
+    /// The genesis has no message to execute, so its own parent state and receipts are returned.
+    pub fn tipset_state(&self, tipset: &Tipset) -> Result<(Cid, Cid)> {
+        if tipset.height() == 0 {
+            let genesis = &tipset.blocks()[0];
+            return Ok((
+                genesis.parent_state_root.clone(),
+                genesis.parent_message_receipts.clone(),
+            ));
+        }
+        if let Some(state) = self.cached_state(tipset.key())? {
+            return Ok(state);
+        }
+
+        let lock = self
+            .computing
+            .lock()
+            .entry(tipset.key().clone())
+            .or_default()
+            .clone();
+        let result = {
+            let _guard = lock.lock();
+            // The state may be computed while waiting for the lock.
+            match self.cached_state(tipset.key()) {
+                Ok(Some(state)) => Ok(state),
+                Ok(None) => self.compute_state(tipset),
+                Err(err) => Err(err),
+            }
+        };
+        self.computing.lock().remove(tipset.key());
+        result
+    }
+
+    /// Return the lookback tipset of `round` in the chain of `tipset`, i.e. the last non-null
+    /// tipset at or before `round - lookback`, along with the state after it.
+    ///
+    /// The state is the parent state of the next non-null tipset, so it's not computed unless
+    /// the lookback tipset is `tipset` itself. It's the same as GetLookbackTipSetForRound of
+    /// Lotus, e.g. the sector set of the winning PoSt is sampled from the state with the
+    /// `winning_post_lookback` of `LookbackParams`.
+    pub fn get_lookback_tipset_for_round<L>(
+        &self,
+        cache: &TipsetCache<L>,
+        tipset: &Arc<Tipset>,
+        round: ChainEpoch,
+        lookback: ChainEpoch,
+    ) -> Result<(Arc<Tipset>, Cid)>
+    where
+        L: TipsetLoader,
+    {
+        let lookback_round = (round - lookback).max(0);
+        if lookback_round >= tipset.height() {
+            let (state, _) = self.tipset_state(tipset)?;
+            return Ok((tipset.clone(), state));
+        }
+
+        let next = cache.tipset_by_height(lookback_round + 1, tipset, true)?;
+        ensure!(
+            next.height() > lookback_round,
+            "failed to find non-null tipset after {} which is known to exist, found {} ({})",
+            lookback_round,
+            next.key(),
+            next.height()
+        );
+        let lookback_tipset = cache.tipset_by_height(lookback_round, &next, false)?;
+        Ok((lookback_tipset, next.parent_state().clone()))
+    }
+
+    /// Return true if the state after the tipset is already computed.
+    pub fn has_tipset_state(&self, key: &TipsetKey) -> Result<bool> {
+        Ok(self.cache.lock().contains(key) || self.datastore.has(&self.state_key(key))?)
+    }
+
+    fn cached_state(&self, key: &TipsetKey) -> Result<Option<(Cid, Cid)>> {
+        if let Some(state) = self.cache.lock().get(key) {
+            return Ok(Some(state.clone()));
+        }
+        match self.datastore.get(&self.state_key(key))? {
+            Some(data) => {
+                let roots = minicbor::decode::<Vec<Cid>>(&data)?;
+                ensure!(roots.len() == 2, "malformed tipset state of {}", key);
+                let state = (roots[0].clone(), roots[1].clone());
+                self.cache.lock().put(key.clone(), state.clone());
+                Ok(Some(state))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn compute_state(&self, tipset: &Tipset) -> Result<(Cid, Cid)> {
+        debug!(
+            "[statemgr] computing the state of tipset {} at height {}",
+            tipset.key(),
+            tipset.height()
+        );
+        let (state_root, receipts_root) = self.executor.execute_tipset(tipset)?;
+        let data = minicbor::to_vec(vec![state_root.clone(), receipts_root.clone()])
+            .expect("CBOR serialization of tipset state shouldn't fail; qed");
+        self.datastore.put(self.state_key(tipset.key()), data)?;
+        let state = (state_root, receipts_root);
+        self.cache.lock().put(tipset.key().clone(), state.clone());
+        Ok(state)
+    }
+
+    fn state_key(&self, key: &TipsetKey) -> Key {
+        let cids = key
+            .cids()
+            .iter()
+            .map(|cid| cid.to_string())
+            .collect::<Vec<_>>();
+        self.namespace.child(Key::new(cids.join(",")))
+    }
+}
+
+/// The state manager executes a tipset by computing the state after it, so that the computed
+/// states can be replayed against the chain, see `Replayer`.
+impl<E, DS> TipsetExecutor for StateManager<E, DS>
+where
+    E: TipsetExecutor,
+    DS: DataStore,
+{
+    fn execute_tipset(&self, tipset: &Tipset) -> Result<(Cid, Cid)> {
+        self.tipset_state(tipset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use plum_block::BlockHeader;
+
+    use crate::test_utils::new_header;
+
+    fn cid(s: &str) -> Cid {
+        s.parse().unwrap()
+    }
+
+    const STATE: &str = "bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i";
+    const RECEIPTS: &str = "bafy2bzacecesrkxghscnq7vatble2hqdvwat6ed23vdu4vvo3uuggsoaya7ki";
+
+    #[derive(Default)]
+    struct CountingExecutor(AtomicUsize);
+
+    impl TipsetExecutor for &CountingExecutor {
+        fn execute_tipset(&self, _tipset: &Tipset) -> Result<(Cid, Cid)> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok((cid(RECEIPTS), cid(STATE)))
+        }
+    }
+
+    fn new_tipset(height: i64) -> Tipset {
+        let header = BlockHeader {
+            parent_message_receipts: cid(RECEIPTS),
+            ..new_header(vec![], height, 1000)
+        };
+        Tipset::new(vec![header]).unwrap()
+    }
+
+    #[test]
+    fn test_tipset_state() {
+        let executor = CountingExecutor::default();
+        let datastore = SyncDataStore::new(MapDataStore::new());
+
+        let genesis = new_tipset(0);
+        let tipset = new_tipset(1);
+        let manager = StateManager::new(&executor, datastore.clone());
+        assert_eq!(
+            manager.tipset_state(&genesis).unwrap(),
+            (cid(STATE), cid(RECEIPTS))
+        );
+        assert_eq!(executor.0.load(Ordering::SeqCst), 0);
+
+        assert!(!manager.has_tipset_state(tipset.key()).unwrap());
+        for _ in 0..3 {
+            assert_eq!(
+                manager.tipset_state(&tipset).unwrap(),
+                (cid(RECEIPTS), cid(STATE))
+            );
+        }
+        assert_eq!(executor.0.load(Ordering::SeqCst), 1);
+        assert!(manager.has_tipset_state(tipset.key()).unwrap());
+
+        // the state is loaded from the datastore after restarting.
+        let manager = StateManager::new(&executor, datastore);
+        assert_eq!(
+            manager.tipset_state(&tipset).unwrap(),
+            (cid(RECEIPTS), cid(STATE))
+        );
+        assert_eq!(
+            manager.execute_tipset(&tipset).unwrap(),
+            (cid(RECEIPTS), cid(STATE))
+        );
+        assert_eq!(executor.0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,552 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use cid::Cid;
+use rayon::prelude::*;
+
+use plum_address::Address;
+use plum_types::{Actor, ChainEpoch};
+
+use crate::view::StateView;
+
+/// The default number of the migrated actors between two progress reports.
+pub const DEFAULT_PROGRESS_INTERVAL: usize = 10_000;
+
+/// The access to the state trees needed by the migrations, the migrated actor states are
+/// written to the same store.
+pub trait MigrationStore: StateView + Sync {
+    /// Write the raw DAG-CBOR block, return the CID of the block.
+    fn put_block(&self, data: Vec<u8>) -> Result<Cid>;
+
+    /// Write the state tree of the `actors`, return the root of the state tree.
+    fn put_actors(&self, actors: Vec<(Address, Actor)>) -> Result<Cid>;
+}
+
+/// The actor being migrated.
+#[derive(Clone, Copy, Debug)]
+pub struct MigrationInput<'a> {
+    /// The address of the actor.
+    pub address: &'a Address,
+    /// The actor before the migration.
+    pub actor: &'a Actor,
+    /// The upgrade epoch of the migration.
+    pub epoch: ChainEpoch,
+}
+
+/// The migration of the actors of a code from an actors version to the next one.
+pub trait ActorMigration<S>: Send + Sync {
+    /// Return the code of the actors after the migration.
+    fn migrated_code(&self) -> Cid;
+
+    /// Migrate the state of the actor, return the head of the migrated state, which has been
+    /// written to the `store`. The nonce and the balance of the actor are kept.
+    fn migrate_state(&self, store: &S, input: MigrationInput<'_>) -> Result<Cid>;
+}
+
+/// The migration of the actors whose state layout is unchanged, only the code is replaced.
+#[derive(Clone, Debug)]
+pub struct CodeMigration(pub Cid);
+
+impl<S> ActorMigration<S> for CodeMigration {
+    fn migrated_code(&self) -> Cid {
+        self.0.clone()
+    }
+
+    fn migrate_state(&self, _store: &S, input: MigrationInput<'_>) -> Result<Cid> {
+        Ok(input.actor.head.clone())
+    }
+}
+
+/// The progress of a running migration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MigrationProgress {
+    /// The number of the migrated actors.
+    pub migrated: usize,
+    /// The number of all the actors in the state tree.
+    pub total: usize,
+    /// The time elapsed since the start of the migration.
+    pub elapsed: Duration,
+}
+
+type ProgressCallback = Box<dyn Fn(&MigrationProgress) + Send + Sync>;
+
+/// The migration of the state tree from an actors version to the next one.
+///
+/// Every actor in the state tree is migrated by the migration registered for its code, the
+/// actors are migrated in parallel, and the migrated state tree is verified against the
+/// original one before being returned.
+pub struct StateMigration<S> {
+    name: String,
+    migrations: HashMap<Cid, Box<dyn ActorMigration<S>>>,
+    workers: usize,
+    progress_interval: usize,
+    progress: Option<ProgressCallback>,
+}
+
+impl<S> StateMigration<S>
+where
+    S: MigrationStore,
+{
+    /// Create a migration without any actor migration, the `name` is used by the logs.
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            migrations: HashMap::new(),
+            workers: 0,
+            progress_interval: DEFAULT_PROGRESS_INTERVAL,
+            progress: None,
+        }
+    }
+
+    /// Register the migration of the actors of the `code`.
+    pub fn with_migration<M>(mut self, code: Cid, migration: M) -> Self
+    where
+        M: ActorMigration<S> + 'static,
+    {
+        self.migrations.insert(code, Box::new(migration));
+        self
+    }
+
+    /// Set the number of the worker threads, `0` means the number of the CPUs.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers;
+        self
+    }
+
+    /// Report the progress to the `callback` every `interval` migrated actors and when all the
+    /// actors are migrated. The progress is logged in any case.
+    pub fn with_progress<F>(mut self, interval: usize, callback: F) -> Self
+    where
+        F: Fn(&MigrationProgress) + Send + Sync + 'static,
+    {
+        self.progress_interval = interval.max(1);
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Return the name of the migration.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Migrate the state tree of the `root` at the upgrade `epoch`, return the root of the
+    /// migrated state tree.
+    ///
+    /// The migration fails if any actor has no registered migration, so an upgrade never
+    /// leaves the actors of the previous version behind.
+    pub fn migrate(&self, store: &S, root: &Cid, epoch: ChainEpoch) -> Result<Cid> {
+        let actors = store.actors(root)?;
+        let total = actors.len();
+        info!(
+            "Migration {}: migrating {} actors of state {} at epoch {}",
+            self.name, total, root, epoch
+        );
+
+        let start = Instant::now();
+        let migrated = AtomicUsize::new(0);
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(self.workers)
+            .build()?;
+        let migrated_actors = pool.install(|| {
+            actors
+                .par_iter()
+                .map(|(address, actor)| {
+                    let migrated_actor = self.migrate_actor(store, address, actor, epoch)?;
+                    let count = migrated.fetch_add(1, Ordering::Relaxed) + 1;
+                    if count % self.progress_interval == 0 || count == total {
+                        self.report(&MigrationProgress {
+                            migrated: count,
+                            total,
+                            elapsed: start.elapsed(),
+                        });
+                    }
+                    Ok((address.clone(), migrated_actor))
+                })
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let new_root = store.put_actors(migrated_actors)?;
+        self.verify(store, &actors, &new_root)
+            .with_context(|| format!("migration {} failed verification", self.name))?;
+        info!(
+            "Migration {}: migrated state {} to {} in {:?}",
+            self.name,
+            root,
+            new_root,
+            start.elapsed()
+        );
+        Ok(new_root)
+    }
+
+    /// Verify the migrated state tree of the `new_root` against the original `actors`: every
+    /// actor is kept with its nonce and balance, its code is the migrated code and its head
+    /// is in the store.
+    pub fn verify(&self, store: &S, actors: &[(Address, Actor)], new_root: &Cid) -> Result<()> {
+        let migrated_actors = store.actors(new_root)?;
+        ensure!(
+            migrated_actors.len() == actors.len(),
+            "the number of actors changed from {} to {}",
+            actors.len(),
+            migrated_actors.len()
+        );
+        for ((address, actor), (new_address, new_actor)) in actors.iter().zip(&migrated_actors) {
+            ensure!(
+                address == new_address,
+                "the actor {} is replaced by {}",
+                address,
+                new_address
+            );
+            ensure!(
+                actor.nonce == new_actor.nonce && actor.balance == new_actor.balance,
+                "the nonce or the balance of the actor {} changed",
+                address
+            );
+            let expected_code = self.actor_migration(address, actor)?.migrated_code();
+            ensure!(
+                new_actor.code == expected_code,
+                "the code of the actor {} is {}, expected {}",
+                address,
+                new_actor.code,
+                expected_code
+            );
+            if store.get_block(&new_actor.head)?.is_none() {
+                bail!(
+                    "the head {} of the actor {} is missing",
+                    new_actor.head,
+                    address
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn migrate_actor(
+        &self,
+        store: &S,
+        address: &Address,
+        actor: &Actor,
+        epoch: ChainEpoch,
+    ) -> Result<Actor> {
+        let migration = self.actor_migration(address, actor)?;
+        let input = MigrationInput {
+            address,
+            actor,
+            epoch,
+        };
+        let head = migration
+            .migrate_state(store, input)
+            .with_context(|| format!("failed to migrate the state of the actor {}", address))?;
+        Ok(Actor {
+            code: migration.migrated_code(),
+            head,
+            nonce: actor.nonce,
+            balance: actor.balance.clone(),
+        })
+    }
+
+    fn actor_migration(&self, address: &Address, actor: &Actor) -> Result<&dyn ActorMigration<S>> {
+        self.migrations
+            .get(&actor.code)
+            .map(|migration| migration.as_ref())
+            .ok_or_else(|| {
+                anyhow!(
+                    "no migration for the code {} of the actor {}",
+                    actor.code,
+                    address
+                )
+            })
+    }
+
+    fn report(&self, progress: &MigrationProgress) {
+        info!(
+            "Migration {}: migrated {}/{} actors in {:?}",
+            self.name, progress.migrated, progress.total, progress.elapsed
+        );
+        if let Some(callback) = &self.progress {
+            callback(progress);
+        }
+    }
+}
+
+/// The state migrations at the upgrade epochs of the network.
+pub struct UpgradeSchedule<S> {
+    // Ordered by the upgrade epoch.
+    upgrades: Vec<(ChainEpoch, StateMigration<S>)>,
+}
+
+impl<S> Default for UpgradeSchedule<S> {
+    fn default() -> Self {
+        Self { upgrades: vec![] }
+    }
+}
+
+impl<S> UpgradeSchedule<S>
+where
+    S: MigrationStore,
+{
+    /// Create an empty schedule.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedule the `migration` at the upgrade `epoch`, at most one migration is scheduled
+    /// at an epoch.
+    pub fn add(&mut self, epoch: ChainEpoch, migration: StateMigration<S>) -> Result<()> {
+        match self
+            .upgrades
+            .binary_search_by_key(&epoch, |(epoch, _)| *epoch)
+        {
+            Ok(_) => bail!("an upgrade is already scheduled at epoch {}", epoch),
+            Err(index) => self.upgrades.insert(index, (epoch, migration)),
+        }
+        Ok(())
+    }
+
+    /// Return the migration at the upgrade `epoch`.
+    pub fn migration_at(&self, epoch: ChainEpoch) -> Option<&StateMigration<S>> {
+        self.upgrades
+            .binary_search_by_key(&epoch, |(epoch, _)| *epoch)
+            .ok()
+            .map(|index| &self.upgrades[index].1)
+    }
+
+    /// Run the migrations of the upgrade epochs in `[parent_epoch, epoch)` in order on the
+    /// parent state `root` of a tipset at `epoch` whose parent is at `parent_epoch`, return
+    /// the root of the migrated state tree.
+    ///
+    /// The upgrades at the null rounds between the parent and the tipset are run as well,
+    /// which is the same as the state forks of Lotus.
+    pub fn run_upgrades(
+        &self,
+        store: &S,
+        root: &Cid,
+        parent_epoch: ChainEpoch,
+        epoch: ChainEpoch,
+    ) -> Result<Cid> {
+        let mut root = root.clone();
+        for (upgrade_epoch, migration) in self
+            .upgrades
+            .iter()
+            .filter(|(upgrade_epoch, _)| (parent_epoch..epoch).contains(upgrade_epoch))
+        {
+            root = migration.migrate(store, &root, *upgrade_epoch)?;
+        }
+        Ok(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::AtomicBool;
+    use std::sync::Arc;
+
+    use cid::Codec;
+    use multihash::Blake2b256;
+    use parking_lot::Mutex;
+
+    use ipld::Value;
+    use plum_actor::{miner, power};
+    use plum_bigint::BigInt;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        roots: Mutex<HashMap<Cid, Vec<(Address, Actor)>>>,
+        blocks: Mutex<HashMap<Cid, Vec<u8>>>,
+        // Drop the last actor when writing the state tree, which fails the verification.
+        lossy: AtomicBool,
+    }
+
+    impl MemoryStore {
+        fn put(&self, value: &Value) -> Cid {
+            self.put_block(minicbor::to_vec(value).unwrap()).unwrap()
+        }
+    }
+
+    impl StateView for MemoryStore {
+        fn actors(&self, root: &Cid) -> Result<Vec<(Address, Actor)>> {
+            self.roots
+                .lock()
+                .get(root)
+                .cloned()
+                .ok_or_else(|| anyhow!("state {} not found", root))
+        }
+
+        fn get_block(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+            Ok(self.blocks.lock().get(cid).cloned())
+        }
+
+        fn miner_state(&self, _address: &Address, _actor: &Actor) -> Result<Option<miner::State>> {
+            Ok(None)
+        }
+
+        fn power_claim(&self, _root: &Cid, _miner: &Address) -> Result<Option<power::Claim>> {
+            Ok(None)
+        }
+
+        fn total_power(&self, _root: &Cid) -> Result<power::Claim> {
+            Ok(power::Claim {
+                raw_byte_power: BigInt::from(0),
+                quality_adj_power: BigInt::from(0),
+            })
+        }
+    }
+
+    impl MigrationStore for MemoryStore {
+        fn put_block(&self, data: Vec<u8>) -> Result<Cid> {
+            let cid = Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(&data));
+            self.blocks.lock().insert(cid.clone(), data);
+            Ok(cid)
+        }
+
+        fn put_actors(&self, mut actors: Vec<(Address, Actor)>) -> Result<Cid> {
+            if self.lossy.load(Ordering::Relaxed) {
+                actors.pop();
+            }
+            let addresses = actors
+                .iter()
+                .map(|(address, _)| Value::Bytes(address.as_bytes().to_vec().into()))
+                .collect();
+            let root = self.put(&Value::List(addresses));
+            self.roots.lock().insert(root.clone(), actors);
+            Ok(root)
+        }
+    }
+
+    // Wrap the old state into a list, the new state links to the old one.
+    struct WrapMigration(Cid);
+
+    impl ActorMigration<MemoryStore> for WrapMigration {
+        fn migrated_code(&self) -> Cid {
+            self.0.clone()
+        }
+
+        fn migrate_state(&self, store: &MemoryStore, input: MigrationInput<'_>) -> Result<Cid> {
+            ensure!(
+                store.get_block(&input.actor.head)?.is_some(),
+                "state not found"
+            );
+            Ok(store.put(&Value::List(vec![Value::Link(input.actor.head.clone())])))
+        }
+    }
+
+    fn code(name: &str) -> Cid {
+        Cid::new_v1(Codec::Raw, Blake2b256::digest(name.as_bytes()))
+    }
+
+    fn new_state(store: &MemoryStore, count: u64) -> (Cid, Vec<(Address, Actor)>) {
+        let actors = (0..count)
+            .map(|id| {
+                let actor = Actor {
+                    code: if id % 2 == 0 {
+                        code("fil/1/account")
+                    } else {
+                        code("fil/1/storageminer")
+                    },
+                    head: store.put(&Value::Integer(id.into())),
+                    nonce: id,
+                    balance: BigInt::from(id * 100),
+                };
+                (Address::new_id_addr(id).unwrap(), actor)
+            })
+            .collect::<Vec<_>>();
+        (store.put_actors(actors.clone()).unwrap(), actors)
+    }
+
+    fn new_migration(name: &str, from: &str, to: &str) -> StateMigration<MemoryStore> {
+        StateMigration::new(name)
+            .with_migration(
+                code(&format!("fil/{}/account", from)),
+                CodeMigration(code(&format!("fil/{}/account", to))),
+            )
+            .with_migration(
+                code(&format!("fil/{}/storageminer", from)),
+                WrapMigration(code(&format!("fil/{}/storageminer", to))),
+            )
+    }
+
+    #[test]
+    fn test_migrate() {
+        let store = MemoryStore::default();
+        let (root, actors) = new_state(&store, 100);
+
+        let reports = Arc::new(Mutex::new(vec![]));
+        let migration = {
+            let reports = reports.clone();
+            new_migration("v2", "1", "2")
+                .with_workers(4)
+                .with_progress(30, move |progress| reports.lock().push(progress.migrated))
+        };
+        let new_root = migration.migrate(&store, &root, 1000).unwrap();
+        let mut reports = reports.lock().clone();
+        reports.sort();
+        assert_eq!(reports, vec![30, 60, 90, 100]);
+
+        let migrated = store.actors(&new_root).unwrap();
+        for ((address, actor), (new_address, new_actor)) in actors.iter().zip(&migrated) {
+            assert_eq!(address, new_address);
+            assert_eq!(actor.nonce, new_actor.nonce);
+            assert_eq!(actor.balance, new_actor.balance);
+            if actor.code == code("fil/1/account") {
+                assert_eq!(new_actor.code, code("fil/2/account"));
+                assert_eq!(new_actor.head, actor.head);
+            } else {
+                assert_eq!(new_actor.code, code("fil/2/storageminer"));
+                let data = store.get_block(&new_actor.head).unwrap().unwrap();
+                assert_eq!(
+                    minicbor::decode::<Value>(&data).unwrap(),
+                    Value::List(vec![Value::Link(actor.head.clone())])
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_migrate_failure() {
+        let store = MemoryStore::default();
+        let (root, _) = new_state(&store, 10);
+        // The actors of the storage miner code are not migrated.
+        let migration = StateMigration::<MemoryStore>::new("v2")
+            .with_migration(code("fil/1/account"), CodeMigration(code("fil/2/account")));
+        assert!(migration.migrate(&store, &root, 1000).is_err());
+
+        let migration = new_migration("v2", "1", "2");
+        store.lossy.store(true, Ordering::Relaxed);
+        assert!(migration.migrate(&store, &root, 1000).is_err());
+        store.lossy.store(false, Ordering::Relaxed);
+        assert!(migration.migrate(&store, &root, 1000).is_ok());
+    }
+
+    #[test]
+    fn test_upgrade_schedule() {
+        let store = MemoryStore::default();
+        let (root, _) = new_state(&store, 10);
+        let mut schedule = UpgradeSchedule::new();
+        schedule.add(200, new_migration("v3", "2", "3")).unwrap();
+        schedule.add(100, new_migration("v2", "1", "2")).unwrap();
+        assert!(schedule.add(100, new_migration("v2", "1", "2")).is_err());
+        assert_eq!(schedule.migration_at(200).unwrap().name(), "v3");
+        assert!(schedule.migration_at(150).is_none());
+
+        // No upgrade before the tipset.
+        assert_eq!(schedule.run_upgrades(&store, &root, 98, 100).unwrap(), root);
+        // Both upgrades are run, the upgrade at 200 is at a null round.
+        let new_root = schedule.run_upgrades(&store, &root, 100, 201).unwrap();
+        let codes = store
+            .actors(&new_root)
+            .unwrap()
+            .into_iter()
+            .map(|(_, actor)| actor.code)
+            .collect::<Vec<_>>();
+        assert_eq!(codes[0], code("fil/3/account"));
+        assert_eq!(codes[1], code("fil/3/storageminer"));
+        // The state of the actors v2 can't be migrated by the migration of the actors v1.
+        let v2_root = schedule.run_upgrades(&store, &root, 100, 101).unwrap();
+        assert!(schedule.run_upgrades(&store, &v2_root, 100, 101).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The queries of the miner state, which back the `StateMiner*` RPC methods.
+
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use serde::{Deserialize, Serialize};
+
+use plum_actor::{miner, power};
+use plum_address::Address;
+use plum_bitfield::BitField;
+use plum_types::ChainEpoch;
+
+use crate::view::StateView;
+
+/// The power of the miner and the total power of the network.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MinerPower {
+    /// The power claimed by the miner.
+    pub miner_power: power::Claim,
+    /// The power claimed by all the miners.
+    pub total_power: power::Claim,
+}
+
+/// Return the state of the miner actor in the state tree of the `root`.
+///
+/// An error is returned if the actor is missing or it's not a miner actor.
+pub fn miner_state<V>(view: &V, root: &Cid, addr: &Address) -> Result<miner::State>
+where
+    V: StateView + ?Sized,
+{
+    let actors = view.actors(root)?;
+    let (address, actor) = actors
+        .iter()
+        .find(|(address, _)| address == addr)
+        .ok_or_else(|| anyhow!("actor {} not found in state {}", addr, root))?;
+    view.miner_state(address, actor)?
+        .ok_or_else(|| anyhow!("actor {} is not a miner", addr))
+}
+
+/// Return the info of the current proving deadline of the miner at the `epoch`.
+pub fn miner_proving_deadline<V>(
+    view: &V,
+    root: &Cid,
+    addr: &Address,
+    epoch: ChainEpoch,
+) -> Result<miner::DeadlineInfo>
+where
+    V: StateView + ?Sized,
+{
+    Ok(miner_state(view, root, addr)?.deadline_info(epoch))
+}
+
+/// Return the power of the miner along with the total power.
+///
+/// The power of a miner without any claim is zero, the same as Lotus.
+pub fn miner_power<V>(view: &V, root: &Cid, addr: &Address) -> Result<MinerPower>
+where
+    V: StateView + ?Sized,
+{
+    let miner_power = view
+        .power_claim(root, addr)?
+        .unwrap_or_else(|| power::Claim {
+            raw_byte_power: Default::default(),
+            quality_adj_power: Default::default(),
+        });
+    let total_power = view.total_power(root)?;
+    Ok(MinerPower {
+        miner_power,
+        total_power,
+    })
+}
+
+/// Return the faulty sectors of the miner.
+pub fn miner_faults<V>(view: &V, root: &Cid, addr: &Address) -> Result<BitField>
+where
+    V: StateView + ?Sized,
+{
+    Ok(miner_state(view, root, addr)?.faults)
+}
+
+/// Return the faulty sectors of the miner which are declared to be recovered.
+pub fn miner_recoveries<V>(view: &V, root: &Cid, addr: &Address) -> Result<BitField>
+where
+    V: StateView + ?Sized,
+{
+    Ok(miner_state(view, root, addr)?.recoveries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::Codec;
+    use multihash::Blake2b256;
+    use plum_actor::codes;
+    use plum_bigint::BigInt;
+    use plum_peerid::PeerId;
+    use plum_sector::RegisteredSealProof;
+    use plum_types::Actor;
+
+    struct MinerView {
+        miner: Address,
+        state: miner::State,
+        claim: power::Claim,
+    }
+
+    impl StateView for MinerView {
+        fn actors(&self, _root: &Cid) -> Result<Vec<(Address, Actor)>> {
+            let head = new_cid(b"miner");
+            Ok(vec![(
+                self.miner.clone(),
+                Actor {
+                    code: codes::STORAGE_MINER_ACTOR_CODE_ID.clone(),
+                    head,
+                    nonce: 0,
+                    balance: BigInt::from(0),
+                },
+            )])
+        }
+
+        fn get_block(&self, _cid: &Cid) -> Result<Option<Vec<u8>>> {
+            Ok(None)
+        }
+
+        fn miner_state(&self, _address: &Address, actor: &Actor) -> Result<Option<miner::State>> {
+            if actor.code == *codes::STORAGE_MINER_ACTOR_CODE_ID {
+                Ok(Some(self.state.clone()))
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn power_claim(&self, _root: &Cid, miner: &Address) -> Result<Option<power::Claim>> {
+            if *miner == self.miner {
+                Ok(Some(self.claim.clone()))
+            } else {
+                Ok(None)
+            }
+        }
+
+        fn total_power(&self, _root: &Cid) -> Result<power::Claim> {
+            Ok(power::Claim {
+                raw_byte_power: BigInt::from(4096),
+                quality_adj_power: BigInt::from(8192),
+            })
+        }
+    }
+
+    fn new_cid(data: &[u8]) -> Cid {
+        Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(data))
+    }
+
+    fn new_view() -> MinerView {
+        let miner = Address::new_id_addr(1000).unwrap();
+        let worker = Address::new_id_addr(100).unwrap();
+        let empty = new_cid(b"empty");
+        let state = miner::State {
+            info: miner::MinerInfo {
+                owner: worker.clone(),
+                worker: worker.clone(),
+                pending_worker_key: miner::WorkerKeyChange {
+                    new_worker: worker,
+                    effective_at: 0,
+                },
+                peer_id: PeerId::random(),
+                seal_proof_type: RegisteredSealProof::StackedDrg2KiBV1,
+                sector_size: 2048,
+                window_post_partition_sectors: 2,
+            },
+            pre_commit_deposits: BigInt::from(0),
+            locked_funds: BigInt::from(0),
+            vesting_funds: empty.clone(),
+            pre_committed_sectors: empty.clone(),
+            sectors: empty.clone(),
+            proving_period_start: 100,
+            new_sectors: BitField::new(),
+            sector_expirations: empty.clone(),
+            deadlines: empty.clone(),
+            faults: BitField::from(vec![1, 2, 5]),
+            fault_epochs: empty,
+            recoveries: BitField::from(vec![2]),
+            post_submissions: BitField::new(),
+        };
+        let claim = power::Claim {
+            raw_byte_power: BigInt::from(2048),
+            quality_adj_power: BigInt::from(2048),
+        };
+        MinerView {
+            miner,
+            state,
+            claim,
+        }
+    }
+
+    #[test]
+    fn test_miner_queries() {
+        let view = new_view();
+        let root = new_cid(b"root");
+        let miner = view.miner.clone();
+
+        let deadline = miner_proving_deadline(&view, &root, &miner, 150).unwrap();
+        assert_eq!(deadline, view.state.deadline_info(150));
+        assert_eq!(deadline.period_start, 100);
+
+        let power = miner_power(&view, &root, &miner).unwrap();
+        assert_eq!(power.miner_power, view.claim);
+        assert_eq!(power.total_power.raw_byte_power, BigInt::from(4096));
+        let other = Address::new_id_addr(1001).unwrap();
+        let power = miner_power(&view, &root, &other).unwrap();
+        assert_eq!(power.miner_power.raw_byte_power, BigInt::from(0));
+
+        assert_eq!(
+            miner_faults(&view, &root, &miner).unwrap(),
+            BitField::from(vec![1, 2, 5])
+        );
+        assert_eq!(
+            miner_recoveries(&view, &root, &miner).unwrap(),
+            BitField::from(vec![2])
+        );
+        assert!(miner_faults(&view, &root, &other).is_err());
+    }
+}
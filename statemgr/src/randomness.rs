@@ -0,0 +1,269 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, ensure, Result};
+
+use plum_chain::{draw_randomness, TipsetCache, TipsetLoader};
+use plum_crypto::DomainSeparationTag;
+use plum_tipset::Tipset;
+use plum_types::ChainEpoch;
+
+/// The number of the tipsets searched for the latest beacon entry, the same as Lotus.
+pub const BEACON_ENTRY_SEARCH_DEPTH: usize = 20;
+
+/// The lookbacks of the chain randomness used by the proofs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LookbackParams {
+    /// The lookback of the sector set of the winning PoSt, i.e. the finality.
+    pub winning_post_lookback: ChainEpoch,
+    /// The number of epochs the miner looks back from the head for the seal ticket.
+    pub seal_randomness_lookback: ChainEpoch,
+    /// The max number of epochs the seal ticket of a PreCommit can be behind the current epoch.
+    pub max_seal_lookback: ChainEpoch,
+}
+
+impl Default for LookbackParams {
+    fn default() -> Self {
+        let params = plum_params::params();
+        Self {
+            winning_post_lookback: params.finality,
+            seal_randomness_lookback: params.seal_randomness_lookback,
+            max_seal_lookback: params.max_seal_lookback,
+        }
+    }
+}
+
+impl LookbackParams {
+    /// Return the epoch of the seal ticket selected by the miner with the chain head at `head`.
+    pub fn seal_ticket_epoch(&self, head: ChainEpoch) -> ChainEpoch {
+        (head - self.seal_randomness_lookback).max(0)
+    }
+
+    /// Validate the epoch of the seal ticket of a PreCommit at the `current` epoch, the ticket
+    /// must be in the past and not older than `max_seal_lookback`.
+    pub fn validate_seal_ticket_epoch(
+        &self,
+        seal_epoch: ChainEpoch,
+        current: ChainEpoch,
+    ) -> Result<()> {
+        ensure!(
+            seal_epoch < current,
+            "seal challenge epoch {} must be before the current epoch {}",
+            seal_epoch,
+            current
+        );
+        ensure!(
+            seal_epoch >= current - self.max_seal_lookback,
+            "seal challenge epoch {} is too old, must be after {}",
+            seal_epoch,
+            current - self.max_seal_lookback
+        );
+        Ok(())
+    }
+}
+
+/// Draw the randomness from the tickets of the chain of `tipset` at `round`.
+///
+/// The ticket is the minimum ticket of the last non-null tipset at or before `round`, which
+/// is the same as GetChainRandomness of Lotus.
+pub fn state_get_randomness_from_tickets<L>(
+    cache: &TipsetCache<L>,
+    tipset: &Arc<Tipset>,
+    pers: DomainSeparationTag,
+    round: ChainEpoch,
+    entropy: &[u8],
+) -> Result<[u8; 32]>
+where
+    L: TipsetLoader,
+{
+    let rand_tipset = randomness_tipset(cache, tipset, round, false)?;
+    draw_randomness(&rand_tipset.min_ticket().vrf_proof, pers, round, entropy)
+}
+
+/// Draw the randomness from the beacon entries of the chain of `tipset` at `round`.
+///
+/// The beacon entry is the latest entry included by the first non-null tipset at or after
+/// `round` and its ancestors, which is the same as GetBeaconRandomness of Lotus.
+pub fn state_get_randomness_from_beacon<L>(
+    cache: &TipsetCache<L>,
+    tipset: &Arc<Tipset>,
+    pers: DomainSeparationTag,
+    round: ChainEpoch,
+    entropy: &[u8],
+) -> Result<[u8; 32]>
+where
+    L: TipsetLoader,
+{
+    let mut current = randomness_tipset(cache, tipset, round, true)?;
+    for _ in 0..BEACON_ENTRY_SEARCH_DEPTH {
+        if let Some(entry) = current.blocks()[0].beacon_entries.last() {
+            return draw_randomness(entry.data(), pers, round, entropy);
+        }
+        if current.height() == 0 {
+            bail!("made it back to genesis block without finding beacon entry");
+        }
+        // The parent is at or before `round`, so it's found with the null rounds in between.
+        let height = current.height() - 1;
+        current = cache.tipset_by_height(height, &current, false)?;
+    }
+    Err(anyhow!(
+        "found no beacon entry in the {} latest tipsets",
+        BEACON_ENTRY_SEARCH_DEPTH
+    ))
+}
+
+// Return the tipset at `round` in the chain of `tipset`, the rounds before the genesis are
+// rounded up to the genesis.
+fn randomness_tipset<L>(
+    cache: &TipsetCache<L>,
+    tipset: &Arc<Tipset>,
+    round: ChainEpoch,
+    allow_null: bool,
+) -> Result<Arc<Tipset>>
+where
+    L: TipsetLoader,
+{
+    ensure!(
+        round <= tipset.height(),
+        "cannot draw randomness from the future: {} > {}",
+        round,
+        tipset.height()
+    );
+    cache.tipset_by_height(round.max(0), tipset, allow_null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use cid::{Cid, Codec};
+    use multihash::Blake2b256;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use plum_block::{BeaconEntry, BlockHeader, Ticket};
+
+    use crate::manager::{StateManager, TipsetExecutor};
+    use crate::test_utils::{new_header, MapLoader};
+
+    struct NoExecutor;
+
+    impl TipsetExecutor for NoExecutor {
+        fn execute_tipset(&self, tipset: &Tipset) -> Result<(Cid, Cid)> {
+            Ok((state(tipset.height() + 100), state(0)))
+        }
+    }
+
+    fn state(height: ChainEpoch) -> Cid {
+        Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(&height.to_be_bytes()))
+    }
+
+    // The chain of heights 0..=10 with the null rounds 4 and 5, only the tipsets at 0 and 3
+    // include the beacon entries.
+    fn new_chain(loader: &mut MapLoader) -> Vec<Arc<Tipset>> {
+        let mut chain: Vec<Arc<Tipset>> = vec![];
+        for height in (0..=10).filter(|height| *height != 4 && *height != 5) {
+            let beacon_entries = match height {
+                0 | 3 => vec![BeaconEntry::new(height as u64, vec![height as u8; 96])],
+                _ => vec![],
+            };
+            let parents = chain
+                .last()
+                .map(|parent| parent.cids().to_vec())
+                .unwrap_or_default();
+            let header = BlockHeader {
+                ticket: Ticket::new(vec![height as u8; 32]),
+                beacon_entries,
+                parent_message_receipts: state(0),
+                messages: state(0),
+                parent_state_root: state(height),
+                ..new_header(parents, height, 1000)
+            };
+            let tipset = loader.insert(Tipset::new(vec![header]).unwrap());
+            chain.push(tipset);
+        }
+        chain
+    }
+
+    #[test]
+    fn test_randomness_at_epoch() {
+        let mut loader = MapLoader::default();
+        let chain = new_chain(&mut loader);
+        let head = chain.last().unwrap().clone();
+        let cache = TipsetCache::with_config(&loader, 16, 2);
+        let pers = || DomainSeparationTag::SealRandomness;
+
+        // The null round takes the ticket of the tipset before it.
+        assert_eq!(
+            state_get_randomness_from_tickets(&cache, &head, pers(), 5, b"entropy").unwrap(),
+            draw_randomness(&[3; 32], pers(), 5, b"entropy").unwrap()
+        );
+        assert_eq!(
+            state_get_randomness_from_tickets(&cache, &head, pers(), 8, b"entropy").unwrap(),
+            draw_randomness(&[8; 32], pers(), 8, b"entropy").unwrap()
+        );
+        // The rounds before the genesis take the genesis ticket.
+        assert_eq!(
+            state_get_randomness_from_tickets(&cache, &head, pers(), -5, b"").unwrap(),
+            draw_randomness(&[0; 32], pers(), -5, b"").unwrap()
+        );
+        assert!(state_get_randomness_from_tickets(&cache, &head, pers(), 11, b"").is_err());
+
+        // The beacon entry of the tipset at 3 is the latest one before the tipsets at 6 and 9.
+        for round in &[4, 5, 9] {
+            assert_eq!(
+                state_get_randomness_from_beacon(&cache, &head, pers(), *round, b"entropy")
+                    .unwrap(),
+                draw_randomness(&[3; 96], pers(), *round, b"entropy").unwrap()
+            );
+        }
+        assert_eq!(
+            state_get_randomness_from_beacon(&cache, &head, pers(), 2, b"").unwrap(),
+            draw_randomness(&[0; 96], pers(), 2, b"").unwrap()
+        );
+        assert!(state_get_randomness_from_beacon(&cache, &head, pers(), 11, b"").is_err());
+    }
+
+    #[test]
+    fn test_lookback_tipset_for_round() {
+        let mut loader = MapLoader::default();
+        let chain = new_chain(&mut loader);
+        let head = chain.last().unwrap().clone();
+        let cache = TipsetCache::with_config(&loader, 16, 2);
+        let manager = StateManager::new(NoExecutor, SyncDataStore::new(MapDataStore::new()));
+
+        // The lookback round 5 is null, the state after the tipset at 3 is the parent state of
+        // the tipset at 6.
+        let (tipset, state_root) = manager
+            .get_lookback_tipset_for_round(&cache, &head, 10, 5)
+            .unwrap();
+        assert_eq!(tipset.height(), 3);
+        assert_eq!(state_root, state(6));
+        let (tipset, state_root) = manager
+            .get_lookback_tipset_for_round(&cache, &head, 10, 3)
+            .unwrap();
+        assert_eq!(tipset.height(), 7);
+        assert_eq!(state_root, state(8));
+        // The lookback round is the tipset itself, whose state is computed.
+        let (tipset, state_root) = manager
+            .get_lookback_tipset_for_round(&cache, &head, 12, 2)
+            .unwrap();
+        assert_eq!(tipset, head);
+        assert_eq!(state_root, state(110));
+    }
+
+    #[test]
+    fn test_seal_ticket_epoch() {
+        let params = LookbackParams {
+            winning_post_lookback: 900,
+            seal_randomness_lookback: 900,
+            max_seal_lookback: 4900,
+        };
+        assert_eq!(params.seal_ticket_epoch(1000), 100);
+        assert_eq!(params.seal_ticket_epoch(100), 0);
+        assert!(params.validate_seal_ticket_epoch(100, 1000).is_ok());
+        assert!(params.validate_seal_ticket_epoch(100, 5000).is_ok());
+        assert!(params.validate_seal_ticket_epoch(99, 5000).is_err());
+        assert!(params.validate_seal_ticket_epoch(1000, 1000).is_err());
+    }
+}
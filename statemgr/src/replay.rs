@@ -0,0 +1,542 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::fmt;
+use std::sync::Arc;
+
+use anyhow::{ensure, Result};
+use cid::Cid;
+
+use ipfs_blockstore::BlockStore;
+use ipld::load_amt;
+use plum_chain::{ChainStore, TipsetLoader};
+use plum_message::MessageReceipt;
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+use crate::manager::TipsetExecutor;
+
+/// The loader of the recorded receipts and the messages of the tipsets, e.g. backed by the
+/// blockstore holding an imported snapshot.
+pub trait ReplayLoader {
+    /// Load the receipts of the AMT with the `root`, in ascending order of the indexes.
+    fn load_receipts(&self, root: &Cid) -> Result<Vec<MessageReceipt>>;
+
+    /// Load the CIDs of the messages applied by the tipset, in the order of their receipts,
+    /// see `plum_chain::select_tipset_messages`.
+    fn load_applied_messages(&self, tipset: &Tipset) -> Result<Vec<Cid>>;
+}
+
+/// The replay loader backed by the blockstore, which reads the receipts AMTs and the message
+/// sets of the blocks.
+pub struct BlockStoreReplayLoader<S> {
+    chain: ChainStore<S>,
+}
+
+impl<S: BlockStore> BlockStoreReplayLoader<S> {
+    /// Create a replay loader on the blockstore.
+    pub fn new(store: S) -> Self {
+        Self {
+            chain: ChainStore::new(store),
+        }
+    }
+}
+
+impl<S: BlockStore> ReplayLoader for BlockStoreReplayLoader<S> {
+    fn load_receipts(&self, root: &Cid) -> Result<Vec<MessageReceipt>> {
+        let receipts = load_amt::<_, MessageReceipt>(self.chain.store(), root)?;
+        Ok(receipts.into_iter().map(|(_, receipt)| receipt).collect())
+    }
+
+    fn load_applied_messages(&self, tipset: &Tipset) -> Result<Vec<Cid>> {
+        let messages = self.chain.tipset_messages(tipset)?;
+        Ok(messages
+            .into_iter()
+            .map(|(_, message)| message.cid().clone())
+            .collect())
+    }
+}
+
+/// The field of a receipt diverging from the recorded one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ReceiptField {
+    /// The exit code.
+    ExitCode,
+    /// The return value.
+    Return,
+    /// The used gas.
+    GasUsed,
+}
+
+impl fmt::Display for ReceiptField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReceiptField::ExitCode => f.write_str("exit code"),
+            ReceiptField::Return => f.write_str("return"),
+            ReceiptField::GasUsed => f.write_str("gas used"),
+        }
+    }
+}
+
+/// The first divergence between the re-execution and the recorded chain.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Divergence {
+    /// The receipt of a message differs from the recorded one.
+    Receipt {
+        /// The key of the executed tipset.
+        tipset: TipsetKey,
+        /// The height of the executed tipset.
+        height: ChainEpoch,
+        /// The index of the message among the applied messages of the tipset.
+        index: usize,
+        /// The CID of the message.
+        message: Cid,
+        /// The first diverging field of the receipt.
+        field: ReceiptField,
+        /// The recorded receipt.
+        expected: MessageReceipt,
+        /// The receipt of the re-execution.
+        actual: MessageReceipt,
+    },
+    /// The number of the receipts differs from the recorded one, while the receipts of the
+    /// common messages are the same.
+    ReceiptCount {
+        /// The key of the executed tipset.
+        tipset: TipsetKey,
+        /// The height of the executed tipset.
+        height: ChainEpoch,
+        /// The number of the recorded receipts.
+        expected: usize,
+        /// The number of the receipts of the re-execution.
+        actual: usize,
+    },
+    /// The receipts are the same, but the root of the receipts differs, i.e. the receipts
+    /// AMT is built differently.
+    ReceiptsRoot {
+        /// The key of the executed tipset.
+        tipset: TipsetKey,
+        /// The height of the executed tipset.
+        height: ChainEpoch,
+        /// The recorded root.
+        expected: Cid,
+        /// The root of the re-execution.
+        actual: Cid,
+    },
+    /// The receipts are the same, but the root of the post-state differs, e.g. the cron or
+    /// the block rewards are applied differently.
+    StateRoot {
+        /// The key of the executed tipset.
+        tipset: TipsetKey,
+        /// The height of the executed tipset.
+        height: ChainEpoch,
+        /// The recorded root.
+        expected: Cid,
+        /// The root of the re-execution.
+        actual: Cid,
+    },
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Receipt {
+                tipset,
+                height,
+                index,
+                message,
+                field,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tipset {} at height {}: message #{} {} diverges in {}, expected {:?}, got {:?}",
+                tipset, height, index, message, field, expected, actual
+            ),
+            Divergence::ReceiptCount {
+                tipset,
+                height,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tipset {} at height {}: expected {} receipts, got {}",
+                tipset, height, expected, actual
+            ),
+            Divergence::ReceiptsRoot {
+                tipset,
+                height,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tipset {} at height {}: expected receipts root {}, got {}",
+                tipset, height, expected, actual
+            ),
+            Divergence::StateRoot {
+                tipset,
+                height,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "tipset {} at height {}: expected state root {}, got {}",
+                tipset, height, expected, actual
+            ),
+        }
+    }
+}
+
+/// The result of replaying a range of tipsets.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReplayReport {
+    /// The number of the tipsets re-executed and matching the chain.
+    pub matched: usize,
+    /// The first divergence found, the replay stops at it.
+    pub divergence: Option<Divergence>,
+}
+
+impl ReplayReport {
+    /// Return true if all the replayed tipsets match the chain.
+    pub fn is_ok(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// The replayer re-executing the historical tipsets and checking the results against the
+/// roots recorded by their child tipsets, which is the primary tool for proving that the
+/// execution (and the gas accounting in particular) is the same as the other implementations.
+///
+/// The state after a tipset is recorded as the parent state and the parent receipts of the
+/// next non-null tipset, so the replayed tipsets must be consecutive in the chain, and the
+/// last tipset is only used for the recorded roots.
+pub struct Replayer<E, L> {
+    executor: E,
+    loader: L,
+}
+
+impl<E, L> Replayer<E, L>
+where
+    E: TipsetExecutor,
+    L: ReplayLoader,
+{
+    /// Create a new replayer with the executor and the loader of the receipts.
+    pub fn new(executor: E, loader: L) -> Self {
+        Self { executor, loader }
+    }
+
+    /// Replay the tipsets of the chain of `head` from the height `from`, i.e. re-execute the
+    /// tipsets in `[from, head)` and check them against their child tipsets.
+    pub fn replay_range<T>(
+        &self,
+        tipsets: &T,
+        head: &Arc<Tipset>,
+        from: ChainEpoch,
+    ) -> Result<ReplayReport>
+    where
+        T: TipsetLoader,
+    {
+        let mut chain = vec![head.clone()];
+        let mut current = head.clone();
+        while current.height() > from {
+            current = tipsets.load_tipset(&current.parents())?;
+            chain.push(current.clone());
+        }
+        chain.reverse();
+        self.replay(&chain)
+    }
+
+    /// Replay the consecutive `tipsets` in ascending order of the heights, stop at the first
+    /// divergence.
+    pub fn replay(&self, tipsets: &[Arc<Tipset>]) -> Result<ReplayReport> {
+        let mut report = ReplayReport::default();
+        for pair in tipsets.windows(2) {
+            let (tipset, child) = (&pair[0], &pair[1]);
+            ensure!(
+                &child.parents() == tipset.key(),
+                "tipset {} at height {} is not the parent of {}",
+                tipset.key(),
+                tipset.height(),
+                child.key()
+            );
+            if let Some(divergence) = self.replay_tipset(tipset, child)? {
+                warn!("[replay] {}", divergence);
+                report.divergence = Some(divergence);
+                break;
+            }
+            report.matched += 1;
+        }
+        debug!(
+            "[replay] {} tipsets matched, diverged: {}",
+            report.matched,
+            !report.is_ok()
+        );
+        Ok(report)
+    }
+
+    // Re-execute the tipset and compare the roots with the ones recorded by the child.
+    fn replay_tipset(&self, tipset: &Tipset, child: &Tipset) -> Result<Option<Divergence>> {
+        let (state_root, receipts_root) = self.executor.execute_tipset(tipset)?;
+        let expected_receipts = &child.blocks()[0].parent_message_receipts;
+        if &receipts_root != expected_receipts {
+            let expected = self.loader.load_receipts(expected_receipts)?;
+            let actual = self.loader.load_receipts(&receipts_root)?;
+            if let Some((index, field)) = first_divergence(&expected, &actual) {
+                let messages = self.loader.load_applied_messages(tipset)?;
+                ensure!(
+                    index < messages.len(),
+                    "receipt #{} of tipset {} has no message",
+                    index,
+                    tipset.key()
+                );
+                return Ok(Some(Divergence::Receipt {
+                    tipset: tipset.key().clone(),
+                    height: tipset.height(),
+                    index,
+                    message: messages[index].clone(),
+                    field,
+                    expected: expected[index].clone(),
+                    actual: actual[index].clone(),
+                }));
+            }
+            if expected.len() != actual.len() {
+                return Ok(Some(Divergence::ReceiptCount {
+                    tipset: tipset.key().clone(),
+                    height: tipset.height(),
+                    expected: expected.len(),
+                    actual: actual.len(),
+                }));
+            }
+            return Ok(Some(Divergence::ReceiptsRoot {
+                tipset: tipset.key().clone(),
+                height: tipset.height(),
+                expected: expected_receipts.clone(),
+                actual: receipts_root,
+            }));
+        }
+        if &state_root != child.parent_state() {
+            return Ok(Some(Divergence::StateRoot {
+                tipset: tipset.key().clone(),
+                height: tipset.height(),
+                expected: child.parent_state().clone(),
+                actual: state_root,
+            }));
+        }
+        Ok(None)
+    }
+}
+
+// Return the index and the field of the first diverging receipt among the common messages.
+fn first_divergence(
+    expected: &[MessageReceipt],
+    actual: &[MessageReceipt],
+) -> Option<(usize, ReceiptField)> {
+    expected
+        .iter()
+        .zip(actual)
+        .enumerate()
+        .find_map(|(index, (expected, actual))| {
+            let field = if expected.exit_code != actual.exit_code {
+                ReceiptField::ExitCode
+            } else if expected.r#return != actual.r#return {
+                ReceiptField::Return
+            } else if expected.gas_used != actual.gas_used {
+                ReceiptField::GasUsed
+            } else {
+                return None;
+            };
+            Some((index, field))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    use anyhow::anyhow;
+    use cid::Codec;
+    use multihash::Blake2b256;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use ipld::{build_amt, IpldStore, DEFAULT_AMT_BIT_WIDTH};
+    use plum_bigint::BigInt;
+    use plum_block::{BlockHeader, MsgMeta};
+    use plum_vm_exitcode::ExitCode;
+
+    use crate::test_utils::{new_header, MapLoader};
+
+    fn cid(data: &[u8]) -> Cid {
+        Cid::new_v1(Codec::DagCBOR, Blake2b256::digest(data))
+    }
+
+    fn receipt(gas_used: i64) -> MessageReceipt {
+        MessageReceipt {
+            exit_code: ExitCode::Ok,
+            r#return: vec![],
+            gas_used: BigInt::from(gas_used),
+        }
+    }
+
+    // The chain and its receipts, the receipts root is the hash of the gas used.
+    #[derive(Default)]
+    struct MemoryChain {
+        tipsets: MapLoader,
+        receipts: HashMap<Cid, Vec<MessageReceipt>>,
+    }
+
+    impl MemoryChain {
+        fn put_receipts(&mut self, receipts: Vec<MessageReceipt>) -> Cid {
+            let root = cid(format!("{:?}", receipts).as_bytes());
+            self.receipts.insert(root.clone(), receipts);
+            root
+        }
+
+        // Append a tipset on top of the `parent`, recording the state and receipts of it.
+        fn push(
+            &mut self,
+            parent: Option<&Arc<Tipset>>,
+            state: &[u8],
+            receipts: Cid,
+        ) -> Arc<Tipset> {
+            let height = parent.map(|parent| parent.height() + 1).unwrap_or(0);
+            let parents = parent
+                .map(|parent| parent.cids().to_vec())
+                .unwrap_or_default();
+            let header = BlockHeader {
+                parent_message_receipts: receipts,
+                parent_state_root: cid(state),
+                ..new_header(parents, height, 1000)
+            };
+            self.tipsets.insert(Tipset::new(vec![header]).unwrap())
+        }
+    }
+
+    impl TipsetLoader for &MemoryChain {
+        fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>> {
+            (&self.tipsets).load_tipset(key)
+        }
+    }
+
+    impl ReplayLoader for &MemoryChain {
+        fn load_receipts(&self, root: &Cid) -> Result<Vec<MessageReceipt>> {
+            self.receipts
+                .get(root)
+                .cloned()
+                .ok_or_else(|| anyhow!("missing receipts {}", root))
+        }
+
+        fn load_applied_messages(&self, tipset: &Tipset) -> Result<Vec<Cid>> {
+            Ok((0..3)
+                .map(|index| cid(format!("{}-{}", tipset.height(), index).as_bytes()))
+                .collect())
+        }
+    }
+
+    // The executor returning the given results of the heights.
+    struct FixedExecutor(HashMap<ChainEpoch, (Cid, Cid)>);
+
+    impl TipsetExecutor for FixedExecutor {
+        fn execute_tipset(&self, tipset: &Tipset) -> Result<(Cid, Cid)> {
+            self.0
+                .get(&tipset.height())
+                .cloned()
+                .ok_or_else(|| anyhow!("unexpected tipset at {}", tipset.height()))
+        }
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut chain = MemoryChain::default();
+        let receipts = chain.put_receipts(vec![receipt(10), receipt(20), receipt(30)]);
+        let genesis = chain.push(None, b"state 0", receipts.clone());
+        let one = chain.push(Some(&genesis), b"state 1", receipts.clone());
+        let two = chain.push(Some(&one), b"state 2", receipts.clone());
+
+        let mut results = HashMap::new();
+        results.insert(0, (cid(b"state 1"), receipts.clone()));
+        results.insert(1, (cid(b"state 2"), receipts.clone()));
+        let replayer = Replayer::new(FixedExecutor(results.clone()), &chain);
+        let report = replayer.replay_range(&&chain, &two, 0).unwrap();
+        assert_eq!(
+            report,
+            ReplayReport {
+                matched: 2,
+                divergence: None,
+            }
+        );
+
+        // The gas used of the second message diverges at height 1.
+        let diverged = chain.put_receipts(vec![receipt(10), receipt(21), receipt(30)]);
+        results.insert(1, (cid(b"state 2"), diverged));
+        let replayer = Replayer::new(FixedExecutor(results.clone()), &chain);
+        let report = replayer.replay_range(&&chain, &two, 0).unwrap();
+        assert_eq!(report.matched, 1);
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::Receipt {
+                tipset: one.key().clone(),
+                height: 1,
+                index: 1,
+                message: cid(b"1-1"),
+                field: ReceiptField::GasUsed,
+                expected: receipt(20),
+                actual: receipt(21),
+            })
+        );
+
+        // A missing receipt.
+        let truncated = chain.put_receipts(vec![receipt(10), receipt(20)]);
+        results.insert(1, (cid(b"state 2"), truncated));
+        let replayer = Replayer::new(FixedExecutor(results.clone()), &chain);
+        let report = replayer.replay(&[one.clone(), two.clone()]).unwrap();
+        assert!(matches!(
+            report.divergence,
+            Some(Divergence::ReceiptCount {
+                expected: 3,
+                actual: 2,
+                ..
+            })
+        ));
+
+        // The receipts match while the state doesn't.
+        results.insert(1, (cid(b"state 3"), receipts));
+        let replayer = Replayer::new(FixedExecutor(results), &chain);
+        let report = replayer.replay(&[one.clone(), two.clone()]).unwrap();
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::StateRoot {
+                tipset: one.key().clone(),
+                height: 1,
+                expected: cid(b"state 2"),
+                actual: cid(b"state 3"),
+            })
+        );
+
+        // The tipsets must be consecutive.
+        assert!(replayer.replay(&[genesis, two]).is_err());
+    }
+
+    #[test]
+    fn test_block_store_replay_loader() {
+        let store = SyncDataStore::new(MapDataStore::new());
+        let receipts = vec![receipt(10), receipt(20), receipt(30)];
+        let root = build_amt(&store, &receipts, DEFAULT_AMT_BIT_WIDTH).unwrap();
+        let empty = build_amt::<_, Cid>(&store, &[], DEFAULT_AMT_BIT_WIDTH).unwrap();
+        let meta = MsgMeta {
+            bls_messages: empty.clone(),
+            secpk_messages: empty,
+        };
+        let header = BlockHeader {
+            messages: IpldStore::put(&store, &meta).unwrap(),
+            ..new_header(vec![], 1, 1000)
+        };
+        let tipset = Tipset::new(vec![header]).unwrap();
+
+        let loader = BlockStoreReplayLoader::new(store);
+        assert_eq!(loader.load_receipts(&root).unwrap(), receipts);
+        assert!(loader.load_receipts(&cid(b"missing")).is_err());
+        assert!(loader.load_applied_messages(&tipset).unwrap().is_empty());
+        let tipset = Tipset::new(vec![new_header(vec![], 1, 1000)]).unwrap();
+        assert!(loader.load_applied_messages(&tipset).is_err());
+    }
+}
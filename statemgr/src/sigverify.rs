@@ -0,0 +1,257 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, ensure, Result};
+use cid::Cid;
+use rayon::prelude::*;
+
+use plum_address::Address;
+use plum_block::Block;
+use plum_crypto::Signature;
+use plum_message::{SignedMessage, UnsignedMessage};
+use plum_tipset::Tipset;
+
+use crate::manager::TipsetExecutor;
+
+/// The loader of the messages of the tipsets and the key addresses of their senders.
+pub trait SignatureLoader {
+    /// Load the blocks of the tipset along with their messages.
+    fn load_blocks(&self, tipset: &Tipset) -> Result<Vec<Block>>;
+
+    /// Resolve the address into the key address that signs for it,
+    /// in the parent state of the tipset.
+    fn resolve_key_address(&self, tipset: &Tipset, addr: &Address) -> Result<Address>;
+}
+
+// A signature check, the signers are resolved before the checks are run in parallel.
+enum SignatureCheck<'a> {
+    // The aggregate signature of the BLS messages of the block.
+    Bls {
+        block: Cid,
+        aggregate: &'a Signature,
+        signers: Vec<Address>,
+        messages: &'a [UnsignedMessage],
+    },
+    // The signature of a secp256k1 message.
+    Secp256k1 {
+        message: &'a SignedMessage,
+        signer: Address,
+    },
+}
+
+impl<'a> SignatureCheck<'a> {
+    fn verify(&self) -> Result<()> {
+        match self {
+            SignatureCheck::Bls {
+                block,
+                aggregate,
+                signers,
+                messages,
+            } => {
+                let payloads = messages
+                    .iter()
+                    .map(UnsignedMessage::signing_bytes)
+                    .collect::<Vec<_>>();
+                let valid = aggregate.verify_bls_aggregate(signers, &payloads)?;
+                ensure!(valid, "invalid BLS aggregate signature of block {}", block);
+            }
+            SignatureCheck::Secp256k1 { message, signer } => {
+                let valid = message
+                    .signature
+                    .verify(signer, message.message.signing_bytes())?;
+                ensure!(valid, "invalid signature of message {}", message.cid());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Verify all the message signatures of the blocks of a tipset in parallel.
+///
+/// The senders are resolved into their key addresses by `resolve_key` first, once per sender, then
+/// the aggregate signature of each block and the signatures of the secp256k1 messages are
+/// verified on the rayon thread pool. A secp256k1 message included by multiple blocks is only
+/// verified once. The first invalid signature found is returned as the error.
+pub fn verify_block_signatures<F>(blocks: &[Block], mut resolve_key: F) -> Result<()>
+where
+    F: FnMut(&Address) -> Result<Address>,
+{
+    let mut keys = HashMap::new();
+    let mut resolve = |addr: &Address| -> Result<Address> {
+        if !addr.is_id() {
+            return Ok(addr.clone());
+        }
+        if let Some(key) = keys.get(addr) {
+            return Ok(key.clone());
+        }
+        let key = resolve_key(addr)?;
+        keys.insert(addr.clone(), key.clone());
+        Ok(key)
+    };
+
+    let mut checks = vec![];
+    let mut seen = HashSet::new();
+    for block in blocks {
+        let signers = block
+            .bls_messages
+            .iter()
+            .map(|msg| resolve(&msg.from))
+            .collect::<Result<Vec<_>>>()?;
+        checks.push(SignatureCheck::Bls {
+            block: block.cid(),
+            aggregate: &block.header.bls_aggregate,
+            signers,
+            messages: &block.bls_messages,
+        });
+        for message in &block.secpk_messages {
+            if seen.insert(message.cid()) {
+                let signer = resolve(&message.message.from)?;
+                checks.push(SignatureCheck::Secp256k1 { message, signer });
+            }
+        }
+    }
+    checks.par_iter().try_for_each(SignatureCheck::verify)
+}
+
+/// Verify all the message signatures of the tipset in parallel, see `verify_block_signatures`.
+pub fn verify_tipset_signatures<L>(loader: &L, tipset: &Tipset) -> Result<()>
+where
+    L: SignatureLoader + ?Sized,
+{
+    let blocks = loader.load_blocks(tipset)?;
+    verify_block_signatures(&blocks, |addr| loader.resolve_key_address(tipset, addr)).map_err(
+        |err| {
+            anyhow!(
+                "tipset {} at height {}: {}",
+                tipset.key(),
+                tipset.height(),
+                err
+            )
+        },
+    )
+}
+
+/// The executor verifying all the message signatures of the tipset in a parallel batch before
+/// applying the messages with the inner executor.
+///
+/// The signature checks dominate the CPU time of validating a tipset, so the inner executor
+/// should skip the checks when applying the messages sequentially.
+pub struct SignatureVerifyingExecutor<E, L> {
+    executor: E,
+    loader: L,
+}
+
+impl<E, L> SignatureVerifyingExecutor<E, L> {
+    /// Wrap the executor with the loader of the messages.
+    pub fn new(executor: E, loader: L) -> Self {
+        Self { executor, loader }
+    }
+}
+
+impl<E, L> TipsetExecutor for SignatureVerifyingExecutor<E, L>
+where
+    E: TipsetExecutor,
+    L: SignatureLoader,
+{
+    fn execute_tipset(&self, tipset: &Tipset) -> Result<(Cid, Cid)> {
+        verify_tipset_signatures(&self.loader, tipset)?;
+        self.executor.execute_tipset(tipset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_bigint::BigInt;
+    use plum_crypto::{PrivateKey, PublicKey, SignatureType};
+    use plum_message::MESSAGE_VERSION_FEE_MARKET;
+
+    use crate::test_utils::new_header;
+
+    fn new_key(ty: SignatureType) -> (PrivateKey, Address) {
+        let privkey = match ty {
+            SignatureType::Secp256k1 => PrivateKey::generate_secp256k1_privkey(),
+            SignatureType::Bls => PrivateKey::generate_bls_privkey(),
+        };
+        let pubkey = PublicKey::from_privkey(&privkey).into_vec();
+        let addr = match ty {
+            SignatureType::Secp256k1 => Address::new_secp256k1_addr(&pubkey).unwrap(),
+            SignatureType::Bls => Address::new_bls_addr(&pubkey).unwrap(),
+        };
+        (privkey, addr)
+    }
+
+    fn sign(ty: SignatureType, privkey: &PrivateKey, from: Address, nonce: u64) -> SignedMessage {
+        let message = UnsignedMessage {
+            version: MESSAGE_VERSION_FEE_MARKET,
+            to: Address::new_id_addr(1000).unwrap(),
+            from,
+            nonce,
+            value: BigInt::from(0),
+            gas_price: BigInt::from(0),
+            gas_limit: 1000,
+            gas_fee_cap: BigInt::from(100),
+            gas_premium: BigInt::from(10),
+            method: 0,
+            params: vec![],
+        };
+        let signature =
+            Signature::sign(ty, privkey.clone().into_vec(), message.signing_bytes()).unwrap();
+        SignedMessage { message, signature }
+    }
+
+    fn new_block(miner: u64, messages: Vec<SignedMessage>) -> Block {
+        Block::assemble(new_header(vec![], 1, miner), messages).unwrap()
+    }
+
+    #[test]
+    fn test_verify_block_signatures() {
+        let (bls_key, bls_addr) = new_key(SignatureType::Bls);
+        let (secp_key, secp_addr) = new_key(SignatureType::Secp256k1);
+        let bls_id = Address::new_id_addr(100).unwrap();
+        let secp_msg = sign(SignatureType::Secp256k1, &secp_key, secp_addr.clone(), 0);
+        let blocks = vec![
+            new_block(
+                1000,
+                vec![
+                    // The ID address of the sender is resolved into the key address.
+                    sign(SignatureType::Bls, &bls_key, bls_id.clone(), 0),
+                    secp_msg.clone(),
+                ],
+            ),
+            new_block(
+                1001,
+                vec![
+                    sign(SignatureType::Bls, &bls_key, bls_addr.clone(), 1),
+                    secp_msg,
+                ],
+            ),
+        ];
+        let mut resolved = 0;
+        let resolve = |addr: &Address| {
+            resolved += 1;
+            if *addr == bls_id {
+                Ok(bls_addr.clone())
+            } else {
+                Err(anyhow!("unknown actor {}", addr))
+            }
+        };
+        verify_block_signatures(&blocks, resolve).unwrap();
+        assert_eq!(resolved, 1);
+
+        // The tampered secp256k1 message.
+        let mut tampered = blocks.clone();
+        tampered[1].secpk_messages[0].message.nonce = 1;
+        assert!(verify_block_signatures(&tampered, |addr| Ok(addr.clone())).is_err());
+
+        // The BLS message not covered by the aggregate signature.
+        let mut tampered = blocks.clone();
+        tampered[0].bls_messages[0].nonce = 1;
+        assert!(verify_block_signatures(&tampered, |_| Ok(bls_addr.clone())).is_err());
+
+        // The sender can't be resolved.
+        assert!(verify_block_signatures(&blocks, |_| Err(anyhow!("missing"))).is_err());
+    }
+}
@@ -0,0 +1,71 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+//! The fixtures shared by the tests of the crate.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use cid::Cid;
+
+use plum_address::Address;
+use plum_block::{BlockHeader, ElectionProof, Ticket};
+use plum_chain::TipsetLoader;
+use plum_crypto::Signature;
+use plum_tipset::{Tipset, TipsetKey};
+use plum_types::ChainEpoch;
+
+/// The CID of the roots that are not read by the tests.
+pub fn dummy_cid() -> Cid {
+    "bafyreicmaj5hhoy5mgqvamfhgexxyergw7hdeshizghodwkjg6qmpoco7i"
+        .parse()
+        .unwrap()
+}
+
+/// Create the header of the block mined by the `miner` on top of the `parents`,
+/// all the roots of the header are the dummy CID.
+pub fn new_header(parents: Vec<Cid>, height: ChainEpoch, miner: u64) -> BlockHeader {
+    let cid = dummy_cid();
+    BlockHeader {
+        miner: Address::new_id_addr(miner).unwrap(),
+        ticket: Ticket::new(b"vrf proof".to_vec()),
+        election_proof: ElectionProof {
+            win_count: 1,
+            vrf_proof: b"vrf proof".to_vec(),
+        },
+        beacon_entries: vec![],
+        win_post_proof: vec![],
+        parents,
+        parent_message_receipts: cid.clone(),
+        bls_aggregate: Signature::new_bls("signature"),
+        parent_weight: 0u64.into(),
+        messages: cid.clone(),
+        height,
+        parent_state_root: cid,
+        timestamp: 0u64,
+        block_sig: Signature::new_bls("signature"),
+        fork_signaling: 0u64,
+    }
+}
+
+/// The tipset loader backed by a map.
+#[derive(Default)]
+pub struct MapLoader(pub HashMap<TipsetKey, Arc<Tipset>>);
+
+impl MapLoader {
+    /// Add the tipset into the loader.
+    pub fn insert(&mut self, tipset: Tipset) -> Arc<Tipset> {
+        let tipset = Arc::new(tipset);
+        self.0.insert(tipset.key().clone(), tipset.clone());
+        tipset
+    }
+}
+
+impl TipsetLoader for &MapLoader {
+    fn load_tipset(&self, key: &TipsetKey) -> Result<Arc<Tipset>> {
+        self.0
+            .get(key)
+            .cloned()
+            .ok_or_else(|| anyhow!("tipset {} not found", key))
+    }
+}
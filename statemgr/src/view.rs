@@ -0,0 +1,225 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use minicbor::{decode, Decoder};
+
+use ipfs_blockstore::BlockStore;
+use ipld::hamt::{Hamt, HamtVersion};
+use plum_actor::{codes, miner, power};
+use plum_address::Address;
+use plum_bigint::{BigInt, BigIntWrapper};
+use plum_types::Actor;
+
+/// The read-only access to the state trees, which is usually backed by the blockstore.
+pub trait StateView {
+    /// Return all the actors in the state tree of the `root`, ordered by the address.
+    fn actors(&self, root: &Cid) -> Result<Vec<(Address, Actor)>>;
+
+    /// Return the raw block of the `cid`, `None` is returned if the block is missing.
+    fn get_block(&self, cid: &Cid) -> Result<Option<Vec<u8>>>;
+
+    /// Return the state of the actor if it's a miner actor, `None` is returned otherwise.
+    fn miner_state(&self, address: &Address, actor: &Actor) -> Result<Option<miner::State>>;
+
+    /// Return the power claim of the miner in the power actor of the state tree of the `root`,
+    /// `None` is returned if the miner has no claim.
+    fn power_claim(&self, root: &Cid, miner: &Address) -> Result<Option<power::Claim>>;
+
+    /// Return the total power claimed by all the miners in the state tree of the `root`.
+    fn total_power(&self, root: &Cid) -> Result<power::Claim>;
+}
+
+/// The state view backed by the blockstore, which reads the state trees of actors v0,
+/// i.e. the state tree and the power claims are the HAMTs of `HamtVersion::V2`.
+#[derive(Clone)]
+pub struct BlockStoreView<S> {
+    store: S,
+}
+
+impl<S: BlockStore + Clone> BlockStoreView<S> {
+    /// Create a state view on the blockstore.
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    fn load<T: for<'b> decode::Decode<'b>>(&self, cid: &Cid, name: &str) -> Result<T> {
+        let data = self
+            .get_block(cid)?
+            .ok_or_else(|| anyhow!("{} {} not found", name, cid))?;
+        minicbor::decode(&data).map_err(|err| anyhow!("failed to decode {} {}: {}", name, cid, err))
+    }
+
+    fn power_state(&self, root: &Cid) -> Result<PowerState> {
+        let address = Address::new_id_addr(power::STORAGE_POWER_ACTOR_ID)?;
+        let hamt = Hamt::<S, Actor>::load(self.store.clone(), HamtVersion::V2, root)?;
+        let actor = hamt
+            .get(address.as_bytes())?
+            .ok_or_else(|| anyhow!("power actor not found in state {}", root))?;
+        self.load(&actor.head, "power actor state")
+    }
+}
+
+impl<S: BlockStore + Clone> StateView for BlockStoreView<S> {
+    fn actors(&self, root: &Cid) -> Result<Vec<(Address, Actor)>> {
+        let hamt = Hamt::<S, Actor>::load(self.store.clone(), HamtVersion::V2, root)?;
+        let mut actors = vec![];
+        for entry in hamt.iter() {
+            let (key, actor) = entry?;
+            actors.push((Address::new_from_bytes(&key)?, actor));
+        }
+        actors.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+        Ok(actors)
+    }
+
+    fn get_block(&self, cid: &Cid) -> Result<Option<Vec<u8>>> {
+        Ok(BlockStore::get(&self.store, cid)?.map(|block| block.data().to_vec()))
+    }
+
+    fn miner_state(&self, _address: &Address, actor: &Actor) -> Result<Option<miner::State>> {
+        if actor.code != *codes::STORAGE_MINER_ACTOR_CODE_ID {
+            return Ok(None);
+        }
+        self.load(&actor.head, "miner state").map(Some)
+    }
+
+    fn power_claim(&self, root: &Cid, miner: &Address) -> Result<Option<power::Claim>> {
+        let claims = self.power_state(root)?.claims;
+        let hamt = Hamt::<S, power::Claim>::load(self.store.clone(), HamtVersion::V2, &claims)?;
+        Ok(hamt.get(miner.as_bytes())?)
+    }
+
+    fn total_power(&self, root: &Cid) -> Result<power::Claim> {
+        let state = self.power_state(root)?;
+        Ok(power::Claim {
+            raw_byte_power: state.total_raw_byte_power,
+            quality_adj_power: state.total_quality_adj_power,
+        })
+    }
+}
+
+// The fields of the power actor state (actors v0) read by the view.
+struct PowerState {
+    total_raw_byte_power: BigInt,
+    total_quality_adj_power: BigInt,
+    claims: Cid,
+}
+
+// The number of the fields of the power actor state (actors v0).
+const POWER_STATE_FIELDS: u64 = 16;
+
+// Implement CBOR deserialization for PowerState.
+impl<'b> decode::Decode<'b> for PowerState {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(POWER_STATE_FIELDS) {
+            return Err(decode::Error::Message(
+                "expected 16 fields of power actor state",
+            ));
+        }
+        let total_raw_byte_power = d.decode::<BigIntWrapper>()?.into_inner();
+        // TotalBytesCommitted
+        d.skip()?;
+        let total_quality_adj_power = d.decode::<BigIntWrapper>()?.into_inner();
+        // From TotalQABytesCommitted to LastProcessedCronEpoch
+        for _ in 3..14 {
+            d.skip()?;
+        }
+        let claims = d.decode::<Cid>()?;
+        // ProofValidationBatch
+        d.skip()?;
+        Ok(PowerState {
+            total_raw_byte_power,
+            total_quality_adj_power,
+            claims,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{MapDataStore, SyncDataStore};
+    use ipld::IpldStore;
+    use minicbor::{encode, Encoder};
+    use plum_bigint::BigIntRefWrapper;
+
+    type TestStore = SyncDataStore<MapDataStore>;
+
+    struct TestPowerState {
+        total: power::Claim,
+        claims: Cid,
+    }
+
+    // Implement CBOR serialization for TestPowerState.
+    impl encode::Encode for TestPowerState {
+        fn encode<W: encode::Write>(
+            &self,
+            e: &mut Encoder<W>,
+        ) -> Result<(), encode::Error<W::Error>> {
+            e.array(POWER_STATE_FIELDS)?
+                .encode(BigIntRefWrapper::from(&self.total.raw_byte_power))?
+                .u64(0)?
+                .encode(BigIntRefWrapper::from(&self.total.quality_adj_power))?;
+            for _ in 3..14 {
+                e.u64(0)?;
+            }
+            e.encode(&self.claims)?.null()?.ok()
+        }
+    }
+
+    fn new_actor(code: &Cid, head: Cid, balance: u64) -> Actor {
+        Actor {
+            code: code.clone(),
+            head,
+            nonce: 0,
+            balance: BigInt::from(balance),
+        }
+    }
+
+    #[test]
+    fn test_block_store_view() {
+        let store = TestStore::new(MapDataStore::new());
+        let miner = Address::new_id_addr(1000).unwrap();
+        let claim = power::Claim {
+            raw_byte_power: BigInt::from(2048),
+            quality_adj_power: BigInt::from(4096),
+        };
+        let mut claims = Hamt::new(store.clone()).with_version(HamtVersion::V2);
+        claims.set(miner.as_bytes(), claim.clone()).unwrap();
+        let power_head = IpldStore::put(
+            &store,
+            TestPowerState {
+                total: claim.clone(),
+                claims: claims.flush().unwrap(),
+            },
+        )
+        .unwrap();
+
+        let power_addr = Address::new_id_addr(power::STORAGE_POWER_ACTOR_ID).unwrap();
+        let account = Address::new_id_addr(100).unwrap();
+        let mut tree = Hamt::new(store.clone()).with_version(HamtVersion::V2);
+        let power_actor = new_actor(&codes::STORAGE_POWER_ACTOR_CODE_ID, power_head, 0);
+        let account_actor = new_actor(&codes::ACCOUNT_ACTOR_CODE_ID, power_actor.head.clone(), 10);
+        tree.set(account.as_bytes(), account_actor.clone()).unwrap();
+        tree.set(power_addr.as_bytes(), power_actor.clone())
+            .unwrap();
+        let root = tree.flush().unwrap();
+
+        let view = BlockStoreView::new(store);
+        assert_eq!(
+            view.actors(&root).unwrap(),
+            vec![
+                (power_addr, power_actor),
+                (account.clone(), account_actor.clone())
+            ]
+        );
+        assert_eq!(view.miner_state(&account, &account_actor).unwrap(), None);
+        assert_eq!(
+            view.power_claim(&root, &miner).unwrap(),
+            Some(claim.clone())
+        );
+        assert_eq!(view.power_claim(&root, &account).unwrap(), None);
+        assert_eq!(view.total_power(&root).unwrap(), claim);
+    }
+}
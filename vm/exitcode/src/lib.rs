@@ -190,6 +190,42 @@ impl ExitCode {
     pub fn is_send_failure(self) -> bool {
         self == ExitCode::SysErrSenderInvalid || self == ExitCode::SysErrSenderStateInvalid
     }
+    /// Whether the exit code is reserved for the runtime.
+    pub fn is_system_error(self) -> bool {
+        self.is_error() && i64::from(self) < 16
+    }
+
+    /// Return the human readable description of the exit code, e.g. for the CLI and RPC.
+    pub fn description(self) -> &'static str {
+        match self {
+            ExitCode::Ok => "success",
+            ExitCode::SysErrSenderInvalid => "the sender is not a valid account",
+            ExitCode::SysErrSenderStateInvalid => {
+                "the sender can't send the message, e.g. the nonce mismatches or the balance \
+                 can't cover the gas"
+            }
+            ExitCode::SysErrInvalidMethod => "the method is not found on the receiver",
+            ExitCode::SysErrInvalidParameters => "the params of the method can't be decoded",
+            ExitCode::SysErrInvalidReceiver => "the receiver doesn't exist and can't be created",
+            ExitCode::SysErrInsufficientFunds => "the balance can't cover the value sent",
+            ExitCode::SysErrOutOfGas => "the execution ran out of the gas limit",
+            ExitCode::SysErrForbidden => "the caller is not allowed to call the method",
+            ExitCode::SysErrorIllegalActor => "the actor performed a disallowed operation",
+            ExitCode::SysErrorIllegalArgument => "an invalid argument is passed to the runtime",
+            ExitCode::SysErrSerialization => "an object failed to de/serialize for storage",
+            ExitCode::SysErrorReserved3
+            | ExitCode::SysErrorReserved4
+            | ExitCode::SysErrorReserved5
+            | ExitCode::SysErrorReserved6 => "reserved system error",
+            ExitCode::ErrIllegalArgument => "a param of the method is invalid",
+            ExitCode::ErrNotFound => "the requested resource doesn't exist",
+            ExitCode::ErrForbidden => "the action is disallowed",
+            ExitCode::ErrInsufficientFunds => "the balance of the actor is insufficient",
+            ExitCode::ErrIllegalState => "the state of the actor is invalid",
+            ExitCode::ErrSerialization => "an object failed to de/serialize in the actor",
+            ExitCode::ErrPlaceholder => "unspecified actor error",
+        }
+    }
 }
 
 // Implement CBOR serialization for ExitCode.
@@ -205,3 +241,19 @@ impl<'b> decode::Decode<'b> for ExitCode {
         Self::try_from(d.i64()?).map_err(|err| decode::Error::Message(err))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_code_description() {
+        assert!(ExitCode::SysErrOutOfGas.is_system_error());
+        assert!(!ExitCode::ErrNotFound.is_system_error());
+        assert!(!ExitCode::Ok.is_system_error());
+        assert_eq!(
+            ExitCode::SysErrOutOfGas.description(),
+            "the execution ran out of the gas limit"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::{BTreeMap, HashMap};
+
+use plum_address::Address;
+use plum_types::{ChainEpoch, MethodNum};
+
+/// The errors of the cron simulation.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum CronSimError {
+    /// The target epoch is before the current epoch.
+    #[error("target epoch {target} is before the current epoch {current}")]
+    EpochInPast {
+        /// The current epoch.
+        current: ChainEpoch,
+        /// The target epoch.
+        target: ChainEpoch,
+    },
+    /// No handler is registered for the method of the receiver.
+    #[error("no cron handler for method {method} of {receiver}")]
+    HandlerNotFound {
+        /// The receiver of the call.
+        receiver: Address,
+        /// The method number.
+        method: MethodNum,
+    },
+    /// The handler failed.
+    #[error("cron handler for method {method} of {receiver} failed at epoch {epoch}: {reason}")]
+    Handler {
+        /// The receiver of the call.
+        receiver: Address,
+        /// The method number.
+        method: MethodNum,
+        /// The epoch of the tick.
+        epoch: ChainEpoch,
+        /// The error returned by the handler.
+        reason: String,
+    },
+}
+
+/// A deferred cron event, which is enrolled for a future epoch, like the events enrolled into
+/// the cron event queue of the power actor by the miner actors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeferredCronEvent {
+    /// The receiver of the call.
+    pub receiver: Address,
+    /// The method number.
+    pub method: MethodNum,
+    /// The payload passed to the handler.
+    pub payload: Vec<u8>,
+}
+
+/// The context of a cron handler call.
+pub struct CronContext<'a> {
+    epoch: ChainEpoch,
+    receiver: &'a Address,
+    enrolled: &'a mut Vec<(ChainEpoch, DeferredCronEvent)>,
+}
+
+impl<'a> CronContext<'a> {
+    /// Return the epoch of the tick.
+    pub fn epoch(&self) -> ChainEpoch {
+        self.epoch
+    }
+
+    /// Return the receiver of the call.
+    pub fn receiver(&self) -> &Address {
+        self.receiver
+    }
+
+    /// Enroll a deferred event, which is called at the tick of the `epoch`.
+    ///
+    /// The events enrolled for the current or a past epoch are called at the next tick.
+    pub fn enroll(
+        &mut self,
+        epoch: ChainEpoch,
+        receiver: Address,
+        method: MethodNum,
+        payload: Vec<u8>,
+    ) {
+        let event = DeferredCronEvent {
+            receiver,
+            method,
+            payload,
+        };
+        self.enrolled.push((epoch, event));
+    }
+}
+
+/// The handler of a cron call, which mutates the state and returns an error message on failure.
+pub type CronHandler<S> = Box<dyn FnMut(&mut S, &mut CronContext<'_>, &[u8]) -> Result<(), String>>;
+
+/// The statistics of a cron simulation run.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CronSimStats {
+    /// The number of the executed ticks, the epochs without any call are skipped.
+    pub ticks: u64,
+    /// The number of the handler calls.
+    pub calls: u64,
+}
+
+/// The simulator fast-forwarding the epochs by running only the cron ticks against the state,
+/// which is used to test the deadline and vesting logic of the actors over thousands of epochs
+/// without producing any block.
+///
+/// At every tick, the cron entries are called in the order they are added, then the deferred
+/// events due at the epoch are called in the order they are enrolled. If there is no cron entry,
+/// the epochs without any due event are skipped.
+pub struct CronSimulator<S> {
+    state: S,
+    epoch: ChainEpoch,
+    entries: Vec<(Address, MethodNum)>,
+    handlers: HashMap<(Address, MethodNum), CronHandler<S>>,
+    queue: BTreeMap<ChainEpoch, Vec<DeferredCronEvent>>,
+}
+
+impl<S> CronSimulator<S> {
+    /// Create a simulator of the `state` after the tick of the `epoch`.
+    pub fn new(state: S, epoch: ChainEpoch) -> Self {
+        Self {
+            state,
+            epoch,
+            entries: vec![],
+            handlers: HashMap::new(),
+            queue: BTreeMap::new(),
+        }
+    }
+
+    /// Register the handler of the method of the receiver.
+    pub fn register<F>(&mut self, receiver: Address, method: MethodNum, handler: F)
+    where
+        F: FnMut(&mut S, &mut CronContext<'_>, &[u8]) -> Result<(), String> + 'static,
+    {
+        self.handlers.insert((receiver, method), Box::new(handler));
+    }
+
+    /// Add a cron entry, which is called at every tick, like the entries of the cron actor.
+    pub fn add_entry(&mut self, receiver: Address, method: MethodNum) {
+        self.entries.push((receiver, method));
+    }
+
+    /// Enroll a deferred event, which is called at the tick of the `epoch`, or the next tick
+    /// if the `epoch` is not after the current epoch.
+    pub fn enroll(
+        &mut self,
+        epoch: ChainEpoch,
+        receiver: Address,
+        method: MethodNum,
+        payload: Vec<u8>,
+    ) {
+        let event = DeferredCronEvent {
+            receiver,
+            method,
+            payload,
+        };
+        self.queue.entry(epoch).or_default().push(event);
+    }
+
+    /// Return the epoch of the last tick.
+    pub fn epoch(&self) -> ChainEpoch {
+        self.epoch
+    }
+
+    /// Return the state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Return the mutable state, e.g. to simulate the messages between the ticks.
+    pub fn state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    /// Consume the simulator, returning the state.
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
+    /// Return the number of the pending deferred events.
+    pub fn pending_events(&self) -> usize {
+        self.queue.values().map(Vec::len).sum()
+    }
+
+    /// Run the tick of the next epoch.
+    pub fn tick(&mut self) -> Result<CronSimStats, CronSimError> {
+        let epoch = self.epoch + 1;
+        self.run_tick(epoch)
+    }
+
+    /// Run the ticks until the `target` epoch (inclusive).
+    pub fn run_until(&mut self, target: ChainEpoch) -> Result<CronSimStats, CronSimError> {
+        if target < self.epoch {
+            return Err(CronSimError::EpochInPast {
+                current: self.epoch,
+                target,
+            });
+        }
+        let mut stats = CronSimStats::default();
+        while let Some(epoch) = self.next_tick_epoch(target) {
+            let tick = self.run_tick(epoch)?;
+            stats.ticks += tick.ticks;
+            stats.calls += tick.calls;
+        }
+        self.epoch = target;
+        Ok(stats)
+    }
+
+    /// Run the ticks of the next `epochs` epochs.
+    pub fn advance(&mut self, epochs: ChainEpoch) -> Result<CronSimStats, CronSimError> {
+        self.run_until(self.epoch + epochs)
+    }
+
+    // Return the epoch of the next tick which calls any handler, `None` if it's after `target`.
+    fn next_tick_epoch(&self, target: ChainEpoch) -> Option<ChainEpoch> {
+        let next = self.epoch + 1;
+        let epoch = if self.entries.is_empty() {
+            let due = *self.queue.keys().next()?;
+            due.max(next)
+        } else {
+            next
+        };
+        if epoch <= target {
+            Some(epoch)
+        } else {
+            None
+        }
+    }
+
+    fn run_tick(&mut self, epoch: ChainEpoch) -> Result<CronSimStats, CronSimError> {
+        let mut stats = CronSimStats { ticks: 1, calls: 0 };
+        let mut enrolled = vec![];
+
+        for (receiver, method) in self.entries.clone() {
+            self.call(epoch, &receiver, method, &[], &mut enrolled)?;
+            stats.calls += 1;
+        }
+
+        let later = self.queue.split_off(&(epoch + 1));
+        let due = std::mem::replace(&mut self.queue, later);
+        for event in due.into_iter().flat_map(|(_, events)| events) {
+            self.call(
+                epoch,
+                &event.receiver,
+                event.method,
+                &event.payload,
+                &mut enrolled,
+            )?;
+            stats.calls += 1;
+        }
+
+        for (at, event) in enrolled {
+            self.queue.entry(at.max(epoch + 1)).or_default().push(event);
+        }
+        self.epoch = epoch;
+        Ok(stats)
+    }
+
+    fn call(
+        &mut self,
+        epoch: ChainEpoch,
+        receiver: &Address,
+        method: MethodNum,
+        payload: &[u8],
+        enrolled: &mut Vec<(ChainEpoch, DeferredCronEvent)>,
+    ) -> Result<(), CronSimError> {
+        let handler = self
+            .handlers
+            .get_mut(&(receiver.clone(), method))
+            .ok_or_else(|| CronSimError::HandlerNotFound {
+                receiver: receiver.clone(),
+                method,
+            })?;
+        let mut ctx = CronContext {
+            epoch,
+            receiver,
+            enrolled,
+        };
+        handler(&mut self.state, &mut ctx, payload).map_err(|reason| CronSimError::Handler {
+            receiver: receiver.clone(),
+            method,
+            epoch,
+            reason,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use plum_actor::miner::{
+        DeadlineInfo, Method, W_POST_CHALLENGE_WINDOW, W_POST_PERIOD_DEADLINES,
+        W_POST_PROVING_PERIOD,
+    };
+
+    #[derive(Default)]
+    struct Miner {
+        proving_period_start: ChainEpoch,
+        // (deadline index, epoch of the processing)
+        processed: Vec<(u64, ChainEpoch)>,
+    }
+
+    #[test]
+    fn test_deadline_cron() {
+        let miner = Address::new_id_addr(1000).unwrap();
+        let method = Method::OnDeferredCronEvent.into();
+        let period_start = 100;
+        let mut sim = CronSimulator::new(
+            Miner {
+                proving_period_start: period_start,
+                ..Default::default()
+            },
+            0,
+        );
+        // Process the deadline at its close, then enroll the next deadline.
+        sim.register(miner.clone(), method, move |state: &mut Miner, ctx, _| {
+            let elapsed = ctx.epoch() - state.proving_period_start;
+            let window = W_POST_CHALLENGE_WINDOW as ChainEpoch;
+            if elapsed <= 0 || elapsed % window != 0 {
+                return Err("not at the close of a deadline".into());
+            }
+            let index = (elapsed / window - 1) as u64;
+            if index >= W_POST_PERIOD_DEADLINES {
+                return Err("proving period is not advanced".into());
+            }
+            state.processed.push((index, ctx.epoch()));
+            let mut next = DeadlineInfo::new(state.proving_period_start, index + 1, ctx.epoch());
+            if index + 1 == W_POST_PERIOD_DEADLINES {
+                state.proving_period_start += W_POST_PROVING_PERIOD as ChainEpoch;
+                next = DeadlineInfo::new(state.proving_period_start, 0, ctx.epoch());
+            }
+            let receiver = ctx.receiver().clone();
+            ctx.enroll(next.close, receiver, method, vec![]);
+            Ok(())
+        });
+        sim.enroll(
+            DeadlineInfo::new(period_start, 0, 0).close,
+            miner,
+            method,
+            vec![],
+        );
+
+        let periods = 10;
+        let target = period_start + periods * W_POST_PROVING_PERIOD as ChainEpoch;
+        let stats = sim.run_until(target).unwrap();
+        let deadlines = periods as u64 * W_POST_PERIOD_DEADLINES;
+        // Only the epochs of the deadline closes are ticked.
+        assert_eq!(stats.ticks, deadlines);
+        assert_eq!(stats.calls, deadlines);
+        assert_eq!(sim.epoch(), target);
+        assert_eq!(sim.pending_events(), 1);
+
+        let state = sim.into_state();
+        assert_eq!(state.processed.len() as u64, deadlines);
+        for (i, (index, epoch)) in state.processed.iter().enumerate() {
+            let i = i as u64;
+            assert_eq!(*index, i % W_POST_PERIOD_DEADLINES);
+            let period =
+                period_start + (i / W_POST_PERIOD_DEADLINES * W_POST_PROVING_PERIOD) as ChainEpoch;
+            assert_eq!(*epoch, DeadlineInfo::new(period, *index, *epoch).close);
+        }
+    }
+
+    #[test]
+    fn test_cron_entries() {
+        let power = Address::new_id_addr(4).unwrap();
+        let mut sim = CronSimulator::new(0u64, 10);
+        sim.add_entry(power.clone(), 1);
+        sim.register(power.clone(), 1, |ticks: &mut u64, ctx, _| {
+            *ticks += 1;
+            if ctx.epoch() == 15 {
+                let receiver = ctx.receiver().clone();
+                // Enrolled for the current epoch, called at the next tick.
+                ctx.enroll(15, receiver, 2, vec![42]);
+            }
+            Ok(())
+        });
+        sim.register(power.clone(), 2, |ticks: &mut u64, ctx, payload| {
+            assert_eq!(ctx.epoch(), 16);
+            *ticks += u64::from(payload[0]);
+            Ok(())
+        });
+
+        let stats = sim.advance(10).unwrap();
+        assert_eq!(
+            stats,
+            CronSimStats {
+                ticks: 10,
+                calls: 11
+            }
+        );
+        assert_eq!(*sim.state(), 10 + 42);
+
+        assert_eq!(
+            sim.run_until(0),
+            Err(CronSimError::EpochInPast {
+                current: 20,
+                target: 0
+            })
+        );
+        sim.enroll(21, power.clone(), 3, vec![]);
+        assert_eq!(
+            sim.tick(),
+            Err(CronSimError::HandlerNotFound {
+                receiver: power,
+                method: 3
+            })
+        );
+    }
+}
@@ -0,0 +1,397 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::collections::HashMap;
+
+use cid::Cid;
+use minicbor::{decode, encode, Decoder, Encoder};
+use serde::{Deserialize, Serialize};
+
+use ipld::{build_amt, IpldStore};
+use plum_bytes::Bytes;
+use plum_types::{ActorId, ChainEpoch};
+
+/// The bit width of the events AMT of a message.
+pub const EVENTS_AMT_BIT_WIDTH: u32 = 5;
+/// The entry key is indexed.
+pub const EVENT_FLAG_INDEXED_KEY: u64 = 0b01;
+/// The entry value is indexed.
+pub const EVENT_FLAG_INDEXED_VALUE: u64 = 0b10;
+/// Both the entry key and the entry value are indexed.
+pub const EVENT_FLAG_INDEXED_ALL: u64 = EVENT_FLAG_INDEXED_KEY | EVENT_FLAG_INDEXED_VALUE;
+/// The max length of an entry key in bytes.
+pub const MAX_EVENT_KEY_LEN: usize = 31;
+/// The max length of an entry value in bytes.
+pub const MAX_EVENT_VALUE_LEN: usize = 8 << 10;
+/// The max number of the entries of an event.
+pub const MAX_EVENT_ENTRIES: usize = 255;
+
+/// The errors of emitting an event.
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum EventError {
+    /// The event has no entry.
+    #[error("event has no entry")]
+    NoEntry,
+    /// The event has too many entries.
+    #[error("too many event entries {0}, max: {}", MAX_EVENT_ENTRIES)]
+    TooManyEntries(usize),
+    /// The key of an entry is too long.
+    #[error("event entry key `{0}` is too long, max: {}", MAX_EVENT_KEY_LEN)]
+    KeyTooLong(String),
+    /// The value of an entry is too long.
+    #[error(
+        "event entry value of {0} bytes is too long, max: {}",
+        MAX_EVENT_VALUE_LEN
+    )]
+    ValueTooLong(usize),
+    /// The flags of an entry are unknown.
+    #[error("unknown event entry flags {0:#b}")]
+    UnknownFlags(u64),
+}
+
+/// A key-value entry of an actor event.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventEntry {
+    /// The indexing flags of the entry, see `EVENT_FLAG_INDEXED_*`.
+    pub flags: u64,
+    /// The key of the entry.
+    pub key: String,
+    /// The IPLD codec of the value.
+    pub codec: u64,
+    /// The value of the entry.
+    #[serde(with = "plum_bytes::base64")]
+    pub value: Vec<u8>,
+}
+
+// Implement CBOR serialization for EventEntry.
+impl encode::Encode for EventEntry {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(4)?
+            .u64(self.flags)?
+            .str(&self.key)?
+            .u64(self.codec)?
+            .bytes(&self.value)?
+            .ok()
+    }
+}
+
+// Implement CBOR deserialization for EventEntry.
+impl<'b> decode::Decode<'b> for EventEntry {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(4) {
+            return Err(decode::Error::Message("expected 4 fields of EventEntry"));
+        }
+        Ok(EventEntry {
+            flags: d.u64()?,
+            key: d.str()?.to_owned(),
+            codec: d.u64()?,
+            value: d.bytes()?.to_vec(),
+        })
+    }
+}
+
+/// An event emitted by an actor, which is a list of the entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorEvent {
+    /// The entries of the event.
+    pub entries: Vec<EventEntry>,
+}
+
+// Implement CBOR serialization for ActorEvent.
+impl encode::Encode for ActorEvent {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.encode(&self.entries)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for ActorEvent.
+impl<'b> decode::Decode<'b> for ActorEvent {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        Ok(ActorEvent {
+            entries: d.decode::<Vec<EventEntry>>()?,
+        })
+    }
+}
+
+impl ActorEvent {
+    /// Check the limits of the entries and the flags.
+    pub fn validate(&self) -> Result<(), EventError> {
+        if self.entries.is_empty() {
+            return Err(EventError::NoEntry);
+        }
+        if self.entries.len() > MAX_EVENT_ENTRIES {
+            return Err(EventError::TooManyEntries(self.entries.len()));
+        }
+        for entry in &self.entries {
+            if entry.flags & !EVENT_FLAG_INDEXED_ALL != 0 {
+                return Err(EventError::UnknownFlags(entry.flags));
+            }
+            if entry.key.len() > MAX_EVENT_KEY_LEN {
+                return Err(EventError::KeyTooLong(entry.key.clone()));
+            }
+            if entry.value.len() > MAX_EVENT_VALUE_LEN {
+                return Err(EventError::ValueTooLong(entry.value.len()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An actor event stamped with the ID of the emitter.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StampedEvent {
+    /// The ID of the actor emitting the event.
+    pub emitter: ActorId,
+    /// The event.
+    pub event: ActorEvent,
+}
+
+// Implement CBOR serialization for StampedEvent.
+impl encode::Encode for StampedEvent {
+    fn encode<W: encode::Write>(&self, e: &mut Encoder<W>) -> Result<(), encode::Error<W::Error>> {
+        e.array(2)?.u64(self.emitter)?.encode(&self.event)?.ok()
+    }
+}
+
+// Implement CBOR deserialization for StampedEvent.
+impl<'b> decode::Decode<'b> for StampedEvent {
+    fn decode(d: &mut Decoder<'b>) -> Result<Self, decode::Error> {
+        if d.array()? != Some(2) {
+            return Err(decode::Error::Message("expected 2 fields of StampedEvent"));
+        }
+        Ok(StampedEvent {
+            emitter: d.u64()?,
+            event: d.decode::<ActorEvent>()?,
+        })
+    }
+}
+
+/// The accumulator of the events emitted during the execution of a message.
+///
+/// The events emitted by a call are discarded if the call aborts, so the runtime takes
+/// a checkpoint before each call and rolls back to it when the call fails.
+#[derive(Clone, Debug, Default)]
+pub struct EventAccumulator {
+    events: Vec<StampedEvent>,
+}
+
+impl EventAccumulator {
+    /// Create an empty accumulator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an event emitted by the actor, the event is rejected if it exceeds the limits.
+    pub fn emit_event(&mut self, emitter: ActorId, event: ActorEvent) -> Result<(), EventError> {
+        event.validate()?;
+        self.events.push(StampedEvent { emitter, event });
+        Ok(())
+    }
+
+    /// Return the checkpoint of the current events.
+    pub fn checkpoint(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Discard the events emitted after the checkpoint.
+    pub fn rollback(&mut self, checkpoint: usize) {
+        self.events.truncate(checkpoint);
+    }
+
+    /// Return the events emitted so far.
+    pub fn events(&self) -> &[StampedEvent] {
+        &self.events
+    }
+
+    /// Take all the events emitted during the message, and build the events AMT of the message.
+    /// `None` is returned as the root if there is no event.
+    pub fn finish<S: IpldStore>(
+        &mut self,
+        store: &S,
+    ) -> ipld::Result<(Vec<StampedEvent>, Option<Cid>)> {
+        let events = std::mem::take(&mut self.events);
+        let root = events_root(store, &events)?;
+        Ok((events, root))
+    }
+}
+
+/// Build the events AMT of a message, `None` is returned if there is no event.
+pub fn events_root<S: IpldStore>(store: &S, events: &[StampedEvent]) -> ipld::Result<Option<Cid>> {
+    if events.is_empty() {
+        return Ok(None);
+    }
+    build_amt(store, events, EVENTS_AMT_BIT_WIDTH).map(Some)
+}
+
+/// Build the events root of a tipset, which is an AMT of the events roots of its messages
+/// in the execution order, the messages without any event are stored as null.
+pub fn tipset_events_root<S: IpldStore>(
+    store: &S,
+    message_roots: &[Option<Cid>],
+) -> ipld::Result<Cid> {
+    build_amt(store, message_roots, EVENTS_AMT_BIT_WIDTH)
+}
+
+/// The filter of the actor events, used by the `GetActorEvents` API.
+///
+/// An event matches if it's emitted by one of the `emitters` (any emitter if empty), and for
+/// every key of the `fields`, it has an entry with the key whose value is one of the values
+/// (any value if empty).
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventFilter {
+    /// The IDs of the emitters.
+    pub emitters: Vec<ActorId>,
+    /// The entry keys and the acceptable values.
+    pub fields: HashMap<String, Vec<Bytes>>,
+    /// The lowest epoch of the events, inclusive.
+    pub from_height: Option<ChainEpoch>,
+    /// The highest epoch of the events, inclusive.
+    pub to_height: Option<ChainEpoch>,
+}
+
+impl EventFilter {
+    /// Return true if the event emitted at the height matches the filter.
+    pub fn matches(&self, height: ChainEpoch, event: &StampedEvent) -> bool {
+        if self.from_height.map_or(false, |from| height < from)
+            || self.to_height.map_or(false, |to| height > to)
+        {
+            return false;
+        }
+        if !self.emitters.is_empty() && !self.emitters.contains(&event.emitter) {
+            return false;
+        }
+        self.fields.iter().all(|(key, values)| {
+            event.event.entries.iter().any(|entry| {
+                &entry.key == key
+                    && (values.is_empty()
+                        || values
+                            .iter()
+                            .any(|value| value.as_inner() == &entry.value[..]))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ipfs_datastore::{Delay, DelayDataStore, MapDataStore, SyncDataStore};
+    use ipld::load_amt;
+
+    #[derive(Clone)]
+    struct NoDelay;
+
+    impl Delay for NoDelay {
+        fn wait(&self) {}
+    }
+
+    fn entry(key: &str, value: &[u8]) -> EventEntry {
+        EventEntry {
+            flags: EVENT_FLAG_INDEXED_ALL,
+            key: key.into(),
+            codec: 0x55,
+            value: value.to_vec(),
+        }
+    }
+
+    fn event(entries: Vec<EventEntry>) -> ActorEvent {
+        ActorEvent { entries }
+    }
+
+    #[test]
+    fn test_event_cbor() {
+        let stamped = StampedEvent {
+            emitter: 1000,
+            event: event(vec![entry("t1", b"ab")]),
+        };
+        let ser = minicbor::to_vec(&stamped).unwrap();
+        assert_eq!(
+            ser,
+            vec![
+                0x82, 0x19, 0x03, 0xe8, 0x81, 0x84, 0x03, 0x62, b't', b'1', 0x18, 0x55, 0x42, b'a',
+                b'b'
+            ]
+        );
+        assert_eq!(minicbor::decode::<StampedEvent>(&ser).unwrap(), stamped);
+        assert!(minicbor::decode::<StampedEvent>(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn test_emit_event() {
+        let store = DelayDataStore::new(NoDelay, SyncDataStore::new(MapDataStore::new()));
+        let mut acc = EventAccumulator::new();
+        assert_eq!(
+            acc.emit_event(1000, event(vec![])),
+            Err(EventError::NoEntry)
+        );
+        assert_eq!(
+            acc.emit_event(1000, event(vec![entry(&"k".repeat(32), b"")])),
+            Err(EventError::KeyTooLong("k".repeat(32)))
+        );
+        let mut invalid = entry("k", b"");
+        invalid.flags = 0b100;
+        assert_eq!(
+            acc.emit_event(1000, event(vec![invalid])),
+            Err(EventError::UnknownFlags(0b100))
+        );
+
+        acc.emit_event(1000, event(vec![entry("t1", b"a")]))
+            .unwrap();
+        // the events of an aborted call are discarded.
+        let checkpoint = acc.checkpoint();
+        acc.emit_event(1001, event(vec![entry("t1", b"b")]))
+            .unwrap();
+        acc.rollback(checkpoint);
+        acc.emit_event(1002, event(vec![entry("t2", b"c")]))
+            .unwrap();
+
+        let (events, root) = acc.finish(&store).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(acc.events().is_empty());
+        let loaded = load_amt::<_, StampedEvent>(&store, &root.clone().unwrap()).unwrap();
+        assert_eq!(loaded, vec![(0, events[0].clone()), (1, events[1].clone())]);
+
+        let (_, empty) = acc.finish(&store).unwrap();
+        assert_eq!(empty, None);
+        let tipset_root = tipset_events_root(&store, &[root.clone(), None]).unwrap();
+        let loaded = load_amt::<_, Option<Cid>>(&store, &tipset_root).unwrap();
+        assert_eq!(loaded, vec![(0, root), (1, None)]);
+    }
+
+    #[test]
+    fn test_event_filter() {
+        let stamped = StampedEvent {
+            emitter: 1000,
+            event: event(vec![entry("t1", b"a"), entry("t2", b"b")]),
+        };
+        assert!(EventFilter::default().matches(10, &stamped));
+
+        let mut filter = EventFilter {
+            emitters: vec![1000, 1001],
+            from_height: Some(5),
+            to_height: Some(10),
+            ..Default::default()
+        };
+        assert!(filter.matches(10, &stamped));
+        assert!(!filter.matches(11, &stamped));
+        assert!(!filter.matches(4, &stamped));
+
+        filter.fields.insert("t1".into(), vec![]);
+        assert!(filter.matches(10, &stamped));
+        filter.fields.insert(
+            "t2".into(),
+            vec![b"c".to_vec().into(), b"b".to_vec().into()],
+        );
+        assert!(filter.matches(10, &stamped));
+        filter.fields.insert("t3".into(), vec![]);
+        assert!(!filter.matches(10, &stamped));
+
+        filter.fields.clear();
+        filter.emitters = vec![1001];
+        assert!(!filter.matches(10, &stamped));
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use plum_bigint::num_integer::Integer;
+use plum_bigint::BigInt;
+use plum_message::UnsignedMessage;
+use plum_types::{Gas, TokenAmount};
+
+// The gas limit within 110% of the gas used is not penalized for the overestimation.
+const GAS_OVERUSE_NUM: u64 = 11;
+const GAS_OVERUSE_DENOM: u64 = 10;
+
+/// The distribution of the gas fee of a message after it's executed.
+///
+/// The sender pays `gas_limit * fee_cap` upfront, the base fee of the gas used and of the
+/// overestimated gas is burnt, the premium is paid to the miner, and the rest is refunded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GasOutputs {
+    /// The base fee of the gas used, which is burnt.
+    pub base_fee_burn: TokenAmount,
+    /// The base fee of the overestimated gas, which is burnt.
+    pub over_estimation_burn: TokenAmount,
+    /// The penalty of the miner for including a message whose fee cap is below the base fee,
+    /// which is burnt from the funds of the miner.
+    pub miner_penalty: TokenAmount,
+    /// The premium paid to the miner.
+    pub miner_tip: TokenAmount,
+    /// The funds refunded to the sender.
+    pub refund: TokenAmount,
+    /// The gas refunded to the sender.
+    pub gas_refund: Gas,
+    /// The overestimated gas burnt.
+    pub gas_burned: Gas,
+}
+
+impl GasOutputs {
+    /// Compute the gas outputs of the message with the gas used and the base fee.
+    ///
+    /// The fee cap and the premium of the legacy message are both its gas price.
+    pub fn compute(message: &UnsignedMessage, gas_used: &Gas, base_fee: &TokenAmount) -> Self {
+        compute_gas_outputs(
+            gas_used,
+            &BigInt::from(message.gas_limit),
+            base_fee,
+            message.fee_cap(),
+            message.premium(),
+        )
+    }
+}
+
+/// Compute the gas outputs, see lotus/chain/vm/burn.go for details.
+pub fn compute_gas_outputs(
+    gas_used: &Gas,
+    gas_limit: &Gas,
+    base_fee: &TokenAmount,
+    fee_cap: &TokenAmount,
+    gas_premium: &TokenAmount,
+) -> GasOutputs {
+    let mut out = GasOutputs::default();
+
+    let mut base_fee_to_pay = base_fee;
+    if base_fee > fee_cap {
+        base_fee_to_pay = fee_cap;
+        out.miner_penalty = (base_fee - fee_cap) * gas_used;
+    }
+    out.base_fee_burn = base_fee_to_pay * gas_used;
+
+    let mut miner_tip = gas_premium.clone();
+    if base_fee_to_pay + &miner_tip > *fee_cap {
+        miner_tip = fee_cap - base_fee_to_pay;
+    }
+    out.miner_tip = miner_tip * gas_limit;
+
+    let (gas_refund, gas_burned) = compute_gas_overestimation_burn(gas_used, gas_limit);
+    if gas_burned != BigInt::from(0) {
+        out.over_estimation_burn = base_fee_to_pay * &gas_burned;
+        out.miner_penalty += (base_fee - base_fee_to_pay) * &gas_burned;
+    }
+    out.gas_refund = gas_refund;
+    out.gas_burned = gas_burned;
+
+    let required_funds = gas_limit * fee_cap;
+    out.refund = required_funds - &out.base_fee_burn - &out.miner_tip - &out.over_estimation_burn;
+    out
+}
+
+/// Compute the gas to be refunded and the gas to be burnt for the overestimation of the gas limit,
+/// return `(gas_refund, gas_burned)`.
+pub fn compute_gas_overestimation_burn(gas_used: &Gas, gas_limit: &Gas) -> (Gas, Gas) {
+    let zero = BigInt::from(0);
+    if *gas_used == zero {
+        return (zero, gas_limit.clone());
+    }
+
+    let mut over =
+        gas_limit - (BigInt::from(GAS_OVERUSE_NUM) * gas_used) / BigInt::from(GAS_OVERUSE_DENOM);
+    if over < zero {
+        return (gas_limit - gas_used, zero);
+    }
+    if over > *gas_used {
+        over = gas_used.clone();
+    }
+
+    // The same as the euclidean division of Go.
+    let gas_to_burn = (over * (gas_limit - gas_used)).div_floor(gas_used);
+    (gas_limit - gas_used - &gas_to_burn, gas_to_burn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn int(n: i64) -> BigInt {
+        BigInt::from(n)
+    }
+
+    #[test]
+    fn test_gas_overestimation_burn() {
+        assert_eq!(
+            compute_gas_overestimation_burn(&int(0), &int(100)),
+            (int(0), int(100))
+        );
+        // within 110% of the gas used.
+        assert_eq!(
+            compute_gas_overestimation_burn(&int(100), &int(110)),
+            (int(10), int(0))
+        );
+        assert_eq!(
+            compute_gas_overestimation_burn(&int(100), &int(200)),
+            (int(10), int(90))
+        );
+        assert_eq!(
+            compute_gas_overestimation_burn(&int(100), &int(1000)),
+            (int(0), int(900))
+        );
+    }
+
+    #[test]
+    fn test_gas_outputs() {
+        // fee cap above the base fee + premium.
+        let out = compute_gas_outputs(&int(100), &int(110), &int(10), &int(20), &int(5));
+        assert_eq!(out.base_fee_burn, int(1000));
+        assert_eq!(out.miner_tip, int(550));
+        assert_eq!(out.miner_penalty, int(0));
+        assert_eq!(out.over_estimation_burn, int(0));
+        assert_eq!(out.refund, int(110 * 20 - 1000 - 550));
+
+        // the tip is capped by the fee cap.
+        let out = compute_gas_outputs(&int(100), &int(110), &int(10), &int(12), &int(5));
+        assert_eq!(out.miner_tip, int(2 * 110));
+
+        // fee cap below the base fee, the miner is penalized.
+        let out = compute_gas_outputs(&int(100), &int(200), &int(10), &int(8), &int(5));
+        assert_eq!(out.base_fee_burn, int(800));
+        assert_eq!(out.miner_tip, int(0));
+        assert_eq!(out.gas_burned, int(90));
+        assert_eq!(out.over_estimation_burn, int(8 * 90));
+        assert_eq!(out.miner_penalty, int(2 * 100 + 2 * 90));
+        assert_eq!(out.refund, int(200 * 8 - 800 - 8 * 90));
+    }
+}
@@ -4,9 +4,22 @@
 
 #![deny(missing_docs)]
 
+mod cron;
+mod events;
 mod gas;
+mod gas_outputs;
 mod gas_v0;
 mod types;
 
+pub use self::cron::{
+    CronContext, CronHandler, CronSimError, CronSimStats, CronSimulator, DeferredCronEvent,
+};
+pub use self::events::{
+    events_root, tipset_events_root, ActorEvent, EventAccumulator, EventEntry, EventError,
+    EventFilter, StampedEvent, EVENTS_AMT_BIT_WIDTH, EVENT_FLAG_INDEXED_ALL,
+    EVENT_FLAG_INDEXED_KEY, EVENT_FLAG_INDEXED_VALUE, MAX_EVENT_ENTRIES, MAX_EVENT_KEY_LEN,
+    MAX_EVENT_VALUE_LEN,
+};
 pub use self::gas::*;
+pub use self::gas_outputs::{compute_gas_outputs, compute_gas_overestimation_burn, GasOutputs};
 pub use self::types::ExecutionResult;
@@ -0,0 +1,305 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::fmt;
+use std::str::FromStr;
+
+use hmac::{Hmac, Mac, NewMac};
+use parking_lot::RwLock;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::error::{Result, WalletError};
+use crate::keystore::{KeyInfo, KeyStore, KeyType};
+
+/// The name of the JWT HMAC secret in the keystore.
+pub const JWT_SECRET_NAME: &str = "auth-jwt-private";
+const TOKEN_NAME_PREFIX: &str = "auth-token-";
+const TOKEN_KEY_TYPE: &str = "jwt-token";
+const JWT_HEADER: &str = r#"{"alg":"HS256","typ":"JWT"}"#;
+
+/// The permission of the API.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    /// Read-only permission
+    Read,
+    /// Write permission
+    Write,
+    /// Use wallet keys for signing
+    Sign,
+    /// Manage permissions
+    Admin,
+}
+
+impl Permission {
+    /// All the permissions, from the lowest to the highest.
+    pub const ALL: [Permission; 4] = [
+        Permission::Read,
+        Permission::Write,
+        Permission::Sign,
+        Permission::Admin,
+    ];
+
+    /// Return the permission and all the lower permissions,
+    /// e.g. `sign` grants `read`, `write` and `sign`.
+    pub fn up_to(self) -> Vec<Permission> {
+        Self::ALL.iter().copied().filter(|p| *p <= self).collect()
+    }
+}
+
+impl Default for Permission {
+    fn default() -> Self {
+        Permission::Read
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Permission::Read => f.write_str("read"),
+            Permission::Write => f.write_str("write"),
+            Permission::Sign => f.write_str("sign"),
+            Permission::Admin => f.write_str("admin"),
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = WalletError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(Permission::Read),
+            "write" => Ok(Permission::Write),
+            "sign" => Ok(Permission::Sign),
+            "admin" => Ok(Permission::Admin),
+            _ => Err(WalletError::InvalidToken(format!(
+                "unknown permission `{}`",
+                s
+            ))),
+        }
+    }
+}
+
+/// The claims of the API token.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TokenInfo {
+    /// The permissions granted by the token.
+    pub allow: Vec<Permission>,
+    /// The ID of the token, which is used to revoke the token.
+    #[serde(rename = "ID")]
+    pub id: String,
+}
+
+/// The manager of the API tokens, which are HS256 JWTs signed with the secret in the keystore.
+///
+/// The issued tokens are stored in the keystore as well, a token is revoked by removing it
+/// from the keystore, so that it no longer passes the verification.
+pub struct AuthManager<KS: KeyStore> {
+    keystore: RwLock<KS>,
+    secret: Vec<u8>,
+}
+
+impl<KS: KeyStore> AuthManager<KS> {
+    /// Create a new `AuthManager` with the given `KeyStore`,
+    /// the JWT secret is generated and saved if it's not in the keystore.
+    pub fn new(mut keystore: KS) -> Result<Self> {
+        let secret = match keystore.get(JWT_SECRET_NAME).map_err(keystore_err)? {
+            Some(info) => info.private_key,
+            None => {
+                let mut secret = vec![0u8; 32];
+                rand::rngs::OsRng.fill_bytes(&mut secret);
+                let info = KeyInfo {
+                    r#type: KeyType::JwtHmacSecret,
+                    private_key: secret.clone(),
+                };
+                keystore
+                    .put(JWT_SECRET_NAME.into(), info)
+                    .map_err(keystore_err)?;
+                secret
+            }
+        };
+        Ok(Self {
+            keystore: RwLock::new(keystore),
+            secret,
+        })
+    }
+
+    /// Issue a new token with the permissions.
+    pub fn new_token(&self, allow: &[Permission]) -> Result<String> {
+        let mut id = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut id);
+        let mut allow = allow.to_vec();
+        allow.sort();
+        allow.dedup();
+        let info = TokenInfo {
+            allow,
+            id: hex::encode(id),
+        };
+
+        let header = base64::encode_config(JWT_HEADER, base64::URL_SAFE_NO_PAD);
+        let payload = base64::encode_config(serde_json::to_vec(&info)?, base64::URL_SAFE_NO_PAD);
+        let signing_input = format!("{}.{}", header, payload);
+        let signature = base64::encode_config(
+            self.mac(&signing_input).finalize().into_bytes(),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let token = format!("{}.{}", signing_input, signature);
+
+        let token_info = KeyInfo {
+            r#type: KeyType::Other(TOKEN_KEY_TYPE.into()),
+            private_key: token.clone().into_bytes(),
+        };
+        self.keystore
+            .write()
+            .put(format!("{}{}", TOKEN_NAME_PREFIX, info.id), token_info)
+            .map_err(keystore_err)?;
+        Ok(token)
+    }
+
+    /// Verify the token, return the permissions granted by the token.
+    pub fn verify(&self, token: &str) -> Result<Vec<Permission>> {
+        let info = self.decode(token)?;
+        let name = format!("{}{}", TOKEN_NAME_PREFIX, info.id);
+        match self.keystore.read().get(&name).map_err(keystore_err)? {
+            Some(stored) if stored.private_key == token.as_bytes() => Ok(info.allow),
+            _ => Err(WalletError::InvalidToken(format!(
+                "token `{}` is revoked",
+                info.id
+            ))),
+        }
+    }
+
+    /// List the tokens that are not revoked.
+    pub fn list(&self) -> Result<Vec<TokenInfo>> {
+        let keystore = self.keystore.read();
+        let mut names = keystore.list().map_err(keystore_err)?;
+        names.sort();
+        let mut tokens = vec![];
+        for name in names
+            .iter()
+            .filter(|name| name.starts_with(TOKEN_NAME_PREFIX))
+        {
+            if let Some(stored) = keystore.get(name).map_err(keystore_err)? {
+                let token = String::from_utf8(stored.private_key)
+                    .map_err(|_| WalletError::InvalidToken("token is not UTF-8".into()))?;
+                tokens.push(self.decode(&token)?);
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Revoke the token with the ID, return false if the token doesn't exist.
+    pub fn revoke(&self, id: &str) -> Result<bool> {
+        let name = format!("{}{}", TOKEN_NAME_PREFIX, id);
+        let mut keystore = self.keystore.write();
+        if keystore.get(&name).map_err(keystore_err)?.is_none() {
+            return Ok(false);
+        }
+        keystore.delete(&name).map_err(keystore_err)?;
+        Ok(true)
+    }
+
+    fn mac(&self, signing_input: &str) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_varkey(&self.secret).expect("HMAC can take key of any size; qed");
+        mac.update(signing_input.as_bytes());
+        mac
+    }
+
+    // Check the signature of the token and decode the claims.
+    fn decode(&self, token: &str) -> Result<TokenInfo> {
+        let invalid = |reason: &str| WalletError::InvalidToken(reason.into());
+        let mut parts = token.rsplitn(2, '.');
+        let (signature, signing_input) = match (parts.next(), parts.next()) {
+            (Some(signature), Some(signing_input)) => (signature, signing_input),
+            _ => return Err(invalid("malformed token")),
+        };
+        let (header, payload) = match signing_input.split('.').collect::<Vec<_>>()[..] {
+            [header, payload] => (header, payload),
+            _ => return Err(invalid("malformed token")),
+        };
+        let header = base64::decode_config(header, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| invalid("malformed token header"))?;
+        let header: serde_json::Value = serde_json::from_slice(&header)?;
+        if header["alg"] != "HS256" {
+            return Err(invalid("unsupported signing algorithm"));
+        }
+
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| invalid("malformed token signature"))?;
+        self.mac(signing_input)
+            .verify(&signature)
+            .map_err(|_| invalid("invalid token signature"))?;
+
+        let payload = base64::decode_config(payload, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| invalid("malformed token payload"))?;
+        Ok(serde_json::from_slice(&payload)?)
+    }
+}
+
+fn keystore_err<E: fmt::Display>(err: E) -> WalletError {
+    WalletError::KeyStore(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keystore::MemKeyStore;
+
+    #[test]
+    fn test_permission() {
+        assert_eq!(Permission::Read.up_to(), vec![Permission::Read]);
+        assert_eq!(Permission::Admin.up_to(), Permission::ALL.to_vec());
+        assert_eq!("sign".parse::<Permission>().unwrap(), Permission::Sign);
+        assert!("root".parse::<Permission>().is_err());
+        assert_eq!(
+            serde_json::to_string(&Permission::Write).unwrap(),
+            "\"write\""
+        );
+    }
+
+    #[test]
+    fn test_auth_manager() {
+        let auth = AuthManager::new(MemKeyStore::new()).unwrap();
+        let read = auth.new_token(&[Permission::Read]).unwrap();
+        let admin = auth.new_token(&Permission::Admin.up_to()).unwrap();
+        assert_eq!(auth.verify(&read).unwrap(), vec![Permission::Read]);
+        assert_eq!(auth.verify(&admin).unwrap(), Permission::ALL.to_vec());
+
+        // tampering the payload breaks the signature.
+        let parts = read.split('.').collect::<Vec<_>>();
+        let forged_payload = base64::encode_config(
+            serde_json::to_vec(&TokenInfo {
+                allow: vec![Permission::Admin],
+                id: auth.list().unwrap()[0].id.clone(),
+            })
+            .unwrap(),
+            base64::URL_SAFE_NO_PAD,
+        );
+        let forged = format!("{}.{}.{}", parts[0], forged_payload, parts[2]);
+        assert!(auth.verify(&forged).is_err());
+        assert!(auth.verify("not a token").is_err());
+
+        // the tokens signed with another secret are rejected.
+        let other = AuthManager::new(MemKeyStore::new()).unwrap();
+        assert!(other.verify(&read).is_err());
+
+        let tokens = auth.list().unwrap();
+        assert_eq!(tokens.len(), 2);
+        let read_id = tokens
+            .iter()
+            .find(|token| token.allow == vec![Permission::Read])
+            .unwrap()
+            .id
+            .clone();
+        assert!(auth.revoke(&read_id).unwrap());
+        assert!(!auth.revoke(&read_id).unwrap());
+        assert!(auth.verify(&read).is_err());
+        assert!(auth.verify(&admin).is_ok());
+        assert_eq!(auth.list().unwrap().len(), 1);
+    }
+}
@@ -24,6 +24,9 @@ pub enum WalletError {
     /// Key store error.
     #[error("key store error: {0}")]
     KeyStore(String),
+    /// Invalid API token error.
+    #[error("invalid token: {0}")]
+    InvalidToken(String),
 }
 
 impl From<secp256k1::Error> for WalletError {
@@ -2,14 +2,19 @@
 
 mod key_info;
 mod memory;
+mod shared;
 
 pub use self::key_info::{KeyInfo, KeyType};
 pub use self::memory::MemKeyStore;
+pub use self::shared::SharedKeyStore;
 
 /// The default keystore path.
 pub const DEFAULT_KEYSTORE_PATH: &str = "/.plum/keystore/";
 
 /// KeyStore is used for operating key info.
+///
+/// The KeyStore is implemented for `&mut KS` and `Box<KS>` of any KeyStore `KS`, and
+/// `SharedKeyStore` shares a KeyStore between `Wallet` and the other signers.
 pub trait KeyStore {
     /// The KeyStore error.
     type Error: std::fmt::Display;
@@ -0,0 +1,90 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+
+use crate::keystore::{KeyInfo, KeyStore};
+
+/// A KeyStore shared by the clones, so that the keys are shared by `Wallet` and the other
+/// signers, e.g. `SignMessage`, no matter which KeyStore backend is used.
+pub struct SharedKeyStore<KS: KeyStore> {
+    inner: Arc<RwLock<KS>>,
+}
+
+impl<KS: KeyStore> Clone for SharedKeyStore<KS> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<KS: KeyStore> SharedKeyStore<KS> {
+    /// Create a new shared KeyStore backed by the `keystore`.
+    pub fn new(keystore: KS) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(keystore)),
+        }
+    }
+}
+
+impl<KS: KeyStore> KeyStore for SharedKeyStore<KS> {
+    type Error = KS::Error;
+
+    fn list(&self) -> Result<Vec<String>, Self::Error> {
+        self.inner.read().list()
+    }
+
+    fn get<K: AsRef<str>>(&self, key: K) -> Result<Option<KeyInfo>, Self::Error> {
+        self.inner.read().get(key)
+    }
+
+    fn put(&mut self, key: String, info: KeyInfo) -> Result<(), Self::Error> {
+        self.inner.write().put(key, info)
+    }
+
+    fn delete<K: AsRef<str>>(&mut self, key: K) -> Result<(), Self::Error> {
+        self.inner.write().delete(key)
+    }
+}
+
+impl<KS: KeyStore> KeyStore for &mut KS {
+    type Error = KS::Error;
+
+    fn list(&self) -> Result<Vec<String>, Self::Error> {
+        (**self).list()
+    }
+
+    fn get<K: AsRef<str>>(&self, key: K) -> Result<Option<KeyInfo>, Self::Error> {
+        (**self).get(key)
+    }
+
+    fn put(&mut self, key: String, info: KeyInfo) -> Result<(), Self::Error> {
+        (**self).put(key, info)
+    }
+
+    fn delete<K: AsRef<str>>(&mut self, key: K) -> Result<(), Self::Error> {
+        (**self).delete(key)
+    }
+}
+
+impl<KS: KeyStore> KeyStore for Box<KS> {
+    type Error = KS::Error;
+
+    fn list(&self) -> Result<Vec<String>, Self::Error> {
+        (**self).list()
+    }
+
+    fn get<K: AsRef<str>>(&self, key: K) -> Result<Option<KeyInfo>, Self::Error> {
+        (**self).get(key)
+    }
+
+    fn put(&mut self, key: String, info: KeyInfo) -> Result<(), Self::Error> {
+        (**self).put(key, info)
+    }
+
+    fn delete<K: AsRef<str>>(&mut self, key: K) -> Result<(), Self::Error> {
+        (**self).delete(key)
+    }
+}
@@ -6,13 +6,21 @@
 
 extern crate bls_signatures as bls;
 
+mod auth;
 mod error;
 mod keystore;
+mod signer;
 mod wallet;
 
+pub use self::auth::{AuthManager, Permission, TokenInfo, JWT_SECRET_NAME};
 pub use self::error::{Result, WalletError};
-pub use self::keystore::{KeyInfo, KeyStore, KeyType, MemKeyStore, DEFAULT_KEYSTORE_PATH};
-pub use self::wallet::{generate_key, Wallet};
+pub use self::keystore::{
+    KeyInfo, KeyStore, KeyType, MemKeyStore, SharedKeyStore, DEFAULT_KEYSTORE_PATH,
+};
+pub use self::signer::{sign_with_keystore, SignMessage};
+pub use self::wallet::{
+    generate_key, key_name, tagged_data, verify_data, Key, Wallet, SIGN_DATA_DOMAIN_TAG,
+};
 
 #[test]
 fn test_wallet() {
@@ -21,3 +29,103 @@ fn test_wallet() {
     let addr = wallet.generate_key(KeyType::Secp256k1).unwrap();
     assert!(wallet.has_key(&addr));
 }
+
+#[test]
+fn test_sign_message() {
+    let keystore = MemKeyStore::new();
+    let mut wallet = Wallet::new(keystore);
+    for key_type in vec![KeyType::Secp256k1, KeyType::Bls] {
+        let from = wallet.generate_key(key_type).unwrap();
+        let message = plum_message::UnsignedMessage {
+            version: 0,
+            to: plum_address::Address::new_id_addr(100).unwrap(),
+            from,
+            nonce: 1,
+            value: 10u64.into(),
+            gas_price: 1u64.into(),
+            gas_limit: 1000,
+            gas_fee_cap: 0u64.into(),
+            gas_premium: 0u64.into(),
+            method: 0,
+            params: vec![],
+        };
+        let mut signed = wallet.sign_message(message).unwrap();
+        assert!(signed.verify_signature().unwrap());
+        signed.message.nonce += 1;
+        assert!(!signed.verify_signature().unwrap());
+    }
+}
+
+#[test]
+fn test_sign_data() {
+    let keystore = MemKeyStore::new();
+    let mut wallet = Wallet::new(keystore);
+    for key_type in vec![KeyType::Secp256k1, KeyType::Bls] {
+        let from = wallet.generate_key(key_type).unwrap();
+        let message = plum_message::UnsignedMessage {
+            version: 0,
+            to: plum_address::Address::new_id_addr(100).unwrap(),
+            from: from.clone(),
+            nonce: 1,
+            value: 10u64.into(),
+            gas_price: 1u64.into(),
+            gas_limit: 1000,
+            gas_fee_cap: 0u64.into(),
+            gas_premium: 0u64.into(),
+            method: 0,
+            params: vec![],
+        };
+        // A chain message disguised as arbitrary data.
+        let data = message.signing_bytes();
+        let signature = wallet.sign_data(&from, &data).unwrap();
+        assert!(verify_data(&signature, &from, &data).unwrap());
+        assert!(!signature.verify(&from, &data).unwrap());
+        let signed = plum_message::SignedMessage { message, signature };
+        assert!(!signed.verify_signature().unwrap());
+
+        // The raw signing is explicit.
+        let signature = wallet.sign(&from, &data).unwrap();
+        assert!(signature.verify(&from, &data).unwrap());
+        assert!(!verify_data(&signature, &from, &data).unwrap());
+    }
+}
+
+#[test]
+fn test_shared_keystore() {
+    let keystore = SharedKeyStore::new(MemKeyStore::new());
+    let mut wallet = Wallet::new(keystore.clone());
+    for key_type in vec![KeyType::Secp256k1, KeyType::Bls] {
+        let from = wallet.generate_key(key_type).unwrap();
+        let message = plum_message::UnsignedMessage {
+            version: 0,
+            to: plum_address::Address::new_id_addr(100).unwrap(),
+            from: from.clone(),
+            nonce: 1,
+            value: 10u64.into(),
+            gas_price: 1u64.into(),
+            gas_limit: 1000,
+            gas_fee_cap: 0u64.into(),
+            gas_premium: 0u64.into(),
+            method: 0,
+            params: vec![],
+        };
+        // The key generated by the wallet is found in the shared keystore.
+        let signed = plum_message::SignedMessage::sign(message.clone(), &keystore).unwrap();
+        assert!(signed.verify_signature().unwrap());
+        assert_eq!(signed, wallet.sign_message(message.clone()).unwrap());
+
+        let missing = plum_address::Address::new_id_addr(100).unwrap();
+        let message = plum_message::UnsignedMessage {
+            from: missing,
+            ..message
+        };
+        assert!(plum_message::SignedMessage::sign(message, &keystore).is_err());
+    }
+
+    // The borrowed keystore is a keystore as well.
+    let mut mem = MemKeyStore::new();
+    let mut wallet = Wallet::new(&mut mem);
+    let addr = wallet.generate_key(KeyType::Bls).unwrap();
+    drop(wallet);
+    assert!(sign_with_keystore(&mem, &addr, b"data").is_ok());
+}
@@ -0,0 +1,46 @@
+// Copyright 2019-2020 PolkaX Authors. Licensed under GPL-3.0.
+
+use plum_address::Address;
+use plum_crypto::Signature;
+use plum_message::{SignedMessage, UnsignedMessage};
+
+use crate::error::{Result, WalletError};
+use crate::keystore::KeyStore;
+use crate::wallet::{key_name, Key};
+
+/// Sign the message with the key of the `addr` found in the `keystore`, the key is stored under
+/// the name given by `key_name`, i.e. the same as the keys of `Wallet`.
+pub fn sign_with_keystore<KS, M>(keystore: &KS, addr: &Address, msg: M) -> Result<Signature>
+where
+    KS: KeyStore,
+    M: AsRef<[u8]>,
+{
+    let name = key_name(addr);
+    let info = keystore
+        .get(&name)
+        .map_err(|err| WalletError::KeyStore(err.to_string()))?
+        .ok_or_else(|| WalletError::KeyStore(format!("key `{}` not found", name)))?;
+    let key = Key::new(info)?;
+    if &key.address != addr {
+        return Err(WalletError::KeyStore(format!(
+            "key `{}` belongs to {}",
+            name, key.address
+        )));
+    }
+    key.sign(msg)
+}
+
+/// Sign the chain message with any KeyStore backend, e.g. `SignedMessage::sign(message, &keystore)`.
+///
+/// Wrap the keystore with `SharedKeyStore` to share the keys with `Wallet`.
+pub trait SignMessage: Sized {
+    /// Sign the message with the key of its sender found in the `keystore`.
+    fn sign<KS: KeyStore>(message: UnsignedMessage, keystore: &KS) -> Result<Self>;
+}
+
+impl SignMessage for SignedMessage {
+    fn sign<KS: KeyStore>(message: UnsignedMessage, keystore: &KS) -> Result<Self> {
+        let signature = sign_with_keystore(keystore, &message.from, message.signing_bytes())?;
+        Ok(SignedMessage { message, signature })
+    }
+}
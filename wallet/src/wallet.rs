@@ -7,6 +7,7 @@ use parking_lot::RwLock;
 
 use plum_address::Address;
 use plum_crypto::Signature;
+use plum_message::{SignedMessage, UnsignedMessage};
 
 use crate::error::{Result, WalletError};
 use crate::keystore::{KeyInfo, KeyStore, KeyType};
@@ -14,6 +15,36 @@ use crate::keystore::{KeyInfo, KeyStore, KeyType};
 const WALLET_NAME_PREFIX: &str = "wallet-";
 const DEFAULT_KEY_NAME: &str = "default";
 
+/// The domain separation tag prefixed to the arbitrary data signed by `Wallet::sign_data`.
+///
+/// The signing payload of a chain message or a block header is a CID, which never starts with
+/// `0x19`, so a signature of the tagged data can't be replayed as a signature of the chain
+/// objects, even if the data is a chain object disguised as arbitrary data.
+pub const SIGN_DATA_DOMAIN_TAG: &[u8] = b"\x19Filecoin Signed Data:\n";
+
+/// Return the signing payload of the arbitrary `data`, i.e. the data prefixed with
+/// `SIGN_DATA_DOMAIN_TAG`.
+pub fn tagged_data<D: AsRef<[u8]>>(data: D) -> Vec<u8> {
+    let data = data.as_ref();
+    let mut payload = Vec::with_capacity(SIGN_DATA_DOMAIN_TAG.len() + data.len());
+    payload.extend_from_slice(SIGN_DATA_DOMAIN_TAG);
+    payload.extend_from_slice(data);
+    payload
+}
+
+/// Return the name of the key info of the `addr` in the keystore, i.e. `wallet-<addr>`.
+///
+/// The keys are stored under the names by `Wallet`, so the keystore can be shared by `Wallet`
+/// and the other signers, e.g. `SignMessage`.
+pub fn key_name(addr: &Address) -> String {
+    format!("{}{}", WALLET_NAME_PREFIX, addr)
+}
+
+/// Verify the signature of the arbitrary `data` signed by `Wallet::sign_data`.
+pub fn verify_data<D: AsRef<[u8]>>(signature: &Signature, addr: &Address, data: D) -> Result<bool> {
+    Ok(signature.verify(addr, tagged_data(data))?)
+}
+
 ///
 #[derive(PartialEq, Clone, Debug)]
 pub struct Key {
@@ -35,6 +66,18 @@ impl Key {
         }
     }
 
+    /// Sign the message with the private key.
+    pub fn sign<M: AsRef<[u8]>>(&self, msg: M) -> Result<Signature> {
+        match self.info.r#type {
+            KeyType::Secp256k1 => Ok(Signature::sign_secp256k1(
+                &self.info.private_key,
+                msg.as_ref(),
+            )?),
+            KeyType::Bls => Ok(Signature::sign_bls(&self.info.private_key, msg.as_ref())?),
+            _ => Err(WalletError::UnknownKeyType),
+        }
+    }
+
     /// Create a new secp256k1 `Key` with given private key.
     pub fn new_secp256k1<K: AsRef<[u8]>>(privkey: K) -> Result<Self> {
         let seckey = secp256k1::SecretKey::parse_slice(privkey.as_ref())?;
@@ -95,6 +138,20 @@ impl<KS: KeyStore> Wallet<KS> {
         wallet.sign(addr, msg)
     }
 
+    /// Sign the arbitrary data with the private key found by the given address in the key store,
+    /// the data is prefixed with `SIGN_DATA_DOMAIN_TAG` before signing.
+    ///
+    /// Use `sign` to sign the raw data explicitly, e.g. the signing payload of a chain message.
+    pub fn sign_data<D: AsRef<[u8]>>(&self, addr: &Address, data: D) -> Result<Signature> {
+        self.sign(addr, tagged_data(data))
+    }
+
+    /// Sign the message with the key of its sender, using the signing payload of the message.
+    pub fn sign_message(&self, message: UnsignedMessage) -> Result<SignedMessage> {
+        let signature = self.sign(&message.from, message.signing_bytes())?;
+        Ok(SignedMessage { message, signature })
+    }
+
     /// Export the key info by the address.
     pub fn export(&self, addr: &Address) -> Option<KeyInfo> {
         let wallet = self.imp.read();
@@ -170,17 +227,10 @@ impl<KS: KeyStore> WalletImpl<KS> {
     /// Sign the message with the private key found by the given address in the key store.
     fn sign<M: AsRef<[u8]>>(&self, addr: &Address, msg: M) -> Result<Signature> {
         match self.find_key(addr) {
-            Some(key) => match key.info.r#type {
-                KeyType::Secp256k1 => Ok(Signature::sign_secp256k1(
-                    &key.info.private_key,
-                    msg.as_ref(),
-                )?),
-                KeyType::Bls => Ok(Signature::sign_bls(&key.info.private_key, msg.as_ref())?),
-                _ => Err(WalletError::UnknownKeyType),
-            },
+            Some(key) => key.sign(msg),
             None => Err(WalletError::KeyStore(format!(
-                "key `{}{}` not found",
-                WALLET_NAME_PREFIX, addr
+                "key `{}` not found",
+                key_name(addr)
             ))),
         }
     }
@@ -201,10 +251,7 @@ impl<KS: KeyStore> WalletImpl<KS> {
     fn import(&mut self, key_info: KeyInfo) -> Result<Address> {
         let key = Key::new(key_info)?;
         // import the key (with the `wallet-xxx`format) and key info.
-        match self.keystore.put(
-            format!("{}{}", WALLET_NAME_PREFIX, key.address),
-            key.info.clone(),
-        ) {
+        match self.keystore.put(key_name(&key.address), key.info.clone()) {
             Ok(_) => {
                 // update the key in the memory
                 let address = key.address.clone();
@@ -278,10 +325,7 @@ impl<KS: KeyStore> WalletImpl<KS> {
 
         // generate a random key info and save it into key store and memory.
         self.keystore
-            .put(
-                format!("{}{}", WALLET_NAME_PREFIX, key.address),
-                key.info.clone(),
-            )
+            .put(key_name(&key.address), key.info.clone())
             .map_err(|err| WalletError::KeyStore(err.to_string()))?;
         let address = key.address.clone();
         let key_info = key.info.clone();